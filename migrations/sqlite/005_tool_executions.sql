@@ -0,0 +1,16 @@
+-- Migration: 005_tool_executions
+-- Description: Audit log of tool executions, used to inspect recent runs of a tool
+
+CREATE TABLE IF NOT EXISTS tool_executions (
+    id TEXT PRIMARY KEY NOT NULL,
+    session_id TEXT NOT NULL,
+    tool_name TEXT NOT NULL,
+    arguments TEXT NOT NULL, -- Redacted, truncated summary
+    output TEXT NOT NULL, -- Truncated output or error
+    success INTEGER NOT NULL,
+    duration_ms INTEGER,
+    created_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    FOREIGN KEY (session_id) REFERENCES sessions(id) ON DELETE CASCADE
+);
+
+CREATE INDEX idx_tool_executions_tool ON tool_executions(tool_name, created_at);
@@ -0,0 +1,15 @@
+-- Migration: 006_pending_approvals
+-- Description: Tool approval requests awaiting a user decision, kept across restarts
+
+CREATE TABLE IF NOT EXISTS pending_approvals (
+    request_id TEXT PRIMARY KEY NOT NULL,
+    session_id TEXT NOT NULL,
+    tool_name TEXT NOT NULL,
+    arguments TEXT NOT NULL,
+    policy TEXT NOT NULL,
+    sandbox_available INTEGER NOT NULL,
+    created_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    expires_at TIMESTAMP NOT NULL
+);
+
+CREATE INDEX idx_pending_approvals_session ON pending_approvals(session_id);
@@ -0,0 +1,8 @@
+-- Migration: 007_invites
+-- Description: Single-use invites carrying the role and scopes of the account they create
+
+ALTER TABLE pending_links ADD COLUMN used INTEGER NOT NULL DEFAULT 0;
+ALTER TABLE pending_links ADD COLUMN role TEXT;
+ALTER TABLE pending_links ADD COLUMN scopes TEXT; -- JSON array, NULL for unrestricted
+
+ALTER TABLE identities ADD COLUMN scopes TEXT; -- JSON array, NULL for unrestricted
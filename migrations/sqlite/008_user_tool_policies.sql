@@ -0,0 +1,10 @@
+-- Migration: 008_user_tool_policies
+-- Description: Per-user tool policy overrides set by admins at runtime
+
+CREATE TABLE IF NOT EXISTS user_tool_policies (
+    user_id TEXT NOT NULL,
+    tool_name TEXT NOT NULL,
+    level TEXT NOT NULL, -- 'allow', 'deny', 'elevated'
+    updated_at DATETIME NOT NULL,
+    PRIMARY KEY (user_id, tool_name)
+);
@@ -0,0 +1,4 @@
+-- Migration: 009_message_metadata
+-- Description: JSON metadata on stored messages (e.g. which PII patterns were redacted)
+
+ALTER TABLE messages ADD COLUMN metadata TEXT;
@@ -0,0 +1,12 @@
+-- Migration: 010_document_chunks
+-- Description: Chunks of ingested knowledge documents for the search_docs tool
+
+CREATE TABLE IF NOT EXISTS document_chunks (
+    source TEXT NOT NULL,       -- path relative to the knowledge directory
+    chunk_index INTEGER NOT NULL,
+    content TEXT NOT NULL,
+    embedding BLOB,             -- little-endian f32 values, NULL without embeddings
+    fingerprint TEXT NOT NULL,  -- size and mtime of the ingested file
+    updated_at DATETIME NOT NULL,
+    PRIMARY KEY (source, chunk_index)
+);
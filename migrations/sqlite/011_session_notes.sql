@@ -0,0 +1,12 @@
+-- Migration: 011_session_notes
+-- Description: Pinned notes injected into a session's system prompt
+
+CREATE TABLE IF NOT EXISTS session_notes (
+    id TEXT PRIMARY KEY,
+    session_id TEXT NOT NULL,
+    content TEXT NOT NULL,
+    created_at DATETIME NOT NULL,
+    FOREIGN KEY (session_id) REFERENCES sessions(id) ON DELETE CASCADE
+);
+
+CREATE INDEX idx_session_notes_session ON session_notes(session_id, created_at);
@@ -0,0 +1,15 @@
+-- Migration: 012_message_feedback
+-- Description: User ratings of assistant replies, kept when messages are cleared
+
+CREATE TABLE IF NOT EXISTS message_feedback (
+    message_id TEXT NOT NULL,
+    session_id TEXT NOT NULL,
+    user_id TEXT NOT NULL,
+    rating TEXT NOT NULL,
+    comment TEXT,
+    model TEXT,
+    created_at DATETIME NOT NULL,
+    PRIMARY KEY (message_id, user_id)
+);
+
+CREATE INDEX idx_message_feedback_model ON message_feedback(model);
@@ -0,0 +1,16 @@
+-- Migration: 014_reminders
+-- Description: One-off messages delivered to a channel at a set time
+
+CREATE TABLE IF NOT EXISTS reminders (
+    id TEXT PRIMARY KEY,
+    user_id TEXT NOT NULL,
+    message TEXT NOT NULL,
+    channel TEXT NOT NULL,
+    target TEXT NOT NULL,
+    due_at DATETIME NOT NULL,
+    created_at DATETIME NOT NULL,
+    delivered_at DATETIME
+);
+
+CREATE INDEX idx_reminders_pending ON reminders(delivered_at, due_at);
+CREATE INDEX idx_reminders_user ON reminders(user_id, due_at);
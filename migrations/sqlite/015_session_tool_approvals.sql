@@ -0,0 +1,9 @@
+-- Migration: 015_session_tool_approvals
+-- Description: Tools approved with "remember for session", kept until the session is reset
+
+CREATE TABLE IF NOT EXISTS session_tool_approvals (
+    session_id TEXT NOT NULL,
+    tool_name TEXT NOT NULL,
+    created_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    PRIMARY KEY (session_id, tool_name)
+);
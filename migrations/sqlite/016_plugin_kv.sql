@@ -0,0 +1,10 @@
+-- Migration: 016_plugin_kv
+-- Description: Small JSON values plugins keep between restarts, namespaced by plugin ID
+
+CREATE TABLE IF NOT EXISTS plugin_kv (
+    plugin_id TEXT NOT NULL,
+    key TEXT NOT NULL,
+    value TEXT NOT NULL,
+    updated_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP,
+    PRIMARY KEY (plugin_id, key)
+);
@@ -0,0 +1,7 @@
+-- Migration: 017_paused_channels
+-- Description: Channels paused by an admin, kept paused across restarts
+
+CREATE TABLE IF NOT EXISTS paused_channels (
+    channel TEXT PRIMARY KEY NOT NULL,
+    paused_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
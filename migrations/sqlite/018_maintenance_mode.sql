@@ -0,0 +1,7 @@
+-- Migration: 018_maintenance_mode
+-- Description: Maintenance (read-only) mode switched on by an admin, kept across restarts until cleared
+
+CREATE TABLE IF NOT EXISTS maintenance_mode (
+    id INTEGER PRIMARY KEY CHECK (id = 1),
+    enabled_at TIMESTAMP NOT NULL DEFAULT CURRENT_TIMESTAMP
+);
@@ -0,0 +1,11 @@
+-- Migration: 019_message_drafts
+-- Description: Partial assistant replies saved while streaming, recovered after a crash
+
+CREATE TABLE IF NOT EXISTS message_drafts (
+    id TEXT PRIMARY KEY,
+    session_id TEXT NOT NULL,
+    content TEXT NOT NULL,
+    model_used TEXT,
+    updated_at DATETIME NOT NULL,
+    FOREIGN KEY (session_id) REFERENCES sessions(id) ON DELETE CASCADE
+);
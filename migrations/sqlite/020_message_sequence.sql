@@ -0,0 +1,14 @@
+-- Migration: 020_message_sequence
+-- Description: Monotonic insertion order of messages; messages of one turn can share a timestamp
+
+ALTER TABLE messages ADD COLUMN seq INTEGER;
+
+-- Number existing messages by timestamp, then id
+CREATE TEMP TABLE message_order (id TEXT PRIMARY KEY, seq INTEGER NOT NULL);
+INSERT INTO message_order (id, seq)
+    SELECT id, ROW_NUMBER() OVER (ORDER BY created_at, id) FROM messages;
+UPDATE messages SET seq = (SELECT seq FROM message_order WHERE message_order.id = messages.id);
+DROP TABLE message_order;
+
+CREATE UNIQUE INDEX idx_messages_seq ON messages(seq);
+CREATE INDEX idx_messages_session_seq ON messages(session_id, seq);
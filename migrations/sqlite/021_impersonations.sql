@@ -0,0 +1,14 @@
+-- Migration: 021_impersonations
+-- Description: Short-lived tokens letting an admin act as a user, kept as an audit trail
+
+CREATE TABLE IF NOT EXISTS impersonations (
+    token TEXT PRIMARY KEY,
+    admin_id TEXT NOT NULL,
+    user_id TEXT NOT NULL,
+    reason TEXT,
+    scopes TEXT, -- JSON array of the issuing token's scopes, NULL for unrestricted
+    created_at DATETIME NOT NULL,
+    expires_at DATETIME NOT NULL
+);
+
+CREATE INDEX idx_impersonations_created ON impersonations(created_at);
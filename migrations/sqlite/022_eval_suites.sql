@@ -0,0 +1,10 @@
+-- Migration: 022_eval_suites
+-- Description: Prompt regression suites saved by users to re-run with /api/eval/run
+
+CREATE TABLE IF NOT EXISTS eval_suites (
+    user_id TEXT NOT NULL,
+    name TEXT NOT NULL,
+    cases TEXT NOT NULL,
+    updated_at DATETIME NOT NULL,
+    PRIMARY KEY (user_id, name)
+);
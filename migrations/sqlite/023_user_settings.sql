@@ -0,0 +1,11 @@
+-- Migration: 023_user_settings
+-- Description: Per-user default reply settings (model, temperature, persona, language)
+
+CREATE TABLE IF NOT EXISTS user_settings (
+    user_id TEXT PRIMARY KEY NOT NULL,
+    model TEXT,
+    temperature REAL,
+    persona TEXT,
+    language TEXT,
+    updated_at DATETIME NOT NULL
+);
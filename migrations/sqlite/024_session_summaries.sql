@@ -0,0 +1,10 @@
+-- Migration: 024_session_summaries
+-- Description: Cached summary of a session and the last message it covers
+
+CREATE TABLE IF NOT EXISTS session_summaries (
+    session_id TEXT PRIMARY KEY NOT NULL,
+    summary TEXT NOT NULL,
+    summary_through TEXT NOT NULL,
+    updated_at DATETIME NOT NULL,
+    FOREIGN KEY (session_id) REFERENCES sessions(id) ON DELETE CASCADE
+);
@@ -0,0 +1,205 @@
+//! Session archives for moving conversations between instances
+//!
+//! An archive holds a session with its messages and notes exactly as stored,
+//! so importing it elsewhere recreates the same conversation.
+
+use crate::api::routes::owned_session;
+use crate::api::{ApiError, ApiResponse, AuthUserId};
+use crate::core::Router;
+use crate::storage::{Message, SessionNote, Storage};
+use axum::extract::{Path, State};
+use axum::http::StatusCode;
+use axum::{Extension, Json};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::HashSet;
+use std::sync::Arc;
+use uuid::Uuid;
+
+/// Version of the archive format written by this build
+pub const ARCHIVE_VERSION: u32 = 1;
+/// Scope prefix of imported sessions when the original scope is already
+/// taken; the new session id follows it
+pub const IMPORT_SCOPE: &str = "import";
+/// Most messages a single archive may hold
+const MAX_ARCHIVE_MESSAGES: usize = 100_000;
+
+const MESSAGE_ROLES: [&str; 4] = ["system", "user", "assistant", "tool"];
+
+/// A session with everything needed to recreate it
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct SessionArchive {
+    pub version: u32,
+    pub exported_at: DateTime<Utc>,
+    pub session: ArchivedSession,
+    pub messages: Vec<Message>,
+    #[serde(default)]
+    pub notes: Vec<SessionNote>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct ArchivedSession {
+    pub id: String,
+    pub channel: String,
+    pub scope: String,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+}
+
+/// Where an imported archive ended up
+#[derive(Debug, Serialize, Deserialize)]
+pub struct ImportedSession {
+    pub id: String,
+    /// Id the session had on the exporting instance
+    pub original_id: String,
+    pub channel: String,
+    pub scope: String,
+    pub message_count: usize,
+}
+
+impl SessionArchive {
+    /// Check the archive is one this build can import faithfully
+    pub fn validate(&self) -> Result<(), String> {
+        if self.version != ARCHIVE_VERSION {
+            return Err(format!(
+                "Unsupported archive version {} (expected {})",
+                self.version, ARCHIVE_VERSION
+            ));
+        }
+        if self.session.id.trim().is_empty() {
+            return Err("Archived session has no id".to_string());
+        }
+        if self.session.channel.trim().is_empty() || self.session.scope.trim().is_empty() {
+            return Err("Archived session needs a channel and a scope".to_string());
+        }
+        if self.messages.len() > MAX_ARCHIVE_MESSAGES {
+            return Err(format!(
+                "Archive holds more than {} messages",
+                MAX_ARCHIVE_MESSAGES
+            ));
+        }
+
+        let mut ids = HashSet::new();
+        for message in &self.messages {
+            if message.session_id != self.session.id {
+                return Err(format!("Message {} belongs to another session", message.id));
+            }
+            if !MESSAGE_ROLES.contains(&message.role.as_str()) {
+                return Err(format!(
+                    "Message {} has unknown role '{}'",
+                    message.id, message.role
+                ));
+            }
+            if !ids.insert(message.id.as_str()) {
+                return Err(format!("Message id {} appears twice", message.id));
+            }
+        }
+        if let Some(note) = self
+            .notes
+            .iter()
+            .find(|note| note.session_id != self.session.id)
+        {
+            return Err(format!("Note {} belongs to another session", note.id));
+        }
+        Ok(())
+    }
+}
+
+/// GET /api/sessions/:id/archive - The session as a re-importable bundle
+pub async fn archive_session<S: Storage + 'static>(
+    State(router): State<Arc<Router<S>>>,
+    Extension(AuthUserId(user_id)): Extension<AuthUserId>,
+    Path(session_id): Path<String>,
+) -> Result<Json<SessionArchive>, ApiError> {
+    let session = owned_session(&router, &user_id, &session_id).await?;
+    let storage = router.get_storage();
+    let messages = storage.get_messages(&session.id, None).await?;
+    let notes = storage.list_session_notes(&session.id).await?;
+
+    Ok(Json(SessionArchive {
+        version: ARCHIVE_VERSION,
+        exported_at: Utc::now(),
+        session: ArchivedSession {
+            id: session.id,
+            channel: session.channel,
+            scope: session.scope,
+            created_at: session.created_at,
+            updated_at: session.updated_at,
+        },
+        messages,
+        notes,
+    }))
+}
+
+/// POST /api/sessions/import - Recreate an archived session for the caller
+///
+/// Every id is freshly generated, so an archive can be imported any number
+/// of times. When the caller already has a session in the archived channel
+/// and scope, the import gets its own `import:<id>` scope instead of
+/// shadowing it. The import counts towards `sessions.max_per_user` like any
+/// new session.
+pub async fn import_session<S: Storage + 'static>(
+    State(router): State<Arc<Router<S>>>,
+    Extension(AuthUserId(user_id)): Extension<AuthUserId>,
+    Json(archive): Json<SessionArchive>,
+) -> Result<(StatusCode, Json<ApiResponse<ImportedSession>>), ApiError> {
+    archive.validate().map_err(ApiError::BadRequest)?;
+    let storage = router.get_storage();
+
+    let original = archive.session;
+    let id = Uuid::new_v4().to_string();
+    let scope = match storage
+        .find_session(&user_id, &original.channel, &original.scope)
+        .await?
+    {
+        Some(_) => format!("{}:{}", IMPORT_SCOPE, id),
+        None => original.scope.clone(),
+    };
+    router.make_room_for_new_session(&user_id).await?;
+    let session = crate::storage::Session {
+        id,
+        user_id,
+        channel: original.channel.clone(),
+        scope,
+        created_at: original.created_at,
+        updated_at: original.updated_at,
+    };
+    storage.create_session(session.clone()).await?;
+
+    let copied = async {
+        router
+            .import_messages(&session.id, &archive.messages)
+            .await?;
+        for note in archive.notes {
+            storage
+                .add_session_note(SessionNote {
+                    id: Uuid::new_v4().to_string(),
+                    session_id: session.id.clone(),
+                    ..note
+                })
+                .await?;
+        }
+        anyhow::Ok(())
+    }
+    .await;
+    if let Err(e) = copied {
+        storage.delete_session(&session.id).await.ok();
+        tracing::error!("Failed to import session: {}", e);
+        return Err(ApiError::InternalError(
+            "Failed to import session".to_string(),
+        ));
+    }
+
+    Ok((
+        StatusCode::CREATED,
+        Json(ApiResponse::success(ImportedSession {
+            id: session.id,
+            original_id: original.id,
+            channel: session.channel,
+            scope: session.scope,
+            message_count: archive.messages.len(),
+        })),
+    ))
+}
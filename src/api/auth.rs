@@ -1,7 +1,43 @@
 use crate::api::error::ApiError;
-use crate::storage::Storage;
+use crate::storage::{Impersonation, Page, Storage, UserFilter};
 use axum::{extract::Request, http::HeaderMap, middleware::Next, response::Response};
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::Arc;
+use tracing::Instrument;
+
+/// Prefix marking tokens issued by `POST /api/admin/impersonate/:user_id`
+pub const IMPERSONATION_TOKEN_PREFIX: &str = "imp-";
+
+/// Scope a restricted admin token needs to issue impersonation tokens
+pub const IMPERSONATE_SCOPE: &str = "admin:impersonate";
+
+/// Scope giving a restricted config token admin rights; so do the
+/// narrower `admin:*` scopes
+pub const ADMIN_SCOPE: &str = "admin";
+
+/// Admin ID of config tokens while no admin account exists
+pub const OPERATOR_USER_ID: &str = "operator";
+
+/// Scopes granted to the authenticated token; `None` means unrestricted
+#[derive(Debug, Clone, Default)]
+pub struct TokenScopes(pub Option<Vec<String>>);
+
+impl TokenScopes {
+    /// Reject the request unless the token holds `scope`
+    pub fn require(&self, scope: &str) -> Result<(), ApiError> {
+        match &self.0 {
+            Some(granted) if !granted.iter().any(|s| s == scope) => Err(ApiError::Forbidden(
+                format!("Token is missing the '{}' scope", scope),
+            )),
+            _ => Ok(()),
+        }
+    }
+}
+
+/// User the authenticated request acts as
+#[derive(Debug, Clone)]
+pub struct AuthUserId(pub String);
 
 /// API authentication manager
 #[derive(Clone)]
@@ -10,14 +46,88 @@ pub struct AuthManager<S: Storage> {
     valid_tokens: Arc<Vec<String>>,
     /// Storage for database token lookup
     storage: S,
+    /// Scopes of restricted tokens; unlisted tokens have full access
+    scopes: Arc<HashMap<String, Vec<String>>>,
+    /// Set once a user exists; accounts are never removed down to zero.
+    /// Deployments with config tokens are set up from the start
+    setup_complete: Arc<AtomicBool>,
 }
 
 impl<S: Storage + 'static> AuthManager<S> {
     pub fn new(tokens: Vec<String>, storage: S) -> Self {
+        let setup_complete = !tokens.is_empty();
         Self {
             valid_tokens: Arc::new(tokens),
             storage,
+            scopes: Arc::new(HashMap::new()),
+            setup_complete: Arc::new(AtomicBool::new(setup_complete)),
+        }
+    }
+
+    /// Restrict the listed tokens to the given scopes
+    pub fn with_scopes(mut self, scopes: HashMap<String, Vec<String>>) -> Self {
+        self.scopes = Arc::new(scopes);
+        self
+    }
+
+    /// Scopes granted to a token
+    pub fn token_scopes(&self, token: &str) -> TokenScopes {
+        TokenScopes(self.scopes.get(token).cloned())
+    }
+
+    /// Reject requests until the first admin account exists, unless the
+    /// operator configured API tokens
+    pub async fn ensure_setup(&self) -> Result<(), ApiError> {
+        if self.setup_complete.load(Ordering::Relaxed) {
+            return Ok(());
+        }
+
+        if self.storage.user_count().await? == 0 {
+            return Err(ApiError::SetupRequired);
+        }
+        self.setup_complete.store(true, Ordering::Relaxed);
+        Ok(())
+    }
+
+    /// Admin account acting for a token, if the token may administer the
+    /// gateway.
+    ///
+    /// Tokens of admin users act as themselves. Config tokens belong to the
+    /// operator and act as the first admin, or as [`OPERATOR_USER_ID`] while
+    /// there is none. A config token restricted to scopes without an admin
+    /// scope is not an admin.
+    pub async fn admin_user_id(&self, token: &str) -> Result<Option<String>, ApiError> {
+        if self.valid_tokens.iter().any(|t| t == token) {
+            if let Some(scopes) = self.token_scopes(token).0 {
+                let is_admin_scope =
+                    |scope: &String| scope == ADMIN_SCOPE || scope.starts_with("admin:");
+                if !scopes.iter().any(is_admin_scope) {
+                    return Ok(None);
+                }
+            }
+            let admins = UserFilter {
+                role: Some("admin".to_string()),
+                ..Default::default()
+            };
+            let users = self.storage.list_users(&admins, Page::new(1, 0)).await?;
+            let admin_id = users.items.into_iter().next().map(|user| user.id);
+            return Ok(Some(
+                admin_id.unwrap_or_else(|| OPERATOR_USER_ID.to_string()),
+            ));
         }
+
+        let Some(identity) = self.get_db_identity(token).await else {
+            return Ok(None);
+        };
+        let user = self.storage.get_user(&identity.user_id).await?;
+        Ok(user.filter(|user| user.role == "admin").map(|user| user.id))
+    }
+
+    /// Reject tokens without the admin role, returning the acting admin
+    pub async fn require_admin(&self, token: &str) -> Result<String, ApiError> {
+        self.admin_user_id(token)
+            .await?
+            .ok_or_else(|| ApiError::Forbidden("Admin role required".to_string()))
     }
 
     /// Validate bearer token from headers
@@ -46,7 +156,7 @@ impl<S: Storage + 'static> AuthManager<S> {
         }
 
         // Then check database tokens
-        if self.validate_db_token(token).await {
+        if self.validate_db_token(token).await || self.get_impersonation(token).await.is_some() {
             return Ok(token.to_string());
         }
 
@@ -69,6 +179,14 @@ impl<S: Storage + 'static> AuthManager<S> {
         if let Some(identity) = self.get_db_identity(token).await {
             return Ok(identity.user_id);
         }
+        if let Some(impersonation) = self.get_impersonation(token).await {
+            tracing::info!(
+                "Impersonated WebSocket connection by admin {} as user {}",
+                impersonation.admin_id,
+                impersonation.user_id
+            );
+            return Ok(impersonation.user_id);
+        }
 
         tracing::warn!("Invalid token attempt (ws)");
         Err(ApiError::Unauthorized("Invalid token".to_string()))
@@ -88,6 +206,14 @@ impl<S: Storage + 'static> AuthManager<S> {
             .flatten()
     }
 
+    /// Unexpired impersonation grant of a token
+    pub async fn get_impersonation(&self, token: &str) -> Option<Impersonation> {
+        if !token.starts_with(IMPERSONATION_TOKEN_PREFIX) {
+            return None;
+        }
+        self.storage.get_impersonation(token).await.ok().flatten()
+    }
+
     /// Extract user ID from token
     /// Token format: "web-user-<name>" → user ID is "<name>"
     /// Or just use token as user ID
@@ -106,26 +232,97 @@ impl<S: Storage + 'static> AuthManager<S> {
         mut request: Request,
         next: Next,
     ) -> Result<Response, ApiError> {
+        auth_manager.ensure_setup().await?;
+
         // Validate token
         let token = auth_manager.validate_token(&headers).await?;
 
-        // For database tokens, get user_id from identity
-        let user_id = if let Some(identity) = auth_manager.get_db_identity(&token).await {
-            identity.user_id
-        } else {
-            Self::token_to_user_id(&token)
+        // Impersonation tokens act as their user, and everything done with
+        // them is logged as impersonated
+        if let Some(impersonation) = auth_manager.get_impersonation(&token).await {
+            tracing::info!(
+                "Impersonated request by admin {} as user {}: {} {}",
+                impersonation.admin_id,
+                impersonation.user_id,
+                request.method(),
+                request.uri().path()
+            );
+            let span = tracing::info_span!(
+                "impersonated",
+                admin_id = %impersonation.admin_id,
+                user_id = %impersonation.user_id
+            );
+            request
+                .extensions_mut()
+                .insert(TokenScopes(impersonation.scopes.clone()));
+            request
+                .extensions_mut()
+                .insert(AuthUserId(impersonation.user_id.clone()));
+            request.extensions_mut().insert(auth_manager);
+            request.extensions_mut().insert(impersonation);
+            return Ok(next.run(request).instrument(span).await);
+        }
+
+        // For database tokens, get user_id and scopes from identity
+        let (user_id, scopes) = match auth_manager.get_db_identity(&token).await {
+            Some(identity) => (identity.user_id, TokenScopes(identity.scopes)),
+            None => (
+                Self::token_to_user_id(&token),
+                auth_manager.token_scopes(&token),
+            ),
         };
 
         // Store user ID in request extensions for use in handlers
-        request.extensions_mut().insert(user_id);
-        request.extensions_mut().insert(token);
+        request.extensions_mut().insert(scopes);
+        request.extensions_mut().insert(AuthUserId(user_id));
         request.extensions_mut().insert(auth_manager);
 
         Ok(next.run(request).await)
     }
+
+    /// Middleware for routes that issue or manage credentials (tokens,
+    /// passwords, invites), layered inside `auth_middleware`: impersonation
+    /// tokens may not use them
+    pub async fn credentials_middleware(
+        request: Request,
+        next: Next,
+    ) -> Result<Response, ApiError> {
+        if request.extensions().get::<Impersonation>().is_some() {
+            return Err(ApiError::Forbidden(
+                "Impersonation tokens cannot manage credentials".to_string(),
+            ));
+        }
+
+        Ok(next.run(request).await)
+    }
+
+    /// Middleware for admin-only routes, layered inside `auth_middleware`
+    pub async fn admin_middleware(
+        axum::extract::State(auth_manager): axum::extract::State<AuthManager<S>>,
+        headers: HeaderMap,
+        request: Request,
+        next: Next,
+    ) -> Result<Response, ApiError> {
+        let token = auth_manager.validate_token(&headers).await?;
+        auth_manager.require_admin(&token).await?;
+
+        Ok(next.run(request).await)
+    }
 }
 
 #[cfg(test)]
 mod tests {
-    // Tests require mock storage, skipped for now
+    use super::*;
+
+    #[test]
+    fn test_token_scopes() {
+        assert!(TokenScopes(None).require("tools:write").is_ok());
+
+        let scopes = TokenScopes(Some(vec!["tools:write".to_string()]));
+        assert!(scopes.require("tools:write").is_ok());
+        assert!(matches!(
+            scopes.require("admin"),
+            Err(ApiError::Forbidden(_))
+        ));
+    }
 }
@@ -0,0 +1,183 @@
+//! What this server supports, for client feature detection
+//!
+//! Web and mobile clients read `GET /api/capabilities` once to decide which
+//! UI to show: chat features, channels, models, sandbox and plugin tools.
+//! The descriptor is cached for a few seconds, so polling it stays cheap.
+
+use crate::api::{ApiError, ApiResponse};
+use crate::core::Router;
+use crate::sandbox::SandboxMode;
+use crate::storage::Storage;
+use axum::extract::State;
+use axum::Json;
+use once_cell::sync::Lazy;
+use serde::Serialize;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+/// How long a computed descriptor is served before it is rebuilt
+const CACHE_TTL: Duration = Duration::from_secs(10);
+
+static CACHE: Lazy<Mutex<Option<(Instant, Capabilities)>>> = Lazy::new(|| Mutex::new(None));
+
+#[derive(Debug, Clone, Serialize)]
+pub struct Capabilities {
+    pub version: &'static str,
+    pub chat: ChatFeatures,
+    pub channels: Vec<ChannelCapability>,
+    pub models: Vec<ModelCapability>,
+    pub sandbox: SandboxCapability,
+    /// Tools provided by plugins (skills are not included)
+    pub plugin_tools: Vec<String>,
+    /// Writes are refused while the gateway is in maintenance mode
+    pub maintenance: bool,
+}
+
+/// Features of `/api/chat` and its streaming variant
+#[derive(Debug, Clone, Serialize)]
+pub struct ChatFeatures {
+    /// Server-sent events on `/api/chat` with `stream: true`
+    pub streaming: bool,
+    /// Batches of independent prompts on `/api/chat/batch`
+    pub batch: bool,
+    /// Per-request `context` documents
+    pub context: bool,
+    /// Per-request sampling `seed`
+    pub seed: bool,
+    /// Per-request reply `prefill`
+    pub prefill: bool,
+    /// Per-request `tool_choice` (`auto`, `none` or a tool name)
+    pub tool_choice: bool,
+    /// The assistant can call tools
+    pub tools: bool,
+    /// Elevated tools wait for approval on `/api/approvals`
+    pub approvals: bool,
+    /// Image input in chat messages
+    pub images: bool,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ChannelCapability {
+    pub name: String,
+    /// `connected`, `enabled` (configured, connection not tracked),
+    /// `disconnected` or `disabled`
+    pub status: &'static str,
+    pub paused: bool,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct ModelCapability {
+    pub name: String,
+    /// `primary`, `code`, `fast` or a named role of `llm.models`
+    pub role: String,
+    pub tools: bool,
+    /// Whether the model accepts images; `None` when the backend does not say
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub vision: Option<bool>,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct SandboxCapability {
+    pub mode: SandboxMode,
+    /// Whether the sandbox manager is running (needs a container runtime)
+    pub available: bool,
+}
+
+/// GET /api/capabilities - What this server supports
+pub async fn get_capabilities<S: Storage + 'static>(
+    State(router): State<Arc<Router<S>>>,
+) -> Result<Json<ApiResponse<Capabilities>>, ApiError> {
+    let cached = CACHE
+        .lock()
+        .unwrap()
+        .as_ref()
+        .filter(|(built, _)| built.elapsed() < CACHE_TTL)
+        .map(|(_, capabilities)| capabilities.clone());
+    if let Some(capabilities) = cached {
+        return Ok(Json(ApiResponse::success(capabilities)));
+    }
+
+    let capabilities = build(&router).await;
+    *CACHE.lock().unwrap() = Some((Instant::now(), capabilities.clone()));
+    Ok(Json(ApiResponse::success(capabilities)))
+}
+
+async fn build<S: Storage + 'static>(router: &Router<S>) -> Capabilities {
+    let config = router.config();
+    let config = config.read().await.clone();
+    let llm_client = router.llm_client();
+
+    let mut models = Vec::new();
+    for (role, name) in config.llm.models.roles() {
+        models.push(ModelCapability {
+            name: name.to_string(),
+            role: role.to_string(),
+            tools: llm_client.supports_tools(name).await,
+            vision: llm_client
+                .model_details(name)
+                .await
+                .and_then(|details| details.supports_vision),
+        });
+    }
+
+    let whatsapp_accounts = crate::list_whatsapp_accounts();
+    let whatsapp = match (
+        config.channels.whatsapp.enabled,
+        whatsapp_accounts.is_empty(),
+    ) {
+        (_, false) => "connected",
+        (true, true) => "disconnected",
+        (false, true) => "disabled",
+    };
+    let configured = |enabled: bool| if enabled { "enabled" } else { "disabled" };
+    let mut channels = Vec::new();
+    for (name, status) in [
+        // Whoever reads this reached the web API
+        ("web", "connected"),
+        ("telegram", configured(config.channels.telegram.enabled)),
+        ("discord", configured(config.channels.discord.enabled)),
+        ("whatsapp", whatsapp),
+    ] {
+        channels.push(ChannelCapability {
+            name: name.to_string(),
+            status,
+            paused: router.is_channel_paused(name).await,
+        });
+    }
+
+    let skills: Vec<String> = crate::tools::skills::list_skills()
+        .await
+        .into_iter()
+        .map(|skill| skill.manifest.name)
+        .collect();
+    let mut plugin_tools: Vec<String> = crate::plugins::get_plugin_registry()
+        .and_then(|registry| registry.tools.list_tools().ok())
+        .unwrap_or_default()
+        .into_iter()
+        .filter(|name| !skills.contains(name))
+        .collect();
+    plugin_tools.sort();
+
+    Capabilities {
+        version: env!("CARGO_PKG_VERSION"),
+        chat: ChatFeatures {
+            streaming: true,
+            batch: true,
+            context: true,
+            seed: true,
+            prefill: true,
+            tool_choice: true,
+            tools: !crate::core::available_tools().await.is_empty(),
+            approvals: router.get_approval_manager().is_ok(),
+            images: false,
+        },
+        channels,
+        models,
+        sandbox: SandboxCapability {
+            mode: config.sandbox.mode.clone(),
+            available: crate::get_sandbox_manager().is_some(),
+        },
+        plugin_tools,
+        maintenance: router.in_maintenance(),
+    }
+}
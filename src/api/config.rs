@@ -46,6 +46,11 @@ pub async fn patch_config<S: Storage + 'static>(
     let config_arc = router.config();
     let mut config_guard = config_arc.write().await;
 
+    // Changes the config file can't keep would be lost on restart
+    config_guard
+        .check_saveable()
+        .map_err(|e| ApiError::Conflict(e.to_string()))?;
+
     // Convert current config to Value
     let mut config_value = serde_json::to_value(&*config_guard).map_err(|e| {
         ApiError::InternalError(format!("Failed to serialize current config: {}", e))
@@ -61,13 +66,18 @@ pub async fn patch_config<S: Storage + 'static>(
     // Restore config_path (skipped during serialization)
     new_config.config_path = config_guard.config_path.clone();
 
-    // Update guard
-    *config_guard = new_config;
+    // The policy bypass may only be set in the config file
+    if new_config.tools.dev_bypass_policy != config_guard.tools.dev_bypass_policy {
+        return Err(ApiError::Forbidden(
+            "tools.dev_bypass_policy cannot be changed through the API".to_string(),
+        ));
+    }
 
-    // Save to disk
-    config_guard
+    // Save to disk before the running config changes
+    new_config
         .save()
         .map_err(|e| ApiError::InternalError(format!("Failed to save config: {}", e)))?;
+    *config_guard = new_config;
 
     Ok(Json(config_guard.clone()))
 }
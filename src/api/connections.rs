@@ -0,0 +1,162 @@
+//! Registry of open WebSocket and SSE connections
+//!
+//! Every streaming connection registers itself for as long as it is open,
+//! so admins can list them (`GET /api/admin/connections`) and close one
+//! (`DELETE /api/admin/connections/:id`). Revoking a token closes the
+//! connections opened with it.
+
+use chrono::{DateTime, Utc};
+use once_cell::sync::Lazy;
+use serde::Serialize;
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use tokio::sync::Notify;
+
+/// Transport of a connection
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ConnectionKind {
+    WebSocket,
+    Sse,
+}
+
+/// An open connection, as listed to admins
+#[derive(Debug, Clone, Serialize)]
+pub struct ConnectionInfo {
+    pub id: String,
+    pub user_id: String,
+    pub session_id: Option<String>,
+    pub channel: ConnectionKind,
+    pub connected_at: DateTime<Utc>,
+}
+
+struct Entry {
+    info: ConnectionInfo,
+    /// Token the connection was opened with, to close it on revocation
+    token: String,
+    close: Arc<Notify>,
+}
+
+static CONNECTIONS: Lazy<Mutex<HashMap<String, Entry>>> = Lazy::new(|| Mutex::new(HashMap::new()));
+
+/// Registration of an open connection, removed when dropped
+pub struct ConnectionGuard {
+    id: String,
+    close: Arc<Notify>,
+}
+
+impl ConnectionGuard {
+    pub fn id(&self) -> &str {
+        &self.id
+    }
+
+    /// Resolves once the connection has been asked to close
+    pub async fn closed(&self) {
+        self.close.notified().await
+    }
+}
+
+impl Drop for ConnectionGuard {
+    fn drop(&mut self) {
+        CONNECTIONS.lock().unwrap().remove(&self.id);
+    }
+}
+
+/// Register a connection until the returned guard is dropped
+pub fn register(
+    channel: ConnectionKind,
+    user_id: &str,
+    session_id: Option<&str>,
+    token: &str,
+) -> ConnectionGuard {
+    let id = uuid::Uuid::new_v4().to_string();
+    let close = Arc::new(Notify::new());
+    let entry = Entry {
+        info: ConnectionInfo {
+            id: id.clone(),
+            user_id: user_id.to_string(),
+            session_id: session_id.map(str::to_string),
+            channel,
+            connected_at: Utc::now(),
+        },
+        token: token.to_string(),
+        close: close.clone(),
+    };
+    CONNECTIONS.lock().unwrap().insert(id.clone(), entry);
+    ConnectionGuard { id, close }
+}
+
+/// Open connections, oldest first
+pub fn list() -> Vec<ConnectionInfo> {
+    let mut connections: Vec<ConnectionInfo> = CONNECTIONS
+        .lock()
+        .unwrap()
+        .values()
+        .map(|entry| entry.info.clone())
+        .collect();
+    connections.sort_by_key(|info| info.connected_at);
+    connections
+}
+
+/// Ask a connection to close. Returns false if it is not open.
+pub fn close(id: &str) -> bool {
+    match CONNECTIONS.lock().unwrap().remove(id) {
+        Some(entry) => {
+            // Stored as a permit if the connection is not waiting right now
+            entry.close.notify_one();
+            true
+        }
+        None => false,
+    }
+}
+
+/// Close every connection opened with a token, returning how many were open
+pub fn close_token(token: &str) -> usize {
+    let mut connections = CONNECTIONS.lock().unwrap();
+    let ids: Vec<String> = connections
+        .iter()
+        .filter(|(_, entry)| entry.token == token)
+        .map(|(id, _)| id.clone())
+        .collect();
+    for id in &ids {
+        if let Some(entry) = connections.remove(id) {
+            entry.close.notify_one();
+        }
+    }
+    ids.len()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    #[tokio::test]
+    async fn test_closing_a_connection_wakes_it() {
+        let guard = register(ConnectionKind::WebSocket, "alice", Some("s1"), "tok-a");
+        assert!(list().iter().any(|info| info.id == guard.id()));
+
+        assert!(close(guard.id()));
+        assert!(!close(guard.id()));
+        assert!(!list().iter().any(|info| info.id == guard.id()));
+        tokio::time::timeout(Duration::from_secs(1), guard.closed())
+            .await
+            .expect("connection not told to close");
+    }
+
+    #[tokio::test]
+    async fn test_revoked_token_closes_its_connections() {
+        let first = register(ConnectionKind::Sse, "bob", None, "tok-revoked");
+        let second = register(ConnectionKind::WebSocket, "bob", None, "tok-revoked");
+        let other = register(ConnectionKind::WebSocket, "bob", None, "tok-kept");
+
+        assert_eq!(close_token("tok-revoked"), 2);
+        first.closed().await;
+        second.closed().await;
+        assert!(list().iter().any(|info| info.id == other.id()));
+
+        let other_id = other.id().to_string();
+        drop(other);
+        assert!(!list().iter().any(|info| info.id == other_id));
+    }
+}
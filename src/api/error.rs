@@ -1,3 +1,6 @@
+use crate::core::maintenance::MaintenanceActive;
+use crate::core::{ContextWindowExceeded, ToolNotAvailable};
+use crate::llm::LlmError;
 use axum::http::StatusCode;
 use axum::response::{IntoResponse, Response};
 use serde_json::json;
@@ -20,6 +23,9 @@ pub enum ApiError {
     /// Conflict (409)
     Conflict(String),
 
+    /// No admin account exists yet (409)
+    SetupRequired,
+
     /// Rate limit exceeded (429)
     RateLimited { retry_after: u64 },
 
@@ -28,6 +34,9 @@ pub enum ApiError {
 
     /// Service unavailable (503)
     ServiceUnavailable(String),
+
+    /// Gateway in maintenance mode, only reads are served (503)
+    Maintenance,
 }
 
 impl ApiError {
@@ -38,10 +47,10 @@ impl ApiError {
             Self::Unauthorized(_) => StatusCode::UNAUTHORIZED,
             Self::Forbidden(_) => StatusCode::FORBIDDEN,
             Self::NotFound(_) => StatusCode::NOT_FOUND,
-            Self::Conflict(_) => StatusCode::CONFLICT,
+            Self::Conflict(_) | Self::SetupRequired => StatusCode::CONFLICT,
             Self::RateLimited { .. } => StatusCode::TOO_MANY_REQUESTS,
             Self::InternalError(_) => StatusCode::INTERNAL_SERVER_ERROR,
-            Self::ServiceUnavailable(_) => StatusCode::SERVICE_UNAVAILABLE,
+            Self::ServiceUnavailable(_) | Self::Maintenance => StatusCode::SERVICE_UNAVAILABLE,
         }
     }
 
@@ -52,10 +61,10 @@ impl ApiError {
             Self::Unauthorized(_) => 401,
             Self::Forbidden(_) => 403,
             Self::NotFound(_) => 404,
-            Self::Conflict(_) => 409,
+            Self::Conflict(_) | Self::SetupRequired => 409,
             Self::RateLimited { .. } => 429,
             Self::InternalError(_) => 500,
-            Self::ServiceUnavailable(_) => 503,
+            Self::ServiceUnavailable(_) | Self::Maintenance => 503,
         }
     }
 
@@ -67,9 +76,13 @@ impl ApiError {
             Self::Forbidden(msg) => msg.clone(),
             Self::NotFound(msg) => msg.clone(),
             Self::Conflict(msg) => msg.clone(),
+            Self::SetupRequired => {
+                "Setup required: create the first admin account with POST /api/setup".to_string()
+            }
             Self::RateLimited { .. } => "Rate limit exceeded".to_string(),
             Self::InternalError(msg) => msg.clone(),
             Self::ServiceUnavailable(msg) => msg.clone(),
+            Self::Maintenance => MaintenanceActive.to_string(),
         }
     }
 }
@@ -90,6 +103,16 @@ impl IntoResponse for ApiError {
             body["retry_after"] = json!(retry_after);
         }
 
+        // Let clients tell setup apart from other conflicts
+        if let Self::SetupRequired = self {
+            body["setup_required"] = json!(true);
+        }
+
+        // ...and maintenance apart from a failing backend
+        if let Self::Maintenance = self {
+            body["maintenance"] = json!(true);
+        }
+
         (status, axum::Json(body)).into_response()
     }
 }
@@ -101,6 +124,50 @@ impl From<anyhow::Error> for ApiError {
     }
 }
 
+/// Seconds clients are asked to wait when the backend gives no hint
+const DEFAULT_LLM_RETRY_AFTER: u64 = 30;
+
+impl From<&LlmError> for ApiError {
+    fn from(err: &LlmError) -> Self {
+        match err {
+            LlmError::Timeout => Self::ServiceUnavailable("LLM request timed out".to_string()),
+            LlmError::Unavailable(_) => {
+                Self::ServiceUnavailable("LLM service unavailable".to_string())
+            }
+            LlmError::RateLimited { retry_after } => Self::RateLimited {
+                retry_after: retry_after.unwrap_or(DEFAULT_LLM_RETRY_AFTER),
+            },
+            LlmError::BadRequest(msg) => Self::BadRequest(msg.clone()),
+            LlmError::StreamingUnsupported(_) | LlmError::Backend(_) => {
+                Self::InternalError("Failed to process message".to_string())
+            }
+        }
+    }
+}
+
+impl ApiError {
+    /// Map a message-processing failure, using the LLM error classification
+    /// when the failure originated from the LLM client. Context window
+    /// overflows and unavailable tool choices are reported as bad requests
+    /// the user can act on.
+    pub fn from_processing_error(err: &anyhow::Error) -> Self {
+        if let Some(overflow) = err.downcast_ref::<ContextWindowExceeded>() {
+            return Self::BadRequest(overflow.to_string());
+        }
+        if let Some(unavailable) = err.downcast_ref::<ToolNotAvailable>() {
+            return Self::BadRequest(unavailable.to_string());
+        }
+        if err.downcast_ref::<MaintenanceActive>().is_some() {
+            return Self::Maintenance;
+        }
+
+        err.chain()
+            .find_map(|cause| cause.downcast_ref::<LlmError>())
+            .map(Self::from)
+            .unwrap_or_else(|| Self::InternalError("Failed to process message".to_string()))
+    }
+}
+
 impl From<serde_json::Error> for ApiError {
     fn from(err: serde_json::Error) -> Self {
         tracing::warn!("JSON error: {}", err);
@@ -111,6 +178,7 @@ impl From<serde_json::Error> for ApiError {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use anyhow::Context;
 
     #[test]
     fn test_error_status_codes() {
@@ -139,6 +207,64 @@ mod tests {
         assert_eq!(ApiError::InternalError("test".into()).error_code(), 500);
     }
 
+    #[test]
+    fn test_llm_error_mapping() {
+        assert_eq!(
+            ApiError::from(&LlmError::Timeout).status_code(),
+            StatusCode::SERVICE_UNAVAILABLE
+        );
+        assert_eq!(
+            ApiError::from(&LlmError::Unavailable("down".into())).status_code(),
+            StatusCode::SERVICE_UNAVAILABLE
+        );
+        assert!(matches!(
+            ApiError::from(&LlmError::RateLimited {
+                retry_after: Some(5)
+            }),
+            ApiError::RateLimited { retry_after: 5 }
+        ));
+        assert_eq!(
+            ApiError::from(&LlmError::BadRequest("bad".into())).status_code(),
+            StatusCode::BAD_REQUEST
+        );
+        assert_eq!(
+            ApiError::from(&LlmError::Backend("boom".into())).status_code(),
+            StatusCode::INTERNAL_SERVER_ERROR
+        );
+    }
+
+    #[test]
+    fn test_processing_error_finds_wrapped_llm_error() {
+        let err = Err::<(), _>(LlmError::Unavailable("connection refused".into()))
+            .context("Failed to get LLM response")
+            .unwrap_err();
+        assert_eq!(
+            ApiError::from_processing_error(&err).status_code(),
+            StatusCode::SERVICE_UNAVAILABLE
+        );
+
+        let other = anyhow::anyhow!("database locked");
+        assert_eq!(
+            ApiError::from_processing_error(&other).status_code(),
+            StatusCode::INTERNAL_SERVER_ERROR
+        );
+
+        let overflow = anyhow::Error::new(ContextWindowExceeded {
+            estimated: 9000,
+            limit: 4096,
+        });
+        assert_eq!(
+            ApiError::from_processing_error(&overflow).status_code(),
+            StatusCode::BAD_REQUEST
+        );
+
+        let maintenance = anyhow::Error::new(MaintenanceActive);
+        assert!(matches!(
+            ApiError::from_processing_error(&maintenance),
+            ApiError::Maintenance
+        ));
+    }
+
     #[test]
     fn test_error_message() {
         let msg = "test message";
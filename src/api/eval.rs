@@ -0,0 +1,149 @@
+//! Eval runs: prompt regression suites answered with a fixed seed and
+//! model (see [`crate::core::eval`]). Suites can be saved by name and run
+//! again later.
+
+use crate::api::{ApiError, ApiResponse, AuthUserId};
+use crate::core::eval::{self, EvalCaseResult, DEFAULT_EVAL_SEED};
+use crate::core::Router;
+use crate::storage::{EvalCase, EvalSuite, Storage};
+use axum::extract::{Extension, Path, State};
+use axum::http::StatusCode;
+use axum::Json;
+use chrono::Utc;
+use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+
+/// Longest name a suite is saved under
+const MAX_SUITE_NAME_CHARS: usize = 100;
+
+#[derive(Debug, Deserialize)]
+pub struct EvalRunRequest {
+    /// Cases to run; without any, the saved suite `suite` is run
+    #[serde(default)]
+    pub cases: Vec<EvalCase>,
+    /// Saved suite to run, or name to save `cases` under before running them
+    #[serde(default)]
+    pub suite: Option<String>,
+    #[serde(default)]
+    pub seed: Option<i64>,
+    /// Model name or `role:<name>`; the primary model by default
+    #[serde(default)]
+    pub model: Option<String>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct EvalRunResponse {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub suite: Option<String>,
+    pub seed: i64,
+    pub model: String,
+    pub passed: usize,
+    pub failed: usize,
+    /// Results in case order
+    pub results: Vec<EvalCaseResult>,
+}
+
+/// POST /api/eval/run - Run an eval suite and report pass/fail per case
+///
+/// Cases run in ephemeral sessions, so the user's conversations are left
+/// untouched. Suite size and concurrency follow the batch chat limits.
+pub async fn run_eval<S: Storage + 'static>(
+    State(router): State<Arc<Router<S>>>,
+    Extension(AuthUserId(user_id)): Extension<AuthUserId>,
+    Json(req): Json<EvalRunRequest>,
+) -> Result<Json<ApiResponse<EvalRunResponse>>, ApiError> {
+    let (max_cases, concurrency) = {
+        let config = router.config();
+        let config = config.read().await;
+        (config.api.batch_max_size, config.api.batch_concurrency)
+    };
+
+    let cases = match (req.cases.is_empty(), &req.suite) {
+        (true, None) => {
+            return Err(ApiError::BadRequest(
+                "cases or a saved suite is required".to_string(),
+            ))
+        }
+        (true, Some(name)) => {
+            router
+                .get_storage()
+                .get_eval_suite(&user_id, name)
+                .await?
+                .ok_or_else(|| ApiError::NotFound(format!("Eval suite {} not found", name)))?
+                .cases
+        }
+        (false, _) => req.cases,
+    };
+    if cases.len() > max_cases {
+        return Err(ApiError::BadRequest(format!(
+            "too many cases (max {} per run)",
+            max_cases
+        )));
+    }
+    eval::validate_cases(&cases).map_err(ApiError::BadRequest)?;
+
+    if let Some(name) = &req.suite {
+        if name.trim().is_empty() || name.chars().count() > MAX_SUITE_NAME_CHARS {
+            return Err(ApiError::BadRequest(format!(
+                "suite name must be between 1 and {} chars",
+                MAX_SUITE_NAME_CHARS
+            )));
+        }
+        router
+            .get_storage()
+            .save_eval_suite(&EvalSuite {
+                user_id: user_id.clone(),
+                name: name.clone(),
+                cases: cases.clone(),
+                updated_at: Utc::now(),
+            })
+            .await?;
+    }
+
+    let llm_client = router.llm_client();
+    let model = req
+        .model
+        .as_deref()
+        .map_or(llm_client.primary_model(), |model| {
+            llm_client.resolve_model_name(model)
+        })
+        .to_string();
+    let seed = req.seed.unwrap_or(DEFAULT_EVAL_SEED);
+
+    let results = eval::run_cases(&router, &user_id, cases, seed, &model, concurrency).await;
+    let passed = results.iter().filter(|result| result.passed).count();
+
+    Ok(Json(ApiResponse::success(EvalRunResponse {
+        suite: req.suite,
+        seed,
+        model,
+        passed,
+        failed: results.len() - passed,
+        results,
+    })))
+}
+
+/// GET /api/eval/suites - The user's saved eval suites
+pub async fn list_eval_suites<S: Storage + 'static>(
+    State(router): State<Arc<Router<S>>>,
+    Extension(AuthUserId(user_id)): Extension<AuthUserId>,
+) -> Result<Json<ApiResponse<Vec<EvalSuite>>>, ApiError> {
+    let suites = router.get_storage().list_eval_suites(&user_id).await?;
+    Ok(Json(ApiResponse::success(suites)))
+}
+
+/// DELETE /api/eval/suites/:name - Delete a saved eval suite
+pub async fn delete_eval_suite<S: Storage + 'static>(
+    State(router): State<Arc<Router<S>>>,
+    Extension(AuthUserId(user_id)): Extension<AuthUserId>,
+    Path(name): Path<String>,
+) -> Result<StatusCode, ApiError> {
+    if !router
+        .get_storage()
+        .delete_eval_suite(&user_id, &name)
+        .await?
+    {
+        return Err(ApiError::NotFound(format!("Eval suite {} not found", name)));
+    }
+    Ok(StatusCode::NO_CONTENT)
+}
@@ -0,0 +1,177 @@
+//! Export of stored conversations as fine-tuning data
+
+use crate::api::ApiError;
+use crate::core::redaction::Redactor;
+use crate::core::Router;
+use crate::storage::{Message, Storage};
+use axum::body::Body;
+use axum::extract::{Query, State};
+use axum::http::header;
+use axum::response::{IntoResponse, Response};
+use chrono::{DateTime, Utc};
+use futures::StreamExt;
+use serde::Deserialize;
+use std::sync::Arc;
+
+/// Sessions exported when no limit is given
+const DEFAULT_EXPORT_SESSIONS: usize = 1000;
+/// Most sessions a single export may contain
+const MAX_EXPORT_SESSIONS: usize = 10_000;
+/// Most recent messages kept per session when no limit is given
+const DEFAULT_MESSAGES_PER_SESSION: usize = 200;
+/// Most messages a single exported session may contain
+const MAX_MESSAGES_PER_SESSION: usize = 1000;
+
+#[derive(Debug, Default, Deserialize)]
+pub struct FinetuneExportQuery {
+    /// Only sessions with messages at or after this time (RFC 3339)
+    #[serde(default)]
+    pub since: Option<DateTime<Utc>>,
+    /// Only sessions rated thumbs-up and never thumbs-down
+    #[serde(default)]
+    pub positive_only: bool,
+    /// Most recent messages kept per session
+    #[serde(default)]
+    pub max_messages: Option<usize>,
+    /// Most sessions exported, most recently active first
+    #[serde(default)]
+    pub limit: Option<usize>,
+}
+
+/// GET /api/export/finetune - Conversations as OpenAI fine-tuning JSONL
+///
+/// Streams one `{"messages": [...]}` line per session holding the user and
+/// assistant turns, passed through the configured redaction patterns.
+pub async fn finetune_export<S: Storage + 'static>(
+    State(router): State<Arc<Router<S>>>,
+    Query(query): Query<FinetuneExportQuery>,
+) -> Result<Response, ApiError> {
+    let limit = query
+        .limit
+        .unwrap_or(DEFAULT_EXPORT_SESSIONS)
+        .min(MAX_EXPORT_SESSIONS);
+    let max_messages = query
+        .max_messages
+        .unwrap_or(DEFAULT_MESSAGES_PER_SESSION)
+        .clamp(2, MAX_MESSAGES_PER_SESSION);
+
+    let storage = router.get_storage().clone();
+    let mut sessions = if query.positive_only {
+        let rated = storage.positively_rated_sessions().await?;
+        let mut sessions = storage
+            .list_active_sessions(query.since, MAX_EXPORT_SESSIONS)
+            .await?;
+        sessions.retain(|session| rated.contains(&session.id));
+        sessions
+    } else {
+        storage.list_active_sessions(query.since, limit).await?
+    };
+    sessions.truncate(limit);
+
+    let redactor = {
+        let config = router.config();
+        let config = config.read().await;
+        Redactor::from_config(&config.redaction)
+    }
+    .map_err(|e| ApiError::InternalError(format!("Invalid redaction config: {}", e)))?;
+    let redactor = Arc::new(redactor);
+
+    let lines = futures::stream::iter(sessions)
+        .then(move |session| {
+            let storage = storage.clone();
+            let redactor = redactor.clone();
+            async move {
+                match storage.get_messages(&session.id, Some(max_messages)).await {
+                    Ok(messages) => finetune_record(&messages, redactor.as_ref().as_ref())
+                        .map(|line| Ok::<_, std::io::Error>(line + "\n")),
+                    Err(e) => {
+                        tracing::warn!("Skipping session {} in export: {}", session.id, e);
+                        None
+                    }
+                }
+            }
+        })
+        .filter_map(futures::future::ready);
+
+    Ok((
+        [(header::CONTENT_TYPE, "application/x-ndjson")],
+        Body::from_stream(lines),
+    )
+        .into_response())
+}
+
+/// One fine-tuning example from a conversation, or `None` when it holds no
+/// exchange. Examples start with a user turn and end with a reply.
+fn finetune_record(messages: &[Message], redactor: Option<&Redactor>) -> Option<String> {
+    let mut turns: Vec<&Message> = messages
+        .iter()
+        .filter(|m| m.role == "user" || m.role == "assistant")
+        .collect();
+    let start = turns.iter().position(|m| m.role == "user")?;
+    let end = turns.iter().rposition(|m| m.role == "assistant")?;
+    if end < start {
+        return None;
+    }
+    turns = turns[start..=end].to_vec();
+
+    let messages: Vec<serde_json::Value> = turns
+        .into_iter()
+        .map(|m| {
+            let content = match redactor {
+                Some(redactor) => redactor.redact(&m.content).0,
+                None => m.content.clone(),
+            };
+            serde_json::json!({ "role": m.role, "content": content })
+        })
+        .collect();
+
+    Some(serde_json::json!({ "messages": messages }).to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::RedactionConfig;
+
+    fn message(role: &str, content: &str) -> Message {
+        Message {
+            id: uuid::Uuid::new_v4().to_string(),
+            session_id: "s1".to_string(),
+            role: role.to_string(),
+            content: content.to_string(),
+            created_at: Utc::now(),
+            model_used: None,
+            tokens: None,
+            metadata: None,
+        }
+    }
+
+    #[test]
+    fn test_record_trims_to_user_reply_pairs_and_redacts() {
+        let redactor = Redactor::from_config(&RedactionConfig {
+            enabled: true,
+            ..Default::default()
+        })
+        .unwrap()
+        .unwrap();
+        let messages = vec![
+            message("assistant", "Welcome back"),
+            message("user", "Mail me at jane@example.com"),
+            message("tool", "{\"ok\":true}"),
+            message("assistant", "Done"),
+            message("user", "Thanks"),
+        ];
+
+        let line = finetune_record(&messages, Some(&redactor)).unwrap();
+        let record: serde_json::Value = serde_json::from_str(&line).unwrap();
+
+        assert_eq!(
+            record,
+            serde_json::json!({ "messages": [
+                { "role": "user", "content": "Mail me at [REDACTED]" },
+                { "role": "assistant", "content": "Done" },
+            ]})
+        );
+        assert!(finetune_record(&[message("user", "Hello?")], None).is_none());
+    }
+}
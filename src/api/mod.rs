@@ -1,16 +1,32 @@
+pub mod archive;
 pub mod auth;
+pub mod capabilities;
 pub mod config;
+pub mod connections;
 pub mod error;
+pub mod eval;
+pub mod export;
+pub mod plugins;
 pub mod response;
 pub mod routes;
+pub mod schedules;
+pub mod settings;
+pub mod stats;
+pub mod stream_buffer;
+pub mod tls;
+#[cfg(unix)]
+pub mod unix_socket;
 pub mod websocket;
 pub mod workspace;
 
+use crate::channels::supervisor::ChannelState;
+use crate::config::{TlsConfig, UnixSocketConfig};
 use crate::core::Router;
 use crate::storage::Storage;
 use anyhow::{Context, Result};
 use axum::extract::DefaultBodyLimit;
-use axum::http::StatusCode;
+use axum::http::{Method, StatusCode};
+use axum::response::IntoResponse;
 use axum::routing::{delete, get, post, put};
 use axum::{Json, Router as AxumRouter};
 use std::sync::Arc;
@@ -18,7 +34,7 @@ use tracing::info;
 
 use crate::mcp::sse::{messages_handler, sse_handler};
 
-pub use auth::AuthManager;
+pub use auth::{AuthManager, AuthUserId, TokenScopes};
 pub use error::ApiError;
 pub use response::*;
 
@@ -30,6 +46,8 @@ pub struct WebApiAdapter<S: Storage> {
     port: u16,
     api_path: String,
     ws_path: String,
+    tls: Option<TlsConfig>,
+    unix_socket: Option<UnixSocketConfig>,
 }
 
 impl<S: Storage + 'static> WebApiAdapter<S> {
@@ -50,6 +68,8 @@ impl<S: Storage + 'static> WebApiAdapter<S> {
             port,
             api_path: "/api".to_string(),
             ws_path: "/ws".to_string(),
+            tls: None,
+            unix_socket: None,
         }
     }
 
@@ -59,6 +79,27 @@ impl<S: Storage + 'static> WebApiAdapter<S> {
         self
     }
 
+    /// Restrict config tokens to the given scopes
+    pub fn with_token_scopes(
+        mut self,
+        scopes: std::collections::HashMap<String, Vec<String>>,
+    ) -> Self {
+        self.auth_manager = self.auth_manager.with_scopes(scopes);
+        self
+    }
+
+    /// Serve HTTPS with the given certificate instead of plain HTTP
+    pub fn with_tls(mut self, tls: Option<TlsConfig>) -> Self {
+        self.tls = tls;
+        self
+    }
+
+    /// Listen on a Unix domain socket instead of `host:port`
+    pub fn with_unix_socket(mut self, unix_socket: Option<UnixSocketConfig>) -> Self {
+        self.unix_socket = unix_socket;
+        self
+    }
+
     /// Set custom WebSocket path
     pub fn with_ws_path(mut self, path: String) -> Self {
         self.ws_path = path;
@@ -70,10 +111,23 @@ impl<S: Storage + 'static> WebApiAdapter<S> {
         // Public endpoints (no auth required)
         let public_routes = AxumRouter::new()
             .route("/health", get(health_handler))
+            .route("/ready", get(routes::readiness))
             .route(
                 &format!("{}/auth/join", self.api_path),
                 post(routes::join_invite),
             )
+            .route(
+                &format!("{}/auth/oidc/login", self.api_path),
+                get(routes::oidc_login),
+            )
+            .route(
+                &format!("{}/auth/oidc/callback", self.api_path),
+                get(routes::oidc_callback),
+            )
+            .route(
+                &format!("{}/setup", self.api_path),
+                post(routes::setup_admin),
+            )
             .route(
                 &self.ws_path,
                 axum::routing::get(websocket::websocket_handler),
@@ -84,9 +138,21 @@ impl<S: Storage + 'static> WebApiAdapter<S> {
                 provide_auth_extension,
             ));
 
-        // Protected endpoints (auth required)
-        let api_routes = AxumRouter::new()
-            // Auth endpoints
+        // Endpoints issuing or managing credentials, closed to impersonation
+        // tokens (the admin ones also need the admin role)
+        let credential_routes = AxumRouter::new()
+            .route(
+                &format!("{}/auth/invite", self.api_path),
+                post(routes::create_invite),
+            )
+            .route(
+                &format!("{}/admin/impersonate/:user_id", self.api_path),
+                post(routes::impersonate_user),
+            )
+            .route_layer(axum::middleware::from_fn_with_state(
+                self.auth_manager.clone(),
+                AuthManager::admin_middleware,
+            ))
             .route(
                 &format!("{}/auth/change-password", self.api_path),
                 post(routes::change_password),
@@ -99,6 +165,108 @@ impl<S: Storage + 'static> WebApiAdapter<S> {
                 &format!("{}/auth/tokens/:token_id", self.api_path),
                 delete(routes::revoke_token),
             )
+            .route_layer(axum::middleware::from_fn(
+                AuthManager::<S>::credentials_middleware,
+            ));
+
+        // Admin-only endpoints (auth and admin role required)
+        let admin_routes = AxumRouter::new()
+            .route(
+                &format!("{}/models/:name/load", self.api_path),
+                post(routes::load_model),
+            )
+            .route(
+                &format!("{}/tools", self.api_path),
+                post(routes::create_tool),
+            )
+            .route(
+                &format!("{}/tools/upload", self.api_path),
+                post(routes::upload_tool),
+            )
+            .route(
+                &format!("{}/tools/reload", self.api_path),
+                post(routes::reload_tools),
+            )
+            .route(
+                &format!("{}/tools/:name", self.api_path),
+                put(routes::update_tool).delete(routes::delete_tool),
+            )
+            .route(
+                &format!("{}/tools/:name/rollback", self.api_path),
+                post(routes::rollback_tool),
+            )
+            .route(
+                &format!("{}/config", self.api_path),
+                get(config::get_config).patch(config::patch_config),
+            )
+            .route(&format!("{}/users", self.api_path), get(routes::list_users))
+            .route(
+                &format!("{}/admin/impersonations", self.api_path),
+                get(routes::list_impersonations),
+            )
+            .route(
+                &format!("{}/admin/connections", self.api_path),
+                get(routes::list_connections),
+            )
+            .route(
+                &format!("{}/admin/connections/:id", self.api_path),
+                delete(routes::close_connection),
+            )
+            .route(
+                &format!("{}/plugins", self.api_path),
+                get(plugins::list_plugins),
+            )
+            .route(
+                &format!("{}/plugins/:id/config", self.api_path),
+                put(plugins::update_plugin_config),
+            )
+            .route(
+                &format!("{}/admin/costs/:user_id", self.api_path),
+                delete(routes::reset_user_costs),
+            )
+            .route(
+                &format!("{}/users/:id/policies", self.api_path),
+                get(routes::get_user_policies).put(routes::set_user_policies),
+            )
+            .route(
+                &format!("{}/feedback/summary", self.api_path),
+                get(routes::feedback_summary),
+            )
+            .route(
+                &format!("{}/stats/tools", self.api_path),
+                get(stats::tool_stats),
+            )
+            .route(
+                &format!("{}/channels/:name/pause", self.api_path),
+                post(routes::pause_channel),
+            )
+            .route(
+                &format!("{}/channels/:name/resume", self.api_path),
+                post(routes::resume_channel),
+            )
+            .route(
+                &format!("{}/maintenance", self.api_path),
+                post(routes::start_maintenance).delete(routes::end_maintenance),
+            )
+            .route(
+                &format!("{}/export/finetune", self.api_path),
+                get(export::finetune_export),
+            )
+            .route(
+                &format!("{}/schedules", self.api_path),
+                get(schedules::list_schedules).post(schedules::create_schedule),
+            )
+            .route(
+                &format!("{}/schedules/:id", self.api_path),
+                put(schedules::update_schedule).delete(schedules::delete_schedule),
+            )
+            .route_layer(axum::middleware::from_fn_with_state(
+                self.auth_manager.clone(),
+                AuthManager::admin_middleware,
+            ));
+
+        // Protected endpoints (auth required)
+        let api_routes = AxumRouter::new()
             // Session endpoints
             .route(
                 &format!("{}/sessions", self.api_path),
@@ -116,8 +284,59 @@ impl<S: Storage + 'static> WebApiAdapter<S> {
                 &format!("{}/sessions/:id", self.api_path),
                 delete(routes::delete_session),
             )
+            .route(
+                &format!("{}/sessions/:id/tags", self.api_path),
+                put(routes::set_session_tags),
+            )
+            .route(
+                &format!("{}/sessions/:id/fork", self.api_path),
+                post(routes::fork_session),
+            )
+            .route(
+                &format!("{}/sessions/:id/archive", self.api_path),
+                get(archive::archive_session),
+            )
+            .route(
+                &format!("{}/sessions/import", self.api_path),
+                post(archive::import_session),
+            )
+            .route(
+                &format!("{}/sessions/:id/context", self.api_path),
+                get(routes::get_session_context),
+            )
+            .route(
+                &format!("{}/sessions/:id/summary", self.api_path),
+                get(routes::get_session_summary),
+            )
+            .route(
+                &format!("{}/sessions/:id/notes", self.api_path),
+                get(routes::list_session_notes).post(routes::create_session_note),
+            )
+            .route(
+                &format!("{}/sessions/:id/notes/:note_id", self.api_path),
+                delete(routes::delete_session_note),
+            )
             // Chat endpoint
             .route(&format!("{}/chat", self.api_path), post(routes::chat))
+            .route(
+                &format!("{}/chat/batch", self.api_path),
+                post(routes::chat_batch),
+            )
+            // User settings
+            .route(
+                &format!("{}/settings", self.api_path),
+                get(settings::get_settings).put(settings::update_settings),
+            )
+            // Eval endpoints
+            .route(&format!("{}/eval/run", self.api_path), post(eval::run_eval))
+            .route(
+                &format!("{}/eval/suites", self.api_path),
+                get(eval::list_eval_suites),
+            )
+            .route(
+                &format!("{}/eval/suites/:name", self.api_path),
+                delete(eval::delete_eval_suite),
+            )
             // Message endpoints
             .route(
                 &format!("{}/messages", self.api_path),
@@ -127,32 +346,28 @@ impl<S: Storage + 'static> WebApiAdapter<S> {
                 &format!("{}/messages/:id", self.api_path),
                 get(routes::get_message),
             )
-            // Models endpoints
             .route(
-                &format!("{}/models", self.api_path),
-                get(routes::list_models),
+                &format!("{}/messages/:id/feedback", self.api_path),
+                post(routes::rate_message),
             )
             .route(
-                &format!("{}/models/:name/load", self.api_path),
-                post(routes::load_model),
+                &format!("{}/capabilities", self.api_path),
+                get(capabilities::get_capabilities),
             )
-            // Tool endpoints
+            // Models endpoints
             .route(
-                &format!("{}/tools", self.api_path),
-                post(routes::create_tool),
+                &format!("{}/models", self.api_path),
+                get(routes::list_models),
             )
+            // Tool endpoints
             .route(&format!("{}/tools", self.api_path), get(routes::list_tools))
             .route(
-                &format!("{}/tools/:name", self.api_path),
-                get(routes::get_tool),
-            )
-            .route(
-                &format!("{}/tools/:name", self.api_path),
-                put(routes::update_tool),
+                &format!("{}/tools/tags", self.api_path),
+                get(routes::list_tool_tags),
             )
             .route(
                 &format!("{}/tools/:name", self.api_path),
-                delete(routes::delete_tool),
+                get(routes::get_tool),
             )
             .route(
                 &format!("{}/tools/:name/test", self.api_path),
@@ -162,6 +377,14 @@ impl<S: Storage + 'static> WebApiAdapter<S> {
                 &format!("{}/tools/:name/validate", self.api_path),
                 post(routes::validate_tool),
             )
+            .route(
+                &format!("{}/tools/:name/versions", self.api_path),
+                get(routes::list_tool_versions),
+            )
+            .route(
+                &format!("{}/tools/:name/logs", self.api_path),
+                get(routes::get_tool_logs),
+            )
             .route(
                 &format!("{}/tools/:name/definition", self.api_path),
                 get(routes::get_tool_definition),
@@ -170,11 +393,21 @@ impl<S: Storage + 'static> WebApiAdapter<S> {
                 &format!("{}/tools/definitions/all", self.api_path),
                 get(routes::get_all_tool_definitions),
             )
-            // Config endpoints
+            // Approval endpoints
             .route(
-                &format!("{}/config", self.api_path),
-                get(config::get_config).patch(config::patch_config),
+                &format!("{}/approvals/pending", self.api_path),
+                get(routes::list_pending_approvals),
             )
+            .route(
+                &format!("{}/input/:request_id", self.api_path),
+                post(routes::answer_user_input),
+            )
+            // Prompt endpoints
+            .route(
+                &format!("{}/prompt/preview", self.api_path),
+                get(routes::preview_prompt),
+            )
+            // Config endpoints
             .route(
                 &format!("{}/agents", self.api_path),
                 get(config::get_agents),
@@ -191,6 +424,8 @@ impl<S: Storage + 'static> WebApiAdapter<S> {
             // MCP Endpoints
             .route("/mcp/sse", get(sse_handler))
             .route("/mcp/messages", post(messages_handler))
+            .merge(admin_routes)
+            .merge(credential_routes)
             .with_state(self.router.clone())
             .layer(axum::middleware::from_fn_with_state(
                 self.auth_manager.clone(),
@@ -201,6 +436,13 @@ impl<S: Storage + 'static> WebApiAdapter<S> {
         AxumRouter::new()
             .merge(public_routes)
             .merge(api_routes)
+            .layer(axum::middleware::from_fn_with_state(
+                (
+                    self.router.clone(),
+                    format!("{}/maintenance", self.api_path),
+                ),
+                maintenance_middleware,
+            ))
             .layer(DefaultBodyLimit::max(1024 * 1024 * 10)) // 10MB
             .layer(axum::middleware::from_fn(logging_middleware))
     }
@@ -215,6 +457,41 @@ impl<S: Storage + 'static> WebApiAdapter<S> {
             addr, self.api_path, self.ws_path
         );
 
+        if let Some(unix_socket) = &self.unix_socket {
+            if self.tls.is_some() {
+                tracing::warn!("api.tls is ignored when listening on a Unix socket");
+            }
+            #[cfg(unix)]
+            return unix_socket::serve(app, unix_socket).await;
+            #[cfg(not(unix))]
+            anyhow::bail!(
+                "api.unix_socket ({}) is only supported on Unix",
+                unix_socket.path.display()
+            );
+        }
+
+        if let Some(tls) = &self.tls {
+            let rustls_config = tls::load(tls).await?;
+            let (watched, tls) = (rustls_config.clone(), tls.clone());
+            tokio::spawn(async move {
+                if let Err(e) = tls::watch(watched, tls).await {
+                    tracing::error!("TLS certificate watcher error: {}", e);
+                }
+            });
+
+            let addr = tokio::net::lookup_host(&addr)
+                .await
+                .context("Failed to resolve bind address")?
+                .next()
+                .context("Bind address resolved to nothing")?;
+            info!("Web API serving HTTPS");
+            axum_server::bind_rustls(addr, rustls_config)
+                .serve(app.into_make_service())
+                .await
+                .context("Server error")?;
+            return Ok(());
+        }
+
         let listener = tokio::net::TcpListener::bind(&addr)
             .await
             .context("Failed to bind server")?;
@@ -226,13 +503,21 @@ impl<S: Storage + 'static> WebApiAdapter<S> {
 }
 
 /// Health check handler
-async fn health_handler() -> (StatusCode, Json<HealthResponse>) {
+async fn health_handler<S: Storage + 'static>(
+    axum::extract::State(router): axum::extract::State<Arc<Router<S>>>,
+) -> (StatusCode, Json<HealthResponse>) {
+    let channels = crate::channels::channel_statuses();
+    let down = channels
+        .iter()
+        .any(|channel| channel.state == ChannelState::Down);
     (
         StatusCode::OK,
         Json(HealthResponse {
-            status: "ok".to_string(),
+            status: if down { "degraded" } else { "ok" }.to_string(),
             version: env!("CARGO_PKG_VERSION").to_string(),
             gateway: "rustyclaw".to_string(),
+            maintenance: router.in_maintenance(),
+            channels,
         }),
     )
 }
@@ -253,6 +538,23 @@ async fn logging_middleware(
     response
 }
 
+/// Maintenance middleware: while the gateway is in maintenance mode only
+/// reads (and switching the mode off at `toggle_path`) get through
+async fn maintenance_middleware<S: Storage + 'static>(
+    axum::extract::State((router, toggle_path)): axum::extract::State<(Arc<Router<S>>, String)>,
+    request: axum::extract::Request,
+    next: axum::middleware::Next,
+) -> axum::response::Response {
+    let is_read = matches!(
+        *request.method(),
+        Method::GET | Method::HEAD | Method::OPTIONS
+    );
+    if router.in_maintenance() && !is_read && request.uri().path() != toggle_path {
+        return ApiError::Maintenance.into_response();
+    }
+    next.run(request).await
+}
+
 /// Middleware to provide AuthManager as an extension
 async fn provide_auth_extension<S: Storage + 'static>(
     axum::extract::State(auth): axum::extract::State<AuthManager<S>>,
@@ -276,6 +578,8 @@ mod tests {
             status: "ok".to_string(),
             version: "0.1.0".to_string(),
             gateway: "rustyclaw".to_string(),
+            maintenance: false,
+            channels: Vec::new(),
         };
 
         assert_eq!(response.status, "ok");
@@ -0,0 +1,40 @@
+//! Loaded plugins: listing them with their tools, and changing a plugin's
+//! configuration without a restart. A new configuration is validated
+//! against the plugin's `config_schema` and applied live; it is not written
+//! to the config file.
+
+use crate::api::{ApiError, ApiResponse};
+use crate::plugins::{PluginConfigError, PluginInfo};
+use axum::extract::Path;
+use axum::Json;
+
+/// GET /api/plugins - Loaded plugins with their versions and tools (admin)
+pub async fn list_plugins() -> Result<Json<ApiResponse<Vec<PluginInfo>>>, ApiError> {
+    let plugins = match crate::plugins::get_plugin_registry() {
+        Some(registry) => registry.plugin_infos()?,
+        None => Vec::new(),
+    };
+    Ok(Json(ApiResponse::success(plugins)))
+}
+
+/// PUT /api/plugins/:id/config - Apply a new configuration to a loaded
+/// plugin (admin)
+pub async fn update_plugin_config(
+    Path(id): Path<String>,
+    Json(config): Json<serde_json::Value>,
+) -> Result<Json<ApiResponse<serde_json::Value>>, ApiError> {
+    let registry = crate::plugins::get_plugin_registry()
+        .ok_or_else(|| ApiError::NotFound(format!("Plugin '{}' not found", id)))?;
+    registry
+        .reconfigure_plugin(&id, config)
+        .await
+        .map_err(|e| match e {
+            PluginConfigError::NotFound(_) => ApiError::NotFound(e.to_string()),
+            _ => ApiError::BadRequest(e.to_string()),
+        })?;
+    tracing::info!("Admin reconfigured plugin {}", id);
+    Ok(Json(ApiResponse::success(serde_json::json!({
+        "id": id,
+        "reconfigured": true,
+    }))))
+}
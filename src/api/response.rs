@@ -1,3 +1,5 @@
+use crate::core::{ContextBudget, TurnOptions};
+use crate::llm::ToolChoice;
 use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
 
@@ -68,6 +70,31 @@ pub struct ChatRequest {
     pub session_id: Option<String>,
     #[serde(default)]
     pub stream: bool,
+    /// Documents added to the prompt for this request only (not stored)
+    #[serde(default)]
+    pub context: Vec<String>,
+    /// Sampling seed for reproducible generations (evals, tests)
+    #[serde(default)]
+    pub seed: Option<i64>,
+    /// Text the reply is forced to start with (e.g. `{` for JSON); the
+    /// returned reply includes it
+    #[serde(default)]
+    pub prefill: Option<String>,
+    /// `auto` (default), `none` to send no tools, or the name of a tool
+    /// the model must call first
+    #[serde(default)]
+    pub tool_choice: ToolChoice,
+}
+
+impl ChatRequest {
+    /// The seed, prefill and tool choice the turn is answered with
+    pub fn turn_options(&self) -> TurnOptions {
+        TurnOptions {
+            seed: self.seed,
+            prefill: self.prefill.clone(),
+            tool_choice: self.tool_choice.clone(),
+        }
+    }
 }
 
 /// Chat response
@@ -80,6 +107,9 @@ pub struct ChatResponse {
     pub timestamp: DateTime<Utc>,
     pub input: ChatContent,
     pub response: ChatContent,
+    /// Where the tokens of the final request went, for context gauges
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub budget: Option<ContextBudget>,
     pub latency_ms: u64,
 }
 
@@ -90,6 +120,52 @@ pub struct ChatContent {
     pub tokens: usize,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub model: Option<String>,
+    /// Originally chosen model, set when a fallback model answered
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub fallback_from: Option<String>,
+    /// Seed the reply was generated with, echoed from the request
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub seed: Option<i64>,
+    /// Backend configuration fingerprint; the same seed only reproduces a
+    /// reply while it stays the same
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub system_fingerprint: Option<String>,
+    /// Set when a seed was requested but the backend did not confirm it
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub seed_note: Option<String>,
+}
+
+/// Batch chat request: independent prompts answered in throwaway sessions
+#[derive(Debug, Deserialize)]
+pub struct BatchChatRequest {
+    pub prompts: Vec<String>,
+    /// Send each item as an SSE event as soon as it finishes
+    #[serde(default)]
+    pub stream: bool,
+}
+
+/// Outcome of one prompt of a batch
+#[derive(Debug, Serialize)]
+pub struct BatchChatItem {
+    /// Position of the prompt in the request
+    pub index: usize,
+    /// "success" or "error"
+    pub status: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub response: Option<ChatContent>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error_code: Option<u32>,
+    pub latency_ms: u64,
+}
+
+/// Batch chat response, items in request order
+#[derive(Debug, Serialize)]
+pub struct BatchChatResponse {
+    pub items: Vec<BatchChatItem>,
+    pub succeeded: usize,
+    pub failed: usize,
 }
 
 /// Session list response
@@ -132,6 +208,18 @@ pub struct HealthResponse {
     pub status: String,
     pub version: String,
     pub gateway: String,
+    /// Only reads are served while the gateway is in maintenance mode
+    pub maintenance: bool,
+    /// Supervised channel adapters; `status` is `degraded` while one is down
+    pub channels: Vec<crate::channels::supervisor::ChannelStatus>,
+}
+
+/// Readiness check response
+#[derive(Debug, Serialize)]
+pub struct ReadinessResponse {
+    /// `ready`, or `unavailable` while the LLM circuit is open
+    pub status: String,
+    pub llm_circuit: crate::llm::CircuitState,
 }
 
 /// WebSocket message types
@@ -159,6 +247,35 @@ pub enum WebSocketMessage {
         total_tokens: usize,
         model: String,
         latency_ms: u64,
+        /// Completion tokens per second
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        tps: Option<f64>,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        time_to_first_token_ms: Option<u64>,
+        /// Where the tokens of the final request went
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        budget: Option<ContextBudget>,
+        /// The whole reply, replacing the streamed chunks, when reply
+        /// filters changed it
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        content: Option<String>,
+    },
+
+    /// Server → Client: The turn failed transiently and is retried after
+    /// `delay_ms`
+    Retrying {
+        attempt: u32,
+        max_attempts: u32,
+        delay_ms: u64,
+        reason: String,
+    },
+
+    /// Server → Client: Arguments of a tool call as they stream in; may be
+    /// incomplete JSON until the matching `tool_use` arrives
+    ToolArgsDelta {
+        index: usize,
+        name: String,
+        partial_args: String,
     },
 
     /// Server → Client: Tool execution status with output/error/timing
@@ -194,9 +311,21 @@ pub enum WebSocketMessage {
         remember_for_session: bool,
     },
 
+    /// Server → Client: The model asks the user a question; the reply goes
+    /// on once it is answered
+    UserInputRequest { request_id: String, prompt: String },
+
+    /// Client → Server: Answer to a question, also accepted while a reply
+    /// is streaming
+    UserInputResponse { request_id: String, answer: String },
+
     /// Server → Client: Error occurred
     Error { error: String, error_code: u32 },
 
+    /// Server → Client: The session was deleted after being idle; the
+    /// connection is closed
+    SessionExpired { session_id: String },
+
     /// Server → Client: Keepalive ping
     Ping,
 
@@ -242,6 +371,19 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_websocket_tool_args_delta_serialization() {
+        let msg = WebSocketMessage::ToolArgsDelta {
+            index: 0,
+            name: "web_fetch".to_string(),
+            partial_args: "{\"url\": \"ht".to_string(),
+        };
+        let json: serde_json::Value = serde_json::from_str(&msg.to_json().unwrap()).unwrap();
+
+        assert_eq!(json["type"], "tool_args_delta");
+        assert_eq!(json["partial_args"], "{\"url\": \"ht");
+    }
+
     #[test]
     fn test_websocket_ping_pong() {
         let ping = WebSocketMessage::Ping;
@@ -1,28 +1,40 @@
+use crate::api::connections::{self, ConnectionGuard, ConnectionInfo, ConnectionKind};
+use crate::api::stream_buffer::{stream_buffers, StreamBuffer, RECONNECT_GRACE, RESUME_WINDOW};
 use crate::api::{
-    ApiError, ApiResponse, ChatContent, ChatRequest, ChatResponse, MessageListResponse,
-    MessageResponse, ModelInfo, ModelsResponse, SessionListResponse, SessionResponse,
+    ApiError, ApiResponse, AuthManager, AuthUserId, BatchChatItem, BatchChatRequest,
+    BatchChatResponse, ChatContent, ChatRequest, ChatResponse, MessageListResponse,
+    MessageResponse, ModelInfo, ModelsResponse, ReadinessResponse, SessionListResponse,
+    SessionResponse, TokenScopes,
+};
+use crate::config::OidcConfig;
+use crate::core::oidc::{IdClaims, OidcError};
+use crate::core::{ContextPreview, Router, StreamEvent};
+use crate::llm::CircuitState;
+use crate::storage::{
+    FeedbackRating, FeedbackSummary, MessageFeedback, Page, PendingLink, SessionNote,
+    SessionSummary, Storage, User, UserFilter,
 };
-use crate::core::{Router, StreamEvent};
-use crate::storage::{Storage, User};
 use crate::tools::creator::{get_tool_storage_path, CreateToolRequest};
-use crate::tools::skills::parse_skill_file;
+use crate::tools::policy::{parse_policies, ToolAccessLevel};
+use crate::tools::skills::{parse_skill_file, SkillReloadReport};
 use crate::tools::{get_skill, list_skills, load_skill, unload_skill};
-use axum::extract::{Path, Query, State};
-use axum::http::StatusCode;
+use axum::extract::{Multipart, Path, Query, State};
+use axum::http::{HeaderMap, StatusCode};
 use axum::response::{
     sse::{Event, Sse},
-    IntoResponse, Response,
+    IntoResponse, Redirect, Response,
 };
 use axum::Extension;
 use axum::Json;
 use chrono::Utc;
 use futures::StreamExt;
 use serde::Deserialize;
+use std::collections::HashMap;
+use std::convert::Infallible;
 use std::sync::Arc;
 use std::time::Instant;
-use tokio_stream::wrappers::ReceiverStream;
 
-/// Query parameters for listing messages
+/// Query parameters for listing messages (and other paginated lists)
 #[derive(Deserialize)]
 pub struct MessageQuery {
     #[serde(default)]
@@ -31,6 +43,27 @@ pub struct MessageQuery {
     pub offset: Option<usize>,
 }
 
+impl MessageQuery {
+    /// Requested page: 50 items by default, at most 500
+    fn page(&self) -> Page {
+        Page::new(self.limit.unwrap_or(50).min(500), self.offset.unwrap_or(0))
+    }
+}
+
+/// Query parameters for listing users
+#[derive(Deserialize)]
+pub struct UserListQuery {
+    #[serde(default)]
+    pub limit: Option<usize>,
+    #[serde(default)]
+    pub offset: Option<usize>,
+    /// Part of the username, case-insensitive
+    #[serde(default)]
+    pub username: Option<String>,
+    #[serde(default)]
+    pub role: Option<String>,
+}
+
 /// Create session request
 #[derive(Deserialize)]
 pub struct CreateSessionRequest {
@@ -39,20 +72,105 @@ pub struct CreateSessionRequest {
 }
 
 /// Join request (device linking with username/password)
+///
+/// With an invite code, a new account is created from the invite instead
 #[derive(Deserialize)]
 pub struct JoinRequest {
     pub username: String,
     pub password: String,
     pub label: String,
+    #[serde(default)]
+    pub invite_code: Option<String>,
 }
 
-/// Join response
+/// Pending link provider used for account invites
+pub const INVITE_PROVIDER: &str = "invite";
+
+/// Identity provider linking an OIDC subject (`issuer|sub`) to its account
+pub const OIDC_PROVIDER: &str = "oidc";
+
+/// Create invite request
+#[derive(Deserialize)]
+pub struct CreateInviteRequest {
+    #[serde(default = "default_invite_role")]
+    pub role: String,
+    /// Scopes of the token issued on join; omitted for full access
+    #[serde(default)]
+    pub scopes: Option<Vec<String>>,
+    /// Overrides `api.invite_ttl_secs`
+    #[serde(default)]
+    pub expires_in_secs: Option<u64>,
+}
+
+fn default_invite_role() -> String {
+    "user".to_string()
+}
+
+/// Create invite response
 #[derive(serde::Serialize)]
+pub struct InviteResponse {
+    pub code: String,
+    pub role: String,
+    pub scopes: Option<Vec<String>>,
+    pub expires_at: chrono::DateTime<Utc>,
+}
+
+/// Impersonate request
+#[derive(Deserialize, Default)]
+pub struct ImpersonateRequest {
+    /// Why the admin acts as the user, kept in the audit trail
+    #[serde(default)]
+    pub reason: Option<String>,
+    /// Required to act as another admin
+    #[serde(default)]
+    pub allow_admin: bool,
+    /// Shorter lifetime than `api.impersonation_ttl_secs`
+    #[serde(default)]
+    pub expires_in_secs: Option<u64>,
+}
+
+/// Impersonate response
+#[derive(serde::Serialize)]
+pub struct ImpersonateResponse {
+    pub token: String,
+    pub user_id: String,
+    pub expires_at: chrono::DateTime<Utc>,
+}
+
+/// Audit record of an impersonation, without its token
+#[derive(serde::Serialize)]
+pub struct ImpersonationRecord {
+    pub admin_id: String,
+    pub user_id: String,
+    pub reason: Option<String>,
+    pub created_at: chrono::DateTime<Utc>,
+    pub expires_at: chrono::DateTime<Utc>,
+    /// Scopes the token is restricted to; `None` means unrestricted
+    pub scopes: Option<Vec<String>>,
+    /// Whether the token can still be used
+    pub active: bool,
+}
+
+/// Join response
+#[derive(Debug, serde::Serialize)]
 pub struct JoinResponse {
     pub user: User,
     pub token: String,
 }
 
+/// First-run setup request
+#[derive(Deserialize)]
+pub struct SetupRequest {
+    pub username: String,
+    pub password: String,
+    #[serde(default = "default_setup_label")]
+    pub label: String,
+}
+
+fn default_setup_label() -> String {
+    "setup".to_string()
+}
+
 /// Change password request
 #[derive(Deserialize)]
 pub struct ChangePasswordRequest {
@@ -80,6 +198,28 @@ pub struct TokenInfo {
 #[derive(serde::Serialize)]
 pub struct ListTokensResponse {
     pub tokens: Vec<TokenInfo>,
+    pub total: usize,
+    pub limit: usize,
+    pub offset: usize,
+}
+
+/// A user account, without its password hash
+#[derive(serde::Serialize)]
+pub struct UserSummary {
+    pub id: String,
+    pub username: String,
+    pub role: String,
+    pub created_at: chrono::DateTime<Utc>,
+    pub updated_at: chrono::DateTime<Utc>,
+}
+
+/// List users response
+#[derive(serde::Serialize)]
+pub struct ListUsersResponse {
+    pub users: Vec<UserSummary>,
+    pub total: usize,
+    pub limit: usize,
+    pub offset: usize,
 }
 
 // ===== Device Linking Endpoints =====
@@ -92,6 +232,11 @@ pub async fn join_invite<S: Storage + 'static>(
     State(router): State<Arc<Router<S>>>,
     Json(req): Json<JoinRequest>,
 ) -> Result<Json<ApiResponse<JoinResponse>>, ApiError> {
+    if let Some(code) = &req.invite_code {
+        let response = redeem_invite(&router, code, &req).await?;
+        return Ok(Json(ApiResponse::success(response)));
+    }
+
     // Get user by username
     let user = router
         .get_storage()
@@ -115,23 +260,432 @@ pub async fn join_invite<S: Storage + 'static>(
         ));
     }
 
-    // Generate new API token
-    let token = format!("sk-rustyclaw-{}", uuid::Uuid::new_v4());
-    let identity = crate::storage::Identity {
-        provider: "api_token".to_string(),
-        provider_id: token.clone(),
-        user_id: user.id.clone(),
-        label: Some(req.label.clone()),
+    let token = issue_token(router.get_storage(), &user.id, &req.label, None).await?;
+
+    Ok(Json(ApiResponse::success(JoinResponse { user, token })))
+}
+
+/// Create the account an invite was issued for, using up the invite
+async fn redeem_invite<S: Storage + 'static>(
+    router: &Router<S>,
+    code: &str,
+    req: &JoinRequest,
+) -> Result<JoinResponse, ApiError> {
+    let storage = router.get_storage();
+    let username = req.username.trim();
+    if username.is_empty() {
+        return Err(ApiError::BadRequest("Username cannot be empty".to_string()));
+    }
+    if req.password.len() < 8 {
+        return Err(ApiError::BadRequest(
+            "Password must be at least 8 characters".to_string(),
+        ));
+    }
+    if storage.get_user_by_username(username).await?.is_some() {
+        return Err(ApiError::Conflict("Username is already taken".to_string()));
+    }
+
+    // Only invites are redeemable here, not channel linking codes
+    let is_invite = storage
+        .get_pending_link(code)
+        .await?
+        .is_some_and(|link| link.provider == INVITE_PROVIDER);
+    let invite = match is_invite {
+        true => storage.use_pending_link(code).await?,
+        false => None,
+    }
+    .ok_or_else(|| ApiError::Forbidden("Invite is invalid, expired or already used".to_string()))?;
+
+    let user = User {
+        id: uuid::Uuid::new_v4().to_string(),
+        username: username.to_string(),
+        role: invite.role.unwrap_or_else(default_invite_role),
         created_at: Utc::now(),
-        last_used_at: None,
+        updated_at: Utc::now(),
+        password_hash: Some(crate::core::password::hash_password(&req.password)?),
     };
+    // The username may have been taken since it was checked; the invite
+    // then stays usable
+    if let Err(e) = storage.create_user(user.clone()).await {
+        storage.release_pending_link(code).await?;
+        return Err(match storage.get_user_by_username(username).await? {
+            Some(_) => ApiError::Conflict("Username is already taken".to_string()),
+            None => e.into(),
+        });
+    }
+    let token = issue_token(storage, &user.id, &req.label, invite.scopes).await?;
+
+    tracing::info!("✅ Account {} created from invite", user.username);
+    Ok(JoinResponse { user, token })
+}
+
+/// Generate and store a new API token for a user
+async fn issue_token<S: Storage>(
+    storage: &S,
+    user_id: &str,
+    label: &str,
+    scopes: Option<Vec<String>>,
+) -> Result<String, ApiError> {
+    let token = format!("sk-rustyclaw-{}", uuid::Uuid::new_v4());
+    storage
+        .create_identity(crate::storage::Identity {
+            provider: "api_token".to_string(),
+            provider_id: token.clone(),
+            user_id: user_id.to_string(),
+            label: Some(label.to_string()),
+            created_at: Utc::now(),
+            last_used_at: None,
+            scopes,
+        })
+        .await?;
 
+    Ok(token)
+}
+
+// ===== Single Sign-On Endpoints =====
+
+/// Query of the OIDC provider's redirect back to the gateway
+#[derive(Deserialize)]
+pub struct OidcCallbackQuery {
+    pub code: String,
+    pub state: String,
+}
+
+async fn oidc_config<S: Storage + 'static>(router: &Router<S>) -> Result<OidcConfig, ApiError> {
     router
-        .get_storage()
-        .create_identity(identity)
+        .config()
+        .read()
         .await
-        .map_err(|e| ApiError::InternalError(e.to_string()))?;
+        .api
+        .oidc
+        .clone()
+        .ok_or_else(|| ApiError::NotFound("OIDC login is not configured".to_string()))
+}
+
+fn oidc_error(e: OidcError) -> ApiError {
+    match e {
+        OidcError::Provider(_) => {
+            tracing::error!("OIDC login failed: {}", e);
+            ApiError::ServiceUnavailable("OIDC provider request failed".to_string())
+        }
+        _ => ApiError::Unauthorized(e.to_string()),
+    }
+}
+
+/// GET /api/auth/oidc/login - Redirect to the OIDC provider to sign in
+pub async fn oidc_login<S: Storage + 'static>(
+    State(router): State<Arc<Router<S>>>,
+) -> Result<Redirect, ApiError> {
+    let config = oidc_config(&router).await?;
+    let url = crate::core::oidc::login_url(&config)
+        .await
+        .map_err(oidc_error)?;
+    Ok(Redirect::to(&url))
+}
+
+/// GET /api/auth/oidc/callback - Finish an OIDC login with a new API token
+pub async fn oidc_callback<S: Storage + 'static>(
+    State(router): State<Arc<Router<S>>>,
+    Query(query): Query<OidcCallbackQuery>,
+) -> Result<Json<ApiResponse<JoinResponse>>, ApiError> {
+    let config = oidc_config(&router).await?;
+    let claims = crate::core::oidc::complete_login(&config, &query.code, &query.state)
+        .await
+        .map_err(oidc_error)?;
+    let storage = router.get_storage();
+    let user = oidc_user(storage, &config, &claims).await?;
+    let token = issue_token(storage, &user.id, OIDC_PROVIDER, None).await?;
 
+    tracing::info!("🔑 {} signed in through OIDC", user.username);
+    Ok(Json(ApiResponse::success(JoinResponse { user, token })))
+}
+
+/// The account of an OIDC login: the one linked to its subject, else the
+/// one named after its verified email with `link_by_email`, else a new one
+/// with `auto_provision`. Without `link_by_email`, an account of the same
+/// name is only reached through an identity an admin linked.
+async fn oidc_user<S: Storage>(
+    storage: &S,
+    config: &OidcConfig,
+    claims: &IdClaims,
+) -> Result<User, ApiError> {
+    let subject = format!("{}|{}", config.issuer, claims.sub);
+    if let Some(identity) = storage.get_identity(OIDC_PROVIDER, &subject).await? {
+        return storage.get_user(&identity.user_id).await?.ok_or_else(|| {
+            ApiError::Forbidden("The account of this login no longer exists".to_string())
+        });
+    }
+
+    let existing = match claims.verified_email() {
+        Some(email) => storage.get_user_by_username(email).await?,
+        None => None,
+    };
+    let user = match existing {
+        Some(user) if config.link_by_email => user,
+        Some(_) => {
+            return Err(ApiError::Forbidden(
+                "This login is not linked to its account, ask an admin to link it".to_string(),
+            ))
+        }
+        None if config.auto_provision => {
+            let username = claims
+                .verified_email()
+                .or(claims.preferred_username.as_deref())
+                .unwrap_or(&claims.sub);
+            if storage.get_user_by_username(username).await?.is_some() {
+                return Err(ApiError::Conflict("Username is already taken".to_string()));
+            }
+            let user = User {
+                id: uuid::Uuid::new_v4().to_string(),
+                username: username.to_string(),
+                role: config.default_role.clone(),
+                created_at: Utc::now(),
+                updated_at: Utc::now(),
+                password_hash: None,
+            };
+            storage.create_user(user.clone()).await?;
+            tracing::info!("✅ Account {} created from OIDC login", user.username);
+            user
+        }
+        None => {
+            return Err(ApiError::Forbidden(
+                "No account for this login, ask an admin for an invite".to_string(),
+            ))
+        }
+    };
+
+    storage
+        .create_identity(crate::storage::Identity {
+            provider: OIDC_PROVIDER.to_string(),
+            provider_id: subject,
+            user_id: user.id.clone(),
+            label: claims.email.clone(),
+            created_at: Utc::now(),
+            last_used_at: None,
+            scopes: None,
+        })
+        .await?;
+    Ok(user)
+}
+
+/// POST /api/auth/invite - Create a single-use invite for a new account
+pub async fn create_invite<S: Storage + 'static>(
+    State(router): State<Arc<Router<S>>>,
+    Extension(auth): Extension<AuthManager<S>>,
+    headers: HeaderMap,
+    Json(req): Json<CreateInviteRequest>,
+) -> Result<Json<ApiResponse<InviteResponse>>, ApiError> {
+    let token = auth.validate_token(&headers).await?;
+    let admin_id = auth.require_admin(&token).await?;
+    if admin_id == crate::api::auth::OPERATOR_USER_ID {
+        return Err(ApiError::BadRequest(
+            "Create an admin account before issuing invites".to_string(),
+        ));
+    }
+
+    if !matches!(req.role.as_str(), "admin" | "user") {
+        return Err(ApiError::BadRequest(format!(
+            "Unknown role '{}', expected 'admin' or 'user'",
+            req.role
+        )));
+    }
+
+    let ttl_secs = match req.expires_in_secs {
+        Some(secs) => secs,
+        None => router.config().read().await.api.invite_ttl_secs,
+    };
+    let expires_at = i64::try_from(ttl_secs)
+        .ok()
+        .and_then(chrono::Duration::try_seconds)
+        .and_then(|ttl| Utc::now().checked_add_signed(ttl))
+        .ok_or_else(|| ApiError::BadRequest("Invite lifetime is too long".to_string()))?;
+    let code = format!("inv-{}", uuid::Uuid::new_v4().simple());
+
+    router
+        .get_storage()
+        .create_pending_link(PendingLink {
+            code: code.clone(),
+            user_id: admin_id,
+            provider: INVITE_PROVIDER.to_string(),
+            role: Some(req.role.clone()),
+            scopes: req.scopes.clone(),
+            expires_at,
+            used: false,
+        })
+        .await?;
+
+    Ok(Json(ApiResponse::success(InviteResponse {
+        code,
+        role: req.role,
+        scopes: req.scopes,
+        expires_at,
+    })))
+}
+
+/// POST /api/admin/impersonate/:user_id - Short-lived token acting as a user
+///
+/// Lets support staff see a user's sessions and context. The grant is kept
+/// in the audit trail and requests made with the token are logged as
+/// impersonated. Restricted tokens need the `admin:impersonate` scope, and
+/// the impersonation token gets no more scopes than the one issuing it.
+/// Acting as another admin needs `allow_admin`.
+pub async fn impersonate_user<S: Storage + 'static>(
+    State(router): State<Arc<Router<S>>>,
+    Extension(auth): Extension<AuthManager<S>>,
+    Extension(scopes): Extension<TokenScopes>,
+    headers: HeaderMap,
+    Path(user_id): Path<String>,
+    req: Option<Json<ImpersonateRequest>>,
+) -> Result<Json<ApiResponse<ImpersonateResponse>>, ApiError> {
+    let token = auth.validate_token(&headers).await?;
+    let admin_id = auth.require_admin(&token).await?;
+    scopes.require(crate::api::auth::IMPERSONATE_SCOPE)?;
+    let Json(req) = req.unwrap_or_default();
+
+    let storage = router.get_storage();
+    let user = storage
+        .get_user(&user_id)
+        .await?
+        .ok_or_else(|| ApiError::NotFound(format!("User {} not found", user_id)))?;
+    if user.id == admin_id {
+        return Err(ApiError::BadRequest(
+            "Admins cannot impersonate themselves".to_string(),
+        ));
+    }
+    if user.role == "admin" && !req.allow_admin {
+        return Err(ApiError::Forbidden(
+            "Impersonating another admin requires allow_admin".to_string(),
+        ));
+    }
+
+    let max_ttl = router.config().read().await.api.impersonation_ttl_secs;
+    let ttl_secs = req.expires_in_secs.unwrap_or(max_ttl).min(max_ttl);
+    let expires_at = i64::try_from(ttl_secs)
+        .ok()
+        .and_then(chrono::Duration::try_seconds)
+        .and_then(|ttl| Utc::now().checked_add_signed(ttl))
+        .ok_or_else(|| ApiError::BadRequest("Impersonation lifetime is too long".to_string()))?;
+    let impersonation = crate::storage::Impersonation {
+        token: format!(
+            "{}{}",
+            crate::api::auth::IMPERSONATION_TOKEN_PREFIX,
+            uuid::Uuid::new_v4().simple()
+        ),
+        admin_id,
+        user_id: user.id,
+        reason: req.reason,
+        created_at: Utc::now(),
+        expires_at,
+        scopes: scopes.0,
+    };
+    storage.create_impersonation(&impersonation).await?;
+
+    tracing::warn!(
+        "Admin {} is impersonating user {} until {} (reason: {})",
+        impersonation.admin_id,
+        impersonation.user_id,
+        expires_at,
+        impersonation.reason.as_deref().unwrap_or("none given")
+    );
+    Ok(Json(ApiResponse::success(ImpersonateResponse {
+        token: impersonation.token,
+        user_id: impersonation.user_id,
+        expires_at,
+    })))
+}
+
+/// GET /api/admin/impersonations - Audit trail of impersonations, newest first
+pub async fn list_impersonations<S: Storage + 'static>(
+    State(router): State<Arc<Router<S>>>,
+    Query(params): Query<MessageQuery>,
+) -> Result<Json<ApiResponse<Vec<ImpersonationRecord>>>, ApiError> {
+    let limit = params.page().limit.unwrap_or_default();
+    let now = Utc::now();
+    let records = router
+        .get_storage()
+        .list_impersonations(limit)
+        .await?
+        .into_iter()
+        .map(|impersonation| ImpersonationRecord {
+            admin_id: impersonation.admin_id,
+            user_id: impersonation.user_id,
+            reason: impersonation.reason,
+            created_at: impersonation.created_at,
+            expires_at: impersonation.expires_at,
+            scopes: impersonation.scopes,
+            active: impersonation.expires_at > now,
+        })
+        .collect();
+    Ok(Json(ApiResponse::success(records)))
+}
+
+/// GET /api/admin/connections - Open WebSocket and SSE connections
+pub async fn list_connections() -> Json<ApiResponse<Vec<ConnectionInfo>>> {
+    Json(ApiResponse::success(connections::list()))
+}
+
+/// DELETE /api/admin/connections/:id - Close an open connection
+pub async fn close_connection(Path(id): Path<String>) -> Result<StatusCode, ApiError> {
+    if !connections::close(&id) {
+        return Err(ApiError::NotFound(format!("Connection {} not found", id)));
+    }
+    tracing::info!("Admin closed connection {}", id);
+    Ok(StatusCode::NO_CONTENT)
+}
+
+/// DELETE /api/admin/costs/:user_id - Reset what a user and their sessions
+/// have spent, lifting their cost limits
+pub async fn reset_user_costs<S: Storage + 'static>(
+    State(router): State<Arc<Router<S>>>,
+    Path(user_id): Path<String>,
+) -> Json<ApiResponse<serde_json::Value>> {
+    let spent = router.costs().reset_user(&user_id);
+    tracing::info!("Admin reset the costs of {} (spent {:.4})", user_id, spent);
+    Json(ApiResponse::success(serde_json::json!({
+        "user_id": user_id,
+        "spent": spent,
+    })))
+}
+
+/// Serializes setup so two concurrent requests cannot both create an admin
+static SETUP_LOCK: tokio::sync::Mutex<()> = tokio::sync::Mutex::const_new(());
+
+/// POST /api/setup - Create the first admin account
+///
+/// Only allowed while no users exist; returns an API token for the new admin
+pub async fn setup_admin<S: Storage + 'static>(
+    State(router): State<Arc<Router<S>>>,
+    Json(req): Json<SetupRequest>,
+) -> Result<Json<ApiResponse<JoinResponse>>, ApiError> {
+    let _guard = SETUP_LOCK.lock().await;
+    let storage = router.get_storage();
+
+    if storage.user_count().await? > 0 {
+        return Err(ApiError::Forbidden(
+            "Setup has already been completed".to_string(),
+        ));
+    }
+    if req.username.trim().is_empty() {
+        return Err(ApiError::BadRequest("Username cannot be empty".to_string()));
+    }
+    if req.password.len() < 8 {
+        return Err(ApiError::BadRequest(
+            "Password must be at least 8 characters".to_string(),
+        ));
+    }
+
+    let user = User {
+        id: uuid::Uuid::new_v4().to_string(),
+        username: req.username.trim().to_string(),
+        role: "admin".to_string(),
+        created_at: Utc::now(),
+        updated_at: Utc::now(),
+        password_hash: Some(crate::core::password::hash_password(&req.password)?),
+    };
+    storage.create_user(user.clone()).await?;
+    let token = issue_token(storage, &user.id, &req.label, None).await?;
+
+    tracing::info!("✅ Admin account created through setup: {}", user.username);
     Ok(Json(ApiResponse::success(JoinResponse { user, token })))
 }
 
@@ -140,7 +694,7 @@ pub async fn join_invite<S: Storage + 'static>(
 /// POST /api/auth/change-password - Change user password
 pub async fn change_password<S: Storage + 'static>(
     State(router): State<Arc<Router<S>>>,
-    Extension(user_id): Extension<String>,
+    Extension(AuthUserId(user_id)): Extension<AuthUserId>,
     Json(req): Json<ChangePasswordRequest>,
 ) -> Result<Json<ApiResponse<ChangePasswordResponse>>, ApiError> {
     // Validate new password
@@ -194,17 +748,19 @@ pub async fn change_password<S: Storage + 'static>(
 /// GET /api/auth/tokens - List user's API tokens
 pub async fn list_tokens<S: Storage + 'static>(
     State(router): State<Arc<Router<S>>>,
-    Extension(user_id): Extension<String>,
+    Extension(AuthUserId(user_id)): Extension<AuthUserId>,
+    Query(params): Query<MessageQuery>,
 ) -> Result<Json<ApiResponse<ListTokensResponse>>, ApiError> {
+    let page = params.page();
     let identities = router
         .get_storage()
-        .list_identities(&user_id)
+        .list_identities(&user_id, Some("api_token"), page)
         .await
         .map_err(|e| ApiError::InternalError(e.to_string()))?;
 
     let tokens = identities
+        .items
         .into_iter()
-        .filter(|id| id.provider == "api_token")
         .map(|id| TokenInfo {
             provider_id: id.provider_id,
             label: id.label,
@@ -213,13 +769,18 @@ pub async fn list_tokens<S: Storage + 'static>(
         })
         .collect();
 
-    Ok(Json(ApiResponse::success(ListTokensResponse { tokens })))
+    Ok(Json(ApiResponse::success(ListTokensResponse {
+        tokens,
+        total: identities.total,
+        limit: page.limit.unwrap_or_default(),
+        offset: page.offset,
+    })))
 }
 
 /// DELETE /api/auth/tokens/:token_id - Revoke an API token
 pub async fn revoke_token<S: Storage + 'static>(
     State(router): State<Arc<Router<S>>>,
-    Extension(user_id): Extension<String>,
+    Extension(AuthUserId(user_id)): Extension<AuthUserId>,
     Path(token_id): Path<String>,
 ) -> Result<StatusCode, ApiError> {
     // Verify the token belongs to the user
@@ -243,6 +804,11 @@ pub async fn revoke_token<S: Storage + 'static>(
         .await
         .map_err(|e| ApiError::InternalError(e.to_string()))?;
 
+    let closed = connections::close_token(&token_id);
+    if closed > 0 {
+        tracing::info!("Closed {} connection(s) of a revoked token", closed);
+    }
+
     Ok(StatusCode::NO_CONTENT)
 }
 
@@ -251,7 +817,7 @@ pub async fn revoke_token<S: Storage + 'static>(
 /// POST /api/sessions - Create a new session
 pub async fn create_session<S: Storage + 'static>(
     State(router): State<Arc<Router<S>>>,
-    Extension(user_id): Extension<String>,
+    Extension(AuthUserId(user_id)): Extension<AuthUserId>,
     Json(_req): Json<CreateSessionRequest>,
 ) -> Result<(StatusCode, Json<ApiResponse<SessionResponse>>), ApiError> {
     let session = router
@@ -289,7 +855,7 @@ pub async fn create_session<S: Storage + 'static>(
 /// GET /api/sessions - List user's sessions
 pub async fn list_sessions<S: Storage + 'static>(
     State(router): State<Arc<Router<S>>>,
-    Extension(user_id): Extension<String>,
+    Extension(AuthUserId(user_id)): Extension<AuthUserId>,
 ) -> Result<Json<ApiResponse<SessionListResponse>>, ApiError> {
     // For now, return a single session per user (per-sender scope)
     let session = router
@@ -334,7 +900,7 @@ pub async fn list_sessions<S: Storage + 'static>(
 /// GET /api/sessions/:id - Get session details
 pub async fn get_session<S: Storage + 'static>(
     State(router): State<Arc<Router<S>>>,
-    Extension(user_id): Extension<String>,
+    Extension(AuthUserId(user_id)): Extension<AuthUserId>,
     Path(_session_id): Path<String>,
 ) -> Result<Json<ApiResponse<SessionResponse>>, ApiError> {
     let session = router
@@ -369,10 +935,255 @@ pub async fn get_session<S: Storage + 'static>(
     Ok(Json(ApiResponse::success(response)))
 }
 
+/// Tool tags enabled for a session
+#[derive(Deserialize)]
+pub struct SessionTagsRequest {
+    pub tags: Vec<String>,
+}
+
+/// PUT /api/sessions/:id/tags - Choose the tool tags offered in a session
+pub async fn set_session_tags<S: Storage + 'static>(
+    State(router): State<Arc<Router<S>>>,
+    Extension(AuthUserId(user_id)): Extension<AuthUserId>,
+    Path(session_id): Path<String>,
+    Json(req): Json<SessionTagsRequest>,
+) -> Result<Json<ApiResponse<serde_json::Value>>, ApiError> {
+    match router.get_storage().get_session(&session_id).await {
+        Ok(Some(session)) if session.user_id == user_id => {}
+        Ok(_) => return Err(ApiError::NotFound("Session not found".to_string())),
+        Err(e) => {
+            tracing::error!("Failed to get session: {}", e);
+            return Err(ApiError::InternalError("Failed to get session".to_string()));
+        }
+    }
+
+    router
+        .set_session_tool_tags(&session_id, req.tags.clone())
+        .await;
+
+    Ok(Json(ApiResponse::success(
+        serde_json::json!({ "session_id": session_id, "tags": req.tags }),
+    )))
+}
+
+/// Message a session is forked at
+#[derive(Deserialize)]
+pub struct ForkSessionQuery {
+    pub from_message: String,
+}
+
+/// POST /api/sessions/:id/fork?from_message=<id> - Copy a session's history
+/// up to and including a message into a new session
+pub async fn fork_session<S: Storage + 'static>(
+    State(router): State<Arc<Router<S>>>,
+    Extension(AuthUserId(user_id)): Extension<AuthUserId>,
+    Path(session_id): Path<String>,
+    Query(query): Query<ForkSessionQuery>,
+) -> Result<(StatusCode, Json<ApiResponse<SessionResponse>>), ApiError> {
+    let parent = match router.get_storage().get_session(&session_id).await {
+        Ok(Some(session)) if session.user_id == user_id => session,
+        Ok(_) => return Err(ApiError::NotFound("Session not found".to_string())),
+        Err(e) => {
+            tracing::error!("Failed to get session: {}", e);
+            return Err(ApiError::InternalError("Failed to get session".to_string()));
+        }
+    };
+
+    let fork = router
+        .fork_session(&parent, &query.from_message)
+        .await
+        .map_err(|e| {
+            tracing::error!("Failed to fork session: {}", e);
+            ApiError::InternalError("Failed to fork session".to_string())
+        })?
+        .ok_or_else(|| ApiError::NotFound("Message not found in session".to_string()))?;
+
+    let messages = router
+        .get_storage()
+        .get_messages(&fork.id, None)
+        .await
+        .map_err(|e| {
+            tracing::error!("Failed to get messages: {}", e);
+            ApiError::InternalError("Failed to get messages".to_string())
+        })?;
+
+    let response = SessionResponse {
+        id: fork.id,
+        user_id: fork.user_id,
+        channel: fork.channel,
+        scope: fork.scope,
+        created_at: fork.created_at,
+        updated_at: fork.updated_at,
+        message_count: messages.len(),
+        tokens_used: messages.iter().filter_map(|m| m.tokens).sum(),
+        context_window: 128000,
+        status: "active".to_string(),
+    };
+
+    Ok((StatusCode::CREATED, Json(ApiResponse::success(response))))
+}
+
+/// Longest note that can be pinned to a session
+const MAX_NOTE_CHARS: usize = 2000;
+/// Most notes a session can hold
+const MAX_SESSION_NOTES: usize = 20;
+
+#[derive(Debug, Deserialize)]
+pub struct CreateNoteRequest {
+    pub content: String,
+}
+
+/// Session of `session_id`, if it belongs to `user_id`
+pub(crate) async fn owned_session<S: Storage + 'static>(
+    router: &Router<S>,
+    user_id: &str,
+    session_id: &str,
+) -> Result<crate::storage::Session, ApiError> {
+    match router.get_storage().get_session(session_id).await {
+        Ok(Some(session)) if session.user_id == user_id => Ok(session),
+        Ok(_) => Err(ApiError::NotFound("Session not found".to_string())),
+        Err(e) => {
+            tracing::error!("Failed to get session: {}", e);
+            Err(ApiError::InternalError("Failed to get session".to_string()))
+        }
+    }
+}
+
+/// GET /api/sessions/:id/context - The exact messages and tools the model
+/// would get for the session's next turn, with token estimates
+pub async fn get_session_context<S: Storage + 'static>(
+    State(router): State<Arc<Router<S>>>,
+    Extension(AuthUserId(user_id)): Extension<AuthUserId>,
+    Path(session_id): Path<String>,
+) -> Result<Json<ApiResponse<ContextPreview>>, ApiError> {
+    let session = owned_session(&router, &user_id, &session_id).await?;
+
+    let preview = router
+        .preview_session_context(&session)
+        .await
+        .map_err(|e| {
+            tracing::error!("Failed to build session context: {}", e);
+            ApiError::InternalError("Failed to build session context".to_string())
+        })?;
+
+    Ok(Json(ApiResponse::success(preview)))
+}
+
+/// GET /api/sessions/:id/summary - A short summary of the session's
+/// conversation, regenerated when new messages have arrived
+pub async fn get_session_summary<S: Storage + 'static>(
+    State(router): State<Arc<Router<S>>>,
+    Extension(AuthUserId(user_id)): Extension<AuthUserId>,
+    Path(session_id): Path<String>,
+) -> Result<Json<ApiResponse<SessionSummary>>, ApiError> {
+    owned_session(&router, &user_id, &session_id).await?;
+
+    let summary = router
+        .summarize_session(&session_id)
+        .await
+        .map_err(|e| {
+            tracing::error!("Failed to summarize session: {}", e);
+            ApiError::InternalError("Failed to summarize session".to_string())
+        })?
+        .ok_or_else(|| ApiError::NotFound("Session has no messages to summarize".to_string()))?;
+
+    Ok(Json(ApiResponse::success(summary)))
+}
+
+/// GET /api/sessions/:id/notes - Notes pinned to a session, oldest first
+pub async fn list_session_notes<S: Storage + 'static>(
+    State(router): State<Arc<Router<S>>>,
+    Extension(AuthUserId(user_id)): Extension<AuthUserId>,
+    Path(session_id): Path<String>,
+) -> Result<Json<ApiResponse<Vec<SessionNote>>>, ApiError> {
+    owned_session(&router, &user_id, &session_id).await?;
+
+    let notes = router
+        .get_storage()
+        .list_session_notes(&session_id)
+        .await
+        .map_err(|e| {
+            tracing::error!("Failed to list session notes: {}", e);
+            ApiError::InternalError("Failed to list session notes".to_string())
+        })?;
+
+    Ok(Json(ApiResponse::success(notes)))
+}
+
+/// POST /api/sessions/:id/notes - Pin a note to a session's system prompt
+pub async fn create_session_note<S: Storage + 'static>(
+    State(router): State<Arc<Router<S>>>,
+    Extension(AuthUserId(user_id)): Extension<AuthUserId>,
+    Path(session_id): Path<String>,
+    Json(request): Json<CreateNoteRequest>,
+) -> Result<(StatusCode, Json<ApiResponse<SessionNote>>), ApiError> {
+    let content = request.content.trim();
+    if content.is_empty() {
+        return Err(ApiError::BadRequest("Note content is required".to_string()));
+    }
+    if content.chars().count() > MAX_NOTE_CHARS {
+        return Err(ApiError::BadRequest(format!(
+            "Notes are limited to {} characters",
+            MAX_NOTE_CHARS
+        )));
+    }
+
+    owned_session(&router, &user_id, &session_id).await?;
+
+    let storage = router.get_storage();
+    let existing = storage.list_session_notes(&session_id).await.map_err(|e| {
+        tracing::error!("Failed to list session notes: {}", e);
+        ApiError::InternalError("Failed to list session notes".to_string())
+    })?;
+    if existing.len() >= MAX_SESSION_NOTES {
+        return Err(ApiError::BadRequest(format!(
+            "A session can hold at most {} notes",
+            MAX_SESSION_NOTES
+        )));
+    }
+
+    let note = SessionNote {
+        id: uuid::Uuid::new_v4().to_string(),
+        session_id,
+        content: content.to_string(),
+        created_at: Utc::now(),
+    };
+    storage.add_session_note(note.clone()).await.map_err(|e| {
+        tracing::error!("Failed to add session note: {}", e);
+        ApiError::InternalError("Failed to add session note".to_string())
+    })?;
+
+    Ok((StatusCode::CREATED, Json(ApiResponse::success(note))))
+}
+
+/// DELETE /api/sessions/:id/notes/:note_id - Unpin a note
+pub async fn delete_session_note<S: Storage + 'static>(
+    State(router): State<Arc<Router<S>>>,
+    Extension(AuthUserId(user_id)): Extension<AuthUserId>,
+    Path((session_id, note_id)): Path<(String, String)>,
+) -> Result<StatusCode, ApiError> {
+    owned_session(&router, &user_id, &session_id).await?;
+
+    let deleted = router
+        .get_storage()
+        .delete_session_note(&session_id, &note_id)
+        .await
+        .map_err(|e| {
+            tracing::error!("Failed to delete session note: {}", e);
+            ApiError::InternalError("Failed to delete session note".to_string())
+        })?;
+
+    if deleted {
+        Ok(StatusCode::NO_CONTENT)
+    } else {
+        Err(ApiError::NotFound("Note not found".to_string()))
+    }
+}
+
 /// DELETE /api/sessions/:id - Delete session
 pub async fn delete_session<S: Storage + 'static>(
     State(router): State<Arc<Router<S>>>,
-    Extension(user_id): Extension<String>,
+    Extension(AuthUserId(user_id)): Extension<AuthUserId>,
     Path(_session_id): Path<String>,
 ) -> Result<StatusCode, ApiError> {
     router.clear_session(&user_id, "web").await.map_err(|e| {
@@ -386,9 +1197,14 @@ pub async fn delete_session<S: Storage + 'static>(
 // ===== Chat Endpoints =====
 
 /// POST /api/chat - Send message and get response (supports streaming)
+///
+/// A streaming request repeated with a `Last-Event-ID` header resumes the
+/// earlier stream instead of sending the message again.
 pub async fn chat<S: Storage + 'static>(
     State(router): State<Arc<Router<S>>>,
-    Extension(user_id): Extension<String>,
+    Extension(AuthUserId(user_id)): Extension<AuthUserId>,
+    Extension(auth): Extension<AuthManager<S>>,
+    headers: HeaderMap,
     Json(req): Json<ChatRequest>,
 ) -> Result<axum::response::Response, ApiError> {
     // Validate input
@@ -396,31 +1212,45 @@ pub async fn chat<S: Storage + 'static>(
         return Err(ApiError::BadRequest("message cannot be empty".to_string()));
     }
 
-    if req.message.len() > 10000 {
-        return Err(ApiError::BadRequest(
-            "message too long (max 10000 chars)".to_string(),
-        ));
+    if req.message.len() > MAX_MESSAGE_CHARS {
+        return Err(ApiError::BadRequest(format!(
+            "message too long (max {} chars)",
+            MAX_MESSAGE_CHARS
+        )));
     }
 
+    validate_chat_context(&req.context)?;
+
     // Handle streaming request
     if req.stream {
-        return chat_stream_sse(router, user_id, req).await;
+        // Listed under the token's user, as the WebSocket handler does
+        let token = bearer_token(&headers);
+        let owner = auth.validate_token_str(token).await?;
+        let connection = connections::register(ConnectionKind::Sse, &owner, None, token);
+        if let Some(last_event_id) = headers
+            .get("last-event-id")
+            .and_then(|value| value.to_str().ok())
+        {
+            return resume_chat_stream(&user_id, last_event_id, connection);
+        }
+        return chat_stream_sse(router, user_id, req, connection).await;
     }
 
     let start = Instant::now();
 
     // Non-streaming path: process message through router
     let response = router
-        .handle_message(&user_id, "web", &req.message)
+        .handle_message_with_context(
+            &user_id,
+            "web",
+            &req.message,
+            &req.context,
+            &req.turn_options(),
+        )
         .await
         .map_err(|e| {
-            tracing::error!("Failed to handle message: {}", e);
-            match e.downcast_ref::<std::string::String>() {
-                Some(msg) if msg.contains("unavailable") => {
-                    ApiError::ServiceUnavailable("LLM service unavailable".to_string())
-                }
-                _ => ApiError::InternalError("Failed to process message".to_string()),
-            }
+            tracing::error!("Failed to handle message: {:#}", e);
+            ApiError::from_processing_error(&e)
         })?;
 
     let latency_ms = start.elapsed().as_millis() as u64;
@@ -444,87 +1274,382 @@ pub async fn chat<S: Storage + 'static>(
             text: req.message,
             tokens: 0, // TODO: Calculate token count
             model: None,
+            fallback_from: None,
+            seed: None,
+            system_fingerprint: None,
+            seed_note: None,
         },
         response: ChatContent {
             text: response.content.clone(),
             tokens: response.tokens.unwrap_or(0),
             model: Some(response.model),
+            fallback_from: response.fallback_from,
+            seed: req.seed,
+            seed_note: seed_note(req.seed, response.system_fingerprint.as_deref()),
+            system_fingerprint: response.system_fingerprint,
         },
+        budget: response.budget,
         latency_ms,
     };
 
-    Ok((StatusCode::OK, Json(ApiResponse::success(chat_response))).into_response())
+    Ok((StatusCode::OK, Json(ApiResponse::success(chat_response))).into_response())
+}
+
+/// Note for a seeded reply the backend did not confirm: backends that honour
+/// `seed` report the `system_fingerprint` it is reproducible under
+fn seed_note(seed: Option<i64>, system_fingerprint: Option<&str>) -> Option<String> {
+    match (seed, system_fingerprint) {
+        (Some(_), None) => Some(
+            "The backend did not report a system fingerprint; it may have ignored the seed"
+                .to_string(),
+        ),
+        _ => None,
+    }
+}
+
+/// Longest prompt accepted by the chat endpoints
+const MAX_MESSAGE_CHARS: usize = 10000;
+
+/// Most context documents accepted with one chat request
+const MAX_CONTEXT_DOCUMENTS: usize = 20;
+
+/// Combined size of the context documents of one chat request
+const MAX_CONTEXT_CHARS: usize = 50000;
+
+/// Check the per-request context documents against the size caps
+fn validate_chat_context(context: &[String]) -> Result<(), ApiError> {
+    if context.len() > MAX_CONTEXT_DOCUMENTS {
+        return Err(ApiError::BadRequest(format!(
+            "too many context documents (max {})",
+            MAX_CONTEXT_DOCUMENTS
+        )));
+    }
+
+    let total: usize = context.iter().map(|document| document.len()).sum();
+    if total > MAX_CONTEXT_CHARS {
+        return Err(ApiError::BadRequest(format!(
+            "context too long (max {} chars)",
+            MAX_CONTEXT_CHARS
+        )));
+    }
+
+    Ok(())
+}
+
+/// POST /api/chat/batch - Answer independent prompts with bounded concurrency
+///
+/// Each prompt runs in its own ephemeral session, so the batch never touches
+/// the caller's conversation history. With `stream` set, every item is sent
+/// as an `item` SSE event as soon as it finishes, followed by `done`.
+pub async fn chat_batch<S: Storage + 'static>(
+    State(router): State<Arc<Router<S>>>,
+    Extension(AuthUserId(user_id)): Extension<AuthUserId>,
+    Json(req): Json<BatchChatRequest>,
+) -> Result<Response, ApiError> {
+    let (max_size, concurrency) = {
+        let config = router.config();
+        let config = config.read().await;
+        (
+            config.api.batch_max_size,
+            config.api.batch_concurrency.max(1),
+        )
+    };
+
+    if req.prompts.is_empty() {
+        return Err(ApiError::BadRequest("prompts cannot be empty".to_string()));
+    }
+    if req.prompts.len() > max_size {
+        return Err(ApiError::BadRequest(format!(
+            "too many prompts (max {} per batch)",
+            max_size
+        )));
+    }
+    if let Some(index) = req
+        .prompts
+        .iter()
+        .position(|p| p.is_empty() || p.len() > MAX_MESSAGE_CHARS)
+    {
+        return Err(ApiError::BadRequest(format!(
+            "prompt {} must be between 1 and {} chars",
+            index, MAX_MESSAGE_CHARS
+        )));
+    }
+
+    let total = req.prompts.len();
+    let items = futures::stream::iter(req.prompts.into_iter().enumerate())
+        .map(move |(index, prompt)| {
+            let router = router.clone();
+            let user_id = user_id.clone();
+            async move { run_batch_item(&router, &user_id, index, prompt).await }
+        })
+        .buffer_unordered(concurrency);
+
+    if req.stream {
+        let events = items
+            .map(|item| {
+                Event::default()
+                    .event("item")
+                    .json_data(&item)
+                    .map_err(|e| e.to_string())
+            })
+            .chain(futures::stream::once(async move {
+                Ok(Event::default()
+                    .event("done")
+                    .data(serde_json::json!({ "total": total }).to_string()))
+            }));
+        return Ok(Sse::new(events).into_response());
+    }
+
+    let mut items: Vec<BatchChatItem> = items.collect().await;
+    items.sort_by_key(|item| item.index);
+    let succeeded = items.iter().filter(|item| item.status == "success").count();
+
+    let response = BatchChatResponse {
+        failed: items.len() - succeeded,
+        succeeded,
+        items,
+    };
+
+    Ok((StatusCode::OK, Json(ApiResponse::success(response))).into_response())
+}
+
+/// Answer one batch prompt, turning failures into an error item
+async fn run_batch_item<S: Storage + 'static>(
+    router: &Router<S>,
+    user_id: &str,
+    index: usize,
+    prompt: String,
+) -> BatchChatItem {
+    let start = Instant::now();
+    let result = router
+        .handle_ephemeral_message(user_id, "web", &prompt)
+        .await;
+    let latency_ms = start.elapsed().as_millis() as u64;
+
+    match result {
+        Ok(response) => BatchChatItem {
+            index,
+            status: "success".to_string(),
+            response: Some(ChatContent {
+                text: response.content,
+                tokens: response.tokens.unwrap_or(0),
+                model: Some(response.model),
+                fallback_from: response.fallback_from,
+                seed: None,
+                system_fingerprint: response.system_fingerprint,
+                seed_note: None,
+            }),
+            error: None,
+            error_code: None,
+            latency_ms,
+        },
+        Err(e) => {
+            tracing::warn!("Batch item {} failed: {:#}", index, e);
+            let error = ApiError::from_processing_error(&e);
+            BatchChatItem {
+                index,
+                status: "error".to_string(),
+                response: None,
+                error: Some(error.message()),
+                error_code: Some(error.error_code()),
+                latency_ms,
+            }
+        }
+    }
 }
 
-/// SSE streaming chat response
+/// SSE streaming chat response. The reply is generated in the background
+/// into a replay buffer, so a client whose connection drops can resume it
+/// with `Last-Event-ID` (see [`resume_chat_stream`]). If no client comes
+/// back within [`RECONNECT_GRACE`], the reply is cancelled.
 async fn chat_stream_sse<S: Storage + 'static>(
     router: Arc<Router<S>>,
     user_id: String,
     req: ChatRequest,
+    connection: ConnectionGuard,
 ) -> Result<Response, ApiError> {
     // Get streaming receiver from router
-    let receiver = router
-        .handle_message_stream(&user_id, "web", &req.message)
+    let mut receiver = router
+        .handle_message_stream_with_context(
+            &user_id,
+            "web",
+            &req.message,
+            &req.context,
+            &req.turn_options(),
+        )
         .await
         .map_err(|e| {
-            tracing::error!("Failed to handle message stream: {}", e);
-            ApiError::InternalError("Failed to process message".to_string())
+            tracing::error!("Failed to handle message stream: {:#}", e);
+            ApiError::from_processing_error(&e)
         })?;
 
-    // Convert receiver to stream
-    let stream = ReceiverStream::new(receiver);
-
-    // Map stream events to SSE events
-    let sse_stream = stream.map(|event| -> Result<Event, String> {
-        match event {
-            StreamEvent::Delta(text) => Ok(Event::default().data(text)),
-            StreamEvent::ToolStart { name, .. } => {
-                Ok(Event::default().event("tool_start").data(name))
-            }
-            StreamEvent::ToolEnd {
-                name,
-                result,
-                execution_time_ms,
-                attempt,
-            } => {
-                let data = serde_json::json!({
-                    "name": name,
-                    "result": result,
-                    "execution_time_ms": execution_time_ms,
-                    "attempt": attempt
-                });
-                Ok(Event::default().event("tool_end").data(data.to_string()))
-            }
-            StreamEvent::Done { model, usage } => {
-                let data = serde_json::json!({
-                    "model": model,
-                    "usage": usage
-                });
-                Ok(Event::default().event("done").data(data.to_string()))
-            }
-            StreamEvent::ApprovalRequested {
-                request_id,
-                tool_name,
-                arguments,
-                policy,
-                sandbox_available,
-            } => {
-                let data = serde_json::json!({
-                    "request_id": request_id,
-                    "tool_name": tool_name,
-                    "arguments": arguments,
-                    "policy": policy,
-                    "sandbox_available": sandbox_available
-                });
-                Ok(Event::default()
-                    .event("approval_requested")
-                    .data(data.to_string()))
+    let buffer = stream_buffers().start(&user_id);
+    let producer = buffer.clone();
+    let response = sse_response(buffer, 0, connection);
+    tokio::spawn(async move {
+        let abandoned = producer.abandoned(RECONNECT_GRACE);
+        tokio::pin!(abandoned);
+        loop {
+            tokio::select! {
+                event = receiver.recv() => match event {
+                    Some(event) => {
+                        let (name, data) = sse_payload(event);
+                        producer.push(name, data);
+                    }
+                    None => break,
+                },
+                _ = &mut abandoned => {
+                    tracing::info!(
+                        "Client left stream {}, cancelling the reply",
+                        producer.message_id()
+                    );
+                    // Dropping the receiver cancels generation and tools
+                    drop(receiver);
+                    let (name, data) = sse_payload(StreamEvent::Error(
+                        "Reply cancelled: the client disconnected".to_string(),
+                    ));
+                    producer.push(name, data);
+                    break;
+                }
             }
-            StreamEvent::Error(msg) => Ok(Event::default().event("error").data(msg)),
         }
+        producer.finish();
+
+        tokio::time::sleep(RESUME_WINDOW).await;
+        stream_buffers().remove(producer.message_id());
     });
 
-    Ok(Sse::new(sse_stream).into_response())
+    Ok(response)
+}
+
+/// Replay a dropped chat stream after the client's last received event
+fn resume_chat_stream(
+    user_id: &str,
+    last_event_id: &str,
+    connection: ConnectionGuard,
+) -> Result<Response, ApiError> {
+    let (buffer, after) = stream_buffers()
+        .resume(user_id, last_event_id)
+        .ok_or_else(|| ApiError::NotFound("Stream not found or expired".to_string()))?;
+    tracing::debug!(
+        "Resuming stream {} after event {}",
+        buffer.message_id(),
+        after
+    );
+    Ok(sse_response(buffer, after, connection))
+}
+
+/// Stream a reply buffer to the client until it ends or the connection is
+/// closed (see [`connections`]), which ends the response like a dropped
+/// client: the reply can still be resumed
+fn sse_response(buffer: Arc<StreamBuffer>, after: u64, connection: ConnectionGuard) -> Response {
+    let message_id = buffer.message_id().to_string();
+    let events = buffer
+        .subscribe(after)
+        .map(move |event| Ok::<_, Infallible>(event.to_sse(&message_id)))
+        .take_until(async move { connection.closed().await });
+    Sse::new(events).into_response()
+}
+
+/// Bearer token of an authenticated request
+fn bearer_token(headers: &HeaderMap) -> &str {
+    headers
+        .get("authorization")
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.strip_prefix("Bearer "))
+        .unwrap_or_default()
+}
+
+/// SSE event name and data of a stream event
+fn sse_payload(event: StreamEvent) -> (Option<&'static str>, String) {
+    match event {
+        StreamEvent::Delta(text) => (None, text),
+        StreamEvent::ToolArgsDelta {
+            index,
+            name,
+            partial_args,
+        } => {
+            let data = serde_json::json!({
+                "index": index,
+                "name": name,
+                "partial_args": partial_args
+            });
+            (Some("tool_args_delta"), data.to_string())
+        }
+        StreamEvent::ToolStart { name, .. } => (Some("tool_start"), name),
+        StreamEvent::ToolEnd {
+            name,
+            result,
+            execution_time_ms,
+            attempt,
+            status,
+        } => {
+            let data = serde_json::json!({
+                "name": name,
+                "result": result,
+                "execution_time_ms": execution_time_ms,
+                "attempt": attempt,
+                "status": status.unwrap_or_else(|| "done".to_string())
+            });
+            (Some("tool_end"), data.to_string())
+        }
+        StreamEvent::Done {
+            model,
+            usage,
+            tps,
+            time_to_first_token_ms,
+            budget,
+            content,
+        } => {
+            let data = serde_json::json!({
+                "model": model,
+                "usage": usage,
+                "tps": tps,
+                "time_to_first_token_ms": time_to_first_token_ms,
+                "budget": budget,
+                "content": content
+            });
+            (Some("done"), data.to_string())
+        }
+        StreamEvent::ApprovalRequested {
+            request_id,
+            tool_name,
+            arguments,
+            policy,
+            sandbox_available,
+        } => {
+            let data = serde_json::json!({
+                "request_id": request_id,
+                "tool_name": tool_name,
+                "arguments": arguments,
+                "policy": policy,
+                "sandbox_available": sandbox_available
+            });
+            (Some("approval_requested"), data.to_string())
+        }
+        StreamEvent::UserInputRequested { prompt, request_id } => {
+            let data = serde_json::json!({
+                "request_id": request_id,
+                "prompt": prompt
+            });
+            (Some("user_input_requested"), data.to_string())
+        }
+        StreamEvent::Retrying {
+            attempt,
+            max_attempts,
+            delay_ms,
+            reason,
+        } => {
+            let data = serde_json::json!({
+                "attempt": attempt,
+                "max_attempts": max_attempts,
+                "delay_ms": delay_ms,
+                "reason": reason
+            });
+            (Some("retrying"), data.to_string())
+        }
+        StreamEvent::Error(msg) => (Some("error"), msg),
+    }
 }
 
 // ===== Message Endpoints =====
@@ -532,7 +1657,7 @@ async fn chat_stream_sse<S: Storage + 'static>(
 /// GET /api/messages - Get conversation history
 pub async fn list_messages<S: Storage + 'static>(
     State(router): State<Arc<Router<S>>>,
-    Extension(user_id): Extension<String>,
+    Extension(AuthUserId(user_id)): Extension<AuthUserId>,
     Query(params): Query<MessageQuery>,
 ) -> Result<Json<ApiResponse<MessageListResponse>>, ApiError> {
     let session = router
@@ -569,7 +1694,7 @@ pub async fn list_messages<S: Storage + 'static>(
             timestamp: msg.created_at,
             tokens: msg.tokens,
             model_used: msg.model_used,
-            metadata: None,
+            metadata: msg.metadata,
         })
         .collect();
 
@@ -587,7 +1712,7 @@ pub async fn list_messages<S: Storage + 'static>(
 /// GET /api/messages/:id - Get single message
 pub async fn get_message<S: Storage + 'static>(
     State(router): State<Arc<Router<S>>>,
-    Extension(user_id): Extension<String>,
+    Extension(AuthUserId(user_id)): Extension<AuthUserId>,
     Path(_message_id): Path<String>,
 ) -> Result<Json<ApiResponse<MessageResponse>>, ApiError> {
     let session = router
@@ -621,18 +1746,192 @@ pub async fn get_message<S: Storage + 'static>(
         timestamp: msg.created_at,
         tokens: msg.tokens,
         model_used: msg.model_used.clone(),
-        metadata: None,
+        metadata: msg.metadata.clone(),
     };
 
     Ok(Json(ApiResponse::success(response)))
 }
 
+/// Longest comment accepted with a rating
+const MAX_FEEDBACK_COMMENT_CHARS: usize = 2000;
+
+#[derive(Debug, Deserialize)]
+pub struct FeedbackRequest {
+    pub rating: FeedbackRating,
+    #[serde(default)]
+    pub comment: Option<String>,
+}
+
+/// POST /api/messages/:id/feedback - Rate an assistant reply
+///
+/// Rating the same reply again replaces the earlier rating.
+pub async fn rate_message<S: Storage + 'static>(
+    State(router): State<Arc<Router<S>>>,
+    Extension(AuthUserId(user_id)): Extension<AuthUserId>,
+    Path(message_id): Path<String>,
+    Json(req): Json<FeedbackRequest>,
+) -> Result<Json<ApiResponse<MessageFeedback>>, ApiError> {
+    let comment = req
+        .comment
+        .map(|comment| comment.trim().to_string())
+        .filter(|comment| !comment.is_empty());
+    if comment
+        .as_ref()
+        .is_some_and(|comment| comment.chars().count() > MAX_FEEDBACK_COMMENT_CHARS)
+    {
+        return Err(ApiError::BadRequest(format!(
+            "Comments are limited to {} characters",
+            MAX_FEEDBACK_COMMENT_CHARS
+        )));
+    }
+
+    let storage = router.get_storage();
+    let message = storage
+        .get_message(&message_id)
+        .await
+        .map_err(|e| {
+            tracing::error!("Failed to get message: {}", e);
+            ApiError::InternalError("Failed to get message".to_string())
+        })?
+        .ok_or_else(|| ApiError::NotFound("Message not found".to_string()))?;
+
+    // Users only rate replies in their own sessions
+    owned_session(&router, &user_id, &message.session_id)
+        .await
+        .map_err(|e| match e {
+            ApiError::NotFound(_) => ApiError::NotFound("Message not found".to_string()),
+            other => other,
+        })?;
+    if message.role != "assistant" {
+        return Err(ApiError::BadRequest(
+            "Only assistant replies can be rated".to_string(),
+        ));
+    }
+
+    let feedback = MessageFeedback {
+        message_id: message.id,
+        session_id: message.session_id,
+        user_id,
+        rating: req.rating,
+        comment,
+        model: message.model_used,
+        created_at: Utc::now(),
+    };
+    storage
+        .set_message_feedback(feedback.clone())
+        .await
+        .map_err(|e| {
+            tracing::error!("Failed to store feedback: {}", e);
+            ApiError::InternalError("Failed to store feedback".to_string())
+        })?;
+
+    Ok(Json(ApiResponse::success(feedback)))
+}
+
+/// GET /api/feedback/summary - Rating counts overall and per model (admin)
+pub async fn feedback_summary<S: Storage + 'static>(
+    State(router): State<Arc<Router<S>>>,
+) -> Result<Json<ApiResponse<FeedbackSummary>>, ApiError> {
+    let summary = router.get_storage().feedback_summary().await?;
+    Ok(Json(ApiResponse::success(summary)))
+}
+
+#[derive(Debug, Default, Deserialize)]
+pub struct PauseChannelQuery {
+    /// Keep the channel paused across restarts
+    #[serde(default)]
+    pub persist: bool,
+}
+
+/// Name of a channel that can be paused
+fn inbound_channel(name: &str) -> Result<&str, ApiError> {
+    if crate::channels::INBOUND_CHANNELS.contains(&name) {
+        Ok(name)
+    } else {
+        Err(ApiError::NotFound(format!(
+            "Unknown channel '{}'. Known channels: {}",
+            name,
+            crate::channels::INBOUND_CHANNELS.join(", ")
+        )))
+    }
+}
+
+/// POST /api/channels/:name/pause - Stop answering messages on a channel (admin)
+pub async fn pause_channel<S: Storage + 'static>(
+    State(router): State<Arc<Router<S>>>,
+    Path(name): Path<String>,
+    Query(query): Query<PauseChannelQuery>,
+) -> Result<Json<ApiResponse<serde_json::Value>>, ApiError> {
+    let channel = inbound_channel(&name)?;
+    router.pause_channel(channel, query.persist).await?;
+    Ok(Json(ApiResponse::success(serde_json::json!({
+        "channel": channel,
+        "paused": true,
+        "persisted": query.persist,
+    }))))
+}
+
+/// POST /api/channels/:name/resume - Answer messages on a channel again (admin)
+pub async fn resume_channel<S: Storage + 'static>(
+    State(router): State<Arc<Router<S>>>,
+    Path(name): Path<String>,
+) -> Result<Json<ApiResponse<serde_json::Value>>, ApiError> {
+    let channel = inbound_channel(&name)?;
+    router.resume_channel(channel).await?;
+    Ok(Json(ApiResponse::success(serde_json::json!({
+        "channel": channel,
+        "paused": false,
+    }))))
+}
+
+/// POST /api/maintenance - Serve reads only, refusing new messages, tool
+/// calls and writes until cleared, also across restarts (admin)
+pub async fn start_maintenance<S: Storage + 'static>(
+    State(router): State<Arc<Router<S>>>,
+) -> Result<Json<ApiResponse<serde_json::Value>>, ApiError> {
+    router.set_maintenance(true).await?;
+    Ok(Json(ApiResponse::success(serde_json::json!({
+        "maintenance": true,
+    }))))
+}
+
+/// DELETE /api/maintenance - Leave maintenance mode (admin)
+pub async fn end_maintenance<S: Storage + 'static>(
+    State(router): State<Arc<Router<S>>>,
+) -> Result<Json<ApiResponse<serde_json::Value>>, ApiError> {
+    router.set_maintenance(false).await?;
+    Ok(Json(ApiResponse::success(serde_json::json!({
+        "maintenance": false,
+    }))))
+}
+
+/// GET /ready - Whether the gateway can answer messages right now
+///
+/// Answers 503 while the LLM circuit breaker is open, so load balancers
+/// stop routing chats to an instance whose backend is down.
+pub async fn readiness<S: Storage + 'static>(
+    State(router): State<Arc<Router<S>>>,
+) -> (StatusCode, Json<ReadinessResponse>) {
+    let llm_circuit = router.llm_client().circuit_state();
+    let (status, label) = match llm_circuit {
+        CircuitState::Open => (StatusCode::SERVICE_UNAVAILABLE, "unavailable"),
+        CircuitState::Closed | CircuitState::HalfOpen => (StatusCode::OK, "ready"),
+    };
+    (
+        status,
+        Json(ReadinessResponse {
+            status: label.to_string(),
+            llm_circuit,
+        }),
+    )
+}
+
 // ===== Models Endpoints =====
 
 /// GET /api/models - List available models
 pub async fn list_models<S: Storage + 'static>(
     State(_router): State<Arc<Router<S>>>,
-    Extension(_user_id): Extension<String>,
+    Extension(AuthUserId(_user_id)): Extension<AuthUserId>,
 ) -> Result<Json<ApiResponse<ModelsResponse>>, ApiError> {
     // Return hardcoded models (in production, get from LLM client)
     let models = vec![
@@ -662,7 +1961,7 @@ pub async fn list_models<S: Storage + 'static>(
 /// POST /api/models/:name/load - Load model
 pub async fn load_model<S: Storage + 'static>(
     State(_router): State<Arc<Router<S>>>,
-    Extension(_user_id): Extension<String>,
+    Extension(AuthUserId(_user_id)): Extension<AuthUserId>,
     Path(name): Path<String>,
 ) -> Result<Json<ApiResponse<serde_json::Value>>, ApiError> {
     // In production, call LLM client to load model
@@ -714,7 +2013,7 @@ pub struct ToolListResponse {
 /// POST /api/tools - Create a new tool
 pub async fn create_tool<S: Storage + 'static>(
     State(_router): State<Arc<Router<S>>>,
-    Extension(_user_id): Extension<String>,
+    Extension(AuthUserId(_user_id)): Extension<AuthUserId>,
     Json(req): Json<CreateToolRequest>,
 ) -> Result<(StatusCode, Json<ApiResponse<ToolResponse>>), ApiError> {
     let name = req.name.clone();
@@ -744,10 +2043,115 @@ pub async fn create_tool<S: Storage + 'static>(
     Ok((StatusCode::CREATED, Json(ApiResponse::success(response))))
 }
 
+/// Largest skill file accepted by the upload endpoint
+const MAX_SKILL_FILE_BYTES: usize = 256 * 1024;
+
+/// POST /api/tools/upload - Install a complete skill file (multipart field
+/// `file`); an existing skill of the same name is only replaced when the
+/// `overwrite` field is `true`
+pub async fn upload_tool<S: Storage + 'static>(
+    State(router): State<Arc<Router<S>>>,
+    Extension(scopes): Extension<TokenScopes>,
+    mut multipart: Multipart,
+) -> Result<
+    (
+        StatusCode,
+        Json<ApiResponse<crate::tools::skills::SkillManifest>>,
+    ),
+    ApiError,
+> {
+    scopes.require("tools:write")?;
+
+    let mut content = None;
+    let mut overwrite = false;
+    while let Some(mut field) = multipart
+        .next_field()
+        .await
+        .map_err(|e| ApiError::BadRequest(format!("Invalid multipart body: {}", e)))?
+    {
+        if field.name() == Some("file") {
+            // Read chunk by chunk so an oversized upload is rejected before it is buffered
+            let mut bytes = Vec::new();
+            while let Some(chunk) = field
+                .chunk()
+                .await
+                .map_err(|e| ApiError::BadRequest(format!("Failed to read skill file: {}", e)))?
+            {
+                if bytes.len() + chunk.len() > MAX_SKILL_FILE_BYTES {
+                    return Err(ApiError::BadRequest(format!(
+                        "Skill file too large (max {} KB)",
+                        MAX_SKILL_FILE_BYTES / 1024
+                    )));
+                }
+                bytes.extend_from_slice(&chunk);
+            }
+            content = Some(bytes);
+        } else if field.name() == Some("overwrite") {
+            let value = field
+                .text()
+                .await
+                .map_err(|e| ApiError::BadRequest(format!("Invalid overwrite field: {}", e)))?;
+            overwrite = value.trim().eq_ignore_ascii_case("true");
+        }
+    }
+
+    let content = content
+        .ok_or_else(|| ApiError::BadRequest("Missing multipart field 'file'".to_string()))?;
+
+    let user_tools_dir = router.config().read().await.tools.user_tools_dir.clone();
+    let entry = crate::tools::creator::install_skill_file(
+        &content,
+        std::path::Path::new(&user_tools_dir),
+        overwrite,
+    )
+    .await
+    .map_err(|e| {
+        tracing::error!("Tool upload failed: {:#}", e);
+        ApiError::BadRequest(format!("{:#}", e))
+    })?;
+
+    tracing::info!(
+        "Uploaded tool '{}' to {}",
+        entry.manifest.name,
+        entry.source_path.display()
+    );
+
+    Ok((
+        StatusCode::CREATED,
+        Json(ApiResponse::success(entry.manifest)),
+    ))
+}
+
+/// GET /api/tools/tags - Tool tags in use, with the tools carrying each
+pub async fn list_tool_tags<S: Storage + 'static>(
+    State(router): State<Arc<Router<S>>>,
+    Extension(AuthUserId(_user_id)): Extension<AuthUserId>,
+) -> Result<Json<ApiResponse<serde_json::Value>>, ApiError> {
+    let mut by_tag: std::collections::BTreeMap<String, Vec<String>> = Default::default();
+    for (tool, tags) in crate::core::tool_tags().await {
+        for tag in tags {
+            by_tag.entry(tag).or_default().push(tool.clone());
+        }
+    }
+
+    let tags: Vec<_> = by_tag
+        .into_iter()
+        .map(|(tag, mut tools)| {
+            tools.sort();
+            serde_json::json!({ "tag": tag, "tools": tools })
+        })
+        .collect();
+    let default = router.config().read().await.tools.default_tags.clone();
+
+    Ok(Json(ApiResponse::success(
+        serde_json::json!({ "tags": tags, "default": default }),
+    )))
+}
+
 /// GET /api/tools - List all tools
 pub async fn list_tools<S: Storage + 'static>(
     State(_router): State<Arc<Router<S>>>,
-    Extension(_user_id): Extension<String>,
+    Extension(AuthUserId(_user_id)): Extension<AuthUserId>,
 ) -> Result<Json<ApiResponse<ToolListResponse>>, ApiError> {
     let all_skills = list_skills().await;
 
@@ -779,7 +2183,7 @@ pub async fn list_tools<S: Storage + 'static>(
 /// GET /api/tools/:name - Get tool details
 pub async fn get_tool<S: Storage + 'static>(
     State(_router): State<Arc<Router<S>>>,
-    Extension(_user_id): Extension<String>,
+    Extension(AuthUserId(_user_id)): Extension<AuthUserId>,
     Path(name): Path<String>,
 ) -> Result<Json<ApiResponse<serde_json::Value>>, ApiError> {
     let skill = get_skill(&name)
@@ -799,6 +2203,7 @@ pub async fn get_tool<S: Storage + 'static>(
         "timeout_secs": skill.manifest.timeout_secs,
         "path": skill.source_path.to_string_lossy(),
         "ready": true,
+        "last_error": crate::tools::skills::get_load_error(&skill.source_path).await,
     });
 
     Ok(Json(ApiResponse::success(response)))
@@ -807,7 +2212,7 @@ pub async fn get_tool<S: Storage + 'static>(
 /// DELETE /api/tools/:name - Delete a tool
 pub async fn delete_tool<S: Storage + 'static>(
     State(_router): State<Arc<Router<S>>>,
-    Extension(_user_id): Extension<String>,
+    Extension(AuthUserId(_user_id)): Extension<AuthUserId>,
     Path(name): Path<String>,
 ) -> Result<Json<ApiResponse<serde_json::Value>>, ApiError> {
     let skill = get_skill(&name)
@@ -836,7 +2241,7 @@ pub async fn delete_tool<S: Storage + 'static>(
 /// POST /api/tools/:name/validate - Validate tool syntax
 pub async fn validate_tool<S: Storage + 'static>(
     State(_router): State<Arc<Router<S>>>,
-    Extension(_user_id): Extension<String>,
+    Extension(AuthUserId(_user_id)): Extension<AuthUserId>,
     Path(name): Path<String>,
 ) -> Result<Json<ApiResponse<serde_json::Value>>, ApiError> {
     let skill = get_skill(&name)
@@ -857,7 +2262,7 @@ pub async fn validate_tool<S: Storage + 'static>(
 /// PUT /api/tools/:name - Update tool
 pub async fn update_tool<S: Storage + 'static>(
     State(_router): State<Arc<Router<S>>>,
-    Extension(_user_id): Extension<String>,
+    Extension(AuthUserId(_user_id)): Extension<AuthUserId>,
     Path(name): Path<String>,
     Json(req): Json<CreateToolRequest>,
 ) -> Result<Json<ApiResponse<ToolResponse>>, ApiError> {
@@ -951,7 +2356,7 @@ pub struct ToolTestResponse {
 
 pub async fn test_tool<S: Storage + 'static>(
     State(_router): State<Arc<Router<S>>>,
-    Extension(_user_id): Extension<String>,
+    Extension(AuthUserId(_user_id)): Extension<AuthUserId>,
     Path(name): Path<String>,
     Json(_req): Json<ToolTestRequest>,
 ) -> Result<Json<ApiResponse<ToolTestResponse>>, ApiError> {
@@ -978,10 +2383,74 @@ pub async fn test_tool<S: Storage + 'static>(
     Ok(Json(ApiResponse::success(response)))
 }
 
+/// Rollback request
+#[derive(Deserialize)]
+pub struct RollbackToolRequest {
+    pub version: u32,
+}
+
+/// GET /api/tools/:name/versions - List stored versions of a tool
+pub async fn list_tool_versions<S: Storage + 'static>(
+    State(_router): State<Arc<Router<S>>>,
+    Extension(AuthUserId(_user_id)): Extension<AuthUserId>,
+    Path(name): Path<String>,
+) -> Result<Json<ApiResponse<serde_json::Value>>, ApiError> {
+    let versions = crate::tools::skills::get_skill_versions(&name)
+        .await
+        .map_err(|e| ApiError::ServiceUnavailable(e.to_string()))?;
+
+    if versions.is_empty() {
+        return Err(ApiError::NotFound(format!(
+            "No versions found for tool '{}'",
+            name
+        )));
+    }
+
+    let response = serde_json::json!({
+        "name": name,
+        "versions": versions,
+    });
+
+    Ok(Json(ApiResponse::success(response)))
+}
+
+/// POST /api/tools/:name/rollback - Restore a previous version of a tool
+pub async fn rollback_tool<S: Storage + 'static>(
+    State(_router): State<Arc<Router<S>>>,
+    Extension(AuthUserId(_user_id)): Extension<AuthUserId>,
+    Path(name): Path<String>,
+    Json(req): Json<RollbackToolRequest>,
+) -> Result<Json<ApiResponse<serde_json::Value>>, ApiError> {
+    let entry = crate::tools::skills::rollback_skill(&name, req.version)
+        .await
+        .map_err(|e| ApiError::BadRequest(e.to_string()))?;
+
+    let response = serde_json::json!({
+        "message": format!("Tool '{}' rolled back to version {}", name, req.version),
+        "name": entry.manifest.name,
+        "version": req.version,
+        "path": entry.source_path.to_string_lossy(),
+    });
+
+    tracing::info!("Tool rolled back: {} -> v{}", name, req.version);
+    Ok(Json(ApiResponse::success(response)))
+}
+
+/// POST /api/tools/reload - Rescan the skills directory from scratch
+pub async fn reload_tools<S: Storage + 'static>(
+    State(router): State<Arc<Router<S>>>,
+) -> Result<Json<ApiResponse<SkillReloadReport>>, ApiError> {
+    let skills_dir = router.config().read().await.tools.skills_dir.clone();
+    let report = crate::tools::skills::reload_skills_dir(std::path::Path::new(&skills_dir))
+        .await
+        .map_err(|e| ApiError::InternalError(e.to_string()))?;
+    Ok(Json(ApiResponse::success(report)))
+}
+
 /// GET /api/tools/:name/definition - Get tool definition in OpenAI format
 pub async fn get_tool_definition<S: Storage + 'static>(
     State(_router): State<Arc<Router<S>>>,
-    Extension(_user_id): Extension<String>,
+    Extension(AuthUserId(_user_id)): Extension<AuthUserId>,
     Path(name): Path<String>,
 ) -> Result<Json<ApiResponse<serde_json::Value>>, ApiError> {
     let skill = get_skill(&name)
@@ -1003,9 +2472,14 @@ pub async fn get_tool_definition<S: Storage + 'static>(
 /// GET /api/tools/definitions/all - Get all tool definitions for LLM
 pub async fn get_all_tool_definitions<S: Storage + 'static>(
     State(_router): State<Arc<Router<S>>>,
-    Extension(_user_id): Extension<String>,
+    Extension(AuthUserId(_user_id)): Extension<AuthUserId>,
 ) -> Result<Json<ApiResponse<Vec<serde_json::Value>>>, ApiError> {
-    let all_skills = list_skills().await;
+    // Skills run code, which safe mode turns off
+    let all_skills = if crate::tools::executor::safe_mode() {
+        Vec::new()
+    } else {
+        list_skills().await
+    };
 
     let definitions: Vec<serde_json::Value> = all_skills
         .into_iter()
@@ -1024,8 +2498,251 @@ pub async fn get_all_tool_definitions<S: Storage + 'static>(
     Ok(Json(ApiResponse::success(definitions)))
 }
 
+/// Query parameters for the prompt preview
+#[derive(Deserialize)]
+pub struct PromptPreviewQuery {
+    #[serde(default)]
+    pub channel: Option<String>,
+}
+
+/// GET /api/prompt/preview - Show the assembled system prompt for the caller,
+/// and the welcome/help text they would see
+pub async fn preview_prompt<S: Storage + 'static>(
+    State(router): State<Arc<Router<S>>>,
+    Extension(AuthUserId(user_id)): Extension<AuthUserId>,
+    Query(query): Query<PromptPreviewQuery>,
+) -> Result<Json<ApiResponse<serde_json::Value>>, ApiError> {
+    let channel = query.channel.unwrap_or_else(|| "web".to_string());
+    let prompt = router.preview_system_prompt(&user_id, &channel).await;
+    let help = router.help_text(&user_id, &channel).await;
+
+    let response = serde_json::json!({
+        "channel": channel,
+        "estimated_tokens": crate::core::prompt::estimate_tokens(&prompt),
+        "prompt": prompt,
+        "help": help,
+    });
+
+    Ok(Json(ApiResponse::success(response)))
+}
+
+/// Default and maximum number of executions returned by the tool logs endpoint
+const DEFAULT_TOOL_LOG_LIMIT: usize = 20;
+const MAX_TOOL_LOG_LIMIT: usize = 100;
+
+/// Query parameters for tool execution logs
+#[derive(Deserialize)]
+pub struct ToolLogsQuery {
+    #[serde(default)]
+    pub limit: Option<usize>,
+}
+
+/// GET /api/tools/:name/logs - Recent executions of a tool in the caller's sessions
+pub async fn get_tool_logs<S: Storage + 'static>(
+    State(router): State<Arc<Router<S>>>,
+    Extension(AuthUserId(user_id)): Extension<AuthUserId>,
+    Path(name): Path<String>,
+    Query(query): Query<ToolLogsQuery>,
+) -> Result<Json<ApiResponse<serde_json::Value>>, ApiError> {
+    let limit = query
+        .limit
+        .unwrap_or(DEFAULT_TOOL_LOG_LIMIT)
+        .clamp(1, MAX_TOOL_LOG_LIMIT);
+
+    let executions = router
+        .get_storage()
+        .list_tool_executions(&user_id, &name, limit)
+        .await
+        .map_err(|e| {
+            tracing::error!("Failed to load tool executions: {}", e);
+            ApiError::InternalError("Failed to load tool executions".to_string())
+        })?;
+
+    let response = serde_json::json!({
+        "name": name,
+        "executions": executions,
+    });
+
+    Ok(Json(ApiResponse::success(response)))
+}
+
+/// GET /api/approvals/pending - Unanswered tool approval requests in the caller's sessions
+pub async fn list_pending_approvals<S: Storage + 'static>(
+    State(router): State<Arc<Router<S>>>,
+    Extension(AuthUserId(user_id)): Extension<AuthUserId>,
+) -> Result<Json<ApiResponse<serde_json::Value>>, ApiError> {
+    let approval_manager = router.get_approval_manager().map_err(|e| {
+        tracing::error!("Failed to access approval manager: {}", e);
+        ApiError::InternalError("Failed to load pending approvals".to_string())
+    })?;
+
+    let mut approvals = Vec::new();
+    for approval in approval_manager.list_pending().await {
+        let owned = matches!(
+            router.get_storage().get_session(&approval.session_id).await,
+            Ok(Some(session)) if session.user_id == user_id
+        );
+        if owned {
+            approvals.push(serde_json::json!({
+                "request_id": approval.request_id,
+                "session_id": approval.session_id,
+                "tool": approval.tool_name,
+                "arguments": approval.arguments,
+                "policy": approval.policy,
+                "sandbox_available": approval.sandbox_available,
+                "created_at": approval.created_at,
+                "expires_at": approval.expires_at,
+            }));
+        }
+    }
+
+    Ok(Json(ApiResponse::success(
+        serde_json::json!({ "approvals": approvals }),
+    )))
+}
+
+/// Answer to a question the model asked mid-turn
+#[derive(Deserialize)]
+pub struct UserInputAnswer {
+    pub answer: String,
+}
+
+/// POST /api/input/:request_id - Answer a question the model asked in one of
+/// the caller's sessions
+pub async fn answer_user_input<S: Storage + 'static>(
+    State(router): State<Arc<Router<S>>>,
+    Extension(AuthUserId(user_id)): Extension<AuthUserId>,
+    Path(request_id): Path<String>,
+    Json(body): Json<UserInputAnswer>,
+) -> Result<Json<ApiResponse<serde_json::Value>>, ApiError> {
+    let inputs = crate::core::user_input::user_inputs();
+    let not_found = || ApiError::NotFound(format!("No pending question '{}'", request_id));
+    let request = inputs.get(&request_id).ok_or_else(not_found)?;
+    let owned = matches!(
+        router.get_storage().get_session(&request.session_id).await,
+        Ok(Some(session)) if session.user_id == user_id
+    );
+    if !owned || !inputs.answer(&request_id, &body.answer) {
+        return Err(not_found());
+    }
+
+    Ok(Json(ApiResponse::success(serde_json::json!({
+        "request_id": request_id,
+        "answered": true,
+    }))))
+}
+
 // Helper function to get tool storage path removed as it is now in crate::tools::creator
 
+// ===== User Policy Endpoints =====
+
+/// Replace a user's tool policy overrides
+#[derive(Deserialize)]
+pub struct UserPoliciesRequest {
+    /// Tool name -> "allow", "deny" or "elevated"; an empty map clears them
+    pub policies: HashMap<String, String>,
+}
+
+/// A user's tool policy overrides and the policies in effect for them
+#[derive(serde::Serialize)]
+pub struct UserPoliciesResponse {
+    pub user_id: String,
+    pub overrides: HashMap<String, ToolAccessLevel>,
+    pub effective: HashMap<String, ToolAccessLevel>,
+}
+
+fn policy_engine() -> Result<Arc<crate::tools::policy::ToolPolicyEngine>, ApiError> {
+    crate::get_tool_policy_engine().ok_or_else(|| {
+        ApiError::ServiceUnavailable("Tool policy engine not initialized".to_string())
+    })
+}
+
+async fn user_policies_response(
+    engine: &crate::tools::policy::ToolPolicyEngine,
+    user_id: String,
+) -> UserPoliciesResponse {
+    UserPoliciesResponse {
+        overrides: engine.get_user_policies(&user_id).await,
+        effective: engine.get_effective_policies(&user_id).await,
+        user_id,
+    }
+}
+
+/// GET /api/users - List user accounts, optionally filtered by username or role
+pub async fn list_users<S: Storage + 'static>(
+    State(router): State<Arc<Router<S>>>,
+    Query(params): Query<UserListQuery>,
+) -> Result<Json<ApiResponse<ListUsersResponse>>, ApiError> {
+    let page = MessageQuery {
+        limit: params.limit,
+        offset: params.offset,
+    }
+    .page();
+    let filter = UserFilter {
+        username: params.username.filter(|s| !s.is_empty()),
+        role: params.role.filter(|s| !s.is_empty()),
+    };
+    let users = router.get_storage().list_users(&filter, page).await?;
+
+    Ok(Json(ApiResponse::success(ListUsersResponse {
+        users: users
+            .items
+            .into_iter()
+            .map(|user| UserSummary {
+                id: user.id,
+                username: user.username,
+                role: user.role,
+                created_at: user.created_at,
+                updated_at: user.updated_at,
+            })
+            .collect(),
+        total: users.total,
+        limit: page.limit.unwrap_or_default(),
+        offset: page.offset,
+    })))
+}
+
+/// GET /api/users/:id/policies - Tool policies in effect for a user
+pub async fn get_user_policies(
+    Path(user_id): Path<String>,
+) -> Result<Json<ApiResponse<UserPoliciesResponse>>, ApiError> {
+    let engine = policy_engine()?;
+    Ok(Json(ApiResponse::success(
+        user_policies_response(&engine, user_id).await,
+    )))
+}
+
+/// PUT /api/users/:id/policies - Replace a user's tool policy overrides
+///
+/// Overrides are persisted and applied to the running policy engine.
+pub async fn set_user_policies<S: Storage + 'static>(
+    State(router): State<Arc<Router<S>>>,
+    Path(user_id): Path<String>,
+    Json(req): Json<UserPoliciesRequest>,
+) -> Result<Json<ApiResponse<UserPoliciesResponse>>, ApiError> {
+    let engine = policy_engine()?;
+    let overrides = parse_policies(&req.policies).map_err(ApiError::BadRequest)?;
+
+    let normalized: HashMap<String, String> = overrides
+        .iter()
+        .map(|(tool, level)| (tool.clone(), level.as_str().to_string()))
+        .collect();
+    router
+        .get_storage()
+        .set_user_tool_policies(&user_id, &normalized)
+        .await?;
+    engine.set_user_policies(&user_id, overrides).await;
+
+    tracing::info!(
+        "Tool policy overrides for user {} set to {:?}",
+        user_id,
+        normalized
+    );
+    Ok(Json(ApiResponse::success(
+        user_policies_response(&engine, user_id).await,
+    )))
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -1046,4 +2763,14 @@ mod tests {
         let req = CreateSessionRequest { scope: None };
         assert_eq!(req.scope, None);
     }
+
+    #[test]
+    fn test_chat_context_caps() {
+        assert!(validate_chat_context(&[]).is_ok());
+        assert!(validate_chat_context(&vec!["doc".to_string(); MAX_CONTEXT_DOCUMENTS]).is_ok());
+        assert!(
+            validate_chat_context(&vec!["doc".to_string(); MAX_CONTEXT_DOCUMENTS + 1]).is_err()
+        );
+        assert!(validate_chat_context(&["x".repeat(MAX_CONTEXT_CHARS + 1)]).is_err());
+    }
 }
@@ -0,0 +1,208 @@
+//! Management of scheduled prompts (admin only, since replies go out through
+//! the gateway's own bot accounts)
+
+use crate::api::{ApiError, ApiResponse, AuthUserId};
+use crate::channels::DELIVERY_CHANNELS;
+use crate::core::scheduler;
+use crate::core::Router;
+use crate::storage::{Schedule, Storage};
+use axum::extract::{Extension, Path, State};
+use axum::Json;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+
+/// Longest prompt a schedule may run
+const MAX_SCHEDULE_PROMPT_CHARS: usize = 4000;
+
+#[derive(Debug, Deserialize)]
+pub struct CreateScheduleRequest {
+    pub cron: String,
+    pub prompt: String,
+    pub channel: String,
+    pub target: String,
+    #[serde(default)]
+    pub timezone: Option<String>,
+    #[serde(default = "default_enabled")]
+    pub enabled: bool,
+}
+
+fn default_enabled() -> bool {
+    true
+}
+
+/// Fields to change; absent fields are kept
+#[derive(Debug, Default, Deserialize)]
+pub struct UpdateScheduleRequest {
+    pub cron: Option<String>,
+    pub prompt: Option<String>,
+    pub channel: Option<String>,
+    pub target: Option<String>,
+    pub timezone: Option<String>,
+    pub enabled: Option<bool>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct ScheduleResponse {
+    #[serde(flatten)]
+    pub schedule: Schedule,
+    /// Next time the schedule fires, absent while disabled
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub next_run_at: Option<DateTime<Utc>>,
+}
+
+/// GET /api/schedules - Stored schedules of all users
+pub async fn list_schedules<S: Storage + 'static>(
+    State(router): State<Arc<Router<S>>>,
+) -> Result<Json<ApiResponse<Vec<ScheduleResponse>>>, ApiError> {
+    let schedules = router.get_storage().list_schedules(None).await?;
+    let default_timezone = default_timezone(&router).await;
+    Ok(Json(ApiResponse::success(
+        schedules
+            .into_iter()
+            .map(|schedule| schedule_response(schedule, default_timezone.as_deref()))
+            .collect(),
+    )))
+}
+
+/// POST /api/schedules - Schedule a prompt, run as the calling admin
+pub async fn create_schedule<S: Storage + 'static>(
+    State(router): State<Arc<Router<S>>>,
+    Extension(AuthUserId(user_id)): Extension<AuthUserId>,
+    Json(req): Json<CreateScheduleRequest>,
+) -> Result<Json<ApiResponse<ScheduleResponse>>, ApiError> {
+    let schedule = Schedule {
+        id: uuid::Uuid::new_v4().to_string(),
+        user_id,
+        cron: req.cron.trim().to_string(),
+        prompt: req.prompt.trim().to_string(),
+        channel: req.channel,
+        target: req.target.trim().to_string(),
+        timezone: req.timezone.filter(|tz| !tz.trim().is_empty()),
+        enabled: req.enabled,
+        created_at: Utc::now(),
+        last_run_at: None,
+    };
+    validate_schedule(&schedule)?;
+
+    router
+        .get_storage()
+        .create_schedule(schedule.clone())
+        .await?;
+    tracing::info!(
+        "Schedule {} created by {}: '{}' to {}",
+        schedule.id,
+        schedule.user_id,
+        schedule.cron,
+        schedule.channel
+    );
+
+    let default_timezone = default_timezone(&router).await;
+    Ok(Json(ApiResponse::success(schedule_response(
+        schedule,
+        default_timezone.as_deref(),
+    ))))
+}
+
+/// PUT /api/schedules/:id - Change a schedule
+pub async fn update_schedule<S: Storage + 'static>(
+    State(router): State<Arc<Router<S>>>,
+    Path(id): Path<String>,
+    Json(req): Json<UpdateScheduleRequest>,
+) -> Result<Json<ApiResponse<ScheduleResponse>>, ApiError> {
+    let storage = router.get_storage();
+    let mut schedule = storage
+        .get_schedule(&id)
+        .await?
+        .ok_or_else(|| ApiError::NotFound("Schedule not found".to_string()))?;
+
+    if let Some(cron) = req.cron {
+        schedule.cron = cron.trim().to_string();
+    }
+    if let Some(prompt) = req.prompt {
+        schedule.prompt = prompt.trim().to_string();
+    }
+    if let Some(channel) = req.channel {
+        schedule.channel = channel;
+    }
+    if let Some(target) = req.target {
+        schedule.target = target.trim().to_string();
+    }
+    if let Some(timezone) = req.timezone {
+        // An empty string clears the timezone
+        schedule.timezone = Some(timezone).filter(|tz| !tz.trim().is_empty());
+    }
+    if let Some(enabled) = req.enabled {
+        schedule.enabled = enabled;
+    }
+    validate_schedule(&schedule)?;
+
+    if !storage.update_schedule(schedule.clone()).await? {
+        return Err(ApiError::NotFound("Schedule not found".to_string()));
+    }
+
+    let default_timezone = default_timezone(&router).await;
+    Ok(Json(ApiResponse::success(schedule_response(
+        schedule,
+        default_timezone.as_deref(),
+    ))))
+}
+
+/// DELETE /api/schedules/:id - Remove a schedule
+pub async fn delete_schedule<S: Storage + 'static>(
+    State(router): State<Arc<Router<S>>>,
+    Path(id): Path<String>,
+) -> Result<Json<ApiResponse<()>>, ApiError> {
+    if !router.get_storage().delete_schedule(&id).await? {
+        return Err(ApiError::NotFound("Schedule not found".to_string()));
+    }
+    tracing::info!("Schedule {} deleted", id);
+    Ok(Json(ApiResponse::success(())))
+}
+
+fn validate_schedule(schedule: &Schedule) -> Result<(), ApiError> {
+    scheduler::validate(&schedule.cron, schedule.timezone.as_deref())
+        .map_err(|e| ApiError::BadRequest(e.to_string()))?;
+    if !DELIVERY_CHANNELS.contains(&schedule.channel.as_str()) {
+        return Err(ApiError::BadRequest(format!(
+            "Unsupported channel '{}'. Supported: {}",
+            schedule.channel,
+            DELIVERY_CHANNELS.join(", ")
+        )));
+    }
+    if schedule.target.is_empty() {
+        return Err(ApiError::BadRequest("Target is required".to_string()));
+    }
+    if schedule.prompt.is_empty() {
+        return Err(ApiError::BadRequest("Prompt is required".to_string()));
+    }
+    if schedule.prompt.chars().count() > MAX_SCHEDULE_PROMPT_CHARS {
+        return Err(ApiError::BadRequest(format!(
+            "Prompts are limited to {} characters",
+            MAX_SCHEDULE_PROMPT_CHARS
+        )));
+    }
+    Ok(())
+}
+
+async fn default_timezone<S: Storage + 'static>(router: &Router<S>) -> Option<String> {
+    router.config().read().await.locale.timezone.clone()
+}
+
+fn schedule_response(schedule: Schedule, default_timezone: Option<&str>) -> ScheduleResponse {
+    let next_run_at = schedule
+        .enabled
+        .then(|| {
+            let cron = scheduler::parse_cron(&schedule.cron).ok()?;
+            let timezone = match schedule.timezone.as_deref().or(default_timezone) {
+                Some(name) => scheduler::parse_timezone(name).ok()?,
+                None => chrono_tz::UTC,
+            };
+            scheduler::next_run(&cron, timezone, Utc::now())
+        })
+        .flatten();
+    ScheduleResponse {
+        schedule,
+        next_run_at,
+    }
+}
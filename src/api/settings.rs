@@ -0,0 +1,39 @@
+//! The authenticated user's default reply settings: model, temperature,
+//! persona and language. Sessions use them unless they chose otherwise with
+//! `/temp`, `/persona` or `/lang`.
+
+use crate::api::{ApiError, ApiResponse, AuthUserId};
+use crate::core::overrides::normalize_user_settings;
+use crate::core::Router;
+use crate::storage::{Storage, UserSettings};
+use axum::extract::{Extension, State};
+use axum::Json;
+use std::sync::Arc;
+
+/// GET /api/settings - The user's settings (all unset by default)
+pub async fn get_settings<S: Storage + 'static>(
+    State(router): State<Arc<Router<S>>>,
+    Extension(AuthUserId(user_id)): Extension<AuthUserId>,
+) -> Result<Json<ApiResponse<UserSettings>>, ApiError> {
+    let settings = router
+        .get_storage()
+        .get_user_settings(&user_id)
+        .await?
+        .unwrap_or_default();
+    Ok(Json(ApiResponse::success(settings)))
+}
+
+/// PUT /api/settings - Replace the user's settings; a missing or null field
+/// falls back to the configuration
+pub async fn update_settings<S: Storage + 'static>(
+    State(router): State<Arc<Router<S>>>,
+    Extension(AuthUserId(user_id)): Extension<AuthUserId>,
+    Json(settings): Json<UserSettings>,
+) -> Result<Json<ApiResponse<UserSettings>>, ApiError> {
+    let settings = normalize_user_settings(settings).map_err(ApiError::BadRequest)?;
+    router
+        .get_storage()
+        .set_user_settings(&user_id, &settings)
+        .await?;
+    Ok(Json(ApiResponse::success(settings)))
+}
@@ -0,0 +1,154 @@
+//! Tool usage statistics computed from the tool execution audit log (admin
+//! only)
+
+use crate::api::{ApiError, ApiResponse};
+use crate::core::Router;
+use crate::storage::{Storage, ToolCallSample};
+use axum::extract::{Query, State};
+use axum::Json;
+use chrono::{DateTime, Duration, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::sync::Arc;
+
+/// Window covered when none is given: one week
+const DEFAULT_WINDOW_HOURS: u32 = 24 * 7;
+/// Longest window: one year
+const MAX_WINDOW_HOURS: u32 = 24 * 365;
+/// Entries in the top tools and top users lists when no limit is given
+const DEFAULT_TOP: usize = 10;
+const MAX_TOP: usize = 100;
+
+#[derive(Debug, Default, Deserialize)]
+pub struct ToolStatsQuery {
+    /// Only calls made in the last this many hours
+    #[serde(default)]
+    pub window_hours: Option<u32>,
+    /// Entries in `top_tools` and `top_users`
+    #[serde(default)]
+    pub top: Option<usize>,
+}
+
+/// Usage of one tool over the window
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ToolUsage {
+    pub tool_name: String,
+    pub calls: usize,
+    pub successes: usize,
+    /// Share of calls that succeeded, from 0 to 1
+    pub success_rate: f64,
+    /// Median duration of the calls with a recorded duration
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub median_ms: Option<u64>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub p95_ms: Option<u64>,
+}
+
+/// Tool calls made by one user over the window
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct UserUsage {
+    pub user_id: String,
+    pub calls: usize,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ToolStats {
+    pub since: DateTime<Utc>,
+    pub total_calls: usize,
+    /// Every tool called in the window, by name
+    pub tools: Vec<ToolUsage>,
+    /// Most called tool names, most calls first
+    pub top_tools: Vec<String>,
+    /// Users making the most calls, most calls first
+    pub top_users: Vec<UserUsage>,
+}
+
+impl ToolStats {
+    /// Aggregate the calls made since `since`
+    pub fn from_samples(since: DateTime<Utc>, samples: &[ToolCallSample], top: usize) -> Self {
+        let mut by_tool: HashMap<&str, Vec<&ToolCallSample>> = HashMap::new();
+        let mut by_user: HashMap<&str, usize> = HashMap::new();
+        for sample in samples {
+            by_tool
+                .entry(sample.tool_name.as_str())
+                .or_default()
+                .push(sample);
+            *by_user.entry(sample.user_id.as_str()).or_default() += 1;
+        }
+
+        let mut tools: Vec<ToolUsage> = by_tool
+            .into_iter()
+            .map(|(tool_name, calls)| {
+                let successes = calls.iter().filter(|call| call.success).count();
+                let mut durations: Vec<u64> =
+                    calls.iter().filter_map(|call| call.duration_ms).collect();
+                durations.sort_unstable();
+                ToolUsage {
+                    tool_name: tool_name.to_string(),
+                    calls: calls.len(),
+                    successes,
+                    success_rate: successes as f64 / calls.len() as f64,
+                    median_ms: percentile(&durations, 50),
+                    p95_ms: percentile(&durations, 95),
+                }
+            })
+            .collect();
+        tools.sort_by(|a, b| a.tool_name.cmp(&b.tool_name));
+
+        let mut ranked: Vec<&ToolUsage> = tools.iter().collect();
+        ranked.sort_by(|a, b| b.calls.cmp(&a.calls).then(a.tool_name.cmp(&b.tool_name)));
+        let top_tools = ranked
+            .into_iter()
+            .take(top)
+            .map(|usage| usage.tool_name.clone())
+            .collect();
+
+        let mut top_users: Vec<UserUsage> = by_user
+            .into_iter()
+            .map(|(user_id, calls)| UserUsage {
+                user_id: user_id.to_string(),
+                calls,
+            })
+            .collect();
+        top_users.sort_by(|a, b| b.calls.cmp(&a.calls).then(a.user_id.cmp(&b.user_id)));
+        top_users.truncate(top);
+
+        Self {
+            since,
+            total_calls: samples.len(),
+            tools,
+            top_tools,
+            top_users,
+        }
+    }
+}
+
+/// Nearest-rank percentile of sorted values
+fn percentile(sorted: &[u64], percent: usize) -> Option<u64> {
+    if sorted.is_empty() {
+        return None;
+    }
+    let rank = (sorted.len() * percent).div_ceil(100).max(1);
+    Some(sorted[rank - 1])
+}
+
+/// GET /api/stats/tools - Call counts, success rates and durations per tool
+pub async fn tool_stats<S: Storage + 'static>(
+    State(router): State<Arc<Router<S>>>,
+    Query(query): Query<ToolStatsQuery>,
+) -> Result<Json<ApiResponse<ToolStats>>, ApiError> {
+    let window_hours = query.window_hours.unwrap_or(DEFAULT_WINDOW_HOURS);
+    if window_hours == 0 || window_hours > MAX_WINDOW_HOURS {
+        return Err(ApiError::BadRequest(format!(
+            "window_hours must be between 1 and {}",
+            MAX_WINDOW_HOURS
+        )));
+    }
+    let top = query.top.unwrap_or(DEFAULT_TOP).min(MAX_TOP);
+
+    let since = Utc::now() - Duration::hours(window_hours as i64);
+    let samples = router.get_storage().tool_call_samples(since).await?;
+    Ok(Json(ApiResponse::success(ToolStats::from_samples(
+        since, &samples, top,
+    ))))
+}
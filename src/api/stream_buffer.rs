@@ -0,0 +1,283 @@
+//! Replay buffers for SSE chat streams
+//!
+//! Every streamed reply gets a message id and its events are numbered
+//! `<message id>:<seq>`. The events are kept while the reply is generated and
+//! for a short while after, so a client whose connection drops can reconnect
+//! with `Last-Event-ID` and receive the events it missed. A reply nobody
+//! follows any more is given up on after a short grace period (see
+//! [`StreamBuffer::abandoned`]).
+
+use axum::response::sse::Event;
+use futures::stream::{self, Stream};
+use once_cell::sync::Lazy;
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+use tokio::sync::Notify;
+
+static STREAM_BUFFERS: Lazy<StreamBuffers> = Lazy::new(StreamBuffers::new);
+
+/// How long a finished stream can still be resumed
+pub const RESUME_WINDOW: Duration = Duration::from_secs(60);
+
+/// How long a reply keeps generating with no client connected, giving a
+/// dropped client time to reconnect
+pub const RECONNECT_GRACE: Duration = Duration::from_secs(5);
+
+/// Buffers of the in-flight and recently finished streams
+pub fn stream_buffers() -> &'static StreamBuffers {
+    &STREAM_BUFFERS
+}
+
+/// One SSE event of a stream
+#[derive(Debug, Clone, PartialEq)]
+pub struct BufferedEvent {
+    pub seq: u64,
+    pub event: Option<&'static str>,
+    pub data: String,
+}
+
+impl BufferedEvent {
+    pub fn to_sse(&self, message_id: &str) -> Event {
+        let event = Event::default()
+            .id(format!("{}:{}", message_id, self.seq))
+            .data(&self.data);
+        match self.event {
+            Some(name) => event.event(name),
+            None => event,
+        }
+    }
+}
+
+#[derive(Default)]
+struct BufferState {
+    events: Vec<BufferedEvent>,
+    finished: bool,
+    /// Clients currently following the stream
+    subscribers: usize,
+}
+
+/// Counts a client as following a stream until dropped
+struct Subscription(Arc<StreamBuffer>);
+
+impl Subscription {
+    fn new(buffer: Arc<StreamBuffer>) -> Self {
+        buffer.state.lock().unwrap().subscribers += 1;
+        Self(buffer)
+    }
+}
+
+impl Drop for Subscription {
+    fn drop(&mut self) {
+        self.0.state.lock().unwrap().subscribers -= 1;
+        self.0.updated.notify_waiters();
+    }
+}
+
+/// Events of one streamed reply
+pub struct StreamBuffer {
+    message_id: String,
+    user_id: String,
+    state: Mutex<BufferState>,
+    updated: Notify,
+}
+
+impl StreamBuffer {
+    pub fn message_id(&self) -> &str {
+        &self.message_id
+    }
+
+    /// Append an event, numbered from 1
+    pub fn push(&self, event: Option<&'static str>, data: String) {
+        {
+            let mut state = self.state.lock().unwrap();
+            let seq = state.events.len() as u64 + 1;
+            state.events.push(BufferedEvent { seq, event, data });
+        }
+        self.updated.notify_waiters();
+    }
+
+    /// Mark the reply complete, ending every subscriber's stream
+    pub fn finish(&self) {
+        self.state.lock().unwrap().finished = true;
+        self.updated.notify_waiters();
+    }
+
+    /// Events after `after`, followed live until the reply completes. The
+    /// client counts as following the stream until the returned stream is
+    /// dropped.
+    pub fn subscribe(self: &Arc<Self>, after: u64) -> impl Stream<Item = BufferedEvent> {
+        let subscription = Subscription::new(self.clone());
+        stream::unfold((subscription, after), |(subscription, cursor)| async move {
+            let next = subscription.0.next_after(cursor).await?;
+            let seq = next.seq;
+            Some((next, (subscription, seq)))
+        })
+    }
+
+    /// Resolve once no client has followed the stream for `grace`
+    pub async fn abandoned(&self, grace: Duration) {
+        loop {
+            let updated = self.updated.notified();
+            if self.state.lock().unwrap().subscribers > 0 {
+                updated.await;
+                continue;
+            }
+
+            tokio::time::sleep(grace).await;
+            if self.state.lock().unwrap().subscribers == 0 {
+                return;
+            }
+        }
+    }
+
+    /// The event after `cursor`, waiting for it unless the reply is complete
+    async fn next_after(&self, cursor: u64) -> Option<BufferedEvent> {
+        loop {
+            // Register before checking so a push in between still wakes us
+            let updated = self.updated.notified();
+            {
+                let state = self.state.lock().unwrap();
+                if let Some(event) = state.events.get(cursor as usize) {
+                    return Some(event.clone());
+                }
+                if state.finished {
+                    return None;
+                }
+            }
+            updated.await;
+        }
+    }
+}
+
+/// Stream buffers by message id
+#[derive(Default)]
+pub struct StreamBuffers {
+    buffers: Mutex<HashMap<String, Arc<StreamBuffer>>>,
+}
+
+impl StreamBuffers {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Start buffering a new reply for a user
+    pub fn start(&self, user_id: &str) -> Arc<StreamBuffer> {
+        let buffer = Arc::new(StreamBuffer {
+            message_id: uuid::Uuid::new_v4().to_string(),
+            user_id: user_id.to_string(),
+            state: Mutex::new(BufferState::default()),
+            updated: Notify::new(),
+        });
+        self.buffers
+            .lock()
+            .unwrap()
+            .insert(buffer.message_id.clone(), buffer.clone());
+        buffer
+    }
+
+    /// Find the stream a `Last-Event-ID` belongs to, with the sequence
+    /// number to resume after. Other users' streams are never returned.
+    pub fn resume(&self, user_id: &str, last_event_id: &str) -> Option<(Arc<StreamBuffer>, u64)> {
+        let (message_id, seq) = last_event_id.trim().rsplit_once(':')?;
+        let seq = seq.parse().ok()?;
+        let buffer = self.buffers.lock().unwrap().get(message_id).cloned()?;
+        (buffer.user_id == user_id).then_some((buffer, seq))
+    }
+
+    pub fn remove(&self, message_id: &str) {
+        self.buffers.lock().unwrap().remove(message_id);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use futures::StreamExt;
+
+    #[tokio::test]
+    async fn test_resume_replays_events_after_mid_stream_id() {
+        let buffers = StreamBuffers::new();
+        let buffer = buffers.start("user1");
+        for word in ["The", " quick", " brown"] {
+            buffer.push(None, word.to_string());
+        }
+
+        // The client saw the first two events before its connection dropped
+        let last_event_id = format!("{}:2", buffer.message_id());
+        assert!(buffers.resume("user2", &last_event_id).is_none());
+        let (resumed, after) = buffers.resume("user1", &last_event_id).unwrap();
+        assert_eq!(after, 2);
+        let replay = tokio::spawn(resumed.subscribe(after).collect::<Vec<_>>());
+
+        // Generation continues while the client is reconnected
+        tokio::task::yield_now().await;
+        buffer.push(None, " fox".to_string());
+        buffer.push(Some("done"), "{}".to_string());
+        buffer.finish();
+
+        let replayed = replay.await.unwrap();
+        assert_eq!(
+            replayed,
+            vec![
+                BufferedEvent {
+                    seq: 3,
+                    event: None,
+                    data: " brown".to_string()
+                },
+                BufferedEvent {
+                    seq: 4,
+                    event: None,
+                    data: " fox".to_string()
+                },
+                BufferedEvent {
+                    seq: 5,
+                    event: Some("done"),
+                    data: "{}".to_string()
+                },
+            ]
+        );
+
+        // Resuming a finished stream replays the tail and ends
+        let tail: Vec<_> = buffer.subscribe(4).collect().await;
+        assert_eq!(tail.len(), 1);
+        assert!(buffer.subscribe(5).collect::<Vec<_>>().await.is_empty());
+
+        buffers.remove(buffer.message_id());
+        assert!(buffers.resume("user1", &last_event_id).is_none());
+    }
+
+    #[tokio::test]
+    async fn test_stream_is_abandoned_after_last_client_leaves() {
+        let buffers = StreamBuffers::new();
+        let buffer = buffers.start("user1");
+        let grace = Duration::from_millis(50);
+
+        let client = buffer.subscribe(0);
+        let abandoned = buffer.abandoned(grace);
+        tokio::pin!(abandoned);
+        buffer.push(None, "Hello".to_string());
+        assert!(
+            tokio::time::timeout(grace * 4, &mut abandoned)
+                .await
+                .is_err(),
+            "a followed stream was abandoned"
+        );
+
+        drop(client);
+        tokio::time::timeout(grace * 4, &mut abandoned)
+            .await
+            .expect("stream not abandoned after its client left");
+    }
+
+    #[test]
+    fn test_resume_rejects_malformed_ids() {
+        let buffers = StreamBuffers::new();
+        let buffer = buffers.start("user1");
+        assert!(buffers.resume("user1", buffer.message_id()).is_none());
+        assert!(buffers
+            .resume("user1", &format!("{}:x", buffer.message_id()))
+            .is_none());
+        assert!(buffers.resume("user1", "unknown:1").is_none());
+    }
+}
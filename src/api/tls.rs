@@ -0,0 +1,92 @@
+use crate::config::TlsConfig;
+use anyhow::{Context, Result};
+use axum_server::tls_rustls::RustlsConfig;
+use notify_debouncer_mini::new_debouncer;
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+use tracing::{error, info, warn};
+
+/// Quiet period before reloading, so a cert and key replaced together are
+/// picked up as one change
+const DEBOUNCE_MS: u64 = 500;
+
+/// Load and validate the certificate and key, failing startup on bad files
+pub async fn load(tls: &TlsConfig) -> Result<RustlsConfig> {
+    // rustls is built with ring only; a second install attempt is harmless
+    let _ = rustls::crypto::ring::default_provider().install_default();
+
+    RustlsConfig::from_pem_file(&tls.cert_path, &tls.key_path)
+        .await
+        .with_context(|| {
+            format!(
+                "Invalid TLS certificate '{}' or key '{}'",
+                tls.cert_path.display(),
+                tls.key_path.display()
+            )
+        })
+}
+
+/// Reload the certificate whenever the cert or key file changes.
+///
+/// A reload that fails (e.g. the key was written before the matching cert)
+/// keeps serving the previous certificate.
+pub async fn watch(config: RustlsConfig, tls: TlsConfig) -> Result<()> {
+    let (tx, mut rx) = tokio::sync::mpsc::unbounded_channel();
+    let mut debouncer = new_debouncer(
+        Duration::from_millis(DEBOUNCE_MS),
+        move |res: notify_debouncer_mini::DebounceEventResult| {
+            let _ = tx.send(res);
+        },
+    )
+    .context("Failed to create debouncer")?;
+
+    let files = [&tls.cert_path, &tls.key_path];
+    let mut dirs: Vec<PathBuf> = files.iter().map(|file| parent_dir(file)).collect();
+    dirs.dedup();
+    for dir in &dirs {
+        debouncer
+            .watcher()
+            .watch(dir, notify::RecursiveMode::NonRecursive)
+            .with_context(|| format!("Failed to watch {}", dir.display()))?;
+    }
+    info!("Watching TLS certificate {}", tls.cert_path.display());
+
+    while let Some(event_result) = rx.recv().await {
+        match event_result {
+            Ok(events) => {
+                let changed = events
+                    .iter()
+                    .any(|event| files.iter().any(|file| same_file(&event.path, file)));
+                if !changed {
+                    continue;
+                }
+                match config
+                    .reload_from_pem_file(&tls.cert_path, &tls.key_path)
+                    .await
+                {
+                    Ok(()) => info!("Reloaded TLS certificate {}", tls.cert_path.display()),
+                    Err(e) => error!("Failed to reload TLS certificate, keeping previous: {}", e),
+                }
+            }
+            Err(e) => warn!("TLS watcher error: {}", e),
+        }
+    }
+
+    Ok(())
+}
+
+fn parent_dir(file: &Path) -> PathBuf {
+    match file.parent() {
+        Some(dir) if !dir.as_os_str().is_empty() => dir.to_path_buf(),
+        _ => PathBuf::from("."),
+    }
+}
+
+/// Compare paths after resolving symlinks and relative components, falling
+/// back to the file name for files that no longer exist
+fn same_file(event_path: &Path, file: &Path) -> bool {
+    match (event_path.canonicalize(), file.canonicalize()) {
+        (Ok(a), Ok(b)) => a == b,
+        _ => event_path.file_name() == file.file_name(),
+    }
+}
@@ -0,0 +1,89 @@
+use crate::config::UnixSocketConfig;
+use anyhow::{bail, Context, Result};
+use axum::Router as AxumRouter;
+use hyper_util::rt::{TokioExecutor, TokioIo};
+use hyper_util::server::conn::auto::Builder;
+use hyper_util::service::TowerToHyperService;
+use std::os::unix::fs::{FileTypeExt, PermissionsExt};
+use std::path::Path;
+use tokio::net::{UnixListener, UnixStream};
+use tracing::{debug, info};
+
+/// Serve the API on a Unix domain socket
+pub async fn serve(app: AxumRouter, config: &UnixSocketConfig) -> Result<()> {
+    let mode = config
+        .mode_bits()
+        .with_context(|| format!("Invalid api.unix_socket.mode '{}'", config.mode))?;
+
+    let listener = bind(&config.path, mode).await?;
+    info!(
+        "Web API listening on unix:{} (mode {:o})",
+        config.path.display(),
+        mode
+    );
+
+    loop {
+        let (stream, _) = listener
+            .accept()
+            .await
+            .context("Failed to accept socket connection")?;
+        let service = TowerToHyperService::new(app.clone());
+
+        tokio::spawn(async move {
+            if let Err(e) = Builder::new(TokioExecutor::new())
+                .serve_connection_with_upgrades(TokioIo::new(stream), service)
+                .await
+            {
+                debug!("Socket connection closed with error: {}", e);
+            }
+        });
+    }
+}
+
+/// Bind the socket, replacing a stale socket file left by a previous run
+async fn bind(path: &Path, mode: u32) -> Result<UnixListener> {
+    if let Ok(metadata) = std::fs::symlink_metadata(path) {
+        if !metadata.file_type().is_socket() {
+            bail!("{} exists and is not a socket", path.display());
+        }
+        if UnixStream::connect(path).await.is_ok() {
+            bail!("{} is in use by another process", path.display());
+        }
+        std::fs::remove_file(path)
+            .with_context(|| format!("Failed to remove stale socket {}", path.display()))?;
+        info!("Removed stale socket {}", path.display());
+    }
+
+    let listener = UnixListener::bind(path)
+        .with_context(|| format!("Failed to bind socket {}", path.display()))?;
+    std::fs::set_permissions(path, std::fs::Permissions::from_mode(mode))
+        .with_context(|| format!("Failed to set permissions on {}", path.display()))?;
+
+    Ok(listener)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_bind_replaces_stale_socket_only() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("api.sock");
+
+        // A socket nobody listens on any more
+        drop(UnixListener::bind(&path).unwrap());
+        let listener = bind(&path, 0o600).await.unwrap();
+        let mode = std::fs::metadata(&path).unwrap().permissions().mode();
+        assert_eq!(mode & 0o777, 0o600);
+
+        // A live socket is left alone
+        assert!(bind(&path, 0o600).await.is_err());
+        drop(listener);
+
+        let file = dir.path().join("not-a-socket");
+        std::fs::write(&file, "data").unwrap();
+        assert!(bind(&file, 0o600).await.is_err());
+        assert!(file.exists());
+    }
+}
@@ -1,11 +1,13 @@
+use crate::api::connections::{self, ConnectionKind};
 use crate::api::{ApiError, AuthManager, WebSocketMessage};
+use crate::core::events::SystemEvent;
 use crate::core::{Router, StreamEvent};
 use crate::storage::Storage;
 use axum::extract::ws::{Message, WebSocket, WebSocketUpgrade};
 use axum::extract::{Query, State};
 use axum::response::IntoResponse;
 use axum::Extension;
-use futures::stream::SplitSink;
+use futures::stream::{SplitSink, SplitStream};
 use futures::{SinkExt, StreamExt};
 use serde::Deserialize;
 use std::sync::Arc;
@@ -29,6 +31,8 @@ pub async fn websocket_handler<S: Storage + 'static>(
     Extension(auth): Extension<AuthManager<S>>,
     Query(params): Query<WsQuery>,
 ) -> Result<impl IntoResponse, ApiError> {
+    auth.ensure_setup().await?;
+
     // Validate token
     let user_id = auth.validate_token_str(&params.token).await?;
 
@@ -38,7 +42,9 @@ pub async fn websocket_handler<S: Storage + 'static>(
     );
 
     // Accept WebSocket connection
-    Ok(ws.on_upgrade(move |socket| handle_socket(socket, router, user_id, params.session_id)))
+    Ok(ws.on_upgrade(move |socket| {
+        handle_socket(socket, router, user_id, params.token, params.session_id)
+    }))
 }
 
 /// Handle an individual WebSocket connection
@@ -46,6 +52,7 @@ async fn handle_socket<S: Storage + 'static>(
     socket: WebSocket,
     router: Arc<Router<S>>,
     user_id: String,
+    token: String,
     _session_id: Option<String>,
 ) {
     let (mut sender, mut receiver) = socket.split();
@@ -70,6 +77,13 @@ async fn handle_socket<S: Storage + 'static>(
         }
     };
 
+    let connection = connections::register(
+        ConnectionKind::WebSocket,
+        &user_id,
+        Some(&session.id),
+        &token,
+    );
+
     // Send connected message
     let connected = WebSocketMessage::Connected {
         session_id: session.id.clone(),
@@ -78,6 +92,22 @@ async fn handle_socket<S: Storage + 'static>(
         let _ = sender.send(Message::Text(json)).await;
     }
 
+    // Re-send approval requests still waiting on this session (e.g. after a restart)
+    if let Ok(approval_mgr) = router.get_approval_manager() {
+        for approval in approval_mgr.list_session_pending(&session.id).await {
+            let approval_msg = WebSocketMessage::ToolApprovalRequest {
+                request_id: approval.request_id,
+                tool: approval.tool_name,
+                arguments: approval.arguments,
+                policy: approval.policy,
+                sandbox_available: approval.sandbox_available,
+            };
+            if let Ok(json) = approval_msg.to_json() {
+                let _ = sender.send(Message::Text(json)).await;
+            }
+        }
+    }
+
     // Spawn keepalive task with channel
     let (tx, mut rx) = mpsc::channel(10);
     let keepalive_handle = tokio::spawn(async move {
@@ -97,9 +127,32 @@ async fn handle_socket<S: Storage + 'static>(
     let session_id = session.id.clone();
     let user_id_clone = user_id.clone();
     let router_clone = router.clone();
+    let mut events = crate::core::events::subscribe();
 
     loop {
         tokio::select! {
+            _ = connection.closed() => {
+                info!(
+                    "Closing WebSocket {} on request: user={}",
+                    connection.id(),
+                    user_id_clone
+                );
+                let _ = sender.send(Message::Close(None)).await;
+                break;
+            }
+            Ok(event) = events.recv() => {
+                if matches!(&event, SystemEvent::SessionExpired(id) if *id == session_id) {
+                    info!("Session {} expired, closing WebSocket: user={}", session_id, user_id_clone);
+                    let expired = WebSocketMessage::SessionExpired {
+                        session_id: session_id.clone(),
+                    };
+                    if let Ok(json) = expired.to_json() {
+                        let _ = sender.send(Message::Text(json)).await;
+                    }
+                    let _ = sender.send(Message::Close(None)).await;
+                    break;
+                }
+            }
             msg = receiver.next() => {
                 let msg = match msg {
                     Some(Ok(m)) => m,
@@ -115,6 +168,7 @@ async fn handle_socket<S: Storage + 'static>(
                                 // Process message and stream response
                                 if let Err(e) = process_and_stream(
                                     &mut sender,
+                                    &mut receiver,
                                     router_clone.clone(),
                                     user_id_clone.clone(),
                                     session_id.clone(),
@@ -142,35 +196,18 @@ async fn handle_socket<S: Storage + 'static>(
                                     "Tool approval response from {}: request_id={}, approved={}, use_sandbox={}, remember={}",
                                     user_id_clone, request_id, approved, use_sandbox, remember_for_session
                                 );
-
-                                // Route to ApprovalManager
-                                if let Ok(approval_mgr) = router_clone.get_approval_manager() {
-                                    approval_mgr
-                                        .submit_approval_response(
-                                            &request_id,
-                                            approved,
-                                            use_sandbox,
-                                            remember_for_session,
-                                        )
-                                        .await;
-
-                                    debug!(
-                                        "Tool approval response stored: request_id={}, approved={}",
-                                        request_id, approved
-                                    );
-                                } else {
-                                    warn!(
-                                        "Failed to access approval manager for response: request_id={}",
-                                        request_id
-                                    );
-                                    let err_msg = WebSocketMessage::Error {
-                                        error: "Approval manager not available".to_string(),
-                                        error_code: 500,
-                                    };
-                                    if let Ok(json) = err_msg.to_json() {
-                                        let _ = sender.send(Message::Text(json)).await;
-                                    }
-                                }
+                                submit_approval(
+                                    &mut sender,
+                                    &router_clone,
+                                    &request_id,
+                                    approved,
+                                    use_sandbox,
+                                    remember_for_session,
+                                )
+                                .await;
+                            }
+                            Ok(WebSocketMessage::UserInputResponse { request_id, answer }) => {
+                                submit_user_input(&mut sender, &session_id, &request_id, &answer).await;
                             }
                             Ok(WebSocketMessage::Pong) => {
                                 debug!("Received pong from {}", user_id_clone);
@@ -219,12 +256,123 @@ async fn handle_socket<S: Storage + 'static>(
     info!("WebSocket disconnected: user={}", user_id);
 }
 
+/// Route a tool approval response to the ApprovalManager
+async fn submit_approval<S: Storage + 'static>(
+    sender: &mut SplitSink<WebSocket, Message>,
+    router: &Router<S>,
+    request_id: &str,
+    approved: bool,
+    use_sandbox: bool,
+    remember_for_session: bool,
+) {
+    if let Ok(approval_mgr) = router.get_approval_manager() {
+        approval_mgr
+            .submit_approval_response(request_id, approved, use_sandbox, remember_for_session)
+            .await;
+
+        debug!(
+            "Tool approval response stored: request_id={}, approved={}",
+            request_id, approved
+        );
+    } else {
+        warn!(
+            "Failed to access approval manager for response: request_id={}",
+            request_id
+        );
+        let err_msg = WebSocketMessage::Error {
+            error: "Approval manager not available".to_string(),
+            error_code: 500,
+        };
+        if let Ok(json) = err_msg.to_json() {
+            let _ = sender.send(Message::Text(json)).await;
+        }
+    }
+}
+
+/// Answer a question the model asked in this connection's session
+async fn submit_user_input(
+    sender: &mut SplitSink<WebSocket, Message>,
+    session_id: &str,
+    request_id: &str,
+    answer: &str,
+) {
+    let inputs = crate::core::user_input::user_inputs();
+    let answered = inputs
+        .get(request_id)
+        .is_some_and(|request| request.session_id == session_id)
+        && inputs.answer(request_id, answer);
+    if answered {
+        debug!("User input answered: request_id={}", request_id);
+        return;
+    }
+
+    let err_msg = WebSocketMessage::Error {
+        error: format!("No pending question '{}'", request_id),
+        error_code: 404,
+    };
+    if let Ok(json) = err_msg.to_json() {
+        let _ = sender.send(Message::Text(json)).await;
+    }
+}
+
+/// Handle a client message that arrives while a reply is streaming; false
+/// when the client went away
+async fn handle_mid_turn<S: Storage + 'static>(
+    sender: &mut SplitSink<WebSocket, Message>,
+    router: &Router<S>,
+    session_id: &str,
+    msg: Message,
+) -> bool {
+    let text = match msg {
+        Message::Text(text) => text,
+        Message::Close(_) => return false,
+        Message::Ping(data) => return sender.send(Message::Pong(data)).await.is_ok(),
+        _ => return true,
+    };
+    match serde_json::from_str::<WebSocketMessage>(&text) {
+        Ok(WebSocketMessage::ToolApprovalResponse {
+            request_id,
+            approved,
+            use_sandbox,
+            remember_for_session,
+        }) => {
+            submit_approval(
+                sender,
+                router,
+                &request_id,
+                approved,
+                use_sandbox,
+                remember_for_session,
+            )
+            .await;
+        }
+        Ok(WebSocketMessage::UserInputResponse { request_id, answer }) => {
+            submit_user_input(sender, session_id, &request_id, &answer).await;
+        }
+        Ok(WebSocketMessage::Pong) => {}
+        // A chat message may answer the question the turn waits on
+        Ok(WebSocketMessage::Message { content })
+            if crate::core::user_input::user_inputs().answer_session(session_id, &content) => {}
+        _ => {
+            let err_msg = WebSocketMessage::Error {
+                error: "A reply is still streaming".to_string(),
+                error_code: 409,
+            };
+            if let Ok(json) = err_msg.to_json() {
+                let _ = sender.send(Message::Text(json)).await;
+            }
+        }
+    }
+    true
+}
+
 /// Process message and stream response
 async fn process_and_stream<S: Storage + 'static>(
     sender: &mut SplitSink<WebSocket, Message>,
+    incoming: &mut SplitStream<WebSocket>,
     router: Arc<Router<S>>,
     user_id: String,
-    _session_id: String,
+    session_id: String,
     content: String,
 ) -> Result<(), ApiError> {
     // Validate input
@@ -265,7 +413,18 @@ async fn process_and_stream<S: Storage + 'static>(
     // Consume stream events
     let mut total_tokens = 0;
     let final_model: String;
-    while let Some(event) = receiver.recv().await {
+    loop {
+        // Answers to approvals and questions arrive while the turn waits
+        let event = tokio::select! {
+            event = receiver.recv() => event,
+            msg = incoming.next() => {
+                match msg {
+                    Some(Ok(msg)) if handle_mid_turn(sender, &router, &session_id, msg).await => continue,
+                    _ => return Ok(()),
+                }
+            }
+        };
+        let Some(event) = event else { break };
         match event {
             StreamEvent::Delta(text) => {
                 // Send content chunk
@@ -277,6 +436,22 @@ async fn process_and_stream<S: Storage + 'static>(
                     }
                 }
             }
+            StreamEvent::ToolArgsDelta {
+                index,
+                name,
+                partial_args,
+            } => {
+                let args_msg = WebSocketMessage::ToolArgsDelta {
+                    index,
+                    name,
+                    partial_args,
+                };
+                if let Ok(json) = args_msg.to_json() {
+                    if sender.send(Message::Text(json)).await.is_err() {
+                        return Ok(());
+                    }
+                }
+            }
             StreamEvent::ToolStart {
                 name,
                 attempt,
@@ -303,11 +478,12 @@ async fn process_and_stream<S: Storage + 'static>(
                 result,
                 execution_time_ms,
                 attempt,
+                status,
             } => {
                 // Send tool end event
                 let tool_msg = WebSocketMessage::ToolUse {
                     name,
-                    status: "done".to_string(),
+                    status: status.unwrap_or_else(|| "done".to_string()),
                     output: Some(result),
                     error: None,
                     execution_time_ms,
@@ -320,7 +496,14 @@ async fn process_and_stream<S: Storage + 'static>(
                     }
                 }
             }
-            StreamEvent::Done { model, usage } => {
+            StreamEvent::Done {
+                model,
+                usage,
+                tps,
+                time_to_first_token_ms,
+                budget,
+                content,
+            } => {
                 // Extract final stats
                 final_model = model;
                 if let Some(u) = usage {
@@ -334,6 +517,10 @@ async fn process_and_stream<S: Storage + 'static>(
                     total_tokens,
                     model: final_model,
                     latency_ms,
+                    tps,
+                    time_to_first_token_ms,
+                    budget,
+                    content,
                 };
                 if let Ok(json) = end_msg.to_json() {
                     let _ = sender.send(Message::Text(json)).await;
@@ -362,6 +549,32 @@ async fn process_and_stream<S: Storage + 'static>(
                     let _ = sender.send(Message::Text(json)).await;
                 }
             }
+            StreamEvent::UserInputRequested { prompt, request_id } => {
+                let input_msg = WebSocketMessage::UserInputRequest { request_id, prompt };
+                if let Ok(json) = input_msg.to_json() {
+                    if sender.send(Message::Text(json)).await.is_err() {
+                        return Ok(());
+                    }
+                }
+            }
+            StreamEvent::Retrying {
+                attempt,
+                max_attempts,
+                delay_ms,
+                reason,
+            } => {
+                let retry_msg = WebSocketMessage::Retrying {
+                    attempt,
+                    max_attempts,
+                    delay_ms,
+                    reason,
+                };
+                if let Ok(json) = retry_msg.to_json() {
+                    if sender.send(Message::Text(json)).await.is_err() {
+                        return Ok(());
+                    }
+                }
+            }
             StreamEvent::Error(msg) => {
                 error!("Stream error: {}", msg);
                 let err_msg = WebSocketMessage::Error {
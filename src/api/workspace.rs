@@ -36,6 +36,7 @@ pub async fn list_workspace_files<S: Storage + 'static>(
             "agents".to_string(),
             "user".to_string(),
             "tools".to_string(),
+            "help".to_string(),
         ],
     })
 }
@@ -51,6 +52,7 @@ pub async fn get_workspace_file<S: Storage + 'static>(
         "agents" => WorkspaceFile::Agents,
         "user" => WorkspaceFile::User,
         "tools" => WorkspaceFile::Tools,
+        "help" => WorkspaceFile::Help,
         _ => return Err(StatusCode::NOT_FOUND),
     };
 
@@ -74,6 +76,7 @@ pub async fn update_workspace_file<S: Storage + 'static>(
         "agents" => WorkspaceFile::Agents,
         "user" => WorkspaceFile::User,
         "tools" => WorkspaceFile::Tools,
+        "help" => WorkspaceFile::Help,
         _ => return Err(StatusCode::NOT_FOUND),
     };
 
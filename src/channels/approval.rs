@@ -0,0 +1,120 @@
+use crate::core::Router;
+use crate::storage::Storage;
+
+/// Answer to an approval request, carried in button callback data as
+/// `<choice>:<request_id>`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ApprovalChoice {
+    Approve,
+    Sandbox,
+    Deny,
+}
+
+impl ApprovalChoice {
+    pub fn callback_data(self, request_id: &str) -> String {
+        let choice = match self {
+            Self::Approve => "approve",
+            Self::Sandbox => "sandbox",
+            Self::Deny => "deny",
+        };
+        format!("{}:{}", choice, request_id)
+    }
+
+    pub fn parse(data: &str) -> Option<(Self, &str)> {
+        let (choice, request_id) = data.split_once(':')?;
+        let choice = match choice {
+            "approve" => Self::Approve,
+            "sandbox" => Self::Sandbox,
+            "deny" => Self::Deny,
+            _ => return None,
+        };
+
+        (!request_id.is_empty()).then_some((choice, request_id))
+    }
+
+    fn outcome(self) -> &'static str {
+        match self {
+            Self::Approve => "✅ Approved",
+            Self::Sandbox => "📦 Approved in sandbox",
+            Self::Deny => "❌ Denied",
+        }
+    }
+}
+
+/// Status line shown once a request can no longer be answered
+pub const EXPIRED: &str = "⌛ This approval request has expired";
+
+/// Text of an approval prompt, with redacted and shortened arguments
+pub fn approval_prompt(tool_name: &str, arguments: &str) -> String {
+    format!(
+        "🔐 Approval needed to run {}\n\nArguments: {}",
+        tool_name,
+        crate::tools::audit::summarize_arguments(arguments)
+    )
+}
+
+/// Apply a button press to the approval manager and return the status to show.
+///
+/// Only requests raised in the presser's own session on `channel` can be
+/// answered; unknown, foreign or expired requests report as expired.
+pub async fn answer_approval<S: Storage + 'static>(
+    router: &Router<S>,
+    user_id: &str,
+    channel: &str,
+    choice: ApprovalChoice,
+    request_id: &str,
+) -> &'static str {
+    let pending = match (
+        router.get_or_create_session_api(user_id, channel).await,
+        router.get_approval_manager(),
+    ) {
+        (Ok(session), Ok(manager)) => manager
+            .get_pending_approval(&session.id, request_id)
+            .await
+            .filter(|approval| !approval.is_expired())
+            .map(|_| manager),
+        (Err(e), _) | (_, Err(e)) => {
+            tracing::error!("Failed to resolve approval request {}: {}", request_id, e);
+            None
+        }
+    };
+
+    match pending {
+        Some(manager) => {
+            manager
+                .submit_approval_response(
+                    request_id,
+                    choice != ApprovalChoice::Deny,
+                    choice == ApprovalChoice::Sandbox,
+                    false,
+                )
+                .await;
+            choice.outcome()
+        }
+        None => EXPIRED,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_callback_data_round_trip() {
+        let request_id = "0b6f3f5e-8f1a-4c1e-9a57-1f2d3c4b5a69";
+        for choice in [
+            ApprovalChoice::Approve,
+            ApprovalChoice::Sandbox,
+            ApprovalChoice::Deny,
+        ] {
+            let data = choice.callback_data(request_id);
+            // Telegram limits callback data to 64 bytes
+            assert!(data.len() <= 64);
+            assert_eq!(ApprovalChoice::parse(&data), Some((choice, request_id)));
+        }
+
+        assert_eq!(ApprovalChoice::parse("approve:"), None);
+        assert_eq!(ApprovalChoice::parse("maybe:abc"), None);
+        assert_eq!(ApprovalChoice::parse("approve"), None);
+    }
+}
@@ -0,0 +1,90 @@
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+/// TTL cache of "is this number on WhatsApp" lookups keyed by phone number.
+///
+/// Stores negative results too (`None`), so repeatedly checking an
+/// unregistered number does not hit the network either.
+pub struct ContactCache {
+    ttl: Duration,
+    entries: Mutex<HashMap<String, (Option<String>, Instant)>>,
+}
+
+impl ContactCache {
+    pub fn new(ttl: Duration) -> Self {
+        Self {
+            ttl,
+            entries: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Normalize a phone number into a cache key
+    pub fn key(phone: &str) -> String {
+        phone.trim().trim_start_matches('+').to_string()
+    }
+
+    /// Cached JID for a phone number; `Some(None)` means "known unregistered"
+    pub fn get(&self, phone: &str) -> Option<Option<String>> {
+        if self.ttl.is_zero() {
+            return None;
+        }
+
+        let mut entries = self.entries.lock().unwrap();
+        let key = Self::key(phone);
+        match entries.get(&key) {
+            Some((jid, stored_at)) if stored_at.elapsed() < self.ttl => Some(jid.clone()),
+            Some(_) => {
+                entries.remove(&key);
+                None
+            }
+            None => None,
+        }
+    }
+
+    /// Record a lookup result
+    pub fn insert(&self, phone: &str, jid: Option<String>) {
+        if self.ttl.is_zero() {
+            return;
+        }
+
+        self.entries
+            .lock()
+            .unwrap()
+            .insert(Self::key(phone), (jid, Instant::now()));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_hit_and_negative_hit() {
+        let cache = ContactCache::new(Duration::from_secs(60));
+        cache.insert("+15551234", Some("15551234@s.whatsapp.net".to_string()));
+        cache.insert("15559999", None);
+
+        assert_eq!(
+            cache.get("15551234"),
+            Some(Some("15551234@s.whatsapp.net".to_string()))
+        );
+        assert_eq!(cache.get("+15559999"), Some(None));
+        assert_eq!(cache.get("15550000"), None);
+    }
+
+    #[test]
+    fn test_entries_expire() {
+        let cache = ContactCache::new(Duration::from_millis(10));
+        cache.insert("15551234", None);
+        std::thread::sleep(Duration::from_millis(20));
+        assert_eq!(cache.get("15551234"), None);
+    }
+
+    #[test]
+    fn test_zero_ttl_disables_cache() {
+        let cache = ContactCache::new(Duration::ZERO);
+        cache.insert("15551234", None);
+        assert_eq!(cache.get("15551234"), None);
+    }
+}
@@ -1,10 +1,67 @@
-use crate::config::DiscordConfig;
-use crate::core::Router;
+use crate::channels::approval::{answer_approval, approval_prompt, ApprovalChoice, EXPIRED};
+use crate::channels::format::{format_reply, Platform};
+use crate::channels::placeholder::{Placeholder, ReplyChat};
+use crate::channels::ChannelAdapter;
+use crate::config::{DiscordConfig, MessageFormat};
+use crate::core::approval::APPROVAL_TIMEOUT_SECS;
+use crate::core::identity::{resolve_user_id, ChannelIdentity};
+use crate::core::{Router, StreamEvent};
 use crate::storage::Storage;
 use anyhow::Result;
 use async_trait::async_trait;
+use serenity::builder::{
+    CreateActionRow, CreateButton, CreateCommand, CreateCommandOption, CreateInteractionResponse,
+    CreateInteractionResponseFollowup, CreateInteractionResponseMessage, EditInteractionResponse,
+    EditMessage,
+};
 use serenity::{model::prelude::*, prelude::*, Client};
 use std::sync::Arc;
+use std::time::Duration;
+
+const CHANNEL: &str = "discord";
+
+/// Answer to slash commands while the channel is paused
+const PAUSED_REPLY: &str = "RustyClaw is paused on Discord. Try again later.";
+
+/// RustyClaw user ID of a Discord user
+fn user_id_of(user: &User) -> String {
+    resolve_user_id(&ChannelIdentity::new(CHANNEL, &user.id.to_string()))
+}
+
+/// Discord caps message content at 2000 characters
+const MAX_MESSAGE_CHARS: usize = 2000;
+
+/// A channel replies are sent to, in the configured format
+struct DiscordChat {
+    http: Arc<serenity::http::Http>,
+    channel_id: ChannelId,
+    format: MessageFormat,
+}
+
+#[async_trait]
+impl ReplyChat for DiscordChat {
+    type Message = Message;
+
+    async fn send(&self, text: &str) -> Result<Message> {
+        let text = format_reply(text, Platform::Discord, self.format);
+        Ok(self.channel_id.say(&self.http, text).await?)
+    }
+
+    async fn edit(&self, message: &Message, text: &str) -> Result<()> {
+        let text = format_reply(text, Platform::Discord, self.format);
+        self.channel_id
+            .edit_message(&self.http, message.id, EditMessage::new().content(text))
+            .await?;
+        Ok(())
+    }
+
+    async fn delete(&self, message: &Message) -> Result<()> {
+        self.channel_id
+            .delete_message(&self.http, message.id)
+            .await?;
+        Ok(())
+    }
+}
 
 /// Discord event handler
 struct DiscordHandler<S: Storage> {
@@ -31,6 +88,11 @@ impl<S: Storage + 'static> serenity::client::EventHandler for DiscordHandler<S>
             return;
         }
 
+        // Paused channels stay silent
+        if self.router.is_channel_paused(CHANNEL).await {
+            return;
+        }
+
         // Handle commands
         if msg.content.starts_with('/') {
             handle_command(&ctx, &msg, &self.router, &self.config).await;
@@ -42,38 +104,390 @@ impl<S: Storage + 'static> serenity::client::EventHandler for DiscordHandler<S>
             return;
         }
 
-        let user_id = msg.author.id.to_string();
-        let channel = "discord";
+        let user_id = user_id_of(&msg.author);
 
         // Send typing indicator
         let _ = msg.channel_id.start_typing(&ctx.http);
+        let chat = DiscordChat {
+            http: ctx.http.clone(),
+            channel_id: msg.channel_id,
+            format: self.config.format,
+        };
+        let reply = Placeholder::start(chat, &self.config.placeholder).await;
 
         // Process with router
-        match self
+        let content = match self
             .router
-            .handle_message(&user_id, channel, &msg.content)
+            .handle_message(&user_id, CHANNEL, &msg.content)
             .await
         {
-            Ok(response) => {
-                if let Err(e) = msg.channel_id.say(&ctx.http, response.content).await {
-                    tracing::error!("Failed to send Discord message: {}", e);
-                }
-            }
+            // Empty when a plugin hook blocked the reply
+            Ok(response) if response.content.is_empty() => None,
+            Ok(response) => Some(response.content),
             Err(e) => {
                 tracing::error!("Error processing Discord message: {}", e);
-                let _ = msg
-                    .channel_id
-                    .say(
-                        &ctx.http,
-                        "Sorry, I encountered an error processing your message.",
-                    )
-                    .await;
+                Some("Sorry, I encountered an error processing your message.".to_string())
+            }
+        };
+        if let Err(e) = reply.finish(content.as_deref()).await {
+            tracing::error!("Failed to send Discord message: {}", e);
+        }
+    }
+
+    async fn interaction_create(&self, ctx: Context, interaction: Interaction) {
+        match interaction {
+            Interaction::Command(command) => {
+                if !is_allowed(command.user.id, command.guild_id, &self.config) {
+                    tracing::warn!("Unauthorized Discord user: {}", command.user.id);
+                    return;
+                }
+                if self.router.is_channel_paused(CHANNEL).await {
+                    // Commands must be acknowledged, so say why nothing happens
+                    let response = CreateInteractionResponse::Message(
+                        CreateInteractionResponseMessage::new()
+                            .content(PAUSED_REPLY)
+                            .ephemeral(true),
+                    );
+                    if let Err(e) = command.create_response(&ctx.http, response).await {
+                        tracing::error!("Failed to answer Discord command: {}", e);
+                    }
+                    return;
+                }
+                handle_slash_command(&ctx, &command, &self.router, self.config.format).await;
+            }
+            Interaction::Component(component) => {
+                if !is_allowed(component.user.id, component.guild_id, &self.config) {
+                    tracing::warn!("Unauthorized Discord user: {}", component.user.id);
+                    return;
+                }
+                handle_approval_button(&ctx, &component, &self.router).await;
             }
+            _ => {}
         }
     }
 
-    async fn ready(&self, _ctx: Context, ready: Ready) {
+    async fn ready(&self, ctx: Context, ready: Ready) {
         tracing::info!("Discord bot ready: {}", ready.user.name);
+
+        if let Err(e) = Command::set_global_commands(&ctx.http, slash_commands()).await {
+            tracing::error!("Failed to register Discord slash commands: {}", e);
+        }
+    }
+}
+
+/// Slash commands registered on startup
+fn slash_commands() -> Vec<CreateCommand> {
+    vec![
+        CreateCommand::new("ask")
+            .description("Ask RustyClaw something")
+            .add_option(
+                CreateCommandOption::new(CommandOptionType::String, "prompt", "Your message")
+                    .required(true),
+            ),
+        CreateCommand::new("reset").description("Clear conversation history"),
+        CreateCommand::new("elevated")
+            .description("Allow elevated tools in this conversation without approval")
+            .add_option(
+                CreateCommandOption::new(
+                    CommandOptionType::String,
+                    "mode",
+                    "Turn elevated mode on or off",
+                )
+                .required(true)
+                .add_string_choice("on", "on")
+                .add_string_choice("off", "off"),
+            ),
+    ]
+}
+
+/// String value of a slash-command option
+fn option_str<'a>(command: &'a CommandInteraction, name: &str) -> Option<&'a str> {
+    command
+        .data
+        .options
+        .iter()
+        .find(|option| option.name == name)
+        .and_then(|option| option.value.as_str())
+}
+
+async fn handle_slash_command<S: Storage + 'static>(
+    ctx: &Context,
+    command: &CommandInteraction,
+    router: &Arc<Router<S>>,
+    format: MessageFormat,
+) {
+    let user_id = user_id_of(&command.user);
+
+    let reply = match command.data.name.as_str() {
+        "ask" => {
+            let prompt = option_str(command, "prompt").unwrap_or_default();
+            handle_ask(ctx, command, router, &user_id, prompt, format).await;
+            return;
+        }
+        "reset" => match router.clear_session(&user_id, CHANNEL).await {
+            Ok(_) => "Conversation history cleared!".to_string(),
+            Err(e) => {
+                tracing::error!("Failed to clear session: {}", e);
+                "Failed to clear conversation history.".to_string()
+            }
+        },
+        "elevated" => {
+            let enabled = option_str(command, "mode") == Some("on");
+            match (
+                router.get_or_create_session_api(&user_id, CHANNEL).await,
+                crate::get_tool_policy_engine(),
+            ) {
+                (Ok(session), Some(policy)) => {
+                    policy.set_elevated(&session.id, enabled).await;
+                    if enabled {
+                        "Elevated mode enabled for this conversation.".to_string()
+                    } else {
+                        "Elevated mode disabled.".to_string()
+                    }
+                }
+                (Err(e), _) => {
+                    tracing::error!("Failed to load session: {}", e);
+                    "Failed to change elevated mode.".to_string()
+                }
+                (_, None) => "Tool policies are not available.".to_string(),
+            }
+        }
+        other => format!("Unknown command: /{}", other),
+    };
+
+    let response = CreateInteractionResponse::Message(
+        CreateInteractionResponseMessage::new()
+            .content(reply)
+            .ephemeral(true),
+    );
+    if let Err(e) = command.create_response(&ctx.http, response).await {
+        tracing::error!("Failed to answer Discord command: {}", e);
+    }
+}
+
+/// Answer `/ask` from the message stream.
+///
+/// Discord expects an acknowledgement within 3 seconds, so the response is
+/// deferred first and edited once the stream completes. Approval requests
+/// raised along the way are posted as follow-ups with buttons.
+async fn handle_ask<S: Storage + 'static>(
+    ctx: &Context,
+    command: &CommandInteraction,
+    router: &Arc<Router<S>>,
+    user_id: &str,
+    prompt: &str,
+    format: MessageFormat,
+) {
+    if let Err(e) = command.defer(&ctx.http).await {
+        tracing::error!("Failed to defer Discord command: {}", e);
+        return;
+    }
+
+    let mut events = match router.handle_message_stream(user_id, CHANNEL, prompt).await {
+        Ok(events) => events,
+        Err(e) => {
+            tracing::error!("Error processing Discord command: {}", e);
+            edit_reply(
+                ctx,
+                command,
+                "Sorry, I encountered an error processing your message.",
+            )
+            .await;
+            return;
+        }
+    };
+
+    let mut content = String::new();
+    while let Some(event) = events.recv().await {
+        match event {
+            StreamEvent::Delta(delta) => content.push_str(&delta),
+            // Reply filters changed the streamed reply
+            StreamEvent::Done {
+                content: Some(filtered),
+                ..
+            } => content = filtered,
+            StreamEvent::ApprovalRequested {
+                request_id,
+                tool_name,
+                arguments,
+                sandbox_available,
+                ..
+            } => {
+                send_approval_request(
+                    ctx,
+                    command,
+                    &request_id,
+                    approval_prompt(&tool_name, &arguments),
+                    sandbox_available,
+                )
+                .await;
+            }
+            // Answered by the user's next message
+            StreamEvent::UserInputRequested { prompt, .. } => {
+                let followup = CreateInteractionResponseFollowup::new().content(prompt);
+                if let Err(e) = command.create_followup(&ctx.http, followup).await {
+                    tracing::error!("Failed to send Discord question: {}", e);
+                }
+            }
+            StreamEvent::Error(e) => {
+                tracing::error!("Error processing Discord command: {}", e);
+                edit_reply(
+                    ctx,
+                    command,
+                    "Sorry, I encountered an error processing your message.",
+                )
+                .await;
+                return;
+            }
+            _ => {}
+        }
+    }
+
+    // Empty when a plugin hook dropped the prompt without a reply
+    let content = match content.is_empty() {
+        true => None,
+        false => router.outbound_message(user_id, CHANNEL, content).await,
+    };
+    match content {
+        Some(content) => {
+            let content = format_reply(&content, Platform::Discord, format);
+            edit_reply(ctx, command, &truncate(&content, MAX_MESSAGE_CHARS)).await
+        }
+        None => {
+            if let Err(e) = command.delete_response(&ctx.http).await {
+                tracing::error!("Failed to delete Discord response: {}", e);
+            }
+        }
+    }
+}
+
+async fn edit_reply(ctx: &Context, command: &CommandInteraction, content: &str) {
+    let edit = EditInteractionResponse::new().content(content);
+    if let Err(e) = command.edit_response(&ctx.http, edit).await {
+        tracing::error!("Failed to send Discord message: {}", e);
+    }
+}
+
+/// Approve / Approve-in-sandbox / Deny buttons for one request
+fn approval_buttons(request_id: &str, sandbox_available: bool) -> CreateActionRow {
+    let mut buttons = vec![
+        CreateButton::new(ApprovalChoice::Approve.callback_data(request_id))
+            .label("Approve")
+            .style(ButtonStyle::Success),
+    ];
+    if sandbox_available {
+        buttons.push(
+            CreateButton::new(ApprovalChoice::Sandbox.callback_data(request_id))
+                .label("Approve in sandbox")
+                .style(ButtonStyle::Primary),
+        );
+    }
+    buttons.push(
+        CreateButton::new(ApprovalChoice::Deny.callback_data(request_id))
+            .label("Deny")
+            .style(ButtonStyle::Danger),
+    );
+
+    CreateActionRow::Buttons(buttons)
+}
+
+/// Post an approval prompt whose buttons are withdrawn once the request expires
+async fn send_approval_request(
+    ctx: &Context,
+    command: &CommandInteraction,
+    request_id: &str,
+    prompt: String,
+    sandbox_available: bool,
+) {
+    let followup = CreateInteractionResponseFollowup::new()
+        .content(prompt.clone())
+        .components(vec![approval_buttons(request_id, sandbox_available)]);
+    let message = match command.create_followup(&ctx.http, followup).await {
+        Ok(message) => message,
+        Err(e) => {
+            tracing::error!("Failed to send Discord approval request: {}", e);
+            return;
+        }
+    };
+
+    let http = ctx.http.clone();
+    let command = command.clone();
+    tokio::spawn(async move {
+        tokio::time::sleep(Duration::from_secs(APPROVAL_TIMEOUT_SECS)).await;
+        // Answered prompts already had their buttons removed
+        let unanswered = http
+            .get_followup_message(&command.token, message.id)
+            .await
+            .map(|message| !message.components.is_empty())
+            .unwrap_or(false);
+        if unanswered {
+            let expired = CreateInteractionResponseFollowup::new()
+                .content(format!("{}\n\n{}", prompt, EXPIRED))
+                .components(vec![]);
+            let _ = command.edit_followup(&http, message.id, expired).await;
+        }
+    });
+}
+
+async fn handle_approval_button<S: Storage + 'static>(
+    ctx: &Context,
+    component: &ComponentInteraction,
+    router: &Arc<Router<S>>,
+) {
+    let Some((choice, request_id)) = ApprovalChoice::parse(&component.data.custom_id) else {
+        return;
+    };
+
+    let user_id = user_id_of(&component.user);
+    let status = answer_approval(router, &user_id, CHANNEL, choice, request_id).await;
+
+    // Updating the message acknowledges the press and removes the buttons
+    let update = CreateInteractionResponse::UpdateMessage(
+        CreateInteractionResponseMessage::new()
+            .content(format!("{}\n\n{}", component.message.content, status))
+            .components(vec![]),
+    );
+    if let Err(e) = component.create_response(&ctx.http, update).await {
+        tracing::error!("Failed to answer Discord approval button: {}", e);
+    }
+}
+
+/// Truncate to at most `max` characters
+fn truncate(text: &str, max: usize) -> String {
+    match text.char_indices().nth(max) {
+        Some((idx, _)) => text[..idx].to_string(),
+        None => text.to_string(),
+    }
+}
+
+/// The Discord bot as a [`ChannelAdapter`]
+pub struct DiscordAdapter<S: Storage> {
+    config: DiscordConfig,
+    router: Router<S>,
+}
+
+impl<S: Storage + 'static> DiscordAdapter<S> {
+    pub fn new(config: DiscordConfig, router: Router<S>) -> Self {
+        Self { config, router }
+    }
+}
+
+#[async_trait]
+impl<S: Storage + 'static> ChannelAdapter for DiscordAdapter<S> {
+    fn name(&self) -> &'static str {
+        CHANNEL
+    }
+
+    async fn run(&self) -> Result<()> {
+        run(self.config.clone(), self.router.clone()).await
+    }
+
+    async fn send(&self, target: &str, text: &str) -> Result<()> {
+        send_text(&self.config, target, text).await
+    }
+
+    async fn validate_credentials(&self) -> Result<String> {
+        validate_credentials(&self.config).await
     }
 }
 
@@ -101,15 +515,74 @@ pub async fn run<S: Storage + 'static>(config: DiscordConfig, router: Router<S>)
     Ok(())
 }
 
+/// Send a direct message to a user outside of any conversation, e.g. the
+/// reply of a scheduled prompt
+pub async fn send_text(config: &DiscordConfig, user_id: &str, text: &str) -> Result<()> {
+    let token = config
+        .token
+        .clone()
+        .ok_or_else(|| anyhow::anyhow!("Discord token not configured"))?;
+    let user_id = user_id
+        .parse::<u64>()
+        .ok()
+        .filter(|id| *id != 0)
+        .ok_or_else(|| anyhow::anyhow!("Invalid Discord user ID: {}", user_id))?;
+
+    let http = serenity::http::Http::new(&token);
+    let channel = UserId::new(user_id).create_dm_channel(&http).await?;
+    let text = format_reply(text, Platform::Discord, config.format);
+    channel
+        .say(&http, truncate(&text, MAX_MESSAGE_CHARS))
+        .await?;
+    Ok(())
+}
+
+/// Check the bot token and that the gateway would accept an identify,
+/// without connecting. Returns the bot's name and remaining session starts.
+pub async fn validate_credentials(config: &DiscordConfig) -> Result<String> {
+    let token = config
+        .token
+        .clone()
+        .ok_or_else(|| anyhow::anyhow!("Discord token not configured"))?;
+
+    let http = serenity::http::Http::new(&token);
+    let user = http
+        .get_current_user()
+        .await
+        .map_err(|e| anyhow::anyhow!("Discord rejected the token: {}", e))?;
+    let gateway = http
+        .get_bot_gateway()
+        .await
+        .map_err(|e| anyhow::anyhow!("Discord gateway is unavailable: {}", e))?;
+
+    let limit = gateway.session_start_limit;
+    if limit.remaining == 0 {
+        anyhow::bail!(
+            "{} has no gateway session starts left; retry in {}s",
+            user.name,
+            limit.reset_after / 1000
+        );
+    }
+    Ok(format!(
+        "{} ({} of {} gateway session starts left)",
+        user.name, limit.remaining, limit.total
+    ))
+}
+
 /// Authorization check
 fn is_authorized(msg: &Message, config: &DiscordConfig) -> bool {
+    is_allowed(msg.author.id, msg.guild_id, config)
+}
+
+/// Authorization check for a user, optionally within a guild
+fn is_allowed(user_id: UserId, guild_id: Option<GuildId>, config: &DiscordConfig) -> bool {
     // Check user authorization
-    if !config.allowed_users.is_empty() && !config.allowed_users.contains(&msg.author.id.get()) {
+    if !config.allowed_users.is_empty() && !config.allowed_users.contains(&user_id.get()) {
         return false;
     }
 
     // Check guild authorization
-    if let Some(guild_id) = msg.guild_id {
+    if let Some(guild_id) = guild_id {
         if !config.allowed_guilds.is_empty() && !config.allowed_guilds.contains(&guild_id.get()) {
             return false;
         }
@@ -125,15 +598,22 @@ async fn handle_command<S: Storage + 'static>(
     router: &Arc<Router<S>>,
     _config: &DiscordConfig,
 ) {
-    let user_id = msg.author.id.to_string();
-    let channel = "discord";
+    let user_id = user_id_of(&msg.author);
+    let channel = CHANNEL;
 
     let response = match msg.content.as_str() {
-        "/start" | "/help" => "Available commands:\n\
+        "/start" | "/help" => format!(
+            "{}\n\n\
+            Available commands:\n\
             /help - Show this message\n\
             /clear - Clear conversation history\n\
-            /stats - Show session statistics"
-            .to_string(),
+            /stats - Show session statistics and settings\n\
+            /temp <0.0-2.0> - Set the reply temperature for this session\n\
+            /persona <name> - Set the reply style for this session\n\
+            /lang <language> - Set the reply language for this session\n\
+            /reset - Clear the conversation and session settings",
+            router.help_text(&user_id, channel).await
+        ),
         "/clear" => match router.clear_session(&user_id, channel).await {
             Ok(_) => "Conversation history cleared!".to_string(),
             Err(e) => {
@@ -143,12 +623,7 @@ async fn handle_command<S: Storage + 'static>(
         },
         "/stats" => match router.get_session_stats(&user_id, channel).await {
             Ok(stats) => {
-                let stats_msg = format!(
-                    "**Session Statistics**\n\
-                    Messages: {}\n\
-                    Tokens used: {}",
-                    stats.total_messages, stats.total_tokens
-                );
+                let stats_msg = format!("**Session Statistics**\n{}", stats.summary());
                 if let Err(e) = msg.channel_id.say(&ctx.http, stats_msg).await {
                     tracing::error!("Failed to send stats message: {}", e);
                 }
@@ -159,6 +634,16 @@ async fn handle_command<S: Storage + 'static>(
                 "Failed to retrieve session statistics.".to_string()
             }
         },
+        // Session settings are handled by the router, like on other channels
+        content if crate::core::overrides::parse_command(content).is_some() => {
+            match router.handle_message(&user_id, channel, content).await {
+                Ok(response) => response.content,
+                Err(e) => {
+                    tracing::error!("Failed to run command: {}", e);
+                    "Failed to run the command.".to_string()
+                }
+            }
+        }
         _ => "Unknown command. Use /help for available commands.".to_string(),
     };
 
@@ -176,6 +661,9 @@ mod tests {
             token: Some("test".to_string()),
             allowed_users: vec![],
             allowed_guilds: vec![],
+            persona: None,
+            format: Default::default(),
+            placeholder: Default::default(),
         };
 
         let msg = Message::default();
@@ -189,6 +677,9 @@ mod tests {
             token: Some("test".to_string()),
             allowed_users: vec![123456789],
             allowed_guilds: vec![],
+            persona: None,
+            format: Default::default(),
+            placeholder: Default::default(),
         };
 
         // Test with matching user ID
@@ -200,4 +691,23 @@ mod tests {
         msg.author.id = UserId::new(987654321);
         assert!(!is_authorized(&msg, &config));
     }
+
+    #[test]
+    fn test_slash_commands() {
+        let names: Vec<String> = slash_commands()
+            .into_iter()
+            .map(|command| serde_json::to_value(command).unwrap()["name"].to_string())
+            .collect();
+        assert_eq!(names, vec!["\"ask\"", "\"reset\"", "\"elevated\""]);
+    }
+
+    #[test]
+    fn test_sandbox_button_only_when_available() {
+        let count = |row: CreateActionRow| match row {
+            CreateActionRow::Buttons(buttons) => buttons.len(),
+            _ => 0,
+        };
+        assert_eq!(count(approval_buttons("id", true)), 3);
+        assert_eq!(count(approval_buttons("id", false)), 2);
+    }
 }
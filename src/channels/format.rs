@@ -0,0 +1,534 @@
+//! Assistant replies converted from Markdown to each platform's syntax
+//!
+//! Models answer in Markdown, which only the web UI renders. Before a reply
+//! is sent, the channel's `format` setting decides what happens to it:
+//!
+//! - `formatted` converts it: Telegram gets HTML (`parse_mode = HTML`),
+//!   WhatsApp its own `*bold*` / `_italic_` / `~strike~` markers, Discord its
+//!   Markdown flavor
+//! - `plain` strips the markup, keeping code and link targets
+//! - `raw` sends the model's text unchanged
+//!
+//! Code blocks and inline code are copied verbatim (escaped where the
+//! platform needs it), and links keep their target.
+//!
+//! Replies longer than a platform accepts can be cut into several messages
+//! with [`split_message`].
+
+use crate::config::MessageFormat;
+
+/// Platform a reply is formatted for
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Platform {
+    Telegram,
+    Discord,
+    WhatsApp,
+}
+
+/// A reply in the format configured for a platform
+pub fn format_reply(markdown: &str, platform: Platform, format: MessageFormat) -> String {
+    match (format, platform) {
+        (MessageFormat::Raw, _) => markdown.to_string(),
+        (MessageFormat::Plain, _) => convert(markdown, &Plain),
+        (MessageFormat::Formatted, Platform::Telegram) => convert(markdown, &TelegramHtml),
+        (MessageFormat::Formatted, Platform::Discord) => convert(markdown, &Discord),
+        (MessageFormat::Formatted, Platform::WhatsApp) => convert(markdown, &WhatsApp),
+    }
+}
+
+/// Opening and closing line of a fenced code block
+const FENCE: &str = "```";
+/// Where a long reply is preferably split, best first. Sentence ends stay
+/// with the part they end.
+const BREAKS: &[(&str, usize)] = &[
+    ("\n\n", 0),
+    ("\n", 0),
+    (". ", 1),
+    ("! ", 1),
+    ("? ", 1),
+    (" ", 0),
+];
+
+/// A reply split into messages of at most `max_chars` characters.
+///
+/// Each part ends at the last paragraph, line, sentence or word boundary in
+/// the second half of its room, or mid-word if there is none. A code block
+/// that is cut is closed at the end of one part and reopened at the start of
+/// the next.
+pub fn split_message(text: &str, max_chars: usize) -> Vec<String> {
+    let mut rest = text.trim().to_string();
+    // Room for the fence closing a cut code block
+    let reserve = if rest.contains(FENCE) {
+        FENCE.len() + 1
+    } else {
+        0
+    };
+    let room = max_chars.saturating_sub(reserve).max(2 * (FENCE.len() + 1));
+
+    let mut parts = Vec::new();
+    while rest.chars().count() > max_chars {
+        let window_end = rest.char_indices().nth(room).map_or(rest.len(), |(i, _)| i);
+        let window = &rest[..window_end];
+        let (head_end, tail_start) = BREAKS
+            .iter()
+            .find_map(|&(separator, kept)| {
+                window
+                    .rfind(separator)
+                    .filter(|&i| i > 0 && i >= window.len() / 2)
+                    .map(|i| (i + kept, i + separator.len()))
+            })
+            .unwrap_or((window_end, window_end));
+
+        let mut head = rest[..head_end].trim_end().to_string();
+        let mut tail = rest[tail_start..].trim_start_matches('\n').to_string();
+        let open = head
+            .lines()
+            .filter(|line| line.trim_start().starts_with(FENCE))
+            .count()
+            % 2
+            == 1;
+        if open {
+            match head.rsplit_once('\n') {
+                // A block opening on the last line starts in the next part
+                Some((before, last))
+                    if last.trim_start().starts_with(FENCE) && !before.trim().is_empty() =>
+                {
+                    tail = format!("{}\n{}", last, tail);
+                    head = before.trim_end().to_string();
+                }
+                _ => {
+                    head.push('\n');
+                    head.push_str(FENCE);
+                    // Unless the block closes right there, reopen it
+                    match tail.split_once('\n') {
+                        Some((first, after)) if first.trim() == FENCE => {
+                            tail = after.trim_start_matches('\n').to_string()
+                        }
+                        None if tail.trim() == FENCE => tail.clear(),
+                        _ => tail = format!("{}\n{}", FENCE, tail),
+                    }
+                }
+            }
+        }
+        if !head.is_empty() {
+            parts.push(head);
+        }
+        rest = tail;
+    }
+    if !rest.is_empty() {
+        parts.push(rest);
+    }
+    parts
+}
+
+/// How each Markdown construct is written on a platform. Text passed in has
+/// been converted already; `code` and `url` are verbatim.
+trait Syntax {
+    fn escape(&self, text: &str) -> String {
+        text.to_string()
+    }
+    fn bold(&self, text: &str) -> String;
+    fn italic(&self, text: &str) -> String;
+    fn strike(&self, text: &str) -> String;
+    fn code(&self, code: &str) -> String;
+    fn code_block(&self, language: &str, code: &str) -> String;
+    fn link(&self, text: &str, url: &str) -> String {
+        if text.is_empty() || text == url {
+            url.to_string()
+        } else {
+            format!("{} ({})", text, url)
+        }
+    }
+    fn heading(&self, _level: usize, text: &str) -> String {
+        self.bold(text)
+    }
+    fn bullet(&self) -> &'static str {
+        "•"
+    }
+}
+
+struct TelegramHtml;
+
+impl Syntax for TelegramHtml {
+    fn escape(&self, text: &str) -> String {
+        text.replace('&', "&amp;")
+            .replace('<', "&lt;")
+            .replace('>', "&gt;")
+    }
+    fn bold(&self, text: &str) -> String {
+        format!("<b>{}</b>", text)
+    }
+    fn italic(&self, text: &str) -> String {
+        format!("<i>{}</i>", text)
+    }
+    fn strike(&self, text: &str) -> String {
+        format!("<s>{}</s>", text)
+    }
+    fn code(&self, code: &str) -> String {
+        format!("<code>{}</code>", self.escape(code))
+    }
+    fn code_block(&self, language: &str, code: &str) -> String {
+        match language {
+            "" => format!("<pre>{}</pre>", self.escape(code)),
+            language => format!(
+                "<pre><code class=\"language-{}\">{}</code></pre>",
+                self.escape(language),
+                self.escape(code)
+            ),
+        }
+    }
+    fn link(&self, text: &str, url: &str) -> String {
+        let text = match text {
+            "" => self.escape(url),
+            text => text.to_string(),
+        };
+        format!(
+            "<a href=\"{}\">{}</a>",
+            self.escape(url).replace('"', "&quot;"),
+            text
+        )
+    }
+}
+
+struct WhatsApp;
+
+impl Syntax for WhatsApp {
+    fn bold(&self, text: &str) -> String {
+        format!("*{}*", text)
+    }
+    fn italic(&self, text: &str) -> String {
+        format!("_{}_", text)
+    }
+    fn strike(&self, text: &str) -> String {
+        format!("~{}~", text)
+    }
+    fn code(&self, code: &str) -> String {
+        format!("`{}`", code)
+    }
+    fn code_block(&self, _language: &str, code: &str) -> String {
+        format!("```\n{}\n```", code)
+    }
+}
+
+struct Discord;
+
+impl Syntax for Discord {
+    fn bold(&self, text: &str) -> String {
+        format!("**{}**", text)
+    }
+    fn italic(&self, text: &str) -> String {
+        format!("*{}*", text)
+    }
+    fn strike(&self, text: &str) -> String {
+        format!("~~{}~~", text)
+    }
+    fn code(&self, code: &str) -> String {
+        format!("`{}`", code)
+    }
+    fn code_block(&self, language: &str, code: &str) -> String {
+        format!("```{}\n{}\n```", language, code)
+    }
+    fn link(&self, text: &str, url: &str) -> String {
+        if text.is_empty() || text == url {
+            url.to_string()
+        } else {
+            format!("[{}]({})", text, url)
+        }
+    }
+    // Discord renders three heading levels
+    fn heading(&self, level: usize, text: &str) -> String {
+        match level {
+            1..=3 => format!("{} {}", "#".repeat(level), text),
+            _ => self.bold(text),
+        }
+    }
+    fn bullet(&self) -> &'static str {
+        "-"
+    }
+}
+
+struct Plain;
+
+impl Syntax for Plain {
+    fn bold(&self, text: &str) -> String {
+        text.to_string()
+    }
+    fn italic(&self, text: &str) -> String {
+        text.to_string()
+    }
+    fn strike(&self, text: &str) -> String {
+        text.to_string()
+    }
+    fn code(&self, code: &str) -> String {
+        code.to_string()
+    }
+    fn code_block(&self, _language: &str, code: &str) -> String {
+        code.to_string()
+    }
+}
+
+/// Convert block by block: fenced code is copied verbatim, headings and
+/// list items are recognized per line, the rest is converted inline
+fn convert(markdown: &str, syntax: &dyn Syntax) -> String {
+    let mut out = Vec::new();
+    let mut lines = markdown.lines();
+
+    while let Some(line) = lines.next() {
+        let trimmed = line.trim_start();
+        if let Some(language) = trimmed.strip_prefix("```") {
+            let mut code = Vec::new();
+            for line in lines.by_ref() {
+                if line.trim_start().starts_with("```") {
+                    break;
+                }
+                code.push(line);
+            }
+            out.push(syntax.code_block(language.trim(), &code.join("\n")));
+            continue;
+        }
+
+        let indent = &line[..line.len() - trimmed.len()];
+        let hashes = trimmed.chars().take_while(|c| *c == '#').count();
+        if (1..=6).contains(&hashes) && trimmed[hashes..].starts_with(' ') {
+            let text = inline(trimmed[hashes..].trim(), syntax);
+            out.push(syntax.heading(hashes, &text));
+        } else if let Some(item) = ["- ", "* ", "+ "]
+            .iter()
+            .find_map(|marker| trimmed.strip_prefix(marker))
+        {
+            out.push(format!(
+                "{}{} {}",
+                indent,
+                syntax.bullet(),
+                inline(item, syntax)
+            ));
+        } else {
+            out.push(inline(line, syntax));
+        }
+    }
+    out.join("\n")
+}
+
+/// Convert the emphasis, code spans and links of one line
+fn inline(text: &str, syntax: &dyn Syntax) -> String {
+    let chars: Vec<char> = text.chars().collect();
+    let mut out = String::new();
+    let mut plain = String::new();
+    let mut i = 0;
+
+    while i < chars.len() {
+        let converted = match chars[i] {
+            '`' => find(&chars, i + 1, &['`']).map(|end| {
+                let code: String = chars[i + 1..end].iter().collect();
+                (syntax.code(&code), end + 1)
+            }),
+            '!' if chars.get(i + 1) == Some(&'[') => link(&chars, i + 1, syntax),
+            '[' => link(&chars, i, syntax),
+            '*' | '_' | '~' if chars.get(i + 1) == Some(&chars[i]) => {
+                emphasis(&chars, i, 2, syntax)
+            }
+            '*' | '_' => emphasis(&chars, i, 1, syntax),
+            _ => None,
+        };
+
+        match converted {
+            Some((converted, next)) => {
+                out.push_str(&syntax.escape(&plain));
+                plain.clear();
+                out.push_str(&converted);
+                i = next;
+            }
+            None => {
+                plain.push(chars[i]);
+                i += 1;
+            }
+        }
+    }
+    out.push_str(&syntax.escape(&plain));
+    out
+}
+
+/// `[text](url)` starting at `start`, with the index after it
+fn link(chars: &[char], start: usize, syntax: &dyn Syntax) -> Option<(String, usize)> {
+    let close = find(chars, start + 1, &[']'])?;
+    if chars.get(close + 1) != Some(&'(') {
+        return None;
+    }
+    let end = find(chars, close + 2, &[')'])?;
+    let text: String = chars[start + 1..close].iter().collect();
+    let url: String = chars[close + 2..end].iter().collect();
+    if url.trim().is_empty() || url.contains(char::is_whitespace) {
+        return None;
+    }
+    Some((syntax.link(&inline(&text, syntax), &url), end + 1))
+}
+
+/// Emphasis delimited by `width` copies of the character at `start`: `**`
+/// and `__` for bold, `~~` for strikethrough, `*` and `_` for italic
+fn emphasis(
+    chars: &[char],
+    start: usize,
+    width: usize,
+    syntax: &dyn Syntax,
+) -> Option<(String, usize)> {
+    let marker = chars[start];
+    let delimiter = vec![marker; width];
+    let open = start + width;
+    // `_` inside words (snake_case) and `*` before a space are literal
+    match chars.get(open) {
+        Some(c) if !c.is_whitespace() => {}
+        _ => return None,
+    }
+    if marker == '_' && start > 0 && chars[start - 1].is_alphanumeric() {
+        return None;
+    }
+
+    let mut from = open;
+    let end = loop {
+        let end = find(chars, from, &delimiter)?;
+        let after = chars.get(end + width);
+        let closes = end > open
+            && !chars[end - 1].is_whitespace()
+            && after != Some(&marker)
+            && !(marker == '_' && after.is_some_and(|c| c.is_alphanumeric()));
+        if closes {
+            break end;
+        }
+        from = end + 1;
+    };
+
+    let inner: String = chars[open..end].iter().collect();
+    let inner = inline(&inner, syntax);
+    let converted = match (marker, width) {
+        ('~', _) => syntax.strike(&inner),
+        (_, 2) => syntax.bold(&inner),
+        _ => syntax.italic(&inner),
+    };
+    Some((converted, end + width))
+}
+
+/// Index of the next `pattern` at or after `from`
+fn find(chars: &[char], from: usize, pattern: &[char]) -> Option<usize> {
+    (from..=chars.len().checked_sub(pattern.len())?)
+        .find(|&i| chars[i..i + pattern.len()] == *pattern)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn whatsapp(markdown: &str) -> String {
+        format_reply(markdown, Platform::WhatsApp, MessageFormat::Formatted)
+    }
+
+    fn telegram(markdown: &str) -> String {
+        format_reply(markdown, Platform::Telegram, MessageFormat::Formatted)
+    }
+
+    #[test]
+    fn test_whatsapp_conversion() {
+        assert_eq!(
+            whatsapp("**Note:** this is *important* and ~~wrong~~"),
+            "*Note:* this is _important_ and ~wrong~"
+        );
+        assert_eq!(whatsapp("## Steps\n- one\n* two"), "*Steps*\n• one\n• two");
+        assert_eq!(
+            whatsapp("See [the docs](https://example.com/docs) or https://example.com"),
+            "See the docs (https://example.com/docs) or https://example.com"
+        );
+        assert_eq!(
+            whatsapp("Run `cargo **test**`:\n```bash\ncargo test -- *\n```"),
+            "Run `cargo **test**`:\n```\ncargo test -- *\n```"
+        );
+        // Lone markers and snake_case stay as they are
+        assert_eq!(
+            whatsapp("2 * 3 = 6, my_var_name and __init__"),
+            "2 * 3 = 6, my_var_name and *init*"
+        );
+    }
+
+    #[test]
+    fn test_telegram_conversion() {
+        assert_eq!(
+            telegram("**Bold** and _italic_ with <tags> & more"),
+            "<b>Bold</b> and <i>italic</i> with &lt;tags&gt; &amp; more"
+        );
+        assert_eq!(
+            telegram("```rust\nif a < b { x }\n```"),
+            "<pre><code class=\"language-rust\">if a &lt; b { x }</code></pre>"
+        );
+        assert_eq!(telegram("```\nplain\n```"), "<pre>plain</pre>");
+        assert_eq!(
+            telegram("Use `Vec<u8>` via [**this** link](https://x.io/?a=1&b=\"2\")"),
+            "Use <code>Vec&lt;u8&gt;</code> via \
+             <a href=\"https://x.io/?a=1&amp;b=&quot;2&quot;\"><b>this</b> link</a>"
+        );
+        assert_eq!(telegram("# Title\n  - item"), "<b>Title</b>\n  • item");
+        // An unclosed fence runs to the end of the reply
+        assert_eq!(telegram("```\nlet x = 1;"), "<pre>let x = 1;</pre>");
+    }
+
+    #[test]
+    fn test_split_message_at_boundaries() {
+        assert_eq!(split_message("Short reply", 30), ["Short reply"]);
+        assert_eq!(
+            split_message("First paragraph.\n\nSecond one is here.\nThird line.", 30),
+            ["First paragraph.", "Second one is here.", "Third line."]
+        );
+        assert_eq!(
+            split_message("One sentence here. Another sentence follows it.", 25),
+            ["One sentence here.", "Another sentence follows", "it."]
+        );
+        // Without a boundary a word is cut
+        let word = "x".repeat(50);
+        assert_eq!(
+            split_message(&word, 20),
+            [&word[..20], &word[20..40], &word[40..]]
+        );
+        assert_eq!(
+            split_message("héllo wörld ünïcode ßtring ok", 10),
+            ["héllo", "wörld", "ünïcode", "ßtring ok"]
+        );
+    }
+
+    #[test]
+    fn test_split_message_keeps_code_blocks_closed() {
+        assert_eq!(
+            split_message(
+                "Run this:\n```\nline one\nline two\nline three\n```\nDone.",
+                30
+            ),
+            [
+                "Run this:\n```\nline one\n```",
+                "```\nline two\nline three\n```",
+                "Done."
+            ]
+        );
+        // A block opening at the end of a part moves to the next one
+        assert_eq!(
+            split_message("Intro line\n```\nfirst code line\n```", 20),
+            ["Intro line", "```\nfirst code\n```", "```\nline\n```"]
+        );
+    }
+
+    #[test]
+    fn test_plain_and_raw() {
+        let markdown = "# Hi\n**bold** [link](https://a.b)\n```\ncode\n```";
+        assert_eq!(
+            format_reply(markdown, Platform::Telegram, MessageFormat::Plain),
+            "Hi\nbold link (https://a.b)\ncode"
+        );
+        assert_eq!(
+            format_reply(markdown, Platform::WhatsApp, MessageFormat::Raw),
+            markdown
+        );
+        assert_eq!(
+            format_reply(
+                "#### Deep\n[a](https://a.b)",
+                Platform::Discord,
+                MessageFormat::Formatted
+            ),
+            "**Deep**\n[a](https://a.b)"
+        );
+    }
+}
@@ -1,11 +1,72 @@
+use crate::core::Router;
+use crate::storage::Storage;
 use anyhow::Result;
+use async_trait::async_trait;
+use std::sync::Arc;
 
+pub mod approval;
+pub mod contact_cache;
 pub mod discord;
+pub mod format;
+pub mod placeholder;
+pub mod retry;
+pub mod supervisor;
 pub mod telegram;
 pub mod whatsapp;
 
+pub use discord::DiscordAdapter;
+pub use supervisor::{channel_statuses, supervise, RestartPolicy};
+pub use telegram::TelegramAdapter;
 pub use whatsapp::WhatsAppAdapter;
 
+/// A messaging channel: receives messages, answers them through the router
+/// and can send messages outside of a conversation
+#[async_trait]
+pub trait ChannelAdapter: Send + Sync {
+    /// Channel name, as used for routing, pausing and [`deliver`]
+    fn name(&self) -> &'static str;
+
+    /// Receive and answer messages until the connection ends
+    async fn run(&self) -> Result<()>;
+
+    /// Send a message outside of any conversation; see [`deliver`] for the
+    /// targets each channel accepts
+    async fn send(&self, target: &str, text: &str) -> Result<()>;
+
+    /// Check credentials and connectivity without processing messages.
+    /// Returns a short description of what was checked.
+    async fn validate_credentials(&self) -> Result<String>;
+}
+
+/// Adapters of the channels enabled in the configuration
+pub fn enabled_adapters<S: Storage + 'static>(
+    config: &crate::Config,
+    router: &Router<S>,
+) -> Result<Vec<Arc<dyn ChannelAdapter>>> {
+    let mut adapters: Vec<Arc<dyn ChannelAdapter>> = Vec::new();
+    if config.channels.telegram.enabled {
+        adapters.push(Arc::new(TelegramAdapter::new(
+            config.channels.telegram.clone(),
+            router.clone(),
+        )));
+    }
+    if config.channels.discord.enabled {
+        adapters.push(Arc::new(DiscordAdapter::new(
+            config.channels.discord.clone(),
+            router.clone(),
+        )));
+    }
+    if config.channels.whatsapp.enabled {
+        let whatsapp_config =
+            WhatsAppAdapter::<S>::config_from_channel(config.channels.whatsapp.clone())?;
+        adapters.push(Arc::new(WhatsAppAdapter::new(
+            Arc::new(router.clone()),
+            whatsapp_config,
+        )?));
+    }
+    Ok(adapters)
+}
+
 /// Connect to a channel (CLI command handler)
 pub async fn connect(channel: &str, _config: crate::Config) -> Result<()> {
     match channel {
@@ -19,3 +80,147 @@ pub async fn connect(channel: &str, _config: crate::Config) -> Result<()> {
 
     Ok(())
 }
+
+/// Check a channel's credentials and connectivity without processing
+/// messages (CLI command handler)
+pub async fn test(channel: &str, config: crate::Config) -> Result<()> {
+    let (name, enabled, result) = match channel {
+        "telegram" => (
+            "Telegram",
+            config.channels.telegram.enabled,
+            telegram::validate_credentials(&config.channels.telegram).await,
+        ),
+        "discord" => (
+            "Discord",
+            config.channels.discord.enabled,
+            discord::validate_credentials(&config.channels.discord).await,
+        ),
+        "whatsapp" => (
+            "WhatsApp",
+            config.channels.whatsapp.enabled,
+            whatsapp::validate_credentials(&config.channels.whatsapp),
+        ),
+        other => anyhow::bail!(
+            "Unknown channel: {}. Supported: telegram, discord, whatsapp",
+            other
+        ),
+    };
+
+    if !enabled {
+        println!("ℹ️  {} is disabled in the configuration", name);
+    }
+    match result {
+        Ok(detail) => {
+            println!("✅ {}: PASS ({})", name, detail);
+            Ok(())
+        }
+        Err(e) => {
+            println!("❌ {}: FAIL ({})", name, e);
+            anyhow::bail!("{} channel check failed", name)
+        }
+    }
+}
+
+/// Channels that receive messages, and can be paused
+pub const INBOUND_CHANNELS: [&str; 4] = ["web", "telegram", "discord", "whatsapp"];
+
+/// Channels that [`deliver`] can send to
+pub const DELIVERY_CHANNELS: [&str; 3] = ["telegram", "discord", "whatsapp"];
+
+/// Send a message outside of any conversation, e.g. the reply of a scheduled
+/// prompt. `target` is a Telegram chat ID, a Discord user ID, or a WhatsApp
+/// phone number or group (`self` for the connected account).
+pub async fn deliver(
+    config: &crate::Config,
+    channel: &str,
+    target: &str,
+    text: &str,
+) -> Result<()> {
+    match channel {
+        "telegram" => telegram::send_text(&config.channels.telegram, target, text).await,
+        "discord" => discord::send_text(&config.channels.discord, target, text).await,
+        "whatsapp" => {
+            let whatsapp = &config.channels.whatsapp;
+            whatsapp::send_text(
+                &whatsapp.phone_number,
+                target,
+                text,
+                whatsapp.format,
+                whatsapp.max_message_chars,
+            )
+            .await
+        }
+        other => anyhow::bail!(
+            "Cannot deliver to channel '{}'. Supported: telegram, discord, whatsapp",
+            other
+        ),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Mutex;
+
+    /// Records what it is asked to do
+    #[derive(Default)]
+    struct MockAdapter {
+        sent: Mutex<Vec<(String, String)>>,
+        runs: Mutex<usize>,
+    }
+
+    #[async_trait]
+    impl ChannelAdapter for MockAdapter {
+        fn name(&self) -> &'static str {
+            "mock"
+        }
+
+        async fn run(&self) -> Result<()> {
+            *self.runs.lock().unwrap() += 1;
+            Ok(())
+        }
+
+        async fn send(&self, target: &str, text: &str) -> Result<()> {
+            if target.is_empty() {
+                anyhow::bail!("No target");
+            }
+            self.sent
+                .lock()
+                .unwrap()
+                .push((target.to_string(), text.to_string()));
+            Ok(())
+        }
+
+        async fn validate_credentials(&self) -> Result<String> {
+            Ok("mock credentials".to_string())
+        }
+    }
+
+    #[tokio::test]
+    async fn test_adapters_are_driven_through_the_trait() {
+        let mock = Arc::new(MockAdapter::default());
+        let adapters: Vec<Arc<dyn ChannelAdapter>> = vec![mock.clone()];
+
+        let mut handles = Vec::new();
+        for adapter in adapters.clone() {
+            assert_eq!(adapter.name(), "mock");
+            handles.push(tokio::spawn(async move { adapter.run().await }));
+        }
+        for handle in handles {
+            handle.await.unwrap().unwrap();
+        }
+        assert_eq!(*mock.runs.lock().unwrap(), 1);
+
+        let adapter = &adapters[0];
+        adapter.send("42", "hello").await.unwrap();
+        assert!(adapter.send("", "lost").await.is_err());
+        assert_eq!(
+            *mock.sent.lock().unwrap(),
+            [("42".to_string(), "hello".to_string())]
+        );
+        assert_eq!(
+            adapter.validate_credentials().await.unwrap(),
+            "mock credentials"
+        );
+    }
+}
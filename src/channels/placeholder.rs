@@ -0,0 +1,147 @@
+//! "Thinking…" message shown while a reply is generated
+//!
+//! With a channel's `placeholder.enabled`, a short message is sent as soon
+//! as a message arrives and replaced by the answer once it is ready. Where
+//! the placeholder cannot be edited, the answer follows it as a new message.
+
+use crate::config::PlaceholderConfig;
+use anyhow::Result;
+use async_trait::async_trait;
+
+/// A chat replies are sent to
+#[async_trait]
+pub trait ReplyChat: Send + Sync {
+    /// A sent message, as needed to edit or delete it
+    type Message: Send + Sync;
+
+    async fn send(&self, text: &str) -> Result<Self::Message>;
+
+    /// Replace the text of a sent message. Fails on platforms that cannot
+    /// edit messages.
+    async fn edit(&self, message: &Self::Message, text: &str) -> Result<()>;
+
+    async fn delete(&self, message: &Self::Message) -> Result<()>;
+}
+
+/// A reply in progress, with the placeholder sent for it if any
+pub struct Placeholder<C: ReplyChat> {
+    chat: C,
+    sent: Option<C::Message>,
+}
+
+impl<C: ReplyChat> Placeholder<C> {
+    /// Send the placeholder, if enabled, before the reply is generated
+    pub async fn start(chat: C, config: &PlaceholderConfig) -> Self {
+        let mut sent = None;
+        if config.enabled {
+            match chat.send(&config.text).await {
+                Ok(message) => sent = Some(message),
+                Err(e) => tracing::warn!("Failed to send placeholder: {}", e),
+            }
+        }
+        Self { chat, sent }
+    }
+
+    /// Replace the placeholder with the reply, or send the reply when there
+    /// is no placeholder or it cannot be edited. Without a reply (a plugin
+    /// dropped it) the placeholder is removed.
+    pub async fn finish(self, reply: Option<&str>) -> Result<()> {
+        match (self.sent, reply) {
+            (None, None) => Ok(()),
+            (None, Some(reply)) => self.chat.send(reply).await.map(|_| ()),
+            (Some(sent), None) => self.chat.delete(&sent).await,
+            (Some(sent), Some(reply)) => match self.chat.edit(&sent, reply).await {
+                Ok(()) => Ok(()),
+                Err(e) => {
+                    tracing::debug!("Sending the reply after the placeholder: {}", e);
+                    self.chat.send(reply).await.map(|_| ())
+                }
+            },
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::{Arc, Mutex};
+
+    /// Logs what is done to the chat; message IDs count up from 1
+    struct MockChat {
+        log: Arc<Mutex<Vec<String>>>,
+        edits: bool,
+    }
+
+    #[async_trait]
+    impl ReplyChat for MockChat {
+        type Message = usize;
+
+        async fn send(&self, text: &str) -> Result<usize> {
+            let mut log = self.log.lock().unwrap();
+            log.push(format!("send {}", text));
+            Ok(log.iter().filter(|entry| entry.starts_with("send")).count())
+        }
+
+        async fn edit(&self, message: &usize, text: &str) -> Result<()> {
+            if !self.edits {
+                anyhow::bail!("edits not supported");
+            }
+            self.log
+                .lock()
+                .unwrap()
+                .push(format!("edit {} {}", message, text));
+            Ok(())
+        }
+
+        async fn delete(&self, message: &usize) -> Result<()> {
+            self.log.lock().unwrap().push(format!("delete {}", message));
+            Ok(())
+        }
+    }
+
+    /// Reply through the placeholder, logging when the router answers
+    async fn reply(enabled: bool, edits: bool, answer: Option<&str>) -> Vec<String> {
+        let log = Arc::new(Mutex::new(Vec::new()));
+        let config = PlaceholderConfig {
+            enabled,
+            text: "🤔 thinking…".to_string(),
+        };
+        let chat = MockChat {
+            log: log.clone(),
+            edits,
+        };
+
+        let placeholder = Placeholder::start(chat, &config).await;
+        let router = async {
+            log.lock().unwrap().push("router".to_string());
+            answer
+        };
+        placeholder.finish(router.await).await.unwrap();
+
+        let log = log.lock().unwrap().clone();
+        log
+    }
+
+    #[tokio::test]
+    async fn test_placeholder_is_sent_before_the_reply() {
+        assert_eq!(
+            reply(true, true, Some("42")).await,
+            ["send 🤔 thinking…", "router", "edit 1 42"]
+        );
+        // Without edits the answer follows the placeholder
+        assert_eq!(
+            reply(true, false, Some("42")).await,
+            ["send 🤔 thinking…", "router", "send 42"]
+        );
+        assert_eq!(
+            reply(true, true, None).await,
+            ["send 🤔 thinking…", "router", "delete 1"]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_disabled_placeholder_sends_only_the_reply() {
+        assert_eq!(reply(false, true, Some("42")).await, ["router", "send 42"]);
+        assert_eq!(reply(false, true, None).await, ["router"]);
+    }
+}
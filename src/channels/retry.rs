@@ -0,0 +1,174 @@
+use crate::config::RetryConfig;
+use std::future::Future;
+use std::time::Duration;
+use tracing::warn;
+
+/// Outcome of an operation that exhausted its retries or failed permanently
+#[derive(Debug)]
+pub struct RetryError {
+    /// Number of attempts made
+    pub attempts: u32,
+    /// Whether the final error was considered transient
+    pub retryable: bool,
+    /// The last error returned by the operation
+    pub error: anyhow::Error,
+}
+
+/// Delay before the given retry (1-based), doubling up to the configured cap
+pub fn backoff_delay(config: &RetryConfig, retry: u32) -> Duration {
+    let factor = 1u64 << retry.saturating_sub(1).min(16);
+    let delay = config.initial_backoff_ms.saturating_mul(factor);
+    Duration::from_millis(delay.min(config.max_backoff_ms))
+}
+
+/// Run `op` until it succeeds, fails permanently, or runs out of attempts.
+///
+/// `is_retryable` decides whether an error is transient. The operation is
+/// invoked at most `max_attempts` times (at least once).
+pub async fn retry_with_backoff<T, F, Fut>(
+    config: &RetryConfig,
+    is_retryable: impl Fn(&anyhow::Error) -> bool,
+    op: F,
+) -> Result<T, RetryError>
+where
+    F: FnMut(u32) -> Fut,
+    Fut: Future<Output = anyhow::Result<T>>,
+{
+    retry_with_delay(
+        config,
+        is_retryable,
+        |_, attempt| backoff_delay(config, attempt),
+        op,
+    )
+    .await
+}
+
+/// Like [`retry_with_backoff`], with `delay` choosing the wait before each
+/// retry from the error and the attempt that failed (e.g. to honour a
+/// server's `Retry-After`)
+pub async fn retry_with_delay<T, F, Fut>(
+    config: &RetryConfig,
+    is_retryable: impl Fn(&anyhow::Error) -> bool,
+    delay: impl Fn(&anyhow::Error, u32) -> Duration,
+    mut op: F,
+) -> Result<T, RetryError>
+where
+    F: FnMut(u32) -> Fut,
+    Fut: Future<Output = anyhow::Result<T>>,
+{
+    let max_attempts = config.max_attempts.max(1);
+    let mut attempt = 1;
+
+    loop {
+        match op(attempt).await {
+            Ok(value) => return Ok(value),
+            Err(error) => {
+                let retryable = is_retryable(&error);
+                if !retryable || attempt >= max_attempts {
+                    return Err(RetryError {
+                        attempts: attempt,
+                        retryable,
+                        error,
+                    });
+                }
+
+                let delay = delay(&error, attempt);
+                warn!(
+                    "Attempt {}/{} failed, retrying in {:?}: {}",
+                    attempt, max_attempts, delay, error
+                );
+                tokio::time::sleep(delay).await;
+                attempt += 1;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicU32, Ordering};
+
+    /// Mock transport that fails a fixed number of times before succeeding
+    struct FlakyClient {
+        failures: u32,
+        calls: AtomicU32,
+    }
+
+    impl FlakyClient {
+        async fn send(&self) -> anyhow::Result<String> {
+            let call = self.calls.fetch_add(1, Ordering::SeqCst) + 1;
+            if call <= self.failures {
+                anyhow::bail!("connection reset by peer");
+            }
+            Ok(format!("msg-{}", call))
+        }
+    }
+
+    fn fast_config(max_attempts: u32) -> RetryConfig {
+        RetryConfig {
+            max_attempts,
+            initial_backoff_ms: 1,
+            max_backoff_ms: 5,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_fails_once_then_succeeds() {
+        let client = FlakyClient {
+            failures: 1,
+            calls: AtomicU32::new(0),
+        };
+
+        let id = retry_with_backoff(&fast_config(3), |_| true, |_| client.send())
+            .await
+            .unwrap();
+
+        assert_eq!(id, "msg-2");
+        assert_eq!(client.calls.load(Ordering::SeqCst), 2);
+    }
+
+    #[tokio::test]
+    async fn test_gives_up_after_max_attempts() {
+        let client = FlakyClient {
+            failures: 10,
+            calls: AtomicU32::new(0),
+        };
+
+        let err = retry_with_backoff(&fast_config(3), |_| true, |_| client.send())
+            .await
+            .unwrap_err();
+
+        assert_eq!(err.attempts, 3);
+        assert!(err.retryable);
+        assert_eq!(client.calls.load(Ordering::SeqCst), 3);
+    }
+
+    #[tokio::test]
+    async fn test_permanent_error_is_not_retried() {
+        let client = FlakyClient {
+            failures: 10,
+            calls: AtomicU32::new(0),
+        };
+
+        let err = retry_with_backoff(&fast_config(3), |_| false, |_| client.send())
+            .await
+            .unwrap_err();
+
+        assert_eq!(err.attempts, 1);
+        assert!(!err.retryable);
+    }
+
+    #[test]
+    fn test_backoff_is_capped() {
+        let config = RetryConfig {
+            max_attempts: 10,
+            initial_backoff_ms: 100,
+            max_backoff_ms: 1000,
+        };
+
+        assert_eq!(backoff_delay(&config, 1), Duration::from_millis(100));
+        assert_eq!(backoff_delay(&config, 2), Duration::from_millis(200));
+        assert_eq!(backoff_delay(&config, 8), Duration::from_millis(1000));
+    }
+}
@@ -0,0 +1,241 @@
+//! Keeps channel adapters running independently of each other
+//!
+//! Each adapter runs under [`supervise`]: when it fails or panics the error
+//! is logged and, with `channels.restart_on_failure`, the adapter is
+//! restarted after a growing backoff, up to `channels.max_restarts` times.
+//! A channel that stays down does not stop the gateway or the other
+//! channels; its state is reported by `/health`.
+
+use super::ChannelAdapter;
+use crate::config::ChannelsConfig;
+use once_cell::sync::Lazy;
+use serde::Serialize;
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+/// Backoff before the first restart, doubled for each further one
+pub const INITIAL_BACKOFF: Duration = Duration::from_secs(1);
+/// Longest wait between restarts
+pub const MAX_BACKOFF: Duration = Duration::from_secs(60);
+
+/// When a failed adapter is restarted
+#[derive(Debug, Clone, Copy)]
+pub struct RestartPolicy {
+    pub enabled: bool,
+    pub max_restarts: u32,
+    pub initial_backoff: Duration,
+}
+
+impl RestartPolicy {
+    pub fn from_config(config: &ChannelsConfig) -> Self {
+        Self {
+            enabled: config.restart_on_failure,
+            max_restarts: config.max_restarts,
+            initial_backoff: INITIAL_BACKOFF,
+        }
+    }
+
+    /// Wait before restart number `attempt` (from 0)
+    fn backoff(&self, attempt: u32) -> Duration {
+        self.initial_backoff
+            .saturating_mul(2u32.saturating_pow(attempt))
+            .min(MAX_BACKOFF)
+    }
+}
+
+/// State of a supervised channel
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ChannelState {
+    Running,
+    /// Failed, waiting to be restarted
+    Restarting,
+    /// Failed and not restarted any more
+    Down,
+    /// The adapter returned without an error
+    Stopped,
+}
+
+/// A supervised channel, as reported by `/health`
+#[derive(Debug, Clone, Serialize)]
+pub struct ChannelStatus {
+    pub name: String,
+    pub state: ChannelState,
+    pub restarts: u32,
+    /// Error of the last failure
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+static STATUSES: Lazy<Mutex<HashMap<&'static str, ChannelStatus>>> =
+    Lazy::new(|| Mutex::new(HashMap::new()));
+
+/// Supervised channels, by name
+pub fn channel_statuses() -> Vec<ChannelStatus> {
+    let mut statuses: Vec<ChannelStatus> = STATUSES.lock().unwrap().values().cloned().collect();
+    statuses.sort_by(|a, b| a.name.cmp(&b.name));
+    statuses
+}
+
+fn set_status(name: &'static str, state: ChannelState, restarts: u32, error: Option<String>) {
+    STATUSES.lock().unwrap().insert(
+        name,
+        ChannelStatus {
+            name: name.to_string(),
+            state,
+            restarts,
+            error,
+        },
+    );
+}
+
+/// Run an adapter until it stops, restarting it on failure as the policy
+/// allows. Never fails: a channel that cannot be kept up is marked down.
+pub async fn supervise(adapter: Arc<dyn ChannelAdapter>, policy: RestartPolicy) -> ChannelState {
+    let name = adapter.name();
+    let mut restarts = 0;
+    loop {
+        set_status(name, ChannelState::Running, restarts, None);
+
+        // A task of its own, so a panic ends up here as an error
+        let running = adapter.clone();
+        let error = match tokio::spawn(async move { running.run().await }).await {
+            Ok(Ok(())) => {
+                tracing::info!("{} adapter stopped", name);
+                set_status(name, ChannelState::Stopped, restarts, None);
+                return ChannelState::Stopped;
+            }
+            Ok(Err(e)) => format!("{:#}", e),
+            Err(e) if e.is_panic() => "adapter panicked".to_string(),
+            Err(e) => e.to_string(),
+        };
+
+        if !policy.enabled || restarts >= policy.max_restarts {
+            tracing::error!(
+                "{} adapter failed, leaving it down after {} restart(s): {}",
+                name,
+                restarts,
+                error
+            );
+            set_status(name, ChannelState::Down, restarts, Some(error));
+            return ChannelState::Down;
+        }
+
+        let backoff = policy.backoff(restarts);
+        tracing::warn!(
+            "{} adapter failed, restarting in {:?}: {}",
+            name,
+            backoff,
+            error
+        );
+        set_status(name, ChannelState::Restarting, restarts, Some(error));
+        tokio::time::sleep(backoff).await;
+        restarts += 1;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use anyhow::Result;
+    use async_trait::async_trait;
+    use std::sync::atomic::{AtomicU32, Ordering};
+
+    /// Fails (or panics) on every run, or runs until the test ends
+    struct TestAdapter {
+        name: &'static str,
+        fails: Option<bool>,
+        runs: AtomicU32,
+    }
+
+    impl TestAdapter {
+        fn new(name: &'static str, fails: Option<bool>) -> Arc<Self> {
+            Arc::new(Self {
+                name,
+                fails,
+                runs: AtomicU32::new(0),
+            })
+        }
+    }
+
+    #[async_trait]
+    impl ChannelAdapter for TestAdapter {
+        fn name(&self) -> &'static str {
+            self.name
+        }
+
+        async fn run(&self) -> Result<()> {
+            self.runs.fetch_add(1, Ordering::SeqCst);
+            match self.fails {
+                Some(true) => panic!("adapter bug"),
+                Some(false) => anyhow::bail!("connection refused"),
+                None => std::future::pending().await,
+            }
+        }
+
+        async fn send(&self, _target: &str, _text: &str) -> Result<()> {
+            Ok(())
+        }
+
+        async fn validate_credentials(&self) -> Result<String> {
+            Ok(String::new())
+        }
+    }
+
+    fn policy(enabled: bool) -> RestartPolicy {
+        RestartPolicy {
+            enabled,
+            max_restarts: 2,
+            initial_backoff: Duration::from_millis(1),
+        }
+    }
+
+    fn status(name: &str) -> ChannelStatus {
+        channel_statuses()
+            .into_iter()
+            .find(|status| status.name == name)
+            .unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_failed_channel_goes_down_while_others_keep_running() {
+        let healthy = TestAdapter::new("supervisor-healthy", None);
+        tokio::spawn(supervise(healthy.clone(), policy(true)));
+        while healthy.runs.load(Ordering::SeqCst) == 0 {
+            tokio::task::yield_now().await;
+        }
+
+        let failing = TestAdapter::new("supervisor-failing", Some(false));
+        assert_eq!(
+            supervise(failing.clone(), policy(true)).await,
+            ChannelState::Down
+        );
+        assert_eq!(failing.runs.load(Ordering::SeqCst), 3);
+        let down = status("supervisor-failing");
+        assert_eq!(down.restarts, 2);
+        assert_eq!(down.error.as_deref(), Some("connection refused"));
+
+        let panicking = TestAdapter::new("supervisor-panicking", Some(true));
+        assert_eq!(
+            supervise(panicking.clone(), policy(false)).await,
+            ChannelState::Down
+        );
+        assert_eq!(panicking.runs.load(Ordering::SeqCst), 1);
+
+        assert_eq!(status("supervisor-healthy").state, ChannelState::Running);
+        assert_eq!(healthy.runs.load(Ordering::SeqCst), 1);
+    }
+
+    #[test]
+    fn test_backoff_doubles_up_to_the_maximum() {
+        let policy = RestartPolicy {
+            enabled: true,
+            max_restarts: 100,
+            initial_backoff: INITIAL_BACKOFF,
+        };
+        assert_eq!(policy.backoff(0), Duration::from_secs(1));
+        assert_eq!(policy.backoff(3), Duration::from_secs(8));
+        assert_eq!(policy.backoff(40), MAX_BACKOFF);
+    }
+}
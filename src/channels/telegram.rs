@@ -1,8 +1,22 @@
-use crate::config::TelegramConfig;
-use crate::core::Router;
-use crate::storage::Storage;
+use crate::channels::approval::{answer_approval, approval_prompt, ApprovalChoice};
+use crate::channels::format::{format_reply, Platform};
+use crate::channels::placeholder::{Placeholder, ReplyChat};
+use crate::channels::ChannelAdapter;
+use crate::config::{MessageFormat, TelegramConfig};
+use crate::core::approval::APPROVAL_TIMEOUT_SECS;
+use crate::core::identity::{resolve_user_id, ChannelIdentity};
+use crate::core::{Router, StreamEvent};
+use crate::storage::{FeedbackRating, Storage};
 use anyhow::Result;
+use async_trait::async_trait;
+use std::time::Duration;
+use teloxide::types::{InlineKeyboardButton, InlineKeyboardMarkup, ParseMode, User};
 use teloxide::{prelude::*, utils::command::BotCommands};
+use tokio::sync::mpsc;
+
+const CHANNEL: &str = "telegram";
+
+const ERROR_REPLY: &str = "Sorry, I encountered an error processing your message.";
 
 #[derive(BotCommands, Clone)]
 #[command(rename_rule = "lowercase", description = "Available commands:")]
@@ -13,6 +27,65 @@ enum Command {
     Help,
     #[command(description = "Clear conversation history")]
     Clear,
+    #[command(description = "Show tool calls waiting for approval")]
+    Approvals,
+}
+
+/// RustyClaw user ID of a Telegram user
+fn user_id_of(user: Option<&User>) -> String {
+    let native_id = user.map(|u| u.id.to_string()).unwrap_or_default();
+    resolve_user_id(&ChannelIdentity::new(CHANNEL, &native_id))
+}
+
+/// Approve / Approve-in-sandbox / Deny buttons for one request
+fn approval_keyboard(request_id: &str, sandbox_available: bool) -> InlineKeyboardMarkup {
+    let mut row = vec![InlineKeyboardButton::callback(
+        "✅ Approve",
+        ApprovalChoice::Approve.callback_data(request_id),
+    )];
+    if sandbox_available {
+        row.push(InlineKeyboardButton::callback(
+            "📦 Sandbox",
+            ApprovalChoice::Sandbox.callback_data(request_id),
+        ));
+    }
+    row.push(InlineKeyboardButton::callback(
+        "❌ Deny",
+        ApprovalChoice::Deny.callback_data(request_id),
+    ));
+
+    InlineKeyboardMarkup::new(vec![row])
+}
+
+/// The Telegram bot as a [`ChannelAdapter`]
+pub struct TelegramAdapter<S: Storage> {
+    config: TelegramConfig,
+    router: Router<S>,
+}
+
+impl<S: Storage + 'static> TelegramAdapter<S> {
+    pub fn new(config: TelegramConfig, router: Router<S>) -> Self {
+        Self { config, router }
+    }
+}
+
+#[async_trait]
+impl<S: Storage + 'static> ChannelAdapter for TelegramAdapter<S> {
+    fn name(&self) -> &'static str {
+        CHANNEL
+    }
+
+    async fn run(&self) -> Result<()> {
+        run(self.config.clone(), self.router.clone()).await
+    }
+
+    async fn send(&self, target: &str, text: &str) -> Result<()> {
+        send_text(&self.config, target, text).await
+    }
+
+    async fn validate_credentials(&self) -> Result<String> {
+        validate_credentials(&self.config).await
+    }
 }
 
 pub async fn run<S: Storage + 'static>(config: TelegramConfig, router: Router<S>) -> Result<()> {
@@ -31,7 +104,8 @@ pub async fn run<S: Storage + 'static>(config: TelegramConfig, router: Router<S>
                 .filter_command::<Command>()
                 .endpoint(handle_command::<S>),
         )
-        .branch(Update::filter_message().endpoint(handle_message::<S>));
+        .branch(Update::filter_message().endpoint(handle_message::<S>))
+        .branch(Update::filter_callback_query().endpoint(handle_callback::<S>));
 
     let mut dispatcher = Dispatcher::builder(bot, handler)
         .dependencies(dptree::deps![router, config])
@@ -43,6 +117,111 @@ pub async fn run<S: Storage + 'static>(config: TelegramConfig, router: Router<S>
     Ok(())
 }
 
+/// Send a message to a chat outside of any conversation, e.g. the reply of
+/// a scheduled prompt
+pub async fn send_text(config: &TelegramConfig, chat_id: &str, text: &str) -> Result<()> {
+    let token = config
+        .token
+        .clone()
+        .ok_or_else(|| anyhow::anyhow!("Telegram token not configured"))?;
+    let chat_id = chat_id
+        .parse::<i64>()
+        .map_err(|_| anyhow::anyhow!("Invalid Telegram chat ID: {}", chat_id))?;
+
+    send_reply(&Bot::new(token), ChatId(chat_id), text, config.format).await?;
+    Ok(())
+}
+
+/// Send an assistant reply in the configured format, as plain text if
+/// Telegram rejects the converted markup
+async fn send_reply(
+    bot: &Bot,
+    chat_id: ChatId,
+    text: &str,
+    format: MessageFormat,
+) -> Result<Message> {
+    if format == MessageFormat::Formatted {
+        let html = format_reply(text, Platform::Telegram, format);
+        match bot
+            .send_message(chat_id, html)
+            .parse_mode(ParseMode::Html)
+            .await
+        {
+            Ok(message) => return Ok(message),
+            Err(e) => tracing::debug!("Sending Telegram reply as plain text: {}", e),
+        }
+    }
+    let text = format_reply(text, Platform::Telegram, unformatted(format));
+    Ok(bot.send_message(chat_id, text).await?)
+}
+
+/// `format`, falling back from converted markup to plain text
+fn unformatted(format: MessageFormat) -> MessageFormat {
+    match format {
+        MessageFormat::Formatted => MessageFormat::Plain,
+        other => other,
+    }
+}
+
+/// A chat replies are sent to, in the configured format
+struct TelegramChat {
+    bot: Bot,
+    chat_id: ChatId,
+    format: MessageFormat,
+}
+
+#[async_trait]
+impl ReplyChat for TelegramChat {
+    type Message = Message;
+
+    async fn send(&self, text: &str) -> Result<Message> {
+        send_reply(&self.bot, self.chat_id, text, self.format).await
+    }
+
+    async fn edit(&self, message: &Message, text: &str) -> Result<()> {
+        if self.format == MessageFormat::Formatted {
+            let html = format_reply(text, Platform::Telegram, self.format);
+            match self
+                .bot
+                .edit_message_text(self.chat_id, message.id, html)
+                .parse_mode(ParseMode::Html)
+                .await
+            {
+                Ok(_) => return Ok(()),
+                Err(e) => tracing::debug!("Editing Telegram reply as plain text: {}", e),
+            }
+        }
+        let text = format_reply(text, Platform::Telegram, unformatted(self.format));
+        self.bot
+            .edit_message_text(self.chat_id, message.id, text)
+            .await?;
+        Ok(())
+    }
+
+    async fn delete(&self, message: &Message) -> Result<()> {
+        self.bot.delete_message(self.chat_id, message.id).await?;
+        Ok(())
+    }
+}
+
+/// Check the bot token with `getMe`, without receiving updates. Returns the
+/// bot's username.
+pub async fn validate_credentials(config: &TelegramConfig) -> Result<String> {
+    let token = config
+        .token
+        .clone()
+        .ok_or_else(|| anyhow::anyhow!("Telegram token not configured"))?;
+    validate_bot(&Bot::new(token)).await
+}
+
+async fn validate_bot(bot: &Bot) -> Result<String> {
+    let me = bot
+        .get_me()
+        .await
+        .map_err(|e| anyhow::anyhow!("Telegram rejected the token: {}", e))?;
+    Ok(format!("@{}", me.username()))
+}
+
 async fn handle_command<S: Storage + 'static>(
     bot: Bot,
     msg: Message,
@@ -61,21 +240,23 @@ async fn handle_command<S: Storage + 'static>(
 
     match cmd {
         Command::Start => {
-            bot.send_message(
-                msg.chat.id,
-                "Welcome to RustyClaw! Send me a message to start chatting.",
-            )
-            .await?;
+            let user_id = user_id_of(msg.from());
+            let welcome = router.help_text(&user_id, CHANNEL).await;
+            bot.send_message(msg.chat.id, welcome).await?;
         }
         Command::Help => {
-            let help_text = Command::descriptions().to_string();
+            let user_id = user_id_of(msg.from());
+            let help_text = format!(
+                "{}\n\n{}",
+                router.help_text(&user_id, CHANNEL).await,
+                Command::descriptions()
+            );
             bot.send_message(msg.chat.id, help_text).await?;
         }
         Command::Clear => {
-            let user_id = msg.from().map(|u| u.id.to_string()).unwrap_or_default();
-            let channel = "telegram";
+            let user_id = user_id_of(msg.from());
 
-            if let Err(e) = router.clear_session(&user_id, channel).await {
+            if let Err(e) = router.clear_session(&user_id, CHANNEL).await {
                 tracing::error!("Failed to clear session: {}", e);
                 bot.send_message(msg.chat.id, "Failed to clear conversation history")
                     .await?;
@@ -84,6 +265,40 @@ async fn handle_command<S: Storage + 'static>(
                     .await?;
             }
         }
+        Command::Approvals => {
+            let user_id = user_id_of(msg.from());
+            let session = match router.get_or_create_session_api(&user_id, CHANNEL).await {
+                Ok(session) => session,
+                Err(e) => {
+                    tracing::error!("Failed to load session: {}", e);
+                    return Ok(());
+                }
+            };
+
+            let pending = match router.get_approval_manager() {
+                Ok(manager) => manager.list_session_pending(&session.id).await,
+                Err(_) => Vec::new(),
+            };
+            if pending.is_empty() {
+                bot.send_message(msg.chat.id, "No tool calls are waiting for approval.")
+                    .await?;
+            }
+            for approval in pending {
+                let expires_in = (approval.expires_at - chrono::Utc::now())
+                    .to_std()
+                    .unwrap_or_default();
+                send_approval_request(
+                    &bot,
+                    msg.chat.id,
+                    &approval.request_id,
+                    &approval.tool_name,
+                    &approval.arguments,
+                    approval.sandbox_available,
+                    expires_in,
+                )
+                .await?;
+            }
+        }
     }
 
     Ok(())
@@ -109,26 +324,249 @@ async fn handle_message<S: Storage + 'static>(
         return Ok(());
     }
 
-    let user_id = msg.from().map(|u| u.id.to_string()).unwrap_or_default();
-    let channel = "telegram";
+    // Paused channels stay silent
+    if router.is_channel_paused(CHANNEL).await {
+        return Ok(());
+    }
+
+    let user_id = user_id_of(msg.from());
+
+    // A lone thumbs-up/down rates the previous reply (Telegram bots do not
+    // receive reactions)
+    if let Some(rating) = FeedbackRating::from_emoji(text) {
+        match router.rate_latest_response(&user_id, CHANNEL, rating).await {
+            Ok(true) => {
+                bot.send_message(msg.chat.id, "Thanks for the feedback!")
+                    .await?;
+            }
+            Ok(false) => {}
+            Err(e) => tracing::error!("Failed to store feedback: {}", e),
+        }
+        return Ok(());
+    }
 
     // Send typing indicator
     bot.send_chat_action(msg.chat.id, teloxide::types::ChatAction::Typing)
         .await?;
 
-    match router.handle_message(&user_id, channel, text).await {
-        Ok(response) => {
-            bot.send_message(msg.chat.id, response.content).await?;
+    let chat = TelegramChat {
+        bot: bot.clone(),
+        chat_id: msg.chat.id,
+        format: config.format,
+    };
+    let reply = Placeholder::start(chat, &config.placeholder).await;
+
+    match router.handle_message_stream(&user_id, CHANNEL, text).await {
+        Ok(events) => {
+            // Updates from one chat are handled in order, so the reply is
+            // delivered from a task to let approval button presses through
+            tokio::spawn(deliver_reply(
+                bot,
+                msg.chat.id,
+                events,
+                router.clone(),
+                user_id,
+                reply,
+            ));
         }
         Err(e) => {
             tracing::error!("Error handling message: {}", e);
-            bot.send_message(
-                msg.chat.id,
-                "Sorry, I encountered an error processing your message.",
-            )
-            .await?;
+            if let Err(e) = reply.finish(Some(ERROR_REPLY)).await {
+                tracing::warn!("Failed to send Telegram message: {}", e);
+            }
         }
     }
 
     Ok(())
 }
+
+/// Send the streamed reply once complete, in place of the placeholder if
+/// one was sent, prompting for approvals on the way
+async fn deliver_reply<S: Storage + 'static>(
+    bot: Bot,
+    chat_id: ChatId,
+    mut events: mpsc::Receiver<StreamEvent>,
+    router: Router<S>,
+    user_id: String,
+    reply: Placeholder<TelegramChat>,
+) {
+    let mut content = String::new();
+    let mut reply = Some(reply);
+
+    while let Some(event) = events.recv().await {
+        let sent = match event {
+            StreamEvent::Delta(delta) => {
+                content.push_str(&delta);
+                continue;
+            }
+            // Reply filters changed the streamed reply
+            StreamEvent::Done {
+                content: Some(filtered),
+                ..
+            } => {
+                content = filtered;
+                continue;
+            }
+            StreamEvent::ApprovalRequested {
+                request_id,
+                tool_name,
+                arguments,
+                sandbox_available,
+                ..
+            } => {
+                send_approval_request(
+                    &bot,
+                    chat_id,
+                    &request_id,
+                    &tool_name,
+                    &arguments,
+                    sandbox_available,
+                    Duration::from_secs(APPROVAL_TIMEOUT_SECS),
+                )
+                .await
+            }
+            // Answered by the user's next message
+            StreamEvent::UserInputRequested { prompt, .. } => {
+                bot.send_message(chat_id, prompt).await.map(|_| ())
+            }
+            StreamEvent::Error(e) => {
+                tracing::error!("Error handling message: {}", e);
+                content.clear();
+                match reply.take() {
+                    Some(reply) => reply.finish(Some(ERROR_REPLY)).await,
+                    None => Ok(()),
+                }
+            }
+            _ => continue,
+        };
+
+        if let Err(e) = sent {
+            tracing::warn!("Failed to send Telegram message: {}", e);
+        }
+    }
+
+    // Already answered with the error
+    let Some(reply) = reply else {
+        return;
+    };
+    // Empty when a plugin hook dropped the reply
+    let content = match content.is_empty() {
+        true => None,
+        false => router.outbound_message(&user_id, CHANNEL, content).await,
+    };
+    if let Err(e) = reply.finish(content.as_deref()).await {
+        tracing::warn!("Failed to send Telegram message: {}", e);
+    }
+}
+
+/// Ask for approval with inline buttons that are withdrawn once the request expires
+async fn send_approval_request(
+    bot: &Bot,
+    chat_id: ChatId,
+    request_id: &str,
+    tool_name: &str,
+    arguments: &str,
+    sandbox_available: bool,
+    expires_in: Duration,
+) -> ResponseResult<()> {
+    let prompt = approval_prompt(tool_name, arguments);
+    let message = bot
+        .send_message(chat_id, prompt.clone())
+        .reply_markup(approval_keyboard(request_id, sandbox_available))
+        .await?;
+
+    let bot = bot.clone();
+    tokio::spawn(async move {
+        tokio::time::sleep(expires_in).await;
+        // Answered prompts have no keyboard left, and Telegram refuses the
+        // no-op edit, so only unanswered prompts are marked as expired
+        if bot
+            .edit_message_reply_markup(chat_id, message.id)
+            .await
+            .is_ok()
+        {
+            let text = format!("{}\n\n⌛ Expired", prompt);
+            let _ = bot.edit_message_text(chat_id, message.id, text).await;
+        }
+    });
+
+    Ok(())
+}
+
+async fn handle_callback<S: Storage + 'static>(
+    bot: Bot,
+    query: CallbackQuery,
+    router: Router<S>,
+    config: TelegramConfig,
+) -> ResponseResult<()> {
+    if !config.allowed_users.is_empty() && !config.allowed_users.contains(&(query.from.id.0 as i64))
+    {
+        tracing::warn!("Unauthorized user attempt: {}", query.from.id);
+        return Ok(());
+    }
+
+    let Some((choice, request_id)) = query.data.as_deref().and_then(ApprovalChoice::parse) else {
+        bot.answer_callback_query(query.id).await?;
+        return Ok(());
+    };
+
+    let user_id = user_id_of(Some(&query.from));
+    let status = answer_approval(&router, &user_id, CHANNEL, choice, request_id).await;
+
+    bot.answer_callback_query(query.id).text(status).await?;
+
+    // Editing the text drops the inline keyboard
+    if let Some(message) = query.message {
+        let text = format!("{}\n\n{}", message.text().unwrap_or_default(), status);
+        bot.edit_message_text(message.chat.id, message.id, text)
+            .await?;
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_validate_credentials_calls_get_me() {
+        let mut server = mockito::Server::new_async().await;
+        let valid = server
+            .mock("POST", mockito::Matcher::Regex("(?i)^/botgood-token/getme$".into()))
+            .with_header("content-type", "application/json")
+            .with_body(
+                r#"{"ok":true,"result":{"id":42,"is_bot":true,"first_name":"Claw","username":"claw_bot","can_join_groups":true,"can_read_all_group_messages":false,"supports_inline_queries":false}}"#,
+            )
+            .create_async()
+            .await;
+        let rejected = server
+            .mock(
+                "POST",
+                mockito::Matcher::Regex("(?i)^/botbad-token/getme$".into()),
+            )
+            .with_status(401)
+            .with_header("content-type", "application/json")
+            .with_body(r#"{"ok":false,"error_code":401,"description":"Unauthorized"}"#)
+            .create_async()
+            .await;
+        let api_url = reqwest::Url::parse(&server.url()).unwrap();
+
+        let bot = Bot::new("good-token").set_api_url(api_url.clone());
+        assert_eq!(validate_bot(&bot).await.unwrap(), "@claw_bot");
+
+        let bot = Bot::new("bad-token").set_api_url(api_url);
+        let error = validate_bot(&bot).await.unwrap_err().to_string();
+        assert!(error.contains("Unauthorized"), "{}", error);
+
+        valid.assert_async().await;
+        rejected.assert_async().await;
+    }
+
+    #[test]
+    fn test_sandbox_button_only_when_available() {
+        let count = |markup: InlineKeyboardMarkup| markup.inline_keyboard[0].len();
+        assert_eq!(count(approval_keyboard("id", true)), 3);
+        assert_eq!(count(approval_keyboard("id", false)), 2);
+    }
+}
@@ -1,12 +1,23 @@
+use super::contact_cache::ContactCache;
+use super::format::{format_reply, split_message, Platform};
+use super::retry;
+use super::ChannelAdapter;
+use crate::config::{MessageFormat, PlaceholderConfig, RetryConfig};
+use crate::core::identity::{resolve_user_id, ChannelIdentity};
 use crate::core::Router;
-use crate::storage::Storage;
+use crate::storage::{FeedbackRating, Storage};
+use crate::tools::policy::ToolPolicyEngine;
 use anyhow::{Context, Result};
+use async_trait::async_trait;
 use serde::{Deserialize, Serialize};
 use std::fs;
 use std::path::{Path, PathBuf};
 use std::sync::Arc;
+use std::time::Duration;
 use tracing::{error, info};
+use wacore::download::MediaType;
 use wacore::types::events::Event;
+use wacore::types::message::MessageInfo;
 use wacore_binary::jid::Jid;
 use waproto::whatsapp as wa;
 use whatsapp_rust::bot::{Bot, MessageContext};
@@ -17,11 +28,171 @@ use whatsapp_rust_ureq_http_client::UreqHttpClient;
 #[cfg(unix)]
 use std::os::unix::fs::PermissionsExt;
 
+/// Default lifetime of cached contact verification results
+const DEFAULT_VERIFY_CACHE_TTL_SECS: u64 = 3600;
+const DEFAULT_MAX_MESSAGE_CHARS: usize = 4000;
+/// Name of the document a reply is sent as
+const REPLY_FILE_NAME: &str = "reply.txt";
+
+/// RustyClaw user ID of a sender, `whatsapp:<account>:<phone>` unless mapped
+fn user_id_of(account_id: &str, phone: &str) -> String {
+    resolve_user_id(&ChannelIdentity::new("whatsapp", phone).with_account(account_id))
+}
+
+/// Whether "typing…" is shown while a reply is generated: with receipts, or
+/// in place of a placeholder message
+fn shows_typing(config: &WhatsAppConfig) -> bool {
+    config.send_receipts || config.placeholder.enabled
+}
+
+/// Whether a sender's session runs in elevated mode: only the account
+/// owner's, and only with `auto_elevate_self` on
+fn auto_elevated(config: &WhatsAppConfig, sender_phone: &str) -> bool {
+    config.auto_elevate_self
+        && !config.phone_number.is_empty()
+        && sender_phone == config.phone_number
+}
+
+/// How a reply is sent
+#[derive(Debug, PartialEq)]
+enum Delivery {
+    /// Text messages, in order
+    Messages(Vec<String>),
+    /// A `.txt` document holding the reply's Markdown
+    Document,
+}
+
+/// Whether a reply has code blocks or tables, which read better in a file
+/// than cut across messages
+fn is_structured(markdown: &str) -> bool {
+    markdown.contains("```")
+        || markdown
+            .lines()
+            .any(|line| line.trim_start().starts_with('|'))
+}
+
+/// A formatted reply in parts of at most `max_message_chars`
+fn split_reply(config: &WhatsAppConfig, formatted: &str) -> Vec<String> {
+    if config.max_message_chars == 0 || formatted.chars().count() <= config.max_message_chars {
+        return vec![formatted.to_string()];
+    }
+    split_message(formatted, config.max_message_chars)
+}
+
+/// How a reply is sent: as a document when `document_fallback_chars` is set
+/// and the reply is longer, or structured and too long for one message;
+/// otherwise as messages of at most `max_message_chars`
+fn delivery(config: &WhatsAppConfig, markdown: &str, formatted: &str) -> Delivery {
+    let chars = formatted.chars().count();
+    let too_long = config.max_message_chars > 0 && chars > config.max_message_chars;
+    if config
+        .document_fallback_chars
+        .is_some_and(|threshold| chars > threshold || (too_long && is_structured(markdown)))
+    {
+        return Delivery::Document;
+    }
+    Delivery::Messages(split_reply(config, formatted))
+}
+
+/// Upload a reply's Markdown as a `.txt` document
+async fn document_message(client: &whatsapp_rust::Client, markdown: &str) -> Result<wa::Message> {
+    let upload = client
+        .upload(markdown.as_bytes().to_vec(), MediaType::Document)
+        .await
+        .context("Failed to upload reply document")?;
+    Ok(wa::Message {
+        document_message: Some(Box::new(wa::message::DocumentMessage {
+            url: Some(upload.url),
+            direct_path: Some(upload.direct_path),
+            media_key: Some(upload.media_key),
+            file_enc_sha256: Some(upload.file_enc_sha256),
+            file_sha256: Some(upload.file_sha256),
+            file_length: Some(upload.file_length),
+            mimetype: Some("text/plain".to_string()),
+            file_name: Some(REPLY_FILE_NAME.to_string()),
+            ..Default::default()
+        })),
+        ..Default::default()
+    })
+}
+
+/// Send a reply to the chat a message came from, split or as a document as
+/// configured. A document that cannot be uploaded is sent as messages.
+async fn send_reply(ctx: &MessageContext, config: &WhatsAppConfig, markdown: &str) -> Result<()> {
+    let formatted = format_reply(markdown, Platform::WhatsApp, config.format);
+    let parts = match delivery(config, markdown, &formatted) {
+        Delivery::Messages(parts) => parts,
+        Delivery::Document => match document_message(&ctx.client, markdown).await {
+            Ok(document) => {
+                ctx.send_message(document).await?;
+                return Ok(());
+            }
+            Err(e) => {
+                error!("{:#}; sending the reply as messages", e);
+                split_reply(config, &formatted)
+            }
+        },
+    };
+    for part in parts {
+        ctx.send_message(wa::Message {
+            conversation: Some(part),
+            ..Default::default()
+        })
+        .await?;
+    }
+    Ok(())
+}
+
+/// Enable elevated mode for a user's WhatsApp session
+async fn elevate_session<S: Storage + 'static>(
+    router: &Router<S>,
+    policy: &ToolPolicyEngine,
+    user_id: &str,
+) {
+    match router.get_or_create_session_api(user_id, "whatsapp").await {
+        Ok(session) => policy.set_elevated(&session.id, true).await,
+        Err(e) => error!("Failed to load session to elevate: {}", e),
+    }
+}
+
+/// Prepare the session an incoming message is handled in and return its
+/// user id. The owner's own session is elevated, so their tools run without
+/// approval.
+async fn open_sender_session<S: Storage + 'static>(
+    router: &Router<S>,
+    policy: Option<&ToolPolicyEngine>,
+    config: &WhatsAppConfig,
+    account_id: &str,
+    sender_phone: &str,
+) -> String {
+    let user_id = user_id_of(account_id, sender_phone);
+    if let (Some(policy), true) = (policy, auto_elevated(config, sender_phone)) {
+        elevate_session(router, policy, &user_id).await;
+    }
+    note_session_account(router, &user_id, account_id).await;
+    user_id
+}
+
+/// Remember the account a user's WhatsApp session is reached on, so messages
+/// the model sends from it go out from the same account
+async fn note_session_account<S: Storage + 'static>(
+    router: &Router<S>,
+    user_id: &str,
+    account_id: &str,
+) {
+    match router.get_or_create_session_api(user_id, "whatsapp").await {
+        Ok(session) => crate::tools::whatsapp::set_session_account(&session.id, account_id),
+        Err(e) => error!("Failed to load session for its account: {}", e),
+    }
+}
+
 /// Service for sending outbound WhatsApp messages
 #[derive(Clone)]
 pub struct WhatsAppService {
     #[allow(dead_code)]
     client: Arc<whatsapp_rust::Client>,
+    retry: RetryConfig,
+    contact_cache: Arc<ContactCache>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -31,9 +202,194 @@ pub struct GroupInfo {
     pub participant_count: usize,
 }
 
+/// Result of checking whether a phone number is registered on WhatsApp
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ContactVerification {
+    pub phone: String,
+    /// JID when registered, `None` otherwise
+    pub jid: Option<String>,
+    /// Whether the result came from the verification cache
+    pub cached: bool,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct GroupParticipantInfo {
+    pub jid: String,
+    pub is_admin: bool,
+}
+
+/// Turn permission failures from group mutations into a clear message
+fn group_admin_error(group: &str, err: anyhow::Error) -> anyhow::Error {
+    let msg = format!("{:#}", err).to_lowercase();
+    if msg.contains("not-authorized") || msg.contains("forbidden") || msg.contains("403") {
+        anyhow::anyhow!(
+            "Cannot change participants of group '{}': this account is not a group admin",
+            group
+        )
+    } else {
+        err.context(format!(
+            "Failed to update participants of group '{}'",
+            group
+        ))
+    }
+}
+
+/// Structured failure for outbound WhatsApp sends
+#[derive(Debug, thiserror::Error)]
+pub enum WhatsAppSendError {
+    #[error("Invalid WhatsApp recipient '{target}': {reason}")]
+    InvalidRecipient { target: String, reason: String },
+
+    #[error("WhatsApp message to {jid} was not sent: {reason}")]
+    Rejected { jid: String, reason: String },
+
+    #[error("WhatsApp message to {jid} failed after {attempts} attempts: {reason}")]
+    RetriesExhausted {
+        jid: String,
+        attempts: u32,
+        reason: String,
+    },
+}
+
+/// Whether a send error is a transient failure worth retrying.
+///
+/// Only failures where the message cannot have left the client, or that the
+/// server refused outright, are retried. Errors while or after writing it
+/// (lost connections, timeouts) are ambiguous, since the server may have
+/// accepted the message before the ack was lost, so they are reported
+/// rather than retried.
+fn is_retryable_send_error(err: &anyhow::Error) -> bool {
+    let msg = format!("{:#}", err).to_lowercase();
+
+    if msg.contains("timeout") || msg.contains("timed out") {
+        return false;
+    }
+
+    ["not connected", "rate-overlimit", "temporarily unavailable"]
+        .iter()
+        .any(|marker| msg.contains(marker))
+}
+
+/// Sends a prepared message under a given ID, so every attempt of a retried
+/// send is the same message to the server
+#[async_trait]
+trait MessageSender: Sync {
+    async fn new_message_id(&self) -> String;
+    async fn send_with_id(&self, jid: Jid, message: &wa::Message, message_id: &str) -> Result<()>;
+}
+
+#[async_trait]
+impl MessageSender for whatsapp_rust::Client {
+    async fn new_message_id(&self) -> String {
+        self.generate_message_id().await
+    }
+
+    async fn send_with_id(&self, jid: Jid, message: &wa::Message, message_id: &str) -> Result<()> {
+        self.send_message_impl(
+            jid,
+            message,
+            Some(message_id.to_string()),
+            false,
+            false,
+            None,
+        )
+        .await
+        .map_err(anyhow::Error::from)
+    }
+}
+
+/// Send a text message, retrying transient failures. The message and its ID
+/// are built once, so a retry cannot deliver a second copy.
+async fn send_with_retry(
+    sender: &impl MessageSender,
+    retry: &RetryConfig,
+    jid: Jid,
+    message: &str,
+) -> Result<String> {
+    let message_id = sender.new_message_id().await;
+    let msg = wa::Message {
+        conversation: Some(message.to_string()),
+        ..Default::default()
+    };
+
+    let result = retry::retry_with_backoff(retry, is_retryable_send_error, |_| {
+        sender.send_with_id(jid.clone(), &msg, &message_id)
+    })
+    .await;
+
+    match result {
+        Ok(()) => Ok(message_id),
+        Err(e) => {
+            let reason = format!("{:#}", e.error);
+            let jid = jid.to_string();
+            error!("WhatsApp send to {} failed: {}", jid, reason);
+
+            let err = if e.retryable {
+                WhatsAppSendError::RetriesExhausted {
+                    jid,
+                    attempts: e.attempts,
+                    reason,
+                }
+            } else {
+                WhatsAppSendError::Rejected { jid, reason }
+            };
+            Err(err.into())
+        }
+    }
+}
+
+/// Mark an incoming message read (blue ticks). Failures are only logged.
+async fn mark_read(client: &whatsapp_rust::Client, info: &MessageInfo) {
+    // Group receipts name the participant who sent the message
+    let sender = info.source.is_group.then_some(&info.source.sender);
+    if let Err(e) = client
+        .mark_as_read(&info.source.chat, sender, vec![info.id.clone()])
+        .await
+    {
+        tracing::warn!("Failed to mark WhatsApp message {} read: {}", info.id, e);
+    }
+}
+
+/// Show or clear "typing…" in a chat. Failures are only logged.
+async fn set_composing(client: &whatsapp_rust::Client, chat: &Jid, composing: bool) {
+    let result = if composing {
+        client.chatstate().send_composing(chat).await
+    } else {
+        client.chatstate().send_paused(chat).await
+    };
+    if let Err(e) = result {
+        tracing::warn!("Failed to update WhatsApp chat state in {}: {}", chat, e);
+    }
+}
+
+/// Appear online, so contacts see the account as available
+async fn set_available(client: &whatsapp_rust::Client) {
+    if let Err(e) = client.presence().set_available().await {
+        tracing::warn!("Failed to set WhatsApp presence: {}", e);
+    }
+}
+
 impl WhatsAppService {
     pub fn new(client: Arc<whatsapp_rust::Client>) -> Self {
-        Self { client }
+        Self {
+            client,
+            retry: RetryConfig::default(),
+            contact_cache: Arc::new(ContactCache::new(Duration::from_secs(
+                DEFAULT_VERIFY_CACHE_TTL_SECS,
+            ))),
+        }
+    }
+
+    /// Set how long contact verification results are cached (zero disables)
+    pub fn with_verify_cache_ttl(mut self, ttl: Duration) -> Self {
+        self.contact_cache = Arc::new(ContactCache::new(ttl));
+        self
+    }
+
+    /// Set the retry policy for outbound sends
+    pub fn with_retry(mut self, retry: RetryConfig) -> Self {
+        self.retry = retry;
+        self
     }
 
     /// Send message to a contact by phone number
@@ -44,20 +400,12 @@ impl WhatsAppService {
         // Parse JID
         let jid = jid_str
             .parse::<Jid>()
-            .context("Invalid phone number format - must be numeric")?;
+            .map_err(|e| WhatsAppSendError::InvalidRecipient {
+                target: phone.to_string(),
+                reason: format!("phone number must be numeric ({})", e),
+            })?;
 
-        // Create message
-        let msg = wa::Message {
-            conversation: Some(message.to_string()),
-            ..Default::default()
-        };
-
-        // Send message using the Client API
-        let message_id = self
-            .client
-            .send_message(jid.clone(), msg)
-            .await
-            .context("Failed to send WhatsApp message to contact")?;
+        let message_id = self.send_with_retry(jid, message).await?;
 
         info!("✓ Sent WhatsApp message to {}: ID={}", jid_str, message_id);
 
@@ -71,24 +419,16 @@ impl WhatsAppService {
             // Direct JID provided
             group_identifier
                 .parse::<Jid>()
-                .context("Invalid group JID format")?
+                .map_err(|e| WhatsAppSendError::InvalidRecipient {
+                    target: group_identifier.to_string(),
+                    reason: format!("invalid group JID ({})", e),
+                })?
         } else {
             // Look up group by name
             self.find_group_by_name(group_identifier).await?
         };
 
-        // Create message
-        let msg = wa::Message {
-            conversation: Some(message.to_string()),
-            ..Default::default()
-        };
-
-        // Send message
-        let message_id = self
-            .client
-            .send_message(jid.clone(), msg)
-            .await
-            .context("Failed to send WhatsApp message to group")?;
+        let message_id = self.send_with_retry(jid.clone(), message).await?;
 
         info!(
             "✓ Sent WhatsApp message to group {}: ID={}",
@@ -98,6 +438,11 @@ impl WhatsAppService {
         Ok(message_id)
     }
 
+    /// Send a text message, retrying transient failures
+    async fn send_with_retry(&self, jid: Jid, message: &str) -> Result<String> {
+        send_with_retry(self.client.as_ref(), &self.retry, jid, message).await
+    }
+
     /// List all groups
     pub async fn list_groups(&self) -> Result<Vec<GroupInfo>> {
         let groups = self
@@ -122,23 +467,171 @@ impl WhatsAppService {
 
     /// Verify if a phone number is on WhatsApp
     pub async fn verify_contact(&self, phone: &str) -> Result<Option<String>> {
-        let results = self
-            .client
-            .contacts()
-            .is_on_whatsapp(&[phone])
-            .await
-            .context("Failed to verify contact")?;
+        let results = self.verify_contacts(&[phone]).await?;
+        Ok(results.into_iter().next().and_then(|r| r.jid))
+    }
 
-        match results.first() {
-            Some(result) if result.is_registered => {
-                info!("✓ Contact {} is registered on WhatsApp", phone);
-                Ok(Some(result.jid.to_string()))
+    /// Verify several phone numbers at once.
+    ///
+    /// Numbers are deduplicated, cached results are reused, and the remaining
+    /// numbers are checked with a single batched lookup.
+    pub async fn verify_contacts(&self, phones: &[&str]) -> Result<Vec<ContactVerification>> {
+        let mut unique: Vec<String> = Vec::new();
+        for phone in phones {
+            let key = ContactCache::key(phone);
+            if !key.is_empty() && !unique.contains(&key) {
+                unique.push(key);
+            }
+        }
+
+        let mut results = Vec::with_capacity(unique.len());
+        let mut misses = Vec::new();
+        for phone in &unique {
+            match self.contact_cache.get(phone) {
+                Some(jid) => results.push(ContactVerification {
+                    phone: phone.clone(),
+                    jid,
+                    cached: true,
+                }),
+                None => misses.push(phone.as_str()),
             }
-            _ => {
-                info!("Contact {} is not on WhatsApp", phone);
-                Ok(None)
+        }
+
+        if !misses.is_empty() {
+            let lookups = self
+                .client
+                .contacts()
+                .is_on_whatsapp(&misses)
+                .await
+                .context("Failed to verify contacts")?;
+
+            for phone in misses {
+                let jid = lookups
+                    .iter()
+                    .filter(|r| r.is_registered)
+                    .map(|r| r.jid.to_string())
+                    .find(|jid| jid.split('@').next() == Some(phone));
+
+                self.contact_cache.insert(phone, jid.clone());
+                results.push(ContactVerification {
+                    phone: phone.to_string(),
+                    jid,
+                    cached: false,
+                });
             }
         }
+
+        info!(
+            "✓ Verified {} contact(s) ({} from cache)",
+            results.len(),
+            results.iter().filter(|r| r.cached).count()
+        );
+        Ok(results)
+    }
+
+    /// List the participants of a group (by JID or name)
+    pub async fn list_group_participants(
+        &self,
+        group_identifier: &str,
+    ) -> Result<Vec<GroupParticipantInfo>> {
+        let jid = self.resolve_group(group_identifier).await?;
+
+        let groups = self
+            .client
+            .groups()
+            .get_participating()
+            .await
+            .context("Failed to fetch participating groups")?;
+
+        let metadata = groups
+            .into_values()
+            .find(|metadata| metadata.id == jid)
+            .context(format!("Group '{}' not found", group_identifier))?;
+
+        let participants: Vec<GroupParticipantInfo> = metadata
+            .participants
+            .iter()
+            .map(|p| GroupParticipantInfo {
+                jid: p.jid.to_string(),
+                is_admin: p.is_admin,
+            })
+            .collect();
+
+        info!(
+            "✓ Fetched {} participants for group {}",
+            participants.len(),
+            jid
+        );
+        Ok(participants)
+    }
+
+    /// Add contacts (by phone number) to a group
+    pub async fn add_group_participants(
+        &self,
+        group_identifier: &str,
+        phones: &[String],
+    ) -> Result<()> {
+        let jid = self.resolve_group(group_identifier).await?;
+        let participants = Self::participant_jids(phones)?;
+
+        self.client
+            .groups()
+            .add_participants(&jid, &participants)
+            .await
+            .map_err(|e| group_admin_error(group_identifier, anyhow::Error::from(e)))?;
+
+        info!("✓ Added {} participant(s) to group {}", phones.len(), jid);
+        Ok(())
+    }
+
+    /// Remove contacts (by phone number) from a group
+    pub async fn remove_group_participants(
+        &self,
+        group_identifier: &str,
+        phones: &[String],
+    ) -> Result<()> {
+        let jid = self.resolve_group(group_identifier).await?;
+        let participants = Self::participant_jids(phones)?;
+
+        self.client
+            .groups()
+            .remove_participants(&jid, &participants)
+            .await
+            .map_err(|e| group_admin_error(group_identifier, anyhow::Error::from(e)))?;
+
+        info!(
+            "✓ Removed {} participant(s) from group {}",
+            phones.len(),
+            jid
+        );
+        Ok(())
+    }
+
+    /// Resolve a group JID from either a JID string or a group name
+    async fn resolve_group(&self, group_identifier: &str) -> Result<Jid> {
+        if group_identifier.contains('@') {
+            group_identifier
+                .parse::<Jid>()
+                .context("Invalid group JID format")
+        } else {
+            self.find_group_by_name(group_identifier).await
+        }
+    }
+
+    /// Convert phone numbers into user JIDs
+    fn participant_jids(phones: &[String]) -> Result<Vec<Jid>> {
+        if phones.is_empty() {
+            anyhow::bail!("At least one participant phone number is required");
+        }
+
+        phones
+            .iter()
+            .map(|phone| {
+                format!("{}@s.whatsapp.net", phone.trim_start_matches('+'))
+                    .parse::<Jid>()
+                    .context(format!("Invalid phone number '{}'", phone))
+            })
+            .collect()
     }
 
     /// Find group JID by name (case-insensitive)
@@ -178,15 +671,51 @@ pub struct WhatsAppConfig {
     /// Enable self-chat mode (only respond to messages from yourself)
     #[serde(default = "default_self_chat_mode")]
     pub self_chat_mode: bool,
+    /// Run the owner's own messages in elevated mode
+    #[serde(default)]
+    pub auto_elevate_self: bool,
     /// Account ID for multi-account support (defaults to phone number)
     #[serde(default)]
     pub account_id: Option<String>,
+    /// Retry behaviour for outbound sends
+    #[serde(default)]
+    pub send_retry: RetryConfig,
+    /// Seconds to cache contact verification results (0 disables caching)
+    #[serde(default = "default_verify_cache_ttl_secs")]
+    pub verify_cache_ttl_secs: u64,
+    /// Send read receipts and presence (online, typing) updates
+    #[serde(default = "default_send_receipts")]
+    pub send_receipts: bool,
+    /// How the assistant's Markdown is sent
+    #[serde(default)]
+    pub format: MessageFormat,
+    /// Shown as "typing…", since sent messages cannot be edited
+    #[serde(default)]
+    pub placeholder: PlaceholderConfig,
+    /// Longer replies are split into several messages (0 sends them whole)
+    #[serde(default = "default_max_message_chars")]
+    pub max_message_chars: usize,
+    /// Replies longer than this are sent as a `.txt` document
+    #[serde(default)]
+    pub document_fallback_chars: Option<usize>,
 }
 
 fn default_self_chat_mode() -> bool {
     true
 }
 
+fn default_send_receipts() -> bool {
+    true
+}
+
+fn default_verify_cache_ttl_secs() -> u64 {
+    DEFAULT_VERIFY_CACHE_TTL_SECS
+}
+
+fn default_max_message_chars() -> usize {
+    DEFAULT_MAX_MESSAGE_CHARS
+}
+
 impl<S: Storage + 'static> WhatsAppAdapter<S> {
     /// Get the credentials directory path (~/.rustyclaw/whatsapp)
     fn creds_dir() -> Result<PathBuf> {
@@ -246,7 +775,15 @@ impl<S: Storage + 'static> WhatsAppAdapter<S> {
             enabled: channel_config.enabled,
             phone_number: channel_config.phone_number,
             self_chat_mode: channel_config.self_chat_mode,
+            auto_elevate_self: channel_config.auto_elevate_self,
             account_id: channel_config.account_id,
+            send_retry: channel_config.send_retry,
+            verify_cache_ttl_secs: channel_config.verify_cache_ttl_secs,
+            send_receipts: channel_config.send_receipts,
+            format: channel_config.format,
+            placeholder: channel_config.placeholder,
+            max_message_chars: channel_config.max_message_chars,
+            document_fallback_chars: channel_config.document_fallback_chars,
         })
     }
 
@@ -382,7 +919,8 @@ impl<S: Storage + 'static> WhatsAppAdapter<S> {
         // Set up network transport
         let transport_factory = TokioWebSocketTransportFactory::new();
 
-        // Set up HTTP client for media operations
+        // Set up HTTP client for media operations (proxied through the
+        // HTTP(S)_PROXY variables exported by `network::init_network`)
         let http_client = UreqHttpClient::new();
 
         // Clone router and config for event handler (needed for 'static closure)
@@ -410,12 +948,38 @@ impl<S: Storage + 'static> WhatsAppAdapter<S> {
                                 .strip_suffix("@s.whatsapp.net")
                                 .unwrap_or(&sender_jid);
 
+                            // A thumbs-up/down reaction rates the latest reply
+                            if let Some(reaction) = message.reaction_message.as_ref() {
+                                let from_self = sender_phone == config.phone_number;
+                                let rating = reaction
+                                    .text
+                                    .as_deref()
+                                    .and_then(FeedbackRating::from_emoji);
+                                if let (Some(rating), true) =
+                                    (rating, from_self || !config.self_chat_mode)
+                                {
+                                    let user_id = user_id_of(&account_id, sender_phone);
+                                    if let Err(e) = router
+                                        .rate_latest_response(&user_id, "whatsapp", rating)
+                                        .await
+                                    {
+                                        error!("Failed to store WhatsApp feedback: {}", e);
+                                    }
+                                }
+                                return;
+                            }
+
                             // Get text content from the message
                             if let Some(text) = message.conversation.clone() {
                                 if text.trim().is_empty() {
                                     return;
                                 }
 
+                                // Paused channels stay silent
+                                if router.is_channel_paused("whatsapp").await {
+                                    return;
+                                }
+
                                 // SELF-CHAT MODE FILTER
                                 if config.self_chat_mode {
                                     // Only process messages from yourself
@@ -432,13 +996,14 @@ impl<S: Storage + 'static> WhatsAppAdapter<S> {
                                     info!("WhatsApp message from {}: {}", sender_jid, text);
                                 }
 
-                                // Create user_id for session
-                                // Format: whatsapp:<account_id>:<sender_phone>
-                                let user_id = format!(
-                                    "whatsapp:{}:{}",
-                                    account_id,
-                                    sender_phone
-                                );
+                                let user_id = open_sender_session(
+                                    &router,
+                                    crate::get_tool_policy_engine().as_deref(),
+                                    &config,
+                                    &account_id,
+                                    sender_phone,
+                                )
+                                .await;
 
                                 // Create message context for sending reply
                                 let ctx = MessageContext {
@@ -447,17 +1012,28 @@ impl<S: Storage + 'static> WhatsAppAdapter<S> {
                                     client: _client.clone(),
                                 };
 
+                                // Read ticks now, "typing…" while the reply is generated
+                                if config.send_receipts {
+                                    mark_read(&_client, &info).await;
+                                }
+                                if shows_typing(&config) {
+                                    set_composing(&_client, &info.source.chat, true).await;
+                                }
+
                                 // Process message through router
-                                match router.handle_message(&user_id, "whatsapp", &text).await {
+                                let result =
+                                    router.handle_message(&user_id, "whatsapp", &text).await;
+                                if shows_typing(&config) {
+                                    set_composing(&_client, &info.source.chat, false).await;
+                                }
+                                match result {
+                                    // Empty when a plugin hook blocked the reply
+                                    Ok(response) if response.content.is_empty() => {}
                                     Ok(response) => {
-                                        // Create response message
-                                        let reply = wa::Message {
-                                            conversation: Some(response.content),
-                                            ..Default::default()
-                                        };
-
                                         // Send response (works for 1-on-1 and groups)
-                                        if let Err(e) = ctx.send_message(reply).await {
+                                        if let Err(e) =
+                                            send_reply(&ctx, &config, &response.content).await
+                                        {
                                             error!(
                                                 "Failed to send WhatsApp response to {}: {}",
                                                 sender_jid, e
@@ -487,22 +1063,24 @@ impl<S: Storage + 'static> WhatsAppAdapter<S> {
                         Event::Connected(_) => {
                             info!("✅ WhatsApp account '{}' connected successfully!", account_id);
 
+                            if config.send_receipts {
+                                set_available(&_client).await;
+                            }
+
                             // Send welcome message to self in self-chat mode
                             if config.self_chat_mode {
                                 let jid_str = format!("{}@s.whatsapp.net", config.phone_number);
                                 match jid_str.parse::<Jid>() {
                                     Ok(self_jid) => {
+                                        let user_id =
+                                            user_id_of(&account_id, &config.phone_number);
+                                        let help = router.help_text(&user_id, "whatsapp").await;
                                         let welcome = wa::Message {
                                             conversation: Some(format!(
                                                 "🤖 RustyClaw connected (account: {})!\n\n\
-                                                You can now send me messages here to interact with the LLM.\n\n\
                                                 This is SELF-CHAT MODE - only messages you send to yourself are processed.\n\n\
-                                                Try:\n\
-                                                • Ask questions: 'What is Rust?'\n\
-                                                • Send messages: 'Send WhatsApp to John: Meeting at 3pm'\n\
-                                                • List groups: 'What WhatsApp groups do I have?'\n\
-                                                • List accounts: 'What WhatsApp accounts are connected?'",
-                                                account_id
+                                                {}",
+                                                account_id, help
                                             )),
                                             ..Default::default()
                                         };
@@ -531,7 +1109,11 @@ impl<S: Storage + 'static> WhatsAppAdapter<S> {
             .context("Failed to initialize WhatsApp bot")?;
 
         // Create and register WhatsApp service for outbound messaging
-        let service = Arc::new(WhatsAppService::new(bot.client().clone()));
+        let service = Arc::new(
+            WhatsAppService::new(bot.client().clone())
+                .with_retry(self.config.send_retry.clone())
+                .with_verify_cache_ttl(Duration::from_secs(self.config.verify_cache_ttl_secs)),
+        );
 
         // Register service for this account
         crate::register_whatsapp_service(self.account_id.clone(), service);
@@ -556,6 +1138,88 @@ impl<S: Storage + 'static> WhatsAppAdapter<S> {
     }
 }
 
+#[async_trait]
+impl<S: Storage + 'static> ChannelAdapter for WhatsAppAdapter<S> {
+    fn name(&self) -> &'static str {
+        "whatsapp"
+    }
+
+    async fn run(&self) -> Result<()> {
+        WhatsAppAdapter::run(self).await
+    }
+
+    async fn send(&self, target: &str, text: &str) -> Result<()> {
+        send_text(
+            &self.config.phone_number,
+            target,
+            text,
+            self.config.format,
+            self.config.max_message_chars,
+        )
+        .await
+    }
+
+    async fn validate_credentials(&self) -> Result<String> {
+        check_pairing(&self.config.phone_number)
+    }
+}
+
+/// Send a message through the connected account outside of any
+/// conversation. `target` is a phone number, a group, or `self` for
+/// `own_number`. Text longer than `max_message_chars` (unless 0) is sent as
+/// several messages.
+pub async fn send_text(
+    own_number: &str,
+    target: &str,
+    text: &str,
+    format: MessageFormat,
+    max_message_chars: usize,
+) -> Result<()> {
+    let text = format_reply(text, Platform::WhatsApp, format);
+    let parts = if max_message_chars == 0 {
+        vec![text]
+    } else {
+        split_message(&text, max_message_chars)
+    };
+    let service = crate::get_whatsapp_service()
+        .ok_or_else(|| anyhow::anyhow!("WhatsApp is not connected"))?;
+    let phone = match target {
+        "self" => own_number,
+        other => other.trim_start_matches('+'),
+    };
+    for part in &parts {
+        if !phone.is_empty() && phone.chars().all(|c| c.is_ascii_digit()) {
+            service.send_to_contact(phone, part).await?;
+        } else {
+            service.send_to_group(target, part).await?;
+        }
+    }
+    Ok(())
+}
+
+/// Check that the account is configured and has been paired with
+/// `rustyclaw channels connect whatsapp`, without connecting. Returns the
+/// credentials path.
+pub fn validate_credentials(config: &crate::config::WhatsAppChannelConfig) -> Result<String> {
+    check_pairing(&config.phone_number)
+}
+
+fn check_pairing(phone_number: &str) -> Result<String> {
+    if phone_number.trim().is_empty() {
+        anyhow::bail!("WhatsApp phone number not configured");
+    }
+    let creds =
+        WhatsAppAdapter::<crate::storage::sqlite::SqliteStorage>::creds_dir()?.join("creds.json");
+    match fs::metadata(&creds) {
+        Ok(metadata) if metadata.len() > 0 => Ok(creds.display().to_string()),
+        Ok(_) => anyhow::bail!("WhatsApp credentials at {} are empty", creds.display()),
+        Err(_) => anyhow::bail!(
+            "No WhatsApp credentials at {}; run `rustyclaw channels connect whatsapp`",
+            creds.display()
+        ),
+    }
+}
+
 /// Standalone CLI function for WhatsApp connection
 pub async fn connect_whatsapp_cli() -> Result<()> {
     WhatsAppAdapter::<crate::storage::sqlite::SqliteStorage>::connect_cli_internal().await
@@ -620,6 +1284,17 @@ mod tests {
                 .cloned())
         }
 
+        async fn list_user_sessions(&self, user_id: &str) -> Result<Vec<crate::storage::Session>> {
+            let sessions = self.sessions.lock().unwrap();
+            let mut user_sessions: Vec<crate::storage::Session> = sessions
+                .iter()
+                .filter(|s| s.user_id == user_id)
+                .cloned()
+                .collect();
+            user_sessions.sort_by(|a, b| b.updated_at.cmp(&a.updated_at));
+            Ok(user_sessions)
+        }
+
         async fn get_messages(
             &self,
             session_id: &str,
@@ -639,17 +1314,77 @@ mod tests {
             Ok(session_messages)
         }
 
+        async fn list_active_sessions(
+            &self,
+            _since: Option<chrono::DateTime<chrono::Utc>>,
+            _limit: usize,
+        ) -> Result<Vec<crate::storage::Session>> {
+            Ok(vec![])
+        }
+
+        async fn get_message(&self, id: &str) -> Result<Option<crate::storage::Message>> {
+            let messages = self.messages.lock().unwrap();
+            Ok(messages.iter().find(|m| m.id == id).cloned())
+        }
+
         async fn add_message(&self, message: crate::storage::Message) -> Result<()> {
             let mut messages = self.messages.lock().unwrap();
             messages.push(message);
             Ok(())
         }
+        async fn copy_messages_until(
+            &self,
+            _src: &str,
+            _dst: &str,
+            _message_id: &str,
+        ) -> Result<Option<usize>> {
+            Ok(None)
+        }
 
         async fn delete_session_messages(&self, session_id: &str) -> Result<()> {
             let mut messages = self.messages.lock().unwrap();
             messages.retain(|m| m.session_id != session_id);
             Ok(())
         }
+        async fn add_session_note(&self, _note: crate::storage::SessionNote) -> Result<()> {
+            Ok(())
+        }
+        async fn list_session_notes(
+            &self,
+            _session_id: &str,
+        ) -> Result<Vec<crate::storage::SessionNote>> {
+            Ok(vec![])
+        }
+        async fn delete_session_note(&self, _session_id: &str, _note_id: &str) -> Result<bool> {
+            Ok(false)
+        }
+        async fn get_session_summary(
+            &self,
+            _session_id: &str,
+        ) -> Result<Option<crate::storage::SessionSummary>> {
+            Ok(None)
+        }
+        async fn save_session_summary(
+            &self,
+            _summary: &crate::storage::SessionSummary,
+        ) -> Result<()> {
+            Ok(())
+        }
+
+        async fn delete_session(&self, session_id: &str) -> Result<()> {
+            self.messages
+                .lock()
+                .unwrap()
+                .retain(|m| m.session_id != session_id);
+            self.sessions.lock().unwrap().retain(|s| s.id != session_id);
+            Ok(())
+        }
+        async fn delete_idle_sessions(
+            &self,
+            _idle_before: chrono::DateTime<chrono::Utc>,
+        ) -> Result<Vec<crate::storage::Session>> {
+            Ok(vec![])
+        }
 
         // Identity mock implementation
         async fn get_user(&self, _id: &str) -> Result<Option<crate::storage::User>> {
@@ -678,21 +1413,146 @@ mod tests {
         async fn create_identity(&self, _identity: crate::storage::Identity) -> Result<()> {
             Ok(())
         }
-        async fn list_identities(&self, _user_id: &str) -> Result<Vec<crate::storage::Identity>> {
-            Ok(vec![])
+        async fn list_identities(
+            &self,
+            _user_id: &str,
+            _provider: Option<&str>,
+            _page: crate::storage::Page,
+        ) -> Result<crate::storage::Paged<crate::storage::Identity>> {
+            Ok(crate::storage::Paged {
+                items: vec![],
+                total: 0,
+            })
         }
 
-        async fn create_pending_link(
+        async fn create_pending_link(&self, _link: crate::storage::PendingLink) -> Result<()> {
+            Ok(())
+        }
+        async fn create_impersonation(
+            &self,
+            _impersonation: &crate::storage::Impersonation,
+        ) -> Result<()> {
+            Ok(())
+        }
+        async fn get_impersonation(
+            &self,
+            _token: &str,
+        ) -> Result<Option<crate::storage::Impersonation>> {
+            Ok(None)
+        }
+        async fn list_impersonations(
+            &self,
+            _limit: usize,
+        ) -> Result<Vec<crate::storage::Impersonation>> {
+            Ok(vec![])
+        }
+        async fn get_pending_link(
+            &self,
+            _code: &str,
+        ) -> Result<Option<crate::storage::PendingLink>> {
+            Ok(None)
+        }
+        async fn use_pending_link(
             &self,
             _code: &str,
+        ) -> Result<Option<crate::storage::PendingLink>> {
+            Ok(None)
+        }
+
+        async fn get_user_tool_policies(
+            &self,
             _user_id: &str,
-            _provider: &str,
+        ) -> Result<std::collections::HashMap<String, String>> {
+            Ok(Default::default())
+        }
+        async fn set_user_tool_policies(
+            &self,
+            _user_id: &str,
+            _policies: &std::collections::HashMap<String, String>,
         ) -> Result<()> {
             Ok(())
         }
-        async fn get_pending_link(&self, _code: &str) -> Result<Option<(String, String)>> {
+        async fn list_user_tool_policies(
+            &self,
+        ) -> Result<std::collections::HashMap<String, std::collections::HashMap<String, String>>>
+        {
+            Ok(Default::default())
+        }
+        async fn replace_document_chunks(
+            &self,
+            _source: &str,
+            _chunks: Vec<crate::storage::DocumentChunk>,
+        ) -> Result<()> {
+            Ok(())
+        }
+        async fn delete_document_chunks(&self, _source: &str) -> Result<()> {
+            Ok(())
+        }
+        async fn list_document_chunks(&self) -> Result<Vec<crate::storage::DocumentChunk>> {
+            Ok(vec![])
+        }
+        async fn set_message_feedback(
+            &self,
+            _feedback: crate::storage::MessageFeedback,
+        ) -> Result<()> {
+            Ok(())
+        }
+        async fn feedback_summary(&self) -> Result<crate::storage::FeedbackSummary> {
+            Ok(Default::default())
+        }
+        async fn positively_rated_sessions(&self) -> Result<std::collections::HashSet<String>> {
+            Ok(Default::default())
+        }
+        async fn create_schedule(&self, _schedule: crate::storage::Schedule) -> Result<()> {
+            Ok(())
+        }
+        async fn get_schedule(&self, _id: &str) -> Result<Option<crate::storage::Schedule>> {
             Ok(None)
         }
+        async fn list_schedules(
+            &self,
+            _user_id: Option<&str>,
+        ) -> Result<Vec<crate::storage::Schedule>> {
+            Ok(vec![])
+        }
+        async fn update_schedule(&self, _schedule: crate::storage::Schedule) -> Result<bool> {
+            Ok(false)
+        }
+        async fn delete_schedule(&self, _id: &str) -> Result<bool> {
+            Ok(false)
+        }
+        async fn create_reminder(&self, _reminder: crate::storage::Reminder) -> Result<()> {
+            Ok(())
+        }
+        async fn list_reminders(&self, _user_id: &str) -> Result<Vec<crate::storage::Reminder>> {
+            Ok(vec![])
+        }
+        async fn due_reminders(
+            &self,
+            _now: chrono::DateTime<chrono::Utc>,
+        ) -> Result<Vec<crate::storage::Reminder>> {
+            Ok(vec![])
+        }
+        async fn mark_reminder_delivered(
+            &self,
+            _id: &str,
+            _at: chrono::DateTime<chrono::Utc>,
+        ) -> Result<()> {
+            Ok(())
+        }
+        async fn cancel_reminder(&self, _user_id: &str, _id: &str) -> Result<bool> {
+            Ok(false)
+        }
+        async fn set_schedule_last_run(
+            &self,
+            _id: &str,
+            _at: chrono::DateTime<chrono::Utc>,
+        ) -> Result<()> {
+            Ok(())
+        }
+        async fn release_pending_link(&self, _code: &str) -> Result<()> {
+            Ok(())
+        }
         async fn delete_pending_link(&self, _code: &str) -> Result<()> {
             Ok(())
         }
@@ -703,50 +1563,145 @@ mod tests {
         async fn delete_identity(&self, _provider: &str, _provider_id: &str) -> Result<()> {
             Ok(())
         }
-        async fn list_users(&self) -> Result<Vec<crate::storage::User>> {
-            Ok(vec![])
+        async fn list_users(
+            &self,
+            _filter: &crate::storage::UserFilter,
+            _page: crate::storage::Page,
+        ) -> Result<crate::storage::Paged<crate::storage::User>> {
+            Ok(crate::storage::Paged {
+                items: vec![],
+                total: 0,
+            })
         }
         async fn delete_user(&self, _user_id: &str) -> Result<()> {
             Ok(())
         }
-    }
 
-    #[test]
-    fn test_whatsapp_config() {
-        let config = WhatsAppConfig {
-            enabled: true,
-            phone_number: "1234567890".to_string(),
-            self_chat_mode: true,
-            account_id: Some("personal".to_string()),
-        };
+        async fn add_tool_execution(
+            &self,
+            _execution: crate::storage::ToolExecution,
+        ) -> Result<()> {
+            Ok(())
+        }
+        async fn list_tool_executions(
+            &self,
+            _user_id: &str,
+            _tool_name: &str,
+            _limit: usize,
+        ) -> Result<Vec<crate::storage::ToolExecution>> {
+            Ok(vec![])
+        }
+        async fn save_pending_approval(
+            &self,
+            _approval: crate::storage::PendingApprovalRecord,
+        ) -> Result<()> {
+            Ok(())
+        }
+        async fn delete_pending_approval(&self, _request_id: &str) -> Result<()> {
+            Ok(())
+        }
+        async fn list_pending_approvals(
+            &self,
+        ) -> Result<Vec<crate::storage::PendingApprovalRecord>> {
+            Ok(vec![])
+        }
+        async fn save_session_tool_approval(
+            &self,
+            _session_id: &str,
+            _tool_name: &str,
+        ) -> Result<()> {
+            Ok(())
+        }
+        async fn delete_session_tool_approvals(&self, _session_id: &str) -> Result<()> {
+            Ok(())
+        }
+        async fn list_session_tool_approvals(&self) -> Result<Vec<(String, String)>> {
+            Ok(vec![])
+        }
 
-        assert!(config.enabled);
-        assert_eq!(config.phone_number, "1234567890");
-        assert!(config.self_chat_mode);
-        assert_eq!(config.account_id, Some("personal".to_string()));
-    }
+        async fn tool_call_samples(
+            &self,
+            _since: chrono::DateTime<chrono::Utc>,
+        ) -> Result<Vec<crate::storage::ToolCallSample>> {
+            Ok(Vec::new())
+        }
 
-    #[test]
-    fn test_whatsapp_disabled() {
-        let config = WhatsAppConfig {
-            enabled: false,
-            phone_number: "1234567890".to_string(),
-            self_chat_mode: true,
-            account_id: None,
-        };
+        async fn plugin_kv_get(
+            &self,
+            _plugin_id: &str,
+            _key: &str,
+        ) -> Result<Option<serde_json::Value>> {
+            Ok(None)
+        }
+        async fn plugin_kv_set(
+            &self,
+            _plugin_id: &str,
+            _key: &str,
+            _value: serde_json::Value,
+        ) -> Result<()> {
+            Ok(())
+        }
+        async fn plugin_kv_delete(&self, _plugin_id: &str, _key: &str) -> Result<bool> {
+            Ok(false)
+        }
 
-        assert!(!config.enabled);
-    }
+        async fn set_channel_paused(&self, _channel: &str, _paused: bool) -> Result<()> {
+            Ok(())
+        }
 
-    #[tokio::test]
-    async fn test_whatsapp_adapter_creation() {
-        let config = WhatsAppConfig {
-            enabled: true,
-            phone_number: "1234567890".to_string(),
-            self_chat_mode: true,
-            account_id: Some("test".to_string()),
-        };
+        async fn list_paused_channels(&self) -> Result<Vec<String>> {
+            Ok(vec![])
+        }
+        async fn set_maintenance(&self, _enabled: bool) -> Result<()> {
+            Ok(())
+        }
+        async fn get_maintenance(&self) -> Result<bool> {
+            Ok(false)
+        }
+
+        async fn save_draft(&self, _draft: &crate::storage::MessageDraft) -> Result<()> {
+            Ok(())
+        }
+
+        async fn delete_draft(&self, _id: &str) -> Result<()> {
+            Ok(())
+        }
 
+        async fn list_drafts(&self) -> Result<Vec<crate::storage::MessageDraft>> {
+            Ok(vec![])
+        }
+        async fn save_eval_suite(&self, _suite: &crate::storage::EvalSuite) -> Result<()> {
+            Ok(())
+        }
+        async fn get_eval_suite(
+            &self,
+            _user_id: &str,
+            _name: &str,
+        ) -> Result<Option<crate::storage::EvalSuite>> {
+            Ok(None)
+        }
+        async fn list_eval_suites(&self, _user_id: &str) -> Result<Vec<crate::storage::EvalSuite>> {
+            Ok(vec![])
+        }
+        async fn delete_eval_suite(&self, _user_id: &str, _name: &str) -> Result<bool> {
+            Ok(false)
+        }
+        async fn get_user_settings(
+            &self,
+            _user_id: &str,
+        ) -> Result<Option<crate::storage::UserSettings>> {
+            Ok(None)
+        }
+        async fn set_user_settings(
+            &self,
+            _user_id: &str,
+            _settings: &crate::storage::UserSettings,
+        ) -> Result<()> {
+            Ok(())
+        }
+    }
+
+    async fn mock_router() -> Arc<Router<MockStorage>> {
         let full_config = crate::Config {
             gateway: Default::default(),
             llm: crate::config::LlmConfig {
@@ -756,10 +1711,17 @@ mod tests {
                     primary: "test".to_string(),
                     code: None,
                     fast: None,
+                    roles: Default::default(),
                 },
                 keep_alive: None,
                 cache: Default::default(),
                 routing: None,
+                context_windows: Default::default(),
+                tool_support: Default::default(),
+                tokenizers: Default::default(),
+                coalesce_requests: false,
+                circuit_breaker: Default::default(),
+                warm_up: false,
             },
             channels: Default::default(),
             sessions: Default::default(),
@@ -770,13 +1732,21 @@ mod tests {
             api: Default::default(),
             admin: Default::default(),
             workspace: Default::default(),
+            prompt: Default::default(),
+            locale: Default::default(),
+            network: Default::default(),
+            moderation: Default::default(),
+            redaction: Default::default(),
+            schedules: Default::default(),
             agents: Default::default(),
+            plugins: Default::default(),
+            costs: Default::default(),
             config_path: None,
         };
 
         let shared_config = std::sync::Arc::new(tokio::sync::RwLock::new(full_config));
 
-        let mock_router = Arc::new(
+        Arc::new(
             crate::core::Router::new(
                 shared_config,
                 MockStorage::new(),
@@ -787,17 +1757,287 @@ mod tests {
                         primary: "test".to_string(),
                         code: None,
                         fast: None,
+                        roles: Default::default(),
                     },
                     keep_alive: None,
                     cache: Default::default(),
                     routing: None,
+                    context_windows: Default::default(),
+                    tool_support: Default::default(),
+                    tokenizers: Default::default(),
+                    coalesce_requests: false,
+                    circuit_breaker: Default::default(),
+                    warm_up: false,
                 })
                 .unwrap(),
             )
             .await,
+        )
+    }
+
+    #[test]
+    fn test_whatsapp_config() {
+        let config = WhatsAppConfig {
+            enabled: true,
+            phone_number: "1234567890".to_string(),
+            self_chat_mode: true,
+            auto_elevate_self: false,
+            account_id: Some("personal".to_string()),
+            send_retry: Default::default(),
+            verify_cache_ttl_secs: 3600,
+            send_receipts: true,
+            format: Default::default(),
+            placeholder: Default::default(),
+            max_message_chars: DEFAULT_MAX_MESSAGE_CHARS,
+            document_fallback_chars: None,
+        };
+
+        assert!(config.enabled);
+        assert_eq!(config.phone_number, "1234567890");
+        assert!(config.self_chat_mode);
+        assert_eq!(config.account_id, Some("personal".to_string()));
+    }
+
+    #[test]
+    fn test_receipts_enabled_by_default() {
+        let config: WhatsAppConfig =
+            serde_json::from_str(r#"{"enabled": true, "phone_number": "1234567890"}"#).unwrap();
+        assert!(config.send_receipts);
+
+        let config: WhatsAppConfig =
+            serde_json::from_str(r#"{"enabled": true, "send_receipts": false}"#).unwrap();
+        assert!(!config.send_receipts);
+        assert!(!shows_typing(&config));
+
+        // A placeholder falls back to "typing…"
+        let config: WhatsAppConfig = serde_json::from_str(
+            r#"{"enabled": true, "send_receipts": false, "placeholder": {"enabled": true}}"#,
+        )
+        .unwrap();
+        assert!(shows_typing(&config));
+    }
+
+    #[test]
+    fn test_only_self_messages_are_auto_elevated() {
+        let mut config: WhatsAppConfig =
+            serde_json::from_str(r#"{"enabled": true, "phone_number": "1234567890"}"#).unwrap();
+        assert!(!config.auto_elevate_self);
+        assert!(!auto_elevated(&config, "1234567890"));
+
+        config.auto_elevate_self = true;
+        assert!(auto_elevated(&config, "1234567890"));
+        assert!(!auto_elevated(&config, "4915112345678"));
+
+        // Without a configured owner nobody is elevated
+        config.phone_number.clear();
+        assert!(!auto_elevated(&config, ""));
+    }
+
+    #[tokio::test]
+    async fn test_incoming_messages_elevate_only_the_owners_session() {
+        let router = mock_router().await;
+        let policy = ToolPolicyEngine::new();
+        let config: WhatsAppConfig = serde_json::from_str(
+            r#"{"enabled": true, "phone_number": "1234567890", "auto_elevate_self": true}"#,
+        )
+        .unwrap();
+
+        for (sender_phone, elevated) in [("1234567890", true), ("4915112345678", false)] {
+            let user_id =
+                open_sender_session(&router, Some(&policy), &config, "personal", sender_phone)
+                    .await;
+            let session = router
+                .get_or_create_session_api(&user_id, "whatsapp")
+                .await
+                .unwrap();
+            assert_eq!(policy.is_elevated(&session.id).await, elevated);
+        }
+    }
+
+    #[test]
+    fn test_long_replies_are_split_or_sent_as_document() {
+        let mut config: WhatsAppConfig =
+            serde_json::from_str(r#"{"enabled": true, "max_message_chars": 20}"#).unwrap();
+        assert_eq!(
+            delivery(&config, "Short reply", "Short reply"),
+            Delivery::Messages(vec!["Short reply".to_string()])
+        );
+        let prose = "First line of text.\nSecond line here.";
+        assert_eq!(
+            delivery(&config, prose, prose),
+            Delivery::Messages(vec![
+                "First line of text.".to_string(),
+                "Second line here.".to_string()
+            ])
+        );
+        let table = "| a | b |\n|---|---|\n| 1 | 2 |";
+        assert!(matches!(
+            delivery(&config, table, table),
+            Delivery::Messages(_)
+        ));
+
+        config.document_fallback_chars = Some(30);
+        assert!(matches!(
+            delivery(&config, prose, prose),
+            Delivery::Document
+        ));
+        // Structured replies go as a document once they need splitting
+        assert_eq!(delivery(&config, table, table), Delivery::Document);
+        assert!(matches!(
+            delivery(&config, "| a |", "| a |"),
+            Delivery::Messages(_)
+        ));
+
+        // 0 never splits
+        config.max_message_chars = 0;
+        config.document_fallback_chars = None;
+        assert_eq!(
+            delivery(&config, prose, prose),
+            Delivery::Messages(vec![prose.to_string()])
         );
+    }
+
+    #[test]
+    fn test_send_error_classification() {
+        assert!(is_retryable_send_error(&anyhow::anyhow!(
+            "client not connected"
+        )));
+        assert!(is_retryable_send_error(&anyhow::anyhow!("rate-overlimit")));
+        // The message may have reached the server
+        assert!(!is_retryable_send_error(&anyhow::anyhow!(
+            "websocket connection closed"
+        )));
+        assert!(!is_retryable_send_error(&anyhow::anyhow!(
+            "timed out waiting for server ack"
+        )));
+        assert!(!is_retryable_send_error(&anyhow::anyhow!("invalid jid")));
+    }
+
+    /// Fails with the given errors, then succeeds, recording each attempt
+    struct FlakySender {
+        failures: Mutex<Vec<&'static str>>,
+        attempts: Mutex<Vec<(String, Option<String>)>>,
+    }
+
+    impl FlakySender {
+        fn new(failures: Vec<&'static str>) -> Self {
+            Self {
+                failures: Mutex::new(failures),
+                attempts: Mutex::new(Vec::new()),
+            }
+        }
+    }
+
+    #[async_trait]
+    impl MessageSender for FlakySender {
+        async fn new_message_id(&self) -> String {
+            format!("msg-{}", uuid::Uuid::new_v4())
+        }
+
+        async fn send_with_id(
+            &self,
+            _jid: Jid,
+            message: &wa::Message,
+            message_id: &str,
+        ) -> Result<()> {
+            self.attempts
+                .lock()
+                .unwrap()
+                .push((message_id.to_string(), message.conversation.clone()));
+            match self.failures.lock().unwrap().pop() {
+                Some(failure) => Err(anyhow::anyhow!(failure)),
+                None => Ok(()),
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn test_send_with_retry_resends_the_same_message() {
+        let retry = RetryConfig {
+            max_attempts: 3,
+            initial_backoff_ms: 1,
+            max_backoff_ms: 1,
+        };
+        let jid: Jid = "1234567890@s.whatsapp.net".parse().unwrap();
+
+        let sender = FlakySender::new(vec!["client not connected"]);
+        let message_id = send_with_retry(&sender, &retry, jid.clone(), "hello")
+            .await
+            .unwrap();
+        let attempts = sender.attempts.lock().unwrap().clone();
+        assert_eq!(attempts.len(), 2);
+        assert!(attempts
+            .iter()
+            .all(|(id, text)| *id == message_id && text.as_deref() == Some("hello")));
+
+        // A send that may have reached the server is not repeated
+        let sender = FlakySender::new(vec!["connection reset by peer"]);
+        let err = send_with_retry(&sender, &retry, jid.clone(), "hello")
+            .await
+            .unwrap_err();
+        assert!(matches!(
+            err.downcast_ref::<WhatsAppSendError>(),
+            Some(WhatsAppSendError::Rejected { .. })
+        ));
+        assert_eq!(sender.attempts.lock().unwrap().len(), 1);
+
+        let sender = FlakySender::new(vec!["not connected"; 3]);
+        let err = send_with_retry(&sender, &retry, jid, "hello")
+            .await
+            .unwrap_err();
+        assert!(matches!(
+            err.downcast_ref::<WhatsAppSendError>(),
+            Some(WhatsAppSendError::RetriesExhausted { attempts: 3, .. })
+        ));
+    }
+
+    #[test]
+    fn test_group_admin_error_is_explicit() {
+        let err = group_admin_error("Team", anyhow::anyhow!("server returned 403 forbidden"));
+        assert!(err.to_string().contains("not a group admin"));
+
+        let err = group_admin_error("Team", anyhow::anyhow!("connection lost"));
+        assert!(err.to_string().contains("Failed to update participants"));
+    }
+
+    #[test]
+    fn test_whatsapp_disabled() {
+        let config = WhatsAppConfig {
+            enabled: false,
+            phone_number: "1234567890".to_string(),
+            self_chat_mode: true,
+            auto_elevate_self: false,
+            account_id: None,
+            send_retry: Default::default(),
+            verify_cache_ttl_secs: 3600,
+            send_receipts: true,
+            format: Default::default(),
+            placeholder: Default::default(),
+            max_message_chars: DEFAULT_MAX_MESSAGE_CHARS,
+            document_fallback_chars: None,
+        };
+
+        assert!(!config.enabled);
+    }
+
+    #[tokio::test]
+    async fn test_whatsapp_adapter_creation() {
+        let config = WhatsAppConfig {
+            enabled: true,
+            phone_number: "1234567890".to_string(),
+            self_chat_mode: true,
+            auto_elevate_self: false,
+            account_id: Some("test".to_string()),
+            send_retry: Default::default(),
+            verify_cache_ttl_secs: 3600,
+            send_receipts: true,
+            format: Default::default(),
+            placeholder: Default::default(),
+            max_message_chars: DEFAULT_MAX_MESSAGE_CHARS,
+            document_fallback_chars: None,
+        };
 
-        let adapter = WhatsAppAdapter::new(mock_router, config).unwrap();
+        let adapter = WhatsAppAdapter::new(mock_router().await, config).unwrap();
         assert!(adapter.is_enabled());
     }
 }
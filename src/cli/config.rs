@@ -0,0 +1,16 @@
+use crate::config::Config;
+use anyhow::Result;
+
+/// Enum for config subcommands
+pub enum ConfigCmd {
+    Schema,
+}
+
+pub fn handle_config_command(cmd: ConfigCmd) -> Result<()> {
+    match cmd {
+        ConfigCmd::Schema => {
+            println!("{}", serde_json::to_string_pretty(&Config::json_schema())?);
+            Ok(())
+        }
+    }
+}
@@ -1,2 +1,4 @@
+pub mod config;
+pub mod skill;
 pub mod token;
 pub mod user;
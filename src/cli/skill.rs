@@ -0,0 +1,106 @@
+use crate::config::Config;
+use crate::tools::creator::{write_scaffold, ScaffoldSkillRequest};
+use crate::tools::skills::SkillReloadReport;
+use anyhow::{anyhow, Context, Result};
+use std::path::PathBuf;
+
+/// Enum for skill management subcommands
+pub enum SkillCmd {
+    New {
+        name: String,
+        runtime: String,
+    },
+    /// Ask the running gateway to rescan its skills directory
+    Reload {
+        token: String,
+        url: Option<String>,
+    },
+}
+
+pub async fn handle_skill_command(cmd: SkillCmd, config: Config) -> Result<()> {
+    match cmd {
+        SkillCmd::New { name, runtime } => new_skill(name, runtime, config),
+        SkillCmd::Reload { token, url } => reload_skills(token, url, config).await,
+    }
+}
+
+fn new_skill(name: String, runtime: String, config: Config) -> Result<()> {
+    let path = PathBuf::from(&config.tools.user_tools_dir).join(format!("{}.yaml", name));
+
+    let req = ScaffoldSkillRequest {
+        name,
+        runtime,
+        description: None,
+    };
+    let entry = write_scaffold(&req, &path)?;
+
+    println!("✓ Skill '{}' scaffolded", entry.manifest.name);
+    println!("  File: {}", path.display());
+    println!("  Runtime: {}", entry.manifest.runtime);
+    println!("  Policy: {}", entry.manifest.policy);
+    println!("\nEdit the file to implement it. A running gateway picks up changes automatically.");
+
+    Ok(())
+}
+
+async fn reload_skills(token: String, url: Option<String>, config: Config) -> Result<()> {
+    let url = match url {
+        Some(url) => url,
+        None => {
+            if config.api.unix_socket.is_some() {
+                return Err(anyhow!(
+                    "The gateway listens on a Unix socket; pass --url of a proxy in front of it"
+                ));
+            }
+            let scheme = if config.api.tls.is_some() {
+                "https"
+            } else {
+                "http"
+            };
+            format!(
+                "{}://{}:{}/api/tools/reload",
+                scheme, config.api.host, config.api.port
+            )
+        }
+    };
+
+    let response = reqwest::Client::new()
+        .post(&url)
+        .bearer_auth(token)
+        .send()
+        .await
+        .context(format!("Failed to reach the gateway at {}", url))?;
+    let status = response.status();
+    let body: serde_json::Value = response
+        .json()
+        .await
+        .context("Gateway sent an invalid response")?;
+    if !status.is_success() {
+        return Err(anyhow!(
+            "Reload failed ({}): {}",
+            status,
+            body["error"].as_str().unwrap_or("unknown error")
+        ));
+    }
+    let report: SkillReloadReport =
+        serde_json::from_value(body["data"].clone()).context("Gateway sent an invalid report")?;
+
+    println!("✓ Skills reloaded");
+    for name in &report.loaded {
+        println!("  ✅ {}", name);
+    }
+    for name in &report.unloaded {
+        println!("  ➖ {} (unloaded)", name);
+    }
+    for failure in &report.failed {
+        println!("  ❌ {}: {}", failure.path.display(), failure.error);
+    }
+    if !report.failed.is_empty() {
+        return Err(anyhow!(
+            "{} skill file(s) failed to load",
+            report.failed.len()
+        ));
+    }
+
+    Ok(())
+}
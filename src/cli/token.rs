@@ -1,5 +1,5 @@
 use crate::config::Config;
-use crate::storage::Storage;
+use crate::storage::{Page, Storage};
 use anyhow::{anyhow, Result};
 
 /// Enum for token management subcommands
@@ -26,13 +26,10 @@ async fn list_tokens(username: &str, config: Config) -> Result<()> {
         .ok_or_else(|| anyhow!("User '{}' not found", username))?;
 
     // Get user's identities
-    let identities = storage.list_identities(&user.id).await?;
-
-    // Filter to API tokens only
-    let tokens: Vec<_> = identities
-        .into_iter()
-        .filter(|id| id.provider == "api_token")
-        .collect();
+    let tokens = storage
+        .list_identities(&user.id, Some("api_token"), Page::all())
+        .await?
+        .items;
 
     if tokens.is_empty() {
         println!("User '{}' has no API tokens.", username);
@@ -1,6 +1,6 @@
 use crate::config::Config;
 use crate::core::password;
-use crate::storage::Storage;
+use crate::storage::{Page, Storage, UserFilter};
 use anyhow::{anyhow, Result};
 use std::io::Write;
 
@@ -109,8 +109,8 @@ async fn delete_user(username: &str, force: bool, config: Config) -> Result<()>
     }
 
     // Delete all user's tokens first
-    let identities = storage.list_identities(&user.id).await?;
-    for identity in identities {
+    let identities = storage.list_identities(&user.id, None, Page::all()).await?;
+    for identity in identities.items {
         storage
             .delete_identity(&identity.provider, &identity.provider_id)
             .await?;
@@ -128,7 +128,10 @@ async fn list_users(config: Config) -> Result<()> {
     let storage = crate::storage::sqlite::SqliteStorage::new(&config.storage.path).await?;
 
     // Get all users
-    let users = storage.list_users().await?;
+    let users = storage
+        .list_users(&UserFilter::default(), Page::all())
+        .await?
+        .items;
 
     if users.is_empty() {
         println!("No users found.");
@@ -1,15 +1,71 @@
 use super::Config;
 use anyhow::{Context, Result};
+use serde_yaml::Value;
 use std::fs;
-use std::path::Path;
+use std::path::{Path, PathBuf};
 
+/// Serialization format of a config file, chosen by its extension
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ConfigFormat {
+    Yaml,
+    Json,
+    Toml,
+}
+
+impl ConfigFormat {
+    /// `.json` and `.toml` files use their format, anything else is YAML
+    pub fn from_path(path: &Path) -> Self {
+        match path.extension().and_then(|ext| ext.to_str()) {
+            Some(ext) if ext.eq_ignore_ascii_case("json") => Self::Json,
+            Some(ext) if ext.eq_ignore_ascii_case("toml") => Self::Toml,
+            _ => Self::Yaml,
+        }
+    }
+
+    fn parse(self, contents: &str) -> Result<Value> {
+        Ok(match self {
+            Self::Yaml => serde_yaml::from_str(contents)?,
+            Self::Json => serde_json::from_str(contents)?,
+            Self::Toml => toml::from_str(contents)?,
+        })
+    }
+
+    fn serialize(self, config: &Config) -> Result<String> {
+        Ok(match self {
+            Self::Yaml => serde_yaml::to_string(config)?,
+            Self::Json => serde_json::to_string_pretty(config)? + "\n",
+            Self::Toml => toml::to_string_pretty(config)?,
+        })
+    }
+}
+
+/// Load a config file, or every config file of a config directory
 pub fn load_config<P: AsRef<Path>>(path: P) -> Result<Config> {
     let path = path.as_ref();
-    let contents = fs::read_to_string(path)
-        .with_context(|| format!("Failed to read config file: {}", path.display()))?;
+    let mut config = load_config_files(&[path.to_path_buf()])?;
+    config.config_path = Some(path.to_path_buf());
+    Ok(config)
+}
+
+/// Load several config files or directories, deep-merged in order so later
+/// files override earlier ones. Directories contribute their `.yaml`/`.yml`,
+/// `.json` and `.toml` files in file name order.
+pub fn load_config_files(paths: &[PathBuf]) -> Result<Config> {
+    let mut merged = Value::Mapping(Default::default());
+    for file in expand_config_paths(paths)? {
+        let contents = fs::read_to_string(&file)
+            .with_context(|| format!("Failed to read config file: {}", file.display()))?;
+        let value = ConfigFormat::from_path(&file)
+            .parse(&contents)
+            .with_context(|| format!("Failed to parse config file: {}", file.display()))?;
+        // Empty files contribute nothing
+        if value.is_null() {
+            continue;
+        }
+        merge_values(&mut merged, value);
+    }
 
-    let config: Config = serde_yaml::from_str(&contents)
-        .with_context(|| format!("Failed to parse config file: {}", path.display()))?;
+    let config: Config = serde_yaml::from_value(merged).context("Failed to parse config")?;
 
     // Perform environment variable substitution
     let config = substitute_env_vars(config)?;
@@ -17,15 +73,76 @@ pub fn load_config<P: AsRef<Path>>(path: P) -> Result<Config> {
     // Validate configuration
     validate_config(&config)?;
 
-    let mut config = config;
-    config.config_path = Some(path.to_path_buf());
-
     Ok(config)
 }
 
+/// Config files in load order, with directories replaced by their config files
+fn expand_config_paths(paths: &[PathBuf]) -> Result<Vec<PathBuf>> {
+    let mut files = Vec::new();
+    for path in paths {
+        if !path.is_dir() {
+            files.push(path.clone());
+            continue;
+        }
+
+        let mut entries: Vec<PathBuf> = fs::read_dir(path)
+            .with_context(|| format!("Failed to read config directory: {}", path.display()))?
+            .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+            .filter(|file| {
+                file.is_file()
+                    && matches!(
+                        file.extension().and_then(|ext| ext.to_str()),
+                        Some("yaml" | "yml" | "json" | "toml")
+                    )
+            })
+            .collect();
+        if entries.is_empty() {
+            anyhow::bail!("Config directory has no config files: {}", path.display());
+        }
+        entries.sort();
+        files.extend(entries);
+    }
+    Ok(files)
+}
+
+/// Merge `overlay` into `base`: maps are merged key by key, anything else
+/// (scalars, sequences, null) replaces the base value
+fn merge_values(base: &mut Value, overlay: Value) {
+    match (base, overlay) {
+        (Value::Mapping(base), Value::Mapping(overlay)) => {
+            for (key, value) in overlay {
+                match base.get_mut(&key) {
+                    Some(existing) => merge_values(existing, value),
+                    None => {
+                        base.insert(key, value);
+                    }
+                }
+            }
+        }
+        (base, overlay) => *base = overlay,
+    }
+}
+
+/// The file `save_config` writes to, or why the config cannot be saved:
+/// configs merged from several files or from a directory have no single
+/// file to write back
+pub fn config_save_path(config: &Config) -> Result<&Path> {
+    let path = config
+        .config_path
+        .as_deref()
+        .context("Config was merged from several files; edit them instead")?;
+    if path.is_dir() {
+        anyhow::bail!(
+            "Config was loaded from the directory {}; edit its files instead",
+            path.display()
+        );
+    }
+    Ok(path)
+}
+
 pub fn save_config(config: &Config) -> Result<()> {
-    let path = config.config_path.as_ref().context("No config path set")?;
-    let content = serde_yaml::to_string(config)?;
+    let path = config_save_path(config)?;
+    let content = ConfigFormat::from_path(path).serialize(config)?;
     fs::write(path, content)?;
     Ok(())
 }
@@ -65,6 +182,7 @@ fn validate_config(config: &Config) -> Result<()> {
     if config.llm.models.primary.is_empty() {
         anyhow::bail!("LLM primary model must be specified");
     }
+    validate_model_roles(config)?;
 
     // Validate Telegram config
     if config.channels.telegram.enabled && config.channels.telegram.token.is_none() {
@@ -89,3 +207,252 @@ fn validate_config(config: &Config) -> Result<()> {
 
     Ok(())
 }
+
+/// Every `role:<name>` model reference must name a configured role
+fn validate_model_roles(config: &Config) -> Result<()> {
+    let mut references = vec![(
+        "tools.selection.embedding_model",
+        config.tools.selection.embedding_model.as_str(),
+    )];
+    if let Some(model) = &config.tools.knowledge.embedding_model {
+        references.push(("tools.knowledge.embedding_model", model));
+    }
+    if let Some(routing) = &config.llm.routing {
+        references.extend(
+            routing
+                .rules
+                .iter()
+                .map(|rule| ("llm.routing.rules", rule.model.as_str())),
+        );
+        references.extend(
+            routing
+                .fallbacks
+                .iter()
+                .map(|model| ("llm.routing.fallbacks", model.as_str())),
+        );
+    }
+
+    for (setting, reference) in references {
+        if config.llm.models.resolve(reference).is_none() {
+            let roles: Vec<&str> = config
+                .llm
+                .models
+                .roles()
+                .into_iter()
+                .map(|(role, _)| role)
+                .collect();
+            anyhow::bail!(
+                "{} refers to '{}', but llm.models has no such role (roles: {})",
+                setting,
+                reference,
+                roles.join(", ")
+            );
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::tempdir;
+
+    fn yaml(text: &str) -> Value {
+        serde_yaml::from_str(text).unwrap()
+    }
+
+    #[test]
+    fn test_merge_replaces_scalars_and_sequences() {
+        let mut base =
+            yaml("llm:\n  base_url: http://a\n  keep_alive: 5m\napi:\n  tokens: [one, two]\n");
+        merge_values(
+            &mut base,
+            yaml("llm:\n  base_url: http://b\napi:\n  tokens: [three]\n"),
+        );
+
+        assert_eq!(
+            base,
+            yaml("llm:\n  base_url: http://b\n  keep_alive: 5m\napi:\n  tokens: [three]\n")
+        );
+    }
+
+    #[test]
+    fn test_merge_combines_agents_and_policies_maps() {
+        let mut base = yaml(
+            "agents:\n  work:\n    name: Work\n    channels: ['+1']\n  home:\n    name: Home\n\
+             tools:\n  policies:\n    exec: deny\n    web_fetch: allow\n",
+        );
+        merge_values(
+            &mut base,
+            yaml(
+                "agents:\n  work:\n    channels: ['+2']\n  lab:\n    name: Lab\n\
+                 tools:\n  policies:\n    exec: ask\n",
+            ),
+        );
+
+        assert_eq!(
+            base,
+            yaml(
+                "agents:\n  work:\n    name: Work\n    channels: ['+2']\n  home:\n    name: Home\n  \
+                 lab:\n    name: Lab\ntools:\n  policies:\n    exec: ask\n    web_fetch: allow\n",
+            )
+        );
+    }
+
+    #[test]
+    fn test_load_config_directory_in_file_name_order() {
+        let dir = tempdir().unwrap();
+        fs::write(
+            dir.path().join("10-llm.yaml"),
+            "llm:\n  models:\n    primary: base-model\ntools:\n  policies:\n    exec: deny\n",
+        )
+        .unwrap();
+        fs::write(
+            dir.path().join("20-overrides.yml"),
+            "llm:\n  models:\n    primary: override-model\ntools:\n  policies:\n    web_fetch: allow\n",
+        )
+        .unwrap();
+        fs::write(dir.path().join("30-empty.yaml"), "").unwrap();
+        fs::write(dir.path().join("README.md"), "not: config").unwrap();
+
+        let config = load_config(dir.path()).unwrap();
+
+        assert_eq!(config.llm.models.primary, "override-model");
+        assert_eq!(config.tools.policies["exec"], "deny");
+        assert_eq!(config.tools.policies["web_fetch"], "allow");
+        assert_eq!(config.config_path.as_deref(), Some(dir.path()));
+        assert!(save_config(&config).is_err());
+    }
+
+    #[test]
+    fn test_load_config_files_later_file_wins() {
+        let dir = tempdir().unwrap();
+        let base = dir.path().join("base.yaml");
+        let secrets = dir.path().join("secrets.yaml");
+        fs::write(
+            &base,
+            "llm:\n  models:\n    primary: m\napi:\n  tokens: [placeholder]\n",
+        )
+        .unwrap();
+        fs::write(&secrets, "api:\n  enabled: true\n  tokens: [real-token]\n").unwrap();
+
+        let config = load_config_files(&[base, secrets]).unwrap();
+
+        assert!(config.api.enabled);
+        assert_eq!(config.api.tokens, vec!["real-token"]);
+    }
+
+    fn round_trip(file_name: &str) -> (Config, String) {
+        let dir = tempdir().unwrap();
+        let source = dir.path().join("source.yaml");
+        fs::write(
+            &source,
+            "llm:\n  models:\n    primary: qwen\n  context_windows:\n    qwen: 32768\n\
+             api:\n  enabled: true\n  tokens: [secret]\n\
+             tools:\n  policies:\n    exec: deny\n\
+             agents:\n  work:\n    name: Work\n    channels: ['+1']\n",
+        )
+        .unwrap();
+        let mut config = load_config(&source).unwrap();
+
+        let target = dir.path().join(file_name);
+        config.config_path = Some(target.clone());
+        save_config(&config).unwrap();
+        let written = fs::read_to_string(&target).unwrap();
+        let loaded = load_config(&target).unwrap();
+
+        assert_eq!(
+            serde_json::to_value(&loaded).unwrap(),
+            serde_json::to_value(&config).unwrap()
+        );
+        assert_eq!(loaded.agents["work"].channels, vec!["+1"]);
+        assert_eq!(loaded.tools.policies["exec"], "deny");
+        (loaded, written)
+    }
+
+    #[test]
+    fn test_format_from_extension() {
+        assert_eq!(
+            ConfigFormat::from_path(Path::new("a.json")),
+            ConfigFormat::Json
+        );
+        assert_eq!(
+            ConfigFormat::from_path(Path::new("a.TOML")),
+            ConfigFormat::Toml
+        );
+        assert_eq!(
+            ConfigFormat::from_path(Path::new("a.yml")),
+            ConfigFormat::Yaml
+        );
+        assert_eq!(
+            ConfigFormat::from_path(Path::new("config")),
+            ConfigFormat::Yaml
+        );
+    }
+
+    #[test]
+    fn test_yaml_round_trip() {
+        let (_, written) = round_trip("config.yaml");
+        assert!(serde_yaml::from_str::<Value>(&written).is_ok());
+    }
+
+    #[test]
+    fn test_json_round_trip() {
+        let (_, written) = round_trip("config.json");
+        assert!(serde_json::from_str::<serde_json::Value>(&written).is_ok());
+    }
+
+    #[test]
+    fn test_toml_round_trip() {
+        let (_, written) = round_trip("config.toml");
+        assert!(written.contains("[llm.models]"));
+    }
+
+    #[test]
+    fn test_load_directory_mixing_formats() {
+        let dir = tempdir().unwrap();
+        fs::write(
+            dir.path().join("1-llm.toml"),
+            "[llm.models]\nprimary = \"toml-model\"\n",
+        )
+        .unwrap();
+        fs::write(
+            dir.path().join("2-secrets.json"),
+            r#"{"api": {"enabled": true, "tokens": ["json-token"]}}"#,
+        )
+        .unwrap();
+
+        let config = load_config(dir.path()).unwrap();
+
+        assert_eq!(config.llm.models.primary, "toml-model");
+        assert_eq!(config.api.tokens, vec!["json-token"]);
+    }
+
+    #[test]
+    fn test_model_roles_are_validated() {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("config.yaml");
+        let models = "llm:\n  models:\n    primary: qwen\n    vision: llava\n    embed: nomic\n";
+        fs::write(
+            &path,
+            format!(
+                "{}  routing:\n    rules:\n      - pattern: image\n        model: role:vision\n\
+                 tools:\n  selection:\n    embedding_model: role:embed\n",
+                models
+            ),
+        )
+        .unwrap();
+        let config = load_config(&path).unwrap();
+        assert_eq!(config.llm.models.model_for("vision"), Some("llava"));
+        assert_eq!(config.llm.models.resolve("role:embed"), Some("nomic"));
+
+        fs::write(
+            &path,
+            format!("{}  routing:\n    fallbacks: [role:summarize]\n", models),
+        )
+        .unwrap();
+        let error = load_config(&path).unwrap_err().to_string();
+        assert!(error.contains("llm.routing.fallbacks"), "{}", error);
+        assert!(error.contains("primary, embed, vision"), "{}", error);
+    }
+}
@@ -3,7 +3,9 @@ mod schema;
 pub mod workspace;
 
 pub use loader::load_config;
+pub use loader::load_config_files;
 pub use loader::save_config;
+pub use loader::ConfigFormat;
 pub use schema::*;
 
 use anyhow::Result;
@@ -14,10 +16,26 @@ impl Config {
         loader::load_config(path)
     }
 
+    /// Load several config files or directories, later ones overriding
+    /// earlier ones
+    pub fn load_files(paths: &[std::path::PathBuf]) -> Result<Self> {
+        loader::load_config_files(paths)
+    }
+
     pub fn save(&self) -> Result<()> {
         loader::save_config(self)
     }
 
+    /// Check that `save` has a single file to write to
+    pub fn check_saveable(&self) -> Result<()> {
+        loader::config_save_path(self).map(|_| ())
+    }
+
+    /// JSON Schema of the config file, generated from the config structs
+    pub fn json_schema() -> serde_json::Value {
+        schemars::schema_for!(Config).to_value()
+    }
+
     pub fn default_path() -> std::path::PathBuf {
         dirs::home_dir()
             .expect("Could not find home directory")
@@ -25,3 +43,20 @@ impl Config {
             .join("config.yaml")
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_json_schema_lists_top_level_keys() {
+        let schema = Config::json_schema();
+        let properties = schema["properties"].as_object().unwrap();
+
+        for key in ["llm", "channels", "sessions", "tools", "agents"] {
+            assert!(properties.contains_key(key), "missing {}", key);
+        }
+        assert!(!properties.contains_key("config_path"));
+        assert_eq!(schema["required"], serde_json::json!(["llm"]));
+    }
+}
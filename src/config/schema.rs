@@ -1,8 +1,9 @@
+use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 use std::path::PathBuf;
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
 pub struct ApiConfig {
     #[serde(default)]
     pub enabled: bool,
@@ -12,6 +13,80 @@ pub struct ApiConfig {
     pub port: u16,
     #[serde(default)]
     pub tokens: Vec<String>,
+    /// Scopes granted to individual tokens (e.g. `tools:write`); tokens not
+    /// listed here keep full access. Listed tokens only get admin rights
+    /// with the `admin` scope or an `admin:*` one
+    #[serde(default)]
+    pub token_scopes: HashMap<String, Vec<String>>,
+    /// Maximum number of prompts accepted by one batch chat request
+    #[serde(default = "default_batch_max_size")]
+    pub batch_max_size: usize,
+    /// Prompts of a batch processed at the same time
+    #[serde(default = "default_batch_concurrency")]
+    pub batch_concurrency: usize,
+    /// Serve HTTPS with this certificate instead of plain HTTP
+    #[serde(default)]
+    pub tls: Option<TlsConfig>,
+    /// Listen on a Unix domain socket instead of `host:port`
+    #[serde(default)]
+    pub unix_socket: Option<UnixSocketConfig>,
+    /// Seconds an account invite stays valid unless the request sets its own
+    #[serde(default = "default_invite_ttl_secs")]
+    pub invite_ttl_secs: u64,
+    /// Longest lifetime of a token an admin gets to act as a user
+    #[serde(default = "default_impersonation_ttl_secs")]
+    pub impersonation_ttl_secs: u64,
+    /// Sign in through an OpenID Connect provider, alongside passwords
+    #[serde(default)]
+    pub oidc: Option<OidcConfig>,
+}
+
+/// Unix domain socket for same-host reverse proxies
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct UnixSocketConfig {
+    pub path: PathBuf,
+    /// Octal permissions of the socket file
+    #[serde(default = "default_socket_mode")]
+    pub mode: String,
+}
+
+impl UnixSocketConfig {
+    /// Socket file permissions as a mode bitmask
+    pub fn mode_bits(&self) -> Result<u32, std::num::ParseIntError> {
+        u32::from_str_radix(self.mode.trim_start_matches("0o"), 8)
+    }
+}
+
+/// OpenID Connect provider for single sign-on to the Web API
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct OidcConfig {
+    /// Issuer URL, serving `/.well-known/openid-configuration`
+    pub issuer: String,
+    pub client_id: String,
+    pub client_secret: String,
+    /// Callback registered with the provider, ending in
+    /// `/api/auth/oidc/callback`
+    pub redirect_url: String,
+    #[serde(default = "default_oidc_scopes")]
+    pub scopes: Vec<String>,
+    /// Create an account on the first login of someone without one
+    #[serde(default)]
+    pub auto_provision: bool,
+    /// Link a first login to the local account named after its verified
+    /// email. Only enable this for a provider that verifies who owns an
+    /// email, or anyone registering the address there takes the account.
+    #[serde(default)]
+    pub link_by_email: bool,
+    /// Role of accounts created on login
+    #[serde(default = "default_oidc_role")]
+    pub default_role: String,
+}
+
+/// PEM certificate chain and private key for the Web API
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct TlsConfig {
+    pub cert_path: PathBuf,
+    pub key_path: PathBuf,
 }
 
 impl Default for ApiConfig {
@@ -21,11 +96,19 @@ impl Default for ApiConfig {
             host: default_api_host(),
             port: default_api_port(),
             tokens: vec![],
+            token_scopes: HashMap::new(),
+            batch_max_size: default_batch_max_size(),
+            batch_concurrency: default_batch_concurrency(),
+            tls: None,
+            unix_socket: None,
+            invite_ttl_secs: default_invite_ttl_secs(),
+            impersonation_ttl_secs: default_impersonation_ttl_secs(),
+            oidc: None,
         }
     }
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
 pub struct AdminConfig {
     #[serde(default = "default_admin_username")]
     pub username: String,
@@ -42,7 +125,7 @@ impl Default for AdminConfig {
     }
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
 pub struct Config {
     #[serde(skip)]
     pub config_path: Option<PathBuf>,
@@ -68,10 +151,27 @@ pub struct Config {
     #[serde(default)]
     pub workspace: WorkspaceConfig,
     #[serde(default)]
+    pub prompt: PromptConfig,
+    #[serde(default)]
+    pub locale: LocaleConfig,
+    #[serde(default)]
+    pub network: NetworkConfig,
+    #[serde(default)]
+    pub moderation: ModerationConfig,
+    #[serde(default)]
+    pub redaction: RedactionConfig,
+    /// Prompts run on a schedule, in addition to those created via the API
+    #[serde(default)]
+    pub schedules: Vec<ScheduleConfig>,
+    #[serde(default)]
     pub agents: HashMap<String, AgentConfig>,
+    #[serde(default)]
+    pub plugins: PluginsConfig,
+    #[serde(default)]
+    pub costs: CostsConfig,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
 pub struct AgentConfig {
     pub name: String,
     /// Custom workspace path (overrides default)
@@ -79,9 +179,13 @@ pub struct AgentConfig {
     /// List of channel identifiers this agent handles (e.g. phone numbers)
     #[serde(default)]
     pub channels: Vec<String>,
+    /// Persona replacing the channel's and the global base prompt; inline
+    /// text or a file path, like `prompt.system`
+    #[serde(default)]
+    pub persona: Option<String>,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
 pub struct GatewayConfig {
     #[serde(default = "default_host")]
     pub host: String,
@@ -89,6 +193,11 @@ pub struct GatewayConfig {
     pub port: u16,
     #[serde(default = "default_log_level")]
     pub log_level: String,
+    /// Start in maintenance (read-only) mode: reads keep working, new
+    /// messages, tool calls and API writes are refused. An admin can still
+    /// clear it at runtime with `DELETE /api/maintenance`.
+    #[serde(default)]
+    pub maintenance: bool,
 }
 
 impl Default for GatewayConfig {
@@ -97,11 +206,12 @@ impl Default for GatewayConfig {
             host: default_host(),
             port: default_port(),
             log_level: default_log_level(),
+            maintenance: false,
         }
     }
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
 pub struct LlmConfig {
     #[serde(default = "default_provider")]
     pub provider: String,
@@ -114,18 +224,107 @@ pub struct LlmConfig {
     pub cache: CacheConfig,
     #[serde(default)]
     pub routing: Option<RoutingConfig>,
+    /// Context window size in tokens, keyed by model name (Ollama models
+    /// without an entry use the context length the backend reports)
+    #[serde(default)]
+    pub context_windows: HashMap<String, usize>,
+    /// Whether a model can be sent tools, keyed by model name (Ollama models
+    /// without an entry use the tool support the backend reports)
+    #[serde(default)]
+    pub tool_support: HashMap<String, bool>,
+    /// Tokenizer counting a model's tokens, keyed by model name: `tiktoken`,
+    /// `tiktoken:<encoding>`, `hf:<path to tokenizer.json>` or `heuristic`
+    /// (models without an entry use the heuristic)
+    #[serde(default)]
+    pub tokenizers: HashMap<String, String>,
+    /// Let identical concurrent non-streaming chats at temperature 0 share
+    /// one generation instead of each running their own
+    #[serde(default)]
+    pub coalesce_requests: bool,
+    /// Fail fast while the backend keeps failing
+    #[serde(default)]
+    pub circuit_breaker: CircuitBreakerConfig,
+    /// Load the primary model with a tiny completion on startup, so the
+    /// first message does not wait for it. Leave off for metered APIs.
+    #[serde(default)]
+    pub warm_up: bool,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+impl LlmConfig {
+    /// Configured context window for a model, if any
+    pub fn context_window(&self, model: &str) -> Option<usize> {
+        self.context_windows.get(model).copied()
+    }
+
+    /// Configured tool support for a model, if any
+    pub fn supports_tools(&self, model: &str) -> Option<bool> {
+        self.tool_support.get(model).copied()
+    }
+
+    /// Configured tokenizer for a model, if any
+    pub fn tokenizer(&self, model: &str) -> Option<&str> {
+        self.tokenizers.get(model).map(String::as_str)
+    }
+}
+
+/// Prefix of a model reference naming a role of `llm.models` rather than a
+/// model, e.g. `role:vision`
+pub const ROLE_PREFIX: &str = "role:";
+
+/// Models by role. `code` and `fast` feed the built-in routing heuristics;
+/// any other key adds a named role (`vision`, `summarize`, `embed`, ...)
+/// that routing rules, fallbacks and embedding models can reference as
+/// `role:<name>`. Compaction uses the `summarize` role when there is one.
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
 pub struct LlmModels {
     pub primary: String,
     #[serde(default)]
     pub code: Option<String>,
     #[serde(default)]
     pub fast: Option<String>,
+    /// Further roles, by name
+    #[serde(flatten, default)]
+    pub roles: HashMap<String, String>,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+impl LlmModels {
+    /// Model configured for a role
+    pub fn model_for(&self, role: &str) -> Option<&str> {
+        match role {
+            "primary" => Some(&self.primary),
+            "code" => self.code.as_deref(),
+            "fast" => self.fast.as_deref(),
+            other => self.roles.get(other).map(String::as_str),
+        }
+    }
+
+    /// Model a reference names: `role:<name>` resolves to the role's model,
+    /// anything else is a model name. `None` for a role that is not
+    /// configured.
+    pub fn resolve<'a>(&'a self, reference: &'a str) -> Option<&'a str> {
+        match reference.strip_prefix(ROLE_PREFIX) {
+            Some(role) => self.model_for(role.trim()),
+            None => Some(reference),
+        }
+    }
+
+    /// Configured roles and their models, the well-known ones first
+    pub fn roles(&self) -> Vec<(&str, &str)> {
+        let mut extra: Vec<(&str, &str)> = self
+            .roles
+            .iter()
+            .map(|(role, model)| (role.as_str(), model.as_str()))
+            .collect();
+        extra.sort();
+        ["primary", "code", "fast"]
+            .into_iter()
+            .filter_map(|role| self.model_for(role).map(|model| (role, model)))
+            .chain(extra)
+            .collect()
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
 pub struct CacheConfig {
     #[serde(default = "default_cache_type", rename = "type")]
     pub cache_type: String,
@@ -145,21 +344,61 @@ impl Default for CacheConfig {
     }
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+/// After `failure_threshold` consecutive failed requests the backend is
+/// considered down: requests fail immediately for `cooldown_secs`, then a
+/// single request probes whether it recovered
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct CircuitBreakerConfig {
+    #[serde(default = "default_circuit_breaker_enabled")]
+    pub enabled: bool,
+    #[serde(default = "default_circuit_failure_threshold")]
+    pub failure_threshold: u32,
+    #[serde(default = "default_circuit_cooldown_secs")]
+    pub cooldown_secs: u64,
+}
+
+impl Default for CircuitBreakerConfig {
+    fn default() -> Self {
+        Self {
+            enabled: default_circuit_breaker_enabled(),
+            failure_threshold: default_circuit_failure_threshold(),
+            cooldown_secs: default_circuit_cooldown_secs(),
+        }
+    }
+}
+
+fn default_circuit_breaker_enabled() -> bool {
+    true
+}
+
+fn default_circuit_failure_threshold() -> u32 {
+    5
+}
+
+fn default_circuit_cooldown_secs() -> u64 {
+    30
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
 pub struct RoutingConfig {
     #[serde(default)]
     pub default: Option<String>,
     #[serde(default)]
     pub rules: Vec<RoutingRule>,
+    /// Models to retry with, in order, when the chosen model is unavailable
+    /// or times out (defaults to the fast model)
+    #[serde(default)]
+    pub fallbacks: Vec<String>,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
 pub struct RoutingRule {
     pub pattern: String,
+    /// Model name, or `role:<name>`
     pub model: String,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize, Default)]
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
 pub struct ChannelsConfig {
     #[serde(default)]
     pub telegram: TelegramConfig,
@@ -167,18 +406,124 @@ pub struct ChannelsConfig {
     pub discord: DiscordConfig,
     #[serde(default)]
     pub whatsapp: WhatsAppChannelConfig,
+    /// Channel identities of one person mapped to a shared user ID, each
+    /// written `<channel>:<native id>`, e.g.
+    /// `alice: ["whatsapp:4915112345678", "discord:123456789"]`
+    #[serde(default)]
+    pub identities: HashMap<String, Vec<String>>,
+    /// Restart a channel adapter that fails, with growing backoff, instead
+    /// of leaving it down
+    #[serde(default = "default_restart_on_failure")]
+    pub restart_on_failure: bool,
+    /// Restarts of a failing channel before it is left down
+    #[serde(default = "default_max_restarts")]
+    pub max_restarts: u32,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize, Default)]
+impl Default for ChannelsConfig {
+    fn default() -> Self {
+        Self {
+            telegram: Default::default(),
+            discord: Default::default(),
+            whatsapp: Default::default(),
+            identities: Default::default(),
+            restart_on_failure: default_restart_on_failure(),
+            max_restarts: default_max_restarts(),
+        }
+    }
+}
+
+impl ChannelsConfig {
+    /// Enabled channels, all of which take messages from remote users
+    pub fn enabled_channels(&self) -> Vec<&'static str> {
+        [
+            ("telegram", self.telegram.enabled),
+            ("discord", self.discord.enabled),
+            ("whatsapp", self.whatsapp.enabled),
+        ]
+        .into_iter()
+        .filter_map(|(name, enabled)| enabled.then_some(name))
+        .collect()
+    }
+}
+
+fn default_restart_on_failure() -> bool {
+    true
+}
+
+fn default_max_restarts() -> u32 {
+    5
+}
+
+impl ChannelsConfig {
+    /// Persona configured for a channel, if any
+    pub fn persona(&self, channel: &str) -> Option<&str> {
+        match channel {
+            "telegram" => self.telegram.persona.as_deref(),
+            "discord" => self.discord.persona.as_deref(),
+            "whatsapp" => self.whatsapp.persona.as_deref(),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, Default, JsonSchema)]
 pub struct TelegramConfig {
     #[serde(default)]
     pub enabled: bool,
     pub token: Option<String>,
     #[serde(default)]
     pub allowed_users: Vec<i64>,
+    /// Persona replacing the global base prompt on this channel; inline
+    /// text or a file path, like `prompt.system`
+    #[serde(default)]
+    pub persona: Option<String>,
+    /// How the assistant's Markdown is sent (as Telegram HTML by default)
+    #[serde(default)]
+    pub format: MessageFormat,
+    /// Message shown while a reply is generated, then replaced by it
+    #[serde(default)]
+    pub placeholder: PlaceholderConfig,
+}
+
+/// Opt-in message sent as soon as a message arrives and replaced by the
+/// reply once it is ready; where messages cannot be edited the reply
+/// follows it
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct PlaceholderConfig {
+    #[serde(default)]
+    pub enabled: bool,
+    #[serde(default = "default_placeholder_text")]
+    pub text: String,
+}
+
+fn default_placeholder_text() -> String {
+    "🤔 thinking…".to_string()
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize, Default)]
+impl Default for PlaceholderConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            text: default_placeholder_text(),
+        }
+    }
+}
+
+/// How a channel sends the Markdown replies of the assistant
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum MessageFormat {
+    /// Converted to the platform's own formatting
+    #[default]
+    Formatted,
+    /// Markup stripped
+    Plain,
+    /// Unchanged
+    Raw,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, Default, JsonSchema)]
 pub struct DiscordConfig {
     #[serde(default)]
     pub enabled: bool,
@@ -187,9 +532,19 @@ pub struct DiscordConfig {
     pub allowed_users: Vec<u64>,
     #[serde(default)]
     pub allowed_guilds: Vec<u64>,
+    /// Persona replacing the global base prompt on this channel; inline
+    /// text or a file path, like `prompt.system`
+    #[serde(default)]
+    pub persona: Option<String>,
+    /// How the assistant's Markdown is sent
+    #[serde(default)]
+    pub format: MessageFormat,
+    /// Message shown while a reply is generated, then replaced by it
+    #[serde(default)]
+    pub placeholder: PlaceholderConfig,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize, Default)]
+#[derive(Debug, Clone, Serialize, Deserialize, Default, JsonSchema)]
 pub struct WhatsAppChannelConfig {
     #[serde(default)]
     pub enabled: bool,
@@ -200,12 +555,71 @@ pub struct WhatsAppChannelConfig {
     /// Enable self-chat mode (only respond to messages from yourself)
     #[serde(default = "default_self_chat_mode")]
     pub self_chat_mode: bool,
+    /// Run the owner's own messages (from `phone_number`) in elevated mode,
+    /// so their tools need no approval; other senders still face policy
+    #[serde(default)]
+    pub auto_elevate_self: bool,
     /// Account ID for multi-account support (defaults to phone number)
     #[serde(default)]
     pub account_id: Option<String>,
+    /// Retry behaviour for outbound sends
+    #[serde(default)]
+    pub send_retry: RetryConfig,
+    /// Seconds to cache contact verification results (0 disables caching)
+    #[serde(default = "default_verify_cache_ttl_secs")]
+    pub verify_cache_ttl_secs: u64,
+    /// Mark incoming messages read and show "typing…" while replying
+    #[serde(default = "default_send_receipts")]
+    pub send_receipts: bool,
+    /// How the assistant's Markdown is sent (as `*bold*`/`_italic_` by
+    /// default)
+    #[serde(default)]
+    pub format: MessageFormat,
+    /// WhatsApp cannot edit sent messages, so an enabled placeholder shows
+    /// "typing…" while the reply is generated, even without
+    /// `send_receipts`; its text is not used
+    #[serde(default)]
+    pub placeholder: PlaceholderConfig,
+    /// Longer replies are split into several messages, at paragraph, line,
+    /// sentence or word boundaries (0 sends them whole)
+    #[serde(default = "default_whatsapp_max_message_chars")]
+    pub max_message_chars: usize,
+    /// Send replies longer than this as a `.txt` document instead of
+    /// splitting them; replies with code blocks or tables already once
+    /// they need splitting. Unset always splits.
+    #[serde(default)]
+    pub document_fallback_chars: Option<usize>,
+    /// Persona replacing the global base prompt on this channel; inline
+    /// text or a file path, like `prompt.system`
+    #[serde(default)]
+    pub persona: Option<String>,
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+/// Bounded exponential backoff for retrying transient failures
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct RetryConfig {
+    /// Total attempts including the first one
+    #[serde(default = "default_retry_attempts")]
+    pub max_attempts: u32,
+    /// Delay before the first retry, doubled on each subsequent retry
+    #[serde(default = "default_retry_initial_backoff_ms")]
+    pub initial_backoff_ms: u64,
+    /// Upper bound for a single retry delay
+    #[serde(default = "default_retry_max_backoff_ms")]
+    pub max_backoff_ms: u64,
+}
+
+impl Default for RetryConfig {
+    fn default() -> Self {
+        Self {
+            max_attempts: default_retry_attempts(),
+            initial_backoff_ms: default_retry_initial_backoff_ms(),
+            max_backoff_ms: default_retry_max_backoff_ms(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
 pub struct SessionsConfig {
     #[serde(default = "default_scope")]
     pub scope: String,
@@ -218,14 +632,139 @@ pub struct SessionsConfig {
     /// Channel routing mode: isolated, shared, or bridged
     #[serde(default = "default_channel_routing")]
     pub channel_routing: String,
+    /// Retry behaviour for a whole turn failing on a transient error (a
+    /// locked database, an unavailable LLM backend)
+    #[serde(default)]
+    pub retry: RetryConfig,
+    /// Periodic saving of streamed replies, so a crash leaves a partial
+    #[serde(default)]
+    pub drafts: DraftsConfig,
+    /// Deletion of sessions left idle
+    #[serde(default)]
+    pub expiry: SessionExpiryConfig,
+    /// Shortening of tool results from earlier turns sent to the model
+    #[serde(default)]
+    pub tool_results: ToolResultsConfig,
+    /// Most sessions a user may have; opening one more deletes their least
+    /// recently used session other than the shared `global` ones and those
+    /// with notes. Unset means no limit.
+    #[serde(default)]
+    pub max_per_user: Option<usize>,
 }
 
 fn default_compaction_enabled() -> bool {
     false
 }
 
+/// Opt-in saving of a streamed reply while it is generated. Every save is a
+/// database write, so keep this off on write-heavy setups.
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct DraftsConfig {
+    #[serde(default)]
+    pub enabled: bool,
+    /// Save the accumulated reply after this many streamed chunks...
+    #[serde(default = "default_draft_every_chunks")]
+    pub every_chunks: usize,
+    /// ...or after this many milliseconds since the last save
+    #[serde(default = "default_draft_interval_ms")]
+    pub interval_ms: u64,
+    /// What to do with drafts left by an interrupted reply on startup
+    #[serde(default)]
+    pub on_startup: DraftRecovery,
+}
+
+fn default_draft_every_chunks() -> usize {
+    50
+}
+
+fn default_draft_interval_ms() -> u64 {
+    2000
+}
+
+impl Default for DraftsConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            every_chunks: default_draft_every_chunks(),
+            interval_ms: default_draft_interval_ms(),
+            on_startup: DraftRecovery::default(),
+        }
+    }
+}
+
+/// Opt-in deletion of sessions, with their messages, after a period without
+/// activity. Sessions with pinned notes and sessions shared across channels
+/// are kept.
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct SessionExpiryConfig {
+    #[serde(default)]
+    pub enabled: bool,
+    /// Hours without activity after which a session is deleted
+    #[serde(default = "default_session_ttl_hours")]
+    pub ttl_hours: u64,
+    /// Minutes between checks for idle sessions
+    #[serde(default = "default_session_expiry_interval_minutes")]
+    pub check_interval_minutes: u64,
+}
+
+fn default_session_ttl_hours() -> u64 {
+    24 * 30
+}
+
+fn default_session_expiry_interval_minutes() -> u64 {
+    60
+}
+
+/// Tool results from earlier turns are re-sent with every later turn; this
+/// replaces them with a short summary in the context sent to the model. The
+/// stored transcript keeps them in full.
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct ToolResultsConfig {
+    /// Turns after its own that a tool result is still sent in full; unset
+    /// keeps every result in full
+    #[serde(default)]
+    pub keep_full_turns: Option<usize>,
+    /// Characters of an older result kept in its summary; 0 drops the
+    /// result, leaving a note that it was omitted
+    #[serde(default = "default_tool_result_summary_chars")]
+    pub summary_chars: usize,
+}
+
+fn default_tool_result_summary_chars() -> usize {
+    200
+}
+
+impl Default for ToolResultsConfig {
+    fn default() -> Self {
+        Self {
+            keep_full_turns: None,
+            summary_chars: default_tool_result_summary_chars(),
+        }
+    }
+}
+
+impl Default for SessionExpiryConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            ttl_hours: default_session_ttl_hours(),
+            check_interval_minutes: default_session_expiry_interval_minutes(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum DraftRecovery {
+    /// Store the partial reply as an assistant message marked `partial`
+    #[default]
+    Promote,
+    /// Drop the partial reply
+    Discard,
+}
+
 /// Channel routing modes for cross-channel context sharing
-#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, JsonSchema)]
 #[serde(rename_all = "snake_case")]
 pub enum ChannelRoutingMode {
     /// Each channel has isolated sessions (default)
@@ -243,11 +782,16 @@ impl Default for SessionsConfig {
             max_tokens: default_max_tokens(),
             compaction_enabled: default_compaction_enabled(),
             channel_routing: default_channel_routing(),
+            retry: RetryConfig::default(),
+            drafts: DraftsConfig::default(),
+            expiry: SessionExpiryConfig::default(),
+            tool_results: ToolResultsConfig::default(),
+            max_per_user: None,
         }
     }
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
 pub struct StorageConfig {
     #[serde(default = "default_storage_type")]
     pub storage_type: String,
@@ -264,12 +808,23 @@ impl Default for StorageConfig {
     }
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
 pub struct LoggingConfig {
     #[serde(default = "default_log_level")]
     pub level: String,
     #[serde(default = "default_log_format")]
     pub format: String,
+    /// Per-target directives such as `hyper=warn`, applied on top of `level`.
+    /// Ignored when `RUST_LOG` is set, which replaces the whole filter.
+    #[serde(default)]
+    pub filters: Vec<String>,
+    /// Also write logs to a rotating file (stdout only when unset)
+    #[serde(default)]
+    pub file: Option<LogFileConfig>,
+    /// Export tracing spans to an OpenTelemetry collector (needs a build
+    /// with the `otel` feature)
+    #[serde(default)]
+    pub otlp: Option<OtlpConfig>,
 }
 
 impl Default for LoggingConfig {
@@ -277,12 +832,54 @@ impl Default for LoggingConfig {
         Self {
             level: default_log_level(),
             format: default_log_format(),
+            filters: Vec::new(),
+            file: None,
+            otlp: None,
         }
     }
 }
 
+/// OpenTelemetry span export over OTLP/gRPC
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct OtlpConfig {
+    /// Collector endpoint (default: http://localhost:4317)
+    #[serde(default = "default_otlp_endpoint")]
+    pub endpoint: String,
+    /// `service.name` reported with every span (default: rustyclaw)
+    #[serde(default = "default_otlp_service_name")]
+    pub service_name: String,
+}
+
+/// Rotating log file
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct LogFileConfig {
+    /// Log file path; rotated files are written next to it
+    pub path: PathBuf,
+    /// When to start a new file (default: daily)
+    #[serde(default)]
+    pub rotation: LogRotation,
+    /// Size limit per file for `size` rotation (default: 100 MB)
+    #[serde(default = "default_log_max_size_mb")]
+    pub max_size_mb: u64,
+    /// Rotated files to keep (default: 7)
+    #[serde(default = "default_log_max_files")]
+    pub max_files: usize,
+}
+
+/// Log file rotation policy
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum LogRotation {
+    Hourly,
+    #[default]
+    Daily,
+    /// Rotate once the file reaches `max_size_mb`
+    Size,
+    Never,
+}
+
 /// Workspace configuration for dynamic system prompts
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
 pub struct WorkspaceConfig {
     /// Path to the workspace directory containing markdown files
     #[serde(default = "default_workspace_path")]
@@ -301,7 +898,328 @@ impl Default for WorkspaceConfig {
     }
 }
 
+/// Deployment-wide system prompt configuration
+#[derive(Debug, Clone, Default, Serialize, Deserialize, JsonSchema)]
+pub struct PromptConfig {
+    /// Base persona prepended to every system prompt. Either inline text or a
+    /// path to a file; supports `{{date}}` and `{{user}}` placeholders.
+    #[serde(default)]
+    pub system: Option<String>,
+    /// Describe the tools in the prompt of models that cannot call them, so
+    /// they can suggest one to the user
+    #[serde(default)]
+    pub describe_tools_without_calling: bool,
+    /// Detect the language of each message and ask for the reply in it;
+    /// `/lang` sets the language of a session instead
+    #[serde(default)]
+    pub detect_language: bool,
+    /// Reply sent instead of an empty answer from the model (default: "I
+    /// wasn't able to generate a response, please try again.")
+    #[serde(default)]
+    pub empty_reply: Option<String>,
+    /// Transforms applied in order to every final reply before it is stored
+    /// and sent
+    #[serde(default)]
+    pub reply_filters: Vec<ReplyFilter>,
+}
+
+/// A transform of the model's final reply
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum ReplyFilter {
+    /// Remove `<think>` reasoning, including an unclosed block and whatever
+    /// precedes a stray closing tag
+    StripThinking,
+    /// Remove leading, trailing and end-of-line whitespace and collapse runs
+    /// of blank lines into one
+    Trim,
+    /// Cut the reply to `max_chars` characters, ending it with `suffix`
+    Truncate {
+        max_chars: usize,
+        #[serde(default = "default_truncate_suffix")]
+        suffix: String,
+    },
+    /// Replace every match of `pattern`; `replacement` may refer to groups
+    /// as `$1` or `${name}`
+    RegexReplace {
+        pattern: String,
+        #[serde(default)]
+        replacement: String,
+    },
+}
+
+fn default_truncate_suffix() -> String {
+    "…".to_string()
+}
+
+/// Timezone settings for dates shown to the model and memory log names
+#[derive(Debug, Clone, Default, Serialize, Deserialize, JsonSchema)]
+pub struct LocaleConfig {
+    /// Default IANA timezone (e.g. "Europe/Berlin"); host local time if unset
+    #[serde(default)]
+    pub timezone: Option<String>,
+    /// Per-user timezone overrides keyed by user ID
+    #[serde(default)]
+    pub user_timezones: HashMap<String, String>,
+}
+
+impl LocaleConfig {
+    /// Timezone for a user, falling back to the default
+    pub fn timezone_for(&self, user_id: Option<&str>) -> Option<String> {
+        user_id
+            .and_then(|id| self.user_timezones.get(id))
+            .or(self.timezone.as_ref())
+            .cloned()
+    }
+}
+
+/// Proxy for outbound HTTP requests; unset values fall back to the
+/// standard `HTTP_PROXY` / `HTTPS_PROXY` / `NO_PROXY` environment variables
+#[derive(Debug, Clone, Default, Serialize, Deserialize, JsonSchema)]
+pub struct NetworkConfig {
+    #[serde(default)]
+    pub http_proxy: Option<String>,
+    #[serde(default)]
+    pub https_proxy: Option<String>,
+    /// Comma-separated hosts, domains and CIDR ranges reached directly
+    #[serde(default)]
+    pub no_proxy: Option<String>,
+}
+
+/// Opt-in moderation of incoming messages before generation
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct ModerationConfig {
+    #[serde(default)]
+    pub enabled: bool,
+    #[serde(default)]
+    pub backend: ModerationBackend,
+    /// Base URL of an OpenAI-compatible API (defaults to `llm.base_url`)
+    #[serde(default)]
+    pub base_url: Option<String>,
+    #[serde(default)]
+    pub model: Option<String>,
+    /// Environment variable holding the moderation API key
+    #[serde(default)]
+    pub api_key_env: Option<String>,
+    /// Local classifier (program and arguments) for the `command` backend
+    #[serde(default)]
+    pub command: Vec<String>,
+    /// Category score at or above which a message is refused
+    #[serde(default = "default_moderation_threshold")]
+    pub threshold: f32,
+    #[serde(default = "default_moderation_refusal")]
+    pub refusal_message: String,
+    /// Refuse messages when the moderation backend fails
+    #[serde(default)]
+    pub fail_closed: bool,
+}
+
+impl Default for ModerationConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            backend: ModerationBackend::default(),
+            base_url: None,
+            model: None,
+            api_key_env: None,
+            command: vec![],
+            threshold: default_moderation_threshold(),
+            refusal_message: default_moderation_refusal(),
+            fail_closed: false,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum ModerationBackend {
+    /// `POST /moderations` on an OpenAI-compatible API
+    #[default]
+    Openai,
+    /// Local classifier command
+    Command,
+}
+/// Opt-in masking of PII in stored messages and the tool audit log
+/// Opt-in masking of PII in stored messages
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct RedactionConfig {
+    #[serde(default)]
+    pub enabled: bool,
+    /// Patterns applied in order (defaults: email, credit card, phone)
+    #[serde(default = "default_redaction_patterns")]
+    pub patterns: Vec<RedactionPattern>,
+    #[serde(default = "default_redaction_replacement")]
+    pub replacement: String,
+    /// Also send the redacted text to the model instead of the original
+    #[serde(default)]
+    pub redact_model_input: bool,
+}
+
+impl Default for RedactionConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            patterns: default_redaction_patterns(),
+            replacement: default_redaction_replacement(),
+            redact_model_input: false,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+pub struct RedactionPattern {
+    /// Recorded in message metadata when the pattern matches
+    pub name: String,
+    pub regex: String,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize, JsonSchema)]
+pub struct PluginsConfig {
+    #[serde(default)]
+    pub hooks: HookConfig,
+}
+
+/// How plugin hooks run
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct HookConfig {
+    /// How hooks that only observe an event run. Hooks that can modify
+    /// behavior always run one after another in priority order.
+    #[serde(default)]
+    pub execution: HookExecution,
+    /// How long a single hook may run before it is skipped (default: 5000)
+    #[serde(default = "default_hook_timeout_ms")]
+    pub timeout_ms: u64,
+}
+
+impl Default for HookConfig {
+    fn default() -> Self {
+        Self {
+            execution: HookExecution::default(),
+            timeout_ms: default_hook_timeout_ms(),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum HookExecution {
+    /// All hooks for the event at once (default)
+    #[default]
+    Concurrent,
+    /// One after another, highest priority first
+    Sequential,
+}
+
+/// Prices of models and ceilings on what a conversation may cost
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct CostsConfig {
+    /// Price per 1,000 tokens by model name; models not listed cost nothing
+    #[serde(default)]
+    pub prices: HashMap<String, f64>,
+    /// Most a session may cost until it is reset (no limit by default)
+    #[serde(default)]
+    pub session_limit: Option<f64>,
+    /// Most a user may cost across sessions until an admin resets it (no
+    /// limit by default)
+    #[serde(default)]
+    pub user_limit: Option<f64>,
+    /// Fraction of a limit at which a warning is emitted (default: 0.8)
+    #[serde(default = "default_cost_warn_at")]
+    pub warn_at: f64,
+}
+
+impl Default for CostsConfig {
+    fn default() -> Self {
+        Self {
+            prices: HashMap::new(),
+            session_limit: None,
+            user_limit: None,
+            warn_at: default_cost_warn_at(),
+        }
+    }
+}
+
+/// Prompt run on a cron schedule, its reply delivered to a channel
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, JsonSchema)]
+pub struct ScheduleConfig {
+    /// Cron expression: `min hour day month weekday`, optionally with a
+    /// leading seconds field. Prefer weekday names (`MON-FRI`): numbers
+    /// count from 1 = Sunday
+    pub cron: String,
+    pub prompt: String,
+    /// Channel the reply is sent to: telegram, discord or whatsapp
+    pub channel: String,
+    /// Telegram chat ID, Discord user ID, or WhatsApp phone number / group
+    /// (`self` for the connected account)
+    pub target: String,
+    /// IANA timezone the expression is read in (default: `locale.timezone`,
+    /// then UTC)
+    #[serde(default)]
+    pub timezone: Option<String>,
+}
+
 // Default functions
+fn default_redaction_patterns() -> Vec<RedactionPattern> {
+    [
+        ("email", r"[A-Za-z0-9._%+-]+@[A-Za-z0-9.-]+\.[A-Za-z]{2,}"),
+        ("credit_card", r"\b(?:\d[ -]?){12,18}\d\b"),
+        (
+            "phone",
+            r"(?:\+\d{1,3}[\s.-]?\(?|\(|\b)\d{3}\)?[\s.-]?\d{3}[\s.-]?\d{4}\b",
+        ),
+    ]
+    .into_iter()
+    .map(|(name, regex)| RedactionPattern {
+        name: name.to_string(),
+        regex: regex.to_string(),
+    })
+    .collect()
+}
+
+fn default_redaction_replacement() -> String {
+    "[REDACTED]".to_string()
+}
+
+fn default_hook_timeout_ms() -> u64 {
+    5000
+}
+
+fn default_cost_warn_at() -> f64 {
+    0.8
+}
+
+fn default_moderation_threshold() -> f32 {
+    0.5
+}
+
+fn default_moderation_refusal() -> String {
+    "Sorry, I can't help with that request.".to_string()
+}
+
+fn default_invite_ttl_secs() -> u64 {
+    24 * 60 * 60
+}
+
+fn default_impersonation_ttl_secs() -> u64 {
+    15 * 60
+}
+
+fn default_oidc_scopes() -> Vec<String> {
+    vec![
+        "openid".to_string(),
+        "email".to_string(),
+        "profile".to_string(),
+    ]
+}
+
+fn default_oidc_role() -> String {
+    "user".to_string()
+}
+
+fn default_socket_mode() -> String {
+    "660".to_string()
+}
+
 fn default_host() -> String {
     "127.0.0.1".to_string()
 }
@@ -349,6 +1267,22 @@ fn default_log_format() -> String {
     "pretty".to_string()
 }
 
+fn default_log_max_size_mb() -> u64 {
+    100
+}
+
+fn default_log_max_files() -> usize {
+    7
+}
+
+fn default_otlp_endpoint() -> String {
+    "http://localhost:4317".to_string()
+}
+
+fn default_otlp_service_name() -> String {
+    "rustyclaw".to_string()
+}
+
 fn default_cache_type() -> String {
     "ram".to_string()
 }
@@ -375,6 +1309,30 @@ fn default_self_chat_mode() -> bool {
     true
 }
 
+fn default_send_receipts() -> bool {
+    true
+}
+
+fn default_verify_cache_ttl_secs() -> u64 {
+    3600
+}
+
+fn default_whatsapp_max_message_chars() -> usize {
+    4000
+}
+
+fn default_retry_attempts() -> u32 {
+    3
+}
+
+fn default_retry_initial_backoff_ms() -> u64 {
+    500
+}
+
+fn default_retry_max_backoff_ms() -> u64 {
+    5000
+}
+
 fn default_channel_routing() -> String {
     "isolated".to_string()
 }
@@ -387,8 +1345,16 @@ fn default_api_port() -> u16 {
     18789
 }
 
+fn default_batch_max_size() -> usize {
+    50
+}
+
+fn default_batch_concurrency() -> usize {
+    4
+}
+
 // Sandbox configuration
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
 pub struct SandboxConfig {
     /// Sandbox mode: off, non-main, or all
     #[serde(default = "default_sandbox_mode")]
@@ -406,6 +1372,12 @@ pub struct SandboxConfig {
     #[serde(default = "default_workspace_mode")]
     pub workspace: crate::sandbox::WorkspaceMode,
 
+    /// Directory holding one workspace directory per session, shared by that
+    /// session's exec and skill calls and mounted at `/session` in its
+    /// container (default: ~/.rustyclaw/sessions)
+    #[serde(default = "crate::sandbox::default_session_workspace_root")]
+    pub session_workspace_root: PathBuf,
+
     /// Enable network access for containers
     #[serde(default)]
     pub network: bool,
@@ -421,6 +1393,10 @@ pub struct SandboxConfig {
     /// Automatic pruning configuration
     #[serde(default)]
     pub pruning: crate::sandbox::PruningConfig,
+
+    /// Host environment variables passed to exec/skill processes (all others are scrubbed)
+    #[serde(default = "crate::sandbox::default_env_allowlist")]
+    pub env_allowlist: Vec<String>,
 }
 
 impl Default for SandboxConfig {
@@ -430,14 +1406,20 @@ impl Default for SandboxConfig {
             scope: default_sandbox_scope(),
             image: default_sandbox_image(),
             workspace: default_workspace_mode(),
+            session_workspace_root: crate::sandbox::default_session_workspace_root(),
             network: false,
             setup_command: None,
             mounts: vec![],
             pruning: Default::default(),
+            env_allowlist: crate::sandbox::default_env_allowlist(),
         }
     }
 }
 
+fn default_tool_policy() -> crate::tools::policy::ToolAccessLevel {
+    crate::tools::policy::ToolAccessLevel::Deny
+}
+
 fn default_sandbox_mode() -> crate::sandbox::SandboxMode {
     crate::sandbox::SandboxMode::NonMain
 }
@@ -455,23 +1437,242 @@ fn default_workspace_mode() -> crate::sandbox::WorkspaceMode {
 }
 
 // Tools configuration
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
 pub struct ToolsConfig {
     /// Tool access policies: tool_name -> access_level (allow, deny, elevated)
     #[serde(default)]
     pub policies: HashMap<String, String>,
+    /// Access level of tools without a policy (deny, elevated or allow;
+    /// default: deny)
+    #[serde(default = "default_tool_policy")]
+    pub default_policy: crate::tools::policy::ToolAccessLevel,
+    /// Turn off exec, bash, skills, plugin tools and file-changing tools
+    /// whatever the policies say, leaving only read-only and messaging tools
+    /// (default: false)
+    #[serde(default)]
+    pub safe_mode: bool,
     /// Directory to watch for skill files (default: ~/.rustyclaw/skills)
     #[serde(default = "default_skills_dir")]
     pub skills_dir: String,
     /// Enable the skill watcher (default: true)
     #[serde(default = "default_skills_enabled")]
     pub skills_enabled: bool,
+    /// Prefix skills in sub-directories of `skills_dir` with their folder,
+    /// e.g. `web__fetch` (default: false)
+    #[serde(default)]
+    pub namespace_skills: bool,
     /// Directory for user-created tools (default: ~/.rustyclaw/skills/user-created)
     #[serde(default = "default_user_tools_dir")]
     pub user_tools_dir: String,
     /// Enable tool creation via API (default: true)
     #[serde(default = "default_tool_creation_enabled")]
     pub creation_enabled: bool,
+    /// Dangerous command detection for exec/bash
+    #[serde(default)]
+    pub command_guard: CommandGuardConfig,
+    /// Timeout for tools that do not declare their own (default: 120s)
+    #[serde(default = "default_tool_timeout_secs")]
+    pub default_timeout_secs: u64,
+    /// Skip all tool policy checks (local development only, default: false).
+    /// Cannot be changed through the API and is ignored unless the API
+    /// listens on a loopback address and no channel is enabled.
+    #[serde(default)]
+    pub dev_bypass_policy: bool,
+    /// Users and roles whose elevated tool calls skip interactive approval
+    #[serde(default)]
+    pub auto_approve: AutoApproveConfig,
+    /// Roles held by each user: user_id -> role names
+    #[serde(default)]
+    pub user_roles: HashMap<String, Vec<String>>,
+    /// Argument patterns that escalate individual calls: tool_name -> rules
+    #[serde(default)]
+    pub argument_rules: HashMap<String, Vec<ArgumentRuleConfig>>,
+    /// Tags offered to the model when a session has not chosen its own.
+    /// Empty offers every tool; core and untagged tools are always offered.
+    #[serde(default)]
+    pub default_tags: Vec<String>,
+    /// Embedding-based selection of the most relevant skill/plugin tools
+    #[serde(default)]
+    pub selection: ToolSelectionConfig,
+    /// Document ingestion for the `search_docs` tool
+    #[serde(default)]
+    pub knowledge: KnowledgeConfig,
+    /// Largest tool call arguments accepted, in bytes (default: 256 KiB)
+    #[serde(default = "default_max_argument_bytes")]
+    pub max_argument_bytes: usize,
+    /// Per-tool overrides of `max_argument_bytes`: tool_name -> bytes
+    #[serde(default)]
+    pub argument_size_limits: HashMap<String, usize>,
+    /// Most tool definitions sent to the model per turn, after tag and
+    /// relevance filtering. Built-in tools are always sent; the skill and
+    /// plugin tools used least recently are dropped first (default: no cap)
+    #[serde(default)]
+    pub max_definitions: Option<usize>,
+    /// Repair of malformed tool call arguments
+    #[serde(default)]
+    pub json_repair: JsonRepairConfig,
+}
+
+/// Tool call arguments that are not valid JSON are repaired when the fix is
+/// obvious (trailing commas, unquoted keys, ...); otherwise the model is told
+/// what is wrong and asked to resend the call
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct JsonRepairConfig {
+    /// Repair arguments before rejecting them (default: true)
+    #[serde(default = "default_json_repair_enabled")]
+    pub enabled: bool,
+    /// Times per turn the model is asked to resend a call whose arguments
+    /// could not be repaired (default: 1)
+    #[serde(default = "default_json_resend_attempts")]
+    pub resend_attempts: usize,
+}
+
+impl Default for JsonRepairConfig {
+    fn default() -> Self {
+        Self {
+            enabled: default_json_repair_enabled(),
+            resend_attempts: default_json_resend_attempts(),
+        }
+    }
+}
+
+fn default_json_repair_enabled() -> bool {
+    true
+}
+
+fn default_json_resend_attempts() -> usize {
+    1
+}
+
+/// Per-turn tool retrieval: only the `top_k` skill and plugin tools closest to
+/// the user message are offered, alongside the built-in tools
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct ToolSelectionConfig {
+    #[serde(default)]
+    pub enabled: bool,
+    #[serde(default = "default_tool_selection_top_k")]
+    pub top_k: usize,
+    #[serde(default = "default_embedding_model")]
+    pub embedding_model: String,
+}
+
+impl Default for ToolSelectionConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            top_k: default_tool_selection_top_k(),
+            embedding_model: default_embedding_model(),
+        }
+    }
+}
+
+/// Text, Markdown and PDF files in `dir` are chunked, embedded and kept
+/// searchable through the `search_docs` tool
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct KnowledgeConfig {
+    #[serde(default)]
+    pub enabled: bool,
+    #[serde(default = "default_knowledge_dir")]
+    pub dir: String,
+    /// Model used to embed chunks; without one, search falls back to keywords
+    #[serde(default = "default_knowledge_embedding_model")]
+    pub embedding_model: Option<String>,
+    /// Maximum chunk length in characters
+    #[serde(default = "default_knowledge_chunk_size")]
+    pub chunk_size: usize,
+    /// Chunks returned by a search unless the call asks for fewer or more
+    #[serde(default = "default_knowledge_top_k")]
+    pub top_k: usize,
+}
+
+impl Default for KnowledgeConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            dir: default_knowledge_dir(),
+            embedding_model: default_knowledge_embedding_model(),
+            chunk_size: default_knowledge_chunk_size(),
+            top_k: default_knowledge_top_k(),
+        }
+    }
+}
+
+fn default_tool_selection_top_k() -> usize {
+    8
+}
+
+fn default_knowledge_dir() -> String {
+    dirs::home_dir()
+        .map(|h: std::path::PathBuf| {
+            h.join(".rustyclaw")
+                .join("knowledge")
+                .to_string_lossy()
+                .to_string()
+        })
+        .unwrap_or_else(|| "./.rustyclaw/knowledge".to_string())
+}
+
+fn default_knowledge_embedding_model() -> Option<String> {
+    Some(default_embedding_model())
+}
+
+fn default_knowledge_chunk_size() -> usize {
+    1000
+}
+
+fn default_knowledge_top_k() -> usize {
+    5
+}
+
+fn default_embedding_model() -> String {
+    "nomic-embed-text".to_string()
+}
+
+impl ToolsConfig {
+    /// Whether the dev policy bypass takes effect for this deployment: only
+    /// when every inbound surface is local, i.e. the API is bound to loopback
+    /// and no channel is enabled
+    pub fn dev_bypass_active(&self, api: &ApiConfig, channels: &ChannelsConfig) -> bool {
+        self.dev_bypass_policy
+            && is_loopback_host(&api.host)
+            && channels.enabled_channels().is_empty()
+    }
+
+    /// User IDs on the approval allowlist, directly or through one of their roles
+    pub fn auto_approved_users(&self) -> HashSet<String> {
+        let by_role = self.user_roles.iter().filter(|(_, roles)| {
+            roles
+                .iter()
+                .any(|role| self.auto_approve.roles.contains(role))
+        });
+
+        self.auto_approve
+            .users
+            .iter()
+            .chain(by_role.map(|(user, _)| user))
+            .cloned()
+            .collect()
+    }
+}
+
+/// Approval allowlist: listed users get elevated tools without approval
+#[derive(Debug, Clone, Default, Serialize, Deserialize, JsonSchema)]
+pub struct AutoApproveConfig {
+    /// User IDs approved automatically
+    #[serde(default)]
+    pub users: Vec<String>,
+    /// Roles (see `tools.user_roles`) whose members are approved automatically
+    #[serde(default)]
+    pub roles: Vec<String>,
+}
+
+/// Whether a bind address only accepts local connections
+fn is_loopback_host(host: &str) -> bool {
+    host.eq_ignore_ascii_case("localhost")
+        || host
+            .parse::<std::net::IpAddr>()
+            .map(|ip| ip.is_loopback())
+            .unwrap_or(false)
 }
 
 impl Default for ToolsConfig {
@@ -489,15 +1690,91 @@ impl Default for ToolsConfig {
                 ("read_file".to_string(), "elevated".to_string()),
                 ("write_file".to_string(), "elevated".to_string()),
                 ("list_files".to_string(), "elevated".to_string()),
+                ("search_docs".to_string(), "allow".to_string()),
             ]),
+            default_policy: default_tool_policy(),
+            safe_mode: false,
             skills_dir: default_skills_dir(),
             skills_enabled: default_skills_enabled(),
+            namespace_skills: false,
             user_tools_dir: default_user_tools_dir(),
             creation_enabled: default_tool_creation_enabled(),
+            command_guard: CommandGuardConfig::default(),
+            default_timeout_secs: default_tool_timeout_secs(),
+            dev_bypass_policy: false,
+            auto_approve: AutoApproveConfig::default(),
+            user_roles: HashMap::new(),
+            argument_rules: HashMap::new(),
+            default_tags: Vec::new(),
+            selection: ToolSelectionConfig::default(),
+            knowledge: KnowledgeConfig::default(),
+            max_argument_bytes: default_max_argument_bytes(),
+            argument_size_limits: HashMap::new(),
+            max_definitions: None,
+            json_repair: JsonRepairConfig::default(),
         }
     }
 }
 
+/// Regex matched against a tool call's arguments as compact JSON with sorted
+/// keys and string escapes decoded
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct ArgumentRuleConfig {
+    pub pattern: String,
+    /// What a match does to an otherwise allowed call
+    pub action: ArgumentRuleAction,
+}
+
+/// Escalation applied when an argument rule matches
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum ArgumentRuleAction {
+    /// Ask the user before running the call
+    RequireApproval,
+    /// Never run the call
+    Deny,
+}
+
+/// Denylist of command patterns blocked before exec/bash runs
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+pub struct CommandGuardConfig {
+    /// Enable dangerous command blocking (default: true)
+    #[serde(default = "default_command_guard_enabled")]
+    pub enabled: bool,
+    /// Include the built-in denylist patterns (default: true)
+    #[serde(default = "default_command_guard_use_defaults")]
+    pub use_defaults: bool,
+    /// Additional regex patterns to block
+    #[serde(default)]
+    pub patterns: Vec<String>,
+}
+
+impl Default for CommandGuardConfig {
+    fn default() -> Self {
+        Self {
+            enabled: default_command_guard_enabled(),
+            use_defaults: default_command_guard_use_defaults(),
+            patterns: vec![],
+        }
+    }
+}
+
+fn default_tool_timeout_secs() -> u64 {
+    120
+}
+
+fn default_max_argument_bytes() -> usize {
+    256 * 1024
+}
+
+fn default_command_guard_enabled() -> bool {
+    true
+}
+
+fn default_command_guard_use_defaults() -> bool {
+    true
+}
+
 fn default_skills_dir() -> String {
     dirs::home_dir()
         .map(|h: std::path::PathBuf| {
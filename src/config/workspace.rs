@@ -21,6 +21,8 @@ pub enum WorkspaceFile {
     User,
     /// TOOLS.md - Tool usage conventions and creation guide
     Tools,
+    /// HELP.md - Welcome and `/help` text shown to users (not the model)
+    Help,
 }
 
 impl WorkspaceFile {
@@ -32,6 +34,7 @@ impl WorkspaceFile {
             WorkspaceFile::Agents => "AGENTS.md",
             WorkspaceFile::User => "USER.md",
             WorkspaceFile::Tools => "TOOLS.md",
+            WorkspaceFile::Help => "HELP.md",
         }
     }
 
@@ -43,6 +46,7 @@ impl WorkspaceFile {
             WorkspaceFile::Agents,
             WorkspaceFile::User,
             WorkspaceFile::Tools,
+            WorkspaceFile::Help,
         ]
     }
 
@@ -54,6 +58,7 @@ impl WorkspaceFile {
             "AGENTS.MD" | "AGENTS" => Some(WorkspaceFile::Agents),
             "USER.MD" | "USER" => Some(WorkspaceFile::User),
             "TOOLS.MD" | "TOOLS" => Some(WorkspaceFile::Tools),
+            "HELP.MD" | "HELP" => Some(WorkspaceFile::Help),
             _ => None,
         }
     }
@@ -120,6 +125,17 @@ impl Workspace {
         }
     }
 
+    /// Load a workspace file, falling back to the built-in default when the
+    /// file is absent
+    pub fn load_or_default(&self, file_type: WorkspaceFile) -> String {
+        if self.file_path(file_type).exists() {
+            if let Some(content) = self.load_file(file_type) {
+                return content;
+            }
+        }
+        self.default_content(file_type).to_string()
+    }
+
     /// Save a workspace file
     pub fn save_file(&self, file_type: WorkspaceFile, content: &str) -> Result<()> {
         let file_path = self.file_path(file_type);
@@ -158,6 +174,7 @@ impl Workspace {
             WorkspaceFile::Agents => include_str!("../../templates/AGENTS.md"),
             WorkspaceFile::User => include_str!("../../templates/USER.md"),
             WorkspaceFile::Tools => include_str!("../../templates/TOOLS.md"),
+            WorkspaceFile::Help => include_str!("../../templates/HELP.md"),
         }
     }
 }
@@ -174,6 +191,7 @@ mod tests {
         assert_eq!(WorkspaceFile::Agents.filename(), "AGENTS.md");
         assert_eq!(WorkspaceFile::User.filename(), "USER.md");
         assert_eq!(WorkspaceFile::Tools.filename(), "TOOLS.md");
+        assert_eq!(WorkspaceFile::Help.filename(), "HELP.md");
     }
 
     #[test]
@@ -203,7 +221,7 @@ mod tests {
 
         // Should be able to list and load files
         let files = workspace.list_files();
-        assert_eq!(files.len(), 5);
+        assert_eq!(files.len(), 6);
 
         let soul = workspace.load_file(WorkspaceFile::Soul);
         assert!(soul.is_some());
@@ -224,4 +242,22 @@ mod tests {
         let loaded = workspace.load_file(WorkspaceFile::Soul).unwrap();
         assert_eq!(loaded, new_content);
     }
+
+    #[test]
+    fn test_load_or_default_falls_back_to_template() {
+        let dir = tempdir().unwrap();
+        let workspace = Workspace::new(dir.path());
+        assert_eq!(
+            workspace.load_or_default(WorkspaceFile::Help),
+            include_str!("../../templates/HELP.md")
+        );
+
+        workspace
+            .save_file(WorkspaceFile::Help, "Ask me anything.")
+            .unwrap();
+        assert_eq!(
+            workspace.load_or_default(WorkspaceFile::Help),
+            "Ask me anything."
+        );
+    }
 }
@@ -1,18 +1,62 @@
+use crate::storage::{PendingApprovalRecord, Storage};
+use crate::tools::policy::ToolPolicyEngine;
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
 use std::collections::HashMap;
 use std::sync::Arc;
 use std::time::Instant;
-use tokio::sync::RwLock;
+use tokio::sync::{broadcast, RwLock};
 use uuid::Uuid;
 
+/// How long a tool call waits for the user to answer an approval request
+pub const APPROVAL_TIMEOUT_SECS: u64 = 60;
+
 /// Represents a pending tool approval request
 #[derive(Debug, Clone)]
 pub struct PendingApproval {
     pub request_id: String,
+    pub session_id: String,
     pub tool_name: String,
     pub arguments: String,
     pub policy: String,
     pub sandbox_available: bool,
     pub timestamp: Instant,
+    pub created_at: DateTime<Utc>,
+    pub expires_at: DateTime<Utc>,
+}
+
+impl PendingApproval {
+    fn to_record(&self) -> PendingApprovalRecord {
+        PendingApprovalRecord {
+            request_id: self.request_id.clone(),
+            session_id: self.session_id.clone(),
+            tool_name: self.tool_name.clone(),
+            arguments: self.arguments.clone(),
+            policy: self.policy.clone(),
+            sandbox_available: self.sandbox_available,
+            created_at: self.created_at,
+            expires_at: self.expires_at,
+        }
+    }
+
+    fn from_record(record: PendingApprovalRecord) -> Self {
+        Self {
+            request_id: record.request_id,
+            session_id: record.session_id,
+            tool_name: record.tool_name,
+            arguments: record.arguments,
+            policy: record.policy,
+            sandbox_available: record.sandbox_available,
+            timestamp: Instant::now(),
+            created_at: record.created_at,
+            expires_at: record.expires_at,
+        }
+    }
+
+    /// Whether the request can no longer be answered
+    pub fn is_expired(&self) -> bool {
+        self.expires_at <= Utc::now()
+    }
 }
 
 /// User response to an approval request
@@ -24,6 +68,45 @@ pub struct ApprovalResponse {
     pub timestamp: Instant,
 }
 
+/// Persistence for pending and remembered approvals, so they survive a restart
+#[async_trait]
+pub trait ApprovalStore: Send + Sync {
+    async fn save(&self, approval: PendingApprovalRecord) -> anyhow::Result<()>;
+    async fn delete(&self, request_id: &str) -> anyhow::Result<()>;
+    async fn list(&self) -> anyhow::Result<Vec<PendingApprovalRecord>>;
+    async fn save_remembered(&self, session_id: &str, tool_name: &str) -> anyhow::Result<()>;
+    async fn delete_remembered(&self, session_id: &str) -> anyhow::Result<()>;
+    /// Remembered approvals as (session ID, tool name)
+    async fn list_remembered(&self) -> anyhow::Result<Vec<(String, String)>>;
+}
+
+#[async_trait]
+impl<S: Storage> ApprovalStore for S {
+    async fn save(&self, approval: PendingApprovalRecord) -> anyhow::Result<()> {
+        self.save_pending_approval(approval).await
+    }
+
+    async fn delete(&self, request_id: &str) -> anyhow::Result<()> {
+        self.delete_pending_approval(request_id).await
+    }
+
+    async fn list(&self) -> anyhow::Result<Vec<PendingApprovalRecord>> {
+        self.list_pending_approvals().await
+    }
+
+    async fn save_remembered(&self, session_id: &str, tool_name: &str) -> anyhow::Result<()> {
+        self.save_session_tool_approval(session_id, tool_name).await
+    }
+
+    async fn delete_remembered(&self, session_id: &str) -> anyhow::Result<()> {
+        self.delete_session_tool_approvals(session_id).await
+    }
+
+    async fn list_remembered(&self) -> anyhow::Result<Vec<(String, String)>> {
+        self.list_session_tool_approvals().await
+    }
+}
+
 /// Manages tool approval requests and responses
 ///
 /// This manager handles the asynchronous approval flow:
@@ -32,12 +115,21 @@ pub struct ApprovalResponse {
 /// 3. Server waits for response from client
 /// 4. Client responds with approval decision
 /// 5. Server continues tool execution based on response
+///
+/// With a store attached, pending requests are persisted until they are
+/// answered or time out, and restored by `restore_pending` after a restart.
+/// Approvals remembered for a session are persisted too, until the session
+/// is reset, and restored into the policy engine by `restore_remembered`.
 #[derive(Clone)]
 pub struct ApprovalManager {
     /// Map of session_id → (request_id → PendingApproval)
     pending: Arc<RwLock<HashMap<String, HashMap<String, PendingApproval>>>>,
     /// Map of request_id → ApprovalResponse
     responses: Arc<RwLock<HashMap<String, ApprovalResponse>>>,
+    /// Persistent copy of pending requests and remembered approvals
+    store: Option<Arc<dyn ApprovalStore>>,
+    /// Announces newly created requests to channels that render them
+    created: broadcast::Sender<PendingApproval>,
 }
 
 impl ApprovalManager {
@@ -46,9 +138,104 @@ impl ApprovalManager {
         Self {
             pending: Arc::new(RwLock::new(HashMap::new())),
             responses: Arc::new(RwLock::new(HashMap::new())),
+            store: None,
+            created: broadcast::channel(64).0,
+        }
+    }
+
+    /// Persist pending approvals in the given store
+    pub fn with_store(mut self, store: Arc<dyn ApprovalStore>) -> Self {
+        self.store = Some(store);
+        self
+    }
+
+    /// Receive every approval request created from now on
+    pub fn subscribe(&self) -> broadcast::Receiver<PendingApproval> {
+        self.created.subscribe()
+    }
+
+    /// Reload pending approvals persisted before a restart.
+    ///
+    /// Requests that expired while the server was down are auto-denied and
+    /// dropped from the store. Returns the number of requests still pending.
+    pub async fn restore_pending(&self) -> anyhow::Result<usize> {
+        let Some(store) = &self.store else {
+            return Ok(0);
+        };
+
+        let mut restored = 0;
+        for record in store.list().await? {
+            let approval = PendingApproval::from_record(record);
+            if approval.is_expired() {
+                tracing::info!(
+                    "Auto-denied expired approval request: request_id={}, tool={}, session={}",
+                    approval.request_id,
+                    approval.tool_name,
+                    approval.session_id
+                );
+                store.delete(&approval.request_id).await?;
+                continue;
+            }
+
+            tracing::info!(
+                "Approval request still pending after restart: request_id={}, tool={}, session={}",
+                approval.request_id,
+                approval.tool_name,
+                approval.session_id
+            );
+            self.pending
+                .write()
+                .await
+                .entry(approval.session_id.clone())
+                .or_default()
+                .insert(approval.request_id.clone(), approval);
+            restored += 1;
+        }
+
+        Ok(restored)
+    }
+
+    /// Persist a tool approved for the rest of a session
+    pub async fn remember_for_session(&self, session_id: &str, tool_name: &str) {
+        if let Some(store) = &self.store {
+            if let Err(e) = store.save_remembered(session_id, tool_name).await {
+                tracing::warn!(
+                    "Failed to persist remembered approval of '{}' for session {}: {}",
+                    tool_name,
+                    session_id,
+                    e
+                );
+            }
         }
     }
 
+    /// Drop the persisted approvals remembered for a session
+    pub async fn forget_session(&self, session_id: &str) {
+        if let Some(store) = &self.store {
+            if let Err(e) = store.delete_remembered(session_id).await {
+                tracing::warn!(
+                    "Failed to remove remembered approvals for session {}: {}",
+                    session_id,
+                    e
+                );
+            }
+        }
+    }
+
+    /// Reload approvals remembered before a restart into the policy engine.
+    /// Returns the number of approvals restored.
+    pub async fn restore_remembered(&self, policy: &ToolPolicyEngine) -> anyhow::Result<usize> {
+        let Some(store) = &self.store else {
+            return Ok(0);
+        };
+
+        let remembered = store.list_remembered().await?;
+        for (session_id, tool_name) in &remembered {
+            policy.remember_approval(session_id, tool_name).await;
+        }
+        Ok(remembered.len())
+    }
+
     /// Create a new approval request and return the request_id
     pub async fn create_approval_request(
         &self,
@@ -59,21 +246,35 @@ impl ApprovalManager {
         sandbox_available: bool,
     ) -> String {
         let request_id = Uuid::new_v4().to_string();
+        let created_at = Utc::now();
         let approval = PendingApproval {
             request_id: request_id.clone(),
+            session_id: session_id.to_string(),
             tool_name: tool_name.to_string(),
             arguments: arguments.to_string(),
             policy: policy.to_string(),
             sandbox_available,
             timestamp: Instant::now(),
+            created_at,
+            expires_at: created_at + chrono::Duration::seconds(APPROVAL_TIMEOUT_SECS as i64),
         };
 
+        if let Some(store) = &self.store {
+            if let Err(e) = store.save(approval.to_record()).await {
+                tracing::warn!("Failed to persist approval request {}: {}", request_id, e);
+            }
+        }
+
         // Store pending approval
         let mut pending = self.pending.write().await;
         pending
             .entry(session_id.to_string())
             .or_insert_with(HashMap::new)
-            .insert(request_id.clone(), approval);
+            .insert(request_id.clone(), approval.clone());
+        drop(pending);
+
+        // Nobody listening is fine; the request stays listed as pending
+        let _ = self.created.send(approval);
 
         tracing::debug!(
             "Created approval request: request_id={}, tool={}, session={}",
@@ -106,7 +307,10 @@ impl ApprovalManager {
                         request_id,
                         response.approved
                     );
-                    return Some(response.clone());
+                    let response = response.clone();
+                    drop(responses);
+                    self.complete_request(request_id).await;
+                    return Some(response);
                 }
             }
 
@@ -116,6 +320,7 @@ impl ApprovalManager {
                     timeout_secs,
                     request_id
                 );
+                self.complete_request(request_id).await;
                 return None; // Timeout = deny
             }
 
@@ -141,6 +346,10 @@ impl ApprovalManager {
 
         let mut responses = self.responses.write().await;
         responses.insert(request_id.to_string(), response);
+        drop(responses);
+
+        // Answered requests no longer need to survive a restart
+        self.delete_stored(request_id).await;
 
         tracing::debug!(
             "Stored approval response: request_id={}, approved={}",
@@ -162,10 +371,57 @@ impl ApprovalManager {
             .cloned()
     }
 
+    /// Unanswered, unexpired requests, oldest first
+    pub async fn list_pending(&self) -> Vec<PendingApproval> {
+        let pending = self.pending.read().await;
+        let responses = self.responses.read().await;
+
+        let mut approvals: Vec<PendingApproval> = pending
+            .values()
+            .flat_map(|session_requests| session_requests.values())
+            .filter(|a| !a.is_expired() && !responses.contains_key(&a.request_id))
+            .cloned()
+            .collect();
+        approvals.sort_by_key(|a| a.created_at);
+        approvals
+    }
+
+    /// Unanswered, unexpired requests of one session, oldest first
+    pub async fn list_session_pending(&self, session_id: &str) -> Vec<PendingApproval> {
+        let mut approvals = self.list_pending().await;
+        approvals.retain(|a| a.session_id == session_id);
+        approvals
+    }
+
+    /// Drop an answered or timed-out request from memory and the store
+    async fn complete_request(&self, request_id: &str) {
+        let mut pending = self.pending.write().await;
+        for session_requests in pending.values_mut() {
+            session_requests.remove(request_id);
+        }
+        pending.retain(|_, session_requests| !session_requests.is_empty());
+        drop(pending);
+
+        self.delete_stored(request_id).await;
+    }
+
+    async fn delete_stored(&self, request_id: &str) {
+        if let Some(store) = &self.store {
+            if let Err(e) = store.delete(request_id).await {
+                tracing::warn!("Failed to remove stored approval {}: {}", request_id, e);
+            }
+        }
+    }
+
     /// Clear all approvals for a session
     pub async fn clear_session_approvals(&self, session_id: &str) {
-        let mut pending = self.pending.write().await;
-        pending.remove(session_id);
+        let removed = self.pending.write().await.remove(session_id);
+        for request_id in removed
+            .into_iter()
+            .flat_map(|requests| requests.into_keys())
+        {
+            self.delete_stored(&request_id).await;
+        }
 
         tracing::debug!("Cleared all approvals for session: {}", session_id);
     }
@@ -222,6 +478,21 @@ mod tests {
         assert_eq!(request_id.len(), 36); // UUID format: 8-4-4-4-12 = 36 chars
     }
 
+    #[tokio::test]
+    async fn test_subscribers_see_new_requests() {
+        let manager = ApprovalManager::new();
+        let mut created = manager.subscribe();
+
+        let request_id = manager
+            .create_approval_request("session-1", "bash", r#"{"cmd":"ls"}"#, "elevated", false)
+            .await;
+
+        let approval = created.recv().await.unwrap();
+        assert_eq!(approval.request_id, request_id);
+        assert_eq!(approval.session_id, "session-1");
+        assert_eq!(approval.tool_name, "bash");
+    }
+
     #[tokio::test]
     async fn test_submit_and_wait_for_approval() {
         let manager = ApprovalManager::new();
@@ -359,4 +630,104 @@ mod tests {
         assert_eq!(stats.total_responses, 1);
         assert_eq!(stats.sessions_with_pending, 2);
     }
+
+    #[tokio::test]
+    async fn test_pending_approvals_survive_restart() {
+        let storage = crate::storage::sqlite::SqliteStorage::new(":memory:")
+            .await
+            .unwrap();
+        let store: Arc<dyn ApprovalStore> = Arc::new(storage.clone());
+
+        let before_restart = ApprovalManager::new().with_store(store.clone());
+        let request_id = before_restart
+            .create_approval_request("session-1", "bash", r#"{"cmd":"ls"}"#, "elevated", true)
+            .await;
+
+        // A request that expired while the server was down
+        storage
+            .save_pending_approval(PendingApprovalRecord {
+                request_id: "expired".to_string(),
+                session_id: "session-1".to_string(),
+                tool_name: "exec".to_string(),
+                arguments: "{}".to_string(),
+                policy: "elevated".to_string(),
+                sandbox_available: false,
+                created_at: Utc::now() - chrono::Duration::minutes(10),
+                expires_at: Utc::now() - chrono::Duration::minutes(9),
+            })
+            .await
+            .unwrap();
+
+        let after_restart = ApprovalManager::new().with_store(store);
+        assert_eq!(after_restart.restore_pending().await.unwrap(), 1);
+
+        let pending = after_restart.list_session_pending("session-1").await;
+        assert_eq!(pending.len(), 1);
+        assert_eq!(pending[0].request_id, request_id);
+        assert_eq!(pending[0].tool_name, "bash");
+        assert!(pending[0].sandbox_available);
+
+        // The expired request was auto-denied and dropped from storage
+        let stored = storage.list_pending_approvals().await.unwrap();
+        assert_eq!(stored.len(), 1);
+        assert_eq!(stored[0].request_id, request_id);
+
+        // Answering completes the request everywhere
+        after_restart
+            .submit_approval_response(&request_id, true, false, false)
+            .await;
+        assert!(after_restart.list_pending().await.is_empty());
+        assert!(storage.list_pending_approvals().await.unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_remembered_approvals_survive_restart() {
+        let storage = crate::storage::sqlite::SqliteStorage::new(":memory:")
+            .await
+            .unwrap();
+        let store: Arc<dyn ApprovalStore> = Arc::new(storage.clone());
+
+        let before_restart = ApprovalManager::new().with_store(store.clone());
+        before_restart
+            .remember_for_session("session-1", "bash")
+            .await;
+        before_restart
+            .remember_for_session("session-1", "bash")
+            .await;
+        before_restart
+            .remember_for_session("session-2", "exec")
+            .await;
+
+        let policy = ToolPolicyEngine::new();
+        let after_restart = ApprovalManager::new().with_store(store);
+        assert_eq!(after_restart.restore_remembered(&policy).await.unwrap(), 2);
+        assert!(policy.is_approval_remembered("session-1", "bash").await);
+        assert!(policy.is_approval_remembered("session-2", "exec").await);
+        assert!(!policy.is_approval_remembered("session-1", "exec").await);
+
+        // Resetting the session drops it from storage
+        after_restart.forget_session("session-1").await;
+        assert_eq!(
+            storage.list_session_tool_approvals().await.unwrap(),
+            vec![("session-2".to_string(), "exec".to_string())]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_completed_approval_is_removed() {
+        let manager = ApprovalManager::new();
+        let request_id = manager
+            .create_approval_request("session-1", "bash", "{}", "elevated", true)
+            .await;
+
+        manager
+            .submit_approval_response(&request_id, false, false, false)
+            .await;
+        manager.wait_for_approval(&request_id, 5).await;
+
+        assert!(manager
+            .get_pending_approval("session-1", &request_id)
+            .await
+            .is_none());
+    }
 }
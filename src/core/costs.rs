@@ -0,0 +1,238 @@
+//! Cost of conversations
+//!
+//! The tokens of every turn are priced with `costs.prices` and added up per
+//! session and per user. A session or user that has reached its limit gets
+//! no further replies until the session is cleared with `/reset` or an admin
+//! resets the user (`DELETE /api/admin/costs/:user_id`). Crossing
+//! `costs.warn_at` of a limit publishes a [`SystemEvent::CostWarning`].
+//! Plugins' own LLM calls are charged the same way, to the user and session
+//! `plugin:<id>`, so each plugin has the same budget as a user.
+//! Spending is kept in memory and starts over when the gateway restarts.
+
+use crate::config::CostsConfig;
+use crate::core::events::{publish_event, SystemEvent};
+use serde::Serialize;
+use std::collections::HashMap;
+use std::sync::Mutex;
+
+/// Reply to a message sent after a cost limit was reached
+pub const COST_LIMIT_REPLY: &str = "Cost limit reached, contact admin or /reset.";
+
+/// What a cost limit applies to
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum CostScope {
+    Session,
+    User,
+}
+
+/// User and session a plugin's LLM calls are charged to
+pub fn plugin_account(plugin_id: &str) -> String {
+    format!("plugin:{}", plugin_id)
+}
+
+/// Price of `tokens` tokens of `model`; models without a price cost nothing
+pub fn price(config: &CostsConfig, model: &str, tokens: usize) -> f64 {
+    config
+        .prices
+        .get(model)
+        .map_or(0.0, |per_thousand| per_thousand * tokens as f64 / 1000.0)
+}
+
+/// Spending by session and by user since it was last reset
+#[derive(Default)]
+pub struct CostLedger {
+    /// Spent by session, with the session's user
+    sessions: Mutex<HashMap<String, (String, f64)>>,
+    users: Mutex<HashMap<String, f64>>,
+}
+
+impl CostLedger {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Spent by a user since they were last reset
+    pub fn user_cost(&self, user_id: &str) -> f64 {
+        self.users
+            .lock()
+            .unwrap()
+            .get(user_id)
+            .copied()
+            .unwrap_or(0.0)
+    }
+
+    /// Spent in a session since it was last reset
+    pub fn session_cost(&self, session_id: &str) -> f64 {
+        self.sessions
+            .lock()
+            .unwrap()
+            .get(session_id)
+            .map_or(0.0, |(_, cost)| *cost)
+    }
+
+    /// The limit a user or their session has reached, the user's first
+    pub fn limit_reached(
+        &self,
+        config: &CostsConfig,
+        user_id: &str,
+        session_id: &str,
+    ) -> Option<CostScope> {
+        if config
+            .user_limit
+            .is_some_and(|limit| self.user_cost(user_id) >= limit)
+        {
+            return Some(CostScope::User);
+        }
+        if config
+            .session_limit
+            .is_some_and(|limit| self.session_cost(session_id) >= limit)
+        {
+            return Some(CostScope::Session);
+        }
+        None
+    }
+
+    /// Add the cost of a turn, warning about each limit whose `warn_at`
+    /// fraction it crosses
+    pub fn record(&self, config: &CostsConfig, user_id: &str, session_id: &str, cost: f64) {
+        if cost <= 0.0 {
+            return;
+        }
+        let session = {
+            let mut sessions = self.sessions.lock().unwrap();
+            let (_, spent) = sessions
+                .entry(session_id.to_string())
+                .or_insert_with(|| (user_id.to_string(), 0.0));
+            *spent += cost;
+            *spent
+        };
+        let user = {
+            let mut users = self.users.lock().unwrap();
+            let spent = users.entry(user_id.to_string()).or_insert(0.0);
+            *spent += cost;
+            *spent
+        };
+
+        for (scope, spent, limit) in [
+            (CostScope::Session, session, config.session_limit),
+            (CostScope::User, user, config.user_limit),
+        ] {
+            let Some(limit) = limit else { continue };
+            let threshold = limit * config.warn_at;
+            if spent - cost < threshold && spent >= threshold {
+                tracing::warn!(
+                    "Cost of {:?} {} reached {:.4} of its {:.4} limit",
+                    scope,
+                    match scope {
+                        CostScope::Session => session_id,
+                        CostScope::User => user_id,
+                    },
+                    spent,
+                    limit
+                );
+                publish_event(SystemEvent::CostWarning {
+                    scope,
+                    user_id: user_id.to_string(),
+                    session_id: session_id.to_string(),
+                    spent,
+                    limit,
+                });
+            }
+        }
+    }
+
+    /// Forget what a session has spent; its user's total is kept
+    pub fn reset_session(&self, session_id: &str) {
+        self.sessions.lock().unwrap().remove(session_id);
+    }
+
+    /// Forget what a user and each of their sessions have spent, returning
+    /// the user's total
+    pub fn reset_user(&self, user_id: &str) -> f64 {
+        self.sessions
+            .lock()
+            .unwrap()
+            .retain(|_, (owner, _)| owner != user_id);
+        self.users.lock().unwrap().remove(user_id).unwrap_or(0.0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn config() -> CostsConfig {
+        CostsConfig {
+            prices: HashMap::from([("big-model".to_string(), 2.0)]),
+            session_limit: Some(1.0),
+            user_limit: Some(3.0),
+            warn_at: 0.5,
+        }
+    }
+
+    #[test]
+    fn test_price_per_thousand_tokens() {
+        let config = config();
+        assert_eq!(price(&config, "big-model", 250), 0.5);
+        assert_eq!(price(&config, "free-model", 250), 0.0);
+    }
+
+    #[tokio::test]
+    async fn test_crossing_warn_at_publishes_one_warning() {
+        let config = config();
+        let ledger = CostLedger::new();
+        let mut events = crate::core::events::subscribe();
+
+        ledger.record(&config, "costs-warn", "s1", 0.4);
+        ledger.record(&config, "costs-warn", "s1", 0.2);
+        ledger.record(&config, "costs-warn", "s1", 0.2);
+
+        let mut warnings = Vec::new();
+        while let Ok(event) = events.try_recv() {
+            if let SystemEvent::CostWarning {
+                scope,
+                user_id,
+                spent,
+                ..
+            } = event
+            {
+                if user_id == "costs-warn" {
+                    warnings.push((scope, spent));
+                }
+            }
+        }
+        assert_eq!(warnings.len(), 1, "{:?}", warnings);
+        assert_eq!(warnings[0].0, CostScope::Session);
+        assert!((warnings[0].1 - 0.6).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_limits_and_resets() {
+        let config = config();
+        let ledger = CostLedger::new();
+
+        ledger.record(&config, "alice", "s1", 1.0);
+        assert_eq!(
+            ledger.limit_reached(&config, "alice", "s1"),
+            Some(CostScope::Session)
+        );
+        assert_eq!(ledger.limit_reached(&config, "alice", "s2"), None);
+
+        ledger.reset_session("s1");
+        assert_eq!(ledger.limit_reached(&config, "alice", "s1"), None);
+        assert_eq!(ledger.user_cost("alice"), 1.0);
+
+        ledger.record(&config, "alice", "s2", 0.9);
+        ledger.record(&config, "alice", "s3", 0.9);
+        ledger.record(&config, "alice", "s4", 0.9);
+        assert_eq!(
+            ledger.limit_reached(&config, "alice", "s5"),
+            Some(CostScope::User)
+        );
+
+        assert!((ledger.reset_user("alice") - 3.7).abs() < 1e-9);
+        assert_eq!(ledger.limit_reached(&config, "alice", "s2"), None);
+        assert_eq!(ledger.session_cost("s4"), 0.0);
+    }
+}
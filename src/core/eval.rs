@@ -0,0 +1,207 @@
+//! Prompt regression suites
+//!
+//! An eval suite is a list of inputs, each with text its reply must contain
+//! and/or a regular expression it must match. Every case is answered in a
+//! fresh ephemeral session with the same seed and model, so a suite can be
+//! re-run after changing prompts, skills or models and compared case by
+//! case. Cases run with bounded concurrency; one that hits the backend's
+//! rate limit waits and is retried.
+
+use crate::core::Router;
+use crate::llm::LlmError;
+use crate::storage::{EvalCase, Storage};
+use futures::StreamExt;
+use regex::RegexBuilder;
+use serde::Serialize;
+use std::time::{Duration, Instant};
+
+/// Seed used when a run does not ask for one
+pub const DEFAULT_EVAL_SEED: i64 = 42;
+/// Attempts of a case that keeps hitting the rate limit
+const RATE_LIMIT_ATTEMPTS: u32 = 3;
+/// Wait after a rate-limited attempt when the backend does not say how long
+const DEFAULT_RATE_LIMIT_WAIT: Duration = Duration::from_secs(2);
+/// Longest wait after a rate-limited attempt
+const MAX_RATE_LIMIT_WAIT: Duration = Duration::from_secs(30);
+
+/// Outcome of one case of a run
+#[derive(Debug, Clone, Serialize)]
+pub struct EvalCaseResult {
+    /// Position of the case in the suite
+    pub index: usize,
+    pub input: String,
+    pub passed: bool,
+    /// The reply, absent when the case failed with an error
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub output: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+    pub latency_ms: u64,
+}
+
+/// Check that every case has an input and a valid expectation; returns the
+/// problem with the first case that does not
+pub fn validate_cases(cases: &[EvalCase]) -> Result<(), String> {
+    for (index, case) in cases.iter().enumerate() {
+        if case.input.trim().is_empty() {
+            return Err(format!("case {} has an empty input", index));
+        }
+        if case.expected_contains.is_none() && case.expected_regex.is_none() {
+            return Err(format!(
+                "case {} needs expected_contains or expected_regex",
+                index
+            ));
+        }
+        if let Some(pattern) = &case.expected_regex {
+            regex::Regex::new(pattern)
+                .map_err(|e| format!("case {} has an invalid expected_regex: {}", index, e))?;
+        }
+    }
+    Ok(())
+}
+
+/// Whether a reply meets every expectation of its case. Both checks ignore
+/// case; an invalid regex never matches.
+pub fn check_output(case: &EvalCase, output: &str) -> bool {
+    let contains = case
+        .expected_contains
+        .as_ref()
+        .is_none_or(|expected| output.to_lowercase().contains(&expected.to_lowercase()));
+    let matches = case.expected_regex.as_ref().is_none_or(|pattern| {
+        RegexBuilder::new(pattern)
+            .case_insensitive(true)
+            .build()
+            .is_ok_and(|regex| regex.is_match(output))
+    });
+    contains && matches
+}
+
+/// Run every case with at most `concurrency` at a time, results in case order
+pub async fn run_cases<S: Storage + 'static>(
+    router: &Router<S>,
+    user_id: &str,
+    cases: Vec<EvalCase>,
+    seed: i64,
+    model: &str,
+    concurrency: usize,
+) -> Vec<EvalCaseResult> {
+    let mut results: Vec<EvalCaseResult> = futures::stream::iter(cases.into_iter().enumerate())
+        .map(|(index, case)| run_case(router, user_id, index, case, seed, model))
+        .buffer_unordered(concurrency.max(1))
+        .collect()
+        .await;
+    results.sort_by_key(|result| result.index);
+    results
+}
+
+async fn run_case<S: Storage + 'static>(
+    router: &Router<S>,
+    user_id: &str,
+    index: usize,
+    case: EvalCase,
+    seed: i64,
+    model: &str,
+) -> EvalCaseResult {
+    let start = Instant::now();
+    let mut attempt = 1;
+    let result = loop {
+        let result = router
+            .handle_eval_message(user_id, &case.input, seed, model)
+            .await;
+        match result.as_ref().err().and_then(rate_limit_wait) {
+            Some(wait) if attempt < RATE_LIMIT_ATTEMPTS => {
+                tracing::info!("Eval case {} rate limited, retrying in {:?}", index, wait);
+                tokio::time::sleep(wait).await;
+                attempt += 1;
+            }
+            _ => break result,
+        }
+    };
+    let latency_ms = start.elapsed().as_millis() as u64;
+
+    match result {
+        Ok(response) => EvalCaseResult {
+            index,
+            passed: check_output(&case, &response.content),
+            input: case.input,
+            output: Some(response.content),
+            error: None,
+            latency_ms,
+        },
+        Err(e) => {
+            tracing::warn!("Eval case {} failed: {:#}", index, e);
+            EvalCaseResult {
+                index,
+                input: case.input,
+                passed: false,
+                output: None,
+                error: Some(format!("{:#}", e)),
+                latency_ms,
+            }
+        }
+    }
+}
+
+/// How long to wait before retrying after a rate-limit error, `None` for
+/// any other error
+fn rate_limit_wait(err: &anyhow::Error) -> Option<Duration> {
+    err.chain()
+        .find_map(|cause| match cause.downcast_ref::<LlmError>() {
+            Some(LlmError::RateLimited { retry_after }) => Some(
+                retry_after
+                    .map(Duration::from_secs)
+                    .unwrap_or(DEFAULT_RATE_LIMIT_WAIT)
+                    .min(MAX_RATE_LIMIT_WAIT),
+            ),
+            _ => None,
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn case(contains: Option<&str>, regex: Option<&str>) -> EvalCase {
+        EvalCase {
+            input: "What is 2+2?".to_string(),
+            expected_contains: contains.map(String::from),
+            expected_regex: regex.map(String::from),
+        }
+    }
+
+    #[test]
+    fn test_check_output() {
+        assert!(check_output(&case(Some("four"), None), "It is Four."));
+        assert!(!check_output(&case(Some("four"), None), "It is 5."));
+        assert!(check_output(&case(None, Some(r"\b4\b")), "2+2 = 4"));
+        assert!(!check_output(&case(None, Some(r"\b4\b")), "2+2 = 42"));
+        // Both expectations must hold
+        assert!(!check_output(&case(Some("four"), Some(r"\d")), "four"));
+        assert!(check_output(&case(Some("four"), Some(r"\d")), "four (4)"));
+    }
+
+    #[test]
+    fn test_validate_cases() {
+        assert!(validate_cases(&[case(Some("4"), None)]).is_ok());
+        let error = validate_cases(&[case(Some("4"), None), case(None, None)]).unwrap_err();
+        assert!(error.starts_with("case 1"), "{}", error);
+        assert!(validate_cases(&[case(None, Some("(unclosed"))]).is_err());
+        let mut blank = case(Some("4"), None);
+        blank.input = "  ".to_string();
+        assert!(validate_cases(&[blank]).is_err());
+    }
+
+    #[test]
+    fn test_rate_limit_wait() {
+        let limited = anyhow::Error::new(LlmError::RateLimited {
+            retry_after: Some(5),
+        })
+        .context("Failed to answer");
+        assert_eq!(rate_limit_wait(&limited), Some(Duration::from_secs(5)));
+        let long = anyhow::Error::new(LlmError::RateLimited {
+            retry_after: Some(600),
+        });
+        assert_eq!(rate_limit_wait(&long), Some(MAX_RATE_LIMIT_WAIT));
+        assert_eq!(rate_limit_wait(&anyhow::anyhow!("no model")), None);
+    }
+}
@@ -9,8 +9,20 @@ pub enum SystemEvent {
     ToolUpdated(String),
     /// A tool/skill was removed
     ToolRemoved(String),
+    /// A skill file failed to load; the previous version stays active
+    ToolLoadFailed { path: String, error: String },
     /// A session was created
     SessionCreated(String),
+    /// An idle session was deleted by session expiry
+    SessionExpired(String),
+    /// A session or user has spent `costs.warn_at` of its cost limit
+    CostWarning {
+        scope: crate::core::costs::CostScope,
+        user_id: String,
+        session_id: String,
+        spent: f64,
+        limit: f64,
+    },
 }
 
 /// Global event bus
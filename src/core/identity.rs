@@ -0,0 +1,230 @@
+//! Mapping of channel identities to RustyClaw user IDs
+//!
+//! Every adapter derives the user ID of a sender here. Unmapped senders keep
+//! the channel's own format (the plain Telegram/Discord ID,
+//! `whatsapp:<account>:<phone>`); identities listed in
+//! `channels.identities` resolve to the shared user ID they are mapped to,
+//! so one person's sessions, memory and settings follow them across
+//! channels.
+
+use crate::config::ChannelsConfig;
+use once_cell::sync::OnceCell;
+use std::collections::HashMap;
+
+/// Global identity mapping for the channel adapters
+static IDENTITIES: OnceCell<IdentityResolver> = OnceCell::new();
+
+/// Initialize the global identity mapping, warning about identities mapped
+/// to more than one user
+pub fn init_identities(config: &ChannelsConfig) {
+    IDENTITIES.set(IdentityResolver::new(config)).ok();
+}
+
+/// User ID of a sender, using the global mapping
+pub fn resolve_user_id(identity: &ChannelIdentity) -> String {
+    match IDENTITIES.get() {
+        Some(resolver) => resolver.resolve(identity),
+        None => identity.default_user_id(),
+    }
+}
+
+/// Native ID of a user on a channel (where their own chat is), using the
+/// global mapping
+pub fn native_id(channel: &str, user_id: &str) -> String {
+    match IDENTITIES.get() {
+        Some(resolver) => resolver.native_id(channel, user_id),
+        None => default_native_id(channel, user_id),
+    }
+}
+
+/// A sender as a channel knows them
+#[derive(Debug, Clone, Copy)]
+pub struct ChannelIdentity<'a> {
+    pub channel: &'a str,
+    /// Gateway account that received the message, for channels that can
+    /// connect several (WhatsApp)
+    pub account: Option<&'a str>,
+    pub native_id: &'a str,
+}
+
+impl<'a> ChannelIdentity<'a> {
+    pub fn new(channel: &'a str, native_id: &'a str) -> Self {
+        Self {
+            channel,
+            account: None,
+            native_id,
+        }
+    }
+
+    pub fn with_account(mut self, account: &'a str) -> Self {
+        self.account = Some(account);
+        self
+    }
+
+    /// User ID of an unmapped sender
+    fn default_user_id(&self) -> String {
+        match self.account {
+            Some(account) => format!("{}:{}:{}", self.channel, account, self.native_id),
+            None => self.native_id.to_string(),
+        }
+    }
+}
+
+/// Configured identity mapping
+#[derive(Debug, Default)]
+pub struct IdentityResolver {
+    /// User ID by normalized `(channel, native id)`
+    users: HashMap<(String, String), String>,
+    /// Native IDs by user ID and channel
+    native_ids: HashMap<(String, String), String>,
+}
+
+impl IdentityResolver {
+    pub fn new(config: &ChannelsConfig) -> Self {
+        let mut resolver = Self::default();
+        for (user_id, identities) in &config.identities {
+            for identity in identities {
+                let Some((channel, native_id)) = identity.split_once(':') else {
+                    tracing::warn!(
+                        "Ignoring identity '{}' of user {}: expected <channel>:<id>",
+                        identity,
+                        user_id
+                    );
+                    continue;
+                };
+                let key = normalize(channel, native_id);
+                if let Some(other) = resolver.users.get(&key).filter(|u| *u != user_id) {
+                    tracing::warn!(
+                        "Identity '{}' is mapped to both {} and {}; keeping {}",
+                        identity,
+                        other,
+                        user_id,
+                        other
+                    );
+                    continue;
+                }
+                resolver
+                    .native_ids
+                    .entry((user_id.clone(), key.0.clone()))
+                    .or_insert_with(|| key.1.clone());
+                resolver.users.insert(key, user_id.clone());
+            }
+        }
+        resolver
+    }
+
+    /// User ID of a sender: the mapped user, or the channel's own format
+    pub fn resolve(&self, identity: &ChannelIdentity) -> String {
+        self.users
+            .get(&normalize(identity.channel, identity.native_id))
+            .cloned()
+            .unwrap_or_else(|| identity.default_user_id())
+    }
+
+    /// Native ID of a user on a channel: the mapped identity, or the one
+    /// contained in the channel's own user ID format
+    pub fn native_id(&self, channel: &str, user_id: &str) -> String {
+        self.native_ids
+            .get(&(user_id.to_string(), channel.trim().to_lowercase()))
+            .cloned()
+            .unwrap_or_else(|| default_native_id(channel, user_id))
+    }
+}
+
+/// Native ID inside an unmapped user ID
+fn default_native_id(channel: &str, user_id: &str) -> String {
+    match channel {
+        // WhatsApp users are `whatsapp:<account>:<phone>`
+        "whatsapp" => user_id.rsplit(':').next().unwrap_or(user_id).to_string(),
+        _ => user_id.to_string(),
+    }
+}
+
+/// Lowercase channel and native ID without formatting, so `+49 151 123`
+/// and `49151123` are the same WhatsApp number
+fn normalize(channel: &str, native_id: &str) -> (String, String) {
+    let channel = channel.trim().to_lowercase();
+    let native_id = match channel.as_str() {
+        "whatsapp" => native_id
+            .chars()
+            .filter(|c| !matches!(c, '+' | ' ' | '-' | '(' | ')'))
+            .collect(),
+        _ => native_id.trim().to_string(),
+    };
+    (channel, native_id)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn resolver(identities: &[(&str, &[&str])]) -> IdentityResolver {
+        let config = ChannelsConfig {
+            identities: identities
+                .iter()
+                .map(|(user, ids)| {
+                    (
+                        user.to_string(),
+                        ids.iter().map(|s| s.to_string()).collect(),
+                    )
+                })
+                .collect(),
+            ..Default::default()
+        };
+        IdentityResolver::new(&config)
+    }
+
+    #[test]
+    fn test_unmapped_identities_pass_through() {
+        let resolver = resolver(&[]);
+
+        assert_eq!(
+            resolver.resolve(&ChannelIdentity::new("telegram", "12345")),
+            "12345"
+        );
+        assert_eq!(
+            resolver.resolve(&ChannelIdentity::new("discord", "987654321")),
+            "987654321"
+        );
+        assert_eq!(
+            resolver
+                .resolve(&ChannelIdentity::new("whatsapp", "4915112345678").with_account("main")),
+            "whatsapp:main:4915112345678"
+        );
+        assert_eq!(
+            resolver.native_id("whatsapp", "whatsapp:main:4915112345678"),
+            "4915112345678"
+        );
+        assert_eq!(resolver.native_id("discord", "987654321"), "987654321");
+    }
+
+    #[test]
+    fn test_mapped_identities_share_a_user() {
+        let resolver = resolver(&[("alice", &["whatsapp:+49 151 12345678", "discord:987654321"])]);
+
+        let whatsapp = ChannelIdentity::new("whatsapp", "4915112345678").with_account("main");
+        assert_eq!(resolver.resolve(&whatsapp), "alice");
+        assert_eq!(
+            resolver.resolve(&ChannelIdentity::new("discord", "987654321")),
+            "alice"
+        );
+        assert_eq!(
+            resolver.resolve(&ChannelIdentity::new("telegram", "987654321")),
+            "987654321"
+        );
+
+        // Replies to alice go to their own chat on each channel
+        assert_eq!(resolver.native_id("whatsapp", "alice"), "4915112345678");
+        assert_eq!(resolver.native_id("discord", "alice"), "987654321");
+    }
+
+    #[test]
+    fn test_identity_mapped_twice_keeps_one_user() {
+        let resolver = resolver(&[("alice", &["discord:1"]), ("bob", &["discord:1"])]);
+
+        let user = resolver.resolve(&ChannelIdentity::new("discord", "1"));
+        assert!(user == "alice" || user == "bob");
+        assert_eq!(resolver.users.len(), 1);
+        assert_eq!(resolver.native_ids.len(), 1);
+    }
+}
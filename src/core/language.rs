@@ -0,0 +1,316 @@
+//! Language of incoming messages
+//!
+//! A small detector used to ask for replies in the user's language (see
+//! `prompt.detect_language` and `/lang`). The script decides for non-Latin
+//! text and common function words for Latin text. Code blocks, inline code,
+//! links and identifiers are ignored; short or mixed-language messages give
+//! no language rather than a guess.
+
+use once_cell::sync::Lazy;
+use regex::Regex;
+
+/// A language replies can be asked in
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Language {
+    /// ISO 639-1 code
+    pub code: &'static str,
+    /// English name, as written in the prompt
+    pub name: &'static str,
+}
+
+impl Language {
+    const fn new(code: &'static str, name: &'static str) -> Self {
+        Self { code, name }
+    }
+}
+
+/// Languages known to the detector and to `/lang`
+pub const LANGUAGES: &[Language] = &[
+    Language::new("ar", "Arabic"),
+    Language::new("de", "German"),
+    Language::new("el", "Greek"),
+    Language::new("en", "English"),
+    Language::new("es", "Spanish"),
+    Language::new("fr", "French"),
+    Language::new("he", "Hebrew"),
+    Language::new("hi", "Hindi"),
+    Language::new("it", "Italian"),
+    Language::new("ja", "Japanese"),
+    Language::new("ko", "Korean"),
+    Language::new("nl", "Dutch"),
+    Language::new("pt", "Portuguese"),
+    Language::new("ru", "Russian"),
+    Language::new("th", "Thai"),
+    Language::new("uk", "Ukrainian"),
+    Language::new("zh", "Chinese"),
+];
+
+/// Frequent words telling apart the languages written in Latin script
+const FUNCTION_WORDS: &[(&str, &[&str])] = &[
+    (
+        "en",
+        &[
+            "the", "and", "is", "are", "was", "you", "your", "what", "how", "why", "this", "that",
+            "with", "for", "can", "please", "have", "not", "my", "it", "of", "to", "do", "does",
+            "i", "me", "there",
+        ],
+    ),
+    (
+        "de",
+        &[
+            "der", "die", "das", "und", "ist", "nicht", "ich", "du", "sie", "mit", "wie", "was",
+            "ein", "eine", "auf", "für", "bitte", "kann", "zu", "es", "mir", "mich", "warum", "wo",
+            "sind", "auch", "noch",
+        ],
+    ),
+    (
+        "fr",
+        &[
+            "le", "la", "les", "et", "est", "je", "vous", "tu", "que", "qui", "une", "des", "pour",
+            "pas", "avec", "dans", "ce", "comment", "merci", "du", "mon", "ma", "mes", "bonjour",
+            "sont", "il", "elle", "pourquoi",
+        ],
+    ),
+    (
+        "es",
+        &[
+            "el", "la", "los", "las", "y", "es", "que", "en", "por", "para", "una", "con", "no",
+            "como", "qué", "cómo", "estoy", "estás", "gracias", "puedes", "hola", "está", "pero",
+            "mi", "porque", "del",
+        ],
+    ),
+    (
+        "it",
+        &[
+            "il", "lo", "gli", "e", "è", "che", "di", "per", "una", "con", "non", "come", "sono",
+            "grazie", "puoi", "questo", "mi", "del", "ciao", "perché", "della", "anche", "ho",
+        ],
+    ),
+    (
+        "pt",
+        &[
+            "o", "os", "as", "e", "é", "que", "em", "para", "uma", "com", "não", "como", "você",
+            "obrigado", "obrigada", "isso", "do", "da", "meu", "minha", "olá", "está", "por",
+            "mas",
+        ],
+    ),
+    (
+        "nl",
+        &[
+            "de", "het", "een", "en", "is", "niet", "ik", "je", "van", "dat", "met", "wat", "hoe",
+            "voor", "op", "zijn", "kun", "bedankt", "dit", "waarom", "mijn", "ook", "maar",
+        ],
+    ),
+];
+
+/// Fewest letters a message needs for a language to be detected; CJK
+/// characters count three times
+const MIN_LETTERS: usize = 12;
+/// Fewest words a Latin-script message needs
+const MIN_WORDS: usize = 3;
+/// Share of the letters the main script needs; below it a message counts
+/// as mixed-language
+const MIN_SCRIPT_SHARE: f64 = 0.75;
+
+/// Fenced code, inline code and URLs, which say nothing about the language
+static NOT_PROSE: Lazy<Regex> = Lazy::new(|| {
+    Regex::new(r"(?s)```.*?(?:```|\z)|`[^`\n]*`|\b[a-zA-Z][a-zA-Z0-9+.-]*://\S+").unwrap()
+});
+
+/// Characters of code that are rarely part of a word in prose
+const CODE_CHARS: &[char] = &[
+    '_', '(', ')', '{', '}', '[', ']', '<', '>', '=', ';', ':', '/', '\\', '*', '&', '|', '$', '#',
+    '@', '~', '^', '%', '+', '.',
+];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Script {
+    Latin,
+    Cyrillic,
+    Greek,
+    Arabic,
+    Hebrew,
+    Devanagari,
+    Thai,
+    Hangul,
+    /// Chinese characters and Japanese kana together
+    Cjk,
+}
+
+/// Script of a letter, and whether it is Japanese kana
+fn script_of(c: char) -> Option<(Script, bool)> {
+    if !c.is_alphabetic() {
+        return None;
+    }
+    let script = match c as u32 {
+        0x0041..=0x024F => Script::Latin,
+        0x0370..=0x03FF => Script::Greek,
+        0x0400..=0x04FF => Script::Cyrillic,
+        0x0590..=0x05FF => Script::Hebrew,
+        0x0600..=0x06FF => Script::Arabic,
+        0x0900..=0x097F => Script::Devanagari,
+        0x0E00..=0x0E7F => Script::Thai,
+        0x3040..=0x30FF => return Some((Script::Cjk, true)),
+        0x4E00..=0x9FFF => Script::Cjk,
+        0xAC00..=0xD7AF => Script::Hangul,
+        _ => return None,
+    };
+    Some((script, false))
+}
+
+/// Language with the given ISO 639-1 code or English name
+pub fn find(name: &str) -> Option<&'static Language> {
+    let name = name.trim();
+    LANGUAGES.iter().find(|language| {
+        language.code.eq_ignore_ascii_case(name) || language.name.eq_ignore_ascii_case(name)
+    })
+}
+
+/// Language a message is written in, if it can be told with confidence
+pub fn detect(text: &str) -> Option<&'static Language> {
+    let prose = NOT_PROSE.replace_all(text, " ");
+    let words: Vec<&str> = prose
+        .split_whitespace()
+        .map(|token| token.trim_matches(|c: char| !c.is_alphabetic()))
+        // Identifiers, paths and expressions outside code blocks
+        .filter(|token| !token.is_empty() && !token.contains(CODE_CHARS))
+        .collect();
+
+    let mut letters: Vec<(Script, usize)> = Vec::new();
+    let mut kana = 0;
+    let mut total = 0;
+    for (script, is_kana) in words
+        .iter()
+        .flat_map(|word| word.chars())
+        .filter_map(script_of)
+    {
+        let weight = match script {
+            Script::Cjk | Script::Hangul => 3,
+            _ => 1,
+        };
+        match letters.iter_mut().find(|(known, _)| *known == script) {
+            Some((_, count)) => *count += weight,
+            None => letters.push((script, weight)),
+        }
+        kana += usize::from(is_kana);
+        total += weight;
+    }
+
+    let &(script, count) = letters.iter().max_by_key(|(_, count)| *count)?;
+    if total < MIN_LETTERS || (count as f64) < total as f64 * MIN_SCRIPT_SHARE {
+        return None;
+    }
+
+    let code = match script {
+        Script::Latin => return detect_latin(&words),
+        Script::Cyrillic if words.iter().any(|word| word.contains(['і', 'ї', 'є', 'ґ'])) => {
+            "uk"
+        }
+        Script::Cyrillic => "ru",
+        Script::Greek => "el",
+        Script::Arabic => "ar",
+        Script::Hebrew => "he",
+        Script::Devanagari => "hi",
+        Script::Thai => "th",
+        Script::Hangul => "ko",
+        Script::Cjk if kana > 0 => "ja",
+        Script::Cjk => "zh",
+    };
+    find(code)
+}
+
+/// Latin-script language whose function words clearly outnumber those of
+/// any other
+fn detect_latin(words: &[&str]) -> Option<&'static Language> {
+    let words: Vec<String> = words
+        .iter()
+        .flat_map(|word| word.split(['-', '\'', '’']))
+        .filter(|word| !word.is_empty())
+        .map(str::to_lowercase)
+        .collect();
+    if words.len() < MIN_WORDS {
+        return None;
+    }
+
+    let mut scores: Vec<(&str, usize)> = FUNCTION_WORDS
+        .iter()
+        .map(|(code, function_words)| {
+            let hits = words
+                .iter()
+                .filter(|word| function_words.contains(&word.as_str()))
+                .count();
+            (*code, hits)
+        })
+        .collect();
+    scores.sort_by(|a, b| b.1.cmp(&a.1));
+
+    let (code, best) = scores[0];
+    let runner_up = scores[1].1;
+    if best < 2 || best < 2 * runner_up {
+        return None;
+    }
+    find(code)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn code(text: &str) -> Option<&'static str> {
+        detect(text).map(|language| language.code)
+    }
+
+    #[test]
+    fn test_detects_common_languages() {
+        assert_eq!(
+            code("Can you tell me what the weather is like today?"),
+            Some("en")
+        );
+        assert_eq!(
+            code("Hola, ¿cómo estás? Necesito ayuda con mi proyecto."),
+            Some("es")
+        );
+        assert_eq!(
+            code("Bonjour, pouvez-vous m'aider avec mon code ?"),
+            Some("fr")
+        );
+        assert_eq!(
+            code("Привет, как дела? Расскажи мне про погоду."),
+            Some("ru")
+        );
+        assert_eq!(
+            code("Привіт, як справи? Розкажи мені про погоду."),
+            Some("uk")
+        );
+        assert_eq!(code("今日の天気はどうですか"), Some("ja"));
+        assert_eq!(code("今天天气怎么样"), Some("zh"));
+    }
+
+    #[test]
+    fn test_code_is_ignored() {
+        let message = "Warum funktioniert das nicht?\n\
+                       ```rust\nfn main() { let the_value = is_ok(); println!(\"it is the end\"); }\n```\n\
+                       Ich bekomme den Fehler `the value is not defined` von https://example.com/docs.";
+        assert_eq!(code(message), Some("de"));
+    }
+
+    #[test]
+    fn test_short_and_mixed_messages_are_not_detected() {
+        assert_eq!(code("ok"), None);
+        assert_eq!(code("Hi there"), None);
+        assert_eq!(code("Как сказать good morning?"), None);
+        // As many German as English function words
+        assert_eq!(
+            code("Translate 'Guten Morgen, wie geht es dir' to English please"),
+            None
+        );
+        assert_eq!(code("```\nlet x = 1;\n```"), None);
+    }
+
+    #[test]
+    fn test_find_by_code_or_name() {
+        assert_eq!(find("DE").map(|language| language.name), Some("German"));
+        assert_eq!(find("spanish").map(|language| language.code), Some("es"));
+        assert_eq!(find("klingon"), None);
+    }
+}
@@ -0,0 +1,91 @@
+//! Timezone handling for user-facing dates
+//!
+//! Day boundaries for memory logs and the date/time shown to the model are
+//! computed in the configured IANA timezone instead of UTC.
+
+use crate::config::LocaleConfig;
+use anyhow::{anyhow, Result};
+use chrono::{DateTime, FixedOffset, Local, Utc};
+use once_cell::sync::OnceCell;
+
+/// Global locale settings for code paths without access to the config
+static LOCALE: OnceCell<LocaleConfig> = OnceCell::new();
+
+/// Initialize the global locale settings, warning about unknown timezones
+pub fn init_locale(config: &LocaleConfig) {
+    let names = config.timezone.iter().chain(config.user_timezones.values());
+    for name in names {
+        if let Err(e) = validate_timezone(name) {
+            tracing::warn!("{}", e);
+        }
+    }
+    LOCALE.set(config.clone()).ok();
+}
+
+/// Timezone for a user, falling back to the deployment default
+pub fn timezone_for(user_id: Option<&str>) -> Option<String> {
+    LOCALE.get().and_then(|locale| locale.timezone_for(user_id))
+}
+
+/// Check that a timezone name exists in the system tz database
+pub fn validate_timezone(name: &str) -> Result<()> {
+    jiff::tz::TimeZone::get(name)
+        .map(|_| ())
+        .map_err(|e| anyhow!("Unknown timezone '{}': {}", name, e))
+}
+
+/// Convert a UTC instant to local time in the given timezone.
+///
+/// With no timezone (or an unknown one) the host's local offset is used.
+pub fn local_time(utc: DateTime<Utc>, timezone: Option<&str>) -> DateTime<FixedOffset> {
+    let offset = timezone
+        .and_then(|name| jiff::tz::TimeZone::get(name).ok())
+        .and_then(|tz| {
+            let ts = jiff::Timestamp::from_second(utc.timestamp()).ok()?;
+            FixedOffset::east_opt(tz.to_offset(ts).seconds())
+        });
+
+    match offset {
+        Some(offset) => utc.with_timezone(&offset),
+        None => utc.with_timezone(&Local).fixed_offset(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    #[test]
+    fn test_day_boundary_differs_across_timezones() {
+        // 03:30 UTC on Jan 1st is still New Year's Eve in New York
+        let utc = Utc.with_ymd_and_hms(2025, 1, 1, 3, 30, 0).unwrap();
+
+        let ny = local_time(utc, Some("America/New_York"));
+        let tokyo = local_time(utc, Some("Asia/Tokyo"));
+        let london = local_time(utc, Some("Europe/London"));
+
+        assert_eq!(ny.format("%Y-%m-%d %H:%M").to_string(), "2024-12-31 22:30");
+        assert_eq!(
+            tokyo.format("%Y-%m-%d %H:%M").to_string(),
+            "2025-01-01 12:30"
+        );
+        assert_eq!(london.format("%Y-%m-%d").to_string(), "2025-01-01");
+    }
+
+    #[test]
+    fn test_daylight_saving_is_applied() {
+        let winter = Utc.with_ymd_and_hms(2025, 1, 15, 12, 0, 0).unwrap();
+        let summer = Utc.with_ymd_and_hms(2025, 7, 15, 12, 0, 0).unwrap();
+
+        let tz = Some("Europe/Berlin");
+        assert_eq!(local_time(winter, tz).offset().local_minus_utc(), 3600);
+        assert_eq!(local_time(summer, tz).offset().local_minus_utc(), 7200);
+    }
+
+    #[test]
+    fn test_validate_timezone() {
+        assert!(validate_timezone("Asia/Kolkata").is_ok());
+        assert!(validate_timezone("Mars/Olympus_Mons").is_err());
+    }
+}
@@ -0,0 +1,36 @@
+//! Maintenance (read-only) mode
+//!
+//! While an operator upgrades the gateway, reads keep working but new
+//! messages, tool calls and API writes are refused with a "maintenance"
+//! error. The mode is switched by an admin and persisted until cleared.
+
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+
+/// A write refused because the gateway is in maintenance mode
+#[derive(Debug, thiserror::Error)]
+#[error("RustyClaw is in maintenance mode and is not accepting new messages; try again later")]
+pub struct MaintenanceActive;
+
+/// Shared maintenance flag
+#[derive(Debug, Clone, Default)]
+pub struct Maintenance(Arc<AtomicBool>);
+
+impl Maintenance {
+    /// Whether writes are currently refused
+    pub fn is_active(&self) -> bool {
+        self.0.load(Ordering::SeqCst)
+    }
+
+    pub fn set_active(&self, active: bool) {
+        self.0.store(active, Ordering::SeqCst);
+    }
+
+    /// Refuse a write while maintenance mode is on
+    pub fn check(&self) -> Result<(), MaintenanceActive> {
+        match self.is_active() {
+            true => Err(MaintenanceActive),
+            false => Ok(()),
+        }
+    }
+}
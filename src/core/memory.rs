@@ -3,24 +3,33 @@
 //! Handles daily logs (short-term) and curated memory (long-term).
 //! Memories are stored in markdown files within the workspace.
 
+use crate::core::locale;
 use anyhow::{Context, Result};
-use chrono::Local;
+use chrono::{DateTime, Utc};
 use std::fs;
 use std::path::{Path, PathBuf};
 
 #[derive(Debug, Clone)]
 pub struct MemoryManager {
     workspace_path: PathBuf,
+    timezone: Option<String>,
 }
 
 impl MemoryManager {
-    /// Create a new memory manager
+    /// Create a new memory manager using the configured default timezone
     pub fn new<P: AsRef<Path>>(workspace_path: P) -> Self {
         Self {
             workspace_path: workspace_path.as_ref().to_path_buf(),
+            timezone: locale::timezone_for(None),
         }
     }
 
+    /// Use a specific timezone for day boundaries
+    pub fn with_timezone(mut self, timezone: Option<String>) -> Self {
+        self.timezone = timezone;
+        self
+    }
+
     /// Get the path to the memory directory
     fn memory_dir(&self) -> PathBuf {
         self.workspace_path.join("memory")
@@ -37,8 +46,13 @@ impl MemoryManager {
 
     /// Get the path for today's memory log
     fn today_log_path(&self) -> PathBuf {
-        let today = Local::now().format("%Y-%m-%d").to_string();
-        self.memory_dir().join(format!("{}.md", today))
+        self.log_path_at(Utc::now())
+    }
+
+    /// Get the memory log path for the local day containing `now`
+    fn log_path_at(&self, now: DateTime<Utc>) -> PathBuf {
+        let day = locale::local_time(now, self.timezone.as_deref()).format("%Y-%m-%d");
+        self.memory_dir().join(format!("{}.md", day))
     }
 
     /// Get path for curated memory (MEMORY.md)
@@ -61,7 +75,7 @@ impl MemoryManager {
         self.ensure_memory_dir()?;
 
         let path = self.today_log_path();
-        let timestamp = Local::now().format("%H:%M:%S");
+        let timestamp = locale::local_time(Utc::now(), self.timezone.as_deref()).format("%H:%M:%S");
 
         // Append or create
         let entry = format!("\n[{}] {}\n", timestamp, content.trim());
@@ -92,3 +106,21 @@ impl MemoryManager {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    #[test]
+    fn test_log_path_uses_local_day() {
+        // 23:30 UTC on Mar 9th is already Mar 10th in Tokyo
+        let now = Utc.with_ymd_and_hms(2025, 3, 9, 23, 30, 0).unwrap();
+
+        let utc = MemoryManager::new("/ws").with_timezone(Some("UTC".to_string()));
+        let tokyo = MemoryManager::new("/ws").with_timezone(Some("Asia/Tokyo".to_string()));
+
+        assert!(utc.log_path_at(now).ends_with("memory/2025-03-09.md"));
+        assert!(tokyo.log_path_at(now).ends_with("memory/2025-03-10.md"));
+    }
+}
@@ -1,13 +1,32 @@
 pub mod approval;
 pub mod bootstrap;
+pub mod costs;
+pub mod eval;
 pub mod events;
+pub mod identity;
+pub mod language;
+pub mod locale;
+pub mod maintenance;
 pub mod memory;
+pub mod moderation;
+pub mod oidc;
+pub mod overrides;
 pub mod password;
 pub mod prompt;
+pub mod redaction;
+pub mod reply_filters;
 mod router;
+pub mod scheduler;
 mod session;
+pub mod tool_selector;
+pub mod user_input;
 pub mod utils;
 
 pub use approval::{ApprovalManager, ApprovalResponse, PendingApproval};
 pub use router::Router;
-pub use session::{MessageResponse, Session, SessionManager, SessionStats, StreamEvent};
+pub use scheduler::Scheduler;
+pub use session::{
+    available_tools, tool_tags, ContextBudget, ContextMessage, ContextPreview, ContextTool,
+    ContextWindowExceeded, MessageResponse, Session, SessionManager, SessionStats, StreamEvent,
+    ToolNotAvailable, TurnOptions,
+};
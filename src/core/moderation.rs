@@ -0,0 +1,255 @@
+//! Optional content moderation before generation
+//!
+//! Incoming user text is scored by a moderation backend; messages with a
+//! category at or above the threshold are refused without calling the LLM.
+
+use crate::config::{ModerationBackend, ModerationConfig};
+use anyhow::{anyhow, bail, Context, Result};
+use async_trait::async_trait;
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::process::Stdio;
+use std::sync::Arc;
+use tokio::io::AsyncWriteExt;
+
+/// Scores text per moderation category (0.0 - 1.0)
+#[async_trait]
+pub trait Moderator: Send + Sync {
+    async fn category_scores(&self, text: &str) -> Result<HashMap<String, f32>>;
+}
+
+/// One result of an OpenAI-compatible `/moderations` response, also the
+/// output expected from a local classifier command
+#[derive(Debug, Deserialize)]
+struct ModerationResult {
+    #[serde(default)]
+    category_scores: HashMap<String, f32>,
+}
+
+#[derive(Debug, Deserialize)]
+struct ModerationResponse {
+    results: Vec<ModerationResult>,
+}
+
+/// OpenAI-compatible `POST {base_url}/moderations` backend
+pub struct HttpModerator {
+    client: reqwest::Client,
+    url: String,
+    model: Option<String>,
+    api_key: Option<String>,
+}
+
+impl HttpModerator {
+    pub fn new(base_url: &str, model: Option<String>, api_key: Option<String>) -> Result<Self> {
+        Ok(Self {
+            client: crate::network::client_builder()?
+                .timeout(std::time::Duration::from_secs(10))
+                .build()?,
+            url: format!("{}/moderations", base_url.trim_end_matches('/')),
+            model,
+            api_key,
+        })
+    }
+}
+
+#[async_trait]
+impl Moderator for HttpModerator {
+    async fn category_scores(&self, text: &str) -> Result<HashMap<String, f32>> {
+        let mut body = serde_json::json!({ "input": text });
+        if let Some(model) = &self.model {
+            body["model"] = serde_json::json!(model);
+        }
+
+        let mut request = self.client.post(&self.url).json(&body);
+        if let Some(key) = &self.api_key {
+            request = request.bearer_auth(key);
+        }
+        let response = request.send().await?.error_for_status()?;
+        let parsed: ModerationResponse = response
+            .json()
+            .await
+            .context("Invalid moderation response")?;
+
+        parsed
+            .results
+            .into_iter()
+            .next()
+            .map(|result| result.category_scores)
+            .ok_or_else(|| anyhow!("Moderation response has no results"))
+    }
+}
+
+/// Local classifier: the text is written to the command's stdin and a JSON
+/// object with `category_scores` is read from its stdout
+pub struct CommandModerator {
+    command: Vec<String>,
+}
+
+impl CommandModerator {
+    pub fn new(command: Vec<String>) -> Result<Self> {
+        if command.is_empty() {
+            bail!("moderation.command is required for the command backend");
+        }
+        Ok(Self { command })
+    }
+}
+
+#[async_trait]
+impl Moderator for CommandModerator {
+    async fn category_scores(&self, text: &str) -> Result<HashMap<String, f32>> {
+        let mut child = tokio::process::Command::new(&self.command[0])
+            .args(&self.command[1..])
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .stderr(Stdio::null())
+            .kill_on_drop(true)
+            .spawn()
+            .with_context(|| format!("Failed to start moderation command {}", self.command[0]))?;
+
+        if let Some(mut stdin) = child.stdin.take() {
+            stdin.write_all(text.as_bytes()).await?;
+        }
+        let output =
+            tokio::time::timeout(std::time::Duration::from_secs(10), child.wait_with_output())
+                .await
+                .context("Moderation command timed out")??;
+        if !output.status.success() {
+            bail!("Moderation command exited with {}", output.status);
+        }
+
+        let parsed: ModerationResult = serde_json::from_slice(&output.stdout)
+            .context("Moderation command printed invalid JSON")?;
+        Ok(parsed.category_scores)
+    }
+}
+
+/// Moderation gate applied to incoming messages
+pub struct Moderation {
+    moderator: Arc<dyn Moderator>,
+    threshold: f32,
+    refusal_message: String,
+    fail_closed: bool,
+}
+
+impl Moderation {
+    pub fn new(moderator: Arc<dyn Moderator>, config: &ModerationConfig) -> Self {
+        Self {
+            moderator,
+            threshold: config.threshold,
+            refusal_message: config.refusal_message.clone(),
+            fail_closed: config.fail_closed,
+        }
+    }
+
+    /// Build the configured gate, or `None` when moderation is disabled
+    pub fn from_config(config: &ModerationConfig, llm_base_url: &str) -> Result<Option<Self>> {
+        if !config.enabled {
+            return Ok(None);
+        }
+
+        let moderator: Arc<dyn Moderator> = match config.backend {
+            ModerationBackend::Openai => {
+                let api_key = config
+                    .api_key_env
+                    .as_deref()
+                    .and_then(|name| std::env::var(name).ok());
+                Arc::new(HttpModerator::new(
+                    config.base_url.as_deref().unwrap_or(llm_base_url),
+                    config.model.clone(),
+                    api_key,
+                )?)
+            }
+            ModerationBackend::Command => Arc::new(CommandModerator::new(config.command.clone())?),
+        };
+
+        Ok(Some(Self::new(moderator, config)))
+    }
+
+    /// Refusal to send instead of generating, if the text is flagged.
+    ///
+    /// Only the flagged categories are logged, never the text itself.
+    pub async fn refusal_for(&self, user_id: &str, channel: &str, text: &str) -> Option<String> {
+        let scores = match self.moderator.category_scores(text).await {
+            Ok(scores) => scores,
+            Err(e) if self.fail_closed => {
+                tracing::warn!("Moderation failed, refusing message: {}", e);
+                return Some(self.refusal_message.clone());
+            }
+            Err(e) => {
+                tracing::warn!("Moderation failed, allowing message: {}", e);
+                return None;
+            }
+        };
+
+        let flagged = flagged_categories(&scores, self.threshold);
+        if flagged.is_empty() {
+            return None;
+        }
+
+        tracing::warn!(
+            user_id = %user_id,
+            channel = %channel,
+            categories = ?flagged,
+            "Message refused by moderation"
+        );
+        Some(self.refusal_message.clone())
+    }
+}
+
+/// Categories scoring at or above `threshold`, highest first
+fn flagged_categories(scores: &HashMap<String, f32>, threshold: f32) -> Vec<String> {
+    let mut flagged: Vec<(&String, f32)> = scores
+        .iter()
+        .filter(|(_, score)| **score >= threshold)
+        .map(|(category, score)| (category, *score))
+        .collect();
+    flagged.sort_by(|a, b| b.1.total_cmp(&a.1));
+    flagged
+        .into_iter()
+        .map(|(category, _)| category.clone())
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_flagged_categories_above_threshold() {
+        let scores = HashMap::from([
+            ("violence".to_string(), 0.91),
+            ("harassment".to_string(), 0.62),
+            ("self-harm".to_string(), 0.1),
+        ]);
+
+        assert_eq!(
+            flagged_categories(&scores, 0.6),
+            vec!["violence", "harassment"]
+        );
+        assert!(flagged_categories(&scores, 0.95).is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_http_moderator_reads_category_scores() {
+        let mut server = mockito::Server::new_async().await;
+        let mock = server
+            .mock("POST", "/v1/moderations")
+            .match_header("authorization", "Bearer secret")
+            .with_body(
+                r#"{"id":"modr-1","model":"omni","results":[{"flagged":true,"category_scores":{"violence":0.97,"hate":0.01}}]}"#,
+            )
+            .create_async()
+            .await;
+
+        let moderator = HttpModerator::new(
+            &format!("{}/v1/", server.url()),
+            None,
+            Some("secret".to_string()),
+        )
+        .unwrap();
+        let scores = moderator.category_scores("text").await.unwrap();
+
+        assert_eq!(scores["violence"], 0.97);
+        mock.assert_async().await;
+    }
+}
@@ -0,0 +1,290 @@
+//! Single sign-on through an OpenID Connect provider
+//!
+//! `GET /api/auth/oidc/login` sends the browser to the provider's
+//! authorization endpoint with a fresh `state` and `nonce`; the provider
+//! redirects back to `/api/auth/oidc/callback` with a code, which is
+//! exchanged for an id token. The token's signature is checked against the
+//! issuer's JWKS, along with its issuer, audience, expiry and nonce. Logins
+//! in progress are kept in memory for [`LOGIN_TTL_SECS`].
+
+use crate::config::OidcConfig;
+use anyhow::Context;
+use jsonwebtoken::jwk::JwkSet;
+use jsonwebtoken::{Algorithm, DecodingKey, Validation};
+use once_cell::sync::Lazy;
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+/// How long a login may take between redirect and callback
+pub const LOGIN_TTL_SECS: u64 = 600;
+
+/// Nonce and start of each login in progress, by state
+static LOGINS: Lazy<Mutex<HashMap<String, (String, Instant)>>> =
+    Lazy::new(|| Mutex::new(HashMap::new()));
+
+/// Why a login could not be completed
+#[derive(Debug, thiserror::Error)]
+pub enum OidcError {
+    #[error("Login expired or was not started here")]
+    UnknownState,
+    #[error("Invalid id token: {0}")]
+    InvalidToken(String),
+    #[error("OIDC provider request failed: {0:#}")]
+    Provider(#[from] anyhow::Error),
+}
+
+/// Endpoints from the issuer's discovery document
+#[derive(Debug, Deserialize)]
+struct Discovery {
+    authorization_endpoint: String,
+    token_endpoint: String,
+    jwks_uri: String,
+}
+
+#[derive(Deserialize)]
+struct TokenResponse {
+    id_token: String,
+}
+
+/// Claims of a verified id token used to find the account
+#[derive(Debug, Clone, Deserialize)]
+pub struct IdClaims {
+    pub sub: String,
+    #[serde(default)]
+    pub email: Option<String>,
+    #[serde(default)]
+    pub email_verified: Option<bool>,
+    #[serde(default)]
+    pub preferred_username: Option<String>,
+    #[serde(default)]
+    nonce: Option<String>,
+}
+
+impl IdClaims {
+    /// The email, if the provider vouches for it
+    pub fn verified_email(&self) -> Option<&str> {
+        self.email
+            .as_deref()
+            .filter(|_| self.email_verified == Some(true))
+    }
+}
+
+async fn discover(client: &reqwest::Client, issuer: &str) -> anyhow::Result<Discovery> {
+    let url = format!(
+        "{}/.well-known/openid-configuration",
+        issuer.trim_end_matches('/')
+    );
+    client
+        .get(&url)
+        .send()
+        .await?
+        .error_for_status()?
+        .json()
+        .await
+        .with_context(|| format!("Invalid discovery document at {}", url))
+}
+
+fn http_client() -> anyhow::Result<reqwest::Client> {
+    Ok(crate::network::client_builder()?
+        .timeout(Duration::from_secs(10))
+        .build()?)
+}
+
+/// Start a login: the provider URL to send the browser to
+pub async fn login_url(config: &OidcConfig) -> Result<String, OidcError> {
+    let discovery = discover(&http_client()?, &config.issuer).await?;
+    let state = uuid::Uuid::new_v4().simple().to_string();
+    let nonce = uuid::Uuid::new_v4().simple().to_string();
+
+    let url = reqwest::Url::parse_with_params(
+        &discovery.authorization_endpoint,
+        &[
+            ("response_type", "code"),
+            ("client_id", config.client_id.as_str()),
+            ("redirect_uri", config.redirect_url.as_str()),
+            ("scope", config.scopes.join(" ").as_str()),
+            ("state", state.as_str()),
+            ("nonce", nonce.as_str()),
+        ],
+    )
+    .context("Invalid authorization endpoint")?;
+
+    let mut logins = LOGINS.lock().unwrap();
+    let ttl = Duration::from_secs(LOGIN_TTL_SECS);
+    logins.retain(|_, (_, started)| started.elapsed() < ttl);
+    logins.insert(state, (nonce, Instant::now()));
+    Ok(url.to_string())
+}
+
+/// Finish a login: exchange the code and verify the id token it yields
+pub async fn complete_login(
+    config: &OidcConfig,
+    code: &str,
+    state: &str,
+) -> Result<IdClaims, OidcError> {
+    let nonce = match LOGINS.lock().unwrap().remove(state) {
+        Some((nonce, started)) if started.elapsed() < Duration::from_secs(LOGIN_TTL_SECS) => nonce,
+        _ => return Err(OidcError::UnknownState),
+    };
+
+    let client = http_client()?;
+    let discovery = discover(&client, &config.issuer).await?;
+    let tokens: TokenResponse = client
+        .post(&discovery.token_endpoint)
+        .form(&[
+            ("grant_type", "authorization_code"),
+            ("code", code),
+            ("redirect_uri", config.redirect_url.as_str()),
+            ("client_id", config.client_id.as_str()),
+            ("client_secret", config.client_secret.as_str()),
+        ])
+        .send()
+        .await
+        .context("Token request failed")?
+        .error_for_status()
+        .context("Token request failed")?
+        .json()
+        .await
+        .context("Invalid token response")?;
+    let jwks: JwkSet = client
+        .get(&discovery.jwks_uri)
+        .send()
+        .await?
+        .error_for_status()?
+        .json()
+        .await
+        .context("Invalid JWKS")?;
+
+    verify_id_token(&tokens.id_token, &jwks, config, &nonce)
+}
+
+/// Check an id token's signature against the issuer's keys and its claims
+/// against the login
+pub fn verify_id_token(
+    token: &str,
+    jwks: &JwkSet,
+    config: &OidcConfig,
+    nonce: &str,
+) -> Result<IdClaims, OidcError> {
+    let invalid = |e: jsonwebtoken::errors::Error| OidcError::InvalidToken(e.to_string());
+    let header = jsonwebtoken::decode_header(token).map_err(invalid)?;
+    // Only keys the issuer publishes may sign, never the client secret
+    if matches!(
+        header.alg,
+        Algorithm::HS256 | Algorithm::HS384 | Algorithm::HS512
+    ) {
+        return Err(OidcError::InvalidToken(format!(
+            "{:?} signatures are not accepted",
+            header.alg
+        )));
+    }
+    let jwk = match &header.kid {
+        Some(kid) => jwks.find(kid),
+        None if jwks.keys.len() == 1 => jwks.keys.first(),
+        None => None,
+    }
+    .ok_or_else(|| OidcError::InvalidToken("signed with an unknown key".to_string()))?;
+    let key = DecodingKey::from_jwk(jwk).map_err(invalid)?;
+
+    let mut validation = Validation::new(header.alg);
+    validation.set_issuer(&[&config.issuer]);
+    validation.set_audience(&[&config.client_id]);
+    let claims = jsonwebtoken::decode::<IdClaims>(token, &key, &validation)
+        .map_err(invalid)?
+        .claims;
+    if claims.nonce.as_deref() != Some(nonce) {
+        return Err(OidcError::InvalidToken("nonce does not match".to_string()));
+    }
+    Ok(claims)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+    use base64::Engine;
+    use jsonwebtoken::{EncodingKey, Header};
+    use serde_json::json;
+
+    fn config() -> OidcConfig {
+        OidcConfig {
+            issuer: "https://login.example.com".to_string(),
+            client_id: "rustyclaw".to_string(),
+            client_secret: "secret".to_string(),
+            redirect_url: "https://claw.example.com/api/auth/oidc/callback".to_string(),
+            scopes: vec!["openid".to_string()],
+            auto_provision: false,
+            link_by_email: false,
+            default_role: "user".to_string(),
+        }
+    }
+
+    /// A P-256 signing key and the JWKS publishing it
+    fn signing_key() -> (EncodingKey, JwkSet) {
+        let key_pair = rcgen::KeyPair::generate().unwrap();
+        let point = key_pair.public_key_raw();
+        let jwks = json!({"keys": [{
+            "kty": "EC", "crv": "P-256", "kid": "key-1", "alg": "ES256", "use": "sig",
+            "x": URL_SAFE_NO_PAD.encode(&point[1..33]),
+            "y": URL_SAFE_NO_PAD.encode(&point[33..65]),
+        }]});
+        let key = EncodingKey::from_ec_pem(key_pair.serialize_pem().as_bytes()).unwrap();
+        (key, serde_json::from_value(jwks).unwrap())
+    }
+
+    fn sign(key: &EncodingKey, claims: serde_json::Value) -> String {
+        let mut header = Header::new(Algorithm::ES256);
+        header.kid = Some("key-1".to_string());
+        jsonwebtoken::encode(&header, &claims, key).unwrap()
+    }
+
+    fn claims(audience: &str) -> serde_json::Value {
+        json!({
+            "iss": "https://login.example.com",
+            "aud": audience,
+            "sub": "user-123",
+            "email": "alice@example.com",
+            "email_verified": true,
+            "nonce": "nonce-1",
+            "exp": chrono::Utc::now().timestamp() + 300,
+        })
+    }
+
+    #[test]
+    fn test_valid_id_token_is_accepted() {
+        let (key, jwks) = signing_key();
+        let token = sign(&key, claims("rustyclaw"));
+
+        let claims = verify_id_token(&token, &jwks, &config(), "nonce-1").unwrap();
+        assert_eq!(claims.sub, "user-123");
+        assert_eq!(claims.verified_email(), Some("alice@example.com"));
+    }
+
+    #[test]
+    fn test_forged_or_misdirected_tokens_are_rejected() {
+        let (key, jwks) = signing_key();
+        let config = config();
+
+        let other_client = sign(&key, claims("other-client"));
+        assert!(verify_id_token(&other_client, &jwks, &config, "nonce-1").is_err());
+
+        let token = sign(&key, claims("rustyclaw"));
+        assert!(verify_id_token(&token, &jwks, &config, "nonce-2").is_err());
+
+        // Signed by a key the issuer does not publish
+        let (other_key, _) = signing_key();
+        let forged = sign(&other_key, claims("rustyclaw"));
+        assert!(verify_id_token(&forged, &jwks, &config, "nonce-1").is_err());
+
+        // Signed with the client secret
+        let hmac = jsonwebtoken::encode(
+            &Header::new(Algorithm::HS256),
+            &claims("rustyclaw"),
+            &EncodingKey::from_secret(config.client_secret.as_bytes()),
+        )
+        .unwrap();
+        assert!(verify_id_token(&hmac, &jwks, &config, "nonce-1").is_err());
+    }
+}
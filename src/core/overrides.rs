@@ -0,0 +1,348 @@
+//! Per-session reply settings changed from the chat
+//!
+//! `/temp 0.2` sets the sampling temperature, `/persona concise` a reply
+//! style and `/lang de` the reply language for the rest of a session;
+//! `/reset` clears the conversation along with them, and `/stats` shows
+//! them. The router answers these commands
+//! itself, without calling the model.
+//!
+//! Users can keep defaults for the same settings (`GET/PUT /api/settings`);
+//! what a session chose wins over them, and they win over the
+//! configuration.
+
+use crate::core::language;
+use crate::storage::UserSettings;
+
+/// Lowest temperature accepted by `/temp`
+pub const MIN_TEMPERATURE: f32 = 0.0;
+/// Highest temperature accepted by `/temp`
+pub const MAX_TEMPERATURE: f32 = 2.0;
+
+/// Personas known to `/persona`, with the style instructions added to the
+/// system prompt
+pub const PERSONAS: &[(&str, &str)] = &[
+    (
+        "concise",
+        "Answer as briefly as possible: short sentences, no preamble, no repetition.",
+    ),
+    (
+        "detailed",
+        "Answer thoroughly: explain your reasoning, cover edge cases and give examples.",
+    ),
+    ("friendly", "Answer in a warm, casual and encouraging tone."),
+    (
+        "formal",
+        "Answer in a formal, professional tone, without slang or emoji.",
+    ),
+];
+
+/// Reply settings chosen for one session
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct SessionOverrides {
+    pub temperature: Option<f32>,
+    /// Name of one of the `PERSONAS`
+    pub persona: Option<String>,
+    /// Code of the language replies are asked in, instead of the detected one
+    pub language: Option<String>,
+    /// Model answering instead of the routed one (a model name or
+    /// `role:<name>`); set for eval runs and by user settings, not by chat
+    /// commands
+    pub model: Option<String>,
+}
+
+impl SessionOverrides {
+    /// Style instructions of the chosen persona
+    pub fn persona_instructions(&self) -> Option<&'static str> {
+        let persona = self.persona.as_deref()?;
+        PERSONAS
+            .iter()
+            .find(|(name, _)| *name == persona)
+            .map(|(_, instructions)| *instructions)
+    }
+
+    /// These settings with the user's defaults for those the session has
+    /// not chosen
+    pub fn or_user_settings(self, settings: UserSettings) -> Self {
+        Self {
+            temperature: self.temperature.or(settings.temperature),
+            persona: self.persona.or(settings.persona),
+            language: self.language.or(settings.language),
+            model: self.model.or(settings.model),
+        }
+    }
+}
+
+/// A user's settings checked like the chat commands check them, with the
+/// persona and language normalized. Returns the problem with the first
+/// invalid one.
+pub fn normalize_user_settings(settings: UserSettings) -> Result<UserSettings, String> {
+    if let Some(temperature) = settings.temperature {
+        if !(MIN_TEMPERATURE..=MAX_TEMPERATURE).contains(&temperature) {
+            return Err(format!(
+                "temperature must be between {:.1} and {:.1}",
+                MIN_TEMPERATURE, MAX_TEMPERATURE
+            ));
+        }
+    }
+    let persona = match settings.persona {
+        Some(persona) => {
+            let persona = persona.trim().to_lowercase();
+            if !PERSONAS.iter().any(|(name, _)| *name == persona) {
+                let names: Vec<&str> = PERSONAS.iter().map(|(name, _)| *name).collect();
+                return Err(format!("persona must be one of {}", names.join(", ")));
+            }
+            Some(persona)
+        }
+        None => None,
+    };
+    let language = match settings.language {
+        Some(name) => match language::find(&name) {
+            Some(language) => Some(language.code.to_string()),
+            None => return Err(format!("unknown language '{}'", name)),
+        },
+        None => None,
+    };
+    let model = settings
+        .model
+        .map(|model| model.trim().to_string())
+        .filter(|model| !model.is_empty());
+    Ok(UserSettings {
+        model,
+        temperature: settings.temperature,
+        persona,
+        language,
+    })
+}
+
+/// A command changing or showing a session's settings
+#[derive(Debug, Clone, PartialEq)]
+pub enum SessionCommand {
+    /// `/temp <value>`, or `/temp default` (`None`)
+    Temperature(Option<f32>),
+    /// `/persona <name>`, or `/persona default` (`None`)
+    Persona(Option<String>),
+    /// `/lang <code or name>`, or `/lang auto` (`None`)
+    Language(Option<String>),
+    /// `/reset`: clear the conversation and the settings
+    Reset,
+    /// `/stats`: show the session's statistics and settings
+    Stats,
+}
+
+/// Parse a session command. Returns `None` for any other message, and the
+/// usage to reply with when a command's argument is invalid.
+pub fn parse_command(content: &str) -> Option<Result<SessionCommand, String>> {
+    let content = content.trim();
+    let (command, argument) = match content.split_once(char::is_whitespace) {
+        Some((command, argument)) => (command, argument.trim()),
+        None => (content, ""),
+    };
+    // Telegram appends the bot's name in groups: `/temp@my_bot 0.2`
+    let command = command.split('@').next().unwrap_or(command).to_lowercase();
+
+    match (command.as_str(), argument) {
+        ("/temp" | "/temperature", argument) => Some(parse_temperature(argument)),
+        ("/persona", argument) => Some(parse_persona(argument)),
+        ("/lang" | "/language", argument) => Some(parse_language(argument)),
+        ("/reset", "") => Some(Ok(SessionCommand::Reset)),
+        ("/stats", "") => Some(Ok(SessionCommand::Stats)),
+        _ => None,
+    }
+}
+
+fn parse_temperature(argument: &str) -> Result<SessionCommand, String> {
+    let usage = || {
+        format!(
+            "Usage: /temp <{:.1}-{:.1}>, or /temp default",
+            MIN_TEMPERATURE, MAX_TEMPERATURE
+        )
+    };
+    if is_default(argument) {
+        return Ok(SessionCommand::Temperature(None));
+    }
+    match argument.parse::<f32>() {
+        Ok(value) if (MIN_TEMPERATURE..=MAX_TEMPERATURE).contains(&value) => {
+            Ok(SessionCommand::Temperature(Some(value)))
+        }
+        _ => Err(usage()),
+    }
+}
+
+fn parse_persona(argument: &str) -> Result<SessionCommand, String> {
+    let names: Vec<&str> = PERSONAS.iter().map(|(name, _)| *name).collect();
+    if is_default(argument) {
+        return Ok(SessionCommand::Persona(None));
+    }
+    let persona = argument.to_lowercase();
+    if argument.is_empty() || !names.contains(&persona.as_str()) {
+        return Err(format!(
+            "Usage: /persona <{}>, or /persona default",
+            names.join("|")
+        ));
+    }
+    Ok(SessionCommand::Persona(Some(persona)))
+}
+
+fn parse_language(argument: &str) -> Result<SessionCommand, String> {
+    if is_default(argument) || argument.eq_ignore_ascii_case("auto") {
+        return Ok(SessionCommand::Language(None));
+    }
+    match language::find(argument) {
+        Some(language) => Ok(SessionCommand::Language(Some(language.code.to_string()))),
+        None => {
+            let codes: Vec<&str> = language::LANGUAGES.iter().map(|l| l.code).collect();
+            Err(format!("Usage: /lang <{}>, or /lang auto", codes.join("|")))
+        }
+    }
+}
+
+/// Whether an argument asks for the configured default back
+fn is_default(argument: &str) -> bool {
+    matches!(
+        argument.to_lowercase().as_str(),
+        "default" | "off" | "reset"
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_temperature() {
+        assert_eq!(
+            parse_command("/temp 0.2"),
+            Some(Ok(SessionCommand::Temperature(Some(0.2))))
+        );
+        assert_eq!(
+            parse_command("  /TEMP@rusty_bot   1.5 "),
+            Some(Ok(SessionCommand::Temperature(Some(1.5))))
+        );
+        assert_eq!(
+            parse_command("/temperature default"),
+            Some(Ok(SessionCommand::Temperature(None)))
+        );
+        for invalid in ["/temp", "/temp 2.5", "/temp -0.1", "/temp hot", "/temp NaN"] {
+            let Some(Err(usage)) = parse_command(invalid) else {
+                panic!("{} accepted", invalid);
+            };
+            assert!(usage.starts_with("Usage: /temp"));
+        }
+    }
+
+    #[test]
+    fn test_parse_persona() {
+        assert_eq!(
+            parse_command("/persona Concise"),
+            Some(Ok(SessionCommand::Persona(Some("concise".to_string()))))
+        );
+        assert_eq!(
+            parse_command("/persona off"),
+            Some(Ok(SessionCommand::Persona(None)))
+        );
+        let Some(Err(usage)) = parse_command("/persona pirate") else {
+            panic!("unknown persona accepted");
+        };
+        assert!(usage.contains("concise|detailed|friendly|formal"));
+        assert!(matches!(parse_command("/persona"), Some(Err(_))));
+    }
+
+    #[test]
+    fn test_parse_language() {
+        assert_eq!(
+            parse_command("/lang German"),
+            Some(Ok(SessionCommand::Language(Some("de".to_string()))))
+        );
+        assert_eq!(
+            parse_command("/language ja"),
+            Some(Ok(SessionCommand::Language(Some("ja".to_string()))))
+        );
+        assert_eq!(
+            parse_command("/lang auto"),
+            Some(Ok(SessionCommand::Language(None)))
+        );
+        for invalid in ["/lang", "/lang klingon"] {
+            let Some(Err(usage)) = parse_command(invalid) else {
+                panic!("{} accepted", invalid);
+            };
+            assert!(usage.starts_with("Usage: /lang <ar|de"));
+        }
+    }
+
+    #[test]
+    fn test_other_messages_are_not_commands() {
+        assert_eq!(parse_command("/reset"), Some(Ok(SessionCommand::Reset)));
+        assert_eq!(parse_command("/stats"), Some(Ok(SessionCommand::Stats)));
+        for message in [
+            "What is the temp outside?",
+            "/temporary files",
+            "/reset the counter for me",
+            "/languages are hard",
+            "/help",
+            "",
+        ] {
+            assert_eq!(parse_command(message), None, "{:?}", message);
+        }
+    }
+
+    #[test]
+    fn test_persona_instructions() {
+        let overrides = SessionOverrides {
+            temperature: None,
+            persona: Some("formal".to_string()),
+            language: None,
+            model: None,
+        };
+        assert!(overrides.persona_instructions().unwrap().contains("formal"));
+        assert_eq!(SessionOverrides::default().persona_instructions(), None);
+    }
+
+    #[test]
+    fn test_session_choices_win_over_user_settings() {
+        let session = SessionOverrides {
+            temperature: Some(0.2),
+            ..Default::default()
+        };
+        let merged = session.or_user_settings(UserSettings {
+            model: Some("user-model".to_string()),
+            temperature: Some(1.0),
+            persona: Some("friendly".to_string()),
+            language: None,
+        });
+        assert_eq!(merged.temperature, Some(0.2));
+        assert_eq!(merged.model.as_deref(), Some("user-model"));
+        assert_eq!(merged.persona.as_deref(), Some("friendly"));
+        assert_eq!(merged.language, None);
+    }
+
+    #[test]
+    fn test_normalize_user_settings() {
+        let settings = normalize_user_settings(UserSettings {
+            model: Some("  ".to_string()),
+            temperature: Some(0.7),
+            persona: Some(" Concise".to_string()),
+            language: Some("German".to_string()),
+        })
+        .unwrap();
+        assert_eq!(settings.model, None);
+        assert_eq!(settings.persona.as_deref(), Some("concise"));
+        assert_eq!(settings.language.as_deref(), Some("de"));
+
+        for invalid in [
+            UserSettings {
+                temperature: Some(3.0),
+                ..Default::default()
+            },
+            UserSettings {
+                persona: Some("pirate".to_string()),
+                ..Default::default()
+            },
+            UserSettings {
+                language: Some("klingon".to_string()),
+                ..Default::default()
+            },
+        ] {
+            assert!(normalize_user_settings(invalid).is_err());
+        }
+    }
+}
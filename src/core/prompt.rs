@@ -4,26 +4,83 @@
 //! following the OpenClaw-style approach.
 
 use crate::config::workspace::{Workspace, WorkspaceFile};
+use crate::config::{Config, PromptConfig};
+use crate::core::{language, locale};
 use crate::llm::ToolDefinition;
-use chrono::{Local, Utc};
+use chrono::{DateTime, FixedOffset, Utc};
 use std::env;
+use std::path::Path;
 
 /// Builds dynamic system prompts from workspace files and runtime context
 pub struct SystemPromptBuilder {
     workspace: Workspace,
     tools: Vec<ToolDefinition>,
+    base_prompt: Option<String>,
+    user: Option<String>,
+    timezone: Option<String>,
+    notes: Vec<String>,
+    tool_calling: bool,
 }
 
 impl SystemPromptBuilder {
     /// Create a new prompt builder
     pub fn new(workspace: Workspace, tools: Vec<ToolDefinition>) -> Self {
-        Self { workspace, tools }
+        Self {
+            workspace,
+            tools,
+            base_prompt: None,
+            user: None,
+            timezone: None,
+            notes: Vec::new(),
+            tool_calling: true,
+        }
+    }
+
+    /// Set the deployment-wide base persona (placed above all other sections)
+    pub fn with_base_prompt(mut self, prompt: Option<String>) -> Self {
+        self.base_prompt = prompt;
+        self
+    }
+
+    /// Set the user the prompt is built for (used for `{{user}}`)
+    pub fn with_user(mut self, user: impl Into<String>) -> Self {
+        self.user = Some(user.into());
+        self
+    }
+
+    /// Set the IANA timezone used for local dates and times
+    pub fn with_timezone(mut self, timezone: Option<String>) -> Self {
+        self.timezone = timezone;
+        self
+    }
+
+    /// Set the notes pinned to the session
+    pub fn with_notes(mut self, notes: Vec<String>) -> Self {
+        self.notes = notes;
+        self
+    }
+
+    /// Set whether the model can call the tools (defaults to true); models
+    /// that cannot are only told about them
+    pub fn with_tool_calling(mut self, tool_calling: bool) -> Self {
+        self.tool_calling = tool_calling;
+        self
+    }
+
+    /// Current time in the prompt's timezone
+    fn local_now(&self) -> DateTime<FixedOffset> {
+        locale::local_time(Utc::now(), self.timezone.as_deref())
     }
 
     /// Build the complete system prompt
     pub fn build(&self) -> String {
         let mut sections = Vec::new();
 
+        // 0. Operator-configured base persona
+        if let Some(section) = self.build_base_section() {
+            sections.push(section);
+        }
+
         // 1. Identity and Soul
         if let Some(section) = self.build_identity_section() {
             sections.push(section);
@@ -45,6 +102,11 @@ impl SystemPromptBuilder {
             sections.push(section);
         }
 
+        // 5b. Notes pinned to the session
+        if let Some(section) = self.build_notes_section() {
+            sections.push(section);
+        }
+
         // 6. Runtime information
         sections.push(self.build_runtime_section());
 
@@ -59,6 +121,33 @@ impl SystemPromptBuilder {
         sections.join("\n\n")
     }
 
+    /// Build the base persona section with placeholders substituted
+    fn build_base_section(&self) -> Option<String> {
+        let base = self.base_prompt.as_deref()?.trim();
+        if base.is_empty() {
+            return None;
+        }
+
+        Some(
+            base.replace("{{date}}", &self.local_now().format("%Y-%m-%d").to_string())
+                .replace("{{user}}", self.user.as_deref().unwrap_or("user")),
+        )
+    }
+
+    /// Build the section listing the session's pinned notes
+    fn build_notes_section(&self) -> Option<String> {
+        if self.notes.is_empty() {
+            return None;
+        }
+
+        let mut section =
+            String::from("## Pinned Notes\n\nThe user asked you to always follow these notes:\n");
+        for note in &self.notes {
+            section.push_str(&format!("\n- {}", note.trim()));
+        }
+        Some(section)
+    }
+
     /// Build identity section from IDENTITY.md and SOUL.md
     fn build_identity_section(&self) -> Option<String> {
         let mut parts = Vec::new();
@@ -83,6 +172,22 @@ impl SystemPromptBuilder {
     fn build_tooling_section(&self) -> String {
         let mut section = String::from("## Available Tools\n\n");
 
+        if !self.tool_calling {
+            if self.tools.is_empty() {
+                section.push_str("Tool calling is not available with this model.\n");
+            } else {
+                section.push_str(
+                    "This model cannot call tools directly. When one of these tools would help, \
+                     tell the user which tool you would use and with what input:\n\n",
+                );
+                for tool in &self.tools {
+                    section.push_str(&format!("- `{}`: {}\n", tool.name, tool.description));
+                }
+            }
+            // The tool creation guide needs tool calls
+            return section;
+        }
+
         if self.tools.is_empty() {
             section.push_str("No tools are currently available.\n");
         } else {
@@ -165,18 +270,85 @@ impl SystemPromptBuilder {
     /// Build current time section
     fn build_time_section(&self) -> String {
         let utc_time = Utc::now();
-        let local_time = Local::now();
+        let local_time = self.local_now();
+        let zone = self.timezone.as_deref().unwrap_or("server local time");
 
         format!(
             "## Current Time\n\n\
              - **UTC**: {}\n\
-             - **Local**: {}",
+             - **Local**: {} ({})\n\n\
+             Use the local time when answering questions about dates and times.",
             utc_time.format("%Y-%m-%d %H:%M:%S UTC"),
-            local_time.format("%Y-%m-%d %H:%M:%S %Z")
+            local_time.format("%A, %Y-%m-%d %H:%M:%S %:z"),
+            zone
         )
     }
 }
 
+/// Resolve the configured base persona.
+///
+/// `prompt.system` may be inline text or a path to a file; an existing file
+/// path is read, anything else is used verbatim.
+pub fn load_base_prompt(config: &PromptConfig) -> Option<String> {
+    config.system.as_deref().and_then(load_prompt_text)
+}
+
+/// Resolve the persona of a session: the agent's persona wins over the
+/// channel's, which wins over the global `prompt.system`
+pub fn resolve_base_prompt(
+    config: &Config,
+    agent_id: Option<&str>,
+    channel: &str,
+) -> Option<String> {
+    let value = agent_id
+        .and_then(|id| config.agents.get(id))
+        .and_then(|agent| agent.persona.as_deref())
+        .or_else(|| config.channels.persona(channel))
+        .or(config.prompt.system.as_deref())?;
+    load_prompt_text(value)
+}
+
+/// Read a prompt that is either inline text or a path to a file
+fn load_prompt_text(value: &str) -> Option<String> {
+    let path = Path::new(value.trim());
+
+    if !value.contains('\n') && path.is_file() {
+        match std::fs::read_to_string(path) {
+            Ok(content) => return Some(content),
+            Err(e) => {
+                tracing::warn!("Failed to read system prompt {}: {}", path.display(), e);
+                return None;
+            }
+        }
+    }
+
+    Some(value.to_string())
+}
+
+/// Section asking for replies in the session's language: the one chosen
+/// with `/lang`, or with `detect` the language `message` is written in.
+/// `None` when no language is chosen or can be detected.
+pub fn build_language_section(chosen: Option<&str>, message: &str, detect: bool) -> Option<String> {
+    let language = match chosen {
+        Some(chosen) => language::find(chosen),
+        None if detect => language::detect(message),
+        None => None,
+    }?;
+
+    Some(format!(
+        "## Reply Language\n\n\
+         Reply in {name} unless the user asks for another language. Keep code, \
+         commands, identifiers and quoted text exactly as written; do not translate \
+         them into {name}.",
+        name = language.name
+    ))
+}
+
+/// Rough token estimate for prompt budgeting (~4 characters per token)
+pub fn estimate_tokens(text: &str) -> usize {
+    text.chars().count().div_ceil(4)
+}
+
 /// Build a system prompt with minimal context (for sub-agents)
 pub fn build_minimal_prompt(tools: &[ToolDefinition]) -> String {
     let mut prompt = String::from("You are a helpful AI assistant.\n\n");
@@ -191,6 +363,40 @@ pub fn build_minimal_prompt(tools: &[ToolDefinition]) -> String {
     prompt
 }
 
+/// Build the welcome and `/help` text shown to users: HELP.md from the
+/// workspace (or the built-in default) followed by the available tools
+pub fn build_help_text(workspace: &Workspace, tools: &[ToolDefinition]) -> String {
+    let mut text = workspace
+        .load_or_default(WorkspaceFile::Help)
+        .trim_end()
+        .to_string();
+
+    let mut tools: Vec<&ToolDefinition> = tools.iter().collect();
+    tools.sort_by(|a, b| a.name.cmp(&b.name));
+    tools.dedup_by(|a, b| a.name == b.name);
+    if !tools.is_empty() {
+        text.push_str("\n\nTools I can use:\n");
+        for tool in tools {
+            text.push_str(&format!(
+                "\n- {}: {}",
+                tool.name,
+                first_sentence(&tool.description)
+            ));
+        }
+    }
+
+    text
+}
+
+/// The first sentence of a tool description, for compact listings
+fn first_sentence(description: &str) -> &str {
+    let description = description.trim();
+    match description.find(". ") {
+        Some(end) => &description[..=end],
+        None => description,
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -232,9 +438,161 @@ mod tests {
         assert!(prompt.contains("A test tool"));
     }
 
+    #[test]
+    fn test_base_prompt_is_first_and_substituted() {
+        let dir = tempdir().unwrap();
+        let workspace = Workspace::new(dir.path().join("workspace"));
+        workspace.init_default().unwrap();
+
+        let prompt = SystemPromptBuilder::new(workspace, vec![])
+            .with_base_prompt(Some(
+                "You are Ada. Speaking with {{user}} on {{date}}.".into(),
+            ))
+            .with_user("alice")
+            .build();
+
+        let today = chrono::Local::now().format("%Y-%m-%d").to_string();
+        assert!(prompt.starts_with(&format!("You are Ada. Speaking with alice on {}.", today)));
+        assert!(prompt.contains("## Safety Guidelines"));
+    }
+
+    #[test]
+    fn test_time_section_uses_timezone() {
+        let dir = tempdir().unwrap();
+        let workspace = Workspace::new(dir.path().join("workspace"));
+
+        let prompt = SystemPromptBuilder::new(workspace, vec![])
+            .with_base_prompt(Some("Today is {{date}}.".into()))
+            .with_timezone(Some("Pacific/Kiritimati".to_string()))
+            .build();
+
+        let today = locale::local_time(Utc::now(), Some("Pacific/Kiritimati"))
+            .format("%Y-%m-%d")
+            .to_string();
+        assert!(prompt.starts_with(&format!("Today is {}.", today)));
+        assert!(prompt.contains("+14:00 (Pacific/Kiritimati)"));
+    }
+
+    #[test]
+    fn test_notes_section_lists_pinned_notes() {
+        let dir = tempdir().unwrap();
+        let workspace = Workspace::new(dir.path().join("workspace"));
+
+        let prompt = SystemPromptBuilder::new(workspace.clone(), vec![])
+            .with_notes(vec!["Answer in French".into(), " Be brief ".into()])
+            .build();
+        assert!(prompt.contains("## Pinned Notes"));
+        assert!(prompt.contains("\n- Answer in French\n- Be brief"));
+
+        let prompt = SystemPromptBuilder::new(workspace, vec![]).build();
+        assert!(!prompt.contains("## Pinned Notes"));
+    }
+
+    #[test]
+    fn test_persona_precedence_agent_channel_global() {
+        let mut config: Config = serde_yaml::from_str(
+            "llm:\n  models:\n    primary: m\n\
+             prompt:\n  system: Global persona\n\
+             channels:\n  whatsapp:\n    persona: Casual persona\n\
+             agents:\n  work:\n    name: Work\n    persona: Formal persona\n  plain:\n    name: Plain\n",
+        )
+        .unwrap();
+
+        let resolve = |config: &Config, agent: Option<&str>, channel: &str| {
+            resolve_base_prompt(config, agent, channel)
+        };
+        assert_eq!(
+            resolve(&config, Some("work"), "whatsapp").as_deref(),
+            Some("Formal persona")
+        );
+        assert_eq!(
+            resolve(&config, Some("plain"), "whatsapp").as_deref(),
+            Some("Casual persona")
+        );
+        assert_eq!(
+            resolve(&config, None, "web").as_deref(),
+            Some("Global persona")
+        );
+
+        config.prompt.system = None;
+        assert!(resolve(&config, None, "telegram").is_none());
+    }
+
+    #[test]
+    fn test_load_base_prompt_from_file() {
+        let dir = tempdir().unwrap();
+        let path = dir.path().join("persona.md");
+        std::fs::write(&path, "Persona from file").unwrap();
+
+        let from_file = PromptConfig {
+            system: Some(path.to_string_lossy().to_string()),
+            ..Default::default()
+        };
+        assert_eq!(
+            load_base_prompt(&from_file).as_deref(),
+            Some("Persona from file")
+        );
+
+        let inline = PromptConfig {
+            system: Some("Be terse.".to_string()),
+            ..Default::default()
+        };
+        assert_eq!(load_base_prompt(&inline).as_deref(), Some("Be terse."));
+        assert!(load_base_prompt(&PromptConfig::default()).is_none());
+    }
+
+    #[test]
+    fn test_detected_language_sets_the_reply_language() {
+        let section = build_language_section(None, "Wie spät ist es gerade in Tokio?", true)
+            .expect("no language detected");
+        assert!(section.starts_with("## Reply Language"));
+        assert!(section.contains("Reply in German"));
+
+        // Detection is opt-in, and `/lang` wins over it
+        assert_eq!(
+            build_language_section(None, "Wie spät ist es gerade in Tokio?", false),
+            None
+        );
+        let chosen = build_language_section(Some("fr"), "Wie spät ist es gerade in Tokio?", true);
+        assert!(chosen.unwrap().contains("Reply in French"));
+        assert_eq!(build_language_section(None, "`cargo build`", true), None);
+    }
+
     #[test]
     fn test_minimal_prompt() {
         let prompt = build_minimal_prompt(&[]);
         assert!(prompt.contains("helpful AI assistant"));
     }
+
+    #[test]
+    fn test_help_text_from_workspace_with_tools() {
+        let dir = tempdir().unwrap();
+        let workspace = Workspace::new(dir.path());
+        let tools = vec![
+            ToolDefinition {
+                name: "web_search".to_string(),
+                description: "Search the web. Returns titles and links.".to_string(),
+                parameters: serde_json::json!({}),
+            },
+            ToolDefinition {
+                name: "calculate".to_string(),
+                description: "Evaluate arithmetic".to_string(),
+                parameters: serde_json::json!({}),
+            },
+        ];
+
+        // Without HELP.md the built-in welcome is used
+        let help = build_help_text(&workspace, &tools);
+        assert!(help.starts_with("# Welcome to RustyClaw"), "{}", help);
+
+        workspace
+            .save_file(WorkspaceFile::Help, "Hi! I'm the ops bot.\n")
+            .unwrap();
+        assert_eq!(
+            build_help_text(&workspace, &tools),
+            "Hi! I'm the ops bot.\n\nTools I can use:\n\n\
+             - calculate: Evaluate arithmetic\n\
+             - web_search: Search the web."
+        );
+    }
 }
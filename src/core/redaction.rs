@@ -0,0 +1,152 @@
+//! Optional PII redaction of stored messages and tool audit records
+//!
+//! Configured patterns are masked before either is persisted; the text
+//! sent to the model for the current turn is left intact unless
+//! `redaction.redact_model_input` is set.
+
+use crate::config::RedactionConfig;
+use crate::storage::Message;
+use anyhow::{Context, Result};
+use regex::Regex;
+
+/// Compiled redaction patterns
+pub struct Redactor {
+    patterns: Vec<(String, Regex)>,
+    replacement: String,
+}
+
+impl Redactor {
+    /// Compile the configured patterns, or `None` when redaction is disabled
+    pub fn from_config(config: &RedactionConfig) -> Result<Option<Self>> {
+        if !config.enabled {
+            return Ok(None);
+        }
+
+        let patterns = config
+            .patterns
+            .iter()
+            .map(|pattern| {
+                Regex::new(&pattern.regex)
+                    .map(|regex| (pattern.name.clone(), regex))
+                    .with_context(|| format!("Invalid redaction pattern '{}'", pattern.name))
+            })
+            .collect::<Result<_>>()?;
+
+        Ok(Some(Self {
+            patterns,
+            replacement: config.replacement.clone(),
+        }))
+    }
+
+    /// Mask every match, returning the masked text and the names of the
+    /// patterns that matched
+    pub fn redact(&self, text: &str) -> (String, Vec<String>) {
+        let mut redacted = text.to_string();
+        let mut matched = Vec::new();
+
+        for (name, regex) in &self.patterns {
+            if regex.is_match(&redacted) {
+                redacted = regex
+                    .replace_all(&redacted, regex::NoExpand(&self.replacement))
+                    .into_owned();
+                matched.push(name.clone());
+            }
+        }
+
+        (redacted, matched)
+    }
+
+    /// Redact a message before it is stored, recording the matched patterns
+    /// under `metadata.redacted`
+    pub fn apply(&self, message: &mut Message) {
+        let (content, matched) = self.redact(&message.content);
+        if matched.is_empty() {
+            return;
+        }
+
+        message.content = content;
+        let metadata = message
+            .metadata
+            .get_or_insert_with(|| serde_json::json!({}));
+        if let Some(object) = metadata.as_object_mut() {
+            object.insert("redacted".to_string(), serde_json::json!(matched));
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn redactor() -> Redactor {
+        Redactor::from_config(&RedactionConfig {
+            enabled: true,
+            ..Default::default()
+        })
+        .unwrap()
+        .unwrap()
+    }
+
+    #[test]
+    fn test_default_patterns() {
+        let redactor = redactor();
+
+        let (text, matched) = redactor.redact("Mail jane.doe+work@example.co.uk today");
+        assert_eq!(text, "Mail [REDACTED] today");
+        assert_eq!(matched, vec!["email"]);
+
+        for phone in [
+            "555-123-4567",
+            "(555) 123-4567",
+            "+1 (555) 123-4567",
+            "+15551234567",
+        ] {
+            let (text, matched) = redactor.redact(&format!("Call {} now", phone));
+            assert_eq!(text, "Call [REDACTED] now", "{}", phone);
+            assert_eq!(matched, vec!["phone"]);
+        }
+
+        let (text, matched) = redactor.redact("Card 4111 1111 1111 1111, exp 12/27");
+        assert_eq!(text, "Card [REDACTED], exp 12/27");
+        assert_eq!(matched, vec!["credit_card"]);
+
+        let (text, matched) = redactor.redact("Order 1234 shipped in 2024");
+        assert_eq!(text, "Order 1234 shipped in 2024");
+        assert!(matched.is_empty());
+    }
+
+    #[test]
+    fn test_apply_records_metadata() {
+        let mut message = Message {
+            id: "m1".to_string(),
+            session_id: "s1".to_string(),
+            role: "user".to_string(),
+            content: "I'm bob@example.com, card 5500-0000-0000-0004".to_string(),
+            created_at: chrono::Utc::now(),
+            model_used: None,
+            tokens: None,
+            metadata: None,
+        };
+
+        redactor().apply(&mut message);
+
+        assert_eq!(message.content, "I'm [REDACTED], card [REDACTED]");
+        assert_eq!(
+            message.metadata,
+            Some(serde_json::json!({ "redacted": ["email", "credit_card"] }))
+        );
+    }
+
+    #[test]
+    fn test_invalid_pattern_is_an_error() {
+        let config = RedactionConfig {
+            enabled: true,
+            patterns: vec![crate::config::RedactionPattern {
+                name: "broken".to_string(),
+                regex: "(".to_string(),
+            }],
+            ..Default::default()
+        };
+        assert!(Redactor::from_config(&config).is_err());
+    }
+}
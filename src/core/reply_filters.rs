@@ -0,0 +1,207 @@
+//! Post-processing of the assistant's final reply
+//!
+//! `prompt.reply_filters` lists transforms applied in order to every final
+//! reply before it is stored and sent, e.g. to drop `<think>` leftovers of
+//! reasoning models or cap the length of replies. A streamed reply is
+//! filtered once complete; when that changes it, `StreamEvent::Done`
+//! carries the filtered text to show instead of the streamed one.
+
+use crate::config::ReplyFilter;
+use anyhow::{Context, Result};
+use once_cell::sync::Lazy;
+use regex::Regex;
+
+/// Complete `<think>` blocks
+static THINKING: Lazy<Regex> = Lazy::new(|| Regex::new(r"(?s)<think>.*?</think>").unwrap());
+
+/// Three or more line breaks, possibly with whitespace between them
+static BLANK_LINES: Lazy<Regex> = Lazy::new(|| Regex::new(r"\n[ \t]*(?:\n[ \t]*){2,}").unwrap());
+
+/// A configured filter, ready to apply
+enum CompiledFilter {
+    StripThinking,
+    Trim,
+    Truncate { max_chars: usize, suffix: String },
+    RegexReplace { regex: Regex, replacement: String },
+}
+
+/// The configured reply filters, in order
+pub struct ReplyFilters {
+    filters: Vec<CompiledFilter>,
+}
+
+impl ReplyFilters {
+    /// Compile the configured filters, or `None` when there are none
+    pub fn from_config(filters: &[ReplyFilter]) -> Result<Option<Self>> {
+        if filters.is_empty() {
+            return Ok(None);
+        }
+
+        let filters = filters
+            .iter()
+            .map(|filter| {
+                Ok(match filter {
+                    ReplyFilter::StripThinking => CompiledFilter::StripThinking,
+                    ReplyFilter::Trim => CompiledFilter::Trim,
+                    ReplyFilter::Truncate { max_chars, suffix } => CompiledFilter::Truncate {
+                        max_chars: *max_chars,
+                        suffix: suffix.clone(),
+                    },
+                    ReplyFilter::RegexReplace {
+                        pattern,
+                        replacement,
+                    } => CompiledFilter::RegexReplace {
+                        regex: Regex::new(pattern).with_context(|| {
+                            format!("Invalid reply filter pattern '{}'", pattern)
+                        })?,
+                        replacement: replacement.clone(),
+                    },
+                })
+            })
+            .collect::<Result<_>>()?;
+
+        Ok(Some(Self { filters }))
+    }
+
+    /// Run a reply through every filter
+    pub fn apply(&self, reply: &str) -> String {
+        self.filters
+            .iter()
+            .fold(reply.to_string(), |reply, filter| match filter {
+                CompiledFilter::StripThinking => strip_thinking(&reply),
+                CompiledFilter::Trim => trim(&reply),
+                CompiledFilter::Truncate { max_chars, suffix } => {
+                    truncate(reply, *max_chars, suffix)
+                }
+                CompiledFilter::RegexReplace { regex, replacement } => {
+                    regex.replace_all(&reply, replacement.as_str()).into_owned()
+                }
+            })
+    }
+}
+
+fn strip_thinking(reply: &str) -> String {
+    let mut reply = THINKING.replace_all(reply, "").into_owned();
+    // The opening tag may have been in the prefill or cut off
+    if let Some(end) = reply.rfind("</think>") {
+        reply.replace_range(..end + "</think>".len(), "");
+    }
+    // Reasoning that never finished
+    if let Some(start) = reply.find("<think>") {
+        reply.truncate(start);
+    }
+    reply.trim_start().to_string()
+}
+
+fn trim(reply: &str) -> String {
+    let lines: Vec<&str> = reply.lines().map(str::trim_end).collect();
+    BLANK_LINES
+        .replace_all(lines.join("\n").trim(), "\n\n")
+        .into_owned()
+}
+
+fn truncate(reply: String, max_chars: usize, suffix: &str) -> String {
+    if reply.chars().count() <= max_chars {
+        return reply;
+    }
+    let kept = max_chars.saturating_sub(suffix.chars().count());
+    let mut truncated: String = reply.chars().take(kept).collect();
+    truncated.push_str(suffix);
+    truncated
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn filters(filters: Vec<ReplyFilter>) -> ReplyFilters {
+        ReplyFilters::from_config(&filters).unwrap().unwrap()
+    }
+
+    #[test]
+    fn test_strip_thinking() {
+        let filters = filters(vec![ReplyFilter::StripThinking]);
+        assert_eq!(
+            filters.apply("<think>The user wants a greeting.</think>\n\nHello!"),
+            "Hello!"
+        );
+        assert_eq!(
+            filters.apply("Hi <think>a</think>there<think>b</think>!"),
+            "Hi there!"
+        );
+        // A closing tag without its opening one, and an unfinished block
+        assert_eq!(filters.apply("plan the reply</think>Done."), "Done.");
+        assert_eq!(filters.apply("Sure. <think>maybe also"), "Sure. ");
+        assert_eq!(filters.apply("No reasoning here"), "No reasoning here");
+    }
+
+    #[test]
+    fn test_trim() {
+        let filters = filters(vec![ReplyFilter::Trim]);
+        assert_eq!(
+            filters.apply("\n  First line   \n\n\n \nSecond line\t\n\n"),
+            "First line\n\nSecond line"
+        );
+        assert_eq!(filters.apply("a\n\nb"), "a\n\nb");
+    }
+
+    #[test]
+    fn test_truncate() {
+        let filters = filters(vec![ReplyFilter::Truncate {
+            max_chars: 8,
+            suffix: "…".to_string(),
+        }]);
+        assert_eq!(filters.apply("Hello, world"), "Hello, …");
+        assert_eq!(filters.apply("Grüße"), "Grüße");
+        assert_eq!(filters.apply("Grüße aus Köln"), "Grüße a…");
+    }
+
+    #[test]
+    fn test_regex_replace() {
+        let filters = filters(vec![ReplyFilter::RegexReplace {
+            pattern: r"\bTODO\((\w+)\)".to_string(),
+            replacement: "(ask $1)".to_string(),
+        }]);
+        assert_eq!(
+            filters.apply("TODO(alice) and TODO(bob)"),
+            "(ask alice) and (ask bob)"
+        );
+
+        let invalid = ReplyFilters::from_config(&[ReplyFilter::RegexReplace {
+            pattern: "(".to_string(),
+            replacement: String::new(),
+        }]);
+        assert!(invalid.is_err());
+    }
+
+    #[test]
+    fn test_filters_run_in_order() {
+        let strip_then_truncate = filters(vec![
+            ReplyFilter::StripThinking,
+            ReplyFilter::Trim,
+            ReplyFilter::Truncate {
+                max_chars: 5,
+                suffix: String::new(),
+            },
+        ]);
+        assert_eq!(
+            strip_then_truncate.apply("<think>long reasoning</think> Hello there"),
+            "Hello"
+        );
+
+        // Cut inside the reasoning, which is then dropped as unfinished
+        let truncate_then_strip = filters(vec![
+            ReplyFilter::Truncate {
+                max_chars: 12,
+                suffix: String::new(),
+            },
+            ReplyFilter::StripThinking,
+        ]);
+        assert_eq!(
+            truncate_then_strip.apply("<think>long reasoning</think> Hello there"),
+            ""
+        );
+
+        assert!(ReplyFilters::from_config(&[]).unwrap().is_none());
+    }
+}
@@ -1,10 +1,19 @@
+use crate::channels::retry::retry_with_delay;
 use crate::config::workspace::Workspace;
-use crate::config::Config;
-use crate::core::{ApprovalManager, MessageResponse, SessionManager};
+use crate::config::{Config, DraftRecovery};
+use crate::core::costs::{self, CostLedger, COST_LIMIT_REPLY};
+use crate::core::language;
+use crate::core::moderation::Moderation;
+use crate::core::overrides::{parse_command, SessionCommand};
+use crate::core::session::{is_transient_error, retry_delay, FailedAfterTools};
+use crate::core::{ApprovalManager, MessageResponse, SessionManager, StreamEvent, TurnOptions};
 use crate::llm::Client as LlmClient;
-use crate::storage::Storage;
+use crate::plugins::InboundMessage;
+use crate::storage::{FeedbackRating, Message as StorageMessage, MessageFeedback, Storage};
 use crate::tools::ToolPolicyEngine;
 use anyhow::Result;
+use std::collections::HashSet;
+use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::Arc;
 use tokio::sync::RwLock;
 
@@ -16,17 +25,30 @@ pub struct Router<S: Storage> {
     session_manager: SessionManager<S>,
     approval_manager: Arc<ApprovalManager>,
     policy_engine: Arc<ToolPolicyEngine>,
+    moderation: Option<Arc<Moderation>>,
+    /// Channels whose messages are ignored until resumed
+    paused_channels: Arc<RwLock<HashSet<String>>>,
+    costs: Arc<CostLedger>,
 }
 
 impl<S: Storage + 'static> Router<S> {
     pub async fn new(config: Arc<RwLock<Config>>, storage: S, llm_client: LlmClient) -> Self {
         // Read initial config for workspace setup
-        let (workspace_path, _sessions_config, _agents_config) = {
+        let (workspace_path, sessions_config, _agents_config, moderation, maintenance) = {
             let cfg = config.read().await; // Use async read
+            let moderation = match Moderation::from_config(&cfg.moderation, &cfg.llm.base_url) {
+                Ok(moderation) => moderation.map(Arc::new),
+                Err(e) => {
+                    tracing::error!("Moderation disabled: {}", e);
+                    None
+                }
+            };
             (
                 cfg.workspace.path.clone(),
                 cfg.sessions.clone(),
                 cfg.agents.clone(),
+                moderation,
+                cfg.gateway.maintenance,
             )
         };
 
@@ -38,8 +60,43 @@ impl<S: Storage + 'static> Router<S> {
         }
 
         // Create approval manager and policy engine
-        let approval_manager = Arc::new(ApprovalManager::new());
+        let approval_manager =
+            Arc::new(ApprovalManager::new().with_store(Arc::new(storage.clone())));
+        match approval_manager.restore_pending().await {
+            Ok(0) => {}
+            Ok(count) => tracing::info!("Restored {} pending tool approval(s)", count),
+            Err(e) => tracing::warn!("Failed to restore pending approvals: {}", e),
+        }
+        if let Some(policy) = crate::get_tool_policy_engine() {
+            match approval_manager.restore_remembered(&policy).await {
+                Ok(0) => {}
+                Ok(count) => tracing::info!("Restored {} remembered tool approval(s)", count),
+                Err(e) => tracing::warn!("Failed to restore remembered approvals: {}", e),
+            }
+        }
         let policy_engine = Arc::new(ToolPolicyEngine::new());
+        let paused_channels = match storage.list_paused_channels().await {
+            Ok(channels) => {
+                if !channels.is_empty() {
+                    tracing::info!("Channels paused before restart: {}", channels.join(", "));
+                }
+                channels.into_iter().collect()
+            }
+            Err(e) => {
+                tracing::warn!("Failed to restore paused channels: {}", e);
+                HashSet::new()
+            }
+        };
+
+        let maintenance = match storage.get_maintenance().await {
+            Ok(persisted) => maintenance || persisted,
+            Err(e) => {
+                tracing::warn!("Failed to restore maintenance mode: {}", e);
+                maintenance
+            }
+        };
+
+        recover_drafts(&storage, sessions_config.drafts.on_startup).await;
 
         let session_manager = SessionManager::with_approval_manager(
             storage.clone(),
@@ -48,15 +105,102 @@ impl<S: Storage + 'static> Router<S> {
             workspace,
             approval_manager.clone(),
         );
+        if maintenance {
+            tracing::warn!("Starting in maintenance mode: only reads are served");
+            session_manager.maintenance().set_active(true);
+        }
 
         Self {
             config,
             session_manager,
             approval_manager,
             policy_engine,
+            moderation,
+            paused_channels: Arc::new(RwLock::new(paused_channels)),
+            costs: Arc::new(CostLedger::new()),
+        }
+    }
+
+    /// Stop answering messages on a channel until it is resumed. A pause
+    /// that is not persisted ends when the gateway restarts.
+    pub async fn pause_channel(&self, channel: &str, persist: bool) -> Result<()> {
+        if persist {
+            self.get_storage().set_channel_paused(channel, true).await?;
+        }
+        self.paused_channels
+            .write()
+            .await
+            .insert(channel.to_string());
+        tracing::info!("Channel {} paused", channel);
+        Ok(())
+    }
+
+    /// Answer messages on a paused channel again
+    pub async fn resume_channel(&self, channel: &str) -> Result<()> {
+        self.get_storage()
+            .set_channel_paused(channel, false)
+            .await?;
+        self.paused_channels.write().await.remove(channel);
+        tracing::info!("Channel {} resumed", channel);
+        Ok(())
+    }
+
+    /// Whether messages on a channel are currently ignored
+    pub async fn is_channel_paused(&self, channel: &str) -> bool {
+        self.paused_channels.read().await.contains(channel)
+    }
+
+    /// Switch maintenance (read-only) mode on or off. The mode is persisted
+    /// and stays on across restarts until cleared.
+    pub async fn set_maintenance(&self, enabled: bool) -> Result<()> {
+        self.get_storage().set_maintenance(enabled).await?;
+        self.session_manager.maintenance().set_active(enabled);
+        match enabled {
+            true => tracing::warn!("Maintenance mode on: new messages and writes are refused"),
+            false => tracing::info!("Maintenance mode off"),
+        }
+        Ok(())
+    }
+
+    /// Whether new messages, tool calls and writes are currently refused
+    pub fn in_maintenance(&self) -> bool {
+        self.session_manager.maintenance().is_active()
+    }
+
+    /// Replace the moderation gate applied to incoming messages
+    pub fn with_moderation(mut self, moderation: Option<Arc<Moderation>>) -> Self {
+        self.moderation = moderation;
+        self
+    }
+
+    /// Refusal for a message flagged by moderation, if enabled
+    async fn moderation_refusal(
+        &self,
+        user_id: &str,
+        channel: &str,
+        content: &str,
+    ) -> Option<String> {
+        match &self.moderation {
+            Some(moderation) => moderation.refusal_for(user_id, channel, content).await,
+            None => None,
         }
     }
 
+    /// Run the plugins' message_received hooks on an inbound message, ahead
+    /// of moderation and the LLM. Blocked messages are never stored.
+    async fn inbound_hooks(
+        &self,
+        user_id: &str,
+        channel: &str,
+        content: &str,
+    ) -> Result<InboundMessage> {
+        if crate::plugins::get_plugin_registry().is_none() {
+            return Ok(InboundMessage::Process(content.to_string()));
+        }
+        let session = self.get_or_create_session_api(user_id, channel).await?;
+        Ok(crate::plugins::run_inbound_hooks(&session.id, channel, user_id, content).await)
+    }
+
     pub fn config(&self) -> Arc<RwLock<Config>> {
         self.config.clone()
     }
@@ -69,6 +213,10 @@ impl<S: Storage + 'static> Router<S> {
         self.session_manager.storage()
     }
 
+    pub fn llm_client(&self) -> &LlmClient {
+        self.session_manager.llm_client()
+    }
+
     /// Get the approval manager (for handling tool approval requests)
     pub fn get_approval_manager(&self) -> Result<Arc<ApprovalManager>> {
         Ok(self.approval_manager.clone())
@@ -101,24 +249,112 @@ impl<S: Storage + 'static> Router<S> {
         user_id: &str,
         channel: &str,
         content: &str,
+    ) -> Result<MessageResponse> {
+        self.handle_message_with_context(user_id, channel, content, &[], &TurnOptions::default())
+            .await
+    }
+
+    /// Handle a message with caller-supplied context documents that are
+    /// used for this turn only and never stored, and the turn's options: an
+    /// optional sampling seed for reproducible generations, an optional
+    /// prefill the reply starts with, and the tool calls the turn allows.
+    ///
+    /// The reply passes through the plugins' message_sending hooks after it
+    /// is stored; a blocked reply comes back with empty content. Runs in a
+    /// `request` span that parents the turn's LLM and tool spans.
+    #[tracing::instrument(
+        name = "request",
+        skip_all,
+        fields(
+            request_id = %uuid::Uuid::new_v4(),
+            user_id = user_id,
+            channel = channel,
+            session_id = tracing::field::Empty,
+        )
+    )]
+    pub async fn handle_message_with_context(
+        &self,
+        user_id: &str,
+        channel: &str,
+        content: &str,
+        extra_context: &[String],
+        options: &TurnOptions,
     ) -> Result<MessageResponse> {
         tracing::debug!("Handling message from user {} on {}", user_id, channel);
 
+        self.session_manager.maintenance().check()?;
+
+        if self.is_channel_paused(channel).await {
+            return Ok(canned_response(String::new(), PAUSED_MODEL));
+        }
+
+        if let Some(command) = parse_command(content) {
+            let reply = self.run_session_command(user_id, channel, command).await?;
+            return Ok(canned_response(reply, COMMAND_MODEL));
+        }
+
+        if self.answers_user_input(user_id, channel, content).await? {
+            return Ok(canned_response(String::new(), USER_INPUT_MODEL));
+        }
+
+        let content = match self.inbound_hooks(user_id, channel, content).await? {
+            InboundMessage::Process(content) => content,
+            InboundMessage::Blocked(reply) => {
+                return Ok(canned_response(reply.unwrap_or_default(), PLUGIN_MODEL))
+            }
+        };
+        let content = content.as_str();
+
+        if let Some(refusal) = self.moderation_refusal(user_id, channel, content).await {
+            return Ok(canned_response(refusal, MODERATION_MODEL));
+        }
+
         // Resolve agent
         let agent_id = self.resolve_agent(user_id, channel).await;
         let agent_id_ref = agent_id.as_deref();
 
-        // Get or create session
-        let session = self
-            .session_manager
-            .get_or_create_session(user_id, channel, agent_id_ref)
-            .await?;
+        if self
+            .cost_limit_reached(user_id, channel, agent_id_ref)
+            .await?
+        {
+            return Ok(canned_response(
+                COST_LIMIT_REPLY.to_string(),
+                COST_LIMIT_MODEL,
+            ));
+        }
 
-        // Process message (SessionManager handles LLM interaction)
-        let response = self
-            .session_manager
-            .process_message(&session.id, content, agent_id_ref)
-            .await?;
+        // Transient failures retry the whole turn, after the wait the backend
+        // asked for when rate limited, unless its tools already ran; the user
+        // message is stored by the first attempt that gets that far and never
+        // again
+        let retry = self.config.read().await.sessions.retry.clone();
+        let stored = AtomicBool::new(false);
+        let delay = |err: &anyhow::Error, attempt| retry_delay(&retry, attempt, err);
+        let (session, mut response) = retry_with_delay(&retry, is_transient_error, delay, |_| {
+            let stored = &stored;
+            async move {
+                let session = self
+                    .session_manager
+                    .get_or_create_session(user_id, channel, agent_id_ref)
+                    .await?;
+                tracing::Span::current().record("session_id", session.id.as_str());
+                if !stored.load(Ordering::SeqCst) {
+                    self.session_manager
+                        .add_message(&session.id, "user", content, None, None)
+                        .await?;
+                    stored.store(true, Ordering::SeqCst);
+                }
+
+                // SessionManager handles LLM interaction
+                let response = self
+                    .session_manager
+                    .reply_with_context(&session.id, content, agent_id_ref, extra_context, options)
+                    .await?;
+                Ok((session, response))
+            }
+        })
+        .await
+        .map_err(|e| FailedAfterTools::unwrap(e.error))?;
 
         tracing::info!(
             "Message processed: session={}, model={}, tokens={:?}",
@@ -126,10 +362,176 @@ impl<S: Storage + 'static> Router<S> {
             response.model,
             response.tokens
         );
+        if let Some(tokens) = response.tokens {
+            self.record_cost(user_id, &session.id, &response.model, tokens)
+                .await;
+        }
+
+        // The reply is already stored, so history keeps the original text
+        response.content =
+            crate::plugins::run_outbound_hooks(&session.id, channel, response.content)
+                .await
+                .unwrap_or_default();
 
         Ok(response)
     }
 
+    /// Handle a one-off message in a throwaway session, leaving the user's
+    /// conversation history untouched
+    #[tracing::instrument(
+        name = "request",
+        skip_all,
+        fields(
+            request_id = %uuid::Uuid::new_v4(),
+            user_id = user_id,
+            channel = channel,
+            session_id = tracing::field::Empty,
+        )
+    )]
+    pub async fn handle_ephemeral_message(
+        &self,
+        user_id: &str,
+        channel: &str,
+        content: &str,
+    ) -> Result<MessageResponse> {
+        self.session_manager.maintenance().check()?;
+
+        if self.is_channel_paused(channel).await {
+            return Ok(canned_response(String::new(), PAUSED_MODEL));
+        }
+
+        let content = match self.inbound_hooks(user_id, channel, content).await? {
+            InboundMessage::Process(content) => content,
+            InboundMessage::Blocked(reply) => {
+                return Ok(canned_response(reply.unwrap_or_default(), PLUGIN_MODEL))
+            }
+        };
+        let content = content.as_str();
+
+        if let Some(refusal) = self.moderation_refusal(user_id, channel, content).await {
+            return Ok(canned_response(refusal, MODERATION_MODEL));
+        }
+
+        let agent_id = self.resolve_agent(user_id, channel).await;
+        let agent_id_ref = agent_id.as_deref();
+
+        let session = self
+            .session_manager
+            .create_ephemeral_session(user_id, agent_id_ref)
+            .await?;
+        tracing::Span::current().record("session_id", session.id.as_str());
+
+        let response = if self.session_cost_limit_reached(user_id, &session.id).await {
+            Ok(canned_response(
+                COST_LIMIT_REPLY.to_string(),
+                COST_LIMIT_MODEL,
+            ))
+        } else {
+            let response = self
+                .session_manager
+                .process_message(&session.id, content, agent_id_ref)
+                .await;
+            self.record_response_cost(user_id, &session.id, &response)
+                .await;
+            response
+        };
+
+        if let Err(e) = self.session_manager.delete_session(&session.id).await {
+            tracing::warn!("Failed to delete ephemeral session {}: {}", session.id, e);
+        }
+        // The user keeps what the throwaway session spent
+        self.costs.reset_session(&session.id);
+
+        response
+    }
+
+    /// Answer an eval case in a fresh ephemeral session with a fixed seed
+    /// and model, at temperature 0 and without the user's agent, plugins or
+    /// moderation, so the same suite gives comparable replies across runs
+    pub async fn handle_eval_message(
+        &self,
+        user_id: &str,
+        content: &str,
+        seed: i64,
+        model: &str,
+    ) -> Result<MessageResponse> {
+        self.session_manager.maintenance().check()?;
+
+        let session = self
+            .session_manager
+            .create_ephemeral_session(user_id, None)
+            .await?;
+        self.session_manager
+            .update_session_overrides(&session.id, |overrides| {
+                overrides.temperature = Some(0.0);
+                overrides.model = Some(model.to_string());
+            })
+            .await;
+
+        let response = if self.session_cost_limit_reached(user_id, &session.id).await {
+            Ok(canned_response(
+                COST_LIMIT_REPLY.to_string(),
+                COST_LIMIT_MODEL,
+            ))
+        } else {
+            let response = self
+                .session_manager
+                .process_message_with_context(
+                    &session.id,
+                    content,
+                    None,
+                    &[],
+                    &TurnOptions {
+                        seed: Some(seed),
+                        ..Default::default()
+                    },
+                )
+                .await;
+            self.record_response_cost(user_id, &session.id, &response)
+                .await;
+            response
+        };
+
+        self.session_manager
+            .clear_session_overrides(&session.id)
+            .await;
+        if let Err(e) = self.session_manager.delete_session(&session.id).await {
+            tracing::warn!("Failed to delete eval session {}: {}", session.id, e);
+        }
+        self.costs.reset_session(&session.id);
+
+        response
+    }
+
+    /// Rate the latest assistant reply in a user's session on a channel, as
+    /// done by chat reactions. Returns false when there is no reply to rate.
+    pub async fn rate_latest_response(
+        &self,
+        user_id: &str,
+        channel: &str,
+        rating: FeedbackRating,
+    ) -> Result<bool> {
+        let session = self.get_or_create_session_api(user_id, channel).await?;
+        let storage = self.get_storage();
+        let messages = storage.get_messages(&session.id, Some(20)).await?;
+
+        let Some(reply) = messages.into_iter().rev().find(|m| m.role == "assistant") else {
+            return Ok(false);
+        };
+        storage
+            .set_message_feedback(MessageFeedback {
+                message_id: reply.id,
+                session_id: session.id,
+                user_id: user_id.to_string(),
+                rating,
+                comment: None,
+                model: reply.model_used,
+                created_at: chrono::Utc::now(),
+            })
+            .await?;
+        Ok(true)
+    }
+
     /// Clear a user's session (reset conversation)
     pub async fn clear_session(&self, user_id: &str, channel: &str) -> Result<()> {
         let agent_id = self.resolve_agent(user_id, channel).await;
@@ -143,6 +545,90 @@ impl<S: Storage + 'static> Router<S> {
         self.session_manager.clear_session(&session.id).await
     }
 
+    /// Delete sessions idle past `sessions.expiry.ttl_hours` as of `now`
+    pub async fn expire_idle_sessions(
+        &self,
+        now: chrono::DateTime<chrono::Utc>,
+    ) -> Result<Vec<crate::storage::Session>> {
+        self.session_manager.expire_idle_sessions(now).await
+    }
+
+    /// Check for idle sessions every `sessions.expiry.check_interval_minutes`
+    /// until the process exits. The config is re-read on every check, so
+    /// expiry can be turned on and off while running.
+    pub async fn run_session_expiry(self) {
+        loop {
+            let minutes = self
+                .config
+                .read()
+                .await
+                .sessions
+                .expiry
+                .check_interval_minutes
+                .max(1);
+            tokio::time::sleep(std::time::Duration::from_secs(minutes * 60)).await;
+            if let Err(e) = self.expire_idle_sessions(chrono::Utc::now()).await {
+                tracing::error!("Session expiry failed: {}", e);
+            }
+        }
+    }
+
+    /// Apply a session command (`/temp`, `/persona`, `/lang`, `/reset`, `/stats`)
+    /// and return the reply, or the usage when its argument was invalid
+    async fn run_session_command(
+        &self,
+        user_id: &str,
+        channel: &str,
+        command: Result<SessionCommand, String>,
+    ) -> Result<String> {
+        let command = match command {
+            Ok(command) => command,
+            Err(usage) => return Ok(usage),
+        };
+        let session = self.get_or_create_session_api(user_id, channel).await?;
+        let sessions = &self.session_manager;
+
+        let reply = match command {
+            SessionCommand::Temperature(temperature) => {
+                sessions
+                    .update_session_overrides(&session.id, |o| o.temperature = temperature)
+                    .await;
+                match temperature {
+                    Some(t) => format!("Temperature set to {} for this session.", t),
+                    None => "Temperature is back to the default.".to_string(),
+                }
+            }
+            SessionCommand::Persona(persona) => {
+                let reply = match &persona {
+                    Some(p) => format!("Persona set to {} for this session.", p),
+                    None => "Persona is back to the default.".to_string(),
+                };
+                sessions
+                    .update_session_overrides(&session.id, |o| o.persona = persona)
+                    .await;
+                reply
+            }
+            SessionCommand::Language(language) => {
+                let reply = match language.as_deref().and_then(language::find) {
+                    Some(l) => format!("Replies will be in {} for this session.", l.name),
+                    None => "Reply language is back to the default.".to_string(),
+                };
+                sessions
+                    .update_session_overrides(&session.id, |o| o.language = language)
+                    .await;
+                reply
+            }
+            SessionCommand::Reset => {
+                sessions.clear_session(&session.id).await?;
+                sessions.clear_session_overrides(&session.id).await;
+                self.costs.reset_session(&session.id);
+                "Conversation and session settings reset.".to_string()
+            }
+            SessionCommand::Stats => sessions.get_session_stats(&session.id).await?.summary(),
+        };
+        Ok(reply)
+    }
+
     /// Get session statistics
     pub async fn get_session_stats(
         &self,
@@ -161,6 +647,25 @@ impl<S: Storage + 'static> Router<S> {
     }
 
     /// Get or create session (exposed for web API)
+    /// Evict the user's least recently used sessions so one more fits
+    /// under `sessions.max_per_user`
+    pub async fn make_room_for_new_session(&self, user_id: &str) -> Result<()> {
+        self.session_manager
+            .make_room_for_new_session(user_id)
+            .await
+    }
+
+    /// Store imported messages in a session, redacted like any other
+    pub async fn import_messages(
+        &self,
+        session_id: &str,
+        messages: &[StorageMessage],
+    ) -> Result<()> {
+        self.session_manager
+            .import_messages(session_id, messages)
+            .await
+    }
+
     pub async fn get_or_create_session_api(
         &self,
         user_id: &str,
@@ -174,6 +679,70 @@ impl<S: Storage + 'static> Router<S> {
             .await
     }
 
+    /// Pass a reply assembled by a channel adapter (e.g. from a stream)
+    /// through the plugins' message_sending hooks before delivery. Returns
+    /// `None` when a hook blocked it. Like `handle_message`, this runs after
+    /// the reply is stored, so only the delivered copy is rewritten.
+    pub async fn outbound_message(
+        &self,
+        user_id: &str,
+        channel: &str,
+        content: String,
+    ) -> Option<String> {
+        let session_id = match self.get_or_create_session_api(user_id, channel).await {
+            Ok(session) => session.id,
+            Err(e) => {
+                tracing::warn!("No session for outbound hooks: {}", e);
+                String::new()
+            }
+        };
+        crate::plugins::run_outbound_hooks(&session_id, channel, content).await
+    }
+
+    /// Welcome and `/help` text for a user on a channel, from the HELP.md of
+    /// the agent serving them
+    pub async fn help_text(&self, user_id: &str, channel: &str) -> String {
+        let agent_id = self.resolve_agent(user_id, channel).await;
+        self.session_manager.help_text(agent_id.as_deref()).await
+    }
+
+    /// Preview the system prompt a user would receive on a channel
+    pub async fn preview_system_prompt(&self, user_id: &str, channel: &str) -> String {
+        let agent_id = self.resolve_agent(user_id, channel).await;
+        let tools = self.session_manager.get_available_tools().await;
+        // As for a message the router sends to the default model
+        let tool_calling = self.session_manager.tool_calling_for("").await;
+        let tools = self
+            .session_manager
+            .prompt_tools(&tools, tool_calling)
+            .await;
+
+        // Include the notes pinned to the user's session on this channel
+        match self
+            .session_manager
+            .get_or_create_session(user_id, channel, agent_id.as_deref())
+            .await
+        {
+            Ok(session) => {
+                self.session_manager
+                    .build_system_prompt(&session.id, agent_id.as_deref(), tools, tool_calling)
+                    .await
+            }
+            Err(e) => {
+                tracing::warn!("Previewing prompt without session notes: {}", e);
+                self.session_manager
+                    .preview_system_prompt(
+                        user_id,
+                        channel,
+                        agent_id.as_deref(),
+                        tools,
+                        tool_calling,
+                    )
+                    .await
+            }
+        }
+    }
+
     /// Get session messages (exposed for web API)
     pub async fn get_session_messages(
         &self,
@@ -182,23 +751,340 @@ impl<S: Storage + 'static> Router<S> {
         self.session_manager.get_messages(session_id).await
     }
 
+    /// Context the model would get for a session's next turn (see
+    /// `SessionManager::preview_context`)
+    pub async fn preview_session_context(
+        &self,
+        session: &crate::storage::Session,
+    ) -> Result<crate::core::ContextPreview> {
+        let agent_id = self.resolve_agent(&session.user_id, &session.channel).await;
+        self.session_manager
+            .preview_context(&session.id, agent_id.as_deref())
+            .await
+    }
+
+    /// Cached summary of a session, brought up to date (see
+    /// `SessionManager::session_summary`)
+    pub async fn summarize_session(
+        &self,
+        session_id: &str,
+    ) -> Result<Option<crate::storage::SessionSummary>> {
+        self.session_manager.session_summary(session_id).await
+    }
+
+    /// Fork a session at a message (see `SessionManager::fork_session`)
+    pub async fn fork_session(
+        &self,
+        parent: &crate::storage::Session,
+        message_id: &str,
+    ) -> Result<Option<crate::storage::Session>> {
+        self.session_manager.fork_session(parent, message_id).await
+    }
+
+    /// Choose the tool tags offered in a session (empty offers every tool)
+    pub async fn set_session_tool_tags(&self, session_id: &str, tags: Vec<String>) {
+        self.session_manager
+            .set_session_tags(session_id, tags)
+            .await
+    }
+
     /// Handle message with streaming (returns receiver for StreamEvent)
     pub async fn handle_message_stream(
         &self,
         user_id: &str,
         channel: &str,
         content: &str,
-    ) -> Result<tokio::sync::mpsc::Receiver<crate::core::StreamEvent>> {
+    ) -> Result<tokio::sync::mpsc::Receiver<StreamEvent>> {
+        self.handle_message_stream_with_context(
+            user_id,
+            channel,
+            content,
+            &[],
+            &TurnOptions::default(),
+        )
+        .await
+    }
+
+    /// Streaming variant of `handle_message_with_context`; the `request` span
+    /// stays open until the stream ends
+    #[tracing::instrument(
+        name = "request",
+        skip_all,
+        fields(
+            request_id = %uuid::Uuid::new_v4(),
+            user_id = user_id,
+            channel = channel,
+            session_id = tracing::field::Empty,
+        )
+    )]
+    pub async fn handle_message_stream_with_context(
+        &self,
+        user_id: &str,
+        channel: &str,
+        content: &str,
+        extra_context: &[String],
+        options: &TurnOptions,
+    ) -> Result<tokio::sync::mpsc::Receiver<StreamEvent>> {
+        self.session_manager.maintenance().check()?;
+
+        if self.is_channel_paused(channel).await {
+            return Ok(canned_stream(None, PAUSED_MODEL).await);
+        }
+
+        if let Some(command) = parse_command(content) {
+            let reply = self.run_session_command(user_id, channel, command).await?;
+            return Ok(canned_stream(Some(reply), COMMAND_MODEL).await);
+        }
+
+        if self.answers_user_input(user_id, channel, content).await? {
+            return Ok(canned_stream(None, USER_INPUT_MODEL).await);
+        }
+
+        let content = match self.inbound_hooks(user_id, channel, content).await? {
+            InboundMessage::Process(content) => content,
+            InboundMessage::Blocked(reply) => return Ok(canned_stream(reply, PLUGIN_MODEL).await),
+        };
+        let content = content.as_str();
+
+        if let Some(refusal) = self.moderation_refusal(user_id, channel, content).await {
+            return Ok(canned_stream(Some(refusal), MODERATION_MODEL).await);
+        }
+
         let agent_id = self.resolve_agent(user_id, channel).await;
         let agent_id_ref = agent_id.as_deref();
 
+        if self
+            .cost_limit_reached(user_id, channel, agent_id_ref)
+            .await?
+        {
+            return Ok(canned_stream(Some(COST_LIMIT_REPLY.to_string()), COST_LIMIT_MODEL).await);
+        }
+
         let session = self
             .session_manager
             .get_or_create_session(user_id, channel, agent_id_ref)
             .await?;
+        tracing::Span::current().record("session_id", session.id.as_str());
 
-        self.session_manager
-            .process_message_stream(&session.id, content, agent_id_ref)
-            .await
+        let mut events = self
+            .session_manager
+            .process_message_stream_with_context(
+                &session.id,
+                content,
+                agent_id_ref,
+                extra_context,
+                options,
+            )
+            .await?;
+
+        // Passed through so the cost can be recorded once the usage is known
+        let (tx, rx) = tokio::sync::mpsc::channel(32);
+        let router = self.clone();
+        let user_id = user_id.to_string();
+        tokio::spawn(async move {
+            while let Some(event) = events.recv().await {
+                if let StreamEvent::Done {
+                    model,
+                    usage: Some(usage),
+                    ..
+                } = &event
+                {
+                    router
+                        .record_cost(&user_id, &session.id, model, usage.total_tokens)
+                        .await;
+                }
+                if tx.send(event).await.is_err() {
+                    break;
+                }
+            }
+        });
+        Ok(rx)
+    }
+
+    /// Spending tracked against the cost limits, shared with plugins'
+    /// LLM calls (see [`crate::plugins::DefaultPluginApi::with_cost_ledger`])
+    pub fn costs(&self) -> &Arc<CostLedger> {
+        &self.costs
+    }
+
+    /// Whether the user or their session has reached a cost limit
+    async fn cost_limit_reached(
+        &self,
+        user_id: &str,
+        channel: &str,
+        agent_id: Option<&str>,
+    ) -> Result<bool> {
+        let config = self.config.read().await.costs.clone();
+        if config.session_limit.is_none() && config.user_limit.is_none() {
+            return Ok(false);
+        }
+        let session = self
+            .session_manager
+            .get_or_create_session(user_id, channel, agent_id)
+            .await?;
+        Ok(self.session_cost_limit_reached(user_id, &session.id).await)
+    }
+
+    /// Whether a user or the given session of theirs has reached its cost
+    /// limit
+    async fn session_cost_limit_reached(&self, user_id: &str, session_id: &str) -> bool {
+        let config = self.config.read().await.costs.clone();
+        match self.costs.limit_reached(&config, user_id, session_id) {
+            Some(scope) => {
+                tracing::info!(
+                    "Refusing message from {}: {:?} cost limit reached",
+                    user_id,
+                    scope
+                );
+                true
+            }
+            None => false,
+        }
+    }
+
+    /// Pass a message on as the answer to the question the session's turn
+    /// is waiting on, if there is one
+    async fn answers_user_input(
+        &self,
+        user_id: &str,
+        channel: &str,
+        content: &str,
+    ) -> Result<bool> {
+        let inputs = crate::core::user_input::user_inputs();
+        if !inputs.has_pending() {
+            return Ok(false);
+        }
+        let agent_id = self.resolve_agent(user_id, channel).await;
+        let session = self
+            .session_manager
+            .get_or_create_session(user_id, channel, agent_id.as_deref())
+            .await?;
+        Ok(inputs.answer_session(&session.id, content))
+    }
+
+    /// Add the cost of a turn's tokens to its session and user
+    async fn record_cost(&self, user_id: &str, session_id: &str, model: &str, tokens: usize) {
+        let config = self.config.read().await;
+        let cost = costs::price(&config.costs, model, tokens);
+        self.costs.record(&config.costs, user_id, session_id, cost);
+    }
+
+    /// Add the cost of a successful reply, if it reports its tokens
+    async fn record_response_cost(
+        &self,
+        user_id: &str,
+        session_id: &str,
+        response: &Result<MessageResponse>,
+    ) {
+        if let Ok(MessageResponse {
+            model,
+            tokens: Some(tokens),
+            ..
+        }) = response
+        {
+            self.record_cost(user_id, session_id, model, *tokens).await;
+        }
+    }
+}
+
+/// Model reported for replies produced by moderation instead of the LLM
+const MODERATION_MODEL: &str = "moderation";
+/// Model reported for replies produced by a plugin hook instead of the LLM
+const PLUGIN_MODEL: &str = "plugin";
+/// Model reported for the empty replies of a paused channel
+const PAUSED_MODEL: &str = "paused";
+/// Model reported for replies to session commands such as `/temp`
+const COMMAND_MODEL: &str = "command";
+/// Model reported for the refusal sent once a cost limit is reached
+const COST_LIMIT_MODEL: &str = "cost-limit";
+/// Model reported for the empty replies to messages answering a question
+/// the model asked mid-turn
+const USER_INPUT_MODEL: &str = "user-input";
+
+fn canned_response(content: String, model: &str) -> MessageResponse {
+    MessageResponse {
+        content,
+        model: model.to_string(),
+        tokens: None,
+        fallback_from: None,
+        system_fingerprint: None,
+        budget: None,
+    }
+}
+
+/// Stream of a reply produced without the LLM, with no text when `content`
+/// is `None`
+async fn canned_stream(
+    content: Option<String>,
+    model: &str,
+) -> tokio::sync::mpsc::Receiver<StreamEvent> {
+    let (tx, rx) = tokio::sync::mpsc::channel(2);
+    if let Some(content) = content {
+        tx.send(StreamEvent::Delta(content)).await.ok();
+    }
+    tx.send(StreamEvent::Done {
+        model: model.to_string(),
+        usage: None,
+        tps: None,
+        time_to_first_token_ms: None,
+        budget: None,
+        content: None,
+    })
+    .await
+    .ok();
+    rx
+}
+
+/// Promote or discard the drafts of replies that were still streaming when
+/// the gateway stopped. Promoted drafts become assistant messages marked
+/// `partial` in their metadata.
+async fn recover_drafts<S: Storage>(storage: &S, recovery: DraftRecovery) {
+    let drafts = match storage.list_drafts().await {
+        Ok(drafts) => drafts,
+        Err(e) => {
+            tracing::warn!("Failed to load drafts of interrupted replies: {}", e);
+            return;
+        }
+    };
+    if drafts.is_empty() {
+        return;
+    }
+
+    let count = drafts.len();
+    for draft in drafts {
+        if recovery == DraftRecovery::Promote && !draft.content.is_empty() {
+            let message = StorageMessage {
+                id: draft.id.clone(),
+                session_id: draft.session_id,
+                role: "assistant".to_string(),
+                content: draft.content,
+                created_at: draft.updated_at,
+                model_used: draft.model_used,
+                tokens: None,
+                metadata: Some(serde_json::json!({ "partial": true })),
+            };
+            if let Err(e) = storage.add_message(message).await {
+                // Keep the draft for the next attempt
+                tracing::warn!("Failed to promote draft {}: {}", draft.id, e);
+                continue;
+            }
+        }
+        if let Err(e) = storage.delete_draft(&draft.id).await {
+            tracing::warn!("Failed to delete draft {}: {}", draft.id, e);
+        }
+    }
+    match recovery {
+        DraftRecovery::Promote => {
+            tracing::info!(
+                "Recovered {} partial reply(ies) of interrupted turns",
+                count
+            )
+        }
+        DraftRecovery::Discard => {
+            tracing::info!(
+                "Discarded {} partial reply(ies) of interrupted turns",
+                count
+            )
+        }
     }
 }
@@ -0,0 +1,296 @@
+//! Prompts run on a cron schedule
+//!
+//! Each due schedule's prompt runs through the Router in a throwaway session
+//! and the reply is sent to the schedule's target on its channel. Schedules
+//! come from `schedules` in the config and from storage (`/api/schedules`).
+//! Runs missed while the gateway was down are not caught up.
+
+use crate::core::Router;
+use crate::storage::{Schedule, Storage};
+use anyhow::{anyhow, Result};
+use chrono::{DateTime, Utc};
+use std::str::FromStr;
+use std::time::Duration;
+
+/// Seconds between checks for due schedules
+const TICK_SECS: u64 = 30;
+
+/// User that the prompts of config schedules run as
+pub const SCHEDULER_USER: &str = "scheduler";
+
+/// Parse a cron expression; the 5-field form runs at second 0
+pub fn parse_cron(expr: &str) -> Result<cron::Schedule> {
+    let expr = expr.trim();
+    let normalized = match expr.split_whitespace().count() {
+        5 => format!("0 {}", expr),
+        _ => expr.to_string(),
+    };
+    cron::Schedule::from_str(&normalized)
+        .map_err(|e| anyhow!("Invalid cron expression '{}': {}", expr, e))
+}
+
+pub fn parse_timezone(name: &str) -> Result<chrono_tz::Tz> {
+    name.parse::<chrono_tz::Tz>()
+        .map_err(|e| anyhow!("Unknown timezone '{}': {}", name, e))
+}
+
+/// Check a schedule's cron expression and timezone
+pub fn validate(cron: &str, timezone: Option<&str>) -> Result<()> {
+    parse_cron(cron)?;
+    timezone.map(parse_timezone).transpose()?;
+    Ok(())
+}
+
+/// First time after `after` matching the expression in the timezone
+pub fn next_run(
+    cron: &cron::Schedule,
+    timezone: chrono_tz::Tz,
+    after: DateTime<Utc>,
+) -> Option<DateTime<Utc>> {
+    cron.after(&after.with_timezone(&timezone))
+        .next()
+        .map(|time| time.with_timezone(&Utc))
+}
+
+/// Enabled schedules with a run in `(since, now]`. Schedules without a
+/// timezone use `default_timezone`, then UTC; invalid ones are skipped.
+pub fn due_schedules(
+    schedules: Vec<Schedule>,
+    default_timezone: Option<&str>,
+    since: DateTime<Utc>,
+    now: DateTime<Utc>,
+) -> Vec<Schedule> {
+    schedules
+        .into_iter()
+        .filter(|schedule| schedule.enabled)
+        .filter(|schedule| {
+            let timezone = schedule.timezone.as_deref().or(default_timezone);
+            let parsed = parse_cron(&schedule.cron).and_then(|cron| {
+                let timezone = timezone
+                    .map(parse_timezone)
+                    .transpose()?
+                    .unwrap_or(chrono_tz::UTC);
+                Ok((cron, timezone))
+            });
+            match parsed {
+                Ok((cron, timezone)) => {
+                    next_run(&cron, timezone, since).is_some_and(|next| next <= now)
+                }
+                Err(e) => {
+                    tracing::warn!("Skipping schedule {}: {}", schedule.id, e);
+                    false
+                }
+            }
+        })
+        .collect()
+}
+
+/// Fires due schedules through the Router
+pub struct Scheduler<S: Storage> {
+    router: Router<S>,
+}
+
+impl<S: Storage + 'static> Scheduler<S> {
+    pub fn new(router: Router<S>) -> Self {
+        Self { router }
+    }
+
+    /// Check for due schedules every few seconds, firing each in its own task
+    pub async fn run(self) {
+        let scheduler = std::sync::Arc::new(self);
+        let mut interval = tokio::time::interval(Duration::from_secs(TICK_SECS));
+        interval.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+        let mut last_tick = Utc::now();
+
+        loop {
+            interval.tick().await;
+            let now = Utc::now();
+            for schedule in scheduler.due(last_tick, now).await {
+                let scheduler = scheduler.clone();
+                tokio::spawn(async move {
+                    if let Err(e) = scheduler.fire(&schedule, now).await {
+                        tracing::error!("Schedule {} failed: {}", schedule.id, e);
+                    }
+                });
+            }
+            last_tick = now;
+        }
+    }
+
+    /// Config and stored schedules with a run in `(since, now]`
+    pub async fn due(&self, since: DateTime<Utc>, now: DateTime<Utc>) -> Vec<Schedule> {
+        let (mut schedules, default_timezone) = {
+            let config = self.router.config();
+            let config = config.read().await;
+            let schedules: Vec<Schedule> = config
+                .schedules
+                .iter()
+                .enumerate()
+                .map(|(i, entry)| Schedule {
+                    id: format!("config-{}", i),
+                    user_id: SCHEDULER_USER.to_string(),
+                    cron: entry.cron.clone(),
+                    prompt: entry.prompt.clone(),
+                    channel: entry.channel.clone(),
+                    target: entry.target.clone(),
+                    timezone: entry.timezone.clone(),
+                    enabled: true,
+                    created_at: now,
+                    last_run_at: None,
+                })
+                .collect();
+            (schedules, config.locale.timezone.clone())
+        };
+
+        match self.router.get_storage().list_schedules(None).await {
+            Ok(stored) => schedules.extend(stored),
+            Err(e) => tracing::error!("Failed to load schedules: {}", e),
+        }
+
+        due_schedules(schedules, default_timezone.as_deref(), since, now)
+    }
+
+    /// Run a schedule's prompt and deliver the reply to its target
+    pub async fn fire(&self, schedule: &Schedule, now: DateTime<Utc>) -> Result<()> {
+        tracing::info!(
+            "Running schedule {} for {} on {}",
+            schedule.id,
+            schedule.user_id,
+            schedule.channel
+        );
+        if !schedule.id.starts_with("config-") {
+            self.router
+                .get_storage()
+                .set_schedule_last_run(&schedule.id, now)
+                .await?;
+        }
+
+        let response = self
+            .router
+            .handle_ephemeral_message(&schedule.user_id, &schedule.channel, &schedule.prompt)
+            .await?;
+        let Some(content) = self
+            .router
+            .outbound_message(&schedule.user_id, &schedule.channel, response.content)
+            .await
+            .filter(|content| !content.is_empty())
+        else {
+            return Ok(());
+        };
+
+        let config = self.router.config().read().await.clone();
+        crate::channels::deliver(&config, &schedule.channel, &schedule.target, &content).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    fn schedule(id: &str, cron: &str, timezone: Option<&str>) -> Schedule {
+        Schedule {
+            id: id.to_string(),
+            user_id: "u1".to_string(),
+            cron: cron.to_string(),
+            prompt: "Daily summary".to_string(),
+            channel: "telegram".to_string(),
+            target: "42".to_string(),
+            timezone: timezone.map(str::to_string),
+            enabled: true,
+            created_at: Utc::now(),
+            last_run_at: None,
+        }
+    }
+
+    /// Ticks a fake clock through `[start, end)` in `step` increments,
+    /// collecting the IDs of the schedules fired at each tick
+    fn fire_between(
+        schedules: &[Schedule],
+        default_timezone: Option<&str>,
+        start: DateTime<Utc>,
+        end: DateTime<Utc>,
+        step: chrono::Duration,
+    ) -> Vec<(DateTime<Utc>, String)> {
+        let mut fired = Vec::new();
+        let mut last_tick = start;
+        while last_tick < end {
+            let now = last_tick + step;
+            for due in due_schedules(schedules.to_vec(), default_timezone, last_tick, now) {
+                fired.push((now, due.id));
+            }
+            last_tick = now;
+        }
+        fired
+    }
+
+    #[test]
+    fn test_schedule_fires_once_per_run_in_its_timezone() {
+        let schedules = [
+            schedule("ny", "0 9 * * *", Some("America/New_York")),
+            schedule("default", "30 8 * * MON-FRI", None),
+            schedule("off", "* * * * *", None),
+            schedule("broken", "every day", None),
+        ];
+        let schedules = schedules.map(|mut s| {
+            s.enabled = s.id != "off";
+            s
+        });
+
+        // Friday 2025-01-03 through Monday 2025-01-06 (UTC), ticking every
+        // minute
+        let start = Utc.with_ymd_and_hms(2025, 1, 3, 0, 0, 0).unwrap();
+        let fired = fire_between(
+            &schedules,
+            Some("Asia/Tokyo"),
+            start,
+            start + chrono::Duration::days(4),
+            chrono::Duration::minutes(1),
+        );
+
+        // 09:00 EST is 14:00 UTC. 08:30 JST is 23:30 UTC the day before, so
+        // the weekday schedule skips the Tokyo weekend (Jan 4 and 5)
+        let at = |day, hour, min| Utc.with_ymd_and_hms(2025, 1, day, hour, min, 0).unwrap();
+        assert_eq!(
+            fired,
+            vec![
+                (at(3, 14, 0), "ny".to_string()),
+                (at(4, 14, 0), "ny".to_string()),
+                (at(5, 14, 0), "ny".to_string()),
+                (at(5, 23, 30), "default".to_string()),
+                (at(6, 14, 0), "ny".to_string()),
+                (at(6, 23, 30), "default".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_daylight_saving_shifts_utc_run_time() {
+        let schedules = [schedule("ny", "0 9 * * *", Some("America/New_York"))];
+        let start = Utc.with_ymd_and_hms(2025, 7, 1, 0, 0, 0).unwrap();
+        let fired = fire_between(
+            &schedules,
+            None,
+            start,
+            start + chrono::Duration::days(1),
+            chrono::Duration::minutes(1),
+        );
+
+        // 09:00 EDT is 13:00 UTC
+        assert_eq!(
+            fired,
+            vec![(
+                Utc.with_ymd_and_hms(2025, 7, 1, 13, 0, 0).unwrap(),
+                "ny".to_string()
+            )]
+        );
+    }
+
+    #[test]
+    fn test_validate() {
+        assert!(validate("0 9 * * *", Some("Europe/Berlin")).is_ok());
+        assert!(validate("*/10 0 9 * * MON", None).is_ok());
+        assert!(validate("0 25 * * *", None).is_err());
+        assert!(validate("0 9 * * *", Some("Mars/Olympus")).is_err());
+    }
+}
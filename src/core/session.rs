@@ -1,12 +1,25 @@
 use crate::config::workspace::Workspace;
-use crate::config::Config;
-use crate::core::prompt::SystemPromptBuilder;
-use crate::llm::{ChatMessage, ChatRequest, Client as LlmClient, ToolDefinition};
-use crate::storage::{Message as StorageMessage, Session as StorageSession, Storage};
+use crate::config::{Config, ToolResultsConfig};
+use crate::core::overrides::SessionOverrides;
+use crate::core::prompt::{estimate_tokens, SystemPromptBuilder};
+use crate::core::redaction::Redactor;
+use crate::core::reply_filters::ReplyFilters;
+use crate::llm::{
+    ChatMessage, ChatRequest, Client as LlmClient, TokenUsage, Tokenizer, ToolChoice,
+    ToolDefinition,
+};
+use crate::storage::{
+    Message as StorageMessage, MessageDraft, Session as StorageSession, SessionNote,
+    SessionSummary, Storage,
+};
 use anyhow::{Context, Result};
 use chrono::Utc;
+use once_cell::sync::Lazy;
+use regex::Regex;
+use std::collections::{HashMap, HashSet};
 use std::sync::Arc;
 use tokio::sync::{mpsc, RwLock};
+use tracing::Instrument;
 use uuid::Uuid;
 
 /// Stream events sent from process_message_stream
@@ -14,6 +27,13 @@ use uuid::Uuid;
 pub enum StreamEvent {
     /// Content token(s) from LLM
     Delta(String),
+    /// Tool-call arguments streamed so far; `partial_args` is the raw,
+    /// possibly incomplete JSON accumulated for the call at `index`
+    ToolArgsDelta {
+        index: usize,
+        name: String,
+        partial_args: String,
+    },
     /// About to execute a tool
     ToolStart {
         name: String,
@@ -30,6 +50,9 @@ pub enum StreamEvent {
         execution_time_ms: Option<u64>,
         #[serde(default)]
         attempt: Option<usize>,
+        /// Execution status: "done", "error" or "timeout"
+        #[serde(default)]
+        status: Option<String>,
     },
     /// Tool approval request sent to client
     ApprovalRequested {
@@ -39,15 +62,54 @@ pub enum StreamEvent {
         policy: String,
         sandbox_available: bool,
     },
+    /// The model asked the user a question with `ask_user`; the turn goes
+    /// on once it is answered (see `crate::core::user_input`)
+    UserInputRequested { prompt: String, request_id: String },
     /// Streaming finished
     Done {
         model: String,
         usage: Option<crate::llm::TokenUsage>,
+        /// Completion tokens per second spent waiting on the model; `None`
+        /// when the backend reports no usage
+        #[serde(default)]
+        tps: Option<f64>,
+        /// Time from the start of the turn to the first streamed text
+        #[serde(default)]
+        time_to_first_token_ms: Option<u64>,
+        /// Where the tokens of the final request went
+        #[serde(default)]
+        budget: Option<ContextBudget>,
+        /// The whole reply, when reply filters changed what was streamed;
+        /// shown in place of the deltas
+        #[serde(default)]
+        content: Option<String>,
+    },
+    /// A transient failure before any output; the turn is retried after
+    /// `delay_ms`
+    Retrying {
+        attempt: u32,
+        max_attempts: u32,
+        delay_ms: u64,
+        reason: String,
     },
     /// Error occurred
     Error(String),
 }
 
+/// Scope of sessions created by forking another session
+pub const FORK_SCOPE: &str = "fork";
+
+/// Caller choices for a single turn
+#[derive(Debug, Clone, Default)]
+pub struct TurnOptions {
+    /// Sampling seed
+    pub seed: Option<i64>,
+    /// Start of the reply, continued by the model
+    pub prefill: Option<String>,
+    /// Tool calls allowed in the turn's first model call
+    pub tool_choice: ToolChoice,
+}
+
 /// Session manager with LLM integration
 #[derive(Clone)]
 pub struct SessionManager<S: Storage> {
@@ -56,6 +118,14 @@ pub struct SessionManager<S: Storage> {
     llm_client: LlmClient,
     workspace: Workspace,
     approval_manager: Arc<crate::core::ApprovalManager>,
+    /// Tool tags chosen per session, overriding `tools.default_tags`
+    session_tags: Arc<RwLock<std::collections::HashMap<String, Vec<String>>>>,
+    /// Reply settings changed from the chat (`/temp`, `/persona`, `/lang`)
+    /// per session
+    session_overrides: Arc<RwLock<std::collections::HashMap<String, SessionOverrides>>>,
+    tool_selector: Arc<crate::core::tool_selector::ToolSelector>,
+    /// Refuses tool calls while the gateway is in maintenance mode
+    maintenance: crate::core::maintenance::Maintenance,
 }
 
 #[derive(Clone)]
@@ -71,6 +141,109 @@ pub struct MessageResponse {
     pub content: String,
     pub model: String,
     pub tokens: Option<usize>,
+    /// Model that was originally chosen when a fallback model answered
+    pub fallback_from: Option<String>,
+    /// Backend configuration fingerprint reported with the reply
+    pub system_fingerprint: Option<String>,
+    /// Where the tokens of the final request went
+    pub budget: Option<ContextBudget>,
+}
+
+/// Tokens of a turn's final request by part, counted with the model's
+/// tokenizer, and how much of the context window they leave
+#[derive(Debug, Clone, PartialEq, serde::Serialize, serde::Deserialize)]
+pub struct ContextBudget {
+    pub system_prompt: usize,
+    /// Conversation so far, including this turn's message and tool results
+    pub history: usize,
+    /// Tool definitions
+    pub tools: usize,
+    pub response: usize,
+    /// Sum of the parts above
+    pub total: usize,
+    /// `None` when the model's context window is unknown
+    pub context_window: Option<usize>,
+    /// Tokens left in the context window after the response
+    pub remaining: Option<usize>,
+}
+
+/// A request that would not fit in the model's context window
+#[derive(Debug, thiserror::Error)]
+#[error(
+    "Context window exceeded (~{estimated} tokens, limit {limit}): enable compaction or /reset the session"
+)]
+pub struct ContextWindowExceeded {
+    pub estimated: usize,
+    pub limit: usize,
+}
+
+/// A tool choice naming a tool the turn cannot call
+#[derive(Debug, thiserror::Error)]
+#[error("Tool '{0}' is not available for this message")]
+pub struct ToolNotAvailable(pub String);
+
+/// A message of a session's next-turn context, with its estimated size
+#[derive(Debug, Clone, serde::Serialize)]
+pub struct ContextMessage {
+    pub role: String,
+    pub content: String,
+    pub tokens: usize,
+}
+
+/// A tool sent with a session's next-turn context, with its estimated size
+#[derive(Debug, Clone, serde::Serialize)]
+pub struct ContextTool {
+    #[serde(flatten)]
+    pub definition: ToolDefinition,
+    pub tokens: usize,
+}
+
+/// The context the model would get for a session's next turn
+#[derive(Debug, Clone, serde::Serialize)]
+pub struct ContextPreview {
+    pub model: String,
+    /// System prompt, extra context and windowed history, in request order
+    pub messages: Vec<ContextMessage>,
+    pub tools: Vec<ContextTool>,
+    pub total_tokens: usize,
+    pub context_window: Option<usize>,
+    /// Whether the context fits the context window
+    pub fits: bool,
+    /// Whether the session would be compacted before the next request
+    pub compaction_pending: bool,
+}
+
+/// Reply sent instead of an empty answer unless `prompt.empty_reply` is set
+const DEFAULT_EMPTY_REPLY: &str = "I wasn't able to generate a response, please try again.";
+
+/// Conversation loaded for an LLM request
+struct PreparedContext {
+    messages: Vec<ChatMessage>,
+    model: String,
+    context_window: Option<usize>,
+    /// Counts the tokens of `model`
+    tokenizer: Arc<dyn Tokenizer>,
+    /// Redaction applied to messages stored while answering
+    redactor: Option<Arc<Redactor>>,
+    /// Transforms of the final reply (`prompt.reply_filters`)
+    reply_filters: Option<Arc<ReplyFilters>>,
+    /// Retries of streaming model calls failing transiently
+    retry: crate::config::RetryConfig,
+    /// Saving of the streamed reply while it is generated
+    drafts: crate::config::DraftsConfig,
+    /// Sampling temperature chosen for the session
+    temperature: Option<f32>,
+    /// Sampling seed requested for this turn
+    seed: Option<i64>,
+    /// Start of the reply requested for this turn
+    prefill: Option<String>,
+    /// Tool calls allowed in the turn's first model call; later calls are
+    /// left to the model
+    tool_choice: ToolChoice,
+    /// Sent and stored instead of an empty answer
+    empty_reply: String,
+    /// Times the model is asked to resend a call with unrepairable arguments
+    argument_resends: usize,
 }
 
 impl<S: Storage + 'static> SessionManager<S> {
@@ -86,6 +259,10 @@ impl<S: Storage + 'static> SessionManager<S> {
             llm_client,
             workspace,
             approval_manager: Arc::new(crate::core::ApprovalManager::new()),
+            session_tags: Default::default(),
+            session_overrides: Default::default(),
+            tool_selector: Default::default(),
+            maintenance: Default::default(),
         }
     }
 
@@ -103,6 +280,10 @@ impl<S: Storage + 'static> SessionManager<S> {
             llm_client,
             workspace,
             approval_manager,
+            session_tags: Default::default(),
+            session_overrides: Default::default(),
+            tool_selector: Default::default(),
+            maintenance: Default::default(),
         }
     }
 
@@ -114,6 +295,14 @@ impl<S: Storage + 'static> SessionManager<S> {
         &self.storage
     }
 
+    pub fn llm_client(&self) -> &LlmClient {
+        &self.llm_client
+    }
+
+    pub fn maintenance(&self) -> &crate::core::maintenance::Maintenance {
+        &self.maintenance
+    }
+
     /// Resolve workspace based on agent ID
     async fn resolve_workspace(
         &self,
@@ -222,6 +411,8 @@ impl<S: Storage + 'static> SessionManager<S> {
             });
         }
 
+        self.make_room_for_new_session(user_id).await?;
+
         // Create new session
         let session_id = Uuid::new_v4().to_string();
         let now = Utc::now();
@@ -244,6 +435,91 @@ impl<S: Storage + 'static> SessionManager<S> {
         })
     }
 
+    /// Create a one-off session that no lookup will ever return; callers
+    /// remove it with `delete_session` once done
+    pub async fn create_ephemeral_session(
+        &self,
+        user_id: &str,
+        agent_id: Option<&str>,
+    ) -> Result<Session> {
+        let session_id = Uuid::new_v4().to_string();
+        let channel = match agent_id {
+            Some(agent) => format!("{}:ephemeral:{}", agent, session_id),
+            None => format!("ephemeral:{}", session_id),
+        };
+        let now = Utc::now();
+
+        self.storage
+            .create_session(StorageSession {
+                id: session_id.clone(),
+                user_id: user_id.to_string(),
+                channel: channel.clone(),
+                scope: "ephemeral".to_string(),
+                created_at: now,
+                updated_at: now,
+            })
+            .await?;
+
+        Ok(Session {
+            id: session_id,
+            user_id: user_id.to_string(),
+            channel,
+        })
+    }
+
+    /// Fork a session into a new one holding its history up to and
+    /// including `message_id`, plus its pinned notes. Returns `None` when the
+    /// message is not part of the session.
+    pub async fn fork_session(
+        &self,
+        parent: &StorageSession,
+        message_id: &str,
+    ) -> Result<Option<StorageSession>> {
+        let now = Utc::now();
+        let fork = StorageSession {
+            id: Uuid::new_v4().to_string(),
+            user_id: parent.user_id.clone(),
+            channel: parent.channel.clone(),
+            // A distinct scope keeps forks out of the channel's session lookup
+            scope: FORK_SCOPE.to_string(),
+            created_at: now,
+            updated_at: now,
+        };
+        self.storage.create_session(fork.clone()).await?;
+
+        match self
+            .storage
+            .copy_messages_until(&parent.id, &fork.id, message_id)
+            .await
+        {
+            Ok(Some(_)) => {
+                for note in self.storage.list_session_notes(&parent.id).await? {
+                    self.storage
+                        .add_session_note(SessionNote {
+                            id: Uuid::new_v4().to_string(),
+                            session_id: fork.id.clone(),
+                            ..note
+                        })
+                        .await?;
+                }
+                Ok(Some(fork))
+            }
+            Ok(None) => {
+                self.storage.delete_session(&fork.id).await?;
+                Ok(None)
+            }
+            Err(e) => {
+                self.storage.delete_session(&fork.id).await.ok();
+                Err(e)
+            }
+        }
+    }
+
+    /// Delete a session and its history
+    pub async fn delete_session(&self, session_id: &str) -> Result<()> {
+        self.storage.delete_session(session_id).await
+    }
+
     /// Process a user message and return the assistant's response
     /// This is the main method for handling conversations
     /// Handles tool calling with automatic feedback loops
@@ -255,24 +531,204 @@ impl<S: Storage + 'static> SessionManager<S> {
         session_id: &str,
         user_message: &str,
         agent_id: Option<&str>,
+    ) -> Result<MessageResponse> {
+        self.process_message_with_context(
+            session_id,
+            user_message,
+            agent_id,
+            &[],
+            &TurnOptions::default(),
+        )
+        .await
+    }
+
+    /// Process a user message with caller-supplied context documents and
+    /// turn options.
+    ///
+    /// The documents are sent as an extra system message for this turn only;
+    /// they are never stored in the session history.
+    pub async fn process_message_with_context(
+        &self,
+        session_id: &str,
+        user_message: &str,
+        agent_id: Option<&str>,
+        extra_context: &[String],
+        options: &TurnOptions,
     ) -> Result<MessageResponse> {
         // Add user message to storage
         self.add_message(session_id, "user", user_message, None, None)
             .await?;
 
+        self.reply_with_context(session_id, user_message, agent_id, extra_context, options)
+            .await
+            .map_err(FailedAfterTools::unwrap)
+    }
+
+    /// Answer a user message already stored in the session. Retrying a turn
+    /// calls this again without storing the message twice; errors after the
+    /// turn's tools ran come wrapped in [`FailedAfterTools`].
+    pub async fn reply_with_context(
+        &self,
+        session_id: &str,
+        user_message: &str,
+        agent_id: Option<&str>,
+        extra_context: &[String],
+        options: &TurnOptions,
+    ) -> Result<MessageResponse> {
         // Check for compaction
         if let Err(e) = self.compact_session(session_id).await {
             tracing::warn!("Session compaction failed: {}", e);
         }
 
-        // Get tools available
-        let tools = self.get_available_tools().await;
+        // Get tools available; models without tool calling are sent none
+        let mut tools = self
+            .tools_for_choice(session_id, user_message, &options.tool_choice)
+            .await;
+        let tool_calling = self.tool_calling_for(user_message).await;
 
-        // Resolve workspace for agent
-        let workspace = self.resolve_workspace(agent_id).await;
+        // Build system prompt for the session's user and agent workspace
+        let system_prompt = self
+            .build_system_prompt(
+                session_id,
+                agent_id,
+                self.prompt_tools(&tools, tool_calling).await,
+                tool_calling,
+            )
+            .await;
+        if !tool_calling {
+            tools.clear();
+        }
+        ensure_tool_choice(&tools, &options.tool_choice)?;
+
+        let mut context = self
+            .prepare_context(
+                session_id,
+                user_message,
+                &system_prompt,
+                extra_context,
+                &tools,
+            )
+            .await?;
+        context.seed = options.seed;
+        context.prefill = options.prefill.clone();
+        context.tool_choice = options.tool_choice.clone();
 
         // Process message through LLM with tool calling
-        self.process_with_tools(session_id, tools, workspace).await
+        self.process_with_tools(session_id, tools, context).await
+    }
+
+    /// Tools for a turn: none when the caller chose `none`, and always the
+    /// tool the caller named even if selection left it out
+    async fn tools_for_choice(
+        &self,
+        session_id: &str,
+        user_message: &str,
+        tool_choice: &ToolChoice,
+    ) -> Vec<ToolDefinition> {
+        let name = match tool_choice {
+            ToolChoice::None => return Vec::new(),
+            ToolChoice::Auto => None,
+            ToolChoice::Tool(name) => Some(name),
+        };
+        let mut tools = self.get_tools_for_message(session_id, user_message).await;
+        if let Some(name) = name.filter(|name| !tools.iter().any(|tool| &tool.name == *name)) {
+            let named = self.get_available_tools().await.into_iter();
+            tools.extend(named.filter(|tool| &tool.name == name));
+        }
+        tools
+    }
+
+    /// Whether the model that will answer `user_message` can be sent tools
+    pub async fn tool_calling_for(&self, user_message: &str) -> bool {
+        let model = self.llm_client.route_model(user_message).to_string();
+        self.llm_client.supports_tools(&model).await
+    }
+
+    /// Tools to list in the system prompt. Models without tool calling only
+    /// get them described when `prompt.describe_tools_without_calling` is set.
+    pub async fn prompt_tools(
+        &self,
+        tools: &[ToolDefinition],
+        tool_calling: bool,
+    ) -> Vec<ToolDefinition> {
+        if tool_calling
+            || self
+                .config
+                .read()
+                .await
+                .prompt
+                .describe_tools_without_calling
+        {
+            tools.to_vec()
+        } else {
+            Vec::new()
+        }
+    }
+
+    /// Build the system prompt for a session (base persona + workspace
+    /// context + the session's pinned notes)
+    pub async fn build_system_prompt(
+        &self,
+        session_id: &str,
+        agent_id: Option<&str>,
+        tools: Vec<ToolDefinition>,
+        tool_calling: bool,
+    ) -> String {
+        let (user_id, channel) = match self.storage.get_session(session_id).await {
+            Ok(Some(session)) => (session.user_id, session.channel),
+            _ => ("user".to_string(), String::new()),
+        };
+        let notes = match self.storage.list_session_notes(session_id).await {
+            Ok(notes) => notes.into_iter().map(|note| note.content).collect(),
+            Err(e) => {
+                tracing::warn!("Failed to load notes of session {}: {}", session_id, e);
+                Vec::new()
+            }
+        };
+        self.system_prompt(&user_id, &channel, agent_id, tools, tool_calling, notes)
+            .await
+    }
+
+    /// Assemble the system prompt exactly as it would be sent for a user
+    /// on a channel
+    pub async fn preview_system_prompt(
+        &self,
+        user_id: &str,
+        channel: &str,
+        agent_id: Option<&str>,
+        tools: Vec<ToolDefinition>,
+        tool_calling: bool,
+    ) -> String {
+        self.system_prompt(user_id, channel, agent_id, tools, tool_calling, Vec::new())
+            .await
+    }
+
+    async fn system_prompt(
+        &self,
+        user_id: &str,
+        channel: &str,
+        agent_id: Option<&str>,
+        tools: Vec<ToolDefinition>,
+        tool_calling: bool,
+        notes: Vec<String>,
+    ) -> String {
+        // Read on every call so reloaded personas apply right away
+        let (base_prompt, timezone) = {
+            let config = self.config.read().await;
+            (
+                crate::core::prompt::resolve_base_prompt(&config, agent_id, channel),
+                config.locale.timezone_for(Some(user_id)),
+            )
+        };
+        let workspace = self.resolve_workspace(agent_id).await;
+
+        SystemPromptBuilder::new(workspace, tools)
+            .with_base_prompt(base_prompt)
+            .with_user(user_id)
+            .with_timezone(timezone)
+            .with_tool_calling(tool_calling)
+            .with_notes(notes)
+            .build()
     }
 
     /// Process a user message with streaming (returns receiver for StreamEvent)
@@ -293,6 +749,25 @@ impl<S: Storage + 'static> SessionManager<S> {
         session_id: &str,
         user_message: &str,
         agent_id: Option<&str>,
+    ) -> Result<mpsc::Receiver<StreamEvent>> {
+        self.process_message_stream_with_context(
+            session_id,
+            user_message,
+            agent_id,
+            &[],
+            &TurnOptions::default(),
+        )
+        .await
+    }
+
+    /// Streaming variant of `process_message_with_context`
+    pub async fn process_message_stream_with_context(
+        &self,
+        session_id: &str,
+        user_message: &str,
+        agent_id: Option<&str>,
+        extra_context: &[String],
+        options: &TurnOptions,
     ) -> Result<mpsc::Receiver<StreamEvent>> {
         // Add user message to storage
         self.add_message(session_id, "user", user_message, None, None)
@@ -303,69 +778,137 @@ impl<S: Storage + 'static> SessionManager<S> {
             tracing::warn!("Session compaction failed: {}", e);
         }
 
-        // Get tools available
-        let tools = self.get_available_tools().await;
+        // Get tools available; models without tool calling are sent none
+        let mut tools = self
+            .tools_for_choice(session_id, user_message, &options.tool_choice)
+            .await;
+        let tool_calling = self.tool_calling_for(user_message).await;
 
         // Create channel for streaming events
         let (tx, rx) = mpsc::channel::<StreamEvent>(32);
 
-        // Clone what we need for the spawned task
-        let storage = self.storage.clone();
-        let llm_client = self.llm_client.clone();
-        let session_id = session_id.to_string();
-        let workspace = self.resolve_workspace(agent_id).await;
-        let approval_manager = self.approval_manager.clone();
-
-        // Spawn streaming task
-        tokio::spawn(async move {
-            if let Err(e) = process_message_stream_task(
-                storage,
-                llm_client,
+        let system_prompt = self
+            .build_system_prompt(
+                session_id,
+                agent_id,
+                self.prompt_tools(&tools, tool_calling).await,
+                tool_calling,
+            )
+            .await;
+        if !tool_calling {
+            tools.clear();
+        }
+        ensure_tool_choice(&tools, &options.tool_choice)?;
+        let mut context = match self
+            .prepare_context(
                 session_id,
-                tools,
-                tx,
-                workspace,
-                approval_manager,
+                user_message,
+                &system_prompt,
+                extra_context,
+                &tools,
             )
             .await
-            {
-                tracing::error!("Error in streaming task: {}", e);
+        {
+            Ok(context) => context,
+            Err(e) => match e.downcast_ref::<ContextWindowExceeded>() {
+                Some(overflow) => {
+                    let _ = tx.send(StreamEvent::Error(overflow.to_string())).await;
+                    return Ok(rx);
+                }
+                None => return Err(e),
+            },
+        };
+        context.seed = options.seed;
+        context.prefill = options.prefill.clone();
+        context.tool_choice = options.tool_choice.clone();
+        let session_id = session_id.to_string();
+        let manager = self.clone();
+
+        // Spawn streaming task, still inside the caller's request span.
+        // Dropping the receiver cancels it right away, along with the model
+        // call or tool it is waiting on.
+        let receiver_dropped = tx.clone();
+        tokio::spawn(
+            async move {
+                tokio::select! {
+                    result = process_message_stream_task(
+                        manager,
+                        session_id.clone(),
+                        tools,
+                        tx,
+                        context,
+                    ) => {
+                        if let Err(e) = result {
+                            tracing::error!("Error in streaming task: {}", e);
+                        }
+                    }
+                    _ = receiver_dropped.closed() => {
+                        tracing::info!(
+                            "Stream for session {} abandoned, cancelling the reply",
+                            session_id
+                        );
+                    }
+                }
             }
-        });
+            .in_current_span(),
+        );
 
         Ok(rx)
     }
 
     /// Process message with tool support
     /// Handles tool calling loops until the model generates final response
-    /// Process message with tool support
-    /// Handles tool calling loops until the model generates final response
+    ///
+    /// Errors after the first tool call ran come wrapped in
+    /// [`FailedAfterTools`], so the turn isn't retried and the tools run twice
     async fn process_with_tools(
         &self,
         session_id: &str,
         tools: Vec<ToolDefinition>,
-        workspace: Workspace,
+        context: PreparedContext,
     ) -> Result<MessageResponse> {
-        // Get conversation history
-        let history = self
-            .storage
-            .get_messages(session_id, Some(50))
+        let mut tools_ran = false;
+        self.run_tool_loop(session_id, tools, context, &mut tools_ran)
             .await
-            .context("Failed to get message history")?;
-
-        // Convert storage messages to LLM messages
-        // Build dynamic system prompt
-        let system_prompt = SystemPromptBuilder::new(workspace.clone(), tools.clone()).build();
-
-        let mut llm_messages: Vec<ChatMessage> = vec![ChatMessage {
-            role: "system".to_string(),
-            content: system_prompt,
-        }];
+            .map_err(|err| match tools_ran {
+                true => FailedAfterTools(err).into(),
+                false => err,
+            })
+    }
 
-        llm_messages.extend(history.iter().map(|msg| ChatMessage {
-            role: msg.role.clone(),
-            content: msg.content.clone(),
-        }));
+    /// The tool calling loop of [`Self::process_with_tools`]; sets `tools_ran`
+    /// before executing the first tool call
+    async fn run_tool_loop(
+        &self,
+        session_id: &str,
+        tools: Vec<ToolDefinition>,
+        context: PreparedContext,
+        tools_ran: &mut bool,
+    ) -> Result<MessageResponse> {
+        let PreparedContext {
+            messages: mut llm_messages,
+            model,
+            context_window,
+            tokenizer,
+            temperature,
+            seed,
+            mut prefill,
+            mut tool_choice,
+            empty_reply,
+            mut argument_resends,
+            redactor,
+            reply_filters,
+            ..
+        } = context;
+
+        // The session owner's tool policy overrides apply to its calls
+        let user_id = self
+            .storage
+            .get_session(session_id)
+            .await
+            .ok()
+            .flatten()
+            .map(|session| session.user_id);
 
         tracing::info!(
             "Processing message for session {}: {} messages in context, {} tools available",
@@ -374,34 +917,33 @@ impl<S: Storage + 'static> SessionManager<S> {
             tools.len()
         );
 
-        // Determine model to use (auto-route based on last user message)
-        let model = if let Some(last_user_msg) = llm_messages
-            .iter()
-            .rev()
-            .find(|m| m.role == "user")
-            .map(|m| m.content.as_str())
-        {
-            self.llm_client.route_model(last_user_msg).to_string()
-        } else {
-            self.llm_client.primary_model().to_string()
-        };
+        // Whether an earlier call of the turn already said something
+        let mut produced_content = false;
 
         // Tool calling loop - continue until no more tool calls
         loop {
+            // Tool results grow the context, so re-check before every call
+            check_context_window(&*tokenizer, context_window, &llm_messages, &tools)?;
+
             // Send request to LLM
             let request = ChatRequest {
                 model: model.clone(),
                 messages: llm_messages.clone(),
                 max_tokens: None,
-                temperature: None,
+                temperature,
                 tools: if tools.is_empty() {
                     None
                 } else {
                     Some(tools.clone())
                 },
+                seed,
+                // Only the first call of the turn starts the reply or is
+                // held to the tool choice
+                prefill: prefill.take(),
+                tool_choice: std::mem::take(&mut tool_choice),
             };
 
-            let response = self
+            let mut response = self
                 .llm_client
                 .chat(request)
                 .await
@@ -410,6 +952,7 @@ impl<S: Storage + 'static> SessionManager<S> {
             // Check if we have tool calls to process
             if let Some(tool_calls) = response.tool_calls {
                 tracing::info!("LLM generated {} tool calls", tool_calls.len());
+                produced_content |= !response.content.trim().is_empty();
 
                 // Add assistant response to message history (contains tool_use)
                 llm_messages.push(ChatMessage {
@@ -417,40 +960,110 @@ impl<S: Storage + 'static> SessionManager<S> {
                     content: response.content.clone(),
                 });
 
-                // Execute each tool and collect results
-                for tool_call in tool_calls {
-                    tracing::info!("Executing tool: {}", tool_call.name);
+                // Side-effect-free calls run concurrently; results keep call order
+                *tools_ran = true;
+                let (maintenance, user_id) = (&self.maintenance, user_id.as_deref());
+                let executions = run_tool_calls(
+                    &tool_calls,
+                    |tool_call| tool_call.name.as_str(),
+                    |tool_call| async move {
+                        tracing::info!("Executing tool: {}", tool_call.name);
+
+                        let started = std::time::Instant::now();
+                        let outcome = match maintenance.check() {
+                            Err(e) => Err(e.into()),
+                            Ok(()) => {
+                                crate::tools::executor::execute_tool_with_context(
+                                    &tool_call.name,
+                                    &tool_call.arguments,
+                                    Some(session_id),
+                                    user_id,
+                                    true, // In session manager, this is usually the main session
+                                    None,
+                                )
+                                .await
+                            }
+                        };
+                        (outcome, started.elapsed())
+                    },
+                )
+                .await;
 
-                    let result = match crate::tools::executor::execute_tool_with_context(
-                        &tool_call.name,
-                        &tool_call.arguments,
-                        Some(session_id),
-                        true, // In session manager, this is usually the main session
-                    )
-                    .await
-                    {
+                for (tool_call, (outcome, elapsed)) in tool_calls.iter().zip(executions) {
+                    let (result, success) = match outcome {
                         Ok(result) => {
                             tracing::info!("Tool {} succeeded", tool_call.name);
-                            result
+                            (result, true)
                         }
                         Err(err) => {
                             tracing::error!("Tool {} failed: {}", tool_call.name, err);
-                            format!("Error: {}", err)
+                            (format!("Error: {}", err), false)
                         }
                     };
 
+                    crate::tools::audit::record_execution(
+                        &self.storage,
+                        session_id,
+                        crate::tools::audit::ExecutionRecord {
+                            tool_name: &tool_call.name,
+                            arguments: &tool_call.arguments,
+                            output: &result,
+                            success,
+                            duration_ms: Some(elapsed.as_millis() as u64),
+                        },
+                        redactor.as_deref(),
+                    )
+                    .await;
+
+                    // The audit log keeps the full result, the context what fits
+                    let result = match success {
+                        true => fit_tool_result(
+                            &*tokenizer,
+                            context_window,
+                            &llm_messages,
+                            &tools,
+                            session_id,
+                            &tool_call.name,
+                            result,
+                        ),
+                        false => result,
+                    };
+
                     // Add tool result to message history
+                    let content = match success {
+                        true => None,
+                        false => malformed_arguments_feedback(
+                            &tool_call.name,
+                            &tool_call.arguments,
+                            &mut argument_resends,
+                        ),
+                    }
+                    .unwrap_or_else(|| format!("Tool {} result: {}", tool_call.name, result));
                     llm_messages.push(ChatMessage {
                         role: "user".to_string(),
-                        content: format!("Tool {} result: {}", tool_call.name, result),
+                        content,
                     });
                 }
             } else {
                 // No tool calls - this is the final response
+                if let Some(filters) = &reply_filters {
+                    response.content = filters.apply(&response.content);
+                }
+                if response.content.trim().is_empty() && !produced_content {
+                    tracing::warn!(
+                        "Model {} returned an empty reply in session {}, sending the fallback",
+                        response.model,
+                        session_id
+                    );
+                    response.content = empty_reply;
+                }
+                let usage = response.usage.clone().unwrap_or_else(|| {
+                    estimate_usage(&*tokenizer, &llm_messages, &tools, &response.content)
+                });
                 tracing::info!(
                     "Final response generated: model={}, tokens={}",
                     response.model,
-                    response.usage.as_ref().map(|u| u.total_tokens).unwrap_or(0)
+                    usage.total_tokens
                 );
 
                 // Add final assistant response to storage
@@ -459,14 +1072,24 @@ impl<S: Storage + 'static> SessionManager<S> {
                     "assistant",
                     &response.content,
                     Some(&response.model),
-                    response.usage.as_ref().map(|u| u.total_tokens),
+                    Some(usage.total_tokens),
                 )
                 .await?;
 
+                let budget = context_budget(
+                    &*tokenizer,
+                    context_window,
+                    &llm_messages,
+                    &tools,
+                    &response.content,
+                );
                 return Ok(MessageResponse {
                     content: response.content,
                     model: response.model,
-                    tokens: response.usage.map(|u| u.total_tokens),
+                    tokens: Some(usage.total_tokens),
+                    fallback_from: response.fallback_from,
+                    system_fingerprint: response.system_fingerprint,
+                    budget: Some(budget),
                 });
             }
         }
@@ -474,83 +1097,149 @@ impl<S: Storage + 'static> SessionManager<S> {
 
     /// Get available tools for this session
     pub async fn get_available_tools(&self) -> Vec<ToolDefinition> {
-        let mut tools = Vec::new();
-
-        // Helper function to extract tool definition from JSON
-        let extract_tool = |def: &serde_json::Value| -> Option<ToolDefinition> {
-            let func = def.get("function")?;
-            let name = func.get("name")?.as_str()?;
-            let description = func.get("description")?.as_str()?;
-            let parameters = func.get("parameters")?;
-            Some(ToolDefinition {
-                name: name.to_string(),
-                description: description.to_string(),
-                parameters: parameters.clone(),
-            })
-        };
+        available_tools().await
+    }
 
-        // 1. Add exec tools (always available)
-        let exec_defs = crate::tools::get_exec_tool_definitions();
-        for def in exec_defs {
-            if let Some(tool) = extract_tool(&def) {
-                tools.push(tool);
-            }
-        }
+    /// Welcome and `/help` text for an agent (see `prompt::build_help_text`)
+    pub async fn help_text(&self, agent_id: Option<&str>) -> String {
+        let workspace = self.resolve_workspace(agent_id).await;
+        let tools = self.get_available_tools().await;
+        crate::core::prompt::build_help_text(&workspace, &tools)
+    }
 
-        // Add memory tools
-        tools.extend(crate::tools::get_memory_tool_definitions());
+    /// Tools offered for one turn.
+    ///
+    /// With enabled tags (the session's own, else `tools.default_tags`),
+    /// tagged tools are only offered when one of their tags is enabled or
+    /// mentioned in the user message. Core and untagged tools always are.
+    /// With `tools.selection` enabled, skill and plugin tools are further cut
+    /// down to the ones most relevant to the message, and `tools.max_definitions`
+    /// caps how many are sent at all.
+    pub async fn get_tools_for_message(
+        &self,
+        session_id: &str,
+        user_message: &str,
+    ) -> Vec<ToolDefinition> {
+        let mut tools = self.get_available_tools().await;
 
-        // 1b. Add creator tools (always available)
-        let creator_defs = crate::tools::get_creator_tool_definitions();
-        for def in creator_defs {
-            if let Some(tool) = extract_tool(&def) {
-                tools.push(tool);
-            }
+        let (default_tags, selection, max_definitions) = {
+            let config = self.config.read().await;
+            (
+                config.tools.default_tags.clone(),
+                config.tools.selection.clone(),
+                config.tools.max_definitions,
+            )
+        };
+        let enabled = self
+            .get_session_tags(session_id)
+            .await
+            .unwrap_or(default_tags);
+        if enabled.is_empty() && !selection.enabled && max_definitions.is_none() {
+            return tools;
         }
 
-        // 1c. Add web tools (always available)
-        let web_defs = crate::tools::web::get_web_tool_definitions();
-        for def in web_defs {
-            if let Some(tool) = extract_tool(&def) {
-                tools.push(tool);
-            }
+        let tags = tool_tags().await;
+        if !enabled.is_empty() {
+            tools.retain(|tool| {
+                tags.get(&tool.name)
+                    .is_none_or(|tags| tool_selected(tags, &enabled, user_message))
+            });
         }
 
-        // 2. Add WhatsApp tools if service is available
-        if crate::get_whatsapp_service().is_some() {
-            let whatsapp_defs = crate::tools::whatsapp::get_whatsapp_tool_definitions();
-            tools.extend(whatsapp_defs);
+        let selectable: HashSet<String> = tags.into_keys().collect();
+        if selection.enabled {
+            tools = self
+                .tool_selector
+                .select(
+                    &self.llm_client,
+                    &selection.embedding_model,
+                    tools,
+                    &selectable,
+                    user_message,
+                    selection.top_k,
+                )
+                .await;
         }
 
-        // 3. Add plugin tools from PluginRegistry if available
-        if let Some(registry) = crate::plugins::get_plugin_registry() {
-            if let Ok(tool_names) = registry.tools.list_tools() {
-                for tool_name in tool_names {
-                    if let Ok(Some(tool)) = registry.tools.get_tool(&tool_name) {
-                        tools.push(ToolDefinition {
-                            name: tool.name,
-                            description: tool.description,
-                            parameters: tool.parameters,
-                        });
-                    }
-                }
+        if let Some(max) = max_definitions {
+            let (kept, dropped) =
+                cap_tool_definitions(tools, max, &selectable, &crate::tools::audit::last_used());
+            if !dropped.is_empty() {
+                tracing::info!(
+                    "Sending {} tool definitions (tools.max_definitions = {}), dropped: {}",
+                    kept.len(),
+                    max,
+                    dropped.join(", ")
+                );
             }
+            tools = kept;
         }
 
-        // 4. Add skill tools from SKILL_BODIES if available
-        tools.extend(get_skill_tool_definitions().await);
-
         tools
     }
 
-    /// Add a message to a session
-    pub async fn add_message(
+    /// Choose the tool tags offered in a session (empty offers every tool)
+    pub async fn set_session_tags(&self, session_id: &str, tags: Vec<String>) {
+        self.session_tags
+            .write()
+            .await
+            .insert(session_id.to_string(), tags);
+    }
+
+    /// Tool tags chosen for a session, if any
+    pub async fn get_session_tags(&self, session_id: &str) -> Option<Vec<String>> {
+        self.session_tags.read().await.get(session_id).cloned()
+    }
+
+    /// Change the reply settings of a session
+    pub async fn update_session_overrides(
         &self,
         session_id: &str,
-        role: &str,
-        content: &str,
-        model_used: Option<&str>,
-        tokens: Option<usize>,
+        update: impl FnOnce(&mut SessionOverrides),
+    ) {
+        let mut overrides = self.session_overrides.write().await;
+        update(overrides.entry(session_id.to_string()).or_default());
+    }
+
+    /// Reply settings of a session (empty when none were changed)
+    pub async fn get_session_overrides(&self, session_id: &str) -> SessionOverrides {
+        self.session_overrides
+            .read()
+            .await
+            .get(session_id)
+            .cloned()
+            .unwrap_or_default()
+    }
+
+    /// Go back to the configured reply settings
+    pub async fn clear_session_overrides(&self, session_id: &str) {
+        self.session_overrides.write().await.remove(session_id);
+    }
+
+    /// Reply settings a session's requests are built with: those chosen for
+    /// the session, else its user's settings, else the configuration
+    pub async fn reply_settings(&self, session_id: &str) -> Result<SessionOverrides> {
+        let overrides = self.get_session_overrides(session_id).await;
+        let Some(session) = self.storage.get_session(session_id).await? else {
+            return Ok(overrides);
+        };
+        let settings = self
+            .storage
+            .get_user_settings(&session.user_id)
+            .await
+            .context("Failed to load user settings")?
+            .unwrap_or_default();
+        Ok(overrides.or_user_settings(settings))
+    }
+
+    /// Add a message to a session
+    pub async fn add_message(
+        &self,
+        session_id: &str,
+        role: &str,
+        content: &str,
+        model_used: Option<&str>,
+        tokens: Option<usize>,
     ) -> Result<()> {
         let message = StorageMessage {
             id: Uuid::new_v4().to_string(),
@@ -560,9 +1249,53 @@ impl<S: Storage + 'static> SessionManager<S> {
             created_at: Utc::now(),
             model_used: model_used.map(|s| s.to_string()),
             tokens,
+            metadata: None,
         };
 
-        self.storage.add_message(message).await
+        store_redacted(&self.storage, message, self.redactor().await.as_deref()).await
+    }
+
+    /// Store messages copied from elsewhere (e.g. an imported archive) in a
+    /// session under fresh IDs, masked like any other stored message
+    pub async fn import_messages(
+        &self,
+        session_id: &str,
+        messages: &[StorageMessage],
+    ) -> Result<()> {
+        let redactor = self.redactor().await;
+        for message in messages {
+            let message = StorageMessage {
+                id: Uuid::new_v4().to_string(),
+                session_id: session_id.to_string(),
+                ..message.clone()
+            };
+            store_redacted(&self.storage, message, redactor.as_deref()).await?;
+        }
+        Ok(())
+    }
+
+    /// Redactor for stored messages, if redaction is enabled
+    async fn redactor(&self) -> Option<Arc<Redactor>> {
+        let config = self.config.read().await;
+        match Redactor::from_config(&config.redaction) {
+            Ok(redactor) => redactor.map(Arc::new),
+            Err(e) => {
+                tracing::error!("Redaction disabled: {}", e);
+                None
+            }
+        }
+    }
+
+    /// Filters for final replies, if any are configured
+    async fn reply_filters(&self) -> Option<Arc<ReplyFilters>> {
+        let config = self.config.read().await;
+        match ReplyFilters::from_config(&config.prompt.reply_filters) {
+            Ok(filters) => filters.map(Arc::new),
+            Err(e) => {
+                tracing::error!("Reply filters disabled: {}", e);
+                None
+            }
+        }
     }
 
     /// Get recent messages for a session
@@ -570,9 +1303,123 @@ impl<S: Storage + 'static> SessionManager<S> {
         self.storage.get_messages(session_id, Some(50)).await
     }
 
-    /// Clear all messages in a session (reset conversation)
+    /// Clear all messages in a session (reset conversation) and forget the
+    /// tool approvals remembered for it
     pub async fn clear_session(&self, session_id: &str) -> Result<()> {
-        self.storage.delete_session_messages(session_id).await
+        self.storage.delete_session_messages(session_id).await?;
+
+        // Tools approved "for the rest of the session" need approval again
+        self.approval_manager.forget_session(session_id).await;
+        if let Some(policy) = crate::get_tool_policy_engine() {
+            policy.forget_session_approvals(session_id).await;
+        }
+
+        // Files left behind by the session's tools go with the conversation
+        crate::sandbox::remove_session_workspace(session_id)?;
+        Ok(())
+    }
+
+    /// Drop the in-memory settings and workspace files of a session deleted
+    /// from storage, and tell connected clients it is gone
+    async fn forget_deleted_session(&self, session_id: &str) {
+        self.approval_manager.forget_session(session_id).await;
+        if let Some(policy) = crate::get_tool_policy_engine() {
+            policy.forget_session_approvals(session_id).await;
+        }
+        self.session_tags.write().await.remove(session_id);
+        self.clear_session_overrides(session_id).await;
+        if let Err(e) = crate::sandbox::remove_session_workspace(session_id) {
+            tracing::warn!(
+                "Failed to remove workspace of session {}: {}",
+                session_id,
+                e
+            );
+        }
+        crate::core::events::publish_event(crate::core::events::SystemEvent::SessionExpired(
+            session_id.to_string(),
+        ));
+    }
+
+    /// Delete a user's least recently used sessions until there is room for
+    /// one more under `sessions.max_per_user`, if set. One-off sessions do
+    /// not count. Like idle expiry, eviction spares the shared `global`
+    /// sessions and sessions with notes, though they count towards the limit.
+    pub async fn make_room_for_new_session(&self, user_id: &str) -> Result<()> {
+        let Some(max_per_user) = self.config.read().await.sessions.max_per_user else {
+            return Ok(());
+        };
+        let sessions: Vec<StorageSession> = self
+            .storage
+            .list_user_sessions(user_id)
+            .await?
+            .into_iter()
+            .filter(|session| session.scope != "ephemeral")
+            .collect();
+        let excess = sessions
+            .len()
+            .saturating_sub(max_per_user.saturating_sub(1));
+        let mut evicted = 0;
+        // Least recently used first
+        for session in sessions.iter().rev() {
+            if evicted == excess {
+                break;
+            }
+            let shared = session.channel == "global" || session.channel.ends_with(":global");
+            if shared
+                || !self
+                    .storage
+                    .list_session_notes(&session.id)
+                    .await?
+                    .is_empty()
+            {
+                continue;
+            }
+            tracing::info!(
+                "User {} is at the limit of {} sessions; evicting session {} (last used {})",
+                user_id,
+                max_per_user,
+                session.id,
+                session.updated_at
+            );
+            self.storage.delete_session(&session.id).await?;
+            self.forget_deleted_session(&session.id).await;
+            evicted += 1;
+        }
+        if evicted < excess {
+            tracing::warn!(
+                "User {} is over the limit of {} sessions, but only shared sessions and sessions with notes are left",
+                user_id,
+                max_per_user
+            );
+        }
+        Ok(())
+    }
+
+    /// Delete the sessions idle for longer than `sessions.expiry.ttl_hours`
+    /// as of `now`, with their in-memory settings and workspace files, and
+    /// tell connected clients. Does nothing unless expiry is enabled.
+    pub async fn expire_idle_sessions(
+        &self,
+        now: chrono::DateTime<Utc>,
+    ) -> Result<Vec<StorageSession>> {
+        let expiry = self.config.read().await.sessions.expiry.clone();
+        if !expiry.enabled {
+            return Ok(Vec::new());
+        }
+
+        let idle_before = now - chrono::Duration::hours(expiry.ttl_hours as i64);
+        let expired = self.storage.delete_idle_sessions(idle_before).await?;
+        for session in &expired {
+            self.forget_deleted_session(&session.id).await;
+        }
+        if !expired.is_empty() {
+            tracing::info!(
+                "Expired {} session(s) idle for over {}h",
+                expired.len(),
+                expiry.ttl_hours
+            );
+        }
+        Ok(expired)
     }
 
     /// Get session statistics
@@ -592,15 +1439,288 @@ impl<S: Storage + 'static> SessionManager<S> {
             }
         }
 
+        let overrides = self.get_session_overrides(session_id).await;
         Ok(SessionStats {
             total_messages,
             user_messages,
             assistant_messages,
             total_tokens,
             models_used,
+            temperature: overrides.temperature,
+            persona: overrides.persona,
+            language: overrides.language,
         })
     }
 
+    /// Load the conversation and make sure it fits the model's context window.
+    ///
+    /// An oversized conversation is compacted first when compaction is
+    /// enabled; if it still does not fit, `ContextWindowExceeded` is returned.
+    /// The just-stored `user_message` is sent unredacted unless
+    /// `redaction.redact_model_input` is set; `extra_context` counts toward
+    /// the context window like the rest of the conversation.
+    async fn prepare_context(
+        &self,
+        session_id: &str,
+        user_message: &str,
+        system_prompt: &str,
+        extra_context: &[String],
+        tools: &[ToolDefinition],
+    ) -> Result<PreparedContext> {
+        let overrides = self.reply_settings(session_id).await?;
+        let system_prompt = &self
+            .with_reply_language(
+                with_persona(system_prompt, &overrides),
+                &overrides,
+                user_message,
+            )
+            .await;
+        tracing::debug!(
+            "System prompt for session {}: ~{} tokens",
+            session_id,
+            estimate_tokens(system_prompt)
+        );
+
+        let redactor = self.redactor().await;
+        let original = match redactor {
+            Some(_) if !self.config.read().await.redaction.redact_model_input => Some(user_message),
+            _ => None,
+        };
+
+        let mut messages = self
+            .load_messages(session_id, system_prompt, original, extra_context)
+            .await?;
+
+        // Determine model to use (auto-route based on last user message)
+        let model = match (
+            &overrides.model,
+            messages.iter().rev().find(|m| m.role == "user"),
+        ) {
+            (Some(model), _) => self.llm_client.resolve_model_name(model),
+            (None, Some(last_user_msg)) => self.llm_client.route_model(&last_user_msg.content),
+            (None, None) => self.llm_client.primary_model(),
+        }
+        .to_string();
+
+        let context_window = self.context_window(&model).await;
+        let tokenizer = self.tokenizer(&model).await;
+        let compaction_enabled = self.config.read().await.sessions.compaction_enabled;
+
+        if compaction_enabled
+            && check_context_window(&*tokenizer, context_window, &messages, tools).is_err()
+        {
+            tracing::info!(
+                "Session {} exceeds the context window of {}, compacting",
+                session_id,
+                model
+            );
+            self.summarize_and_compact(session_id).await?;
+            messages = self
+                .load_messages(session_id, system_prompt, original, extra_context)
+                .await?;
+        }
+
+        check_context_window(&*tokenizer, context_window, &messages, tools)?;
+
+        Ok(PreparedContext {
+            messages,
+            model,
+            context_window,
+            tokenizer,
+            redactor,
+            reply_filters: self.reply_filters().await,
+            retry: self.config.read().await.sessions.retry.clone(),
+            drafts: self.config.read().await.sessions.drafts.clone(),
+            temperature: overrides.temperature,
+            seed: None,
+            prefill: None,
+            tool_choice: ToolChoice::Auto,
+            empty_reply: self
+                .config
+                .read()
+                .await
+                .prompt
+                .empty_reply
+                .clone()
+                .unwrap_or_else(|| DEFAULT_EMPTY_REPLY.to_string()),
+            argument_resends: self.config.read().await.tools.json_repair.resend_attempts,
+        })
+    }
+
+    /// System prompt followed by the language to reply to `user_message`
+    /// in, when one is chosen with `/lang` or detected
+    async fn with_reply_language(
+        &self,
+        mut system_prompt: String,
+        overrides: &SessionOverrides,
+        user_message: &str,
+    ) -> String {
+        let detect = self.config.read().await.prompt.detect_language;
+        if let Some(section) = crate::core::prompt::build_language_section(
+            overrides.language.as_deref(),
+            user_message,
+            detect,
+        ) {
+            system_prompt.push_str("\n\n");
+            system_prompt.push_str(&section);
+        }
+        system_prompt
+    }
+
+    /// Context window of a model: the configured one, or else the one the
+    /// backend reports
+    async fn context_window(&self, model: &str) -> Option<usize> {
+        let configured = self.config.read().await.llm.context_window(model);
+        match configured {
+            Some(window) => Some(window),
+            None => self
+                .llm_client
+                .model_details(model)
+                .await
+                .and_then(|details| details.context_length),
+        }
+    }
+
+    /// Tokenizer of a model, as configured in `llm.tokenizers`
+    async fn tokenizer(&self, model: &str) -> Arc<dyn Tokenizer> {
+        let config = self.config.read().await;
+        crate::llm::tokenizer_for(model, config.llm.tokenizer(model))
+    }
+
+    /// The exact context the model would get for a session's next turn:
+    /// system prompt, windowed history and tools, with token estimates.
+    ///
+    /// Nothing is compacted or stored; `compaction_pending` tells whether the
+    /// next turn would compact the session first.
+    pub async fn preview_context(
+        &self,
+        session_id: &str,
+        agent_id: Option<&str>,
+    ) -> Result<ContextPreview> {
+        // Tools and model are picked as if the latest user message came again
+        let history = self
+            .get_messages(session_id)
+            .await
+            .context("Failed to get message history")?;
+        let last_user_message = history
+            .iter()
+            .rev()
+            .find(|m| m.role == "user")
+            .map(|m| m.content.clone())
+            .unwrap_or_default();
+
+        let mut tools = self
+            .get_tools_for_message(session_id, &last_user_message)
+            .await;
+        let tool_calling = self.tool_calling_for(&last_user_message).await;
+        let system_prompt = self
+            .build_system_prompt(
+                session_id,
+                agent_id,
+                self.prompt_tools(&tools, tool_calling).await,
+                tool_calling,
+            )
+            .await;
+        if !tool_calling {
+            tools.clear();
+        }
+        let overrides = self.reply_settings(session_id).await?;
+        let system_prompt = self
+            .with_reply_language(
+                with_persona(&system_prompt, &overrides),
+                &overrides,
+                &last_user_message,
+            )
+            .await;
+
+        let messages = self
+            .load_messages(session_id, &system_prompt, None, &[])
+            .await?;
+        let model = match (
+            &overrides.model,
+            messages.iter().rev().find(|m| m.role == "user"),
+        ) {
+            (Some(model), _) => self.llm_client.resolve_model_name(model),
+            (None, Some(last_user_msg)) => self.llm_client.route_model(&last_user_msg.content),
+            (None, None) => self.llm_client.primary_model(),
+        }
+        .to_string();
+        let context_window = self.context_window(&model).await;
+        let tokenizer = self.tokenizer(&model).await;
+        let fits = check_context_window(&*tokenizer, context_window, &messages, &tools).is_ok();
+        let compaction_pending = !fits && self.config.read().await.sessions.compaction_enabled;
+
+        Ok(ContextPreview {
+            model,
+            total_tokens: estimate_request_tokens(&*tokenizer, &messages, &tools),
+            messages: messages
+                .into_iter()
+                .map(|m| ContextMessage {
+                    tokens: estimate_message_tokens(&*tokenizer, &m),
+                    role: m.role,
+                    content: m.content,
+                })
+                .collect(),
+            tools: tools
+                .into_iter()
+                .map(|t| ContextTool {
+                    tokens: estimate_tool_tokens(&*tokenizer, &t),
+                    definition: t,
+                })
+                .collect(),
+            context_window,
+            fits,
+            compaction_pending,
+        })
+    }
+
+    /// Convert the system prompt and recent history into LLM messages.
+    ///
+    /// `current_message` replaces the stored (possibly redacted) content of
+    /// the latest user message; `extra_context` follows the system prompt as
+    /// a second system message.
+    async fn load_messages(
+        &self,
+        session_id: &str,
+        system_prompt: &str,
+        current_message: Option<&str>,
+        extra_context: &[String],
+    ) -> Result<Vec<ChatMessage>> {
+        // Get conversation history
+        let history = self
+            .storage
+            .get_messages(session_id, Some(50))
+            .await
+            .context("Failed to get message history")?;
+
+        let mut messages = vec![ChatMessage {
+            role: "system".to_string(),
+            content: system_prompt.to_string(),
+        }];
+
+        if !extra_context.is_empty() {
+            messages.push(ChatMessage {
+                role: "system".to_string(),
+                content: format_extra_context(extra_context),
+            });
+        }
+
+        messages.extend(history.iter().map(|msg| ChatMessage {
+            role: msg.role.clone(),
+            content: msg.content.clone(),
+        }));
+        let tool_results = self.config.read().await.sessions.tool_results.clone();
+        summarize_old_tool_results(&mut messages, &tool_results);
+
+        if let (Some(current), Some(last)) = (current_message, messages.last_mut()) {
+            if last.role == "user" {
+                last.content = current.to_string();
+            }
+        }
+
+        Ok(messages)
+    }
+
     /// Check if session needs compaction based on configuration
     async fn check_compaction_needed(&self, session_id: &str) -> Result<bool> {
         let enabled = self.config.read().await.sessions.compaction_enabled;
@@ -620,6 +1740,11 @@ impl<S: Storage + 'static> SessionManager<S> {
             return Ok(());
         }
 
+        self.summarize_and_compact(session_id).await
+    }
+
+    /// Summarize all but the most recent messages into memory
+    async fn summarize_and_compact(&self, session_id: &str) -> Result<()> {
         tracing::info!("Compacting session {}", session_id);
 
         let messages = self.storage.get_messages(session_id, None).await?;
@@ -639,38 +1764,19 @@ impl<S: Storage + 'static> SessionManager<S> {
         let to_summarize = &messages[..split_idx];
         let keep_messages = &messages[split_idx..];
 
-        // Build summary prompt
-        let conversation_text = to_summarize
-            .iter()
-            .map(|m| format!("{}: {}", m.role, m.content))
-            .collect::<Vec<_>>()
-            .join("\n");
-
-        let summary_request = ChatRequest {
-            model: String::new(), // Auto-route
-            messages: vec![
-                ChatMessage {
-                    role: "system".to_string(),
-                    content: "You are a helpful assistant. Summarize the following conversation, extracting key facts, user preferences, and the current goal. Be concise.".to_string(),
-                },
-                ChatMessage {
-                    role: "user".to_string(),
-                    content: conversation_text,
-                },
-            ],
-            max_tokens: None,
-            temperature: Some(0.0),
-            tools: None,
-        };
-
-        // Call LLM for summary
-        let response = self
+        // The `summarize` role if configured, otherwise auto-route
+        let model = self
             .llm_client
-            .chat(summary_request)
-            .await
-            .context("Failed to generate session summary")?;
-
-        let summary = response.content;
+            .model_for_role("summarize")
+            .map(String::from)
+            .unwrap_or_default();
+        let summary = self
+            .summarize(
+                model,
+                COMPACTION_INSTRUCTION,
+                conversation_text(to_summarize),
+            )
+            .await?;
 
         // Append to memory
         let memory_manager = crate::core::memory::MemoryManager::new(self.workspace.path());
@@ -700,6 +1806,442 @@ impl<S: Storage + 'static> SessionManager<S> {
         tracing::info!("Session {} compacted. Summary saved to memory.", session_id);
         Ok(())
     }
+
+    /// Ask `model` to summarize conversation text as `instruction` says
+    async fn summarize(&self, model: String, instruction: &str, text: String) -> Result<String> {
+        let summary_request = ChatRequest {
+            model,
+            messages: vec![
+                ChatMessage {
+                    role: "system".to_string(),
+                    content: instruction.to_string(),
+                },
+                ChatMessage {
+                    role: "user".to_string(),
+                    content: text,
+                },
+            ],
+            max_tokens: None,
+            temperature: Some(0.0),
+            tools: None,
+            seed: None,
+            prefill: None,
+            tool_choice: ToolChoice::None,
+        };
+
+        let response = self
+            .llm_client
+            .chat(summary_request)
+            .await
+            .context("Failed to generate session summary")?;
+        Ok(response.content)
+    }
+
+    /// Summary of a session's conversation, or `None` while it has no
+    /// messages. The summary is cached with the last message it covers; when
+    /// new messages arrive, only they are summarized into the cached one.
+    pub async fn session_summary(&self, session_id: &str) -> Result<Option<SessionSummary>> {
+        let messages = self.storage.get_messages(session_id, None).await?;
+        let Some(last) = messages.last() else {
+            return Ok(None);
+        };
+
+        let cached = self.storage.get_session_summary(session_id).await?;
+        let covered = cached.as_ref().and_then(|cached| {
+            messages
+                .iter()
+                .position(|m| m.id == cached.summary_through)
+                .map(|i| (cached, i))
+        });
+        let (instruction, text) = match covered {
+            Some((cached, i)) if i + 1 == messages.len() => return Ok(Some(cached.clone())),
+            Some((cached, i)) => (
+                SUMMARY_UPDATE_INSTRUCTION,
+                format!(
+                    "Summary so far:\n{}\n\nNew messages:\n{}",
+                    cached.summary,
+                    conversation_text(&messages[i + 1..])
+                ),
+            ),
+            // No summary yet, or its messages were cleared or compacted away
+            None => (SUMMARY_INSTRUCTION, conversation_text(&messages)),
+        };
+
+        // The `fast` role, then the `summarize` one, otherwise auto-route
+        let model = self
+            .llm_client
+            .model_for_role("fast")
+            .or_else(|| self.llm_client.model_for_role("summarize"))
+            .map(String::from)
+            .unwrap_or_default();
+        let summary = SessionSummary {
+            session_id: session_id.to_string(),
+            summary: self.summarize(model, instruction, text).await?,
+            summary_through: last.id.clone(),
+            updated_at: Utc::now(),
+        };
+        self.storage.save_session_summary(&summary).await?;
+        Ok(Some(summary))
+    }
+}
+
+/// Instruction for summarizing the messages compacted out of a session
+const COMPACTION_INSTRUCTION: &str = "You are a helpful assistant. Summarize the following conversation, extracting key facts, user preferences, and the current goal. Be concise.";
+
+/// Instruction for a session's summary
+const SUMMARY_INSTRUCTION: &str = "You are a helpful assistant. Summarize the following conversation in a few sentences: what it is about, what was decided, and what is still open. Be concise.";
+
+/// Instruction for bringing a session's summary up to date
+const SUMMARY_UPDATE_INSTRUCTION: &str = "You are a helpful assistant. Update the summary of a conversation with its new messages. Reply with the complete summary in a few sentences: what it is about, what was decided, and what is still open. Be concise.";
+
+/// Messages as `role: content` lines, for summarizing
+fn conversation_text(messages: &[StorageMessage]) -> String {
+    messages
+        .iter()
+        .map(|m| format!("{}: {}", m.role, m.content))
+        .collect::<Vec<_>>()
+        .join("\n")
+}
+
+/// System message carrying caller-supplied context documents
+fn format_extra_context(documents: &[String]) -> String {
+    let mut content = String::from(
+        "Additional context for this request. Use it if relevant to the user's message:",
+    );
+    for (i, document) in documents.iter().enumerate() {
+        content.push_str(&format!("\n\n[Document {}]\n{}", i + 1, document.trim()));
+    }
+    content
+}
+
+/// Tool results fed back to the model as user messages
+static TOOL_RESULT: Lazy<Regex> =
+    Lazy::new(|| Regex::new(r"^Tool (\S+) (?:result:|executed successfully|failed)").unwrap());
+
+/// Replace the tool results of turns more than `keep_full_turns` before the
+/// latest user message with a summary. A turn starts with a user message
+/// that is not a tool result.
+fn summarize_old_tool_results(messages: &mut [ChatMessage], config: &ToolResultsConfig) {
+    let Some(keep_full_turns) = config.keep_full_turns else {
+        return;
+    };
+    let mut later_turns = 0;
+    for message in messages.iter_mut().rev() {
+        let tool = match message.role.as_str() {
+            "tool" => None,
+            "user" => match TOOL_RESULT.captures(&message.content) {
+                Some(captures) => Some(captures[1].to_string()),
+                None => {
+                    later_turns += 1;
+                    continue;
+                }
+            },
+            _ => continue,
+        };
+        if later_turns > keep_full_turns {
+            message.content =
+                summarize_tool_result(tool.as_deref(), &message.content, config.summary_chars);
+        }
+    }
+}
+
+/// Summary of a tool result from an earlier turn: its size and first
+/// `summary_chars` characters, or only its size when that is 0. Results
+/// that short are kept as they are.
+fn summarize_tool_result(tool: Option<&str>, content: &str, summary_chars: usize) -> String {
+    let chars = content.chars().count();
+    if chars <= summary_chars {
+        return content.to_string();
+    }
+    let source = tool.map_or_else(|| "a tool".to_string(), |tool| format!("tool {}", tool));
+    if summary_chars == 0 {
+        return format!(
+            "[Result of {} from an earlier turn omitted ({} chars)]",
+            source, chars
+        );
+    }
+    let excerpt: String = content.chars().take(summary_chars).collect();
+    format!(
+        "[Result of {} from an earlier turn, shortened from {} chars] {}…",
+        source,
+        chars,
+        excerpt.trim_end()
+    )
+}
+
+/// Directory of the session workspace holding tool results too large for
+/// the context
+const OVERSIZED_RESULTS_DIR: &str = "tool-results";
+
+/// A tool result as it goes into the context. One that alone would not fit
+/// the room left in the context window is saved in full to the session
+/// workspace and cut to half that room, followed by a note on where the
+/// rest is; the other half is left for the reply and later calls.
+fn fit_tool_result(
+    tokenizer: &dyn Tokenizer,
+    context_window: Option<usize>,
+    messages: &[ChatMessage],
+    tools: &[ToolDefinition],
+    session_id: &str,
+    tool_name: &str,
+    result: String,
+) -> String {
+    let Some(limit) = context_window else {
+        return result;
+    };
+    let remaining = limit.saturating_sub(estimate_request_tokens(tokenizer, messages, tools));
+    if tokenizer.count_tokens(&result) + 4 <= remaining {
+        return result;
+    }
+
+    let note = match save_oversized_result(session_id, tool_name, &result) {
+        Ok(path) => format!(
+            "[Result truncated to fit the context: full result saved to workspace/{}, {} bytes. Read it with read_workspace_file.]",
+            path,
+            result.len()
+        ),
+        Err(e) => {
+            tracing::warn!(
+                "Failed to save oversized result of tool {} in session {}: {}",
+                tool_name,
+                session_id,
+                e
+            );
+            format!(
+                "[Result truncated to fit the context: {} bytes in full]",
+                result.len()
+            )
+        }
+    };
+    tracing::info!(
+        "Result of tool {} ({} bytes) exceeds the {} tokens left in the context, truncating",
+        tool_name,
+        result.len(),
+        remaining
+    );
+
+    let budget = (remaining / 2).saturating_sub(tokenizer.count_tokens(&note) + 4);
+    let kept = truncate_to_tokens(tokenizer, &result, budget);
+    format!("{}\n{}", kept.trim_end(), note)
+}
+
+/// Write a tool result to the session workspace; returns its path there
+fn save_oversized_result(session_id: &str, tool_name: &str, result: &str) -> Result<String> {
+    let dir = crate::sandbox::ensure_session_workspace(session_id)?.join(OVERSIZED_RESULTS_DIR);
+    std::fs::create_dir_all(&dir)?;
+    let tool: String = tool_name
+        .chars()
+        .map(|c| if c.is_ascii_alphanumeric() { c } else { '_' })
+        .collect();
+    let file = format!("{}-{}.txt", tool, &Uuid::new_v4().simple().to_string()[..8]);
+    std::fs::write(dir.join(&file), result)?;
+    Ok(format!("{}/{}", OVERSIZED_RESULTS_DIR, file))
+}
+
+/// Longest prefix of `text` within `max_tokens`, cut at a char boundary
+fn truncate_to_tokens<'a>(tokenizer: &dyn Tokenizer, text: &'a str, max_tokens: usize) -> &'a str {
+    let boundaries: Vec<usize> = text
+        .char_indices()
+        .map(|(i, _)| i)
+        .chain(std::iter::once(text.len()))
+        .collect();
+    // Binary search for the last boundary whose prefix fits
+    let (mut low, mut high) = (0, boundaries.len() - 1);
+    while low < high {
+        let mid = (low + high).div_ceil(2);
+        if tokenizer.count_tokens(&text[..boundaries[mid]]) <= max_tokens {
+            low = mid;
+        } else {
+            high = mid - 1;
+        }
+    }
+    &text[..boundaries[low]]
+}
+
+/// System prompt followed by the style instructions of the session's
+/// persona, if one was chosen
+fn with_persona(system_prompt: &str, overrides: &SessionOverrides) -> String {
+    match overrides.persona_instructions() {
+        Some(instructions) => format!("{}\n\n## Reply Style\n\n{}", system_prompt, instructions),
+        None => system_prompt.to_string(),
+    }
+}
+
+/// Persist a message, masking PII first when redaction is enabled
+async fn store_redacted<S: Storage>(
+    storage: &S,
+    mut message: StorageMessage,
+    redactor: Option<&Redactor>,
+) -> Result<()> {
+    if let Some(redactor) = redactor {
+        redactor.apply(&mut message);
+    }
+    storage.add_message(message).await
+}
+
+/// Periodic saving of a reply while it is streamed. One draft row is kept
+/// per turn and overwritten on every save.
+struct DraftWriter {
+    id: String,
+    session_id: String,
+    config: crate::config::DraftsConfig,
+    chunks: usize,
+    saved_at: std::time::Instant,
+}
+
+impl DraftWriter {
+    fn new(session_id: &str, config: crate::config::DraftsConfig) -> Self {
+        Self {
+            id: uuid::Uuid::new_v4().to_string(),
+            session_id: session_id.to_string(),
+            config,
+            chunks: 0,
+            saved_at: std::time::Instant::now(),
+        }
+    }
+
+    /// Count a streamed chunk and save the reply so far once either
+    /// threshold is reached. A failed save only costs recoverability.
+    async fn update<S: Storage>(
+        &mut self,
+        storage: &S,
+        content: &str,
+        model: &str,
+        redactor: Option<&Redactor>,
+    ) {
+        self.chunks += 1;
+        let interval = std::time::Duration::from_millis(self.config.interval_ms);
+        if self.chunks < self.config.every_chunks && self.saved_at.elapsed() < interval {
+            return;
+        }
+
+        let content = match redactor {
+            Some(redactor) => redactor.redact(content).0,
+            None => content.to_string(),
+        };
+        let draft = MessageDraft {
+            id: self.id.clone(),
+            session_id: self.session_id.clone(),
+            content,
+            model_used: Some(model.to_string()),
+            updated_at: Utc::now(),
+        };
+        if let Err(e) = storage.save_draft(&draft).await {
+            tracing::warn!("Failed to save draft of session {}: {}", self.session_id, e);
+        }
+        self.chunks = 0;
+        self.saved_at = std::time::Instant::now();
+    }
+
+    /// Drop the draft once the complete reply is stored
+    async fn finish<S: Storage>(self, storage: &S) {
+        if let Err(e) = storage.delete_draft(&self.id).await {
+            tracing::warn!(
+                "Failed to delete draft of session {}: {}",
+                self.session_id,
+                e
+            );
+        }
+    }
+}
+
+/// Every tool currently registered: built-in, channel, plugin and skill tools
+pub async fn available_tools() -> Vec<ToolDefinition> {
+    let mut tools = Vec::new();
+
+    // Helper function to extract tool definition from JSON
+    let extract_tool = |def: &serde_json::Value| -> Option<ToolDefinition> {
+        let func = def.get("function")?;
+        let name = func.get("name")?.as_str()?;
+        let description = func.get("description")?.as_str()?;
+        let parameters = func.get("parameters")?;
+        Some(ToolDefinition {
+            name: name.to_string(),
+            description: description.to_string(),
+            parameters: parameters.clone(),
+        })
+    };
+
+    // 1. Add exec tools (always available)
+    let exec_defs = crate::tools::get_exec_tool_definitions();
+    for def in exec_defs {
+        if let Some(tool) = extract_tool(&def) {
+            tools.push(tool);
+        }
+    }
+
+    // Add memory tools
+    tools.extend(crate::tools::get_memory_tool_definitions());
+
+    // Add clock, calculator and ask_user tools (always available)
+    tools.extend(crate::tools::clock::get_clock_tool_definitions());
+    tools.extend(crate::tools::calculator::get_calculator_tool_definitions());
+    tools.extend(crate::tools::ask_user::get_ask_user_tool_definitions());
+
+    // Add session workspace file tools (always available)
+    tools.extend(crate::tools::workspace_files::get_workspace_file_tool_definitions());
+
+    // 1b. Add creator tools (always available)
+    let creator_defs = crate::tools::get_creator_tool_definitions();
+    for def in creator_defs {
+        if let Some(tool) = extract_tool(&def) {
+            tools.push(tool);
+        }
+    }
+
+    // 1c. Add web tools (always available)
+    let web_defs = crate::tools::web::get_web_tool_definitions();
+    for def in web_defs {
+        if let Some(tool) = extract_tool(&def) {
+            tools.push(tool);
+        }
+    }
+
+    // 1d. Add knowledge search if documents are ingested
+    if crate::tools::rag::get_knowledge_base().is_some() {
+        tools.extend(crate::tools::rag::get_rag_tool_definitions());
+    }
+
+    // 1e. Add reminder tools once reminders are initialized
+    if crate::tools::reminders::get_reminders().is_some() {
+        tools.extend(crate::tools::reminders::get_reminder_tool_definitions());
+    }
+
+    // 1f. Add gateway introspection once the gateway is running
+    if crate::tools::gateway_info::get_gateway().is_some() {
+        tools.extend(crate::tools::gateway_info::get_gateway_info_tool_definitions());
+    }
+
+    // 2. Add WhatsApp tools if service is available
+    if crate::get_whatsapp_service().is_some() {
+        let whatsapp_defs = crate::tools::whatsapp::get_whatsapp_tool_definitions();
+        tools.extend(whatsapp_defs);
+    }
+
+    // 3. Add plugin tools from PluginRegistry if available
+    if let Some(registry) = crate::plugins::get_plugin_registry() {
+        if let Ok(tool_names) = registry.tools.list_tools() {
+            for tool_name in tool_names {
+                if let Ok(Some(tool)) = registry.tools.get_tool(&tool_name) {
+                    tools.push(ToolDefinition {
+                        name: tool.name,
+                        description: tool.description,
+                        parameters: tool.parameters,
+                    });
+                }
+            }
+        }
+    }
+
+    // 4. Add skill tools from SKILL_BODIES if available
+    tools.extend(get_skill_tool_definitions().await);
+
+    // Safe mode leaves only read-only and messaging tools
+    if crate::tools::executor::safe_mode() {
+        tools.retain(|tool| crate::tools::executor::allowed_in_safe_mode(&tool.name));
+    }
+
+    tools
 }
 
 /// Helper function to convert skill entries to tool definitions
@@ -715,6 +2257,95 @@ async fn get_skill_tool_definitions() -> Vec<ToolDefinition> {
         .collect()
 }
 
+/// Tags of skill and plugin tools, by tool name
+pub async fn tool_tags() -> std::collections::HashMap<String, Vec<String>> {
+    let mut tags: std::collections::HashMap<String, Vec<String>> =
+        crate::tools::skills::list_skills()
+            .await
+            .into_iter()
+            .map(|entry| (entry.manifest.name, entry.manifest.tags))
+            .collect();
+
+    if let Some(registry) = crate::plugins::get_plugin_registry() {
+        for name in registry.tools.list_tools().unwrap_or_default() {
+            if let Ok(Some(tool)) = registry.tools.get_tool(&name) {
+                // Skills are registered as plugin tools too; keep their manifest tags
+                tags.entry(name).or_insert(tool.tags);
+            }
+        }
+    }
+
+    tags
+}
+
+/// Keep at most `max` tool definitions, returning the kept tools (in their
+/// original order) and the names of the dropped ones.
+///
+/// Core tools (anything not in `droppable`) are always kept, even beyond
+/// `max`. The remaining room goes to the droppable tools used most recently,
+/// then to the ones listed first.
+fn cap_tool_definitions(
+    tools: Vec<ToolDefinition>,
+    max: usize,
+    droppable: &HashSet<String>,
+    last_used: &HashMap<String, std::time::Instant>,
+) -> (Vec<ToolDefinition>, Vec<String>) {
+    if tools.len() <= max {
+        return (tools, Vec::new());
+    }
+
+    let core = tools
+        .iter()
+        .filter(|tool| !droppable.contains(&tool.name))
+        .count();
+    let mut candidates: Vec<(usize, &ToolDefinition)> = tools
+        .iter()
+        .enumerate()
+        .filter(|(_, tool)| droppable.contains(&tool.name))
+        .collect();
+    // Most recently used first; never-used tools keep their listed order
+    candidates.sort_by(|(a_index, a), (b_index, b)| {
+        last_used
+            .get(&b.name)
+            .cmp(&last_used.get(&a.name))
+            .then(a_index.cmp(b_index))
+    });
+    let keep: HashSet<usize> = candidates
+        .into_iter()
+        .take(max.saturating_sub(core))
+        .map(|(index, _)| index)
+        .collect();
+
+    let mut kept = Vec::new();
+    let mut dropped = Vec::new();
+    for (index, tool) in tools.into_iter().enumerate() {
+        if !droppable.contains(&tool.name) || keep.contains(&index) {
+            kept.push(tool);
+        } else {
+            dropped.push(tool.name);
+        }
+    }
+    (kept, dropped)
+}
+
+/// Whether a tool with `tags` is offered given the enabled tags and the
+/// user's message (a tag mentioned as a word counts as enabled)
+fn tool_selected(tags: &[String], enabled: &[String], message: &str) -> bool {
+    if tags.is_empty() {
+        return true;
+    }
+
+    let words: Vec<String> = message
+        .split(|c: char| !c.is_alphanumeric() && c != '-' && c != '_')
+        .map(str::to_lowercase)
+        .collect();
+
+    tags.iter().any(|tag| {
+        let tag = tag.to_lowercase();
+        enabled.iter().any(|e| e.to_lowercase() == tag) || words.contains(&tag)
+    })
+}
+
 /// Session statistics
 #[derive(Debug, Clone)]
 pub struct SessionStats {
@@ -723,6 +2354,167 @@ pub struct SessionStats {
     pub assistant_messages: usize,
     pub total_tokens: usize,
     pub models_used: std::collections::HashMap<String, usize>,
+    /// Temperature set with `/temp`, if any
+    pub temperature: Option<f32>,
+    /// Persona chosen with `/persona`, if any
+    pub persona: Option<String>,
+    /// Code of the language chosen with `/lang`, if any
+    pub language: Option<String>,
+}
+
+impl SessionStats {
+    /// Statistics and reply settings, one per line, for chat replies
+    pub fn summary(&self) -> String {
+        let temperature = self
+            .temperature
+            .map(|t| t.to_string())
+            .unwrap_or_else(|| "default".to_string());
+        format!(
+            "Messages: {}\nTokens used: {}\nTemperature: {}\nPersona: {}\nLanguage: {}",
+            self.total_messages,
+            self.total_tokens,
+            temperature,
+            self.persona.as_deref().unwrap_or("default"),
+            self.language.as_deref().unwrap_or("default")
+        )
+    }
+}
+
+/// Token size of a request: message contents, tool schemas and a small
+/// per-message overhead for role markers
+pub fn estimate_request_tokens(
+    tokenizer: &dyn Tokenizer,
+    messages: &[ChatMessage],
+    tools: &[ToolDefinition],
+) -> usize {
+    let message_tokens: usize = messages
+        .iter()
+        .map(|message| estimate_message_tokens(tokenizer, message))
+        .sum();
+    let tool_tokens: usize = tools
+        .iter()
+        .map(|tool| estimate_tool_tokens(tokenizer, tool))
+        .sum();
+
+    message_tokens + tool_tokens
+}
+
+fn estimate_message_tokens(tokenizer: &dyn Tokenizer, message: &ChatMessage) -> usize {
+    tokenizer.count_tokens(&message.content) + 4
+}
+
+fn estimate_tool_tokens(tokenizer: &dyn Tokenizer, tool: &ToolDefinition) -> usize {
+    tokenizer.count_tokens(&tool.name)
+        + tokenizer.count_tokens(&tool.description)
+        + tokenizer.count_tokens(&tool.parameters.to_string())
+}
+
+/// Usage of a call whose backend did not report it, counted with the
+/// model's tokenizer
+fn estimate_usage(
+    tokenizer: &dyn Tokenizer,
+    messages: &[ChatMessage],
+    tools: &[ToolDefinition],
+    reply: &str,
+) -> TokenUsage {
+    let prompt_tokens = estimate_request_tokens(tokenizer, messages, tools);
+    let completion_tokens = tokenizer.count_tokens(reply);
+    TokenUsage {
+        prompt_tokens,
+        completion_tokens,
+        total_tokens: prompt_tokens + completion_tokens,
+    }
+}
+
+/// Feedback for a failed call whose arguments are not JSON even after
+/// repair: the model is asked to resend it while the turn has resends left,
+/// and told to stop trying after that
+fn malformed_arguments_feedback(
+    tool: &str,
+    arguments: &str,
+    resends_left: &mut usize,
+) -> Option<String> {
+    let detail = crate::tools::json_repair::repair_arguments(arguments).err()?;
+    if *resends_left == 0 {
+        return Some(format!(
+            "Tool {} failed: its arguments were invalid JSON again ({}). Do not call it again; answer with what you have.",
+            tool, detail
+        ));
+    }
+    *resends_left -= 1;
+    Some(format!(
+        "Your arguments for tool {} were invalid JSON: {}. Please resend the call with valid JSON arguments.",
+        tool, detail
+    ))
+}
+
+/// Split the tokens of a final request and its reply by part
+fn context_budget(
+    tokenizer: &dyn Tokenizer,
+    context_window: Option<usize>,
+    messages: &[ChatMessage],
+    tools: &[ToolDefinition],
+    reply: &str,
+) -> ContextBudget {
+    let (system, history): (Vec<&ChatMessage>, Vec<&ChatMessage>) = messages
+        .iter()
+        .partition(|message| message.role == "system");
+    let system_prompt = system
+        .into_iter()
+        .map(|message| estimate_message_tokens(tokenizer, message))
+        .sum();
+    let history = history
+        .into_iter()
+        .map(|message| estimate_message_tokens(tokenizer, message))
+        .sum();
+    let tools = tools
+        .iter()
+        .map(|tool| estimate_tool_tokens(tokenizer, tool))
+        .sum();
+    let response = tokenizer.count_tokens(reply);
+    let total = system_prompt + history + tools + response;
+
+    ContextBudget {
+        system_prompt,
+        history,
+        tools,
+        response,
+        total,
+        context_window,
+        remaining: context_window.map(|window| window.saturating_sub(total)),
+    }
+}
+
+/// Fail if the tool choice names a tool the turn is not sending
+fn ensure_tool_choice(
+    tools: &[ToolDefinition],
+    tool_choice: &ToolChoice,
+) -> Result<(), ToolNotAvailable> {
+    match tool_choice {
+        ToolChoice::Tool(name) if !tools.iter().any(|tool| &tool.name == name) => {
+            Err(ToolNotAvailable(name.clone()))
+        }
+        _ => Ok(()),
+    }
+}
+
+/// Fail if a request would overflow the (optional) context window
+fn check_context_window(
+    tokenizer: &dyn Tokenizer,
+    context_window: Option<usize>,
+    messages: &[ChatMessage],
+    tools: &[ToolDefinition],
+) -> Result<(), ContextWindowExceeded> {
+    let Some(limit) = context_window else {
+        return Ok(());
+    };
+
+    let estimated = estimate_request_tokens(tokenizer, messages, tools);
+    if estimated > limit {
+        Err(ContextWindowExceeded { estimated, limit })
+    } else {
+        Ok(())
+    }
 }
 
 /// Accumulated tool call during streaming
@@ -732,38 +2524,128 @@ struct AccumulatedToolCall {
     arguments: String,
 }
 
+/// Merge a streamed tool-call fragment into its call. Returns the
+/// `ToolArgsDelta` to emit when the fragment carried arguments; they are
+/// passed on unparsed, so incomplete JSON is fine.
+fn accumulate_tool_call(
+    calls: &mut std::collections::HashMap<usize, AccumulatedToolCall>,
+    chunk: &crate::llm::ToolCallChunk,
+) -> Option<StreamEvent> {
+    let entry = calls
+        .entry(chunk.index)
+        .or_insert_with(|| AccumulatedToolCall {
+            id: chunk.id.clone().unwrap_or_default(),
+            name: chunk.name.clone().unwrap_or_default(),
+            arguments: String::new(),
+        });
+
+    if let Some(id) = &chunk.id {
+        entry.id = id.clone();
+    }
+    if let Some(name) = &chunk.name {
+        entry.name = name.clone();
+    }
+
+    let args = chunk.arguments.as_deref().filter(|args| !args.is_empty())?;
+    entry.arguments.push_str(args);
+    Some(StreamEvent::ToolArgsDelta {
+        index: chunk.index,
+        name: entry.name.clone(),
+        partial_args: entry.arguments.clone(),
+    })
+}
+
+/// At most this many side-effect-free tool calls run at once
+const MAX_PARALLEL_TOOLS: usize = 4;
+
+/// Run a turn's tool calls in order, except that consecutive calls to
+/// side-effect-free tools run concurrently, at most `MAX_PARALLEL_TOOLS` at a
+/// time. Results are returned in call order.
+async fn run_tool_calls<'a, T, R, Fut>(
+    calls: &'a [T],
+    name: impl Fn(&T) -> &str,
+    run: impl Fn(&'a T) -> Fut,
+) -> Vec<R>
+where
+    Fut: std::future::Future<Output = R>,
+{
+    let mut side_effect_free = Vec::with_capacity(calls.len());
+    for call in calls {
+        side_effect_free.push(crate::tools::executor::is_side_effect_free(name(call)).await);
+    }
+
+    let permits = tokio::sync::Semaphore::new(MAX_PARALLEL_TOOLS);
+    let mut results = Vec::with_capacity(calls.len());
+    let mut start = 0;
+    while start < calls.len() {
+        let end = if side_effect_free[start] {
+            start
+                + side_effect_free[start..]
+                    .iter()
+                    .take_while(|free| **free)
+                    .count()
+        } else {
+            start + 1
+        };
+
+        let batch = calls[start..end].iter().map(|call| async {
+            let _permit = permits.acquire().await.expect("tool semaphore closed");
+            run(call).await
+        });
+        results.extend(futures::future::join_all(batch).await);
+        start = end;
+    }
+    results
+}
+
 /// Streaming task worker function
 async fn process_message_stream_task<S: Storage + 'static>(
-    storage: S,
-    llm_client: crate::llm::Client,
+    manager: SessionManager<S>,
     session_id: String,
     tools: Vec<ToolDefinition>,
     tx: mpsc::Sender<StreamEvent>,
-    workspace: Workspace,
-    approval_manager: Arc<crate::core::ApprovalManager>,
+    context: PreparedContext,
 ) -> Result<()> {
     use futures::StreamExt;
-    use std::collections::HashMap;
-
-    // Get conversation history
-    let history = storage
-        .get_messages(&session_id, Some(50))
+    use std::time::{Duration, Instant};
+
+    let SessionManager {
+        storage,
+        llm_client,
+        approval_manager,
+        maintenance,
+        ..
+    } = manager;
+
+    let PreparedContext {
+        messages: mut llm_messages,
+        model,
+        context_window,
+        tokenizer,
+        redactor,
+        reply_filters,
+        retry,
+        drafts,
+        temperature,
+        seed,
+        mut prefill,
+        mut tool_choice,
+        empty_reply,
+        mut argument_resends,
+    } = context;
+
+    // Left behind when the turn ends without `Done`, recovered on startup
+    let mut draft = drafts
+        .enabled
+        .then(|| DraftWriter::new(&session_id, drafts.clone()));
+
+    // The session owner decides whether elevated tools are auto-approved
+    let user_id = storage
+        .get_session(&session_id)
         .await
-        .context("Failed to get message history")?;
-
-    // Convert storage messages to LLM messages
-    // Build dynamic system prompt
-    let system_prompt = SystemPromptBuilder::new(workspace, tools.clone()).build();
-
-    let mut llm_messages: Vec<ChatMessage> = vec![ChatMessage {
-        role: "system".to_string(),
-        content: system_prompt,
-    }];
-
-    llm_messages.extend(history.iter().map(|msg| ChatMessage {
-        role: msg.role.clone(),
-        content: msg.content.clone(),
-    }));
+        .ok()
+        .flatten()
+        .map(|session| session.user_id);
 
     tracing::info!(
         "Starting streaming for session {}: {} messages in context, {} tools available",
@@ -772,40 +2654,97 @@ async fn process_message_stream_task<S: Storage + 'static>(
         tools.len()
     );
 
-    // Determine model to use (auto-route based on last user message)
-    let model = if let Some(last_user_msg) = llm_messages
-        .iter()
-        .rev()
-        .find(|m| m.role == "user")
-        .map(|m| m.content.as_str())
-    {
-        llm_client.route_model(last_user_msg).to_string()
-    } else {
-        llm_client.primary_model().to_string()
-    };
+    // Throughput over every model call of the turn, tool execution excluded
+    let started = Instant::now();
+    let mut first_token_at: Option<Instant> = None;
+    let mut completion_tokens = 0;
+    let mut generation_time = Duration::ZERO;
+    // Whether an earlier call of the turn already streamed something
+    let mut produced_content = false;
 
     // Tool calling loop - continue until no more tool calls
     loop {
+        // Tool results grow the context, so re-check before every call
+        if let Err(overflow) =
+            check_context_window(&*tokenizer, context_window, &llm_messages, &tools)
+        {
+            let _ = tx.send(StreamEvent::Error(overflow.to_string())).await;
+            return Err(overflow.into());
+        }
+
         // Send request to LLM with streaming
         let request = ChatRequest {
             model: model.clone(),
             messages: llm_messages.clone(),
             max_tokens: None,
-            temperature: None,
+            temperature,
             tools: if tools.is_empty() {
                 None
             } else {
                 Some(tools.clone())
             },
+            seed,
+            // Only the first call of the turn starts the reply or is held to
+            // the tool choice
+            prefill: prefill.take(),
+            tool_choice: std::mem::take(&mut tool_choice),
         };
 
-        let mut stream = match llm_client.chat_stream(request).await {
-            Ok(s) => s,
-            Err(e) => {
-                let _ = tx
-                    .send(StreamEvent::Error(format!("LLM error: {}", e)))
-                    .await;
-                return Err(e);
+        // Nothing has been streamed for this call yet, so a transient
+        // failure to open the stream can be retried
+        let max_attempts = retry.max_attempts.max(1);
+        let mut attempt = 1;
+        let llm_span = tracing::info_span!(
+            "llm.stream",
+            model = tracing::field::Empty,
+            prompt_tokens = tracing::field::Empty,
+            completion_tokens = tracing::field::Empty,
+        );
+        let mut requested = Instant::now();
+        let mut stream = loop {
+            match llm_client
+                .chat_stream(request.clone())
+                .instrument(llm_span.clone())
+                .await
+            {
+                Ok(s) => break s,
+                Err(e) if attempt < max_attempts && is_transient_llm_error(&e) => {
+                    let delay = e
+                        .retry_after()
+                        .unwrap_or_else(|| crate::channels::retry::backoff_delay(&retry, attempt));
+                    tracing::warn!(
+                        "Streaming attempt {}/{} failed, retrying in {:?}: {}",
+                        attempt,
+                        max_attempts,
+                        delay,
+                        e
+                    );
+                    let reason = match e {
+                        crate::llm::LlmError::RateLimited { .. } => format!(
+                            "rate limited, retrying in {}s",
+                            delay.as_millis().div_ceil(1000)
+                        ),
+                        _ => e.to_string(),
+                    };
+                    let retrying = StreamEvent::Retrying {
+                        attempt,
+                        max_attempts,
+                        delay_ms: delay.as_millis() as u64,
+                        reason,
+                    };
+                    if tx.send(retrying).await.is_err() {
+                        return Ok(());
+                    }
+                    tokio::time::sleep(delay).await;
+                    attempt += 1;
+                    requested = Instant::now();
+                }
+                Err(e) => {
+                    let _ = tx
+                        .send(StreamEvent::Error(format!("LLM error: {}", e)))
+                        .await;
+                    return Err(e.into());
+                }
             }
         };
 
@@ -814,15 +2753,27 @@ async fn process_message_stream_task<S: Storage + 'static>(
         let mut tool_calls_map: HashMap<usize, AccumulatedToolCall> = HashMap::new();
         let mut finish_reason_: Option<String> = None;
         let mut final_usage = None;
+        // Chunks report the model that actually answered (it differs on fallback)
+        let mut model = model.clone();
 
         // Consume the stream
-        while let Some(result) = stream.next().await {
+        while let Some(result) = stream.next().instrument(llm_span.clone()).await {
             match result {
                 Ok(chunk) => {
+                    if let Some(answered) = &chunk.model {
+                        model.clone_from(answered);
+                    }
+
                     // Accumulate content
                     if let Some(content) = &chunk.content {
                         if !content.is_empty() {
+                            first_token_at.get_or_insert_with(Instant::now);
                             content_buf.push_str(content);
+                            if let Some(draft) = draft.as_mut() {
+                                draft
+                                    .update(&storage, &content_buf, &model, redactor.as_deref())
+                                    .await;
+                            }
                             // Send delta event (per-token)
                             if tx.send(StreamEvent::Delta(content.clone())).await.is_err() {
                                 // Receiver dropped - client disconnected
@@ -831,25 +2782,13 @@ async fn process_message_stream_task<S: Storage + 'static>(
                         }
                     }
 
-                    // Accumulate tool calls
+                    // Accumulate tool calls, reporting their arguments as they form
                     if let Some(tool_calls) = &chunk.tool_calls {
                         for tc in tool_calls {
-                            let entry = tool_calls_map.entry(tc.index).or_insert_with(|| {
-                                AccumulatedToolCall {
-                                    id: tc.id.clone().unwrap_or_default(),
-                                    name: tc.name.clone().unwrap_or_default(),
-                                    arguments: String::new(),
+                            if let Some(event) = accumulate_tool_call(&mut tool_calls_map, tc) {
+                                if tx.send(event).await.is_err() {
+                                    return Ok(());
                                 }
-                            });
-
-                            if let Some(id) = &tc.id {
-                                entry.id = id.clone();
-                            }
-                            if let Some(name) = &tc.name {
-                                entry.name = name.clone();
-                            }
-                            if let Some(args) = &tc.arguments {
-                                entry.arguments.push_str(args);
                             }
                         }
                     }
@@ -868,14 +2807,33 @@ async fn process_message_stream_task<S: Storage + 'static>(
                     let _ = tx
                         .send(StreamEvent::Error(format!("Stream error: {}", e)))
                         .await;
-                    return Err(e);
+                    return Err(e.into());
                 }
             }
         }
 
+        generation_time += requested.elapsed();
+        if final_usage.is_none() {
+            final_usage = Some(estimate_usage(
+                &*tokenizer,
+                &llm_messages,
+                &tools,
+                &content_buf,
+            ));
+        }
+        if let Some(usage) = &final_usage {
+            completion_tokens += usage.completion_tokens;
+            llm_span.record("prompt_tokens", usage.prompt_tokens);
+            llm_span.record("completion_tokens", usage.completion_tokens);
+        }
+        llm_span.record("model", model.as_str());
+        // Close the span now; the tool calls below are its siblings
+        drop(llm_span);
+
         // Check finish reason to determine if we have tool calls
         if finish_reason_.as_deref() == Some("tool_calls") && !tool_calls_map.is_empty() {
             tracing::info!("Streaming generated {} tool calls", tool_calls_map.len());
+            produced_content |= !content_buf.trim().is_empty();
 
             // Add assistant response to message history
             llm_messages.push(ChatMessage {
@@ -886,72 +2844,147 @@ async fn process_message_stream_task<S: Storage + 'static>(
             // Execute tools (sort by index)
             let mut sorted_tools: Vec<_> = tool_calls_map.into_iter().collect();
             sorted_tools.sort_by_key(|a| a.0);
+            let tool_calls: Vec<AccumulatedToolCall> =
+                sorted_tools.into_iter().map(|(_, call)| call).collect();
+
+            // Side-effect-free calls run concurrently, each reporting its own
+            // start and end; results keep call order
+            let mut approvals = approval_manager.subscribe();
+            let mut questions = crate::core::user_input::user_inputs().listen(&session_id);
+            let (session_id, user_id, approval_manager, maintenance) =
+                (&session_id, &user_id, &approval_manager, &maintenance);
+            let executions = run_tool_calls(
+                &tool_calls,
+                |tool_call| tool_call.name.as_str(),
+                |tool_call| {
+                    let tx = tx.clone();
+                    async move {
+                        tracing::info!("Executing tool: {}", tool_call.name);
+                        let _ = tx
+                            .send(StreamEvent::ToolStart {
+                                name: tool_call.name.clone(),
+                                attempt: None,
+                                max_attempts: None,
+                            })
+                            .await;
+
+                        // Execute tool with approval flow and retry mechanism
+                        let execution_result = match maintenance.check() {
+                            Err(e) => {
+                                crate::tools::ToolExecutionResult::error(e.to_string(), 0, 1, 1)
+                            }
+                            Ok(()) => {
+                                crate::tools::executor::execute_tool_with_approval(
+                                    &tool_call.name,
+                                    &tool_call.arguments,
+                                    session_id,
+                                    user_id.as_deref(),
+                                    approval_manager,
+                                )
+                                .await
+                            }
+                        };
+
+                        // Format result for LLM feedback
+                        let result_content = if execution_result.is_success() {
+                            execution_result.output.clone().unwrap_or_default()
+                        } else {
+                            let error_msg = execution_result
+                                .error
+                                .clone()
+                                .unwrap_or_else(|| "Unknown error".to_string());
+                            format!("Error: {}", error_msg)
+                        };
+
+                        // Send tool end event with full execution metadata
+                        let _ = tx
+                            .send(StreamEvent::ToolEnd {
+                                name: tool_call.name.clone(),
+                                result: result_content.clone(),
+                                execution_time_ms: execution_result.execution_time_ms,
+                                attempt: Some(execution_result.attempt),
+                                status: Some(execution_result.status.clone()),
+                            })
+                            .await;
+                        (execution_result, result_content)
+                    }
+                },
+            );
+            tokio::pin!(executions);
+
+            // Forward this session's approval requests while tools wait on them
+            let executions = loop {
+                tokio::select! {
+                    results = &mut executions => break results,
+                    Ok(approval) = approvals.recv() => {
+                        if &approval.session_id != session_id {
+                            continue;
+                        }
+                        let _ = tx
+                            .send(StreamEvent::ApprovalRequested {
+                                request_id: approval.request_id,
+                                tool_name: approval.tool_name,
+                                arguments: approval.arguments,
+                                policy: approval.policy,
+                                sandbox_available: approval.sandbox_available,
+                            })
+                            .await;
+                    }
+                    Some(question) = questions.recv() => {
+                        let _ = tx
+                            .send(StreamEvent::UserInputRequested {
+                                prompt: question.prompt,
+                                request_id: question.request_id,
+                            })
+                            .await;
+                    }
+                }
+            };
+            if tx.is_closed() {
+                // Receiver dropped
+                return Ok(());
+            }
 
-            for (_idx, tool_call) in sorted_tools {
-                tracing::info!("Executing tool: {}", tool_call.name);
-
-                // Determine if sandbox is available (from server config)
-                let sandbox_available = crate::get_sandbox_manager().is_some();
-
-                // Execute tool with approval flow and retry mechanism
-                let execution_result = crate::tools::executor::execute_tool_with_approval(
-                    &tool_call.name,
-                    &tool_call.arguments,
-                    &session_id,
-                    &approval_manager,
-                    sandbox_available,
+            for (tool_call, (execution_result, result_content)) in tool_calls.iter().zip(executions)
+            {
+                crate::tools::audit::record_execution(
+                    &storage,
+                    session_id,
+                    crate::tools::audit::ExecutionRecord {
+                        tool_name: &tool_call.name,
+                        arguments: &tool_call.arguments,
+                        output: &result_content,
+                        success: execution_result.is_success(),
+                        duration_ms: execution_result.execution_time_ms,
+                    },
+                    redactor.as_deref(),
                 )
                 .await;
 
-                // Send tool start event with attempt tracking
-                if tx
-                    .send(StreamEvent::ToolStart {
-                        name: tool_call.name.clone(),
-                        attempt: Some(execution_result.attempt),
-                        max_attempts: Some(execution_result.max_attempts),
-                    })
-                    .await
-                    .is_err()
-                {
-                    // Receiver dropped
-                    return Ok(());
-                }
-
-                // Format result for LLM feedback
-                let result_content = if execution_result.is_success() {
-                    execution_result.output.clone().unwrap_or_default()
-                } else {
-                    let error_msg = execution_result
-                        .error
-                        .clone()
-                        .unwrap_or_else(|| "Unknown error".to_string());
-                    format!("Error: {}", error_msg)
-                };
-
-                // Send tool end event with full execution metadata
-                if tx
-                    .send(StreamEvent::ToolEnd {
-                        name: tool_call.name.clone(),
-                        result: result_content.clone(),
-                        execution_time_ms: execution_result.execution_time_ms,
-                        attempt: Some(execution_result.attempt),
-                    })
-                    .await
-                    .is_err()
-                {
-                    // Receiver dropped
-                    return Ok(());
-                }
-
-                // Add tool result to message history (for LLM to learn from)
+                // Add tool result to message history (for LLM to learn from);
+                // the audit log keeps the full result, the context what fits
                 let feedback = if execution_result.is_success() {
                     format!(
                         "Tool {} executed successfully (attempt {}/{}): {}",
                         tool_call.name,
                         execution_result.attempt,
                         execution_result.max_attempts,
-                        result_content
+                        fit_tool_result(
+                            &*tokenizer,
+                            context_window,
+                            &llm_messages,
+                            &tools,
+                            session_id,
+                            &tool_call.name,
+                            result_content,
+                        )
                     )
+                } else if let Some(feedback) = malformed_arguments_feedback(
+                    &tool_call.name,
+                    &tool_call.arguments,
+                    &mut argument_resends,
+                ) {
+                    feedback
                 } else if execution_result.attempt < execution_result.max_attempts {
                     format!(
                         "Tool {} failed (attempt {}/{}), will retry: {}",
@@ -988,9 +3021,57 @@ async fn process_message_stream_task<S: Storage + 'static>(
                 final_usage.as_ref().map(|u| u.total_tokens).unwrap_or(0)
             );
 
+            // Filtering may change what was already streamed; `Done` then
+            // carries the reply to show instead
+            let mut replaced = false;
+            if let Some(filters) = &reply_filters {
+                let filtered = filters.apply(&content_buf);
+                replaced = filtered != content_buf;
+                content_buf = filtered;
+            }
+
+            if content_buf.trim().is_empty() && !produced_content {
+                tracing::warn!(
+                    "Model {} returned an empty reply in session {}, sending the fallback",
+                    model,
+                    session_id
+                );
+                if !replaced
+                    && tx
+                        .send(StreamEvent::Delta(empty_reply.clone()))
+                        .await
+                        .is_err()
+                {
+                    return Ok(());
+                }
+                content_buf = empty_reply;
+            }
+            let content = replaced.then(|| content_buf.clone());
+
+            let time_to_first_token_ms = first_token_at.map(|at| (at - started).as_millis() as u64);
+            let tps = final_usage
+                .as_ref()
+                .filter(|_| !generation_time.is_zero())
+                .map(|_| completion_tokens as f64 / generation_time.as_secs_f64());
+            let metadata = (tps.is_some() || time_to_first_token_ms.is_some()).then(|| {
+                serde_json::json!({
+                    "tps": tps,
+                    "time_to_first_token_ms": time_to_first_token_ms,
+                })
+            });
+
+            let budget = context_budget(
+                &*tokenizer,
+                context_window,
+                &llm_messages,
+                &tools,
+                &content_buf,
+            );
+
             // Add final assistant response to storage
-            storage
-                .add_message(StorageMessage {
+            store_redacted(
+                &storage,
+                StorageMessage {
                     id: uuid::Uuid::new_v4().to_string(),
                     session_id: session_id.to_string(),
                     role: "assistant".to_string(),
@@ -998,14 +3079,24 @@ async fn process_message_stream_task<S: Storage + 'static>(
                     created_at: Utc::now(),
                     model_used: Some(model.clone()),
                     tokens: final_usage.as_ref().map(|u| u.total_tokens),
-                })
-                .await?;
+                    metadata,
+                },
+                redactor.as_deref(),
+            )
+            .await?;
+            if let Some(draft) = draft.take() {
+                draft.finish(&storage).await;
+            }
 
             // Send done event
             if tx
                 .send(StreamEvent::Done {
                     model,
                     usage: final_usage,
+                    tps,
+                    time_to_first_token_ms,
+                    budget: Some(budget),
+                    content,
                 })
                 .await
                 .is_err()
@@ -1020,10 +3111,368 @@ async fn process_message_stream_task<S: Storage + 'static>(
     Ok(())
 }
 
+/// A turn that failed after some of its tool calls ran. Retrying it would
+/// run them again, so it is never transient
+#[derive(Debug, thiserror::Error)]
+#[error(transparent)]
+pub(crate) struct FailedAfterTools(anyhow::Error);
+
+impl FailedAfterTools {
+    /// The error the turn failed with, for callers downcasting it
+    pub(crate) fn unwrap(err: anyhow::Error) -> anyhow::Error {
+        match err.downcast::<Self>() {
+            Ok(Self(err)) => err,
+            Err(err) => err,
+        }
+    }
+}
+
+/// Whether a failed turn is worth retrying as a whole: the database was
+/// locked or the LLM backend could not be reached before any tool ran
+pub(crate) fn is_transient_error(err: &anyhow::Error) -> bool {
+    if err.is::<FailedAfterTools>() {
+        return false;
+    }
+    err.chain().any(|cause| {
+        if let Some(err) = cause.downcast_ref::<crate::llm::LlmError>() {
+            return is_transient_llm_error(err);
+        }
+        if let Some(sqlx::Error::PoolTimedOut) = cause.downcast_ref::<sqlx::Error>() {
+            return true;
+        }
+        let message = cause.to_string().to_lowercase();
+        message.contains("database is locked") || message.contains("database table is locked")
+    })
+}
+
+/// Longest `Retry-After` a turn waits for before retrying; a backend asking
+/// for more is reported right away
+const MAX_RATE_LIMIT_WAIT: std::time::Duration = std::time::Duration::from_secs(60);
+
+fn is_transient_llm_error(err: &crate::llm::LlmError) -> bool {
+    use crate::llm::LlmError;
+    match err {
+        LlmError::Unavailable(_) | LlmError::Timeout => true,
+        LlmError::RateLimited { .. } => err
+            .retry_after()
+            .is_none_or(|wait| wait <= MAX_RATE_LIMIT_WAIT),
+        _ => false,
+    }
+}
+
+/// Wait before retrying a failed turn: what the backend asked for when it
+/// rate limited the request, else the configured backoff
+pub(crate) fn retry_delay(
+    retry: &crate::config::RetryConfig,
+    attempt: u32,
+    err: &anyhow::Error,
+) -> std::time::Duration {
+    err.chain()
+        .find_map(|cause| cause.downcast_ref::<crate::llm::LlmError>())
+        .and_then(crate::llm::LlmError::retry_after)
+        .unwrap_or_else(|| crate::channels::retry::backoff_delay(retry, attempt))
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
 
+    #[test]
+    fn test_transient_errors() {
+        use crate::llm::LlmError;
+
+        let unavailable = anyhow::Error::from(LlmError::Unavailable("connection refused".into()));
+        assert!(is_transient_error(&unavailable.context("Failed to answer")));
+        assert!(is_transient_error(&anyhow::anyhow!(
+            "error returned from database: (code: 5) database is locked"
+        )));
+        assert!(!is_transient_error(&anyhow::Error::from(
+            LlmError::BadRequest("bad role".into())
+        )));
+        assert!(!is_transient_error(&anyhow::anyhow!("Session not found")));
+    }
+
+    #[test]
+    fn test_rate_limits_are_retried_after_the_requested_wait() {
+        use crate::llm::LlmError;
+        use std::time::Duration;
+
+        let retry = crate::config::RetryConfig::default();
+        let limited = anyhow::Error::from(LlmError::RateLimited {
+            retry_after: Some(7),
+        });
+        assert!(is_transient_error(&limited));
+        assert_eq!(retry_delay(&retry, 1, &limited), Duration::from_secs(7));
+
+        // Without a hint the configured backoff applies
+        let unhinted = anyhow::Error::from(LlmError::RateLimited { retry_after: None });
+        assert!(is_transient_error(&unhinted));
+        assert_eq!(
+            retry_delay(&retry, 1, &unhinted),
+            Duration::from_millis(500)
+        );
+
+        // Longer waits are reported instead of held
+        let long = anyhow::Error::from(LlmError::RateLimited {
+            retry_after: Some(3600),
+        });
+        assert!(!is_transient_error(&long));
+    }
+
+    #[test]
+    fn test_unrepairable_arguments_are_resent_once() {
+        let mut resends = 1;
+        assert_eq!(
+            malformed_arguments_feedback("exec", r#"{command: "ls",}"#, &mut resends),
+            None
+        );
+        let first = malformed_arguments_feedback("exec", "run ls", &mut resends).unwrap();
+        assert!(first.starts_with("Your arguments for tool exec were invalid JSON: expected value"));
+        assert!(first.ends_with("Please resend the call with valid JSON arguments."));
+        assert_eq!(resends, 0);
+
+        let second = malformed_arguments_feedback("exec", "run ls", &mut resends).unwrap();
+        assert!(second.contains("Do not call it again"));
+        assert_eq!(resends, 0);
+    }
+
+    #[test]
+    fn test_only_tool_results_of_older_turns_are_summarized() {
+        let message = |role: &str, content: &str| ChatMessage {
+            role: role.to_string(),
+            content: content.to_string(),
+        };
+        let mut messages = vec![
+            message("system", "You are a helpful assistant."),
+            message("user", "List the files"),
+            message("tool", "a.txt b.txt c.txt d.txt"),
+            message("user", "Tool exec failed: permission denied"),
+            message("assistant", "There are four files."),
+            message("user", "Thanks"),
+        ];
+        let config = ToolResultsConfig {
+            keep_full_turns: Some(0),
+            summary_chars: 0,
+        };
+        summarize_old_tool_results(&mut messages, &config);
+        assert_eq!(
+            messages[2].content,
+            "[Result of a tool from an earlier turn omitted (23 chars)]"
+        );
+        assert_eq!(
+            messages[3].content,
+            "[Result of tool exec from an earlier turn omitted (35 chars)]"
+        );
+        assert_eq!(messages[1].content, "List the files");
+        assert_eq!(messages[5].content, "Thanks");
+
+        // Unset keeps every result in full
+        let mut unchanged = vec![message("tool", "a.txt"), message("user", "Thanks")];
+        summarize_old_tool_results(&mut unchanged, &ToolResultsConfig::default());
+        assert_eq!(unchanged[0].content, "a.txt");
+    }
+
+    #[test]
+    fn test_oversized_tool_result_is_saved_and_truncated() {
+        let tokenizer = crate::llm::HeuristicTokenizer;
+        let messages = vec![ChatMessage {
+            role: "user".to_string(),
+            content: "Dump the log".to_string(),
+        }];
+        let session_id = format!("oversized-{}", Uuid::new_v4());
+        let small = "line 1\nline 2".to_string();
+        let fitted = fit_tool_result(
+            &tokenizer,
+            Some(1000),
+            &messages,
+            &[],
+            &session_id,
+            "exec",
+            small.clone(),
+        );
+        assert_eq!(fitted, small);
+
+        let log: String = (0..2000).map(|i| format!("log line {}\n", i)).collect();
+        let fitted = fit_tool_result(
+            &tokenizer,
+            Some(1000),
+            &messages,
+            &[],
+            &session_id,
+            "exec",
+            log.clone(),
+        );
+        let remaining = 1000 - estimate_request_tokens(&tokenizer, &messages, &[]);
+        assert!(
+            tokenizer.count_tokens(&fitted) <= remaining / 2,
+            "{}",
+            fitted
+        );
+        assert!(fitted.starts_with("log line 0\nlog line 1\n"));
+        let note = format!(", {} bytes. Read it with read_workspace_file.]", log.len());
+        assert!(fitted.ends_with(&note), "{}", fitted);
+
+        // The full result is in the session workspace
+        let path = fitted
+            .split("workspace/")
+            .nth(1)
+            .and_then(|rest| rest.split(',').next())
+            .unwrap();
+        let workspace = crate::sandbox::session_workspace_path(&session_id);
+        assert_eq!(std::fs::read_to_string(workspace.join(path)).unwrap(), log);
+        crate::sandbox::remove_session_workspace(&session_id).unwrap();
+
+        // Without a known context window nothing is cut
+        let unlimited = fit_tool_result(
+            &tokenizer,
+            None,
+            &messages,
+            &[],
+            &session_id,
+            "exec",
+            log.clone(),
+        );
+        assert_eq!(unlimited, log);
+    }
+
+    #[test]
+    fn test_context_budget_parts_sum_to_the_total() {
+        let tokenizer = crate::llm::HeuristicTokenizer;
+        let message = |role: &str, content: &str| ChatMessage {
+            role: role.to_string(),
+            content: content.to_string(),
+        };
+        let messages = [
+            message("system", "You are a helpful assistant."),
+            message("user", "What's the weather like?"),
+            message("assistant", "Let me check."),
+            message("user", "Tool weather result: sunny, 21 degrees"),
+        ];
+        let tools = [ToolDefinition {
+            name: "weather".to_string(),
+            description: "Current weather for a city".to_string(),
+            parameters: serde_json::json!({"type": "object"}),
+        }];
+        let reply = "It's sunny and 21 degrees.";
+
+        let budget = context_budget(&tokenizer, Some(100), &messages, &tools, reply);
+        assert_eq!(budget.system_prompt, 7 + 4);
+        assert!(budget.history > 0 && budget.tools > 0 && budget.response > 0);
+        assert_eq!(
+            budget.system_prompt + budget.history + budget.tools + budget.response,
+            budget.total
+        );
+        assert_eq!(
+            budget.total - budget.response,
+            estimate_request_tokens(&tokenizer, &messages, &tools)
+        );
+        assert_eq!(budget.remaining, Some(100 - budget.total));
+
+        let overflowing = context_budget(&tokenizer, Some(10), &messages, &tools, reply);
+        assert_eq!(overflowing.remaining, Some(0));
+        let unknown = context_budget(&tokenizer, None, &messages, &tools, reply);
+        assert_eq!(unknown.remaining, None);
+    }
+
+    #[test]
+    fn test_tool_definitions_are_capped_keeping_core_tools() {
+        use std::time::{Duration, Instant};
+
+        let tool = |name: &str| ToolDefinition {
+            name: name.to_string(),
+            description: String::new(),
+            parameters: serde_json::json!({}),
+        };
+        let names = |tools: &[ToolDefinition]| -> Vec<String> {
+            tools.iter().map(|t| t.name.clone()).collect()
+        };
+        let tools: Vec<ToolDefinition> = ["exec", "skill_a", "memory_get", "skill_b", "skill_c"]
+            .into_iter()
+            .map(tool)
+            .collect();
+        let droppable: HashSet<String> = ["skill_a", "skill_b", "skill_c"]
+            .into_iter()
+            .map(String::from)
+            .collect();
+        let now = Instant::now();
+        let last_used = HashMap::from([
+            ("skill_c".to_string(), now),
+            ("skill_b".to_string(), now - Duration::from_secs(60)),
+        ]);
+
+        let (kept, dropped) = cap_tool_definitions(tools.clone(), 3, &droppable, &last_used);
+        assert_eq!(names(&kept), ["exec", "memory_get", "skill_c"]);
+        assert_eq!(dropped, ["skill_a", "skill_b"]);
+
+        let (kept, dropped) = cap_tool_definitions(tools.clone(), 4, &droppable, &last_used);
+        assert_eq!(names(&kept), ["exec", "memory_get", "skill_b", "skill_c"]);
+        assert_eq!(dropped, ["skill_a"]);
+
+        // Without usage, the tools listed first win
+        let (kept, _) = cap_tool_definitions(tools.clone(), 3, &droppable, &HashMap::new());
+        assert_eq!(names(&kept), ["exec", "skill_a", "memory_get"]);
+
+        // Core tools survive a cap smaller than their count
+        let (kept, dropped) = cap_tool_definitions(tools.clone(), 1, &droppable, &last_used);
+        assert_eq!(names(&kept), ["exec", "memory_get"]);
+        assert_eq!(dropped.len(), 3);
+
+        let (kept, dropped) = cap_tool_definitions(tools, 10, &droppable, &last_used);
+        assert_eq!(kept.len(), 5);
+        assert!(dropped.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_side_effect_free_tool_calls_run_in_parallel() {
+        let calls: Vec<String> = ["web_fetch", "web_fetch", "exec", "web_fetch"]
+            .iter()
+            .map(|name| name.to_string())
+            .collect();
+        // Both web_fetch calls must be running at once to get past the barrier
+        let barrier = tokio::sync::Barrier::new(2);
+        let events = std::sync::Mutex::new(Vec::new());
+        let started = std::sync::atomic::AtomicUsize::new(0);
+
+        let results = tokio::time::timeout(
+            std::time::Duration::from_secs(5),
+            run_tool_calls(
+                &calls[..],
+                |name| name.as_str(),
+                |name| {
+                    let (barrier, events) = (&barrier, &events);
+                    let index = started.fetch_add(1, std::sync::atomic::Ordering::SeqCst);
+                    async move {
+                        events.lock().unwrap().push(format!("start {}", name));
+                        if index < 2 {
+                            barrier.wait().await;
+                        }
+                        events.lock().unwrap().push(format!("end {}", name));
+                        format!("{} #{}", name, index)
+                    }
+                },
+            ),
+        )
+        .await
+        .expect("web_fetch calls did not run concurrently");
+
+        assert_eq!(
+            results,
+            vec!["web_fetch #0", "web_fetch #1", "exec #2", "web_fetch #3"]
+        );
+        // The side-effecting call runs alone, after the parallel ones ended
+        let events = events.into_inner().unwrap();
+        assert_eq!(
+            &events[..4],
+            [
+                "start web_fetch",
+                "start web_fetch",
+                "end web_fetch",
+                "end web_fetch"
+            ]
+        );
+        assert_eq!(&events[4..6], ["start exec", "end exec"]);
+    }
+
     #[test]
     fn test_stream_event_delta() {
         let event = StreamEvent::Delta("test content".to_string());
@@ -1054,6 +3503,43 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_accumulate_tool_call_reports_partial_args() {
+        let mut calls = std::collections::HashMap::new();
+        let chunk =
+            |id: Option<&str>, name: Option<&str>, args: Option<&str>| crate::llm::ToolCallChunk {
+                index: 0,
+                id: id.map(String::from),
+                name: name.map(String::from),
+                arguments: args.map(String::from),
+            };
+
+        // The opening chunk names the call but carries no arguments yet
+        assert!(
+            accumulate_tool_call(&mut calls, &chunk(Some("c1"), Some("web_fetch"), None)).is_none()
+        );
+
+        let mut partials = Vec::new();
+        for fragment in ["{\"url\": \"htt", "ps://example.com\"", "}"] {
+            match accumulate_tool_call(&mut calls, &chunk(None, None, Some(fragment))) {
+                Some(StreamEvent::ToolArgsDelta {
+                    index,
+                    name,
+                    partial_args,
+                }) => {
+                    assert_eq!((index, name.as_str()), (0, "web_fetch"));
+                    partials.push(partial_args);
+                }
+                _ => panic!("Expected ToolArgsDelta event"),
+            }
+        }
+
+        assert_eq!(partials[0], "{\"url\": \"htt");
+        assert_eq!(partials[2], "{\"url\": \"https://example.com\"}");
+        assert_eq!(calls[&0].id, "c1");
+        assert_eq!(calls[&0].arguments, partials[2]);
+    }
+
     #[test]
     fn test_stream_event_tool_start_without_attempt() {
         let event = StreamEvent::ToolStart {
@@ -1082,6 +3568,7 @@ mod tests {
             result: "output".to_string(),
             execution_time_ms: Some(1234),
             attempt: Some(1),
+            status: Some("done".to_string()),
         };
         match event {
             StreamEvent::ToolEnd {
@@ -1089,11 +3576,13 @@ mod tests {
                 result,
                 execution_time_ms,
                 attempt,
+                status,
             } => {
                 assert_eq!(name, "bash");
                 assert_eq!(result, "output");
                 assert_eq!(execution_time_ms, Some(1234));
                 assert_eq!(attempt, Some(1));
+                assert_eq!(status.as_deref(), Some("done"));
             }
             _ => panic!("Expected ToolEnd event"),
         }
@@ -1106,6 +3595,7 @@ mod tests {
             result: "output".to_string(),
             execution_time_ms: None,
             attempt: None,
+            status: None,
         };
         match event {
             StreamEvent::ToolEnd {
@@ -1113,16 +3603,30 @@ mod tests {
                 result,
                 execution_time_ms,
                 attempt,
+                status,
             } => {
                 assert_eq!(name, "bash");
                 assert_eq!(result, "output");
                 assert_eq!(execution_time_ms, None);
                 assert_eq!(attempt, None);
+                assert_eq!(status, None);
             }
             _ => panic!("Expected ToolEnd event"),
         }
     }
 
+    #[test]
+    fn test_tool_selected_by_tags() {
+        let tags = vec!["email".to_string(), "Calendar".to_string()];
+        let enabled = vec!["calendar".to_string()];
+
+        assert!(tool_selected(&[], &[], "anything"));
+        assert!(tool_selected(&tags, &enabled, "hello"));
+        assert!(tool_selected(&tags, &[], "please send an email to Bob"));
+        assert!(!tool_selected(&tags, &[], "emails are not the tag"));
+        assert!(!tool_selected(&tags, &["git".to_string()], "hello"));
+    }
+
     #[test]
     fn test_stream_event_approval_requested() {
         let event = StreamEvent::ApprovalRequested {
@@ -1158,4 +3662,30 @@ mod tests {
             _ => panic!("Expected Error event"),
         }
     }
+
+    #[test]
+    fn test_context_window_check() {
+        let messages = vec![
+            ChatMessage {
+                role: "system".to_string(),
+                content: "You are helpful.".to_string(),
+            },
+            ChatMessage {
+                role: "user".to_string(),
+                content: "x".repeat(4000),
+            },
+        ];
+
+        let tokenizer = crate::llm::HeuristicTokenizer;
+        let estimated = estimate_request_tokens(&tokenizer, &messages, &[]);
+        assert!(estimated >= 1000);
+
+        assert!(check_context_window(&tokenizer, None, &messages, &[]).is_ok());
+        assert!(check_context_window(&tokenizer, Some(8192), &messages, &[]).is_ok());
+
+        let err = check_context_window(&tokenizer, Some(512), &messages, &[]).unwrap_err();
+        assert_eq!(err.limit, 512);
+        assert_eq!(err.estimated, estimated);
+        assert!(err.to_string().contains("enable compaction or /reset"));
+    }
 }
@@ -0,0 +1,184 @@
+use crate::llm::{Client as LlmClient, ToolDefinition};
+use std::collections::{HashMap, HashSet};
+use tokio::sync::Mutex;
+
+/// Picks the tools most relevant to a user message by embedding similarity.
+///
+/// Tool embeddings are cached and only recomputed when a tool's description
+/// changes, so each turn costs one embedding call for the message (plus any
+/// new tools).
+#[derive(Default)]
+pub struct ToolSelector {
+    /// tool name -> (embedded text, embedding)
+    cache: Mutex<HashMap<String, (String, Vec<f32>)>>,
+}
+
+impl ToolSelector {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Keep every tool not in `selectable`, plus the `top_k` selectable tools
+    /// closest to `message`. All tools are kept when embedding fails.
+    pub async fn select(
+        &self,
+        llm_client: &LlmClient,
+        model: &str,
+        tools: Vec<ToolDefinition>,
+        selectable: &HashSet<String>,
+        message: &str,
+        top_k: usize,
+    ) -> Vec<ToolDefinition> {
+        let candidates: Vec<&ToolDefinition> = tools
+            .iter()
+            .filter(|tool| selectable.contains(&tool.name))
+            .collect();
+        if candidates.len() <= top_k {
+            return tools;
+        }
+
+        let mut cache = self.cache.lock().await;
+        let stale: Vec<(String, String)> = candidates
+            .iter()
+            .map(|tool| (tool.name.clone(), tool_text(tool)))
+            .filter(|(name, text)| cache.get(name).is_none_or(|(cached, _)| cached != text))
+            .collect();
+
+        let mut inputs = vec![message.to_string()];
+        inputs.extend(stale.iter().map(|(_, text)| text.clone()));
+        let mut vectors = match llm_client.embed(model, inputs).await {
+            Ok(vectors) if vectors.len() == stale.len() + 1 => vectors.into_iter(),
+            Ok(_) => {
+                tracing::warn!("Tool selection skipped: embedding count mismatch");
+                return tools;
+            }
+            Err(e) => {
+                tracing::warn!("Tool selection skipped, sending all tools: {}", e);
+                return tools;
+            }
+        };
+
+        let query = vectors.next().unwrap_or_default();
+        for ((name, text), vector) in stale.into_iter().zip(vectors) {
+            cache.insert(name, (text, vector));
+        }
+
+        let scored: Vec<(&str, &[f32])> = candidates
+            .iter()
+            .filter_map(|tool| {
+                cache
+                    .get(&tool.name)
+                    .map(|(_, vector)| (tool.name.as_str(), vector.as_slice()))
+            })
+            .collect();
+        let keep: HashSet<String> = top_k_names(&query, &scored, top_k).into_iter().collect();
+        drop(cache);
+
+        tracing::debug!("Selected tools for turn: {:?}", keep);
+        tools
+            .into_iter()
+            .filter(|tool| !selectable.contains(&tool.name) || keep.contains(&tool.name))
+            .collect()
+    }
+}
+
+/// Text embedded for a tool
+fn tool_text(tool: &ToolDefinition) -> String {
+    format!("{}: {}", tool.name, tool.description)
+}
+
+/// Names of the `k` candidates most similar to `query`
+fn top_k_names(query: &[f32], candidates: &[(&str, &[f32])], k: usize) -> Vec<String> {
+    let mut scored: Vec<(f32, &str)> = candidates
+        .iter()
+        .map(|(name, vector)| (cosine_similarity(query, vector), *name))
+        .collect();
+    scored.sort_by(|a, b| b.0.total_cmp(&a.0));
+
+    scored
+        .into_iter()
+        .take(k)
+        .map(|(_, name)| name.to_string())
+        .collect()
+}
+
+pub(crate) fn cosine_similarity(a: &[f32], b: &[f32]) -> f32 {
+    let dot: f32 = a.iter().zip(b).map(|(x, y)| x * y).sum();
+    let norm_a = a.iter().map(|x| x * x).sum::<f32>().sqrt();
+    let norm_b = b.iter().map(|x| x * x).sum::<f32>().sqrt();
+
+    if norm_a == 0.0 || norm_b == 0.0 {
+        0.0
+    } else {
+        dot / (norm_a * norm_b)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::{CacheConfig, LlmConfig, LlmModels};
+
+    fn tool(name: &str) -> ToolDefinition {
+        ToolDefinition {
+            name: name.to_string(),
+            description: format!("{} tool", name),
+            parameters: serde_json::json!({"type": "object"}),
+        }
+    }
+
+    #[test]
+    fn test_top_k_by_cosine_similarity() {
+        let weather: &[f32] = &[1.0, 0.1];
+        let email: &[f32] = &[0.0, 1.0];
+        let calendar: &[f32] = &[0.7, 0.7];
+        let candidates = [
+            ("weather", weather),
+            ("email", email),
+            ("calendar", calendar),
+        ];
+
+        assert_eq!(
+            top_k_names(&[1.0, 0.0], &candidates, 2),
+            vec!["weather", "calendar"]
+        );
+        assert_eq!(cosine_similarity(&[0.0, 0.0], &[1.0, 0.0]), 0.0);
+    }
+
+    #[tokio::test]
+    async fn test_keeps_all_tools_when_embeddings_fail() {
+        let client = LlmClient::new(&LlmConfig {
+            provider: "ollama".to_string(),
+            // Nothing listens here, so the embedding call fails
+            base_url: "http://127.0.0.1:9".to_string(),
+            models: LlmModels {
+                primary: "model".to_string(),
+                code: None,
+                fast: None,
+                roles: Default::default(),
+            },
+            keep_alive: None,
+            cache: CacheConfig {
+                cache_type: "ram".to_string(),
+                max_models: 1,
+                eviction: "lru".to_string(),
+            },
+            routing: None,
+            context_windows: Default::default(),
+            tool_support: Default::default(),
+            tokenizers: Default::default(),
+            coalesce_requests: false,
+            circuit_breaker: Default::default(),
+            warm_up: false,
+        })
+        .unwrap();
+
+        let tools = vec![tool("exec"), tool("weather"), tool("email")];
+        let selectable: HashSet<String> = ["weather", "email"].map(String::from).into();
+
+        let selected = ToolSelector::new()
+            .select(&client, "embed", tools, &selectable, "hi", 1)
+            .await;
+        assert_eq!(selected.len(), 3);
+    }
+}
@@ -0,0 +1,265 @@
+//! Questions the model asks the user in the middle of a turn
+//!
+//! The `ask_user` tool puts a question to the user and waits for the answer,
+//! which becomes its result. A streaming turn listens for its session's
+//! questions and passes them on as `StreamEvent::UserInputRequested`; the
+//! answer comes back over the WebSocket (`user_input_response`), the API
+//! (`POST /api/input/:request_id`) or, on chat channels, as the user's next
+//! message. Turns nobody listens to, such as non-streaming ones, cannot ask.
+
+use chrono::{DateTime, Utc};
+use once_cell::sync::Lazy;
+use serde::Serialize;
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Mutex;
+use std::time::Duration;
+use tokio::sync::{mpsc, oneshot};
+use uuid::Uuid;
+
+/// How long a question waits for the user to answer
+pub const USER_INPUT_TIMEOUT_SECS: u64 = 300;
+
+static USER_INPUTS: Lazy<UserInputs> = Lazy::new(UserInputs::default);
+
+/// The questions of all sessions
+pub fn user_inputs() -> &'static UserInputs {
+    &USER_INPUTS
+}
+
+/// A question waiting for the user's answer
+#[derive(Debug, Clone, Serialize)]
+pub struct UserInputRequest {
+    pub request_id: String,
+    pub session_id: String,
+    pub prompt: String,
+    pub created_at: DateTime<Utc>,
+    pub expires_at: DateTime<Utc>,
+}
+
+/// What became of a question
+#[derive(Debug, Clone, PartialEq)]
+pub enum UserInputOutcome {
+    Answered(String),
+    /// Not answered within the timeout
+    TimedOut,
+    /// Nobody is there to show the question to
+    NoListener,
+}
+
+/// Questions waiting for answers, and the turns able to show them
+#[derive(Default)]
+pub struct UserInputs {
+    /// Turns showing their session's questions, by session, with the ID of
+    /// the listener
+    listeners: Mutex<HashMap<String, (u64, mpsc::UnboundedSender<UserInputRequest>)>>,
+    pending: Mutex<HashMap<String, (UserInputRequest, oneshot::Sender<String>)>>,
+    next_listener: AtomicU64,
+}
+
+/// Questions of a session, for as long as it is kept
+pub struct UserInputListener {
+    inputs: &'static UserInputs,
+    session_id: String,
+    id: u64,
+    requests: mpsc::UnboundedReceiver<UserInputRequest>,
+}
+
+impl UserInputListener {
+    /// The next question asked in the session
+    pub async fn recv(&mut self) -> Option<UserInputRequest> {
+        self.requests.recv().await
+    }
+}
+
+impl Drop for UserInputListener {
+    fn drop(&mut self) {
+        let mut listeners = self.inputs.listeners.lock().unwrap();
+        // A later turn of the session may have replaced this listener
+        if listeners
+            .get(&self.session_id)
+            .is_some_and(|(id, _)| *id == self.id)
+        {
+            listeners.remove(&self.session_id);
+        }
+    }
+}
+
+/// Forgets a question that is no longer waited on
+struct PendingGuard<'a> {
+    inputs: &'a UserInputs,
+    request_id: String,
+}
+
+impl Drop for PendingGuard<'_> {
+    fn drop(&mut self) {
+        self.inputs.pending.lock().unwrap().remove(&self.request_id);
+    }
+}
+
+impl UserInputs {
+    /// Receive the questions asked in a session until the listener is
+    /// dropped
+    pub fn listen(&'static self, session_id: &str) -> UserInputListener {
+        let id = self.next_listener.fetch_add(1, Ordering::Relaxed);
+        let (tx, requests) = mpsc::unbounded_channel();
+        self.listeners
+            .lock()
+            .unwrap()
+            .insert(session_id.to_string(), (id, tx));
+        UserInputListener {
+            inputs: self,
+            session_id: session_id.to_string(),
+            id,
+            requests,
+        }
+    }
+
+    /// Ask the user of a session a question and wait up to `timeout` for
+    /// the answer
+    pub async fn ask(&self, session_id: &str, prompt: &str, timeout: Duration) -> UserInputOutcome {
+        let created_at = Utc::now();
+        let request = UserInputRequest {
+            request_id: Uuid::new_v4().to_string(),
+            session_id: session_id.to_string(),
+            prompt: prompt.to_string(),
+            created_at,
+            expires_at: created_at
+                + chrono::Duration::from_std(timeout).unwrap_or(chrono::Duration::zero()),
+        };
+        let (answer_tx, answer_rx) = oneshot::channel();
+        self.pending
+            .lock()
+            .unwrap()
+            .insert(request.request_id.clone(), (request.clone(), answer_tx));
+        let _guard = PendingGuard {
+            inputs: self,
+            request_id: request.request_id.clone(),
+        };
+
+        let shown = self
+            .listeners
+            .lock()
+            .unwrap()
+            .get(session_id)
+            .is_some_and(|(_, listener)| listener.send(request.clone()).is_ok());
+        if !shown {
+            return UserInputOutcome::NoListener;
+        }
+
+        tracing::debug!(
+            "Asked the user of session {}: request_id={}",
+            session_id,
+            request.request_id
+        );
+        match tokio::time::timeout(timeout, answer_rx).await {
+            Ok(Ok(answer)) => UserInputOutcome::Answered(answer),
+            _ => {
+                tracing::info!(
+                    "Question {} of session {} was not answered",
+                    request.request_id,
+                    session_id
+                );
+                UserInputOutcome::TimedOut
+            }
+        }
+    }
+
+    /// A question still waiting for its answer
+    pub fn get(&self, request_id: &str) -> Option<UserInputRequest> {
+        self.pending
+            .lock()
+            .unwrap()
+            .get(request_id)
+            .map(|(request, _)| request.clone())
+    }
+
+    /// Whether any session has a question waiting
+    pub fn has_pending(&self) -> bool {
+        !self.pending.lock().unwrap().is_empty()
+    }
+
+    /// Answer a question; false when it is no longer waiting
+    pub fn answer(&self, request_id: &str, answer: &str) -> bool {
+        let Some((_, answer_tx)) = self.pending.lock().unwrap().remove(request_id) else {
+            return false;
+        };
+        answer_tx.send(answer.to_string()).is_ok()
+    }
+
+    /// Answer the oldest question waiting in a session; false when there is
+    /// none
+    pub fn answer_session(&self, session_id: &str, answer: &str) -> bool {
+        let oldest = self
+            .pending
+            .lock()
+            .unwrap()
+            .values()
+            .filter(|(request, _)| request.session_id == session_id)
+            .min_by_key(|(request, _)| request.created_at)
+            .map(|(request, _)| request.request_id.clone());
+        oldest.is_some_and(|request_id| self.answer(&request_id, answer))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_question_is_answered() {
+        let inputs = user_inputs();
+        let mut listener = inputs.listen("ask-session");
+
+        let ask = tokio::spawn(async {
+            user_inputs()
+                .ask("ask-session", "Which file?", Duration::from_secs(5))
+                .await
+        });
+        let request = listener.recv().await.unwrap();
+        assert_eq!(request.prompt, "Which file?");
+        assert_eq!(
+            inputs.get(&request.request_id).unwrap().session_id,
+            "ask-session"
+        );
+        assert!(!inputs.answer_session("other-session", "notes.txt"));
+        assert!(inputs.answer_session("ask-session", "notes.txt"));
+
+        assert_eq!(
+            ask.await.unwrap(),
+            UserInputOutcome::Answered("notes.txt".to_string())
+        );
+        assert!(inputs.get(&request.request_id).is_none());
+        assert!(!inputs.answer(&request.request_id, "again"));
+    }
+
+    #[tokio::test]
+    async fn test_unanswered_and_unheard_questions() {
+        let inputs = user_inputs();
+        assert_eq!(
+            inputs
+                .ask("nobody-listens", "Hello?", Duration::from_secs(5))
+                .await,
+            UserInputOutcome::NoListener
+        );
+
+        let mut listener = inputs.listen("slow-session");
+        assert_eq!(
+            inputs
+                .ask("slow-session", "Still there?", Duration::from_millis(50))
+                .await,
+            UserInputOutcome::TimedOut
+        );
+        let request = listener.recv().await.unwrap();
+        assert!(inputs.get(&request.request_id).is_none());
+
+        // A dropped listener no longer receives questions
+        drop(listener);
+        assert_eq!(
+            inputs
+                .ask("slow-session", "Anyone?", Duration::from_secs(5))
+                .await,
+            UserInputOutcome::NoListener
+        );
+    }
+}
@@ -4,6 +4,8 @@ pub mod cli;
 pub mod config;
 pub mod core;
 pub mod llm;
+pub mod logging;
+pub mod network;
 pub mod plugins;
 pub mod sandbox;
 pub mod storage;
@@ -11,7 +13,7 @@ pub mod tools;
 
 pub use config::Config;
 pub use core::{Router, Session};
-pub use sandbox::SandboxManager;
+pub use sandbox::{SandboxManager, SandboxMode};
 pub use storage::Storage;
 
 use anyhow::Result;
@@ -29,6 +31,9 @@ static WHATSAPP_SERVICES: OnceCell<WhatsAppServicesRegistry> = OnceCell::new();
 // Global sandbox manager
 static SANDBOX_MANAGER: OnceCell<Arc<SandboxManager>> = OnceCell::new();
 
+// Configured sandbox mode
+static SANDBOX_MODE: OnceCell<SandboxMode> = OnceCell::new();
+
 // Global tool policy engine
 static TOOL_POLICY_ENGINE: OnceCell<Arc<tools::policy::ToolPolicyEngine>> = OnceCell::new();
 
@@ -100,6 +105,11 @@ pub fn get_sandbox_manager() -> Option<Arc<SandboxManager>> {
     SANDBOX_MANAGER.get().cloned()
 }
 
+/// Get the configured sandbox mode (the default mode before startup)
+pub fn get_sandbox_mode() -> SandboxMode {
+    SANDBOX_MODE.get().cloned().unwrap_or_default()
+}
+
 /// Get the global tool policy engine
 pub fn get_tool_policy_engine() -> Option<Arc<tools::policy::ToolPolicyEngine>> {
     TOOL_POLICY_ENGINE.get().cloned()
@@ -111,8 +121,22 @@ pub async fn run(config: Config) -> Result<()> {
     // Initialize WhatsApp services registry
     init_whatsapp_services();
 
+    // Timezone used for memory day boundaries and prompt dates
+    crate::core::locale::init_locale(&config.locale);
+
+    // Mapping of channel identities to shared user IDs
+    crate::core::identity::init_identities(&config.channels);
+
+    // Proxy for outbound HTTP requests
+    network::init_network(&config.network);
+
+    // Restrict the environment inherited by exec/skill processes
+    sandbox::init_env_allowlist(config.sandbox.env_allowlist.clone());
+    sandbox::init_session_workspace_root(config.sandbox.session_workspace_root.clone());
+
     // Initialize sandbox manager if sandboxing is not disabled
-    if config.sandbox.mode != sandbox::SandboxMode::Off {
+    SANDBOX_MODE.set(config.sandbox.mode.clone()).ok();
+    if config.sandbox.mode != SandboxMode::Off {
         tracing::info!(
             "Initializing sandbox manager (mode: {:?})",
             config.sandbox.mode
@@ -121,7 +145,7 @@ pub async fn run(config: Config) -> Result<()> {
         SANDBOX_MANAGER.set(Arc::new(sandbox)).ok();
         tracing::info!("✅ Sandbox manager initialized");
     } else {
-        tracing::info!("Sandbox disabled (mode: off)");
+        tracing::info!("Sandbox disabled (mode: off), exec and bash run on the host");
     }
 
     // Initialize tool policy engine
@@ -131,14 +155,86 @@ pub async fn run(config: Config) -> Result<()> {
             policies.insert(tool.clone(), level);
         }
     }
-    let policy_engine = tools::policy::ToolPolicyEngine::with_policies(policies);
+    let dev_bypass = config
+        .tools
+        .dev_bypass_active(&config.api, &config.channels);
+    if config.tools.dev_bypass_policy && !dev_bypass {
+        let remote_channels = config.channels.enabled_channels();
+        if remote_channels.is_empty() {
+            tracing::warn!(
+                "tools.dev_bypass_policy is ignored: the API is bound to non-loopback address {}",
+                config.api.host
+            );
+        } else {
+            tracing::warn!(
+                "tools.dev_bypass_policy is ignored: remote users reach the gateway through {}",
+                remote_channels.join(", ")
+            );
+        }
+    }
+    if dev_bypass {
+        tracing::warn!("************************************************************");
+        tracing::warn!("*  TOOL POLICY BYPASS ACTIVE (tools.dev_bypass_policy)      *");
+        tracing::warn!("*  Every tool runs without policy checks or approval.      *");
+        tracing::warn!("*  Never enable this outside local development.            *");
+        tracing::warn!("************************************************************");
+    }
+    let auto_approve_users = config.tools.auto_approved_users();
+    if !auto_approve_users.is_empty() {
+        tracing::info!(
+            "Elevated tools auto-approved for {} allowlisted user(s)",
+            auto_approve_users.len()
+        );
+    }
+    if config.tools.default_policy != tools::policy::ToolAccessLevel::Deny {
+        tracing::warn!(
+            "Tools without a policy default to '{}' (tools.default_policy)",
+            config.tools.default_policy.as_str()
+        );
+    }
+    let policy_engine = tools::policy::ToolPolicyEngine::with_policies(policies)
+        .with_default_policy(config.tools.default_policy.clone())
+        .with_dev_bypass(dev_bypass)
+        .with_auto_approve(auto_approve_users)
+        .with_argument_rules(tools::policy::compile_argument_rules(
+            &config.tools.argument_rules,
+        )?);
     TOOL_POLICY_ENGINE.set(Arc::new(policy_engine)).ok();
     tracing::info!("✅ Tool policy engine initialized");
 
+    // Initialize dangerous command guard for exec/bash
+    tools::command_guard::init_command_guard(&config.tools.command_guard)?;
+
+    // Bound tool executions that do not declare their own timeout
+    tools::executor::init_tool_timeout(config.tools.default_timeout_secs);
+
+    // Reject tool calls with oversized arguments
+    tools::executor::init_argument_limits(&config.tools);
+    tools::executor::init_json_repair(config.tools.json_repair.enabled);
+
+    // Turn off code execution and file changes for untrusted deployments
+    tools::executor::init_safe_mode(config.tools.safe_mode);
+    if config.tools.safe_mode {
+        tracing::info!("Safe mode on: code execution and file-changing tools are disabled");
+    }
+
     // Initialize plugin registry
-    let _plugin_registry = plugins::init_plugin_registry();
+    let plugin_registry = plugins::init_plugin_registry();
+    plugin_registry.hooks.configure(&config.plugins.hooks);
     tracing::info!("✅ Plugin registry initialized");
 
+    // Keep previous skill versions for rollback
+    tools::skills::init_skill_history(
+        std::path::Path::new(&config.tools.skills_dir).join(".history"),
+    );
+
+    if config.tools.namespace_skills {
+        tools::skills::init_skill_namespaces(
+            &config.tools.skills_dir,
+            vec![std::path::PathBuf::from(&config.tools.user_tools_dir)],
+        );
+    }
+
     // Initialize and start skill watcher if enabled
     if config.tools.skills_enabled {
         let skills_dir = config.tools.skills_dir.clone();
@@ -158,12 +254,66 @@ pub async fn run(config: Config) -> Result<()> {
     let storage = storage::sqlite::SqliteStorage::new(&config.storage.path).await?;
     tracing::info!("Storage initialized");
 
+    // Apply per-user tool policy overrides set through the API
+    if let Some(engine) = get_tool_policy_engine() {
+        for (user_id, policies) in storage.list_user_tool_policies().await? {
+            match tools::policy::parse_policies(&policies) {
+                Ok(overrides) => engine.set_user_policies(&user_id, overrides).await,
+                Err(e) => tracing::warn!("Ignoring tool policies of user {}: {}", user_id, e),
+            }
+        }
+    }
+
     // Initialize LLM client
     let llm_client = llm::Client::new(&config.llm)?;
     tracing::info!("LLM client initialized: {}", config.llm.base_url);
 
+    // Load the primary model in the background before the first message
+    if config.llm.warm_up {
+        let client = llm_client.clone();
+        tokio::spawn(async move {
+            match client.warm_up().await {
+                Ok(model) => tracing::info!("✅ Model {} warmed up", model),
+                Err(e) => tracing::warn!("Failed to warm up the primary model: {}", e),
+            }
+        });
+    }
+
+    // Ingest knowledge documents for the search_docs tool
+    if config.tools.knowledge.enabled {
+        let knowledge = Arc::new(tools::rag::KnowledgeBase::new(
+            storage.clone(),
+            llm_client.clone(),
+            config.tools.knowledge.clone(),
+        ));
+        tools::rag::init_knowledge_base(knowledge.clone());
+        tokio::spawn(async move {
+            if let Err(e) = tools::rag::watch(knowledge).await {
+                tracing::error!("Knowledge watcher error: {}", e);
+            }
+        });
+        tracing::info!(
+            "✅ Knowledge watcher started (dir: {})",
+            config.tools.knowledge.dir
+        );
+    }
+
     // Initialize router
     let shared_config = Arc::new(tokio::sync::RwLock::new(config.clone()));
+
+    // Deliver reminders set through the reminder tools, including ones
+    // still pending from before a restart
+    let reminders = Arc::new(tools::reminders::Reminders::new(storage.clone()));
+    tools::reminders::init_reminders(reminders.clone());
+    tokio::spawn(tools::reminders::run_dispatcher(
+        reminders,
+        shared_config.clone(),
+    ));
+    tracing::info!("✅ Reminder dispatcher started");
+
+    // Let the gateway_info tool report the live config and loaded models
+    tools::gateway_info::init_gateway(shared_config.clone(), llm_client.clone());
+
     let router = Router::new(shared_config, storage.clone(), llm_client).await;
     tracing::info!("Router initialized");
 
@@ -172,6 +322,21 @@ pub async fn run(config: Config) -> Result<()> {
         tracing::error!("Failed to bootstrap admin account: {}", e);
     }
 
+    // Run scheduled prompts
+    let scheduler = core::Scheduler::new(router.clone());
+    tokio::spawn(scheduler.run());
+    tokio::spawn(router.clone().run_session_expiry());
+    if config.sessions.expiry.enabled {
+        tracing::info!(
+            "✅ Session expiry enabled (idle sessions kept {}h)",
+            config.sessions.expiry.ttl_hours
+        );
+    }
+    tracing::info!(
+        "✅ Scheduler started ({} schedule(s) in config)",
+        config.schedules.len()
+    );
+
     // Start channel adapters
     let mut handles = vec![];
 
@@ -188,43 +353,21 @@ pub async fn run(config: Config) -> Result<()> {
             config.api.port,
             config.api.tokens.clone(),
             storage.clone(),
-        );
+        )
+        .with_token_scopes(config.api.token_scopes.clone())
+        .with_tls(config.api.tls.clone())
+        .with_unix_socket(config.api.unix_socket.clone());
         let api_handle = tokio::spawn(async move { api_adapter.start().await });
         handles.push(api_handle);
     }
 
-    if config.channels.telegram.enabled {
-        tracing::info!("Starting Telegram adapter...");
-        let telegram_handle = tokio::spawn(channels::telegram::run(
-            config.channels.telegram.clone(),
-            router.clone(),
-        ));
-        handles.push(telegram_handle);
-    }
-
-    if config.channels.discord.enabled {
-        tracing::info!("Starting Discord adapter...");
-        let discord_handle = tokio::spawn(channels::discord::run(
-            config.channels.discord.clone(),
-            router.clone(),
-        ));
-        handles.push(discord_handle);
-    }
-
-    if config.channels.whatsapp.enabled {
-        tracing::info!("Starting WhatsApp adapter...");
-
-        let whatsapp_handle = {
-            let router = router.clone();
-            let whatsapp_cfg = config.channels.whatsapp.clone();
-            tokio::spawn(async move {
-                let cfg = channels::whatsapp::WhatsAppAdapter::<storage::sqlite::SqliteStorage>::config_from_channel(whatsapp_cfg)?;
-                let adapter = channels::whatsapp::WhatsAppAdapter::new(Arc::new(router), cfg)?;
-                adapter.run().await
-            })
-        };
-
-        handles.push(whatsapp_handle);
+    // Channels are supervised: one that fails is restarted or left down
+    // without stopping the gateway
+    let restart_policy = channels::RestartPolicy::from_config(&config.channels);
+    let mut channel_handles = vec![];
+    for adapter in channels::enabled_adapters(&config, &router)? {
+        tracing::info!("Starting {} adapter...", adapter.name());
+        channel_handles.push(tokio::spawn(channels::supervise(adapter, restart_policy)));
     }
 
     // Wait for all adapters
@@ -232,6 +375,9 @@ pub async fn run(config: Config) -> Result<()> {
     for handle in handles {
         handle.await??;
     }
+    for handle in channel_handles {
+        handle.await?;
+    }
 
     Ok(())
 }
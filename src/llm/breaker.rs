@@ -0,0 +1,180 @@
+//! Circuit breaker that stops sending requests to a backend that keeps failing
+//! or has asked to wait (`Retry-After` on a rate limit)
+
+use super::LlmError;
+use crate::config::CircuitBreakerConfig;
+use serde::Serialize;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+/// What the breaker currently lets through
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum CircuitState {
+    /// Requests reach the backend
+    Closed,
+    /// Requests fail immediately until the cooldown ends
+    Open,
+    /// The cooldown ended: the next request probes the backend
+    HalfOpen,
+}
+
+#[derive(Debug, Default)]
+struct Breaker {
+    consecutive_failures: u32,
+    /// When the circuit last opened, or when the last probe was let through
+    opened_at: Option<Instant>,
+    /// Until when the backend asked not to be sent requests
+    rate_limited_until: Option<Instant>,
+}
+
+#[derive(Debug)]
+pub struct CircuitBreaker {
+    enabled: bool,
+    failure_threshold: u32,
+    cooldown: Duration,
+    breaker: Mutex<Breaker>,
+}
+
+impl CircuitBreaker {
+    pub fn new(config: &CircuitBreakerConfig) -> Self {
+        Self {
+            enabled: config.enabled,
+            failure_threshold: config.failure_threshold.max(1),
+            cooldown: Duration::from_secs(config.cooldown_secs),
+            breaker: Mutex::new(Breaker::default()),
+        }
+    }
+
+    pub fn state(&self) -> CircuitState {
+        match self.breaker.lock().unwrap().opened_at {
+            None => CircuitState::Closed,
+            Some(opened_at) if opened_at.elapsed() < self.cooldown => CircuitState::Open,
+            Some(_) => CircuitState::HalfOpen,
+        }
+    }
+
+    /// Admit a request, or fail fast while the circuit is open or the
+    /// backend's `Retry-After` has not passed. Once the cooldown ends one
+    /// request is let through as a probe and the cooldown restarts, so a
+    /// probe that never reports back cannot wedge the circuit.
+    pub fn check(&self) -> Result<(), LlmError> {
+        if !self.enabled {
+            return Ok(());
+        }
+        let mut breaker = self.breaker.lock().unwrap();
+        if let Some(until) = breaker.rate_limited_until {
+            let now = Instant::now();
+            if now < until {
+                let wait = until - now;
+                return Err(LlmError::RateLimited {
+                    retry_after: Some(wait.as_secs() + u64::from(wait.subsec_nanos() > 0)),
+                });
+            }
+            breaker.rate_limited_until = None;
+        }
+        let Some(opened_at) = breaker.opened_at else {
+            return Ok(());
+        };
+
+        let elapsed = opened_at.elapsed();
+        if elapsed < self.cooldown {
+            let retry_in = (self.cooldown - elapsed).as_secs().max(1);
+            return Err(LlmError::Unavailable(format!(
+                "circuit open after {} consecutive failures, retrying in {}s",
+                breaker.consecutive_failures, retry_in
+            )));
+        }
+        tracing::info!("LLM circuit half-open, probing the backend");
+        breaker.opened_at = Some(Instant::now());
+        Ok(())
+    }
+
+    /// Count the outcome of an admitted request. Only failures that point at
+    /// the backend itself count; rejected requests and rate limits do not,
+    /// but a rate limit's `Retry-After` holds back requests until it passes.
+    pub fn record<T>(&self, result: &Result<T, LlmError>) {
+        if !self.enabled {
+            return;
+        }
+        let mut breaker = self.breaker.lock().unwrap();
+        match result {
+            Err(LlmError::Timeout | LlmError::Unavailable(_) | LlmError::Backend(_)) => {
+                breaker.consecutive_failures += 1;
+                let probe_failed = breaker.opened_at.is_some();
+                if probe_failed || breaker.consecutive_failures >= self.failure_threshold {
+                    if !probe_failed {
+                        tracing::warn!(
+                            "LLM circuit open after {} consecutive failures; failing fast for {}s",
+                            breaker.consecutive_failures,
+                            self.cooldown.as_secs()
+                        );
+                    }
+                    breaker.opened_at = Some(Instant::now());
+                }
+            }
+            _ => {
+                if breaker.opened_at.is_some() {
+                    tracing::info!("LLM backend recovered, circuit closed");
+                }
+                *breaker = Breaker::default();
+                if let Err(LlmError::RateLimited {
+                    retry_after: Some(secs),
+                }) = result
+                {
+                    tracing::warn!("LLM backend rate limited, holding requests for {}s", secs);
+                    breaker.rate_limited_until = Some(Instant::now() + Duration::from_secs(*secs));
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn breaker(cooldown_secs: u64) -> CircuitBreaker {
+        CircuitBreaker::new(&CircuitBreakerConfig {
+            enabled: true,
+            failure_threshold: 2,
+            cooldown_secs,
+        })
+    }
+
+    #[test]
+    fn test_client_errors_do_not_open_the_circuit() {
+        let breaker = breaker(60);
+        for _ in 0..3 {
+            breaker.record::<()>(&Err(LlmError::BadRequest("bad role".into())));
+            breaker.record::<()>(&Err(LlmError::RateLimited { retry_after: None }));
+        }
+        assert_eq!(breaker.state(), CircuitState::Closed);
+
+        // A success in between resets the count
+        breaker.record::<()>(&Err(LlmError::Timeout));
+        breaker.record(&Ok(()));
+        breaker.record::<()>(&Err(LlmError::Timeout));
+        assert_eq!(breaker.state(), CircuitState::Closed);
+        assert!(breaker.check().is_ok());
+    }
+
+    #[test]
+    fn test_retry_after_holds_requests_back() {
+        let breaker = breaker(60);
+        breaker.record::<()>(&Err(LlmError::RateLimited {
+            retry_after: Some(30),
+        }));
+        assert_eq!(breaker.state(), CircuitState::Closed);
+        assert!(matches!(
+            breaker.check(),
+            Err(LlmError::RateLimited {
+                retry_after: Some(29 | 30)
+            })
+        ));
+
+        // Passed: requests go through again
+        breaker.breaker.lock().unwrap().rate_limited_until = Some(Instant::now());
+        assert!(breaker.check().is_ok());
+    }
+}
@@ -106,6 +106,7 @@ mod tests {
                 primary: "qwen2.5:32b".to_string(),
                 code: Some("deepseek-coder-v2:16b".to_string()),
                 fast: Some("qwen2.5:7b".to_string()),
+                roles: Default::default(),
             },
             keep_alive: None,
             cache: CacheConfig {
@@ -114,6 +115,12 @@ mod tests {
                 eviction: "lru".to_string(),
             },
             routing: None,
+            context_windows: Default::default(),
+            tool_support: Default::default(),
+            tokenizers: Default::default(),
+            coalesce_requests: false,
+            circuit_breaker: Default::default(),
+            warm_up: false,
         }
     }
 
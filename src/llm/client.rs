@@ -1,52 +1,181 @@
+use super::routing::with_fallback;
 use super::{
-    CacheManager, ChatMessage, ChatRequest, ChatResponse, ModelRouter, StreamChunk, TokenUsage,
-    ToolCall, ToolCallChunk,
+    CacheManager, ChatMessage, ChatRequest, ChatResponse, CircuitBreaker, CircuitState, LlmError,
+    ModelDetails, ModelRouter, StreamChunk, TokenUsage, ToolCall, ToolCallChunk, ToolChoice,
 };
 use crate::config::LlmConfig;
-use anyhow::{Context, Result};
+use anyhow::Result;
 use async_openai::{
-    config::OpenAIConfig,
+    config::{Config as _, OpenAIConfig},
     types::{
-        ChatCompletionRequestAssistantMessageArgs, ChatCompletionRequestMessage,
-        ChatCompletionRequestSystemMessageArgs, ChatCompletionRequestUserMessageArgs,
-        CreateChatCompletionRequestArgs,
+        ChatCompletionNamedToolChoice, ChatCompletionRequestAssistantMessageArgs,
+        ChatCompletionRequestMessage, ChatCompletionRequestSystemMessageArgs,
+        ChatCompletionRequestUserMessageArgs, ChatCompletionTool, ChatCompletionToolChoiceOption,
+        ChatCompletionToolType, CreateChatCompletionRequestArgs, CreateChatCompletionResponse,
+        CreateEmbeddingRequestArgs, CreateEmbeddingResponse, FunctionName,
     },
     Client as OpenAIClient,
 };
+use dashmap::mapref::entry::Entry;
+use dashmap::DashMap;
+use futures::future::{BoxFuture, FutureExt, Shared};
 use futures::{Stream, StreamExt};
+use std::collections::{HashMap, HashSet};
 use std::pin::Pin;
-use std::sync::Arc;
+use std::sync::{Arc, RwLock};
 use tokio::sync::Mutex;
 
+/// How long a model details lookup may delay the first request to a model
+const MODEL_DETAILS_TIMEOUT: std::time::Duration = std::time::Duration::from_secs(10);
+
+/// Stream of chat completion chunks
+pub type ChatStream = Pin<Box<dyn Stream<Item = Result<StreamChunk, LlmError>> + Send>>;
+
+/// A chat generation that concurrent identical requests can await together
+type SharedChat = Shared<BoxFuture<'static, Result<ChatResponse, LlmError>>>;
+
 /// LLM client with hot-swapping support
 #[derive(Clone)]
 pub struct Client {
     client: OpenAIClient<OpenAIConfig>,
+    /// URLs and headers of the OpenAI-compatible API, for the requests sent
+    /// without `client` so error responses keep their headers
+    api: OpenAIConfig,
     config: LlmConfig,
     cache_manager: Arc<Mutex<CacheManager>>,
     router: Arc<ModelRouter>,
+    /// Models known to reject streaming requests
+    non_streaming_models: Arc<RwLock<HashSet<String>>>,
+    /// HTTP client for backend APIs beyond the OpenAI-compatible ones
+    http: reqwest::Client,
+    /// `/api/show` results by model; `None` when the lookup failed
+    model_details: Arc<RwLock<HashMap<String, Option<ModelDetails>>>>,
+    /// Coalesced chats still generating, by request key
+    in_flight: Arc<DashMap<String, SharedChat>>,
+    /// Fails requests fast while the backend is down
+    breaker: Arc<CircuitBreaker>,
 }
 
 impl Client {
     pub fn new(config: &LlmConfig) -> Result<Self> {
         let openai_config = OpenAIConfig::new().with_api_base(&config.base_url);
-        let client = OpenAIClient::with_config(openai_config);
+        let client = OpenAIClient::with_config(openai_config.clone())
+            .with_http_client(crate::network::client_builder()?.build()?);
 
         let cache_manager = CacheManager::new(config);
         let router = ModelRouter::new(config)?;
 
         Ok(Self {
             client,
+            api: openai_config,
             config: config.clone(),
             cache_manager: Arc::new(Mutex::new(cache_manager)),
             router: Arc::new(router),
+            non_streaming_models: Arc::new(RwLock::new(HashSet::new())),
+            http: crate::network::client_builder()?.build()?,
+            model_details: Arc::new(RwLock::new(HashMap::new())),
+            in_flight: Arc::new(DashMap::new()),
+            breaker: Arc::new(CircuitBreaker::new(&config.circuit_breaker)),
+        })
+    }
+
+    /// Send chat request with automatic model routing and hot-swapping.
+    ///
+    /// If the chosen model is unavailable or times out, the request is retried
+    /// once on the fallback model and `fallback_from` records the original.
+    ///
+    /// With `coalesce_requests` set, identical deterministic requests made
+    /// while one is generating wait for its response instead.
+    pub async fn chat(&self, request: ChatRequest) -> Result<ChatResponse, LlmError> {
+        let Some(key) = self.coalesce_key(&request) else {
+            return self.chat_routed(request).await;
+        };
+
+        let chat = match self.in_flight.entry(key.clone()) {
+            Entry::Occupied(entry) => {
+                tracing::debug!("Joining identical in-flight chat request");
+                entry.get().clone()
+            }
+            Entry::Vacant(entry) => {
+                let client = self.clone();
+                let chat = async move {
+                    let response = client.chat_routed(request).await;
+                    client.in_flight.remove(&key);
+                    response
+                }
+                .boxed()
+                .shared();
+                entry.insert(chat.clone());
+                chat
+            }
+        };
+        chat.await
+    }
+
+    /// Key identifying requests that may share a generation: non-streaming
+    /// chats at temperature 0 with the same model, messages, tools (whole
+    /// definitions, as same-named tools may differ in their parameters) and
+    /// tool choice
+    fn coalesce_key(&self, request: &ChatRequest) -> Option<String> {
+        if !self.config.coalesce_requests || request.temperature != Some(0.0) {
+            return None;
+        }
+        let messages: Vec<(&str, &str)> = request
+            .messages
+            .iter()
+            .map(|m| (m.role.as_str(), m.content.trim()))
+            .collect();
+        serde_json::to_string(&(
+            self.resolve_model(request),
+            messages,
+            &request.tools,
+            request.max_tokens,
+            request.seed,
+            &request.prefill,
+            &request.tool_choice,
+        ))
+        .ok()
+    }
+
+    #[tracing::instrument(
+        name = "llm.chat",
+        skip_all,
+        fields(
+            model = tracing::field::Empty,
+            prompt_tokens = tracing::field::Empty,
+            completion_tokens = tracing::field::Empty,
+            fallback_from = tracing::field::Empty,
+        )
+    )]
+    async fn chat_routed(&self, request: ChatRequest) -> Result<ChatResponse, LlmError> {
+        let model = self.resolve_model(&request);
+        let fallback = self.router.fallback_for(&model);
+
+        self.breaker.check()?;
+        let result = with_fallback(&model, fallback, |model| {
+            self.chat_with_model(model, request.clone())
         })
+        .await;
+        self.breaker.record(&result);
+        let (mut response, fallback_from) = result?;
+
+        let span = tracing::Span::current();
+        span.record("model", response.model.as_str());
+        if let Some(usage) = &response.usage {
+            span.record("prompt_tokens", usage.prompt_tokens);
+            span.record("completion_tokens", usage.completion_tokens);
+        }
+        if let Some(from) = &fallback_from {
+            span.record("fallback_from", from.as_str());
+        }
+
+        response.fallback_from = fallback_from;
+        Ok(response)
     }
 
-    /// Send chat request with automatic model routing and hot-swapping
-    pub async fn chat(&self, request: ChatRequest) -> Result<ChatResponse> {
-        // Determine which model to use via routing
-        let model = if request.model.is_empty() {
+    /// Determine which model to use via routing
+    fn resolve_model(&self, request: &ChatRequest) -> String {
+        if request.model.is_empty() {
             // Auto-route based on last user message
             let last_message = request
                 .messages
@@ -58,9 +187,18 @@ impl Client {
 
             self.router.route(last_message).to_string()
         } else {
-            // Use explicitly specified model
-            request.model.clone()
-        };
+            // Use explicitly specified model or role
+            self.router.resolve(&request.model).to_string()
+        }
+    }
+
+    /// Send a chat request to a specific model
+    async fn chat_with_model(
+        &self,
+        model: String,
+        mut request: ChatRequest,
+    ) -> Result<ChatResponse, LlmError> {
+        self.drop_unsupported_tools(&model, &mut request).await;
 
         // Get keep_alive from cache strategy
         let keep_alive = {
@@ -75,13 +213,7 @@ impl Client {
             request.messages.len()
         );
 
-        // Convert messages
-        let messages: Result<Vec<ChatCompletionRequestMessage>> = request
-            .messages
-            .iter()
-            .map(|msg| self.convert_message(msg))
-            .collect();
-        let messages = messages?;
+        let messages = self.request_messages(&request)?;
 
         // Build request
         let mut req_builder = CreateChatCompletionRequestArgs::default();
@@ -96,56 +228,31 @@ impl Client {
             req_builder.temperature(temperature);
         }
 
-        // Add tools if provided
-        if let Some(tools) = request.tools {
-            // Convert our ToolDefinition to OpenAI format
-            let openai_tools: Vec<serde_json::Value> = tools
-                .into_iter()
-                .map(|tool| {
-                    serde_json::json!({
-                        "type": "function",
-                        "function": {
-                            "name": tool.name,
-                            "description": tool.description,
-                            "parameters": tool.parameters,
-                        }
-                    })
-                })
-                .collect();
-
-            if !openai_tools.is_empty() {
-                let converted_tools: Vec<async_openai::types::ChatCompletionTool> = openai_tools
-                    .into_iter()
-                    .filter_map(|tool| serde_json::from_value(tool).ok())
-                    .collect();
-
-                if !converted_tools.is_empty() {
-                    req_builder.tools(converted_tools);
-                    // Use auto tool choice to let the model decide
-                    req_builder
-                        .tool_choice(async_openai::types::ChatCompletionToolChoiceOption::Auto);
-                }
-            }
+        if let Some(seed) = request.seed {
+            req_builder.seed(seed);
         }
 
-        let req = req_builder
-            .build()
-            .context("Failed to build chat completion request")?;
+        if let Some((tools, tool_choice)) = request_tools(&request) {
+            req_builder.tools(tools);
+            req_builder.tool_choice(tool_choice);
+        }
+
+        let req = req_builder.build()?;
 
         // Send request to Ollama/LLM backend
-        let response = self
-            .client
-            .chat()
-            .create(req)
-            .await
-            .context("Failed to get chat completion")?;
+        let response: CreateChatCompletionResponse =
+            self.post_api("/chat/completions", &req).await?;
 
         let choice = response
             .choices
             .first()
-            .context("No choices in chat completion response")?;
+            .ok_or_else(|| LlmError::Backend("No choices in chat completion response".into()))?;
 
         let content = choice.message.content.clone().unwrap_or_default();
+        let content = match &request.prefill {
+            Some(prefill) => format!("{}{}", prefill, content),
+            None => content,
+        };
 
         // Extract tool calls if present
         let tool_calls = choice.message.tool_calls.as_ref().map(|calls| {
@@ -193,9 +300,11 @@ impl Client {
         Ok(ChatResponse {
             content,
             model: response.model,
-            finish_reason: choice.finish_reason.as_ref().map(|r| format!("{:?}", r)),
+            finish_reason: choice.finish_reason.as_ref().map(finish_reason_name),
             usage,
             tool_calls,
+            fallback_from: None,
+            system_fingerprint: response.system_fingerprint.clone(),
         })
     }
 
@@ -203,32 +312,217 @@ impl Client {
         &self.config.models.primary
     }
 
+    /// Model named by a model name or `role:<name>` reference
+    pub fn resolve_model_name<'a>(&'a self, model: &'a str) -> &'a str {
+        self.router.resolve(model)
+    }
+
+    /// Model configured for a role of `llm.models`, if any
+    pub fn model_for_role(&self, role: &str) -> Option<&str> {
+        self.router.model_for_role(role)
+    }
+
+    /// Load the primary model with a one-token completion, so the first
+    /// message does not wait for it. Goes to the model itself, not routing
+    /// or fallbacks, and uses the cache strategy's keep_alive like any chat.
+    pub async fn warm_up(&self) -> Result<String, LlmError> {
+        let model = self.config.models.primary.clone();
+        let request = ChatRequest {
+            model: model.clone(),
+            messages: vec![ChatMessage {
+                role: "user".to_string(),
+                content: "Hi".to_string(),
+            }],
+            temperature: Some(0.0),
+            max_tokens: Some(1),
+            tools: None,
+            seed: None,
+            prefill: None,
+            tool_choice: ToolChoice::None,
+        };
+        self.chat_with_model(model.clone(), request).await?;
+        Ok(model)
+    }
+
+    /// Models recently used, as tracked by the cache manager
+    pub async fn loaded_models(&self) -> Vec<String> {
+        let mut models = self.cache_manager.lock().await.loaded_models();
+        models.sort();
+        models
+    }
+
+    /// Context length, families, quantization and tool support of a model,
+    /// looked up once through Ollama's `/api/show`.
+    ///
+    /// Returns `None` for other providers and when the lookup fails, so
+    /// callers fall back to their configured defaults.
+    pub async fn model_details(&self, model: &str) -> Option<ModelDetails> {
+        if self.config.provider != "ollama" {
+            return None;
+        }
+        if let Some(details) = self.model_details.read().unwrap().get(model) {
+            return details.clone();
+        }
+
+        let details = match self.fetch_model_details(model).await {
+            Ok(details) => {
+                tracing::debug!("Model details for {}: {:?}", model, details);
+                Some(details)
+            }
+            Err(e) => {
+                tracing::warn!("Failed to look up details of model {}: {}", model, e);
+                None
+            }
+        };
+        self.model_details
+            .write()
+            .unwrap()
+            .insert(model.to_string(), details.clone());
+        details
+    }
+
+    async fn fetch_model_details(&self, model: &str) -> Result<ModelDetails> {
+        // The OpenAI-compatible API lives under /v1, Ollama's own API beside it
+        let base_url = self.config.base_url.trim_end_matches('/');
+        let root = base_url.strip_suffix("/v1").unwrap_or(base_url);
+
+        let show: serde_json::Value = self
+            .http
+            .post(format!("{}/api/show", root))
+            .timeout(MODEL_DETAILS_TIMEOUT)
+            .json(&serde_json::json!({ "model": model }))
+            .send()
+            .await?
+            .error_for_status()?
+            .json()
+            .await?;
+        Ok(ModelDetails::from_show(&show))
+    }
+
+    /// Whether a model can be sent tools: the configured `tool_support`,
+    /// else what the backend reports, else assumed
+    pub async fn supports_tools(&self, model: &str) -> bool {
+        if let Some(supported) = self.config.supports_tools(model) {
+            return supported;
+        }
+        let details = self.model_details(model).await;
+        details.and_then(|d| d.supports_tools).unwrap_or(true)
+    }
+
+    /// Leave out the tools for models known not to support tool calling
+    async fn drop_unsupported_tools(&self, model: &str, request: &mut ChatRequest) {
+        if request.tools.as_ref().is_none_or(|tools| tools.is_empty()) {
+            return;
+        }
+        if !self.supports_tools(model).await {
+            tracing::info!(
+                "Model {} does not support tool calling, sending the request without tools",
+                model
+            );
+            request.tools = None;
+        }
+    }
+
+    /// Embed texts with the given embedding model (or `role:<name>`), one
+    /// vector per input
+    pub async fn embed(&self, model: &str, inputs: Vec<String>) -> Result<Vec<Vec<f32>>, LlmError> {
+        let model = self.router.resolve(model);
+        let request = CreateEmbeddingRequestArgs::default()
+            .model(model)
+            .input(inputs)
+            .build()?;
+
+        let response: CreateEmbeddingResponse = self.post_api("/embeddings", &request).await?;
+        let mut data = response.data;
+        data.sort_by_key(|embedding| embedding.index);
+
+        Ok(data
+            .into_iter()
+            .map(|embedding| embedding.embedding)
+            .collect())
+    }
+
+    /// POST a request to the OpenAI-compatible API. Error responses are
+    /// classified with their `Retry-After` header, and rate limits are not
+    /// retried here: the caller decides whether to wait.
+    async fn post_api<T: serde::de::DeserializeOwned>(
+        &self,
+        path: &str,
+        body: &impl serde::Serialize,
+    ) -> Result<T, LlmError> {
+        let response = self
+            .http
+            .post(self.api.url(path))
+            .headers(self.api.headers())
+            .json(body)
+            .send()
+            .await?;
+
+        let status = response.status();
+        if !status.is_success() {
+            let retry_after = response
+                .headers()
+                .get(reqwest::header::RETRY_AFTER)
+                .and_then(|value| value.to_str().ok())
+                .map(str::to_string);
+            let body = response.text().await.unwrap_or_default();
+            return Err(LlmError::from_response(
+                status.as_u16(),
+                retry_after.as_deref(),
+                &body,
+            ));
+        }
+
+        let body = response.bytes().await?;
+        serde_json::from_slice(&body)
+            .map_err(|e| LlmError::Backend(format!("Invalid response from the backend: {}", e)))
+    }
+
     /// Route a message to the appropriate model based on content
     pub fn route_model(&self, content: &str) -> &str {
         self.router.route(content)
     }
 
-    /// Stream chat completion (for streaming responses)
-    pub async fn chat_stream(
-        &self,
-        request: ChatRequest,
-    ) -> Result<Pin<Box<dyn Stream<Item = Result<StreamChunk>> + Send>>> {
-        // Determine which model to use via routing
-        let model = if request.model.is_empty() {
-            // Auto-route based on last user message
-            let last_message = request
-                .messages
-                .iter()
-                .rev()
-                .find(|m| m.role == "user")
-                .map(|m| m.content.as_str())
-                .unwrap_or("");
+    /// Stream chat completion (for streaming responses), falling back to
+    /// another model if the chosen one is unavailable
+    pub async fn chat_stream(&self, request: ChatRequest) -> Result<ChatStream, LlmError> {
+        let model = self.resolve_model(&request);
+        let fallback = self.router.fallback_for(&model);
 
-            self.router.route(last_message).to_string()
-        } else {
-            // Use explicitly specified model
-            request.model.clone()
-        };
+        self.breaker.check()?;
+        let result = with_fallback(&model, fallback, |model| {
+            self.chat_stream_with_model(model, request.clone())
+        })
+        .await;
+        self.breaker.record(&result);
+        let (stream, _) = result?;
+
+        Ok(stream)
+    }
+
+    /// State of the circuit breaker guarding the backend
+    pub fn circuit_state(&self) -> CircuitState {
+        self.breaker.state()
+    }
+
+    /// Whether a model has been seen rejecting streaming requests
+    pub fn supports_streaming(&self, model: &str) -> bool {
+        !self.non_streaming_models.read().unwrap().contains(model)
+    }
+
+    /// Open a streaming chat completion against a specific model.
+    ///
+    /// Backends that cannot stream are answered with a single non-streaming
+    /// call instead, and remembered so later requests skip the probe.
+    async fn chat_stream_with_model(
+        &self,
+        model: String,
+        mut request: ChatRequest,
+    ) -> Result<ChatStream, LlmError> {
+        self.drop_unsupported_tools(&model, &mut request).await;
+        if !self.supports_streaming(&model) {
+            return self.chat_as_stream(model, request).await;
+        }
 
         // Get keep_alive from cache strategy
         let keep_alive = {
@@ -243,13 +537,7 @@ impl Client {
             request.messages.len()
         );
 
-        // Convert messages
-        let messages: Result<Vec<ChatCompletionRequestMessage>> = request
-            .messages
-            .iter()
-            .map(|msg| self.convert_message(msg))
-            .collect();
-        let messages = messages?;
+        let messages = self.request_messages(&request)?;
 
         // Build request
         let mut req_builder = CreateChatCompletionRequestArgs::default();
@@ -264,48 +552,27 @@ impl Client {
             req_builder.temperature(temperature);
         }
 
-        // Add tools if provided
-        if let Some(tools) = request.tools {
-            // Convert our ToolDefinition to OpenAI format
-            let openai_tools: Vec<serde_json::Value> = tools
-                .into_iter()
-                .map(|tool| {
-                    serde_json::json!({
-                        "type": "function",
-                        "function": {
-                            "name": tool.name,
-                            "description": tool.description,
-                            "parameters": tool.parameters,
-                        }
-                    })
-                })
-                .collect();
-
-            if !openai_tools.is_empty() {
-                let converted_tools: Vec<async_openai::types::ChatCompletionTool> = openai_tools
-                    .into_iter()
-                    .filter_map(|tool| serde_json::from_value(tool).ok())
-                    .collect();
-
-                if !converted_tools.is_empty() {
-                    req_builder.tools(converted_tools);
-                    req_builder
-                        .tool_choice(async_openai::types::ChatCompletionToolChoiceOption::Auto);
-                }
-            }
+        if let Some(seed) = request.seed {
+            req_builder.seed(seed);
         }
 
-        let req = req_builder
-            .build()
-            .context("Failed to build chat completion request")?;
+        if let Some((tools, tool_choice)) = request_tools(&request) {
+            req_builder.tools(tools);
+            req_builder.tool_choice(tool_choice);
+        }
+
+        let req = req_builder.build()?;
 
         // Send streaming request to Ollama/LLM backend
-        let stream = self
-            .client
-            .chat()
-            .create_stream(req)
-            .await
-            .context("Failed to create chat stream")?;
+        let stream = match self.client.chat().create_stream(req).await {
+            Ok(stream) => stream,
+            Err(e) => match LlmError::from(e) {
+                LlmError::StreamingUnsupported(reason) => {
+                    return self.streaming_fallback(model, request, &reason).await;
+                }
+                other => return Err(other),
+            },
+        };
 
         // Mark model as used in cache
         {
@@ -316,7 +583,7 @@ impl Client {
         // Convert stream items to our StreamChunk type
         let model_clone = model.clone();
         let mapped_stream = stream.map(move |result| {
-            result.context("Stream error").map(|response| {
+            result.map_err(LlmError::from).map(|response| {
                 let choice = response.choices.first();
                 let content = choice
                     .and_then(|c| c.delta.content.clone())
@@ -338,7 +605,7 @@ impl Client {
 
                 let finish_reason = choice
                     .and_then(|c| c.finish_reason.as_ref())
-                    .map(|r| format!("{:?}", r));
+                    .map(finish_reason_name);
 
                 StreamChunk {
                     content,
@@ -350,7 +617,101 @@ impl Client {
             })
         });
 
-        Ok(Box::pin(mapped_stream))
+        // The backend only streams the continuation, so the prefill goes first
+        let prefill = request.prefill.clone().map(|prefill| {
+            Ok(StreamChunk {
+                content: Some(prefill),
+                tool_calls: None,
+                finish_reason: None,
+                model: Some(model.clone()),
+                usage: None,
+            })
+        });
+
+        // A backend without SSE support only reveals it on the first event
+        let mut mapped_stream = Box::pin(mapped_stream);
+        match mapped_stream.next().await {
+            Some(Err(LlmError::StreamingUnsupported(reason))) => {
+                self.streaming_fallback(model, request, &reason).await
+            }
+            // Refused before anything was streamed, so callers can wait and retry
+            Some(Err(rate_limited @ LlmError::RateLimited { .. })) => Err(rate_limited),
+            first => Ok(Box::pin(
+                futures::stream::iter(prefill.into_iter().chain(first)).chain(mapped_stream),
+            )),
+        }
+    }
+
+    /// Remember that a model cannot stream and answer without streaming
+    async fn streaming_fallback(
+        &self,
+        model: String,
+        request: ChatRequest,
+        reason: &str,
+    ) -> Result<ChatStream, LlmError> {
+        tracing::warn!(
+            "Model {} does not support streaming ({}), falling back to a single response",
+            model,
+            reason
+        );
+        self.non_streaming_models
+            .write()
+            .unwrap()
+            .insert(model.clone());
+
+        self.chat_as_stream(model, request).await
+    }
+
+    /// Run a non-streaming completion and expose it as a one-chunk stream
+    async fn chat_as_stream(
+        &self,
+        model: String,
+        request: ChatRequest,
+    ) -> Result<ChatStream, LlmError> {
+        let response = self.chat_with_model(model, request).await?;
+
+        let tool_calls = response.tool_calls.map(|calls| {
+            calls
+                .into_iter()
+                .enumerate()
+                .map(|(index, tc)| ToolCallChunk {
+                    index,
+                    id: Some(tc.id),
+                    name: Some(tc.name),
+                    arguments: Some(tc.arguments),
+                })
+                .collect::<Vec<_>>()
+        });
+
+        let chunk = StreamChunk {
+            content: Some(response.content).filter(|s| !s.is_empty()),
+            tool_calls: tool_calls.filter(|tc| !tc.is_empty()),
+            finish_reason: response.finish_reason,
+            model: Some(response.model),
+            usage: response.usage,
+        };
+
+        Ok(Box::pin(futures::stream::once(async move { Ok(chunk) })))
+    }
+
+    /// Messages of a request in API form, ending with the prefill as a partial
+    /// assistant message that the model continues
+    fn request_messages(
+        &self,
+        request: &ChatRequest,
+    ) -> Result<Vec<ChatCompletionRequestMessage>, LlmError> {
+        let prefill = request.prefill.as_ref().map(|prefill| ChatMessage {
+            role: "assistant".to_string(),
+            content: prefill.clone(),
+        });
+
+        request
+            .messages
+            .iter()
+            .chain(prefill.as_ref())
+            .map(|msg| self.convert_message(msg))
+            .collect::<Result<Vec<ChatCompletionRequestMessage>>>()
+            .map_err(|e| LlmError::BadRequest(e.to_string()))
     }
 
     fn convert_message(&self, msg: &ChatMessage) -> Result<ChatCompletionRequestMessage> {
@@ -371,3 +732,506 @@ impl Client {
         }
     }
 }
+
+/// Finish reason as the API spells it (`stop`, `tool_calls`, ...)
+/// Tools in OpenAI format with the request's tool choice; none when there
+/// are no tools or the choice is `none`
+fn request_tools(
+    request: &ChatRequest,
+) -> Option<(Vec<ChatCompletionTool>, ChatCompletionToolChoiceOption)> {
+    let tool_choice = match &request.tool_choice {
+        ToolChoice::None => return None,
+        ToolChoice::Auto => ChatCompletionToolChoiceOption::Auto,
+        ToolChoice::Tool(name) => {
+            ChatCompletionToolChoiceOption::Named(ChatCompletionNamedToolChoice {
+                r#type: ChatCompletionToolType::Function,
+                function: FunctionName { name: name.clone() },
+            })
+        }
+    };
+    let tools: Vec<ChatCompletionTool> = request
+        .tools
+        .iter()
+        .flatten()
+        .filter_map(|tool| {
+            serde_json::from_value(serde_json::json!({
+                "type": "function",
+                "function": {
+                    "name": tool.name,
+                    "description": tool.description,
+                    "parameters": tool.parameters,
+                }
+            }))
+            .ok()
+        })
+        .collect();
+    (!tools.is_empty()).then_some((tools, tool_choice))
+}
+
+fn finish_reason_name(reason: &async_openai::types::FinishReason) -> String {
+    serde_json::to_value(reason)
+        .ok()
+        .and_then(|value| value.as_str().map(str::to_string))
+        .unwrap_or_else(|| format!("{:?}", reason).to_lowercase())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::{CacheConfig, CircuitBreakerConfig, LlmModels};
+
+    /// Completion body as returned by a backend that ignores `stream: true`
+    const COMPLETION: &str = r#"{
+        "id": "chatcmpl-1",
+        "object": "chat.completion",
+        "created": 1700000000,
+        "model": "plain-model",
+        "choices": [{
+            "index": 0,
+            "message": {"role": "assistant", "content": "Hello there"},
+            "finish_reason": "stop"
+        }],
+        "usage": {"prompt_tokens": 5, "completion_tokens": 2, "total_tokens": 7}
+    }"#;
+
+    fn test_config(base_url: String) -> LlmConfig {
+        LlmConfig {
+            provider: "ollama".to_string(),
+            base_url,
+            models: LlmModels {
+                primary: "plain-model".to_string(),
+                code: None,
+                fast: None,
+                roles: Default::default(),
+            },
+            keep_alive: None,
+            cache: CacheConfig {
+                cache_type: "ram".to_string(),
+                max_models: 1,
+                eviction: "lru".to_string(),
+            },
+            routing: None,
+            context_windows: Default::default(),
+            tool_support: Default::default(),
+            tokenizers: Default::default(),
+            coalesce_requests: false,
+            circuit_breaker: Default::default(),
+            warm_up: false,
+        }
+    }
+
+    fn request() -> ChatRequest {
+        ChatRequest {
+            messages: vec![ChatMessage {
+                role: "user".to_string(),
+                content: "Hi".to_string(),
+            }],
+            model: String::new(),
+            temperature: None,
+            max_tokens: None,
+            tools: None,
+            seed: None,
+            prefill: None,
+            tool_choice: Default::default(),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_non_streaming_backend_falls_back_to_single_chunk() {
+        let mut server = mockito::Server::new_async().await;
+        // Probe + fallback on the first request, a direct call on the second
+        let mock = server
+            .mock("POST", "/chat/completions")
+            .with_header("content-type", "application/json")
+            .with_body(COMPLETION)
+            .expect(3)
+            .create_async()
+            .await;
+
+        let client = Client::new(&test_config(server.url())).unwrap();
+
+        let chunks: Vec<_> = client.chat_stream(request()).await.unwrap().collect().await;
+        assert_eq!(chunks.len(), 1);
+        let chunk = chunks[0].as_ref().unwrap();
+        assert_eq!(chunk.content.as_deref(), Some("Hello there"));
+        assert!(chunk.finish_reason.is_some());
+        assert_eq!(chunk.usage.as_ref().map(|u| u.total_tokens), Some(7));
+        assert!(!client.supports_streaming("plain-model"));
+
+        let chunks: Vec<_> = client.chat_stream(request()).await.unwrap().collect().await;
+        assert_eq!(chunks.len(), 1);
+
+        mock.assert_async().await;
+    }
+
+    #[tokio::test]
+    async fn test_embed_returns_vectors_in_input_order() {
+        let mut server = mockito::Server::new_async().await;
+        let mock = server
+            .mock("POST", "/embeddings")
+            .match_body(mockito::Matcher::PartialJson(serde_json::json!({
+                "model": "embed-model",
+                "input": ["first", "second"]
+            })))
+            .with_header("content-type", "application/json")
+            .with_body(
+                r#"{
+                    "object": "list",
+                    "model": "embed-model",
+                    "data": [
+                        {"object": "embedding", "index": 1, "embedding": [0.0, 1.0]},
+                        {"object": "embedding", "index": 0, "embedding": [1.0, 0.0]}
+                    ],
+                    "usage": {"prompt_tokens": 2, "total_tokens": 2}
+                }"#,
+            )
+            .create_async()
+            .await;
+
+        let client = Client::new(&test_config(server.url())).unwrap();
+        let vectors = client
+            .embed("embed-model", vec!["first".into(), "second".into()])
+            .await
+            .unwrap();
+
+        assert_eq!(vectors, vec![vec![1.0, 0.0], vec![0.0, 1.0]]);
+        mock.assert_async().await;
+    }
+
+    #[tokio::test]
+    async fn test_seed_reaches_the_backend() {
+        let mut server = mockito::Server::new_async().await;
+        // The streaming probe, its fallback and the plain chat all carry it
+        let mock = server
+            .mock("POST", "/chat/completions")
+            .match_body(mockito::Matcher::PartialJson(
+                serde_json::json!({"seed": 42}),
+            ))
+            .with_header("content-type", "application/json")
+            .with_body(COMPLETION.replace(
+                r#""model": "plain-model","#,
+                r#""model": "plain-model", "system_fingerprint": "fp_test","#,
+            ))
+            .expect(3)
+            .create_async()
+            .await;
+
+        let client = Client::new(&test_config(server.url())).unwrap();
+        let seeded = ChatRequest {
+            seed: Some(42),
+            ..request()
+        };
+
+        let response = client.chat(seeded.clone()).await.unwrap();
+        assert_eq!(response.system_fingerprint.as_deref(), Some("fp_test"));
+        let chunks: Vec<_> = client.chat_stream(seeded).await.unwrap().collect().await;
+        assert_eq!(chunks.len(), 1);
+
+        mock.assert_async().await;
+    }
+
+    #[tokio::test]
+    async fn test_tool_choice_reaches_the_backend() {
+        let mut server = mockito::Server::new_async().await;
+        let mut config = test_config(server.url());
+        config.tool_support.insert("plain-model".to_string(), true);
+        let client = Client::new(&config).unwrap();
+
+        // `none` leaves out the tools along with the choice
+        for (tool_choice, expected) in [
+            (ToolChoice::Auto, Some(serde_json::json!("auto"))),
+            (
+                ToolChoice::Tool("calculate".to_string()),
+                Some(serde_json::json!({"type": "function", "function": {"name": "calculate"}})),
+            ),
+            (ToolChoice::None, None),
+        ] {
+            let mock = server
+                .mock("POST", "/chat/completions")
+                .match_request(move |request| {
+                    let body: serde_json::Value =
+                        serde_json::from_str(&request.utf8_lossy_body().unwrap()).unwrap();
+                    body.get("tool_choice") == expected.as_ref()
+                        && body.get("tools").is_some() == expected.is_some()
+                })
+                .with_header("content-type", "application/json")
+                .with_body(COMPLETION)
+                .create_async()
+                .await;
+
+            let request = ChatRequest {
+                tools: Some(vec![super::super::ToolDefinition {
+                    name: "calculate".to_string(),
+                    description: "Evaluate arithmetic".to_string(),
+                    parameters: serde_json::json!({"type": "object", "properties": {}}),
+                }]),
+                tool_choice,
+                ..request()
+            };
+            client.chat(request).await.unwrap();
+
+            mock.assert_async().await;
+            mock.remove_async().await;
+        }
+    }
+
+    #[tokio::test]
+    async fn test_warm_up_targets_the_primary_model() {
+        let mut server = mockito::Server::new_async().await;
+        let mock = server
+            .mock("POST", "/chat/completions")
+            .match_request(|request| {
+                let body: serde_json::Value =
+                    serde_json::from_str(&request.utf8_lossy_body().unwrap()).unwrap();
+                body["model"] == "plain-model"
+                    && body.get("tools").is_none()
+                    && body["max_tokens"] == 1
+            })
+            .with_header("content-type", "application/json")
+            .with_body(COMPLETION)
+            .create_async()
+            .await;
+
+        let client = Client::new(&test_config(server.url())).unwrap();
+        assert_eq!(client.warm_up().await.unwrap(), "plain-model");
+        mock.assert_async().await;
+    }
+
+    #[tokio::test]
+    async fn test_prefill_starts_the_reply() {
+        let mut server = mockito::Server::new_async().await;
+        // The prefill is sent as a trailing partial assistant message
+        let mock = server
+            .mock("POST", "/chat/completions")
+            .match_body(mockito::Matcher::Regex(r#""content":"Well, ""#.to_string()))
+            .with_header("content-type", "application/json")
+            .with_body(COMPLETION)
+            .expect(3)
+            .create_async()
+            .await;
+
+        let client = Client::new(&test_config(server.url())).unwrap();
+        let primed = ChatRequest {
+            prefill: Some("Well, ".to_string()),
+            ..request()
+        };
+
+        let response = client.chat(primed.clone()).await.unwrap();
+        assert_eq!(response.content, "Well, Hello there");
+
+        let chunks: Vec<_> = client.chat_stream(primed).await.unwrap().collect().await;
+        let streamed: String = chunks
+            .iter()
+            .filter_map(|chunk| chunk.as_ref().unwrap().content.clone())
+            .collect();
+        assert_eq!(streamed, "Well, Hello there");
+
+        mock.assert_async().await;
+    }
+
+    #[tokio::test]
+    async fn test_model_details_from_show_are_cached() {
+        let mut server = mockito::Server::new_async().await;
+        let show = server
+            .mock("POST", "/api/show")
+            .match_body(mockito::Matcher::PartialJson(
+                serde_json::json!({"model": "plain-model"}),
+            ))
+            .with_header("content-type", "application/json")
+            .with_body(
+                r#"{
+                    "parameters": "temperature 0.7",
+                    "details": {
+                        "family": "gemma",
+                        "families": ["gemma"],
+                        "parameter_size": "2B",
+                        "quantization_level": "Q4_K_M"
+                    },
+                    "model_info": {
+                        "general.architecture": "gemma",
+                        "gemma.context_length": 8192
+                    },
+                    "capabilities": ["completion"]
+                }"#,
+            )
+            .expect(1)
+            .create_async()
+            .await;
+        // The model cannot call tools, so none are sent
+        let completion = server
+            .mock("POST", "/v1/chat/completions")
+            .match_request(|request| {
+                !request
+                    .utf8_lossy_body()
+                    .is_ok_and(|body| body.contains("\"tools\""))
+            })
+            .with_header("content-type", "application/json")
+            .with_body(COMPLETION)
+            .create_async()
+            .await;
+
+        let client = Client::new(&test_config(format!("{}/v1", server.url()))).unwrap();
+        let details = client.model_details("plain-model").await.unwrap();
+        assert_eq!(details.context_length, Some(8192));
+        assert_eq!(details.families, vec!["gemma"]);
+        assert_eq!(details.quantization.as_deref(), Some("Q4_K_M"));
+        assert_eq!(details.supports_tools, Some(false));
+        assert_eq!(details.supports_vision, Some(false));
+
+        let mut request = request();
+        request.model = "plain-model".to_string();
+        request.tools = Some(vec![super::super::ToolDefinition {
+            name: "calculate".to_string(),
+            description: "Evaluate arithmetic".to_string(),
+            parameters: serde_json::json!({"type": "object", "properties": {}}),
+        }]);
+        let response = client.chat(request).await.unwrap();
+        assert_eq!(response.content, "Hello there");
+
+        show.assert_async().await;
+        completion.assert_async().await;
+    }
+
+    #[tokio::test]
+    async fn test_model_details_unavailable_for_other_providers() {
+        let mut config = test_config("http://127.0.0.1:9/v1".to_string());
+        config.provider = "openai".to_string();
+        let client = Client::new(&config).unwrap();
+        assert!(client.model_details("gpt-4o").await.is_none());
+    }
+
+    #[tokio::test]
+    async fn test_identical_concurrent_chats_are_coalesced() {
+        let mut server = mockito::Server::new_async().await;
+        let mock = server
+            .mock("POST", "/chat/completions")
+            .with_header("content-type", "application/json")
+            .with_body(COMPLETION)
+            .expect(1)
+            .create_async()
+            .await;
+
+        let mut config = test_config(server.url());
+        config.coalesce_requests = true;
+        let client = Client::new(&config).unwrap();
+        let deterministic = ChatRequest {
+            temperature: Some(0.0),
+            ..request()
+        };
+
+        let (first, second) = tokio::join!(
+            client.chat(deterministic.clone()),
+            client.chat(deterministic.clone())
+        );
+        assert_eq!(first.unwrap().content, "Hello there");
+        assert_eq!(second.unwrap().content, "Hello there");
+        mock.assert_async().await;
+        assert!(client.in_flight.is_empty());
+
+        // Same-named tools with other parameters make another request
+        let with_tool = |parameters| ChatRequest {
+            tools: Some(vec![super::super::ToolDefinition {
+                name: "lookup".to_string(),
+                description: "Look something up".to_string(),
+                parameters,
+            }]),
+            ..deterministic.clone()
+        };
+        assert_ne!(
+            client.coalesce_key(&with_tool(serde_json::json!({"type": "object"}))),
+            client.coalesce_key(&with_tool(
+                serde_json::json!({"type": "object", "required": ["id"]})
+            ))
+        );
+    }
+
+    #[tokio::test]
+    async fn test_rate_limit_keeps_retry_after_and_holds_requests() {
+        let mut server = mockito::Server::new_async().await;
+        let limited = server
+            .mock("POST", "/chat/completions")
+            .with_status(429)
+            .with_header("retry-after", "7")
+            .with_header("content-type", "application/json")
+            .with_body(
+                r#"{"error":{"message":"Rate limit reached","type":"rate_limit_error","param":null,"code":null}}"#,
+            )
+            .expect(1)
+            .create_async()
+            .await;
+
+        let mut config = test_config(server.url());
+        config.circuit_breaker = CircuitBreakerConfig {
+            enabled: true,
+            failure_threshold: 2,
+            cooldown_secs: 1,
+        };
+        let client = Client::new(&config).unwrap();
+
+        assert!(matches!(
+            client.chat(request()).await,
+            Err(LlmError::RateLimited {
+                retry_after: Some(7)
+            })
+        ));
+        // Waits out the Retry-After without reaching the backend
+        assert!(matches!(
+            client.chat(request()).await,
+            Err(LlmError::RateLimited {
+                retry_after: Some(6 | 7)
+            })
+        ));
+        assert_eq!(client.circuit_state(), CircuitState::Closed);
+        limited.assert_async().await;
+    }
+
+    #[tokio::test]
+    async fn test_circuit_opens_and_recovers_after_cooldown() {
+        let mut server = mockito::Server::new_async().await;
+        let failing = server
+            .mock("POST", "/chat/completions")
+            .with_status(404)
+            .with_header("content-type", "application/json")
+            .with_body(
+                r#"{"error":{"message":"model 'plain-model' not found","type":"api_error","param":null,"code":null}}"#,
+            )
+            .expect(2)
+            .create_async()
+            .await;
+
+        let mut config = test_config(server.url());
+        config.circuit_breaker = CircuitBreakerConfig {
+            enabled: true,
+            failure_threshold: 2,
+            cooldown_secs: 1,
+        };
+        let client = Client::new(&config).unwrap();
+
+        for _ in 0..2 {
+            assert!(matches!(
+                client.chat(request()).await,
+                Err(LlmError::Unavailable(_))
+            ));
+        }
+        assert_eq!(client.circuit_state(), CircuitState::Open);
+        // Fails fast without reaching the backend
+        assert!(matches!(
+            client.chat(request()).await,
+            Err(LlmError::Unavailable(_))
+        ));
+        failing.assert_async().await;
+        failing.remove_async().await;
+
+        let healthy = server
+            .mock("POST", "/chat/completions")
+            .with_header("content-type", "application/json")
+            .with_body(COMPLETION)
+            .create_async()
+            .await;
+        tokio::time::sleep(std::time::Duration::from_millis(1100)).await;
+        assert_eq!(client.circuit_state(), CircuitState::HalfOpen);
+        assert_eq!(client.chat(request()).await.unwrap().content, "Hello there");
+        assert_eq!(client.circuit_state(), CircuitState::Closed);
+        healthy.assert_async().await;
+    }
+}
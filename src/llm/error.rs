@@ -0,0 +1,292 @@
+use async_openai::error::OpenAIError;
+use chrono::{DateTime, Utc};
+use std::time::Duration;
+
+/// Classified failure from the LLM backend
+#[derive(Debug, Clone, PartialEq, thiserror::Error)]
+pub enum LlmError {
+    /// The backend did not answer in time
+    #[error("LLM request timed out")]
+    Timeout,
+
+    /// The backend or requested model cannot serve requests right now
+    #[error("LLM backend unavailable: {0}")]
+    Unavailable(String),
+
+    /// The backend is throttling requests; `retry_after` is the number of
+    /// seconds it asked to wait, when it said
+    #[error("LLM rate limit exceeded{}", retry_hint(.retry_after))]
+    RateLimited { retry_after: Option<u64> },
+
+    /// The request itself was rejected (bad parameters, unknown role, ...)
+    #[error("Invalid LLM request: {0}")]
+    BadRequest(String),
+
+    /// The backend or model cannot serve server-sent event streams
+    #[error("LLM backend does not support streaming: {0}")]
+    StreamingUnsupported(String),
+
+    /// Any other backend failure
+    #[error("LLM backend error: {0}")]
+    Backend(String),
+}
+
+impl LlmError {
+    /// Classify an error response from the backend by its status, the value
+    /// of its `Retry-After` header and its body (an OpenAI-style error
+    /// object, or plain text)
+    pub(crate) fn from_response(status: u16, retry_after: Option<&str>, body: &str) -> Self {
+        let error = serde_json::from_str::<serde_json::Value>(body)
+            .ok()
+            .filter(|body| body["error"].is_object());
+        let classified = match error {
+            Some(body) => {
+                let field = |name: &str| body["error"][name].as_str().unwrap_or_default();
+                let details = format!(
+                    "{}: {} ({})",
+                    field("type"),
+                    field("message"),
+                    field("code")
+                );
+                // The status tells more than an error object that says nothing
+                match Self::from_api_error(&details, field("message")) {
+                    Self::Backend(message) => Self::from_status(status, message),
+                    other => other,
+                }
+            }
+            None => Self::from_status(status, body.trim().to_string()),
+        };
+
+        match classified {
+            Self::RateLimited { .. } => Self::RateLimited {
+                retry_after: retry_after.and_then(|value| parse_retry_after(value, Utc::now())),
+            },
+            other => other,
+        }
+    }
+
+    /// How long the backend asked to wait before the next request
+    pub fn retry_after(&self) -> Option<Duration> {
+        match self {
+            Self::RateLimited {
+                retry_after: Some(secs),
+            } => Some(Duration::from_secs(*secs)),
+            _ => None,
+        }
+    }
+
+    /// Classify an HTTP status code returned by the backend
+    fn from_status(status: u16, message: String) -> Self {
+        match status {
+            429 => Self::RateLimited { retry_after: None },
+            408 | 504 => Self::Timeout,
+            // Ollama answers 404 when the requested model is not pulled
+            404 | 502 | 503 => Self::Unavailable(message),
+            400..=499 => Self::BadRequest(message),
+            _ => Self::Backend(message),
+        }
+    }
+
+    /// Classify an OpenAI-style error object from its rendered details
+    /// (type, message and code) while keeping the plain message
+    fn from_api_error(details: &str, message: &str) -> Self {
+        let text = details.to_lowercase();
+
+        if text.contains("rate_limit") || text.contains("rate limit") {
+            Self::RateLimited { retry_after: None }
+        } else if is_streaming_unsupported(&text) {
+            Self::StreamingUnsupported(message.to_string())
+        } else if text.contains("timeout") || text.contains("timed out") {
+            Self::Timeout
+        } else if text.contains("not found")
+            || text.contains("model_not_found")
+            || text.contains("overloaded")
+            || text.contains("unavailable")
+            || text.contains("server_error")
+        {
+            Self::Unavailable(message.to_string())
+        } else if text.contains("invalid_request") {
+            Self::BadRequest(message.to_string())
+        } else {
+            Self::Backend(message.to_string())
+        }
+    }
+}
+
+impl From<reqwest::Error> for LlmError {
+    fn from(e: reqwest::Error) -> Self {
+        if e.is_timeout() {
+            Self::Timeout
+        } else if e.is_connect() {
+            Self::Unavailable(e.to_string())
+        } else if let Some(status) = e.status() {
+            Self::from_status(status.as_u16(), e.to_string())
+        } else {
+            Self::Backend(e.to_string())
+        }
+    }
+}
+
+impl From<OpenAIError> for LlmError {
+    fn from(err: OpenAIError) -> Self {
+        match err {
+            OpenAIError::Reqwest(e) => e.into(),
+            OpenAIError::ApiError(api) => Self::from_api_error(&api.to_string(), &api.message),
+            OpenAIError::StreamError(msg) if is_streaming_unsupported(&msg.to_lowercase()) => {
+                Self::StreamingUnsupported(msg)
+            }
+            // A stream request refused with 429; the headers are not kept
+            OpenAIError::StreamError(msg) if msg.to_lowercase().contains("status code: 429") => {
+                Self::RateLimited { retry_after: None }
+            }
+            OpenAIError::InvalidArgument(msg) => Self::BadRequest(msg),
+            other => Self::Backend(other.to_string()),
+        }
+    }
+}
+
+/// Seconds to wait from a `Retry-After` value: either a number of seconds
+/// or an HTTP date
+fn parse_retry_after(value: &str, now: DateTime<Utc>) -> Option<u64> {
+    let value = value.trim();
+    if let Ok(secs) = value.parse::<f64>() {
+        return (secs.is_finite() && secs >= 0.0).then(|| secs.ceil() as u64);
+    }
+    let at = DateTime::parse_from_rfc2822(value).ok()?;
+    Some((at.with_timezone(&Utc) - now).num_seconds().max(0) as u64)
+}
+
+/// Suffix of the rate limit message telling how long to wait
+fn retry_hint(retry_after: &Option<u64>) -> String {
+    match retry_after {
+        Some(secs) => format!(", retry in {}s", secs),
+        None => String::new(),
+    }
+}
+
+/// Whether a (lowercased) error message says streaming is not available.
+///
+/// Besides explicit refusals, a backend answering a stream request with a
+/// plain JSON body surfaces as an invalid `Content-Type` header.
+fn is_streaming_unsupported(text: &str) -> bool {
+    let refused = text.contains("stream")
+        && (text.contains("not supported")
+            || text.contains("unsupported")
+            || text.contains("does not support"));
+
+    refused || text.contains("invalid header value")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_status_classification() {
+        assert_eq!(
+            LlmError::from_status(429, String::new()),
+            LlmError::RateLimited { retry_after: None }
+        );
+        assert_eq!(LlmError::from_status(504, String::new()), LlmError::Timeout);
+        assert!(matches!(
+            LlmError::from_status(404, "model 'x' not found".into()),
+            LlmError::Unavailable(_)
+        ));
+        assert!(matches!(
+            LlmError::from_status(422, String::new()),
+            LlmError::BadRequest(_)
+        ));
+        assert!(matches!(
+            LlmError::from_status(500, String::new()),
+            LlmError::Backend(_)
+        ));
+    }
+
+    #[test]
+    fn test_rate_limited_response_keeps_retry_after() {
+        let err = LlmError::from_response(
+            429,
+            Some("7"),
+            r#"{"error": {"message": "Rate limit reached", "type": "requests"}}"#,
+        );
+        assert_eq!(
+            err,
+            LlmError::RateLimited {
+                retry_after: Some(7)
+            }
+        );
+        assert_eq!(err.retry_after(), Some(Duration::from_secs(7)));
+        assert_eq!(err.to_string(), "LLM rate limit exceeded, retry in 7s");
+
+        assert_eq!(
+            LlmError::from_response(429, None, "Too Many Requests"),
+            LlmError::RateLimited { retry_after: None }
+        );
+        assert_eq!(
+            LlmError::from_response(
+                404,
+                None,
+                r#"{"error": {"message": "model 'x' not found"}}"#
+            ),
+            LlmError::Unavailable("model 'x' not found".into())
+        );
+
+        let now = DateTime::parse_from_rfc2822("Wed, 21 Oct 2015 07:28:00 GMT")
+            .unwrap()
+            .with_timezone(&Utc);
+        assert_eq!(
+            parse_retry_after("Wed, 21 Oct 2015 07:28:30 GMT", now),
+            Some(30)
+        );
+        assert_eq!(parse_retry_after("1.5", now), Some(2));
+        assert_eq!(parse_retry_after("soon", now), None);
+
+        let err: LlmError =
+            OpenAIError::StreamError("Invalid status code: 429 Too Many Requests".into()).into();
+        assert_eq!(err, LlmError::RateLimited { retry_after: None });
+    }
+
+    #[test]
+    fn test_api_error_classification() {
+        assert_eq!(
+            LlmError::from_api_error("slow down (code: rate_limit_exceeded)", "slow down"),
+            LlmError::RateLimited { retry_after: None }
+        );
+        assert!(matches!(
+            LlmError::from_api_error("api_error: model 'llama3' not found", "model not found"),
+            LlmError::Unavailable(_)
+        ));
+        assert!(matches!(
+            LlmError::from_api_error("invalid_request_error: bad role", "bad role"),
+            LlmError::BadRequest(_)
+        ));
+        assert!(matches!(
+            LlmError::from_api_error("something odd", "something odd"),
+            LlmError::Backend(_)
+        ));
+    }
+
+    #[test]
+    fn test_streaming_unsupported_classification() {
+        let err: LlmError =
+            OpenAIError::StreamError("Invalid header value: \"application/json\"".into()).into();
+        assert!(matches!(err, LlmError::StreamingUnsupported(_)));
+
+        assert!(matches!(
+            LlmError::from_api_error(
+                "invalid_request_error: stream is not supported for this model",
+                "stream is not supported for this model"
+            ),
+            LlmError::StreamingUnsupported(_)
+        ));
+
+        let err: LlmError = OpenAIError::StreamError("Stream ended".into()).into();
+        assert!(matches!(err, LlmError::Backend(_)));
+    }
+
+    #[test]
+    fn test_invalid_argument_is_bad_request() {
+        let err: LlmError = OpenAIError::InvalidArgument("missing model".into()).into();
+        assert_eq!(err, LlmError::BadRequest("missing model".into()));
+    }
+}
@@ -1,10 +1,18 @@
+mod breaker;
 mod cache;
 mod client;
+mod error;
+mod model_details;
 mod routing;
+mod tokenizer;
 
+pub use breaker::{CircuitBreaker, CircuitState};
 pub use cache::{CacheManager, CacheStrategy};
-pub use client::Client;
+pub use client::{ChatStream, Client};
+pub use error::LlmError;
+pub use model_details::ModelDetails;
 pub use routing::ModelRouter;
+pub use tokenizer::{tokenizer_for, HeuristicTokenizer, HfTokenizer, TiktokenTokenizer, Tokenizer};
 
 use serde::{Deserialize, Serialize};
 use serde_json::Value;
@@ -29,6 +37,46 @@ pub struct ChatRequest {
     pub max_tokens: Option<usize>,
     pub temperature: Option<f32>,
     pub tools: Option<Vec<ToolDefinition>>,
+    /// Sampling seed for reproducible generations, on backends that support it
+    pub seed: Option<i64>,
+    /// Start of the assistant's reply; the model continues from it and the
+    /// returned content begins with it
+    pub prefill: Option<String>,
+    /// Whether the model may, must not or must call a tool
+    pub tool_choice: ToolChoice,
+}
+
+/// Which tool calls a request allows: `auto`, `none` or a tool's name
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(from = "String", into = "String")]
+pub enum ToolChoice {
+    /// The model decides
+    #[default]
+    Auto,
+    /// No tools are sent
+    None,
+    /// The model must call this tool
+    Tool(String),
+}
+
+impl From<String> for ToolChoice {
+    fn from(choice: String) -> Self {
+        match choice.as_str() {
+            "auto" => Self::Auto,
+            "none" => Self::None,
+            _ => Self::Tool(choice),
+        }
+    }
+}
+
+impl From<ToolChoice> for String {
+    fn from(choice: ToolChoice) -> Self {
+        match choice {
+            ToolChoice::Auto => "auto".to_string(),
+            ToolChoice::None => "none".to_string(),
+            ToolChoice::Tool(name) => name,
+        }
+    }
 }
 
 #[derive(Debug, Clone)]
@@ -45,6 +93,11 @@ pub struct ChatResponse {
     pub finish_reason: Option<String>,
     pub usage: Option<TokenUsage>,
     pub tool_calls: Option<Vec<ToolCall>>,
+    /// Model originally requested when a fallback model answered instead
+    pub fallback_from: Option<String>,
+    /// Backend configuration fingerprint; generations with the same seed are
+    /// only reproducible while it stays the same
+    pub system_fingerprint: Option<String>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -0,0 +1,111 @@
+//! Model metadata reported by Ollama's `/api/show`
+
+use serde::Serialize;
+use serde_json::Value;
+
+/// What the backend knows about a model
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+pub struct ModelDetails {
+    /// Context length in tokens: the model's `num_ctx` parameter when set,
+    /// else the length it was trained with
+    pub context_length: Option<usize>,
+    pub families: Vec<String>,
+    /// Quantization level, e.g. `Q4_K_M`
+    pub quantization: Option<String>,
+    /// Whether the model accepts tool definitions; `None` when unknown
+    pub supports_tools: Option<bool>,
+    /// Whether the model accepts images; `None` when unknown
+    pub supports_vision: Option<bool>,
+}
+
+impl ModelDetails {
+    /// Parse an `/api/show` response body
+    pub fn from_show(show: &Value) -> Self {
+        let details = &show["details"];
+
+        let mut families: Vec<String> = details["families"]
+            .as_array()
+            .map(|families| {
+                families
+                    .iter()
+                    .filter_map(|family| family.as_str().map(str::to_string))
+                    .collect()
+            })
+            .unwrap_or_default();
+        if families.is_empty() {
+            families.extend(details["family"].as_str().map(str::to_string));
+        }
+
+        Self {
+            context_length: num_ctx(show).or_else(|| trained_context_length(show)),
+            families,
+            quantization: details["quantization_level"].as_str().map(str::to_string),
+            supports_tools: supports_tools(show),
+            supports_vision: show["capabilities"]
+                .as_array()
+                .map(|capabilities| capabilities.iter().any(|c| c.as_str() == Some("vision"))),
+        }
+    }
+}
+
+/// `num_ctx` from the model's parameters (one `name value` pair per line)
+fn num_ctx(show: &Value) -> Option<usize> {
+    show["parameters"].as_str()?.lines().find_map(|line| {
+        let mut parts = line.split_whitespace();
+        match (parts.next(), parts.next()) {
+            (Some("num_ctx"), Some(value)) => value.parse().ok(),
+            _ => None,
+        }
+    })
+}
+
+/// `<architecture>.context_length` from the model info
+fn trained_context_length(show: &Value) -> Option<usize> {
+    let info = show["model_info"].as_object()?;
+    let key = match info.get("general.architecture").and_then(Value::as_str) {
+        Some(architecture) => format!("{}.context_length", architecture),
+        None => info
+            .keys()
+            .find(|key| key.ends_with(".context_length"))?
+            .clone(),
+    };
+    info.get(&key)?.as_u64().map(|length| length as usize)
+}
+
+/// Tool support from the reported capabilities, or from whether the chat
+/// template renders tools on Ollama versions that predate capabilities
+fn supports_tools(show: &Value) -> Option<bool> {
+    if let Some(capabilities) = show["capabilities"].as_array() {
+        return Some(capabilities.iter().any(|c| c.as_str() == Some("tools")));
+    }
+    show["template"]
+        .as_str()
+        .map(|template| template.contains(".Tools"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_legacy_show_response_uses_template() {
+        let show = json!({
+            "parameters": "stop \"<|eot_id|>\"\nnum_ctx                        8192",
+            "template": "{{ if .Tools }}{{ .Tools }}{{ end }}{{ .Prompt }}",
+            "details": {"family": "llama", "quantization_level": "Q8_0"},
+            "model_info": {"llama.context_length": 131072}
+        });
+
+        let details = ModelDetails::from_show(&show);
+        assert_eq!(details.context_length, Some(8192));
+        assert_eq!(details.families, vec!["llama"]);
+        assert_eq!(details.quantization.as_deref(), Some("Q8_0"));
+        assert_eq!(details.supports_tools, Some(true));
+    }
+
+    #[test]
+    fn test_missing_fields_are_unknown() {
+        assert_eq!(ModelDetails::from_show(&json!({})), ModelDetails::default());
+    }
+}
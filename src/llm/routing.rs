@@ -1,13 +1,18 @@
-use crate::config::LlmConfig;
-use anyhow::Result;
+use super::LlmError;
+use crate::config::{LlmConfig, LlmModels};
+use anyhow::{anyhow, Result};
 use regex::Regex;
+use std::future::Future;
 
 /// Model router that selects the appropriate model based on request content
 pub struct ModelRouter {
     default_model: String,
     code_model: Option<String>,
     fast_model: Option<String>,
+    /// Every configured role, to resolve `role:<name>` references
+    models: LlmModels,
     rules: Vec<CompiledRoutingRule>,
+    fallbacks: Vec<String>,
 }
 
 struct CompiledRoutingRule {
@@ -18,13 +23,17 @@ struct CompiledRoutingRule {
 impl ModelRouter {
     pub fn new(config: &LlmConfig) -> Result<Self> {
         let mut rules = Vec::new();
+        let mut fallbacks = Vec::new();
 
         // Compile custom routing rules from config
         if let Some(routing) = &config.routing {
+            for fallback in &routing.fallbacks {
+                fallbacks.push(resolve_role(&config.models, fallback)?);
+            }
             for rule in &routing.rules {
                 rules.push(CompiledRoutingRule {
                     pattern: Regex::new(&rule.pattern)?,
-                    model: rule.model.clone(),
+                    model: resolve_role(&config.models, &rule.model)?,
                 });
             }
         }
@@ -33,7 +42,9 @@ impl ModelRouter {
             default_model: config.models.primary.clone(),
             code_model: config.models.code.clone(),
             fast_model: config.models.fast.clone(),
+            models: config.models.clone(),
             rules,
+            fallbacks,
         })
     }
 
@@ -72,6 +83,35 @@ impl ModelRouter {
         &self.default_model
     }
 
+    /// Model of a role (`primary`, `code`, `fast` or a named role)
+    pub fn model_for_role(&self, role: &str) -> Option<&str> {
+        self.models.model_for(role)
+    }
+
+    /// Model a requested model names: `role:<name>` resolves to the role's
+    /// model (the default model for a role that is not configured), anything
+    /// else is used as is
+    pub fn resolve<'a>(&'a self, model: &'a str) -> &'a str {
+        self.models.resolve(model).unwrap_or_else(|| {
+            tracing::warn!(
+                "No model configured for {}, using '{}'",
+                model,
+                self.default_model
+            );
+            &self.default_model
+        })
+    }
+
+    /// Model to retry with when `model` is unavailable: the first configured
+    /// fallback, otherwise the fast model (never the failed model itself)
+    pub fn fallback_for(&self, model: &str) -> Option<&str> {
+        self.fallbacks
+            .iter()
+            .chain(self.fast_model.iter())
+            .map(|m| m.as_str())
+            .find(|m| *m != model)
+    }
+
     /// Heuristics to detect code-related messages
     fn is_code_related(&self, message: &str) -> bool {
         let code_keywords = [
@@ -101,6 +141,46 @@ impl ModelRouter {
     }
 }
 
+/// Model of a configured model reference, failing for an unknown role
+fn resolve_role(models: &LlmModels, reference: &str) -> Result<String> {
+    models
+        .resolve(reference)
+        .map(String::from)
+        .ok_or_else(|| anyhow!("Unknown model role in '{}'", reference))
+}
+
+/// Call `model`, retrying once on `fallback` if it is unavailable or times out.
+///
+/// Returns the result along with the originally requested model when the
+/// fallback answered. Request/content errors never trigger a fallback.
+pub async fn with_fallback<T, F, Fut>(
+    model: &str,
+    fallback: Option<&str>,
+    mut call: F,
+) -> Result<(T, Option<String>), LlmError>
+where
+    F: FnMut(String) -> Fut,
+    Fut: Future<Output = Result<T, LlmError>>,
+{
+    match call(model.to_string()).await {
+        Ok(value) => Ok((value, None)),
+        Err(err @ (LlmError::Unavailable(_) | LlmError::Timeout)) => match fallback {
+            Some(fallback) => {
+                tracing::warn!(
+                    "Model '{}' failed ({}), falling back to '{}'",
+                    model,
+                    err,
+                    fallback
+                );
+                let value = call(fallback.to_string()).await?;
+                Ok((value, Some(model.to_string())))
+            }
+            None => Err(err),
+        },
+        Err(err) => Err(err),
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -114,6 +194,11 @@ mod tests {
                 primary: "qwen2.5:32b".to_string(),
                 code: Some("deepseek-coder-v2:16b".to_string()),
                 fast: Some("qwen2.5:7b".to_string()),
+                roles: [
+                    ("vision".to_string(), "llava:13b".to_string()),
+                    ("summarize".to_string(), "qwen2.5:3b".to_string()),
+                ]
+                .into(),
             },
             keep_alive: None,
             cache: Default::default(),
@@ -123,7 +208,14 @@ mod tests {
                     pattern: r"translate.*to.*language".to_string(),
                     model: "qwen2.5:7b".to_string(),
                 }],
+                fallbacks: vec![],
             }),
+            context_windows: Default::default(),
+            tool_support: Default::default(),
+            tokenizers: Default::default(),
+            coalesce_requests: false,
+            circuit_breaker: Default::default(),
+            warm_up: false,
         }
     }
 
@@ -151,10 +243,83 @@ mod tests {
         assert_eq!(model, "qwen2.5:7b");
     }
 
+    #[test]
+    fn test_fallback_selection() {
+        let router = ModelRouter::new(&test_config()).unwrap();
+        assert_eq!(router.fallback_for("qwen2.5:32b"), Some("qwen2.5:7b"));
+        // The fast model has no other fallback configured
+        assert_eq!(router.fallback_for("qwen2.5:7b"), None);
+
+        let mut config = test_config();
+        config.routing.as_mut().unwrap().fallbacks = vec!["llama3:8b".to_string()];
+        let router = ModelRouter::new(&config).unwrap();
+        assert_eq!(router.fallback_for("qwen2.5:32b"), Some("llama3:8b"));
+    }
+
+    #[tokio::test]
+    async fn test_primary_failure_falls_back() {
+        let calls = std::sync::Mutex::new(Vec::new());
+        let (answer, fallback_from) = with_fallback("primary", Some("fast"), |model| {
+            calls.lock().unwrap().push(model.clone());
+            async move {
+                if model == "primary" {
+                    Err(LlmError::Unavailable("model not loaded".into()))
+                } else {
+                    Ok(format!("answer from {}", model))
+                }
+            }
+        })
+        .await
+        .unwrap();
+
+        assert_eq!(answer, "answer from fast");
+        assert_eq!(fallback_from.as_deref(), Some("primary"));
+        assert_eq!(*calls.lock().unwrap(), vec!["primary", "fast"]);
+    }
+
+    #[tokio::test]
+    async fn test_bad_request_does_not_fall_back() {
+        let calls = std::sync::atomic::AtomicUsize::new(0);
+        let result: Result<(String, _), _> = with_fallback("primary", Some("fast"), |_| {
+            calls.fetch_add(1, std::sync::atomic::Ordering::SeqCst);
+            async { Err(LlmError::BadRequest("invalid role".into())) }
+        })
+        .await;
+
+        assert!(matches!(result, Err(LlmError::BadRequest(_))));
+        assert_eq!(calls.load(std::sync::atomic::Ordering::SeqCst), 1);
+    }
+
     #[test]
     fn test_custom_rule_routing() {
         let router = ModelRouter::new(&test_config()).unwrap();
         let model = router.route("Translate this to Spanish language");
         assert_eq!(model, "qwen2.5:7b");
     }
+
+    #[test]
+    fn test_role_resolution() {
+        let mut config = test_config();
+        config.routing.as_mut().unwrap().rules.insert(
+            0,
+            RoutingRule {
+                pattern: r"(?i)\bimage\b".to_string(),
+                model: "role:vision".to_string(),
+            },
+        );
+        config.routing.as_mut().unwrap().fallbacks = vec!["role:summarize".to_string()];
+        let router = ModelRouter::new(&config).unwrap();
+
+        assert_eq!(router.route("Describe this image please"), "llava:13b");
+        assert_eq!(router.fallback_for("qwen2.5:32b"), Some("qwen2.5:3b"));
+        assert_eq!(router.model_for_role("summarize"), Some("qwen2.5:3b"));
+        assert_eq!(router.model_for_role("code"), Some("deepseek-coder-v2:16b"));
+        assert_eq!(router.model_for_role("embed"), None);
+        assert_eq!(router.resolve("role:fast"), "qwen2.5:7b");
+        assert_eq!(router.resolve("llama3:8b"), "llama3:8b");
+        assert_eq!(router.resolve("role:embed"), "qwen2.5:32b");
+
+        config.routing.as_mut().unwrap().fallbacks = vec!["role:embed".to_string()];
+        assert!(ModelRouter::new(&config).is_err());
+    }
 }
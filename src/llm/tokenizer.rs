@@ -0,0 +1,218 @@
+//! Token counting per model
+//!
+//! Context-window checks, compaction and usage estimates count tokens with
+//! the tokenizer configured for the model in `llm.tokenizers`:
+//!
+//! - `tiktoken` picks the OpenAI encoding of the model by name, and
+//!   `tiktoken:<encoding>` names one (`cl100k_base`, `o200k_base`, ...)
+//! - `hf:<path>` loads a Hugging Face `tokenizer.json`, as shipped with
+//!   local models
+//! - `heuristic` (and any model without an entry) estimates ~4 characters
+//!   per token
+//!
+//! Loaded tokenizers are cached; one that fails to load falls back to the
+//! heuristic with a warning.
+
+use crate::core::prompt::estimate_tokens;
+use anyhow::{anyhow, Result};
+use once_cell::sync::Lazy;
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+
+/// Counts the tokens a model sees for a piece of text
+pub trait Tokenizer: Send + Sync {
+    fn count_tokens(&self, text: &str) -> usize;
+}
+
+/// Character-based estimate for models without a known tokenizer
+#[derive(Debug, Clone, Copy, Default)]
+pub struct HeuristicTokenizer;
+
+impl Tokenizer for HeuristicTokenizer {
+    fn count_tokens(&self, text: &str) -> usize {
+        estimate_tokens(text)
+    }
+}
+
+/// OpenAI BPE encoding
+pub struct TiktokenTokenizer(tiktoken_rs::CoreBPE);
+
+impl TiktokenTokenizer {
+    /// The encoding used by an OpenAI model
+    pub fn for_model(model: &str) -> Result<Self> {
+        tiktoken_rs::get_bpe_from_model(model)
+            .map(Self)
+            .map_err(|e| anyhow!("No tiktoken encoding for model {}: {}", model, e))
+    }
+
+    /// An encoding by name, e.g. `cl100k_base`
+    pub fn from_encoding(encoding: &str) -> Result<Self> {
+        let bpe = match encoding {
+            "o200k_base" => tiktoken_rs::o200k_base(),
+            "cl100k_base" => tiktoken_rs::cl100k_base(),
+            "p50k_base" => tiktoken_rs::p50k_base(),
+            "p50k_edit" => tiktoken_rs::p50k_edit(),
+            "r50k_base" | "gpt2" => tiktoken_rs::r50k_base(),
+            other => return Err(anyhow!("Unknown tiktoken encoding '{}'", other)),
+        };
+        bpe.map(Self)
+            .map_err(|e| anyhow!("Failed to load tiktoken encoding {}: {}", encoding, e))
+    }
+}
+
+impl Tokenizer for TiktokenTokenizer {
+    fn count_tokens(&self, text: &str) -> usize {
+        self.0.encode_with_special_tokens(text).len()
+    }
+}
+
+/// Hugging Face tokenizer loaded from a `tokenizer.json`
+pub struct HfTokenizer(tokenizers::Tokenizer);
+
+impl HfTokenizer {
+    pub fn from_file(path: &str) -> Result<Self> {
+        tokenizers::Tokenizer::from_file(path)
+            .map(Self)
+            .map_err(|e| anyhow!("Failed to load tokenizer {}: {}", path, e))
+    }
+}
+
+impl Tokenizer for HfTokenizer {
+    fn count_tokens(&self, text: &str) -> usize {
+        match self.0.encode(text, false) {
+            Ok(encoding) => encoding.len(),
+            Err(e) => {
+                tracing::debug!("Tokenizer failed, estimating instead: {}", e);
+                estimate_tokens(text)
+            }
+        }
+    }
+}
+
+/// Tokenizers loaded so far, by cache key (see `cache_key`)
+static TOKENIZERS: Lazy<Mutex<HashMap<String, Arc<dyn Tokenizer>>>> =
+    Lazy::new(|| Mutex::new(HashMap::new()));
+
+/// Tokenizer for a model given its `llm.tokenizers` entry, loaded once and
+/// cached. Falls back to the heuristic when there is no entry or it cannot
+/// be loaded.
+pub fn tokenizer_for(model: &str, spec: Option<&str>) -> Arc<dyn Tokenizer> {
+    let Some(spec) = spec
+        .map(str::trim)
+        .filter(|s| !s.is_empty() && *s != "heuristic")
+    else {
+        return Arc::new(HeuristicTokenizer);
+    };
+
+    let key = cache_key(model, spec);
+    let cached = TOKENIZERS.lock().unwrap().get(&key).cloned();
+    if let Some(tokenizer) = cached {
+        return tokenizer;
+    }
+
+    let tokenizer: Arc<dyn Tokenizer> = match load(model, spec) {
+        Ok(tokenizer) => tokenizer,
+        Err(e) => {
+            tracing::warn!("Using estimated token counts for model {}: {}", model, e);
+            Arc::new(HeuristicTokenizer)
+        }
+    };
+    TOKENIZERS
+        .lock()
+        .unwrap()
+        .entry(key)
+        .or_insert(tokenizer)
+        .clone()
+}
+
+/// `tiktoken` alone depends on the model name; other specs are shared by
+/// every model using them
+fn cache_key(model: &str, spec: &str) -> String {
+    match spec {
+        "tiktoken" => format!("tiktoken@{}", model),
+        _ => spec.to_string(),
+    }
+}
+
+fn load(model: &str, spec: &str) -> Result<Arc<dyn Tokenizer>> {
+    let (kind, argument) = match spec.split_once(':') {
+        Some((kind, argument)) => (kind, Some(argument.trim())),
+        None => (spec, None),
+    };
+    match (kind, argument) {
+        ("tiktoken", None) => Ok(Arc::new(TiktokenTokenizer::for_model(model)?)),
+        ("tiktoken", Some(encoding)) => Ok(Arc::new(TiktokenTokenizer::from_encoding(encoding)?)),
+        ("hf", Some(path)) => Ok(Arc::new(HfTokenizer::from_file(path)?)),
+        _ => Err(anyhow!(
+            "Invalid tokenizer '{}': expected heuristic, tiktoken, tiktoken:<encoding> or hf:<path>",
+            spec
+        )),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_tiktoken_counts_known_strings() {
+        let cl100k = tokenizer_for("any-model", Some("tiktoken:cl100k_base"));
+        assert_eq!(cl100k.count_tokens("hello world"), 2);
+        assert_eq!(cl100k.count_tokens("Hello, world!"), 4);
+        assert_eq!(cl100k.count_tokens(""), 0);
+
+        let gpt4o = tokenizer_for("gpt-4o", Some("tiktoken"));
+        assert_eq!(gpt4o.count_tokens("Hello, world!"), 4);
+    }
+
+    #[test]
+    fn test_hf_tokenizer_from_tokenizer_json() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("tokenizer.json");
+        std::fs::write(
+            &path,
+            r#"{
+                "version": "1.0",
+                "truncation": null,
+                "padding": null,
+                "added_tokens": [],
+                "normalizer": null,
+                "pre_tokenizer": {"type": "Whitespace"},
+                "post_processor": null,
+                "decoder": null,
+                "model": {
+                    "type": "WordLevel",
+                    "vocab": {"hello": 0, "world": 1, "[UNK]": 2},
+                    "unk_token": "[UNK]"
+                }
+            }"#,
+        )
+        .unwrap();
+
+        let spec = format!("hf:{}", path.display());
+        let tokenizer = tokenizer_for("local-model", Some(&spec));
+        // Unknown words still count, as the unknown token
+        assert_eq!(tokenizer.count_tokens("hello world, again"), 4);
+    }
+
+    #[test]
+    fn test_falls_back_to_the_heuristic() {
+        for spec in [
+            None,
+            Some("heuristic"),
+            Some("hf:/nonexistent/tokenizer.json"),
+            Some("tiktoken:no_such_encoding"),
+            Some("sentencepiece"),
+        ] {
+            let tokenizer = tokenizer_for("llama3", spec);
+            assert_eq!(tokenizer.count_tokens("abcdefgh"), 2, "{:?}", spec);
+        }
+    }
+
+    #[test]
+    fn test_loaded_tokenizers_are_cached() {
+        let first = tokenizer_for("gpt-4", Some("tiktoken"));
+        let second = tokenizer_for("gpt-4", Some("tiktoken"));
+        assert!(Arc::ptr_eq(&first, &second));
+    }
+}
@@ -0,0 +1,268 @@
+use crate::config::{LogFileConfig, LogRotation, LoggingConfig, OtlpConfig};
+use anyhow::{anyhow, Context, Result};
+use std::ffi::OsString;
+use std::fs::{File, OpenOptions};
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use tracing_appender::non_blocking::{NonBlocking, WorkerGuard};
+use tracing_appender::rolling::{Builder, Rotation};
+use tracing_subscriber::registry::LookupSpan;
+use tracing_subscriber::{EnvFilter, Layer};
+
+/// Build the log filter.
+///
+/// A valid `RUST_LOG` takes precedence over the config entirely; otherwise
+/// `logging.level` is the default and `logging.filters` add per-target
+/// directives (e.g. `hyper=warn`) on top of it.
+pub fn env_filter(config: &LoggingConfig) -> Result<EnvFilter> {
+    match EnvFilter::try_from_default_env() {
+        Ok(filter) => Ok(filter),
+        Err(_) => config_filter(config),
+    }
+}
+
+/// Log filter built from the config alone
+pub fn config_filter(config: &LoggingConfig) -> Result<EnvFilter> {
+    let mut filter = EnvFilter::try_new(&config.level)
+        .with_context(|| format!("Invalid log level: {}", config.level))?;
+
+    for directive in &config.filters {
+        filter = filter.add_directive(
+            directive
+                .parse()
+                .with_context(|| format!("Invalid log filter: {}", directive))?,
+        );
+    }
+
+    Ok(filter)
+}
+
+/// Open the configured log file as a non-blocking writer.
+///
+/// Buffered lines are flushed when the returned guard is dropped, so the
+/// caller must keep it alive for the lifetime of the process.
+pub fn file_writer(config: &LogFileConfig) -> Result<(NonBlocking, WorkerGuard)> {
+    let file_name = config
+        .path
+        .file_name()
+        .and_then(|name| name.to_str())
+        .ok_or_else(|| anyhow!("Invalid log file path: {}", config.path.display()))?;
+    let dir = match config.path.parent() {
+        Some(dir) if !dir.as_os_str().is_empty() => dir.to_path_buf(),
+        _ => PathBuf::from("."),
+    };
+    std::fs::create_dir_all(&dir)
+        .with_context(|| format!("Failed to create log directory {}", dir.display()))?;
+
+    let rotation = match config.rotation {
+        LogRotation::Size => {
+            let writer = SizeRotatingWriter::new(
+                &config.path,
+                config.max_size_mb.saturating_mul(1024 * 1024),
+                config.max_files,
+            )
+            .with_context(|| format!("Failed to open log file {}", config.path.display()))?;
+            return Ok(tracing_appender::non_blocking(writer));
+        }
+        LogRotation::Hourly => Rotation::HOURLY,
+        LogRotation::Daily => Rotation::DAILY,
+        LogRotation::Never => Rotation::NEVER,
+    };
+
+    let appender = Builder::new()
+        .rotation(rotation)
+        .filename_prefix(file_name)
+        .max_log_files(config.max_files.max(1))
+        .build(&dir)
+        .with_context(|| format!("Failed to open log file {}", config.path.display()))?;
+
+    Ok(tracing_appender::non_blocking(appender))
+}
+
+/// Flushes and shuts down the OpenTelemetry exporter when dropped, so the
+/// caller must keep it alive for the lifetime of the process
+pub struct OtlpGuard {
+    #[cfg(feature = "otel")]
+    provider: opentelemetry_sdk::trace::TracerProvider,
+}
+
+impl Drop for OtlpGuard {
+    fn drop(&mut self) {
+        #[cfg(feature = "otel")]
+        if let Err(e) = self.provider.shutdown() {
+            eprintln!("Failed to flush OpenTelemetry spans: {}", e);
+        }
+    }
+}
+
+/// Layer exporting spans to the configured OpenTelemetry collector
+#[cfg(feature = "otel")]
+pub fn otlp_layer<S>(config: &OtlpConfig) -> Result<(Box<dyn Layer<S> + Send + Sync>, OtlpGuard)>
+where
+    S: tracing::Subscriber + for<'a> LookupSpan<'a>,
+{
+    use opentelemetry::trace::TracerProvider as _;
+    use opentelemetry_otlp::WithExportConfig;
+
+    let exporter = opentelemetry_otlp::SpanExporter::builder()
+        .with_tonic()
+        .with_endpoint(&config.endpoint)
+        .build()
+        .with_context(|| format!("Failed to create OTLP exporter for {}", config.endpoint))?;
+    let provider = opentelemetry_sdk::trace::TracerProvider::builder()
+        .with_batch_exporter(exporter, opentelemetry_sdk::runtime::Tokio)
+        .with_resource(opentelemetry_sdk::Resource::new(vec![
+            opentelemetry::KeyValue::new("service.name", config.service_name.clone()),
+        ]))
+        .build();
+    let tracer = provider.tracer("rustyclaw");
+
+    let layer = tracing_opentelemetry::layer().with_tracer(tracer).boxed();
+    Ok((layer, OtlpGuard { provider }))
+}
+
+/// Without the `otel` feature span export is unavailable, so asking for it
+/// is a configuration error rather than silently exporting nothing
+#[cfg(not(feature = "otel"))]
+pub fn otlp_layer<S>(config: &OtlpConfig) -> Result<(Box<dyn Layer<S> + Send + Sync>, OtlpGuard)>
+where
+    S: tracing::Subscriber + for<'a> LookupSpan<'a>,
+{
+    Err(anyhow!(
+        "logging.otlp is set ({}), but rustyclaw was built without the `otel` feature",
+        config.endpoint
+    ))
+}
+
+/// File writer that starts a new file once the current one reaches
+/// `max_bytes`, keeping `max_files` rotated copies as `<path>.1`, `<path>.2`, ...
+pub struct SizeRotatingWriter {
+    path: PathBuf,
+    max_bytes: u64,
+    max_files: usize,
+    file: File,
+    size: u64,
+}
+
+impl SizeRotatingWriter {
+    pub fn new(path: &Path, max_bytes: u64, max_files: usize) -> io::Result<Self> {
+        let file = open_append(path)?;
+        let size = file.metadata()?.len();
+
+        Ok(Self {
+            path: path.to_path_buf(),
+            max_bytes: max_bytes.max(1),
+            max_files,
+            file,
+            size,
+        })
+    }
+
+    fn rotated_path(&self, index: usize) -> PathBuf {
+        let mut name = OsString::from(self.path.as_os_str());
+        name.push(format!(".{}", index));
+        PathBuf::from(name)
+    }
+
+    fn rotate(&mut self) -> io::Result<()> {
+        self.file.flush()?;
+
+        if self.max_files > 0 {
+            let _ = std::fs::remove_file(self.rotated_path(self.max_files));
+            for index in (1..self.max_files).rev() {
+                let from = self.rotated_path(index);
+                if from.exists() {
+                    std::fs::rename(&from, self.rotated_path(index + 1))?;
+                }
+            }
+            std::fs::rename(&self.path, self.rotated_path(1))?;
+        } else {
+            std::fs::remove_file(&self.path)?;
+        }
+
+        self.file = open_append(&self.path)?;
+        self.size = 0;
+        Ok(())
+    }
+}
+
+impl Write for SizeRotatingWriter {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        if self.size > 0 && self.size + buf.len() as u64 > self.max_bytes {
+            self.rotate()?;
+        }
+
+        let written = self.file.write(buf)?;
+        self.size += written as u64;
+        Ok(written)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.file.flush()
+    }
+}
+
+fn open_append(path: &Path) -> io::Result<File> {
+    OpenOptions::new().create(true).append(true).open(path)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_config_filter_includes_directives() {
+        let config = LoggingConfig {
+            level: "debug".to_string(),
+            filters: vec!["hyper=warn".to_string(), "sqlx::query=error".to_string()],
+            ..Default::default()
+        };
+
+        let filter = config_filter(&config).unwrap().to_string();
+        assert!(filter.contains("debug"));
+        assert!(filter.contains("hyper=warn"));
+        assert!(filter.contains("sqlx::query=error"));
+
+        let config = LoggingConfig {
+            filters: vec!["hyper=loud".to_string()],
+            ..Default::default()
+        };
+        assert!(config_filter(&config).is_err());
+    }
+
+    #[test]
+    fn test_size_rotation_keeps_max_files() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("rustyclaw.log");
+        let mut writer = SizeRotatingWriter::new(&path, 10, 2).unwrap();
+
+        for line in ["first line\n", "second line\n", "third line\n", "fourth\n"] {
+            writer.write_all(line.as_bytes()).unwrap();
+        }
+        writer.flush().unwrap();
+
+        let read = |p: PathBuf| std::fs::read_to_string(p).unwrap();
+        assert_eq!(read(path.clone()), "fourth\n");
+        assert_eq!(read(dir.path().join("rustyclaw.log.1")), "third line\n");
+        assert_eq!(read(dir.path().join("rustyclaw.log.2")), "second line\n");
+        assert!(!dir.path().join("rustyclaw.log.3").exists());
+    }
+
+    #[test]
+    fn test_file_writer_flushes_on_guard_drop() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("logs").join("rustyclaw.log");
+        let config = LogFileConfig {
+            path: path.clone(),
+            rotation: LogRotation::Never,
+            max_size_mb: 1,
+            max_files: 1,
+        };
+
+        let (mut writer, guard) = file_writer(&config).unwrap();
+        writer.write_all(b"hello\n").unwrap();
+        drop(guard);
+
+        assert_eq!(std::fs::read_to_string(path).unwrap(), "hello\n");
+    }
+}
@@ -1,15 +1,22 @@
 use anyhow::Result;
 use clap::{Parser, Subcommand};
+use rustyclaw::config::LoggingConfig;
+use rustyclaw::logging::OtlpGuard;
 use rustyclaw::Config;
 use std::path::PathBuf;
-use tracing_subscriber::{layer::SubscriberExt, util::SubscriberInitExt};
+use tracing_appender::non_blocking::WorkerGuard;
+use tracing_subscriber::fmt::MakeWriter;
+use tracing_subscriber::registry::LookupSpan;
+use tracing_subscriber::{layer::SubscriberExt, util::SubscriberInitExt, Layer};
 
 #[derive(Parser)]
 #[command(name = "rustyclaw")]
 #[command(about = "A local-first, privacy-focused AI assistant gateway", long_about = None)]
 struct Cli {
-    #[arg(short, long, value_name = "FILE")]
-    config: Option<PathBuf>,
+    /// Config file or directory of YAML files; repeat to merge several,
+    /// later ones overriding earlier ones
+    #[arg(short, long, value_name = "PATH")]
+    config: Vec<PathBuf>,
 
     #[command(subcommand)]
     command: Option<Commands>,
@@ -31,6 +38,20 @@ enum Commands {
     /// Manage API tokens
     #[command(subcommand)]
     Token(TokenCommands),
+
+    /// Manage skills
+    #[command(subcommand)]
+    Skill(SkillCommands),
+
+    /// Inspect the configuration format
+    #[command(subcommand)]
+    Config(ConfigCommands),
+}
+
+#[derive(Subcommand)]
+enum ConfigCommands {
+    /// Print the JSON Schema of the config file, for editor validation
+    Schema,
 }
 
 #[derive(Subcommand)]
@@ -41,6 +62,14 @@ enum ChannelsCommands {
         #[arg(value_name = "CHANNEL")]
         channel: String,
     },
+
+    /// Check a channel's credentials without starting the gateway
+    /// (e.g., `rustyclaw channels test telegram`)
+    Test {
+        /// Channel to check (telegram, discord or whatsapp)
+        #[arg(value_name = "CHANNEL")]
+        channel: String,
+    },
 }
 
 #[derive(Subcommand)]
@@ -100,34 +129,78 @@ enum TokenCommands {
     },
 }
 
+#[derive(Subcommand)]
+enum SkillCommands {
+    /// Scaffold a new skill file (e.g., `rustyclaw skill new my_skill --runtime bash`)
+    New {
+        /// Skill name
+        #[arg(value_name = "NAME")]
+        name: String,
+
+        /// Runtime (bash or python)
+        #[arg(long, default_value = "bash")]
+        runtime: String,
+    },
+
+    /// Make the running gateway rescan its skills directory
+    Reload {
+        /// Admin API token
+        #[arg(long)]
+        token: String,
+
+        /// Reload endpoint (defaults to the configured API address)
+        #[arg(long)]
+        url: Option<String>,
+    },
+}
+
 #[tokio::main]
 async fn main() -> Result<()> {
     let cli = Cli::parse();
 
+    // Config commands work without a config file
+    if let Some(Commands::Config(config_cmd)) = cli.command {
+        let cmd = match config_cmd {
+            ConfigCommands::Schema => rustyclaw::cli::config::ConfigCmd::Schema,
+        };
+        return rustyclaw::cli::config::handle_config_command(cmd);
+    }
+
     // Load configuration
-    let config_path = cli.config.unwrap_or_else(|| {
+    let config_paths = if cli.config.is_empty() {
         let default_path = Config::default_path();
         if default_path.exists() {
-            default_path
+            vec![default_path]
         } else {
-            PathBuf::from("config/default.yaml")
+            vec![PathBuf::from("config/default.yaml")]
         }
-    });
-
-    let config = if config_path.exists() {
-        Config::load(&config_path)?
     } else {
-        eprintln!("Config file not found: {}", config_path.display());
+        cli.config
+    };
+
+    if let Some(missing) = config_paths.iter().find(|path| !path.exists()) {
+        eprintln!("Config file not found: {}", missing.display());
         eprintln!("Please create a config file or use --config to specify one.");
         eprintln!("See config/default.yaml for an example.");
         std::process::exit(1);
+    }
+    let config = match config_paths.as_slice() {
+        [path] => Config::load(path)?,
+        paths => Config::load_files(paths)?,
     };
 
-    // Initialize logging
-    init_logging(&config.logging.level, &config.logging.format)?;
+    // Initialize logging; the guards flush the file writer and span export on exit
+    let _log_guards = init_logging(&config.logging)?;
 
     tracing::info!("RustyClaw starting...");
-    tracing::info!("Config loaded from: {}", config_path.display());
+    tracing::info!(
+        "Config loaded from: {}",
+        config_paths
+            .iter()
+            .map(|path| path.display().to_string())
+            .collect::<Vec<_>>()
+            .join(", ")
+    );
 
     match cli.command {
         Some(Commands::Serve) | None => {
@@ -136,6 +209,9 @@ async fn main() -> Result<()> {
         Some(Commands::Channels(ChannelsCommands::Connect { channel })) => {
             rustyclaw::channels::connect(&channel, config).await?;
         }
+        Some(Commands::Channels(ChannelsCommands::Test { channel })) => {
+            rustyclaw::channels::test(&channel, config).await?;
+        }
         Some(Commands::User(user_cmd)) => {
             let cmd = match user_cmd {
                 UserCommands::Create { username, password } => {
@@ -162,36 +238,66 @@ async fn main() -> Result<()> {
             };
             rustyclaw::cli::token::handle_token_command(cmd, config).await?;
         }
+        Some(Commands::Skill(skill_cmd)) => {
+            let cmd = match skill_cmd {
+                SkillCommands::New { name, runtime } => {
+                    rustyclaw::cli::skill::SkillCmd::New { name, runtime }
+                }
+                SkillCommands::Reload { token, url } => {
+                    rustyclaw::cli::skill::SkillCmd::Reload { token, url }
+                }
+            };
+            rustyclaw::cli::skill::handle_skill_command(cmd, config).await?;
+        }
+        Some(Commands::Config(_)) => unreachable!("handled before loading the config"),
     }
 
     Ok(())
 }
 
-fn init_logging(level: &str, format: &str) -> Result<()> {
-    let env_filter = tracing_subscriber::EnvFilter::try_from_default_env()
-        .unwrap_or_else(|_| tracing_subscriber::EnvFilter::new(level));
+fn init_logging(config: &LoggingConfig) -> Result<(Option<WorkerGuard>, Option<OtlpGuard>)> {
+    let env_filter = rustyclaw::logging::env_filter(config)?;
 
-    match format {
-        "json" => {
-            tracing_subscriber::registry()
-                .with(env_filter)
-                .with(tracing_subscriber::fmt::layer().json())
-                .init();
+    let mut layers = vec![fmt_layer(&config.format, std::io::stdout, true)];
+    let guard = match &config.file {
+        Some(file) => {
+            let (writer, guard) = rustyclaw::logging::file_writer(file)?;
+            layers.push(fmt_layer(&config.format, writer, false));
+            Some(guard)
         }
-        "compact" => {
-            tracing_subscriber::registry()
-                .with(env_filter)
-                .with(tracing_subscriber::fmt::layer().compact())
-                .init();
-        }
-        _ => {
-            // Default to pretty
-            tracing_subscriber::registry()
-                .with(env_filter)
-                .with(tracing_subscriber::fmt::layer().pretty())
-                .init();
+        None => None,
+    };
+    let otlp_guard = match &config.otlp {
+        Some(otlp) => {
+            let (layer, guard) = rustyclaw::logging::otlp_layer(otlp)?;
+            layers.push(layer);
+            Some(guard)
         }
-    }
+        None => None,
+    };
 
-    Ok(())
+    tracing_subscriber::registry()
+        .with(env_filter)
+        .with(layers)
+        .init();
+
+    Ok((guard, otlp_guard))
+}
+
+/// Formatting layer for one log destination in the configured format
+fn fmt_layer<S, W>(format: &str, writer: W, ansi: bool) -> Box<dyn Layer<S> + Send + Sync>
+where
+    S: tracing::Subscriber + for<'a> LookupSpan<'a>,
+    W: for<'w> MakeWriter<'w> + Send + Sync + 'static,
+{
+    let layer = tracing_subscriber::fmt::layer()
+        .with_writer(writer)
+        .with_ansi(ansi);
+
+    match format {
+        "json" => layer.json().boxed(),
+        "compact" => layer.compact().boxed(),
+        // Default to pretty
+        _ => layer.pretty().boxed(),
+    }
 }
@@ -227,7 +227,9 @@ impl McpServer {
             &params.name,
             &args_str,
             Some(session_id),
+            None,
             false,
+            None,
         )
         .await
         {
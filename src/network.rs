@@ -0,0 +1,206 @@
+//! Outbound HTTP proxy settings
+//!
+//! Proxies from `network` in the config apply to every HTTP client the
+//! gateway builds. Values left unset fall back to the standard
+//! `HTTP_PROXY` / `HTTPS_PROXY` / `NO_PROXY` environment variables.
+
+use crate::config::NetworkConfig;
+use anyhow::{Context, Result};
+use once_cell::sync::OnceCell;
+
+/// Resolved proxy settings for code paths without access to the config
+static NETWORK: OnceCell<NetworkConfig> = OnceCell::new();
+
+/// Initialize the global proxy settings.
+///
+/// Configured values are also exported to the standard environment variables
+/// (when unset) so clients that only read the environment, like the WhatsApp
+/// media client, use the same proxy.
+pub fn init_network(config: &NetworkConfig) {
+    let resolved = resolve(config, |name| std::env::var(name).ok());
+
+    let exports = [
+        ("HTTP_PROXY", &resolved.http_proxy),
+        ("HTTPS_PROXY", &resolved.https_proxy),
+        ("NO_PROXY", &resolved.no_proxy),
+    ];
+    for (name, value) in exports {
+        if let Some(value) = value {
+            if env_var(name, |name| std::env::var(name).ok()).is_none() {
+                std::env::set_var(name, value);
+            }
+        }
+    }
+
+    if resolved.http_proxy.is_some() || resolved.https_proxy.is_some() {
+        tracing::info!(
+            "Outbound HTTP proxy: http={:?} https={:?} no_proxy={:?}",
+            resolved.http_proxy,
+            resolved.https_proxy,
+            resolved.no_proxy
+        );
+    }
+    NETWORK.set(resolved).ok();
+}
+
+/// Fill unset proxy settings from the environment
+pub fn resolve(config: &NetworkConfig, env: impl Fn(&str) -> Option<String>) -> NetworkConfig {
+    NetworkConfig {
+        http_proxy: config
+            .http_proxy
+            .clone()
+            .or_else(|| env_var("HTTP_PROXY", &env)),
+        https_proxy: config
+            .https_proxy
+            .clone()
+            .or_else(|| env_var("HTTPS_PROXY", &env)),
+        no_proxy: config
+            .no_proxy
+            .clone()
+            .or_else(|| env_var("NO_PROXY", &env)),
+    }
+}
+
+/// Non-empty value of an upper- or lowercase environment variable
+fn env_var(name: &str, env: impl Fn(&str) -> Option<String>) -> Option<String> {
+    env(name)
+        .or_else(|| env(&name.to_lowercase()))
+        .filter(|value| !value.trim().is_empty())
+}
+
+/// HTTP client builder with the global proxy settings applied
+pub fn client_builder() -> Result<reqwest::ClientBuilder> {
+    let config = match NETWORK.get() {
+        Some(config) => config.clone(),
+        None => resolve(&NetworkConfig::default(), |name| std::env::var(name).ok()),
+    };
+    configure(reqwest::Client::builder(), &config)
+}
+
+/// Apply proxy settings to an HTTP client builder
+pub fn configure(
+    mut builder: reqwest::ClientBuilder,
+    config: &NetworkConfig,
+) -> Result<reqwest::ClientBuilder> {
+    let no_proxy = || {
+        config
+            .no_proxy
+            .as_deref()
+            .and_then(reqwest::NoProxy::from_string)
+    };
+
+    if let Some(url) = &config.http_proxy {
+        let proxy = reqwest::Proxy::http(url)
+            .with_context(|| format!("Invalid network.http_proxy '{}'", url))?;
+        builder = builder.proxy(proxy.no_proxy(no_proxy()));
+    }
+    if let Some(url) = &config.https_proxy {
+        let proxy = reqwest::Proxy::https(url)
+            .with_context(|| format!("Invalid network.https_proxy '{}'", url))?;
+        builder = builder.proxy(proxy.no_proxy(no_proxy()));
+    }
+
+    Ok(builder)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_config_overrides_env() {
+        let env = |name: &str| match name {
+            "HTTP_PROXY" => Some("http://env-proxy:3128".to_string()),
+            "https_proxy" => Some("http://lower-proxy:3128".to_string()),
+            "NO_PROXY" => Some(" ".to_string()),
+            _ => None,
+        };
+        let config = NetworkConfig {
+            http_proxy: Some("http://config-proxy:8080".to_string()),
+            ..Default::default()
+        };
+
+        let resolved = resolve(&config, env);
+        assert_eq!(
+            resolved.http_proxy.as_deref(),
+            Some("http://config-proxy:8080")
+        );
+        assert_eq!(
+            resolved.https_proxy.as_deref(),
+            Some("http://lower-proxy:3128")
+        );
+        assert_eq!(resolved.no_proxy, None);
+    }
+
+    #[tokio::test]
+    async fn test_proxy_reaches_client() {
+        let mut proxy = mockito::Server::new_async().await;
+        let proxied = proxy
+            .mock("GET", mockito::Matcher::Any)
+            .with_body("via proxy")
+            .create_async()
+            .await;
+
+        let config = NetworkConfig {
+            http_proxy: Some(proxy.url()),
+            ..Default::default()
+        };
+        let client = configure(reqwest::Client::builder(), &config)
+            .unwrap()
+            .build()
+            .unwrap();
+
+        // The host does not resolve, so only the proxy can answer
+        let body = client
+            .get("http://rustyclaw.invalid/page")
+            .send()
+            .await
+            .unwrap()
+            .text()
+            .await
+            .unwrap();
+        assert_eq!(body, "via proxy");
+        proxied.assert_async().await;
+    }
+
+    #[tokio::test]
+    async fn test_no_proxy_bypasses_proxy() {
+        let mut direct = mockito::Server::new_async().await;
+        let direct_mock = direct
+            .mock("GET", "/page")
+            .with_body("direct")
+            .create_async()
+            .await;
+
+        let config = NetworkConfig {
+            // Nothing listens here, so a proxied request would fail
+            http_proxy: Some("http://127.0.0.1:9".to_string()),
+            https_proxy: None,
+            no_proxy: Some("127.0.0.1".to_string()),
+        };
+        let client = configure(reqwest::Client::builder(), &config)
+            .unwrap()
+            .build()
+            .unwrap();
+
+        let body = client
+            .get(format!("{}/page", direct.url()))
+            .send()
+            .await
+            .unwrap()
+            .text()
+            .await
+            .unwrap();
+        assert_eq!(body, "direct");
+        direct_mock.assert_async().await;
+    }
+
+    #[test]
+    fn test_invalid_proxy_url_is_rejected() {
+        let config = NetworkConfig {
+            https_proxy: Some("::not a url::".to_string()),
+            ..Default::default()
+        };
+        assert!(configure(reqwest::Client::builder(), &config).is_err());
+    }
+}
@@ -1,14 +1,97 @@
+use crate::core::costs::{self, CostLedger};
+use crate::llm::{ChatMessage, ChatRequest, ChatResponse, Client as LlmClient, ToolChoice};
 use crate::plugins::traits::{HookType, PluginApi, PluginHook, Tool, ToolFactory};
+use crate::storage::Storage;
 use crate::Config;
-use anyhow::Result;
+use anyhow::{anyhow, Result};
+use async_trait::async_trait;
+use serde_json::Value;
 use std::sync::Arc;
 use tracing::debug;
 
-/// Default implementation of PluginApi
+/// Persistence for the plugins' key-value state
+#[async_trait]
+pub trait PluginKvStore: Send + Sync {
+    async fn get(&self, plugin_id: &str, key: &str) -> Result<Option<Value>>;
+    async fn set(&self, plugin_id: &str, key: &str, value: Value) -> Result<()>;
+    async fn delete(&self, plugin_id: &str, key: &str) -> Result<bool>;
+}
+
+#[async_trait]
+impl<S: Storage> PluginKvStore for S {
+    async fn get(&self, plugin_id: &str, key: &str) -> Result<Option<Value>> {
+        self.plugin_kv_get(plugin_id, key).await
+    }
+
+    async fn set(&self, plugin_id: &str, key: &str, value: Value) -> Result<()> {
+        self.plugin_kv_set(plugin_id, key, value).await
+    }
+
+    async fn delete(&self, plugin_id: &str, key: &str) -> Result<bool> {
+        self.plugin_kv_delete(plugin_id, key).await
+    }
+}
+
+/// LLM access handed to plugins. Chats go through the gateway's client, so
+/// they share its model routing, fallbacks and model cache, and are charged
+/// against the cost limits like a user's turns.
+#[derive(Clone)]
+pub struct PluginLlm {
+    client: LlmClient,
+    plugin_id: String,
+    costs: Arc<CostLedger>,
+    config: Arc<Config>,
+}
+
+impl PluginLlm {
+    /// Chat with the model the gateway routes the last user message to.
+    /// Plugins get a plain completion: no tools are offered. Refused once
+    /// the plugin has reached a cost limit.
+    pub async fn chat(&self, messages: Vec<ChatMessage>) -> Result<ChatResponse> {
+        let account = costs::plugin_account(&self.plugin_id);
+        if let Some(scope) = self
+            .costs
+            .limit_reached(&self.config.costs, &account, &account)
+        {
+            return Err(anyhow!(
+                "Plugin {} has reached its {:?} cost limit",
+                self.plugin_id,
+                scope
+            ));
+        }
+
+        let request = ChatRequest {
+            model: String::new(),
+            messages,
+            max_tokens: None,
+            temperature: None,
+            tools: None,
+            seed: None,
+            prefill: None,
+            tool_choice: ToolChoice::None,
+        };
+        let response = self.client.chat(request).await?;
+
+        if let Some(usage) = &response.usage {
+            let cost = costs::price(&self.config.costs, &response.model, usage.total_tokens);
+            self.costs
+                .record(&self.config.costs, &account, &account, cost);
+        }
+        Ok(response)
+    }
+}
+
+/// Default implementation of PluginApi. Each plugin is registered with its
+/// own handle from [`DefaultPluginApi::for_plugin`], which scopes its state.
+#[derive(Clone)]
 pub struct DefaultPluginApi {
     config: Arc<Config>,
     tool_registry: Arc<crate::plugins::ToolRegistry>,
-    _hook_runner: Arc<crate::plugins::HookRunner>,
+    hook_runner: Arc<crate::plugins::HookRunner>,
+    kv_store: Option<Arc<dyn PluginKvStore>>,
+    llm_client: Option<LlmClient>,
+    costs: Arc<CostLedger>,
+    plugin_id: Option<String>,
 }
 
 impl DefaultPluginApi {
@@ -21,11 +104,57 @@ impl DefaultPluginApi {
         Self {
             config,
             tool_registry,
-            _hook_runner: hook_runner,
+            hook_runner,
+            kv_store: None,
+            llm_client: None,
+            costs: Arc::new(CostLedger::new()),
+            plugin_id: None,
+        }
+    }
+
+    /// Handle for the plugin `plugin_id`: its key-value state is kept apart
+    /// from every other plugin's
+    pub fn for_plugin(&self, plugin_id: &str) -> Self {
+        Self {
+            plugin_id: Some(plugin_id.to_string()),
+            ..self.clone()
         }
     }
+
+    /// Let plugins chat through the gateway's LLM client
+    pub fn with_llm_client(mut self, client: LlmClient) -> Self {
+        self.llm_client = Some(client);
+        self
+    }
+
+    /// Charge the plugins' LLM calls to `ledger`, normally the router's
+    /// ([`crate::core::Router::costs`]), so admins see and reset them there
+    pub fn with_cost_ledger(mut self, ledger: Arc<CostLedger>) -> Self {
+        self.costs = ledger;
+        self
+    }
+
+    /// Back the plugins' key-value store with `store`
+    pub fn with_kv_store(mut self, store: Arc<dyn PluginKvStore>) -> Self {
+        self.kv_store = Some(store);
+        self
+    }
+
+    /// The key-value store and the namespace of the plugin this handle is for
+    fn kv_store(&self) -> Result<(&dyn PluginKvStore, &str)> {
+        let store = self
+            .kv_store
+            .as_deref()
+            .ok_or_else(|| anyhow!("Plugin key-value store is not available"))?;
+        let plugin_id = self
+            .plugin_id
+            .as_deref()
+            .ok_or_else(|| anyhow!("Plugin key-value store needs a plugin's own API handle"))?;
+        Ok((store, plugin_id))
+    }
 }
 
+#[async_trait]
 impl PluginApi for DefaultPluginApi {
     fn register_tool(&self, tool: Tool) -> Result<()> {
         debug!("Registering tool: {}", tool.name);
@@ -45,19 +174,53 @@ impl PluginApi for DefaultPluginApi {
         Ok(())
     }
 
-    fn register_hook(&self, hook_type: HookType, _hook: PluginHook) -> Result<()> {
-        debug!("Registering hook: {:?}", hook_type);
-
-        // Note: We need to spawn this on a runtime
-        // This will be handled by the plugin loader
-        // For now, hook registration is deferred
+    fn register_hook_with_priority(
+        &self,
+        hook_type: HookType,
+        priority: i32,
+        hook: PluginHook,
+    ) -> Result<()> {
+        debug!("Registering hook: {:?} (priority {})", hook_type, priority);
 
-        Ok(())
+        self.hook_runner
+            .try_register_hook(hook_type, hook_type.to_string(), priority, hook)
     }
 
     fn get_config(&self) -> Arc<Config> {
         self.config.clone()
     }
+
+    fn llm(&self) -> Result<PluginLlm> {
+        let client = self
+            .llm_client
+            .clone()
+            .ok_or_else(|| anyhow!("LLM access is not available to plugins"))?;
+        let plugin_id = self
+            .plugin_id
+            .clone()
+            .ok_or_else(|| anyhow!("LLM access needs a plugin's own API handle"))?;
+        Ok(PluginLlm {
+            client,
+            plugin_id,
+            costs: self.costs.clone(),
+            config: self.config.clone(),
+        })
+    }
+
+    async fn kv_get(&self, key: &str) -> Result<Option<Value>> {
+        let (store, plugin_id) = self.kv_store()?;
+        store.get(plugin_id, key).await
+    }
+
+    async fn kv_set(&self, key: &str, value: Value) -> Result<()> {
+        let (store, plugin_id) = self.kv_store()?;
+        store.set(plugin_id, key, value).await
+    }
+
+    async fn kv_delete(&self, key: &str) -> Result<bool> {
+        let (store, plugin_id) = self.kv_store()?;
+        store.delete(plugin_id, key).await
+    }
 }
 
 #[cfg(test)]
@@ -70,4 +233,241 @@ mod tests {
         // This would require mock objects
         // Placeholder for actual test when mocks are available
     }
+
+    /// Counts how often it has been loaded
+    struct RunCounterPlugin;
+
+    impl crate::plugins::RustyclawPlugin for RunCounterPlugin {
+        fn id(&self) -> &str {
+            "run-counter"
+        }
+
+        fn name(&self) -> &str {
+            "Run Counter"
+        }
+
+        fn version(&self) -> &str {
+            "1.0.0"
+        }
+
+        fn register<'a>(
+            &'a self,
+            api: &'a dyn PluginApi,
+        ) -> std::pin::Pin<Box<dyn std::future::Future<Output = Result<()>> + Send + 'a>> {
+            Box::pin(async move {
+                let runs = api.kv_get("runs").await?;
+                let runs = runs.and_then(|runs| runs.as_u64()).unwrap_or(0);
+                api.kv_set("runs", serde_json::json!(runs + 1)).await
+            })
+        }
+    }
+
+    #[tokio::test]
+    async fn test_plugin_kv_survives_restart() {
+        use crate::plugins::{PluginRegistry, RustyclawPlugin};
+
+        let storage = crate::storage::sqlite::SqliteStorage::new(":memory:")
+            .await
+            .unwrap();
+        let store: Arc<dyn PluginKvStore> = Arc::new(storage);
+        let config: Arc<Config> =
+            Arc::new(serde_yaml::from_str("llm:\n  models:\n    primary: test-model\n").unwrap());
+        let start = || {
+            let registry = PluginRegistry::new();
+            DefaultPluginApi::new(
+                config.clone(),
+                registry.tools.clone(),
+                registry.hooks.clone(),
+            )
+            .with_kv_store(store.clone())
+            .for_plugin("run-counter")
+        };
+
+        RunCounterPlugin.register(&start()).await.unwrap();
+        let api = start();
+        RunCounterPlugin.register(&api).await.unwrap();
+        assert_eq!(
+            api.kv_get("runs").await.unwrap(),
+            Some(serde_json::json!(2))
+        );
+        assert!(api.kv_delete("runs").await.unwrap());
+        assert!(!api.kv_delete("runs").await.unwrap());
+        assert_eq!(api.kv_get("runs").await.unwrap(), None);
+    }
+
+    #[tokio::test]
+    async fn test_plugins_cannot_see_each_others_keys() {
+        let storage = crate::storage::sqlite::SqliteStorage::new(":memory:")
+            .await
+            .unwrap();
+        let config: Arc<Config> =
+            Arc::new(serde_yaml::from_str("llm:\n  models:\n    primary: test-model\n").unwrap());
+        let registry = crate::plugins::PluginRegistry::new();
+        let api = DefaultPluginApi::new(config, registry.tools.clone(), registry.hooks.clone())
+            .with_kv_store(Arc::new(storage));
+        let (a, b) = (api.for_plugin("a"), api.for_plugin("b"));
+
+        a.kv_set("secret", serde_json::json!("a's")).await.unwrap();
+        assert_eq!(b.kv_get("secret").await.unwrap(), None);
+        assert!(!b.kv_delete("secret").await.unwrap());
+        b.kv_set("secret", serde_json::json!("b's")).await.unwrap();
+        assert_eq!(
+            a.kv_get("secret").await.unwrap(),
+            Some(serde_json::json!("a's"))
+        );
+
+        // The shared API is bound to no plugin at all
+        assert!(api.kv_get("secret").await.is_err());
+    }
+
+    /// Offers a `summarize` tool that asks the model for a summary
+    struct SummarizePlugin;
+
+    impl crate::plugins::RustyclawPlugin for SummarizePlugin {
+        fn id(&self) -> &str {
+            "summarize"
+        }
+
+        fn name(&self) -> &str {
+            "Summarize"
+        }
+
+        fn version(&self) -> &str {
+            "1.0.0"
+        }
+
+        fn register<'a>(
+            &'a self,
+            api: &'a dyn PluginApi,
+        ) -> std::pin::Pin<Box<dyn std::future::Future<Output = Result<()>> + Send + 'a>> {
+            Box::pin(async move {
+                let llm = api.llm()?;
+                api.register_tool(Tool {
+                    name: "summarize".to_string(),
+                    description: "Summarize a text".to_string(),
+                    parameters: serde_json::json!({"type": "object"}),
+                    tags: Vec::new(),
+                    execute: Arc::new(move |text| {
+                        let llm = llm.clone();
+                        Box::pin(async move {
+                            let response = llm
+                                .chat(vec![
+                                    ChatMessage {
+                                        role: "system".to_string(),
+                                        content: "Summarize the user's text in one line."
+                                            .to_string(),
+                                    },
+                                    ChatMessage {
+                                        role: "user".to_string(),
+                                        content: text,
+                                    },
+                                ])
+                                .await?;
+                            Ok(crate::plugins::ToolResult {
+                                content: response.content,
+                                details: None,
+                                success: true,
+                            })
+                        })
+                    }),
+                })
+            })
+        }
+    }
+
+    #[tokio::test]
+    async fn test_plugin_tool_calls_the_model() {
+        use crate::plugins::{PluginRegistry, RustyclawPlugin};
+
+        let mut server = mockito::Server::new_async().await;
+        let mock = server
+            .mock("POST", "/chat/completions")
+            .match_body(mockito::Matcher::Regex("A long report".to_string()))
+            .with_header("content-type", "application/json")
+            .with_body(
+                r#"{"id": "chatcmpl-1", "object": "chat.completion", "created": 1700000000,
+                    "model": "test-model",
+                    "choices": [{"index": 0, "finish_reason": "stop",
+                                 "message": {"role": "assistant", "content": "Short."}}]}"#,
+            )
+            .expect(1)
+            .create_async()
+            .await;
+
+        let config: Config = serde_yaml::from_str(&format!(
+            "llm:\n  base_url: {}\n  models:\n    primary: test-model\n",
+            server.url()
+        ))
+        .unwrap();
+        let llm_client = LlmClient::new(&config.llm).unwrap();
+        let registry = PluginRegistry::new();
+        let api = DefaultPluginApi::new(
+            Arc::new(config),
+            registry.tools.clone(),
+            registry.hooks.clone(),
+        )
+        .with_llm_client(llm_client)
+        .for_plugin("summarize");
+
+        SummarizePlugin.register(&api).await.unwrap();
+        let tool = registry.tools.get_tool("summarize").unwrap().unwrap();
+        let result = (tool.execute)("A long report".to_string()).await.unwrap();
+        assert_eq!(result.content, "Short.");
+        mock.assert_async().await;
+    }
+
+    #[tokio::test]
+    async fn test_plugin_over_budget_is_refused() {
+        use crate::plugins::PluginRegistry;
+
+        let mut server = mockito::Server::new_async().await;
+        let mock = server
+            .mock("POST", "/chat/completions")
+            .with_header("content-type", "application/json")
+            .with_body(
+                r#"{"id": "chatcmpl-1", "object": "chat.completion", "created": 1700000000,
+                    "model": "test-model",
+                    "choices": [{"index": 0, "finish_reason": "stop",
+                                 "message": {"role": "assistant", "content": "Done."}}],
+                    "usage": {"prompt_tokens": 400, "completion_tokens": 100,
+                              "total_tokens": 500}}"#,
+            )
+            .expect(1)
+            .create_async()
+            .await;
+
+        // One call costs 1.0, the limit
+        let config: Config = serde_yaml::from_str(&format!(
+            "llm:\n  base_url: {}\n  models:\n    primary: test-model\n\
+             costs:\n  prices:\n    test-model: 2.0\n  user_limit: 1.0\n",
+            server.url()
+        ))
+        .unwrap();
+        let llm_client = LlmClient::new(&config.llm).unwrap();
+        let registry = PluginRegistry::new();
+        let ledger = Arc::new(CostLedger::new());
+        let api = DefaultPluginApi::new(
+            Arc::new(config),
+            registry.tools.clone(),
+            registry.hooks.clone(),
+        )
+        .with_llm_client(llm_client)
+        .with_cost_ledger(ledger.clone());
+        let message = || {
+            vec![ChatMessage {
+                role: "user".to_string(),
+                content: "Hi".to_string(),
+            }]
+        };
+
+        let spender = api.for_plugin("spender");
+        spender.chat(message()).await.unwrap();
+        assert_eq!(ledger.user_cost(&costs::plugin_account("spender")), 1.0);
+        let error = spender.chat(message()).await.unwrap_err();
+        assert!(error.to_string().contains("cost limit"), "{}", error);
+        mock.assert_async().await;
+
+        // Other plugins keep their own budget
+        assert_eq!(ledger.user_cost(&costs::plugin_account("other")), 0.0);
+    }
 }
@@ -6,7 +6,7 @@ use serde::{Deserialize, Serialize};
 use serde_json::json;
 use std::future::Future;
 use std::pin::Pin;
-use std::sync::Arc;
+use std::sync::{Arc, RwLock};
 use std::time::Duration;
 use tracing::{debug, info, warn};
 
@@ -138,6 +138,13 @@ impl Default for EmailConfig {
 }
 
 impl EmailConfig {
+    /// Sender mailbox built from the sender name and email
+    fn sender(&self) -> Result<lettre::message::Mailbox> {
+        format!("{} <{}>", self.sender_name, self.sender_email)
+            .parse()
+            .context("Invalid sender email format")
+    }
+
     /// Get the SMTP server, using provider default if not specified
     fn get_server(&self) -> String {
         self.smtp_server
@@ -225,27 +232,34 @@ impl SendEmailParams {
 
 /// Email plugin
 pub struct EmailPlugin {
-    config: Option<EmailConfig>,
+    /// Shared with the registered tool, so reconfiguring applies to it
+    config: Arc<RwLock<Option<EmailConfig>>>,
 }
 
 impl EmailPlugin {
     /// Create a new email plugin
     pub fn new() -> Self {
-        Self { config: None }
+        Self {
+            config: Arc::new(RwLock::new(None)),
+        }
     }
 
     /// Create with configuration
     pub fn with_config(config: EmailConfig) -> Self {
         Self {
-            config: Some(config),
+            config: Arc::new(RwLock::new(Some(config))),
         }
     }
 
+    /// Current configuration, `None` until configured
+    pub fn config(&self) -> Option<EmailConfig> {
+        self.config.read().unwrap().clone()
+    }
+
     /// Send an email with retry logic
     async fn send_email(&self, params: SendEmailParams) -> Result<ToolResult> {
         let config = self
-            .config
-            .as_ref()
+            .config()
             .ok_or_else(|| anyhow!("Email plugin not configured"))?;
 
         // Validate parameters
@@ -258,7 +272,7 @@ impl EmailPlugin {
         // Retry logic with exponential backoff
         let mut last_error = None;
         for attempt in 0..config.retry_attempts {
-            match self.try_send_email(config, &params).await {
+            match self.try_send_email(&config, &params).await {
                 Ok(result) => {
                     if config.logging_enabled {
                         info!(
@@ -295,16 +309,10 @@ impl EmailPlugin {
         params: &SendEmailParams,
     ) -> Result<ToolResult> {
         // Build email message
-        let mut email_builder = Message::builder()
-            .from(
-                format!("{} <{}>", config.sender_name, config.sender_email)
-                    .parse()
-                    .context("Invalid sender email format")?,
-            )
-            .to(params
-                .to
-                .parse()
-                .context("Invalid recipient email format")?);
+        let mut email_builder = Message::builder().from(config.sender()?).to(params
+            .to
+            .parse()
+            .context("Invalid recipient email format")?);
 
         // Add CC if provided
         if let Some(cc_list) = &params.cc {
@@ -485,6 +493,7 @@ impl RustyclawPlugin for EmailPlugin {
                 },
                 "required": ["to", "subject", "body"]
             }),
+            tags: vec!["email".to_string()],
             execute: {
                 let plugin = plugin_for_tool.clone();
                 Arc::new(move |args| {
@@ -504,19 +513,18 @@ impl RustyclawPlugin for EmailPlugin {
         }
 
         Box::pin(async move {
+            let config = plugin_self.config();
             info!("✅ Email plugin v1.0.0 loaded with send_email tool");
             info!(
                 "   Provider: {:?}",
-                plugin_self
-                    .config
+                config
                     .as_ref()
                     .map(|c| &c.provider)
                     .unwrap_or(&EmailProvider::Gmail)
             );
             info!(
                 "   Retry attempts: {}",
-                plugin_self
-                    .config
+                config
                     .as_ref()
                     .map(|c| c.retry_attempts)
                     .unwrap_or(default_retry_attempts())
@@ -528,7 +536,7 @@ impl RustyclawPlugin for EmailPlugin {
 
     fn on_load(&self) -> Pin<Box<dyn Future<Output = Result<()>> + Send + '_>> {
         Box::pin(async {
-            if let Some(config) = &self.config {
+            if let Some(config) = &self.config() {
                 if config.logging_enabled {
                     debug!(
                         "Email plugin configured for {} account",
@@ -607,6 +615,24 @@ impl RustyclawPlugin for EmailPlugin {
             "required": ["smtp_username", "smtp_password", "sender_email"]
         }))
     }
+
+    fn reconfigure(
+        &self,
+        config: serde_json::Value,
+    ) -> Pin<Box<dyn Future<Output = Result<()>> + Send + '_>> {
+        Box::pin(async move {
+            let config: EmailConfig =
+                serde_json::from_value(config).context("Invalid email configuration")?;
+            config.sender()?;
+            info!(
+                "Email plugin reconfigured: sending as {} via {}",
+                config.sender_email,
+                config.get_server()
+            );
+            *self.config.write().unwrap() = Some(config);
+            Ok(())
+        })
+    }
 }
 
 impl Clone for EmailPlugin {
@@ -1,4 +1,6 @@
 /// Example plugins demonstrating the plugin system
 pub mod email;
+pub mod uppercase;
 
 pub use email::EmailPlugin;
+pub use uppercase::UppercasePlugin;
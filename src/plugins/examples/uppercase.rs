@@ -0,0 +1,93 @@
+use crate::plugins::traits::{
+    HookModification, HookType, MessageSendingEvent, PluginApi, RustyclawPlugin,
+};
+use anyhow::Result;
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::Arc;
+use tracing::info;
+
+/// Minimal message_sending hook: uppercases every outbound reply.
+///
+/// Hooks run after the reply is stored, so conversation history keeps the
+/// original text while users receive the rewritten one.
+#[derive(Debug, Clone, Default)]
+pub struct UppercasePlugin;
+
+impl UppercasePlugin {
+    pub fn new() -> Self {
+        Self
+    }
+}
+
+impl RustyclawPlugin for UppercasePlugin {
+    fn id(&self) -> &str {
+        "uppercase"
+    }
+
+    fn name(&self) -> &str {
+        "Uppercase Plugin"
+    }
+
+    fn version(&self) -> &str {
+        "1.0.0"
+    }
+
+    fn description(&self) -> &str {
+        "Example message_sending hook that uppercases outbound replies"
+    }
+
+    fn register(
+        &self,
+        api: &dyn PluginApi,
+    ) -> Pin<Box<dyn Future<Output = Result<()>> + Send + '_>> {
+        let registered = api.register_hook(
+            HookType::MessageSending,
+            Arc::new(|_, ctx| {
+                Box::pin(async move {
+                    Ok(
+                        MessageSendingEvent::from_context(&ctx).map(|event| HookModification {
+                            modified_message: Some(event.message.to_uppercase()),
+                            ..Default::default()
+                        }),
+                    )
+                })
+            }),
+        );
+
+        Box::pin(async move {
+            registered?;
+            info!("✅ Uppercase plugin loaded");
+            Ok(())
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::plugins::{DefaultPluginApi, PluginRegistry};
+    use crate::Config;
+
+    #[tokio::test]
+    async fn test_uppercases_outbound_messages() {
+        let registry = PluginRegistry::new();
+        let config: Config =
+            serde_yaml::from_str("llm:\n  models:\n    primary: test-model\n").unwrap();
+        let api = DefaultPluginApi::new(
+            Arc::new(config),
+            registry.tools.clone(),
+            registry.hooks.clone(),
+        );
+        UppercasePlugin::new().register(&api).await.unwrap();
+
+        let event = MessageSendingEvent {
+            message: "hello there".to_string(),
+            channel: "telegram".to_string(),
+        };
+        assert_eq!(
+            registry.hooks.apply_message_sending("s1", event).await,
+            Some("HELLO THERE".to_string())
+        );
+    }
+}
@@ -1,7 +1,15 @@
-use crate::plugins::traits::{HookModification, HookType, PluginHook, ToolContext};
+use crate::config::{HookConfig, HookExecution};
+use crate::plugins::traits::{
+    HookModification, HookType, InboundMessage, MessageReceivedEvent, MessageSendingEvent,
+    PluginHook, ToolContext,
+};
 use anyhow::Result;
+use futures::FutureExt;
+use serde_json::Value;
 use std::collections::HashMap;
+use std::panic::AssertUnwindSafe;
 use std::sync::Arc;
+use std::time::Duration;
 use tokio::sync::RwLock;
 use tracing::{debug, instrument};
 
@@ -16,6 +24,7 @@ struct HookEntry {
 /// Manages all registered hooks and their execution
 pub struct HookRunner {
     hooks: Arc<RwLock<HashMap<HookType, Vec<HookEntry>>>>,
+    config: std::sync::RwLock<HookConfig>,
 }
 
 impl HookRunner {
@@ -23,9 +32,20 @@ impl HookRunner {
     pub fn new() -> Self {
         Self {
             hooks: Arc::new(RwLock::new(HashMap::new())),
+            config: std::sync::RwLock::new(HookConfig::default()),
         }
     }
 
+    /// Set how hooks run and how long each may take
+    pub fn configure(&self, config: &HookConfig) {
+        *self.config.write().unwrap() = config.clone();
+    }
+
+    fn settings(&self) -> (HookExecution, Duration) {
+        let config = self.config.read().unwrap();
+        (config.execution, Duration::from_millis(config.timeout_ms))
+    }
+
     /// Register a hook
     pub async fn register_hook(
         &self,
@@ -35,52 +55,57 @@ impl HookRunner {
         hook: PluginHook,
     ) -> Result<()> {
         let mut hooks = self.hooks.write().await;
-        let entries = hooks.entry(hook_type).or_insert_with(Vec::new);
-
-        entries.push(HookEntry {
-            name,
-            priority,
-            hook,
-        });
-
-        // Sort by priority (higher first)
-        entries.sort_by_key(|h| std::cmp::Reverse(h.priority));
-
-        debug!(
-            "Registered hook: {} for {:?}",
-            entries.last().unwrap().name,
-            hook_type
-        );
+        insert_hook(&mut hooks, hook_type, name, priority, hook);
+        Ok(())
+    }
 
+    /// Register a hook from synchronous code such as
+    /// `PluginApi::register_hook`; fails while hooks are running
+    pub fn try_register_hook(
+        &self,
+        hook_type: HookType,
+        name: String,
+        priority: i32,
+        hook: PluginHook,
+    ) -> Result<()> {
+        let mut hooks = self
+            .hooks
+            .try_write()
+            .map_err(|_| anyhow::anyhow!("Hooks are running, cannot register {}", name))?;
+        insert_hook(&mut hooks, hook_type, name, priority, hook);
         Ok(())
     }
 
-    /// Execute all hooks of a type (void hooks - parallel execution)
+    /// Execute all hooks of a type (void hooks), concurrently or in
+    /// priority order as configured
     #[instrument(skip(self, ctx), fields(hook_type = ?hook_type))]
     pub async fn run_void_hooks(&self, hook_type: HookType, ctx: ToolContext) -> Result<()> {
         let hooks = self.hooks.read().await;
+        let (execution, timeout) = self.settings();
 
         if let Some(entries) = hooks.get(&hook_type) {
-            let futures: Vec<_> = entries
-                .iter()
-                .map(|entry| {
-                    let hook = entry.hook.clone();
-                    let ctx = ctx.clone();
-                    async move {
-                        if let Err(e) = (hook)(hook_type, ctx).await {
-                            tracing::warn!("Hook error: {}", e);
-                        }
+            match execution {
+                HookExecution::Concurrent => {
+                    let futures = entries
+                        .iter()
+                        .map(|entry| run_hook(entry, hook_type, ctx.clone(), timeout));
+                    futures::future::join_all(futures).await;
+                }
+                HookExecution::Sequential => {
+                    for entry in entries {
+                        run_hook(entry, hook_type, ctx.clone(), timeout).await;
                     }
-                })
-                .collect();
-
-            futures::future::join_all(futures).await;
+                }
+            }
         }
 
         Ok(())
     }
 
-    /// Execute hooks that can modify behavior (sequential)
+    /// Execute hooks that can modify behavior, one after another in
+    /// priority order. Each hook sees the message and tool parameters as
+    /// modified by the hooks before it; for other fields the last hook
+    /// setting them wins.
     #[instrument(skip(self, ctx), fields(hook_type = ?hook_type))]
     pub async fn run_modifying_hooks(
         &self,
@@ -88,19 +113,15 @@ impl HookRunner {
         ctx: ToolContext,
     ) -> Result<Option<HookModification>> {
         let hooks = self.hooks.read().await;
+        let (_, timeout) = self.settings();
 
         if let Some(entries) = hooks.get(&hook_type) {
-            let mut combined_modification = HookModification {
-                system_prompt_override: None,
-                prepend_context: None,
-                block_tool: None,
-                block_reason: None,
-                modified_parameters: None,
-            };
+            let mut combined_modification = HookModification::default();
+            let mut ctx = ctx;
 
             for entry in entries {
-                if let Ok(Some(modification)) = (entry.hook)(hook_type, ctx.clone()).await {
-                    // Apply modifications (first one wins for overrides)
+                if let Some(modification) = run_hook(entry, hook_type, ctx.clone(), timeout).await {
+                    // Apply modifications (later hooks override earlier ones)
                     if modification.system_prompt_override.is_some() {
                         combined_modification.system_prompt_override =
                             modification.system_prompt_override;
@@ -112,9 +133,22 @@ impl HookRunner {
                         combined_modification.block_tool = modification.block_tool;
                         combined_modification.block_reason = modification.block_reason;
                     }
-                    if modification.modified_parameters.is_some() {
-                        combined_modification.modified_parameters =
-                            modification.modified_parameters;
+                    // Later hooks see the parameters as rewritten so far
+                    if let Some(parameters) = modification.modified_parameters {
+                        ctx.metadata
+                            .insert("parameters".to_string(), parameters.clone());
+                        combined_modification.modified_parameters = Some(parameters);
+                    }
+                    // Later hooks see the message as rewritten so far
+                    if let Some(message) = modification.modified_message {
+                        ctx.metadata
+                            .insert("message".to_string(), Value::String(message.clone()));
+                        combined_modification.modified_message = Some(message);
+                    }
+                    if modification.block_message == Some(true) {
+                        combined_modification.block_message = Some(true);
+                        combined_modification.block_reply = modification.block_reply;
+                        break;
                     }
                 }
             }
@@ -149,17 +183,72 @@ impl HookRunner {
         self.run_void_hooks(HookType::AfterCompaction, ctx).await
     }
 
-    /// Run message_received hooks
-    pub async fn run_message_received(&self, ctx: ToolContext) -> Result<()> {
-        self.run_void_hooks(HookType::MessageReceived, ctx).await
+    /// Run message_received hooks (see [`MessageReceivedEvent::to_context`]),
+    /// chained like the message_sending hooks
+    pub async fn run_message_received(&self, ctx: ToolContext) -> Result<Option<HookModification>> {
+        self.run_modifying_hooks(HookType::MessageReceived, ctx)
+            .await
     }
 
-    /// Run message_sending hooks
+    /// Pass an inbound message through the message_received hooks before it
+    /// is processed
+    pub async fn apply_message_received(
+        &self,
+        session_id: &str,
+        event: MessageReceivedEvent,
+    ) -> InboundMessage {
+        let modification = match self
+            .run_message_received(event.to_context(session_id))
+            .await
+        {
+            Ok(modification) => modification.unwrap_or_default(),
+            Err(e) => {
+                tracing::warn!("message_received hooks failed: {}", e);
+                return InboundMessage::Process(event.message);
+            }
+        };
+
+        if modification.block_message == Some(true) {
+            debug!(
+                "Inbound message from {} on {} blocked by a hook",
+                event.sender, event.channel
+            );
+            return InboundMessage::Blocked(modification.block_reply);
+        }
+        InboundMessage::Process(modification.modified_message.unwrap_or(event.message))
+    }
+
+    /// Run message_sending hooks (see [`MessageSendingEvent::to_context`]).
+    ///
+    /// Hooks run in priority order, each seeing the message as rewritten by
+    /// the ones before it; a hook blocking the message stops the chain.
     pub async fn run_message_sending(&self, ctx: ToolContext) -> Result<Option<HookModification>> {
         self.run_modifying_hooks(HookType::MessageSending, ctx)
             .await
     }
 
+    /// Pass an outbound message through the message_sending hooks. Returns
+    /// the text to send, or `None` when a hook blocked it.
+    pub async fn apply_message_sending(
+        &self,
+        session_id: &str,
+        event: MessageSendingEvent,
+    ) -> Option<String> {
+        let modification = match self.run_message_sending(event.to_context(session_id)).await {
+            Ok(modification) => modification.unwrap_or_default(),
+            Err(e) => {
+                tracing::warn!("message_sending hooks failed: {}", e);
+                return Some(event.message);
+            }
+        };
+
+        if modification.block_message == Some(true) {
+            debug!("Outbound message on {} blocked by a hook", event.channel);
+            return None;
+        }
+        Some(modification.modified_message.unwrap_or(event.message))
+    }
+
     /// Run message_sent hooks
     pub async fn run_message_sent(&self, ctx: ToolContext) -> Result<()> {
         self.run_void_hooks(HookType::MessageSent, ctx).await
@@ -218,6 +307,61 @@ impl HookRunner {
     }
 }
 
+/// Run a single hook, bounded by `timeout`. A hook that fails, panics or
+/// times out is logged and skipped so it cannot abort the caller.
+async fn run_hook(
+    entry: &HookEntry,
+    hook_type: HookType,
+    ctx: ToolContext,
+    timeout: Duration,
+) -> Option<HookModification> {
+    let hook = entry.hook.clone();
+    let run = AssertUnwindSafe(async move { hook(hook_type, ctx).await }).catch_unwind();
+
+    match tokio::time::timeout(timeout, run).await {
+        Ok(Ok(Ok(modification))) => modification,
+        Ok(Ok(Err(e))) => {
+            tracing::warn!("Hook {} failed: {}", entry.name, e);
+            None
+        }
+        Ok(Err(_)) => {
+            tracing::warn!("Hook {} panicked", entry.name);
+            None
+        }
+        Err(_) => {
+            tracing::warn!("Hook {} timed out after {:?}", entry.name, timeout);
+            None
+        }
+    }
+}
+
+/// Add a hook, keeping hooks of its type sorted by priority (higher first,
+/// registration order among equal priorities)
+fn insert_hook(
+    hooks: &mut HashMap<HookType, Vec<HookEntry>>,
+    hook_type: HookType,
+    name: String,
+    priority: i32,
+    hook: PluginHook,
+) {
+    let entries = hooks.entry(hook_type).or_default();
+
+    entries.push(HookEntry {
+        name,
+        priority,
+        hook,
+    });
+
+    // Sort by priority (higher first); the stable sort keeps registration order
+    entries.sort_by_key(|h| std::cmp::Reverse(h.priority));
+
+    debug!(
+        "Registered hook: {} for {:?}",
+        entries.last().unwrap().name,
+        hook_type
+    );
+}
+
 impl Default for HookRunner {
     fn default() -> Self {
         Self::new()
@@ -314,4 +458,162 @@ mod tests {
         assert_eq!(orders[1], "medium");
         assert_eq!(orders[2], "low");
     }
+
+    #[tokio::test]
+    async fn test_message_sending_hooks_chain_and_block() {
+        let runner = HookRunner::new();
+
+        // Appends a suffix to the message as left by earlier hooks
+        let suffix = |suffix: &'static str| -> PluginHook {
+            Arc::new(move |_, ctx| {
+                Box::pin(async move {
+                    let event = MessageSendingEvent::from_context(&ctx).unwrap();
+                    Ok(Some(HookModification {
+                        modified_message: Some(event.message + suffix),
+                        ..Default::default()
+                    }))
+                })
+            })
+        };
+        runner
+            .register_hook(HookType::MessageSending, "a".to_string(), 10, suffix("-a"))
+            .await
+            .unwrap();
+        runner
+            .try_register_hook(HookType::MessageSending, "b".to_string(), 5, suffix("-b"))
+            .unwrap();
+
+        let event = || MessageSendingEvent {
+            message: "hi".to_string(),
+            channel: "web".to_string(),
+        };
+        assert_eq!(
+            runner.apply_message_sending("s1", event()).await,
+            Some("hi-a-b".to_string())
+        );
+
+        let block: PluginHook = Arc::new(|_, _| {
+            Box::pin(async {
+                Ok(Some(HookModification {
+                    block_message: Some(true),
+                    ..Default::default()
+                }))
+            })
+        });
+        runner
+            .register_hook(HookType::MessageSending, "block".to_string(), 7, block)
+            .await
+            .unwrap();
+        assert_eq!(runner.apply_message_sending("s1", event()).await, None);
+    }
+
+    #[tokio::test]
+    async fn test_before_tool_call_hooks_run_by_priority() {
+        let runner = HookRunner::new();
+        let seen = Arc::new(tokio::sync::Mutex::new(Vec::new()));
+
+        // Records its name and the parameters it was given
+        let record = |name: &'static str| -> PluginHook {
+            let seen = seen.clone();
+            Arc::new(move |_, ctx| {
+                let seen = seen.clone();
+                Box::pin(async move {
+                    seen.lock()
+                        .await
+                        .push((name, ctx.metadata["parameters"].clone()));
+                    Ok(None)
+                })
+            })
+        };
+        let auth: PluginHook = Arc::new(|_, _| {
+            Box::pin(async {
+                Ok(Some(HookModification {
+                    modified_parameters: Some(serde_json::json!({"token": "checked"})),
+                    ..Default::default()
+                }))
+            })
+        });
+
+        for (name, priority, hook) in [
+            ("log-first", 0, record("log-first")),
+            ("auth", 10, auth),
+            ("log-second", 0, record("log-second")),
+            ("audit", -5, record("audit")),
+        ] {
+            runner
+                .try_register_hook(HookType::BeforeToolCall, name.to_string(), priority, hook)
+                .unwrap();
+        }
+
+        let ctx = ToolContext {
+            session_id: "test".to_string(),
+            workspace_dir: None,
+            agent_id: None,
+            message_channel: None,
+            sandboxed: false,
+            metadata: HashMap::from([("parameters".to_string(), serde_json::json!({}))]),
+        };
+        let modification = runner.run_before_tool_call(ctx).await.unwrap().unwrap();
+        assert_eq!(
+            modification.modified_parameters,
+            Some(serde_json::json!({"token": "checked"}))
+        );
+
+        let seen = seen.lock().await;
+        let order: Vec<&str> = seen.iter().map(|(name, _)| *name).collect();
+        assert_eq!(order, vec!["log-first", "log-second", "audit"]);
+        assert!(seen
+            .iter()
+            .all(|(_, parameters)| parameters["token"] == "checked"));
+    }
+
+    #[tokio::test]
+    async fn test_slow_and_panicking_hooks_do_not_block() {
+        let runner = HookRunner::new();
+        runner.configure(&HookConfig {
+            execution: HookExecution::Sequential,
+            timeout_ms: 50,
+        });
+        let counter = Arc::new(AtomicUsize::new(0));
+
+        let slow: PluginHook = Arc::new(|_, _| {
+            Box::pin(async {
+                tokio::time::sleep(Duration::from_secs(60)).await;
+                Ok(None)
+            })
+        });
+        let panicking: PluginHook = Arc::new(|_, _| Box::pin(async { panic!("broken hook") }));
+        let counter_clone = counter.clone();
+        let counting: PluginHook = Arc::new(move |_, _| {
+            let counter = counter_clone.clone();
+            Box::pin(async move {
+                counter.fetch_add(1, Ordering::SeqCst);
+                Ok(None)
+            })
+        });
+        for (name, priority, hook) in [("slow", 10, slow), ("panicking", 5, panicking)] {
+            runner
+                .register_hook(HookType::AfterToolCall, name.to_string(), priority, hook)
+                .await
+                .unwrap();
+        }
+        runner
+            .register_hook(HookType::AfterToolCall, "counting".to_string(), 1, counting)
+            .await
+            .unwrap();
+
+        let ctx = ToolContext {
+            session_id: "test".to_string(),
+            workspace_dir: None,
+            agent_id: None,
+            message_channel: None,
+            sandboxed: false,
+            metadata: std::collections::HashMap::new(),
+        };
+        let started = std::time::Instant::now();
+        runner.run_after_tool_call(ctx).await.unwrap();
+
+        assert!(started.elapsed() < Duration::from_secs(5));
+        assert_eq!(counter.load(Ordering::SeqCst), 1);
+    }
 }
@@ -16,14 +16,14 @@ pub mod examples;
 // Re-export core types
 pub use traits::{
     AfterToolCallEvent, BeforeAgentStartEvent, BeforeToolCallEvent, HookModification, HookType,
-    MessageReceivedEvent, MessageSendingEvent, PluginApi, PluginHook, PluginManifest,
-    RustyclawPlugin, Tool, ToolContext, ToolFactory, ToolParameter, ToolResult,
+    InboundMessage, MessageReceivedEvent, MessageSendingEvent, PluginApi, PluginHook,
+    PluginManifest, RustyclawPlugin, Tool, ToolContext, ToolFactory, ToolParameter, ToolResult,
 };
 
-pub use api::DefaultPluginApi;
-pub use examples::EmailPlugin;
+pub use api::{DefaultPluginApi, PluginKvStore, PluginLlm};
+pub use examples::{EmailPlugin, UppercasePlugin};
 pub use hooks::HookRunner;
-pub use registry::{PluginRegistry, ToolRegistry};
+pub use registry::{PluginConfigError, PluginInfo, PluginRegistry, ToolRegistry};
 
 use anyhow::Result;
 use std::sync::Arc;
@@ -45,10 +45,60 @@ pub fn get_plugin_registry() -> Option<Arc<PluginRegistry>> {
     PLUGIN_REGISTRY.get().cloned()
 }
 
-/// Initialize plugins from a list
+/// Pass an inbound message through the message_received hooks of the
+/// global registry; the message is processed unchanged when no registry is
+/// initialized.
+pub async fn run_inbound_hooks(
+    session_id: &str,
+    channel: &str,
+    sender: &str,
+    message: &str,
+) -> InboundMessage {
+    let Some(registry) = get_plugin_registry() else {
+        return InboundMessage::Process(message.to_string());
+    };
+
+    registry
+        .hooks
+        .apply_message_received(
+            session_id,
+            MessageReceivedEvent {
+                message: message.to_string(),
+                channel: channel.to_string(),
+                sender: sender.to_string(),
+            },
+        )
+        .await
+}
+
+/// Pass an outbound reply through the message_sending hooks of the global
+/// registry. Returns the text to deliver, or `None` when a hook blocked it;
+/// the message is returned unchanged when no registry is initialized.
+pub async fn run_outbound_hooks(
+    session_id: &str,
+    channel: &str,
+    message: String,
+) -> Option<String> {
+    let Some(registry) = get_plugin_registry() else {
+        return Some(message);
+    };
+
+    registry
+        .hooks
+        .apply_message_sending(
+            session_id,
+            MessageSendingEvent {
+                message,
+                channel: channel.to_string(),
+            },
+        )
+        .await
+}
+
+/// Initialize plugins from a list, each with its own handle on `api`
 pub async fn initialize_plugins(
     plugins: Vec<Arc<dyn RustyclawPlugin>>,
-    api: Arc<dyn PluginApi>,
+    api: Arc<DefaultPluginApi>,
 ) -> Result<Arc<PluginRegistry>> {
     let registry = init_plugin_registry();
     let plugin_count = plugins.len();
@@ -65,8 +115,17 @@ pub async fn initialize_plugins(
             .register_plugin(id.clone(), name.clone(), version.clone())
             .await?;
 
-        // Call plugin's register function
-        plugin.register(api.as_ref()).await?;
+        // Call plugin's register function, noting the tools it adds
+        let known_tools = registry.tools.list_tools()?;
+        plugin.register(&api.for_plugin(&id)).await?;
+        let mut tools: Vec<String> = registry
+            .tools
+            .list_tools()?
+            .into_iter()
+            .filter(|tool| !known_tools.contains(tool))
+            .collect();
+        tools.sort();
+        registry.attach_plugin(&id, plugin.clone(), tools)?;
 
         // Call plugin's on_load hook
         plugin.on_load().await?;
@@ -1,5 +1,7 @@
-use crate::plugins::traits::{Tool, ToolContext, ToolFactory};
+use crate::plugins::traits::{RustyclawPlugin, Tool, ToolContext, ToolFactory};
 use anyhow::{anyhow, Result};
+use serde::Serialize;
+use serde_json::Value;
 use std::collections::HashMap;
 use std::sync::{Arc, Mutex};
 use tracing::{debug, info, warn};
@@ -84,6 +86,7 @@ impl ToolRegistry {
                 name: tool.name.clone(),
                 description: tool.description.clone(),
                 parameters: tool.parameters.clone(),
+                tags: tool.tags.clone(),
                 execute: tool.execute.clone(),
             });
         }
@@ -183,6 +186,37 @@ struct PluginEntry {
     name: String,
     version: String,
     enabled: bool,
+    /// The loaded plugin, kept to reconfigure it
+    plugin: Option<Arc<dyn RustyclawPlugin>>,
+    /// Names of the tools the plugin registered
+    tools: Vec<String>,
+}
+
+/// A registered plugin, as listed to admins
+#[derive(Debug, Clone, Serialize)]
+pub struct PluginInfo {
+    pub id: String,
+    pub name: String,
+    pub version: String,
+    pub description: String,
+    pub enabled: bool,
+    pub tools: Vec<String>,
+    /// Schema a new configuration must match; `None` when the plugin takes
+    /// no configuration
+    pub config_schema: Option<Value>,
+}
+
+/// Why a plugin could not be reconfigured
+#[derive(Debug, thiserror::Error)]
+pub enum PluginConfigError {
+    #[error("Plugin '{0}' not found")]
+    NotFound(String),
+    #[error("Plugin '{0}' has no configuration")]
+    NotConfigurable(String),
+    #[error("Configuration does not match the plugin's config_schema: {}", .0.join("; "))]
+    Invalid(Vec<String>),
+    #[error("Plugin rejected the configuration: {0:#}")]
+    Rejected(anyhow::Error),
 }
 
 impl PluginRegistry {
@@ -213,6 +247,8 @@ impl PluginRegistry {
                 name,
                 version,
                 enabled: true,
+                plugin: None,
+                tools: Vec::new(),
             },
         );
 
@@ -289,6 +325,84 @@ impl PluginRegistry {
         Ok(list)
     }
 
+    /// Keep a registered plugin and the names of the tools it registered,
+    /// so it can be listed and reconfigured
+    pub fn attach_plugin(
+        &self,
+        id: &str,
+        plugin: Arc<dyn RustyclawPlugin>,
+        tools: Vec<String>,
+    ) -> Result<()> {
+        let mut plugins = self
+            .plugins
+            .lock()
+            .map_err(|e| anyhow!("Failed to acquire plugin registry lock: {}", e))?;
+        let entry = plugins
+            .get_mut(id)
+            .ok_or_else(|| anyhow!("Plugin '{}' not found", id))?;
+        entry.plugin = Some(plugin);
+        entry.tools = tools;
+        Ok(())
+    }
+
+    /// Registered plugins with their tools, by ID
+    pub fn plugin_infos(&self) -> Result<Vec<PluginInfo>> {
+        let plugins = self
+            .plugins
+            .lock()
+            .map_err(|e| anyhow!("Failed to acquire plugin registry lock: {}", e))?;
+        let mut infos: Vec<PluginInfo> = plugins
+            .values()
+            .map(|entry| PluginInfo {
+                id: entry.id.clone(),
+                name: entry.name.clone(),
+                version: entry.version.clone(),
+                description: entry
+                    .plugin
+                    .as_ref()
+                    .map(|plugin| plugin.description().to_string())
+                    .unwrap_or_default(),
+                enabled: entry.enabled,
+                tools: entry.tools.clone(),
+                config_schema: entry
+                    .plugin
+                    .as_ref()
+                    .and_then(|plugin| plugin.config_schema()),
+            })
+            .collect();
+        infos.sort_by(|a, b| a.id.cmp(&b.id));
+        Ok(infos)
+    }
+
+    /// Validate a configuration against a plugin's `config_schema` and
+    /// apply it to the loaded plugin
+    pub async fn reconfigure_plugin(
+        &self,
+        id: &str,
+        config: Value,
+    ) -> std::result::Result<(), PluginConfigError> {
+        let plugin = self
+            .plugins
+            .lock()
+            .ok()
+            .and_then(|plugins| plugins.get(id).and_then(|entry| entry.plugin.clone()))
+            .ok_or_else(|| PluginConfigError::NotFound(id.to_string()))?;
+        let schema = plugin
+            .config_schema()
+            .ok_or_else(|| PluginConfigError::NotConfigurable(id.to_string()))?;
+
+        let errors = crate::tools::output_schema::validate(&schema, &config);
+        if !errors.is_empty() {
+            return Err(PluginConfigError::Invalid(errors));
+        }
+        plugin
+            .reconfigure(config)
+            .await
+            .map_err(PluginConfigError::Rejected)?;
+        info!("Reconfigured plugin: {}", id);
+        Ok(())
+    }
+
     /// Get plugin count
     pub async fn plugin_count(&self) -> usize {
         self.plugins
@@ -324,6 +438,7 @@ mod tests {
             name: name.to_string(),
             description: "Test tool".to_string(),
             parameters: json!({"type": "object", "properties": {}}),
+            tags: vec![],
             execute: Arc::new(|_| {
                 Box::pin(async {
                     Ok(crate::plugins::traits::ToolResult {
@@ -66,6 +66,9 @@ pub struct Tool {
     /// Parameter schema (JSON Schema)
     pub parameters: Value,
 
+    /// Categories used to select the tool per turn; untagged tools are always offered
+    pub tags: Vec<String>,
+
     /// Tool executor function
     pub execute: ToolExecutor,
 }
@@ -91,10 +94,10 @@ pub trait RustyclawPlugin: Send + Sync {
 
     /// Called when plugin is registered
     /// Plugins register tools and hooks here
-    fn register(
-        &self,
-        api: &dyn PluginApi,
-    ) -> Pin<Box<dyn Future<Output = Result<()>> + Send + '_>>;
+    fn register<'a>(
+        &'a self,
+        api: &'a dyn PluginApi,
+    ) -> Pin<Box<dyn Future<Output = Result<()>> + Send + 'a>>;
 
     /// Called when plugin loads (after registration)
     fn on_load(&self) -> Pin<Box<dyn Future<Output = Result<()>> + Send + '_>> {
@@ -110,9 +113,22 @@ pub trait RustyclawPlugin: Send + Sync {
     fn config_schema(&self) -> Option<Value> {
         None
     }
+
+    /// Apply a new configuration while the plugin is loaded. Called with a
+    /// value already validated against [`RustyclawPlugin::config_schema`];
+    /// an error leaves the current configuration in place.
+    fn reconfigure(&self, _config: Value) -> Pin<Box<dyn Future<Output = Result<()>> + Send + '_>> {
+        Box::pin(async move {
+            Err(anyhow::anyhow!(
+                "Plugin '{}' cannot be reconfigured while loaded",
+                self.id()
+            ))
+        })
+    }
 }
 
 /// Plugin API - what plugins can do
+#[async_trait::async_trait]
 pub trait PluginApi: Send + Sync {
     /// Register a static tool
     fn register_tool(&self, tool: Tool) -> Result<()>;
@@ -120,11 +136,44 @@ pub trait PluginApi: Send + Sync {
     /// Register a context-aware tool factory
     fn register_tool_factory(&self, factory: ToolFactory) -> Result<()>;
 
-    /// Register a hook
-    fn register_hook(&self, hook_type: HookType, hook: PluginHook) -> Result<()>;
+    /// Register a hook at the default priority (0)
+    fn register_hook(&self, hook_type: HookType, hook: PluginHook) -> Result<()> {
+        self.register_hook_with_priority(hook_type, 0, hook)
+    }
+
+    /// Register a hook with a priority. Hooks of a type run highest
+    /// priority first, in registration order among equal priorities, and
+    /// each sees the message and parameters as modified by those before it.
+    fn register_hook_with_priority(
+        &self,
+        hook_type: HookType,
+        priority: i32,
+        hook: PluginHook,
+    ) -> Result<()>;
 
     /// Get global configuration
     fn get_config(&self) -> Arc<crate::Config>;
+
+    /// Handle for calling the LLM, e.g. from a tool the plugin registers
+    fn llm(&self) -> Result<crate::plugins::PluginLlm>;
+
+    /// Chat with the gateway's LLM (see [`PluginApi::llm`])
+    async fn chat(
+        &self,
+        messages: Vec<crate::llm::ChatMessage>,
+    ) -> Result<crate::llm::ChatResponse> {
+        self.llm()?.chat(messages).await
+    }
+
+    /// Read a value from the plugin's durable key-value store. Each plugin
+    /// only sees its own keys.
+    async fn kv_get(&self, key: &str) -> Result<Option<Value>>;
+
+    /// Store a JSON value under `key`, replacing any previous one
+    async fn kv_set(&self, key: &str, value: Value) -> Result<()>;
+
+    /// Remove `key`; returns whether it was set
+    async fn kv_delete(&self, key: &str) -> Result<bool>;
 }
 
 /// All available hook types
@@ -225,6 +274,43 @@ pub struct MessageReceivedEvent {
     pub sender: String,
 }
 
+impl MessageReceivedEvent {
+    /// Hook context carrying the event in its `message`, `channel` and
+    /// `sender` metadata
+    pub fn to_context(&self, session_id: &str) -> ToolContext {
+        ToolContext {
+            session_id: session_id.to_string(),
+            workspace_dir: None,
+            agent_id: None,
+            message_channel: Some(self.channel.clone()),
+            sandboxed: false,
+            metadata: HashMap::from([
+                ("message".to_string(), Value::String(self.message.clone())),
+                ("channel".to_string(), Value::String(self.channel.clone())),
+                ("sender".to_string(), Value::String(self.sender.clone())),
+            ]),
+        }
+    }
+
+    /// Event carried by a message_received hook context
+    pub fn from_context(ctx: &ToolContext) -> Option<Self> {
+        Some(Self {
+            message: ctx.metadata.get("message")?.as_str()?.to_string(),
+            channel: ctx.metadata.get("channel")?.as_str()?.to_string(),
+            sender: ctx.metadata.get("sender")?.as_str()?.to_string(),
+        })
+    }
+}
+
+/// What the message_received hooks decided about an inbound message
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum InboundMessage {
+    /// Process the message, possibly rewritten by a hook
+    Process(String),
+    /// Drop the message, answering with the canned reply if any
+    Blocked(Option<String>),
+}
+
 /// Hook event for message_sending
 #[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
 pub struct MessageSendingEvent {
@@ -232,8 +318,34 @@ pub struct MessageSendingEvent {
     pub channel: String,
 }
 
+impl MessageSendingEvent {
+    /// Hook context carrying the event in its `message` and `channel`
+    /// metadata
+    pub fn to_context(&self, session_id: &str) -> ToolContext {
+        ToolContext {
+            session_id: session_id.to_string(),
+            workspace_dir: None,
+            agent_id: None,
+            message_channel: Some(self.channel.clone()),
+            sandboxed: false,
+            metadata: HashMap::from([
+                ("message".to_string(), Value::String(self.message.clone())),
+                ("channel".to_string(), Value::String(self.channel.clone())),
+            ]),
+        }
+    }
+
+    /// Event carried by a message_sending hook context
+    pub fn from_context(ctx: &ToolContext) -> Option<Self> {
+        Some(Self {
+            message: ctx.metadata.get("message")?.as_str()?.to_string(),
+            channel: ctx.metadata.get("channel")?.as_str()?.to_string(),
+        })
+    }
+}
+
 /// Hook modification response - what hooks can return to modify behavior
-#[derive(Debug, Clone, serde::Serialize, serde::Deserialize)]
+#[derive(Debug, Clone, Default, serde::Serialize, serde::Deserialize)]
 pub struct HookModification {
     /// Override system prompt
     pub system_prompt_override: Option<String>,
@@ -249,6 +361,18 @@ pub struct HookModification {
 
     /// Modify tool parameters
     pub modified_parameters: Option<Value>,
+
+    /// Replace the message (message_received, message_sending)
+    #[serde(default)]
+    pub modified_message: Option<String>,
+
+    /// Drop the message (message_received, message_sending)
+    #[serde(default)]
+    pub block_message: Option<bool>,
+
+    /// Reply sent in place of a blocked inbound message (message_received)
+    #[serde(default)]
+    pub block_reply: Option<String>,
 }
 
 /// Plugin hook function
@@ -1,8 +1,9 @@
 use crate::config::SandboxConfig;
-use crate::sandbox::docker::{DockerClient, ExecResult};
+use crate::sandbox::docker::{ContainerInfo, DockerClient, ExecResult};
 use crate::sandbox::security::WorkspaceMode;
 use anyhow::{Context, Result};
 use chrono::{DateTime, Utc};
+use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
 use std::sync::Arc;
@@ -10,7 +11,7 @@ use tokio::sync::RwLock;
 use tracing::{debug, info};
 
 /// Scope for container lifecycle
-#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq, Hash)]
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq, Hash, JsonSchema)]
 #[serde(rename_all = "snake_case")]
 pub enum ContainerScope {
     /// One container per session
@@ -36,6 +37,7 @@ pub struct ContainerMetadata {
 /// Manages container lifecycle and caching
 pub struct ContainerManager {
     docker: Arc<DockerClient>,
+    /// Containers by key (see `container_key`)
     containers: Arc<RwLock<HashMap<String, ContainerMetadata>>>,
     config: SandboxConfig,
 }
@@ -46,7 +48,7 @@ impl ContainerManager {
         let docker = Arc::new(DockerClient::new().await?);
 
         // Discover existing containers from Docker
-        let containers = Self::discover_existing_containers(&docker).await?;
+        let containers = Self::discover_existing_containers(&docker, &config.image).await?;
 
         info!(
             "Container manager initialized with {} existing sandbox containers",
@@ -66,43 +68,51 @@ impl ContainerManager {
         self.docker.clone()
     }
 
-    /// Get or create a container for the given scope
-    pub async fn get_or_create_container(&self, scope_id: &str) -> Result<String> {
+    /// Get or create a container for the given scope, running `image`
+    /// (`None`: the configured image). A scope gets one container per image.
+    pub async fn get_or_create_container(
+        &self,
+        scope_id: &str,
+        image: Option<&str>,
+    ) -> Result<String> {
+        let image = image.unwrap_or(&self.config.image);
+        let key = container_key(scope_id, image, &self.config.image);
+
         // Check cache first
         {
             let containers = self.containers.read().await;
-            if let Some(meta) = containers.get(scope_id) {
+            if let Some(meta) = containers.get(&key) {
                 // Verify container still exists
                 if self.docker.container_exists(&meta.id).await? {
                     // Update last_used
-                    self.update_last_used(scope_id).await;
-                    debug!("Reusing existing container for scope: {}", scope_id);
+                    self.update_last_used(&key).await;
+                    debug!("Reusing existing container for scope: {}", key);
                     return Ok(meta.id.clone());
                 } else {
                     debug!(
                         "Container for scope {} no longer exists, will recreate",
-                        scope_id
+                        key
                     );
                 }
             }
         }
 
         // Create new container
-        let container_id = self.create_container(scope_id).await?;
+        let container_id = self.create_container(scope_id, &key, image).await?;
 
         // Cache it
         {
             let mut containers = self.containers.write().await;
             containers.insert(
-                scope_id.to_string(),
+                key.clone(),
                 ContainerMetadata {
                     id: container_id.clone(),
-                    name: format!("rustyclaw-sandbox-{}", scope_id),
+                    name: format!("rustyclaw-sandbox-{}", key),
                     scope: self.config.scope.clone(),
                     scope_id: scope_id.to_string(),
                     created_at: Utc::now(),
                     last_used: Utc::now(),
-                    image: self.config.image.clone(),
+                    image: image.to_string(),
                 },
             );
         }
@@ -110,9 +120,9 @@ impl ContainerManager {
         Ok(container_id)
     }
 
-    /// Create a new sandbox container
-    async fn create_container(&self, scope_id: &str) -> Result<String> {
-        let container_name = format!("rustyclaw-sandbox-{}", scope_id);
+    /// Create a new sandbox container from `image`, named after its key
+    async fn create_container(&self, scope_id: &str, key: &str, image: &str) -> Result<String> {
+        let container_name = format!("rustyclaw-sandbox-{}", key);
 
         // Prepare workspace
         let workspace_path = match self.config.workspace {
@@ -132,19 +142,28 @@ impl ContainerManager {
             }
         };
 
+        // Files written here outlive the command, so later calls in the same
+        // session (on the host or in this container) can read them
+        let session_path = crate::sandbox::ensure_session_workspace(scope_id)?;
+
         let config = crate::sandbox::docker::ContainerConfig {
-            image: self.config.image.clone(),
+            image: image.to_string(),
             workspace_mode: self.config.workspace.clone(),
             workspace_path,
+            session_path: Some(session_path.to_string_lossy().to_string()),
             network_enabled: self.config.network,
             setup_command: self.config.setup_command.clone(),
-            env_vars: vec![],
+            env_vars: vec![(
+                crate::sandbox::SESSION_WORKSPACE_ENV.to_string(),
+                crate::sandbox::SESSION_WORKSPACE_MOUNT.to_string(),
+            )],
             labels: HashMap::from([
                 (
                     "rustyclaw.scope".to_string(),
                     format!("{:?}", self.config.scope),
                 ),
                 ("rustyclaw.scope_id".to_string(), scope_id.to_string()),
+                ("rustyclaw.image".to_string(), image.to_string()),
                 ("rustyclaw.created_at".to_string(), Utc::now().to_rfc3339()),
             ]),
         };
@@ -204,9 +223,9 @@ impl ContainerManager {
     }
 
     /// Update the last_used timestamp for a container
-    async fn update_last_used(&self, scope_id: &str) {
+    async fn update_last_used(&self, key: &str) {
         let mut containers = self.containers.write().await;
-        if let Some(meta) = containers.get_mut(scope_id) {
+        if let Some(meta) = containers.get_mut(key) {
             meta.last_used = Utc::now();
         }
     }
@@ -217,19 +236,23 @@ impl ContainerManager {
         containers.values().cloned().collect()
     }
 
-    /// Remove a container
+    /// Remove the containers of a scope, whatever their image, and the
+    /// workspace of its session
     pub async fn remove_container(&self, scope_id: &str) -> Result<()> {
-        let container_id = {
+        let scope_containers: Vec<(String, String)> = {
             let containers = self.containers.read().await;
-            containers.get(scope_id).map(|m| m.id.clone())
+            containers
+                .iter()
+                .filter(|(key, meta)| key.as_str() == scope_id || meta.scope_id == scope_id)
+                .map(|(key, meta)| (key.clone(), meta.id.clone()))
+                .collect()
         };
 
-        if let Some(id) = container_id {
+        for (key, id) in scope_containers {
             self.docker.remove_container(&id).await?;
+            self.containers.write().await.remove(&key);
         }
-
-        let mut containers = self.containers.write().await;
-        containers.remove(scope_id);
+        crate::sandbox::remove_session_workspace(scope_id)?;
 
         info!("Removed sandbox container for scope: {}", scope_id);
         Ok(())
@@ -244,33 +267,192 @@ impl ContainerManager {
         self.docker.exec_command(container_id, command).await
     }
 
-    /// Discover existing containers with rustyclaw labels
+    /// Write a file into a container (via Docker's archive API). `path` is
+    /// absolute; missing parent directories are created.
+    pub async fn put_file(&self, container_id: &str, path: &str, bytes: &[u8]) -> Result<()> {
+        let name = path
+            .strip_prefix('/')
+            .ok_or_else(|| anyhow::anyhow!("Container path must be absolute: {}", path))?;
+        let archive = crate::sandbox::staging::pack_file(name, bytes)?;
+        self.docker.upload_archive(container_id, "/", archive).await
+    }
+
+    /// Read a file out of a container
+    pub async fn get_file(&self, container_id: &str, path: &str) -> Result<Vec<u8>> {
+        let archive = self.docker.download_archive(container_id, path).await?;
+        crate::sandbox::staging::unpack_file(&archive)
+            .with_context(|| format!("Failed to read {} from container", path))
+    }
+
+    /// Pull `image` unless Docker already has it
+    pub async fn pull_image(&self, image: &str) -> Result<()> {
+        self.docker.pull_image(image).await
+    }
+
+    /// Discover existing containers with rustyclaw labels, keyed as
+    /// `get_or_create_container` looks them up
     async fn discover_existing_containers(
         docker: &Arc<DockerClient>,
+        default_image: &str,
     ) -> Result<HashMap<String, ContainerMetadata>> {
         let sandbox_containers = docker.list_sandbox_containers().await?;
         let mut result = HashMap::new();
 
         for container in sandbox_containers {
-            // Try to extract scope_id from container name
-            // Name format: rustyclaw-sandbox-{scope_id}
-            if let Some(scope_id_str) = container.name.strip_prefix("rustyclaw-sandbox-") {
-                let scope_id = scope_id_str.to_string();
-                result.insert(
-                    scope_id.clone(),
-                    ContainerMetadata {
-                        id: container.id,
-                        name: container.name,
-                        scope: ContainerScope::Session, // Default, could be improved
-                        scope_id,
-                        created_at: Utc::now(), // Could be improved by reading from labels
-                        last_used: Utc::now(),
-                        image: "unknown".to_string(), // Could be improved
-                    },
-                );
+            let name = container.name.clone();
+            match metadata_from_labels(container, default_image) {
+                Some(meta) => {
+                    let key = container_key(&meta.scope_id, &meta.image, default_image);
+                    result.insert(key, meta);
+                }
+                None => debug!("Ignoring sandbox container without a scope ID: {}", name),
             }
         }
 
         Ok(result)
     }
 }
+
+/// Metadata of a discovered container, from the labels it was created with.
+/// Containers from before images were labelled run the configured image.
+fn metadata_from_labels(
+    container: ContainerInfo,
+    default_image: &str,
+) -> Option<ContainerMetadata> {
+    let labels = container.labels;
+    let scope_id = labels.get("rustyclaw.scope_id")?.clone();
+    let scope = match labels.get("rustyclaw.scope").map(String::as_str) {
+        Some("Agent") => ContainerScope::Agent,
+        Some("Shared") => ContainerScope::Shared,
+        _ => ContainerScope::Session,
+    };
+    let created_at = labels
+        .get("rustyclaw.created_at")
+        .and_then(|created| DateTime::parse_from_rfc3339(created).ok())
+        .map_or_else(Utc::now, |created| created.with_timezone(&Utc));
+    let image = labels
+        .get("rustyclaw.image")
+        .cloned()
+        .unwrap_or_else(|| default_image.to_string());
+
+    Some(ContainerMetadata {
+        id: container.id,
+        name: container.name,
+        scope,
+        scope_id,
+        created_at,
+        last_used: Utc::now(),
+        image,
+    })
+}
+
+/// Key of a scope's container for an image: the scope ID for the configured
+/// image, so its containers keep their names, otherwise the scope ID with the
+/// image as a name-safe suffix
+fn container_key(scope_id: &str, image: &str, default_image: &str) -> String {
+    if image == default_image {
+        return scope_id.to_string();
+    }
+    let suffix: String = image
+        .chars()
+        .map(|c| {
+            if c.is_ascii_alphanumeric() || matches!(c, '_' | '.' | '-') {
+                c
+            } else {
+                '-'
+            }
+        })
+        .collect();
+    format!("{}-{}", scope_id, suffix)
+}
+
+/// Check that an image reference is well-formed (`[registry/]name[:tag][@digest]`),
+/// before trying to pull it
+pub fn validate_image_reference(image: &str) -> Result<()> {
+    let valid = !image.is_empty()
+        && image.len() <= 255
+        && !image.starts_with(['-', '.', '/', ':', '@'])
+        && image
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || matches!(c, '.' | '_' | '-' | '/' | ':' | '@'));
+    if !valid {
+        anyhow::bail!("Invalid container image reference '{}'", image);
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_custom_image_gets_its_own_container() {
+        let default = "python:3.11-slim";
+        // The configured image keeps the scope's existing container
+        assert_eq!(container_key("session-1", default, default), "session-1");
+
+        let node = container_key("session-1", "node:20-alpine", default);
+        let numpy = container_key("session-1", "ghcr.io/acme/numpy:1.26", default);
+        assert_eq!(node, "session-1-node-20-alpine");
+        assert_eq!(numpy, "session-1-ghcr.io-acme-numpy-1.26");
+        assert_ne!(container_key("session-2", "node:20-alpine", default), node);
+    }
+
+    #[test]
+    fn test_discovered_containers_keep_their_labels() {
+        let default = "python:3.11-slim";
+        let container = |labels: &[(&str, &str)]| ContainerInfo {
+            id: "abc123".to_string(),
+            name: "rustyclaw-sandbox-whatever".to_string(),
+            labels: labels
+                .iter()
+                .map(|(key, value)| (key.to_string(), value.to_string()))
+                .collect(),
+        };
+
+        let meta = metadata_from_labels(
+            container(&[
+                ("rustyclaw.scope", "Agent"),
+                ("rustyclaw.scope_id", "session-1"),
+                ("rustyclaw.image", "node:20-alpine"),
+                ("rustyclaw.created_at", "2026-01-02T03:04:05+00:00"),
+            ]),
+            default,
+        )
+        .unwrap();
+        assert_eq!(meta.scope, ContainerScope::Agent);
+        assert_eq!(meta.scope_id, "session-1");
+        assert_eq!(meta.image, "node:20-alpine");
+        assert_eq!(meta.created_at.to_rfc3339(), "2026-01-02T03:04:05+00:00");
+
+        // Unlabelled images are the configured one
+        let meta = metadata_from_labels(
+            container(&[
+                ("rustyclaw.scope", "Session"),
+                ("rustyclaw.scope_id", "session-2"),
+            ]),
+            default,
+        )
+        .unwrap();
+        assert_eq!(meta.image, default);
+
+        assert!(
+            metadata_from_labels(container(&[("rustyclaw.scope", "Session")]), default).is_none()
+        );
+    }
+
+    #[test]
+    fn test_validate_image_reference() {
+        for image in [
+            "python:3.11-slim",
+            "node",
+            "ghcr.io/acme/numpy:1.26",
+            "localhost:5000/tools@sha256:0123abcd",
+        ] {
+            assert!(validate_image_reference(image).is_ok(), "{}", image);
+        }
+        for image in ["", "-rm", "python 3", "node;rm -rf /", "ubuntu\n"] {
+            assert!(validate_image_reference(image).is_err(), "{}", image);
+        }
+    }
+}
@@ -25,6 +25,9 @@ pub struct ContainerConfig {
     pub image: String,
     pub workspace_mode: crate::sandbox::security::WorkspaceMode,
     pub workspace_path: String,
+    /// Session workspace mounted read-write at `/session`, whatever the
+    /// workspace mode
+    pub session_path: Option<String>,
     pub network_enabled: bool,
     #[allow(dead_code)]
     pub setup_command: Option<String>,
@@ -61,9 +64,10 @@ impl DockerClient {
             .client
             .create_image(Some(create_image_options), None, None);
 
-        // Consume the stream to ensure the image is pulled
-        while stream.next().await.is_some() {
-            // Just consume the stream
+        // Consume the stream to ensure the image is pulled; an image that
+        // cannot be pulled fails here rather than at container creation
+        while let Some(progress) = stream.next().await {
+            progress.with_context(|| format!("Failed to pull Docker image {}", image))?;
         }
 
         info!("Successfully pulled Docker image: {}", image);
@@ -89,6 +93,8 @@ impl DockerClient {
         // Add default environment
         env_vars
             .push("PATH=/usr/local/sbin:/usr/local/bin:/usr/sbin:/usr/bin:/sbin:/bin".to_string());
+        env_vars.push("HOME=/workspace".to_string());
+        env_vars.push("LANG=C.UTF-8".to_string());
 
         // Prepare bind mounts for workspace
         let mut binds = vec![];
@@ -113,6 +119,14 @@ impl DockerClient {
             // User can mount isolated sandbox dir if needed
         }
 
+        if let Some(session_path) = &config.session_path {
+            binds.push(format!(
+                "{}:{}:rw",
+                session_path,
+                crate::sandbox::SESSION_WORKSPACE_MOUNT
+            ));
+        }
+
         // Create container config
         let container_config = Config {
             image: Some(config.image.clone()),
@@ -217,6 +231,39 @@ impl DockerClient {
         })
     }
 
+    /// Extract a tar archive into a container's filesystem at `dest`
+    pub async fn upload_archive(
+        &self,
+        container_id: &str,
+        dest: &str,
+        archive: Vec<u8>,
+    ) -> Result<()> {
+        let options = bollard::container::UploadToContainerOptions {
+            path: dest.to_string(),
+            ..Default::default()
+        };
+        self.client
+            .upload_to_container(container_id, Some(options), archive.into())
+            .await
+            .context("Failed to upload archive to container")
+    }
+
+    /// Download a path from a container as a tar archive
+    pub async fn download_archive(&self, container_id: &str, path: &str) -> Result<Vec<u8>> {
+        let options = bollard::container::DownloadFromContainerOptions {
+            path: path.to_string(),
+        };
+        let mut stream = self
+            .client
+            .download_from_container(container_id, Some(options));
+
+        let mut archive = Vec::new();
+        while let Some(chunk) = stream.next().await {
+            archive.extend_from_slice(&chunk.context("Failed to download archive from container")?);
+        }
+        Ok(archive)
+    }
+
     /// List all containers with rustyclaw labels
     pub async fn list_sandbox_containers(&self) -> Result<Vec<ContainerInfo>> {
         use bollard::container::ListContainersOptions;
@@ -243,7 +290,8 @@ impl DockerClient {
                 (container.id, container.names.and_then(|mut n| n.pop()))
             {
                 let name = name.trim_start_matches('/').to_string();
-                result.push(ContainerInfo { id, name });
+                let labels = container.labels.unwrap_or_default();
+                result.push(ContainerInfo { id, name, labels });
             }
         }
 
@@ -298,4 +346,5 @@ impl DockerClient {
 pub struct ContainerInfo {
     pub id: String,
     pub name: String,
+    pub labels: HashMap<String, String>,
 }
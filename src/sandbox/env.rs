@@ -0,0 +1,55 @@
+use once_cell::sync::OnceCell;
+use tracing::debug;
+
+/// Global allowlist of host environment variables passed to tool processes
+static ENV_ALLOWLIST: OnceCell<Vec<String>> = OnceCell::new();
+
+/// Default environment variables safe to pass to model-generated code
+pub fn default_env_allowlist() -> Vec<String> {
+    vec!["PATH".to_string(), "LANG".to_string(), "HOME".to_string()]
+}
+
+/// Initialize the global environment allowlist from configuration
+pub fn init_env_allowlist(names: Vec<String>) {
+    debug!("Tool environment allowlist: {:?}", names);
+    ENV_ALLOWLIST.set(names).ok();
+}
+
+/// Collect the allowlisted variables from the current process environment.
+///
+/// Everything else (API keys, tokens, database URLs) is scrubbed so secrets in
+/// the gateway's environment never reach exec/skill child processes.
+pub fn allowed_env() -> Vec<(String, String)> {
+    let allowlist = ENV_ALLOWLIST.get_or_init(default_env_allowlist);
+    filter_env(std::env::vars(), allowlist)
+}
+
+/// Keep only variables whose names appear in the allowlist
+fn filter_env(
+    vars: impl Iterator<Item = (String, String)>,
+    allowlist: &[String],
+) -> Vec<(String, String)> {
+    vars.filter(|(name, _)| allowlist.iter().any(|allowed| allowed == name))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_filter_env_keeps_only_allowlisted() {
+        let vars = vec![
+            ("PATH".to_string(), "/usr/bin".to_string()),
+            ("OPENAI_API_KEY".to_string(), "sk-secret".to_string()),
+            ("LANG".to_string(), "C.UTF-8".to_string()),
+        ];
+
+        let filtered = filter_env(vars.into_iter(), &default_env_allowlist());
+
+        assert_eq!(filtered.len(), 2);
+        assert!(filtered.iter().any(|(k, _)| k == "PATH"));
+        assert!(filtered.iter().any(|(k, _)| k == "LANG"));
+        assert!(!filtered.iter().any(|(k, _)| k == "OPENAI_API_KEY"));
+    }
+}
@@ -1,13 +1,26 @@
 mod container;
 mod docker;
+mod env;
 mod pruning;
 mod security;
+mod session_workspace;
+mod staging;
 mod workspace;
 
-pub use container::{ContainerMetadata, ContainerScope};
+pub use container::{validate_image_reference, ContainerMetadata, ContainerScope};
 pub use docker::ExecResult;
+pub use env::{allowed_env, default_env_allowlist, init_env_allowlist};
 pub use pruning::PruningConfig;
 pub use security::{SandboxMode, WorkspaceMode};
+pub use session_workspace::{
+    default_session_workspace_root, ensure_session_workspace, init_session_workspace_root,
+    remove_session_workspace, session_workspace_path, SESSION_WORKSPACE_ENV,
+    SESSION_WORKSPACE_MOUNT,
+};
+pub use staging::{
+    stage_files_on_host, staged_files, validate_workspace_path, StagedFile, MAX_STAGED_FILES,
+    MAX_STAGED_FILE_BYTES, MAX_STAGED_TOTAL_BYTES,
+};
 
 use crate::config::SandboxConfig;
 use anyhow::{Context, Result};
@@ -15,6 +28,7 @@ use container::ContainerManager;
 use pruning::PruningService;
 use security::SecurityPolicy;
 use std::sync::Arc;
+use std::time::Duration;
 use tracing::info;
 
 /// Main sandbox manager API
@@ -67,43 +81,105 @@ impl SandboxManager {
         })
     }
 
-    /// Execute a command with sandboxing applied based on security policy
+    /// Execute a command with sandboxing applied based on security policy.
+    ///
+    /// `timeout` bounds the command itself: host processes are killed when the
+    /// returned future is dropped, and container commands run under `timeout`.
+    /// `image` picks the container's image (`None`: the configured one).
     pub async fn execute(
         &self,
         session_id: &str,
         is_main_session: bool,
+        image: Option<&str>,
         command: &[&str],
+        timeout: Duration,
     ) -> Result<ExecResult> {
         if !self.security_policy.should_sandbox(is_main_session) {
             // Execute on host
-            return self.execute_on_host(command).await;
+            return execute_on_host(Some(session_id), command).await;
         }
 
-        // Execute in sandbox
+        self.execute_sandboxed(session_id, image, command, timeout)
+            .await
+    }
+
+    /// Execute a command in the session's container for `image`, whatever
+    /// the sandbox mode says (used when the user approved a call to run in
+    /// the sandbox)
+    pub async fn execute_sandboxed(
+        &self,
+        session_id: &str,
+        image: Option<&str>,
+        command: &[&str],
+        timeout: Duration,
+    ) -> Result<ExecResult> {
         let container_id = self
             .container_manager
-            .get_or_create_container(session_id)
+            .get_or_create_container(session_id, image)
             .await?;
 
+        // Dropping a Docker exec stream does not stop the process, so let the
+        // container enforce the deadline itself
+        let secs = timeout.as_secs().max(1).to_string();
+        let mut bounded = vec!["timeout", "-s", "KILL", secs.as_str()];
+        bounded.extend_from_slice(command);
+
         self.container_manager
-            .execute_in_container(&container_id, command)
+            .execute_in_container(&container_id, &bounded)
             .await
     }
 
-    /// Execute a command directly on the host
-    async fn execute_on_host(&self, command: &[&str]) -> Result<ExecResult> {
-        use std::process::Command;
+    /// Stage files into the session workspace before a command runs: in the
+    /// session's container when the security policy sandboxes it, on the host
+    /// otherwise (mirrors `execute`)
+    pub async fn stage_files(
+        &self,
+        session_id: &str,
+        is_main_session: bool,
+        files: &[StagedFile],
+    ) -> Result<()> {
+        if !self.security_policy.should_sandbox(is_main_session) {
+            return stage_files_on_host(session_id, files);
+        }
 
-        let output = Command::new(command[0])
-            .args(&command[1..])
-            .output()
-            .context("Failed to execute command on host")?;
+        self.stage_files_sandboxed(session_id, files).await
+    }
 
-        Ok(ExecResult {
-            stdout: String::from_utf8_lossy(&output.stdout).to_string(),
-            stderr: String::from_utf8_lossy(&output.stderr).to_string(),
-            exit_code: output.status.code().unwrap_or(-1) as i64,
-        })
+    /// Stage files into the session's container, under `/session`
+    pub async fn stage_files_sandboxed(
+        &self,
+        session_id: &str,
+        files: &[StagedFile],
+    ) -> Result<()> {
+        let container_id = self
+            .container_manager
+            .get_or_create_container(session_id, None)
+            .await?;
+
+        for file in files {
+            let path = format!("{}/{}", SESSION_WORKSPACE_MOUNT, file.path.display());
+            self.container_manager
+                .put_file(&container_id, &path, &file.content)
+                .await?;
+        }
+        Ok(())
+    }
+
+    /// Read a file back from the session's container, e.g. output a command
+    /// left in `/session`
+    pub async fn read_file(&self, session_id: &str, path: &str) -> Result<Vec<u8>> {
+        let container_id = self
+            .container_manager
+            .get_or_create_container(session_id, None)
+            .await?;
+
+        self.container_manager.get_file(&container_id, path).await
+    }
+
+    /// Pull `image` ahead of its first container, so a broken image shows up
+    /// before a call needs it
+    pub async fn pull_image(&self, image: &str) -> Result<()> {
+        self.container_manager.pull_image(image).await
     }
 
     /// List all active sandbox containers
@@ -133,3 +209,30 @@ impl SandboxManager {
         )
     }
 }
+
+/// Execute a command directly on the host with the allowlisted environment.
+///
+/// With a session, the session's workspace directory is created and passed
+/// in `RUSTYCLAW_SESSION_WORKSPACE`. The process is killed when the returned
+/// future is dropped, so callers bound it with a timeout.
+pub async fn execute_on_host(session_id: Option<&str>, command: &[&str]) -> Result<ExecResult> {
+    use tokio::process::Command;
+
+    let mut cmd = Command::new(command[0]);
+    cmd.args(&command[1..]).env_clear().envs(allowed_env());
+    if let Some(session_id) = session_id {
+        cmd.env(SESSION_WORKSPACE_ENV, ensure_session_workspace(session_id)?);
+    }
+
+    let output = cmd
+        .kill_on_drop(true)
+        .output()
+        .await
+        .context("Failed to execute command on host")?;
+
+    Ok(ExecResult {
+        stdout: String::from_utf8_lossy(&output.stdout).to_string(),
+        stderr: String::from_utf8_lossy(&output.stderr).to_string(),
+        exit_code: output.status.code().unwrap_or(-1) as i64,
+    })
+}
@@ -1,13 +1,14 @@
 use crate::sandbox::container::ContainerManager;
 use anyhow::Result;
 use chrono::Utc;
+use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 use std::sync::Arc;
 use tokio::time::{interval, Duration};
 use tracing::info;
 
 /// Configuration for container pruning
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
 pub struct PruningConfig {
     /// Enable automatic pruning
     #[serde(default = "default_pruning_enabled")]
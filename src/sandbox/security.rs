@@ -1,7 +1,8 @@
+use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 
 /// Sandbox execution modes
-#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Default)]
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Default, JsonSchema)]
 #[serde(rename_all = "snake_case")]
 pub enum SandboxMode {
     /// No sandboxing, direct host execution
@@ -13,8 +14,19 @@ pub enum SandboxMode {
     All,
 }
 
+impl SandboxMode {
+    /// Whether commands of a session run in the sandbox in this mode
+    pub fn sandboxes(&self, is_main_session: bool) -> bool {
+        match self {
+            SandboxMode::Off => false,
+            SandboxMode::NonMain => !is_main_session,
+            SandboxMode::All => true,
+        }
+    }
+}
+
 /// Workspace access modes
-#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Default)]
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Default, JsonSchema)]
 #[serde(rename_all = "snake_case")]
 pub enum WorkspaceMode {
     /// Isolated workspace per container
@@ -34,11 +46,7 @@ pub struct SecurityPolicy {
 impl SecurityPolicy {
     /// Check if a session should be sandboxed
     pub fn should_sandbox(&self, is_main_session: bool) -> bool {
-        match self.mode {
-            SandboxMode::Off => false,
-            SandboxMode::NonMain => !is_main_session,
-            SandboxMode::All => true,
-        }
+        self.mode.sandboxes(is_main_session)
     }
 
     /// Get a human-readable description of the mode
@@ -0,0 +1,91 @@
+use anyhow::{Context, Result};
+use once_cell::sync::OnceCell;
+use std::path::{Path, PathBuf};
+use tracing::debug;
+
+/// Environment variable telling exec/skill processes where their session's
+/// workspace directory is
+pub const SESSION_WORKSPACE_ENV: &str = "RUSTYCLAW_SESSION_WORKSPACE";
+
+/// Where the session workspace is mounted inside sandbox containers
+pub const SESSION_WORKSPACE_MOUNT: &str = "/session";
+
+/// Global directory holding one workspace directory per session
+static SESSION_WORKSPACE_ROOT: OnceCell<PathBuf> = OnceCell::new();
+
+/// Default root for session workspaces: `~/.rustyclaw/sessions`
+pub fn default_session_workspace_root() -> PathBuf {
+    dirs::home_dir()
+        .map(|h| h.join(".rustyclaw").join("sessions"))
+        .unwrap_or_else(|| std::env::temp_dir().join("rustyclaw-sessions"))
+}
+
+/// Initialize the global session workspace root from configuration
+pub fn init_session_workspace_root(root: PathBuf) {
+    debug!("Session workspace root: {}", root.display());
+    SESSION_WORKSPACE_ROOT.set(root).ok();
+}
+
+fn session_workspace_root() -> &'static Path {
+    SESSION_WORKSPACE_ROOT.get_or_init(default_session_workspace_root)
+}
+
+/// Host path of a session's workspace directory.
+///
+/// Characters other than letters, digits, `-` and `_` are replaced, so a
+/// session id can never point outside the root.
+pub fn session_workspace_path(session_id: &str) -> PathBuf {
+    let dir: String = session_id
+        .chars()
+        .map(|c| {
+            if c.is_ascii_alphanumeric() || c == '-' || c == '_' {
+                c
+            } else {
+                '_'
+            }
+        })
+        .collect();
+    session_workspace_root().join(dir)
+}
+
+/// Create a session's workspace directory if needed and return its path
+pub fn ensure_session_workspace(session_id: &str) -> Result<PathBuf> {
+    let path = session_workspace_path(session_id);
+    std::fs::create_dir_all(&path).with_context(|| {
+        format!(
+            "Failed to create session workspace directory {}",
+            path.display()
+        )
+    })?;
+    Ok(path)
+}
+
+/// Delete a session's workspace directory and everything in it
+pub fn remove_session_workspace(session_id: &str) -> Result<()> {
+    let path = session_workspace_path(session_id);
+    match std::fs::remove_dir_all(&path) {
+        Ok(()) => {
+            debug!("Removed session workspace {}", path.display());
+            Ok(())
+        }
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(()),
+        Err(e) => Err(e).with_context(|| {
+            format!(
+                "Failed to remove session workspace directory {}",
+                path.display()
+            )
+        }),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_session_id_cannot_escape_root() {
+        let path = session_workspace_path("../../etc");
+        assert_eq!(path.parent().unwrap(), session_workspace_root());
+        assert_eq!(path.file_name().unwrap(), "______etc");
+    }
+}
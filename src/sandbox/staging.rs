@@ -0,0 +1,199 @@
+use anyhow::{anyhow, Context, Result};
+use std::collections::HashMap;
+use std::path::{Component, Path, PathBuf};
+
+/// Largest single file that can be staged before a command runs
+pub const MAX_STAGED_FILE_BYTES: usize = 1024 * 1024;
+
+/// Largest total size of the files staged for one command
+pub const MAX_STAGED_TOTAL_BYTES: usize = 4 * 1024 * 1024;
+
+/// Most files that can be staged for one command
+pub const MAX_STAGED_FILES: usize = 32;
+
+const BLOCK: usize = 512;
+
+/// A file to write into the session workspace before a command runs
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct StagedFile {
+    /// Path relative to the session workspace
+    pub path: PathBuf,
+    pub content: Vec<u8>,
+}
+
+/// Validate inline `{path: content}` files against the staging limits.
+///
+/// Paths must be relative and stay inside the session workspace: absolute
+/// paths and `..` components are rejected.
+pub fn staged_files(files: &HashMap<String, String>) -> Result<Vec<StagedFile>> {
+    if files.len() > MAX_STAGED_FILES {
+        return Err(anyhow!(
+            "Too many files to stage: {} (limit {})",
+            files.len(),
+            MAX_STAGED_FILES
+        ));
+    }
+
+    let total: usize = files.values().map(|c| c.len()).sum();
+    if total > MAX_STAGED_TOTAL_BYTES {
+        return Err(anyhow!(
+            "Staged files are {} bytes in total (limit {})",
+            total,
+            MAX_STAGED_TOTAL_BYTES
+        ));
+    }
+
+    let mut staged: Vec<StagedFile> = files
+        .iter()
+        .map(|(path, content)| {
+            if content.len() > MAX_STAGED_FILE_BYTES {
+                return Err(anyhow!(
+                    "Staged file '{}' is {} bytes (limit {})",
+                    path,
+                    content.len(),
+                    MAX_STAGED_FILE_BYTES
+                ));
+            }
+            Ok(StagedFile {
+                path: validate_workspace_path(path)?,
+                content: content.as_bytes().to_vec(),
+            })
+        })
+        .collect::<Result<_>>()?;
+    staged.sort_by(|a, b| a.path.cmp(&b.path));
+    Ok(staged)
+}
+
+/// Check that a path into the session workspace is relative and has no `..`
+/// or `.` components
+pub fn validate_workspace_path(path: &str) -> Result<PathBuf> {
+    let parsed = Path::new(path);
+    let valid = !path.is_empty()
+        && parsed
+            .components()
+            .all(|c| matches!(c, Component::Normal(_)));
+    if !valid {
+        return Err(anyhow!(
+            "Invalid workspace path '{}': must be relative and stay inside the session workspace",
+            path
+        ));
+    }
+    Ok(parsed.to_path_buf())
+}
+
+/// Write staged files into the session's workspace directory on the host
+pub fn stage_files_on_host(session_id: &str, files: &[StagedFile]) -> Result<()> {
+    let root = super::ensure_session_workspace(session_id)?;
+    for file in files {
+        let target = root.join(&file.path);
+        if let Some(parent) = target.parent() {
+            std::fs::create_dir_all(parent)
+                .with_context(|| format!("Failed to create {}", parent.display()))?;
+        }
+        std::fs::write(&target, &file.content)
+            .with_context(|| format!("Failed to stage {}", file.path.display()))?;
+    }
+    Ok(())
+}
+
+/// Pack one file into a tar archive, the format Docker's archive API takes
+pub(crate) fn pack_file(name: &str, content: &[u8]) -> Result<Vec<u8>> {
+    if name.len() > 100 {
+        return Err(anyhow!("File path too long to stage: {}", name));
+    }
+
+    let mut header = [0u8; BLOCK];
+    header[..name.len()].copy_from_slice(name.as_bytes());
+    write_octal(&mut header[100..108], 0o644);
+    write_octal(&mut header[108..116], 0);
+    write_octal(&mut header[116..124], 0);
+    write_octal(&mut header[124..136], content.len() as u64);
+    write_octal(&mut header[136..148], 0);
+    header[156] = b'0';
+    header[257..263].copy_from_slice(b"ustar\0");
+    header[263..265].copy_from_slice(b"00");
+
+    // The checksum is computed with its own field filled with spaces
+    header[148..156].fill(b' ');
+    let checksum: u32 = header.iter().map(|&b| b as u32).sum();
+    write_octal(&mut header[148..155], checksum as u64);
+
+    let padding = (BLOCK - content.len() % BLOCK) % BLOCK;
+    let mut archive = Vec::with_capacity(BLOCK * 3 + content.len() + padding);
+    archive.extend_from_slice(&header);
+    archive.extend_from_slice(content);
+    archive.resize(archive.len() + padding + BLOCK * 2, 0);
+    Ok(archive)
+}
+
+/// Extract the first regular file from a tar archive
+pub(crate) fn unpack_file(archive: &[u8]) -> Result<Vec<u8>> {
+    let mut offset = 0;
+    while offset + BLOCK <= archive.len() {
+        let header = &archive[offset..offset + BLOCK];
+        if header.iter().all(|&b| b == 0) {
+            break;
+        }
+
+        let size = read_octal(&header[124..136]).context("Malformed tar header")? as usize;
+        let start = offset + BLOCK;
+        let end = start + size;
+        if end > archive.len() {
+            return Err(anyhow!("Truncated tar archive"));
+        }
+
+        if matches!(header[156], b'0' | 0) {
+            return Ok(archive[start..end].to_vec());
+        }
+        offset = start + size.div_ceil(BLOCK) * BLOCK;
+    }
+    Err(anyhow!("Archive does not contain a regular file"))
+}
+
+/// Write a zero-padded, NUL-terminated octal number into a header field
+fn write_octal(field: &mut [u8], value: u64) {
+    let digits = format!("{:0width$o}", value, width = field.len() - 1);
+    field[..digits.len()].copy_from_slice(digits.as_bytes());
+    field[digits.len()] = 0;
+}
+
+fn read_octal(field: &[u8]) -> Option<u64> {
+    let text = std::str::from_utf8(field).ok()?;
+    let text = text.trim_matches(|c: char| c == '\0' || c == ' ');
+    if text.is_empty() {
+        return Some(0);
+    }
+    u64::from_str_radix(text, 8).ok()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_staged_file_round_trips_through_archive() {
+        let content = b"id,value\n1,42\n".repeat(100);
+        let archive = pack_file("session/data/input.csv", &content).unwrap();
+
+        assert_eq!(archive.len() % BLOCK, 0);
+        assert_eq!(unpack_file(&archive).unwrap(), content);
+    }
+
+    #[test]
+    fn test_staged_paths_and_sizes_are_validated() {
+        let files = HashMap::from([("data/input.csv".to_string(), "1,2".to_string())]);
+        let staged = staged_files(&files).unwrap();
+        assert_eq!(staged[0].path, PathBuf::from("data/input.csv"));
+
+        for bad in ["../escape.txt", "/etc/passwd", "a/../../b", ""] {
+            let files = HashMap::from([(bad.to_string(), "x".to_string())]);
+            assert!(staged_files(&files).is_err(), "{:?} accepted", bad);
+        }
+
+        let files = HashMap::from([("big.bin".to_string(), "x".repeat(MAX_STAGED_FILE_BYTES + 1))]);
+        assert!(staged_files(&files)
+            .unwrap_err()
+            .to_string()
+            .contains("limit"));
+    }
+}
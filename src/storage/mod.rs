@@ -4,6 +4,7 @@ use anyhow::Result;
 use async_trait::async_trait;
 use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Session {
@@ -26,6 +27,8 @@ pub struct Message {
     pub model_used: Option<String>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub tokens: Option<usize>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub metadata: Option<serde_json::Value>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -39,6 +42,44 @@ pub struct User {
     pub password_hash: Option<String>,
 }
 
+/// Users listed by `Storage::list_users`; unset fields match everyone
+#[derive(Debug, Clone, Default)]
+pub struct UserFilter {
+    /// Part of the username, case-insensitive
+    pub username: Option<String>,
+    /// Exact role
+    pub role: Option<String>,
+}
+
+/// A window into a listing: `limit` items (all when `None`) after skipping
+/// `offset`
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Page {
+    pub limit: Option<usize>,
+    pub offset: usize,
+}
+
+impl Page {
+    pub fn new(limit: usize, offset: usize) -> Self {
+        Self {
+            limit: Some(limit),
+            offset,
+        }
+    }
+
+    /// Every item
+    pub fn all() -> Self {
+        Self::default()
+    }
+}
+
+/// One page of a listing with the number of items on all pages
+#[derive(Debug, Clone)]
+pub struct Paged<T> {
+    pub items: Vec<T>,
+    pub total: usize,
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Identity {
     pub provider: String,
@@ -47,6 +88,253 @@ pub struct Identity {
     pub label: Option<String>,
     pub created_at: DateTime<Utc>,
     pub last_used_at: Option<DateTime<Utc>>,
+    /// Scopes granted to an API token; `None` means unrestricted
+    #[serde(default)]
+    pub scopes: Option<Vec<String>>,
+}
+
+/// One-time code, either for linking a channel or inviting a new account
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PendingLink {
+    pub code: String,
+    /// User claiming the channel, or the admin who created the invite
+    pub user_id: String,
+    pub provider: String,
+    /// Role of the account an invite creates
+    pub role: Option<String>,
+    /// Scopes of the token an invite issues
+    pub scopes: Option<Vec<String>>,
+    pub expires_at: DateTime<Utc>,
+    pub used: bool,
+}
+
+/// Token issued to an admin to act as a user while debugging; every grant is
+/// kept as an audit record
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Impersonation {
+    pub token: String,
+    pub admin_id: String,
+    /// User the token acts as
+    pub user_id: String,
+    pub reason: Option<String>,
+    pub created_at: DateTime<Utc>,
+    pub expires_at: DateTime<Utc>,
+    /// Scopes of the token that issued the grant; `None` means unrestricted
+    pub scopes: Option<Vec<String>>,
+}
+
+/// Audit record of a single tool execution
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ToolExecution {
+    pub id: String,
+    pub session_id: String,
+    pub tool_name: String,
+    /// Redacted, truncated summary of the arguments
+    pub arguments: String,
+    /// Truncated output (or error message)
+    pub output: String,
+    pub success: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub duration_ms: Option<u64>,
+    pub created_at: DateTime<Utc>,
+}
+
+/// One audited tool call, as counted by the usage statistics
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ToolCallSample {
+    pub tool_name: String,
+    /// Owner of the session the call was made in
+    pub user_id: String,
+    pub success: bool,
+    pub duration_ms: Option<u64>,
+}
+
+/// Tool approval request awaiting a user decision
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PendingApprovalRecord {
+    pub request_id: String,
+    pub session_id: String,
+    pub tool_name: String,
+    pub arguments: String,
+    pub policy: String,
+    pub sandbox_available: bool,
+    pub created_at: DateTime<Utc>,
+    pub expires_at: DateTime<Utc>,
+}
+
+/// Instruction pinned to a session's system prompt
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SessionNote {
+    pub id: String,
+    pub session_id: String,
+    pub content: String,
+    pub created_at: DateTime<Utc>,
+}
+
+/// Summary of a session, covering its messages up to `summary_through`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SessionSummary {
+    pub session_id: String,
+    pub summary: String,
+    /// ID of the last message the summary covers
+    pub summary_through: String,
+    pub updated_at: DateTime<Utc>,
+}
+
+/// Assistant reply saved while it is still being streamed
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MessageDraft {
+    pub id: String,
+    pub session_id: String,
+    pub content: String,
+    pub model_used: Option<String>,
+    pub updated_at: DateTime<Utc>,
+}
+
+/// Rating of an assistant reply
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum FeedbackRating {
+    Up,
+    Down,
+}
+
+impl FeedbackRating {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Up => "up",
+            Self::Down => "down",
+        }
+    }
+
+    /// Rating expressed by a lone thumbs-up/down emoji (any skin tone)
+    pub fn from_emoji(text: &str) -> Option<Self> {
+        let mut chars = text.trim().chars();
+        let rating = match chars.next()? {
+            '👍' => Self::Up,
+            '👎' => Self::Down,
+            _ => return None,
+        };
+        chars
+            .all(|c| matches!(c, '\u{1F3FB}'..='\u{1F3FF}' | '\u{FE0F}'))
+            .then_some(rating)
+    }
+}
+
+/// A user's rating of an assistant reply
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MessageFeedback {
+    pub message_id: String,
+    pub session_id: String,
+    pub user_id: String,
+    pub rating: FeedbackRating,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub comment: Option<String>,
+    /// Model that produced the rated reply
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub model: Option<String>,
+    pub created_at: DateTime<Utc>,
+}
+
+/// Rating counts for one model
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ModelFeedback {
+    pub model: String,
+    pub up: usize,
+    pub down: usize,
+}
+
+/// Rating counts across all feedback
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct FeedbackSummary {
+    pub up: usize,
+    pub down: usize,
+    /// Per-model counts, by model name
+    pub by_model: Vec<ModelFeedback>,
+}
+
+/// Prompt run on a cron schedule, its reply delivered to a channel
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Schedule {
+    pub id: String,
+    /// User the prompt runs as
+    pub user_id: String,
+    pub cron: String,
+    pub prompt: String,
+    pub channel: String,
+    pub target: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub timezone: Option<String>,
+    pub enabled: bool,
+    pub created_at: DateTime<Utc>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub last_run_at: Option<DateTime<Utc>>,
+}
+
+/// Input of an eval suite and what its reply must contain or match
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct EvalCase {
+    pub input: String,
+    /// Text the reply must contain (case-insensitive)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub expected_contains: Option<String>,
+    /// Regular expression the reply must match
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub expected_regex: Option<String>,
+}
+
+/// Eval cases saved under a name to be run again
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct EvalSuite {
+    pub user_id: String,
+    pub name: String,
+    pub cases: Vec<EvalCase>,
+    pub updated_at: DateTime<Utc>,
+}
+
+/// A user's default reply settings, used by sessions that have not changed
+/// them; each one unset falls back to the configuration
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct UserSettings {
+    /// Model name or `role:<name>`
+    #[serde(default)]
+    pub model: Option<String>,
+    #[serde(default)]
+    pub temperature: Option<f32>,
+    /// Name of one of the `/persona` personas
+    #[serde(default)]
+    pub persona: Option<String>,
+    /// Code of the language replies are asked in
+    #[serde(default)]
+    pub language: Option<String>,
+}
+
+/// Message delivered to a channel at a set time
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Reminder {
+    pub id: String,
+    /// User who set the reminder
+    pub user_id: String,
+    pub message: String,
+    pub channel: String,
+    pub target: String,
+    pub due_at: DateTime<Utc>,
+    pub created_at: DateTime<Utc>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub delivered_at: Option<DateTime<Utc>>,
+}
+
+/// A chunk of an ingested knowledge document
+#[derive(Debug, Clone, PartialEq)]
+pub struct DocumentChunk {
+    /// Path relative to the knowledge directory
+    pub source: String,
+    pub chunk_index: usize,
+    pub content: String,
+    /// Absent when the document was ingested without an embedding model
+    pub embedding: Option<Vec<f32>>,
+    /// Size and modification time of the file the chunk came from
+    pub fingerprint: String,
 }
 
 #[async_trait]
@@ -60,31 +348,215 @@ pub trait Storage: Send + Sync + Clone {
         channel: &str,
         scope: &str,
     ) -> Result<Option<Session>>;
+    /// Sessions of a user, most recently used first
+    async fn list_user_sessions(&self, user_id: &str) -> Result<Vec<Session>>;
+
+    /// Sessions of all users with messages at or after `since`, most
+    /// recently active first
+    async fn list_active_sessions(
+        &self,
+        since: Option<DateTime<Utc>>,
+        limit: usize,
+    ) -> Result<Vec<Session>>;
 
     async fn get_messages(&self, session_id: &str, limit: Option<usize>) -> Result<Vec<Message>>;
+    async fn get_message(&self, id: &str) -> Result<Option<Message>>;
     async fn add_message(&self, message: Message) -> Result<()>;
+    /// Copy the messages of `src` up to and including `message_id` into
+    /// `dst` under new IDs, each linked to its original through
+    /// `metadata.forked_from`. Returns the number copied, or `None` when the
+    /// message is not in `src`.
+    async fn copy_messages_until(
+        &self,
+        src: &str,
+        dst: &str,
+        message_id: &str,
+    ) -> Result<Option<usize>>;
     async fn delete_session_messages(&self, session_id: &str) -> Result<()>;
 
+    // Pinned notes (kept when messages are cleared or compacted)
+    async fn add_session_note(&self, note: SessionNote) -> Result<()>;
+    /// Notes of a session, oldest first
+    async fn list_session_notes(&self, session_id: &str) -> Result<Vec<SessionNote>>;
+    /// Returns false when the session has no such note
+    async fn delete_session_note(&self, session_id: &str, note_id: &str) -> Result<bool>;
+
+    // Cached summaries, by session
+    async fn get_session_summary(&self, session_id: &str) -> Result<Option<SessionSummary>>;
+    /// Create the session's summary or replace it
+    async fn save_session_summary(&self, summary: &SessionSummary) -> Result<()>;
+    /// Delete a session together with everything kept per session: messages,
+    /// drafts, feedback, tool approvals, notes and summary
+    async fn delete_session(&self, session_id: &str) -> Result<()>;
+    /// Delete the sessions last active before `idle_before`, with their
+    /// messages, drafts, feedback, tool approvals and summaries, and return
+    /// them.
+    /// Sessions with pinned notes and sessions shared across channels are
+    /// kept.
+    async fn delete_idle_sessions(&self, idle_before: DateTime<Utc>) -> Result<Vec<Session>>;
+
     // User & Identity Management
     async fn get_user(&self, id: &str) -> Result<Option<User>>;
     async fn get_user_by_username(&self, username: &str) -> Result<Option<User>>;
     async fn create_user(&self, user: User) -> Result<()>;
     async fn user_count(&self) -> Result<usize>;
-    async fn list_users(&self) -> Result<Vec<User>>;
+    /// Users matching a filter, oldest first
+    async fn list_users(&self, filter: &UserFilter, page: Page) -> Result<Paged<User>>;
     async fn delete_user(&self, user_id: &str) -> Result<()>;
 
     async fn get_identity(&self, provider: &str, provider_id: &str) -> Result<Option<Identity>>;
     async fn create_identity(&self, identity: Identity) -> Result<()>;
-    async fn list_identities(&self, user_id: &str) -> Result<Vec<Identity>>;
+    /// Identities of a user, optionally of one provider only, oldest first
+    async fn list_identities(
+        &self,
+        user_id: &str,
+        provider: Option<&str>,
+        page: Page,
+    ) -> Result<Paged<Identity>>;
 
-    // Pending Links (OTP)
-    async fn create_pending_link(&self, code: &str, user_id: &str, provider: &str) -> Result<()>;
-    async fn get_pending_link(&self, code: &str) -> Result<Option<(String, String)>>; // returns (user_id, provider)
+    // Pending Links (OTP and invites)
+    async fn create_pending_link(&self, link: PendingLink) -> Result<()>;
+    /// Look up a code that is neither expired nor used
+    async fn get_pending_link(&self, code: &str) -> Result<Option<PendingLink>>;
+    /// Atomically mark a valid code as used, returning it; `None` when the
+    /// code is unknown, expired or already used
+    async fn use_pending_link(&self, code: &str) -> Result<Option<PendingLink>>;
+    /// Make a used code valid again, when what it was used for failed
+    async fn release_pending_link(&self, code: &str) -> Result<()>;
     async fn delete_pending_link(&self, code: &str) -> Result<()>;
 
+    // Impersonation (admin acting as a user)
+    async fn create_impersonation(&self, impersonation: &Impersonation) -> Result<()>;
+    /// Look up an impersonation token that has not expired
+    async fn get_impersonation(&self, token: &str) -> Result<Option<Impersonation>>;
+    /// Most recent grants, newest first, expired ones included
+    async fn list_impersonations(&self, limit: usize) -> Result<Vec<Impersonation>>;
+
     // Password management
     async fn update_user_password(&self, user_id: &str, password_hash: String) -> Result<()>;
 
     // Identity management
     async fn delete_identity(&self, provider: &str, provider_id: &str) -> Result<()>;
+
+    // Tool execution audit log
+    async fn add_tool_execution(&self, execution: ToolExecution) -> Result<()>;
+    /// Most recent executions of a tool across the user's sessions, newest first
+    async fn list_tool_executions(
+        &self,
+        user_id: &str,
+        tool_name: &str,
+        limit: usize,
+    ) -> Result<Vec<ToolExecution>>;
+    /// Every audited call made at or after `since`
+    async fn tool_call_samples(&self, since: DateTime<Utc>) -> Result<Vec<ToolCallSample>>;
+
+    // Pending tool approvals
+    async fn save_pending_approval(&self, approval: PendingApprovalRecord) -> Result<()>;
+    async fn delete_pending_approval(&self, request_id: &str) -> Result<()>;
+    async fn list_pending_approvals(&self) -> Result<Vec<PendingApprovalRecord>>;
+
+    // Tools approved for the rest of a session ("remember for session")
+    async fn save_session_tool_approval(&self, session_id: &str, tool_name: &str) -> Result<()>;
+    async fn delete_session_tool_approvals(&self, session_id: &str) -> Result<()>;
+    /// All remembered approvals as (session ID, tool name)
+    async fn list_session_tool_approvals(&self) -> Result<Vec<(String, String)>>;
+
+    // Per-user tool policy overrides (tool name -> access level)
+    async fn get_user_tool_policies(&self, user_id: &str) -> Result<HashMap<String, String>>;
+    /// Replace all overrides of a user
+    async fn set_user_tool_policies(
+        &self,
+        user_id: &str,
+        policies: &HashMap<String, String>,
+    ) -> Result<()>;
+    /// Overrides of every user, keyed by user ID
+    async fn list_user_tool_policies(&self) -> Result<HashMap<String, HashMap<String, String>>>;
+
+    // Reply feedback
+    /// Store a rating, replacing the user's earlier rating of the message
+    async fn set_message_feedback(&self, feedback: MessageFeedback) -> Result<()>;
+    async fn feedback_summary(&self) -> Result<FeedbackSummary>;
+    /// IDs of sessions with a thumbs-up and no thumbs-down
+    async fn positively_rated_sessions(&self) -> Result<HashSet<String>>;
+
+    // Scheduled prompts
+    async fn create_schedule(&self, schedule: Schedule) -> Result<()>;
+    async fn get_schedule(&self, id: &str) -> Result<Option<Schedule>>;
+    /// Schedules of one user, or of everyone when `user_id` is `None`,
+    /// oldest first
+    async fn list_schedules(&self, user_id: Option<&str>) -> Result<Vec<Schedule>>;
+    /// Returns false when there is no such schedule
+    async fn update_schedule(&self, schedule: Schedule) -> Result<bool>;
+    async fn delete_schedule(&self, id: &str) -> Result<bool>;
+    async fn set_schedule_last_run(&self, id: &str, at: DateTime<Utc>) -> Result<()>;
+
+    // Reminders
+    async fn create_reminder(&self, reminder: Reminder) -> Result<()>;
+    /// Undelivered reminders of a user, soonest first
+    async fn list_reminders(&self, user_id: &str) -> Result<Vec<Reminder>>;
+    /// Undelivered reminders of all users due at or before `now`, oldest first
+    async fn due_reminders(&self, now: DateTime<Utc>) -> Result<Vec<Reminder>>;
+    async fn mark_reminder_delivered(&self, id: &str, at: DateTime<Utc>) -> Result<()>;
+    /// Delete an undelivered reminder of the user; returns false when there
+    /// is none
+    async fn cancel_reminder(&self, user_id: &str, id: &str) -> Result<bool>;
+
+    // Knowledge document chunks
+    /// Replace all chunks of a document
+    async fn replace_document_chunks(&self, source: &str, chunks: Vec<DocumentChunk>)
+        -> Result<()>;
+    async fn delete_document_chunks(&self, source: &str) -> Result<()>;
+    async fn list_document_chunks(&self) -> Result<Vec<DocumentChunk>>;
+
+    // Plugin key-value state, namespaced by plugin ID
+    async fn plugin_kv_get(&self, plugin_id: &str, key: &str) -> Result<Option<serde_json::Value>>;
+    async fn plugin_kv_set(
+        &self,
+        plugin_id: &str,
+        key: &str,
+        value: serde_json::Value,
+    ) -> Result<()>;
+    /// Returns whether the key existed
+    async fn plugin_kv_delete(&self, plugin_id: &str, key: &str) -> Result<bool>;
+
+    // Channels paused across restarts
+    async fn set_channel_paused(&self, channel: &str, paused: bool) -> Result<()>;
+    async fn list_paused_channels(&self) -> Result<Vec<String>>;
+
+    // Maintenance mode kept across restarts
+    async fn set_maintenance(&self, enabled: bool) -> Result<()>;
+    async fn get_maintenance(&self) -> Result<bool>;
+
+    // Partial replies of streams that have not finished
+    async fn save_draft(&self, draft: &MessageDraft) -> Result<()>;
+    async fn delete_draft(&self, id: &str) -> Result<()>;
+    async fn list_drafts(&self) -> Result<Vec<MessageDraft>>;
+
+    // Eval suites, by user and name
+    /// Create the suite or replace the one of the same name
+    async fn save_eval_suite(&self, suite: &EvalSuite) -> Result<()>;
+    async fn get_eval_suite(&self, user_id: &str, name: &str) -> Result<Option<EvalSuite>>;
+    /// Suites of a user, by name
+    async fn list_eval_suites(&self, user_id: &str) -> Result<Vec<EvalSuite>>;
+    /// Returns false when there is no such suite
+    async fn delete_eval_suite(&self, user_id: &str, name: &str) -> Result<bool>;
+
+    // Default reply settings, by user
+    async fn get_user_settings(&self, user_id: &str) -> Result<Option<UserSettings>>;
+    /// Replace all of a user's settings
+    async fn set_user_settings(&self, user_id: &str, settings: &UserSettings) -> Result<()>;
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_feedback_rating_from_emoji() {
+        assert_eq!(FeedbackRating::from_emoji(" 👍 "), Some(FeedbackRating::Up));
+        assert_eq!(FeedbackRating::from_emoji("👎🏽"), Some(FeedbackRating::Down));
+        assert_eq!(FeedbackRating::from_emoji("👍 thanks"), None);
+        assert_eq!(FeedbackRating::from_emoji("ok"), None);
+        assert_eq!(FeedbackRating::from_emoji(""), None);
+    }
 }
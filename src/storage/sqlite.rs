@@ -1,7 +1,13 @@
-use super::{Identity, Message, Session, Storage, User};
+use super::{
+    DocumentChunk, EvalSuite, FeedbackSummary, Identity, Impersonation, Message, MessageDraft,
+    MessageFeedback, ModelFeedback, Page, Paged, PendingApprovalRecord, PendingLink, Reminder,
+    Schedule, Session, SessionNote, SessionSummary, Storage, ToolCallSample, ToolExecution, User,
+    UserFilter, UserSettings,
+};
 use anyhow::{Context, Result};
 use async_trait::async_trait;
 use sqlx::{sqlite::SqlitePool, Row};
+use std::collections::{HashMap, HashSet};
 use std::path::Path;
 
 #[derive(Clone)]
@@ -106,13 +112,70 @@ impl Storage for SqliteStorage {
         }))
     }
 
+    async fn list_user_sessions(&self, user_id: &str) -> Result<Vec<Session>> {
+        let rows = sqlx::query(
+            "SELECT id, user_id, channel, scope, created_at, updated_at FROM sessions
+             WHERE user_id = ?
+             ORDER BY updated_at DESC",
+        )
+        .bind(user_id)
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(rows
+            .into_iter()
+            .map(|r| Session {
+                id: r.get("id"),
+                user_id: r.get("user_id"),
+                channel: r.get("channel"),
+                scope: r.get("scope"),
+                created_at: r.get("created_at"),
+                updated_at: r.get("updated_at"),
+            })
+            .collect())
+    }
+
+    async fn list_active_sessions(
+        &self,
+        since: Option<chrono::DateTime<chrono::Utc>>,
+        limit: usize,
+    ) -> Result<Vec<Session>> {
+        let rows = sqlx::query(
+            "SELECT s.id, s.user_id, s.channel, s.scope, s.created_at, s.updated_at,
+                    MAX(m.created_at) AS last_message_at
+             FROM sessions s
+             JOIN messages m ON m.session_id = s.id
+             WHERE ? IS NULL OR m.created_at >= ?
+             GROUP BY s.id
+             ORDER BY last_message_at DESC
+             LIMIT ?",
+        )
+        .bind(since)
+        .bind(since)
+        .bind(limit as i64)
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(rows
+            .into_iter()
+            .map(|r| Session {
+                id: r.get("id"),
+                user_id: r.get("user_id"),
+                channel: r.get("channel"),
+                scope: r.get("scope"),
+                created_at: r.get("created_at"),
+                updated_at: r.get("updated_at"),
+            })
+            .collect())
+    }
+
     async fn get_messages(&self, session_id: &str, limit: Option<usize>) -> Result<Vec<Message>> {
         let limit_val = limit.unwrap_or(100);
 
         let rows = sqlx::query(
-            "SELECT id, session_id, role, content, created_at, model_used, tokens FROM messages
+            "SELECT id, session_id, role, content, created_at, model_used, tokens, metadata FROM messages
              WHERE session_id = ?
-             ORDER BY created_at DESC
+             ORDER BY seq DESC
              LIMIT ?",
         )
         .bind(session_id)
@@ -124,6 +187,7 @@ impl Storage for SqliteStorage {
             .into_iter()
             .map(|r| {
                 let tokens_i64: Option<i64> = r.get("tokens");
+                let metadata: Option<String> = r.get("metadata");
                 Message {
                     id: r.get("id"),
                     session_id: r.get("session_id"),
@@ -132,6 +196,7 @@ impl Storage for SqliteStorage {
                     created_at: r.get("created_at"),
                     model_used: r.get("model_used"),
                     tokens: tokens_i64.map(|t| t as usize),
+                    metadata: metadata.and_then(|m| serde_json::from_str(&m).ok()),
                 }
             })
             .collect();
@@ -140,10 +205,35 @@ impl Storage for SqliteStorage {
         Ok(messages)
     }
 
+    async fn get_message(&self, id: &str) -> Result<Option<Message>> {
+        let row = sqlx::query(
+            "SELECT id, session_id, role, content, created_at, model_used, tokens, metadata FROM messages
+             WHERE id = ?",
+        )
+        .bind(id)
+        .fetch_optional(&self.pool)
+        .await?;
+
+        Ok(row.map(|r| {
+            let tokens_i64: Option<i64> = r.get("tokens");
+            let metadata: Option<String> = r.get("metadata");
+            Message {
+                id: r.get("id"),
+                session_id: r.get("session_id"),
+                role: r.get("role"),
+                content: r.get("content"),
+                created_at: r.get("created_at"),
+                model_used: r.get("model_used"),
+                tokens: tokens_i64.map(|t| t as usize),
+                metadata: metadata.and_then(|m| serde_json::from_str(&m).ok()),
+            }
+        }))
+    }
+
     async fn add_message(&self, message: Message) -> Result<()> {
         sqlx::query(
-            "INSERT INTO messages (id, session_id, role, content, created_at, model_used, tokens)
-             VALUES (?, ?, ?, ?, ?, ?, ?)",
+            "INSERT INTO messages (id, session_id, role, content, created_at, model_used, tokens, metadata, seq)
+             VALUES (?, ?, ?, ?, ?, ?, ?, ?, (SELECT COALESCE(MAX(seq), 0) + 1 FROM messages))",
         )
         .bind(&message.id)
         .bind(&message.session_id)
@@ -152,12 +242,68 @@ impl Storage for SqliteStorage {
         .bind(message.created_at)
         .bind(&message.model_used)
         .bind(message.tokens.map(|t| t as i64))
+        .bind(message.metadata.as_ref().map(|m| m.to_string()))
         .execute(&self.pool)
         .await?;
 
         Ok(())
     }
 
+    async fn copy_messages_until(
+        &self,
+        src: &str,
+        dst: &str,
+        message_id: &str,
+    ) -> Result<Option<usize>> {
+        let mut tx = self.pool.begin().await?;
+        let rows = sqlx::query(
+            "SELECT id, role, content, created_at, model_used, tokens, metadata FROM messages
+             WHERE session_id = ?
+             ORDER BY seq ASC",
+        )
+        .bind(src)
+        .fetch_all(&mut *tx)
+        .await?;
+
+        let Some(end) = rows
+            .iter()
+            .position(|r| r.get::<String, _>("id") == message_id)
+        else {
+            return Ok(None);
+        };
+
+        for r in &rows[..=end] {
+            let original_id: String = r.get("id");
+            let metadata: Option<String> = r.get("metadata");
+            let mut metadata = metadata
+                .and_then(|m| serde_json::from_str::<serde_json::Value>(&m).ok())
+                .filter(serde_json::Value::is_object)
+                .unwrap_or_else(|| serde_json::json!({}));
+            metadata["forked_from"] = serde_json::json!({
+                "session_id": src,
+                "message_id": original_id,
+            });
+
+            sqlx::query(
+                "INSERT INTO messages (id, session_id, role, content, created_at, model_used, tokens, metadata, seq)
+                 VALUES (?, ?, ?, ?, ?, ?, ?, ?, (SELECT COALESCE(MAX(seq), 0) + 1 FROM messages))",
+            )
+            .bind(uuid::Uuid::new_v4().to_string())
+            .bind(dst)
+            .bind(r.get::<String, _>("role"))
+            .bind(r.get::<String, _>("content"))
+            .bind(r.get::<chrono::DateTime<chrono::Utc>, _>("created_at"))
+            .bind(r.get::<Option<String>, _>("model_used"))
+            .bind(r.get::<Option<i64>, _>("tokens"))
+            .bind(metadata.to_string())
+            .execute(&mut *tx)
+            .await?;
+        }
+
+        tx.commit().await?;
+        Ok(Some(end + 1))
+    }
+
     async fn delete_session_messages(&self, session_id: &str) -> Result<()> {
         sqlx::query("DELETE FROM messages WHERE session_id = ?")
             .bind(session_id)
@@ -167,6 +313,165 @@ impl Storage for SqliteStorage {
         Ok(())
     }
 
+    async fn delete_session(&self, session_id: &str) -> Result<()> {
+        let mut tx = self.pool.begin().await?;
+        for table in [
+            "messages",
+            "message_drafts",
+            "message_feedback",
+            "session_tool_approvals",
+            "session_summaries",
+            "session_notes",
+        ] {
+            sqlx::query(&format!("DELETE FROM {} WHERE session_id = ?", table))
+                .bind(session_id)
+                .execute(&mut *tx)
+                .await?;
+        }
+        sqlx::query("DELETE FROM sessions WHERE id = ?")
+            .bind(session_id)
+            .execute(&mut *tx)
+            .await?;
+        tx.commit().await?;
+
+        Ok(())
+    }
+
+    async fn delete_idle_sessions(
+        &self,
+        idle_before: chrono::DateTime<chrono::Utc>,
+    ) -> Result<Vec<Session>> {
+        // Shared routing keeps one `global` session per user (and agent)
+        const IDLE: &str = "SELECT id FROM sessions
+             WHERE updated_at < ?1
+               AND channel != 'global' AND channel NOT LIKE '%:global'
+               AND id NOT IN (SELECT session_id FROM session_notes)";
+
+        let mut tx = self.pool.begin().await?;
+        let rows = sqlx::query(&format!(
+            "SELECT id, user_id, channel, scope, created_at, updated_at FROM sessions
+             WHERE id IN ({})",
+            IDLE
+        ))
+        .bind(idle_before)
+        .fetch_all(&mut *tx)
+        .await?;
+        if rows.is_empty() {
+            return Ok(Vec::new());
+        }
+
+        for table in [
+            "messages",
+            "message_drafts",
+            "message_feedback",
+            "session_tool_approvals",
+            "session_summaries",
+        ] {
+            sqlx::query(&format!(
+                "DELETE FROM {} WHERE session_id IN ({})",
+                table, IDLE
+            ))
+            .bind(idle_before)
+            .execute(&mut *tx)
+            .await?;
+        }
+        sqlx::query(&format!("DELETE FROM sessions WHERE id IN ({})", IDLE))
+            .bind(idle_before)
+            .execute(&mut *tx)
+            .await?;
+        tx.commit().await?;
+
+        Ok(rows
+            .into_iter()
+            .map(|r| Session {
+                id: r.get("id"),
+                user_id: r.get("user_id"),
+                channel: r.get("channel"),
+                scope: r.get("scope"),
+                created_at: r.get("created_at"),
+                updated_at: r.get("updated_at"),
+            })
+            .collect())
+    }
+
+    async fn add_session_note(&self, note: SessionNote) -> Result<()> {
+        sqlx::query(
+            "INSERT INTO session_notes (id, session_id, content, created_at) VALUES (?, ?, ?, ?)",
+        )
+        .bind(&note.id)
+        .bind(&note.session_id)
+        .bind(&note.content)
+        .bind(note.created_at)
+        .execute(&self.pool)
+        .await?;
+        Ok(())
+    }
+
+    async fn list_session_notes(&self, session_id: &str) -> Result<Vec<SessionNote>> {
+        let rows = sqlx::query(
+            "SELECT id, session_id, content, created_at FROM session_notes
+             WHERE session_id = ?
+             ORDER BY created_at ASC, rowid ASC",
+        )
+        .bind(session_id)
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(rows
+            .into_iter()
+            .map(|r| SessionNote {
+                id: r.get("id"),
+                session_id: r.get("session_id"),
+                content: r.get("content"),
+                created_at: r.get("created_at"),
+            })
+            .collect())
+    }
+
+    async fn delete_session_note(&self, session_id: &str, note_id: &str) -> Result<bool> {
+        let result = sqlx::query("DELETE FROM session_notes WHERE session_id = ? AND id = ?")
+            .bind(session_id)
+            .bind(note_id)
+            .execute(&self.pool)
+            .await?;
+        Ok(result.rows_affected() > 0)
+    }
+
+    async fn get_session_summary(&self, session_id: &str) -> Result<Option<SessionSummary>> {
+        let row = sqlx::query(
+            "SELECT session_id, summary, summary_through, updated_at FROM session_summaries
+             WHERE session_id = ?",
+        )
+        .bind(session_id)
+        .fetch_optional(&self.pool)
+        .await?;
+
+        Ok(row.map(|r| SessionSummary {
+            session_id: r.get("session_id"),
+            summary: r.get("summary"),
+            summary_through: r.get("summary_through"),
+            updated_at: r.get("updated_at"),
+        }))
+    }
+
+    async fn save_session_summary(&self, summary: &SessionSummary) -> Result<()> {
+        sqlx::query(
+            "INSERT INTO session_summaries (session_id, summary, summary_through, updated_at)
+             VALUES (?, ?, ?, ?)
+             ON CONFLICT(session_id) DO UPDATE SET
+                summary = excluded.summary,
+                summary_through = excluded.summary_through,
+                updated_at = excluded.updated_at",
+        )
+        .bind(&summary.session_id)
+        .bind(&summary.summary)
+        .bind(&summary.summary_through)
+        .bind(summary.updated_at)
+        .execute(&self.pool)
+        .await?;
+        Ok(())
+    }
+
     // Identity implementation
     async fn get_user(&self, id: &str) -> Result<Option<User>> {
         let row = sqlx::query(
@@ -226,14 +531,36 @@ impl Storage for SqliteStorage {
         Ok(row.0 as usize)
     }
 
-    async fn list_users(&self) -> Result<Vec<User>> {
-        let rows = sqlx::query(
-            "SELECT id, username, role, password_hash, created_at, updated_at FROM users",
-        )
+    async fn list_users(&self, filter: &UserFilter, page: Page) -> Result<Paged<User>> {
+        const MATCHES: &str = "(?1 IS NULL OR username LIKE ?1 ESCAPE '\\')
+             AND (?2 IS NULL OR role = ?2)";
+        let username = filter
+            .username
+            .as_deref()
+            .map(|part| format!("%{}%", escape_like(part)));
+
+        let total: i64 =
+            sqlx::query_scalar(&format!("SELECT COUNT(*) FROM users WHERE {}", MATCHES))
+                .bind(&username)
+                .bind(&filter.role)
+                .fetch_one(&self.pool)
+                .await?;
+
+        let rows = sqlx::query(&format!(
+            "SELECT id, username, role, password_hash, created_at, updated_at FROM users
+             WHERE {}
+             ORDER BY created_at ASC, id ASC
+             LIMIT ?3 OFFSET ?4",
+            MATCHES
+        ))
+        .bind(&username)
+        .bind(&filter.role)
+        .bind(page_limit(page))
+        .bind(page.offset as i64)
         .fetch_all(&self.pool)
         .await?;
 
-        Ok(rows
+        let items = rows
             .into_iter()
             .map(|r| User {
                 id: r.get("id"),
@@ -243,7 +570,11 @@ impl Storage for SqliteStorage {
                 created_at: r.get("created_at"),
                 updated_at: r.get("updated_at"),
             })
-            .collect())
+            .collect();
+        Ok(Paged {
+            items,
+            total: total as usize,
+        })
     }
 
     async fn delete_user(&self, user_id: &str) -> Result<()> {
@@ -256,26 +587,23 @@ impl Storage for SqliteStorage {
 
     async fn get_identity(&self, provider: &str, provider_id: &str) -> Result<Option<Identity>> {
         let row = sqlx::query(
-            "SELECT provider, provider_id, user_id, label, created_at, last_used_at FROM identities WHERE provider = ? AND provider_id = ?"
+            "SELECT provider, provider_id, user_id, label, created_at, last_used_at, scopes FROM identities WHERE provider = ? AND provider_id = ?"
         )
         .bind(provider)
         .bind(provider_id)
         .fetch_optional(&self.pool)
         .await?;
 
-        Ok(row.map(|r| Identity {
-            provider: r.get("provider"),
-            provider_id: r.get("provider_id"),
-            user_id: r.get("user_id"),
-            label: r.get("label"),
-            created_at: r.get("created_at"),
-            last_used_at: r.get("last_used_at"),
-        }))
+        row.map(identity_from_row).transpose()
     }
 
     async fn create_identity(&self, identity: Identity) -> Result<()> {
+        let scopes = identity
+            .scopes
+            .map(|s| serde_json::to_string(&s))
+            .transpose()?;
         sqlx::query(
-            "INSERT INTO identities (provider, provider_id, user_id, label, created_at, last_used_at) VALUES (?, ?, ?, ?, ?, ?)"
+            "INSERT INTO identities (provider, provider_id, user_id, label, created_at, last_used_at, scopes) VALUES (?, ?, ?, ?, ?, ?, ?)"
         )
         .bind(identity.provider)
         .bind(identity.provider_id)
@@ -283,56 +611,159 @@ impl Storage for SqliteStorage {
         .bind(identity.label)
         .bind(identity.created_at)
         .bind(identity.last_used_at)
+        .bind(scopes)
         .execute(&self.pool)
         .await?;
         Ok(())
     }
 
-    async fn list_identities(&self, user_id: &str) -> Result<Vec<Identity>> {
+    async fn list_identities(
+        &self,
+        user_id: &str,
+        provider: Option<&str>,
+        page: Page,
+    ) -> Result<Paged<Identity>> {
+        let total: i64 = sqlx::query_scalar(
+            "SELECT COUNT(*) FROM identities WHERE user_id = ?1 AND (?2 IS NULL OR provider = ?2)",
+        )
+        .bind(user_id)
+        .bind(provider)
+        .fetch_one(&self.pool)
+        .await?;
+
         let rows = sqlx::query(
-            "SELECT provider, provider_id, user_id, label, created_at, last_used_at FROM identities WHERE user_id = ?"
+            "SELECT provider, provider_id, user_id, label, created_at, last_used_at, scopes FROM identities
+             WHERE user_id = ?1 AND (?2 IS NULL OR provider = ?2)
+             ORDER BY created_at ASC, provider_id ASC
+             LIMIT ?3 OFFSET ?4",
         )
         .bind(user_id)
+        .bind(provider)
+        .bind(page_limit(page))
+        .bind(page.offset as i64)
         .fetch_all(&self.pool)
         .await?;
 
-        Ok(rows
-            .into_iter()
-            .map(|r| Identity {
-                provider: r.get("provider"),
-                provider_id: r.get("provider_id"),
-                user_id: r.get("user_id"),
-                label: r.get("label"),
-                created_at: r.get("created_at"),
-                last_used_at: r.get("last_used_at"),
-            })
-            .collect())
+        Ok(Paged {
+            items: rows
+                .into_iter()
+                .map(identity_from_row)
+                .collect::<Result<_>>()?,
+            total: total as usize,
+        })
     }
 
-    async fn create_pending_link(&self, code: &str, user_id: &str, provider: &str) -> Result<()> {
-        // Expiry in 10 minutes
-        let expires_at = chrono::Utc::now() + chrono::Duration::minutes(10);
+    async fn create_pending_link(&self, link: PendingLink) -> Result<()> {
+        let scopes = link.scopes.map(|s| serde_json::to_string(&s)).transpose()?;
         sqlx::query(
-            "INSERT INTO pending_links (code, user_id, provider, expires_at) VALUES (?, ?, ?, ?)",
+            "INSERT INTO pending_links (code, user_id, provider, role, scopes, expires_at, used) VALUES (?, ?, ?, ?, ?, ?, ?)",
         )
-        .bind(code)
-        .bind(user_id)
-        .bind(provider)
-        .bind(expires_at)
+        .bind(link.code)
+        .bind(link.user_id)
+        .bind(link.provider)
+        .bind(link.role)
+        .bind(scopes)
+        .bind(link.expires_at)
+        .bind(link.used)
         .execute(&self.pool)
         .await?;
         Ok(())
     }
 
-    async fn get_pending_link(&self, code: &str) -> Result<Option<(String, String)>> {
+    async fn get_pending_link(&self, code: &str) -> Result<Option<PendingLink>> {
+        // Compare against a bound timestamp: it is stored in the same format,
+        // unlike datetime('now')
+        let row = sqlx::query(
+            "SELECT code, user_id, provider, role, scopes, expires_at, used FROM pending_links WHERE code = ? AND used = 0 AND expires_at > ?"
+        )
+        .bind(code)
+        .bind(chrono::Utc::now())
+        .fetch_optional(&self.pool)
+        .await?;
+
+        row.map(pending_link_from_row).transpose()
+    }
+
+    async fn use_pending_link(&self, code: &str) -> Result<Option<PendingLink>> {
+        let mut tx = self.pool.begin().await?;
+        let claimed = sqlx::query(
+            "UPDATE pending_links SET used = 1 WHERE code = ? AND used = 0 AND expires_at > ?",
+        )
+        .bind(code)
+        .bind(chrono::Utc::now())
+        .execute(&mut *tx)
+        .await?
+        .rows_affected()
+            == 1;
+        if !claimed {
+            return Ok(None);
+        }
+
         let row = sqlx::query(
-            "SELECT user_id, provider FROM pending_links WHERE code = ? AND expires_at > datetime('now')"
+            "SELECT code, user_id, provider, role, scopes, expires_at, used FROM pending_links WHERE code = ?",
         )
         .bind(code)
+        .fetch_one(&mut *tx)
+        .await?;
+        tx.commit().await?;
+
+        pending_link_from_row(row).map(Some)
+    }
+
+    async fn create_impersonation(&self, impersonation: &Impersonation) -> Result<()> {
+        let scopes = impersonation
+            .scopes
+            .as_ref()
+            .map(serde_json::to_string)
+            .transpose()?;
+        sqlx::query(
+            "INSERT INTO impersonations (token, admin_id, user_id, reason, created_at, expires_at, scopes)
+             VALUES (?, ?, ?, ?, ?, ?, ?)",
+        )
+        .bind(&impersonation.token)
+        .bind(&impersonation.admin_id)
+        .bind(&impersonation.user_id)
+        .bind(&impersonation.reason)
+        .bind(impersonation.created_at)
+        .bind(impersonation.expires_at)
+        .bind(scopes)
+        .execute(&self.pool)
+        .await?;
+        Ok(())
+    }
+
+    async fn get_impersonation(&self, token: &str) -> Result<Option<Impersonation>> {
+        let row = sqlx::query(
+            "SELECT token, admin_id, user_id, reason, created_at, expires_at, scopes FROM impersonations
+             WHERE token = ? AND expires_at > ?",
+        )
+        .bind(token)
+        .bind(chrono::Utc::now())
         .fetch_optional(&self.pool)
         .await?;
 
-        Ok(row.map(|r| (r.get("user_id"), r.get("provider"))))
+        row.map(impersonation_from_row).transpose()
+    }
+
+    async fn list_impersonations(&self, limit: usize) -> Result<Vec<Impersonation>> {
+        let rows = sqlx::query(
+            "SELECT token, admin_id, user_id, reason, created_at, expires_at, scopes FROM impersonations
+             ORDER BY created_at DESC
+             LIMIT ?",
+        )
+        .bind(limit as i64)
+        .fetch_all(&self.pool)
+        .await?;
+
+        rows.into_iter().map(impersonation_from_row).collect()
+    }
+
+    async fn release_pending_link(&self, code: &str) -> Result<()> {
+        sqlx::query("UPDATE pending_links SET used = 0 WHERE code = ?")
+            .bind(code)
+            .execute(&self.pool)
+            .await?;
+        Ok(())
     }
 
     async fn delete_pending_link(&self, code: &str) -> Result<()> {
@@ -361,4 +792,1487 @@ impl Storage for SqliteStorage {
             .await?;
         Ok(())
     }
+
+    async fn add_tool_execution(&self, execution: ToolExecution) -> Result<()> {
+        sqlx::query(
+            "INSERT INTO tool_executions (id, session_id, tool_name, arguments, output, success, duration_ms, created_at)
+             VALUES (?, ?, ?, ?, ?, ?, ?, ?)",
+        )
+        .bind(&execution.id)
+        .bind(&execution.session_id)
+        .bind(&execution.tool_name)
+        .bind(&execution.arguments)
+        .bind(&execution.output)
+        .bind(execution.success)
+        .bind(execution.duration_ms.map(|d| d as i64))
+        .bind(execution.created_at)
+        .execute(&self.pool)
+        .await?;
+
+        Ok(())
+    }
+
+    async fn tool_call_samples(
+        &self,
+        since: chrono::DateTime<chrono::Utc>,
+    ) -> Result<Vec<ToolCallSample>> {
+        let rows = sqlx::query(
+            "SELECT e.tool_name, s.user_id, e.success, e.duration_ms
+             FROM tool_executions e
+             JOIN sessions s ON s.id = e.session_id
+             WHERE e.created_at >= ?",
+        )
+        .bind(since)
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(rows
+            .into_iter()
+            .map(|r| {
+                let duration_ms: Option<i64> = r.get("duration_ms");
+                ToolCallSample {
+                    tool_name: r.get("tool_name"),
+                    user_id: r.get("user_id"),
+                    success: r.get("success"),
+                    duration_ms: duration_ms.map(|d| d as u64),
+                }
+            })
+            .collect())
+    }
+
+    async fn list_tool_executions(
+        &self,
+        user_id: &str,
+        tool_name: &str,
+        limit: usize,
+    ) -> Result<Vec<ToolExecution>> {
+        let rows = sqlx::query(
+            "SELECT e.id, e.session_id, e.tool_name, e.arguments, e.output, e.success, e.duration_ms, e.created_at
+             FROM tool_executions e
+             JOIN sessions s ON s.id = e.session_id
+             WHERE s.user_id = ? AND e.tool_name = ?
+             ORDER BY e.created_at DESC
+             LIMIT ?",
+        )
+        .bind(user_id)
+        .bind(tool_name)
+        .bind(limit as i64)
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(rows
+            .into_iter()
+            .map(|r| {
+                let duration_ms: Option<i64> = r.get("duration_ms");
+                ToolExecution {
+                    id: r.get("id"),
+                    session_id: r.get("session_id"),
+                    tool_name: r.get("tool_name"),
+                    arguments: r.get("arguments"),
+                    output: r.get("output"),
+                    success: r.get("success"),
+                    duration_ms: duration_ms.map(|d| d as u64),
+                    created_at: r.get("created_at"),
+                }
+            })
+            .collect())
+    }
+
+    async fn save_pending_approval(&self, approval: PendingApprovalRecord) -> Result<()> {
+        sqlx::query(
+            "INSERT INTO pending_approvals (request_id, session_id, tool_name, arguments, policy, sandbox_available, created_at, expires_at)
+             VALUES (?, ?, ?, ?, ?, ?, ?, ?)",
+        )
+        .bind(&approval.request_id)
+        .bind(&approval.session_id)
+        .bind(&approval.tool_name)
+        .bind(&approval.arguments)
+        .bind(&approval.policy)
+        .bind(approval.sandbox_available)
+        .bind(approval.created_at)
+        .bind(approval.expires_at)
+        .execute(&self.pool)
+        .await?;
+
+        Ok(())
+    }
+
+    async fn delete_pending_approval(&self, request_id: &str) -> Result<()> {
+        sqlx::query("DELETE FROM pending_approvals WHERE request_id = ?")
+            .bind(request_id)
+            .execute(&self.pool)
+            .await?;
+
+        Ok(())
+    }
+
+    async fn list_pending_approvals(&self) -> Result<Vec<PendingApprovalRecord>> {
+        let rows = sqlx::query(
+            "SELECT request_id, session_id, tool_name, arguments, policy, sandbox_available, created_at, expires_at
+             FROM pending_approvals
+             ORDER BY created_at",
+        )
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(rows
+            .into_iter()
+            .map(|r| PendingApprovalRecord {
+                request_id: r.get("request_id"),
+                session_id: r.get("session_id"),
+                tool_name: r.get("tool_name"),
+                arguments: r.get("arguments"),
+                policy: r.get("policy"),
+                sandbox_available: r.get("sandbox_available"),
+                created_at: r.get("created_at"),
+                expires_at: r.get("expires_at"),
+            })
+            .collect())
+    }
+
+    async fn save_session_tool_approval(&self, session_id: &str, tool_name: &str) -> Result<()> {
+        sqlx::query(
+            "INSERT OR IGNORE INTO session_tool_approvals (session_id, tool_name) VALUES (?, ?)",
+        )
+        .bind(session_id)
+        .bind(tool_name)
+        .execute(&self.pool)
+        .await?;
+
+        Ok(())
+    }
+
+    async fn delete_session_tool_approvals(&self, session_id: &str) -> Result<()> {
+        sqlx::query("DELETE FROM session_tool_approvals WHERE session_id = ?")
+            .bind(session_id)
+            .execute(&self.pool)
+            .await?;
+
+        Ok(())
+    }
+
+    async fn list_session_tool_approvals(&self) -> Result<Vec<(String, String)>> {
+        let rows = sqlx::query(
+            "SELECT session_id, tool_name FROM session_tool_approvals ORDER BY created_at",
+        )
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(rows
+            .into_iter()
+            .map(|r| (r.get("session_id"), r.get("tool_name")))
+            .collect())
+    }
+
+    async fn get_user_tool_policies(&self, user_id: &str) -> Result<HashMap<String, String>> {
+        let rows = sqlx::query("SELECT tool_name, level FROM user_tool_policies WHERE user_id = ?")
+            .bind(user_id)
+            .fetch_all(&self.pool)
+            .await?;
+
+        Ok(rows
+            .into_iter()
+            .map(|r| (r.get("tool_name"), r.get("level")))
+            .collect())
+    }
+
+    async fn set_user_tool_policies(
+        &self,
+        user_id: &str,
+        policies: &HashMap<String, String>,
+    ) -> Result<()> {
+        let mut tx = self.pool.begin().await?;
+        sqlx::query("DELETE FROM user_tool_policies WHERE user_id = ?")
+            .bind(user_id)
+            .execute(&mut *tx)
+            .await?;
+
+        let now = chrono::Utc::now();
+        for (tool_name, level) in policies {
+            sqlx::query(
+                "INSERT INTO user_tool_policies (user_id, tool_name, level, updated_at) VALUES (?, ?, ?, ?)",
+            )
+            .bind(user_id)
+            .bind(tool_name)
+            .bind(level)
+            .bind(now)
+            .execute(&mut *tx)
+            .await?;
+        }
+
+        tx.commit().await?;
+        Ok(())
+    }
+
+    async fn list_user_tool_policies(&self) -> Result<HashMap<String, HashMap<String, String>>> {
+        let rows = sqlx::query("SELECT user_id, tool_name, level FROM user_tool_policies")
+            .fetch_all(&self.pool)
+            .await?;
+
+        let mut policies: HashMap<String, HashMap<String, String>> = HashMap::new();
+        for r in rows {
+            policies
+                .entry(r.get("user_id"))
+                .or_default()
+                .insert(r.get("tool_name"), r.get("level"));
+        }
+        Ok(policies)
+    }
+
+    async fn set_message_feedback(&self, feedback: MessageFeedback) -> Result<()> {
+        sqlx::query(
+            "INSERT INTO message_feedback
+                 (message_id, session_id, user_id, rating, comment, model, created_at)
+             VALUES (?, ?, ?, ?, ?, ?, ?)
+             ON CONFLICT(message_id, user_id) DO UPDATE SET
+                 rating = excluded.rating,
+                 comment = excluded.comment,
+                 created_at = excluded.created_at",
+        )
+        .bind(&feedback.message_id)
+        .bind(&feedback.session_id)
+        .bind(&feedback.user_id)
+        .bind(feedback.rating.as_str())
+        .bind(&feedback.comment)
+        .bind(&feedback.model)
+        .bind(feedback.created_at)
+        .execute(&self.pool)
+        .await?;
+        Ok(())
+    }
+
+    async fn feedback_summary(&self) -> Result<FeedbackSummary> {
+        let rows = sqlx::query(
+            "SELECT COALESCE(model, 'unknown') AS model,
+                    SUM(rating = 'up') AS up,
+                    SUM(rating = 'down') AS down
+             FROM message_feedback
+             GROUP BY 1
+             ORDER BY 1",
+        )
+        .fetch_all(&self.pool)
+        .await?;
+
+        let mut summary = FeedbackSummary::default();
+        for r in rows {
+            let up = r.get::<i64, _>("up") as usize;
+            let down = r.get::<i64, _>("down") as usize;
+            summary.up += up;
+            summary.down += down;
+            summary.by_model.push(ModelFeedback {
+                model: r.get("model"),
+                up,
+                down,
+            });
+        }
+        Ok(summary)
+    }
+
+    async fn positively_rated_sessions(&self) -> Result<HashSet<String>> {
+        let rows = sqlx::query(
+            "SELECT session_id FROM message_feedback
+             GROUP BY session_id
+             HAVING SUM(rating = 'up') > 0 AND SUM(rating = 'down') = 0",
+        )
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(rows.into_iter().map(|r| r.get("session_id")).collect())
+    }
+
+    async fn create_schedule(&self, schedule: Schedule) -> Result<()> {
+        sqlx::query(
+            "INSERT INTO schedules (id, user_id, cron, prompt, channel, target, timezone, enabled, created_at, last_run_at)
+             VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?)",
+        )
+        .bind(&schedule.id)
+        .bind(&schedule.user_id)
+        .bind(&schedule.cron)
+        .bind(&schedule.prompt)
+        .bind(&schedule.channel)
+        .bind(&schedule.target)
+        .bind(&schedule.timezone)
+        .bind(schedule.enabled)
+        .bind(schedule.created_at)
+        .bind(schedule.last_run_at)
+        .execute(&self.pool)
+        .await?;
+        Ok(())
+    }
+
+    async fn get_schedule(&self, id: &str) -> Result<Option<Schedule>> {
+        let row = sqlx::query(
+            "SELECT id, user_id, cron, prompt, channel, target, timezone, enabled, created_at, last_run_at
+             FROM schedules WHERE id = ?",
+        )
+        .bind(id)
+        .fetch_optional(&self.pool)
+        .await?;
+
+        Ok(row.map(schedule_from_row))
+    }
+
+    async fn list_schedules(&self, user_id: Option<&str>) -> Result<Vec<Schedule>> {
+        let rows = sqlx::query(
+            "SELECT id, user_id, cron, prompt, channel, target, timezone, enabled, created_at, last_run_at
+             FROM schedules
+             WHERE ?1 IS NULL OR user_id = ?1
+             ORDER BY created_at ASC, rowid ASC",
+        )
+        .bind(user_id)
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(rows.into_iter().map(schedule_from_row).collect())
+    }
+
+    async fn update_schedule(&self, schedule: Schedule) -> Result<bool> {
+        let result = sqlx::query(
+            "UPDATE schedules SET cron = ?, prompt = ?, channel = ?, target = ?, timezone = ?, enabled = ?
+             WHERE id = ?",
+        )
+        .bind(&schedule.cron)
+        .bind(&schedule.prompt)
+        .bind(&schedule.channel)
+        .bind(&schedule.target)
+        .bind(&schedule.timezone)
+        .bind(schedule.enabled)
+        .bind(&schedule.id)
+        .execute(&self.pool)
+        .await?;
+        Ok(result.rows_affected() > 0)
+    }
+
+    async fn delete_schedule(&self, id: &str) -> Result<bool> {
+        let result = sqlx::query("DELETE FROM schedules WHERE id = ?")
+            .bind(id)
+            .execute(&self.pool)
+            .await?;
+        Ok(result.rows_affected() > 0)
+    }
+
+    async fn set_schedule_last_run(
+        &self,
+        id: &str,
+        at: chrono::DateTime<chrono::Utc>,
+    ) -> Result<()> {
+        sqlx::query("UPDATE schedules SET last_run_at = ? WHERE id = ?")
+            .bind(at)
+            .bind(id)
+            .execute(&self.pool)
+            .await?;
+        Ok(())
+    }
+
+    async fn create_reminder(&self, reminder: Reminder) -> Result<()> {
+        sqlx::query(
+            "INSERT INTO reminders (id, user_id, message, channel, target, due_at, created_at, delivered_at)
+             VALUES (?, ?, ?, ?, ?, ?, ?, ?)",
+        )
+        .bind(&reminder.id)
+        .bind(&reminder.user_id)
+        .bind(&reminder.message)
+        .bind(&reminder.channel)
+        .bind(&reminder.target)
+        .bind(reminder.due_at)
+        .bind(reminder.created_at)
+        .bind(reminder.delivered_at)
+        .execute(&self.pool)
+        .await?;
+        Ok(())
+    }
+
+    async fn list_reminders(&self, user_id: &str) -> Result<Vec<Reminder>> {
+        let rows = sqlx::query(
+            "SELECT id, user_id, message, channel, target, due_at, created_at, delivered_at
+             FROM reminders
+             WHERE user_id = ? AND delivered_at IS NULL
+             ORDER BY due_at ASC",
+        )
+        .bind(user_id)
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(rows.into_iter().map(reminder_from_row).collect())
+    }
+
+    async fn due_reminders(&self, now: chrono::DateTime<chrono::Utc>) -> Result<Vec<Reminder>> {
+        let rows = sqlx::query(
+            "SELECT id, user_id, message, channel, target, due_at, created_at, delivered_at
+             FROM reminders
+             WHERE delivered_at IS NULL AND due_at <= ?
+             ORDER BY due_at ASC",
+        )
+        .bind(now)
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(rows.into_iter().map(reminder_from_row).collect())
+    }
+
+    async fn mark_reminder_delivered(
+        &self,
+        id: &str,
+        at: chrono::DateTime<chrono::Utc>,
+    ) -> Result<()> {
+        sqlx::query("UPDATE reminders SET delivered_at = ? WHERE id = ?")
+            .bind(at)
+            .bind(id)
+            .execute(&self.pool)
+            .await?;
+        Ok(())
+    }
+
+    async fn cancel_reminder(&self, user_id: &str, id: &str) -> Result<bool> {
+        let result = sqlx::query(
+            "DELETE FROM reminders WHERE id = ? AND user_id = ? AND delivered_at IS NULL",
+        )
+        .bind(id)
+        .bind(user_id)
+        .execute(&self.pool)
+        .await?;
+        Ok(result.rows_affected() > 0)
+    }
+
+    async fn replace_document_chunks(
+        &self,
+        source: &str,
+        chunks: Vec<DocumentChunk>,
+    ) -> Result<()> {
+        let mut tx = self.pool.begin().await?;
+        sqlx::query("DELETE FROM document_chunks WHERE source = ?")
+            .bind(source)
+            .execute(&mut *tx)
+            .await?;
+
+        let now = chrono::Utc::now();
+        for chunk in chunks {
+            sqlx::query(
+                "INSERT INTO document_chunks (source, chunk_index, content, embedding, fingerprint, updated_at)
+                 VALUES (?, ?, ?, ?, ?, ?)",
+            )
+            .bind(source)
+            .bind(chunk.chunk_index as i64)
+            .bind(&chunk.content)
+            .bind(chunk.embedding.as_deref().map(embedding_to_blob))
+            .bind(&chunk.fingerprint)
+            .bind(now)
+            .execute(&mut *tx)
+            .await?;
+        }
+
+        tx.commit().await?;
+        Ok(())
+    }
+
+    async fn delete_document_chunks(&self, source: &str) -> Result<()> {
+        sqlx::query("DELETE FROM document_chunks WHERE source = ?")
+            .bind(source)
+            .execute(&self.pool)
+            .await?;
+        Ok(())
+    }
+
+    async fn list_document_chunks(&self) -> Result<Vec<DocumentChunk>> {
+        let rows = sqlx::query(
+            "SELECT source, chunk_index, content, embedding, fingerprint FROM document_chunks
+             ORDER BY source, chunk_index",
+        )
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(rows
+            .into_iter()
+            .map(|r| {
+                let chunk_index: i64 = r.get("chunk_index");
+                let embedding: Option<Vec<u8>> = r.get("embedding");
+                DocumentChunk {
+                    source: r.get("source"),
+                    chunk_index: chunk_index as usize,
+                    content: r.get("content"),
+                    embedding: embedding.as_deref().map(embedding_from_blob),
+                    fingerprint: r.get("fingerprint"),
+                }
+            })
+            .collect())
+    }
+
+    async fn plugin_kv_get(&self, plugin_id: &str, key: &str) -> Result<Option<serde_json::Value>> {
+        let row = sqlx::query("SELECT value FROM plugin_kv WHERE plugin_id = ? AND key = ?")
+            .bind(plugin_id)
+            .bind(key)
+            .fetch_optional(&self.pool)
+            .await?;
+
+        row.map(|r| {
+            let value: String = r.get("value");
+            serde_json::from_str(&value).context("Invalid plugin KV value")
+        })
+        .transpose()
+    }
+
+    async fn plugin_kv_set(
+        &self,
+        plugin_id: &str,
+        key: &str,
+        value: serde_json::Value,
+    ) -> Result<()> {
+        sqlx::query(
+            "INSERT INTO plugin_kv (plugin_id, key, value) VALUES (?, ?, ?)
+             ON CONFLICT(plugin_id, key)
+             DO UPDATE SET value = excluded.value, updated_at = CURRENT_TIMESTAMP",
+        )
+        .bind(plugin_id)
+        .bind(key)
+        .bind(value.to_string())
+        .execute(&self.pool)
+        .await?;
+
+        Ok(())
+    }
+
+    async fn plugin_kv_delete(&self, plugin_id: &str, key: &str) -> Result<bool> {
+        let result = sqlx::query("DELETE FROM plugin_kv WHERE plugin_id = ? AND key = ?")
+            .bind(plugin_id)
+            .bind(key)
+            .execute(&self.pool)
+            .await?;
+        Ok(result.rows_affected() > 0)
+    }
+
+    async fn set_channel_paused(&self, channel: &str, paused: bool) -> Result<()> {
+        let query = if paused {
+            "INSERT OR IGNORE INTO paused_channels (channel) VALUES (?)"
+        } else {
+            "DELETE FROM paused_channels WHERE channel = ?"
+        };
+        sqlx::query(query).bind(channel).execute(&self.pool).await?;
+        Ok(())
+    }
+
+    async fn list_paused_channels(&self) -> Result<Vec<String>> {
+        let rows = sqlx::query("SELECT channel FROM paused_channels ORDER BY channel")
+            .fetch_all(&self.pool)
+            .await?;
+        Ok(rows.into_iter().map(|r| r.get("channel")).collect())
+    }
+
+    async fn set_maintenance(&self, enabled: bool) -> Result<()> {
+        let query = if enabled {
+            "INSERT OR IGNORE INTO maintenance_mode (id) VALUES (1)"
+        } else {
+            "DELETE FROM maintenance_mode"
+        };
+        sqlx::query(query).execute(&self.pool).await?;
+        Ok(())
+    }
+
+    async fn get_maintenance(&self) -> Result<bool> {
+        let row = sqlx::query("SELECT 1 FROM maintenance_mode")
+            .fetch_optional(&self.pool)
+            .await?;
+        Ok(row.is_some())
+    }
+
+    async fn save_draft(&self, draft: &MessageDraft) -> Result<()> {
+        sqlx::query(
+            "INSERT INTO message_drafts (id, session_id, content, model_used, updated_at)
+             VALUES (?, ?, ?, ?, ?)
+             ON CONFLICT(id) DO UPDATE SET
+                 content = excluded.content,
+                 model_used = excluded.model_used,
+                 updated_at = excluded.updated_at",
+        )
+        .bind(&draft.id)
+        .bind(&draft.session_id)
+        .bind(&draft.content)
+        .bind(&draft.model_used)
+        .bind(draft.updated_at)
+        .execute(&self.pool)
+        .await?;
+        Ok(())
+    }
+
+    async fn delete_draft(&self, id: &str) -> Result<()> {
+        sqlx::query("DELETE FROM message_drafts WHERE id = ?")
+            .bind(id)
+            .execute(&self.pool)
+            .await?;
+        Ok(())
+    }
+
+    async fn list_drafts(&self) -> Result<Vec<MessageDraft>> {
+        let rows = sqlx::query(
+            "SELECT id, session_id, content, model_used, updated_at FROM message_drafts
+             ORDER BY updated_at ASC",
+        )
+        .fetch_all(&self.pool)
+        .await?;
+
+        Ok(rows
+            .into_iter()
+            .map(|r| MessageDraft {
+                id: r.get("id"),
+                session_id: r.get("session_id"),
+                content: r.get("content"),
+                model_used: r.get("model_used"),
+                updated_at: r.get("updated_at"),
+            })
+            .collect())
+    }
+
+    async fn save_eval_suite(&self, suite: &EvalSuite) -> Result<()> {
+        sqlx::query(
+            "INSERT INTO eval_suites (user_id, name, cases, updated_at) VALUES (?, ?, ?, ?)
+             ON CONFLICT(user_id, name) DO UPDATE SET
+                cases = excluded.cases,
+                updated_at = excluded.updated_at",
+        )
+        .bind(&suite.user_id)
+        .bind(&suite.name)
+        .bind(serde_json::to_string(&suite.cases)?)
+        .bind(suite.updated_at)
+        .execute(&self.pool)
+        .await?;
+        Ok(())
+    }
+
+    async fn get_eval_suite(&self, user_id: &str, name: &str) -> Result<Option<EvalSuite>> {
+        let row = sqlx::query(
+            "SELECT user_id, name, cases, updated_at FROM eval_suites
+             WHERE user_id = ? AND name = ?",
+        )
+        .bind(user_id)
+        .bind(name)
+        .fetch_optional(&self.pool)
+        .await?;
+
+        row.map(eval_suite_from_row).transpose()
+    }
+
+    async fn list_eval_suites(&self, user_id: &str) -> Result<Vec<EvalSuite>> {
+        let rows = sqlx::query(
+            "SELECT user_id, name, cases, updated_at FROM eval_suites
+             WHERE user_id = ?
+             ORDER BY name ASC",
+        )
+        .bind(user_id)
+        .fetch_all(&self.pool)
+        .await?;
+
+        rows.into_iter().map(eval_suite_from_row).collect()
+    }
+
+    async fn delete_eval_suite(&self, user_id: &str, name: &str) -> Result<bool> {
+        let result = sqlx::query("DELETE FROM eval_suites WHERE user_id = ? AND name = ?")
+            .bind(user_id)
+            .bind(name)
+            .execute(&self.pool)
+            .await?;
+        Ok(result.rows_affected() > 0)
+    }
+
+    async fn get_user_settings(&self, user_id: &str) -> Result<Option<UserSettings>> {
+        let row = sqlx::query(
+            "SELECT model, temperature, persona, language FROM user_settings WHERE user_id = ?",
+        )
+        .bind(user_id)
+        .fetch_optional(&self.pool)
+        .await?;
+
+        Ok(row.map(|r| UserSettings {
+            model: r.get("model"),
+            temperature: r.get::<Option<f64>, _>("temperature").map(|t| t as f32),
+            persona: r.get("persona"),
+            language: r.get("language"),
+        }))
+    }
+
+    async fn set_user_settings(&self, user_id: &str, settings: &UserSettings) -> Result<()> {
+        sqlx::query(
+            "INSERT INTO user_settings (user_id, model, temperature, persona, language, updated_at)
+             VALUES (?, ?, ?, ?, ?, ?)
+             ON CONFLICT(user_id) DO UPDATE SET
+                model = excluded.model,
+                temperature = excluded.temperature,
+                persona = excluded.persona,
+                language = excluded.language,
+                updated_at = excluded.updated_at",
+        )
+        .bind(user_id)
+        .bind(&settings.model)
+        .bind(settings.temperature.map(f64::from))
+        .bind(&settings.persona)
+        .bind(&settings.language)
+        .bind(chrono::Utc::now())
+        .execute(&self.pool)
+        .await?;
+        Ok(())
+    }
+}
+
+fn eval_suite_from_row(r: sqlx::sqlite::SqliteRow) -> Result<EvalSuite> {
+    let cases: String = r.get("cases");
+    Ok(EvalSuite {
+        user_id: r.get("user_id"),
+        name: r.get("name"),
+        cases: serde_json::from_str(&cases).context("Invalid stored eval cases")?,
+        updated_at: r.get("updated_at"),
+    })
+}
+
+/// Encode an embedding as little-endian f32 values
+fn embedding_to_blob(embedding: &[f32]) -> Vec<u8> {
+    embedding.iter().flat_map(|v| v.to_le_bytes()).collect()
+}
+
+fn embedding_from_blob(blob: &[u8]) -> Vec<f32> {
+    blob.chunks_exact(4)
+        .map(|bytes| f32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]))
+        .collect()
+}
+
+/// Decode an optional JSON array of scopes
+fn scopes_from_column(scopes: Option<String>) -> Result<Option<Vec<String>>> {
+    scopes
+        .map(|s| serde_json::from_str(&s).context("Invalid stored scopes"))
+        .transpose()
+}
+
+fn impersonation_from_row(r: sqlx::sqlite::SqliteRow) -> Result<Impersonation> {
+    Ok(Impersonation {
+        token: r.get("token"),
+        admin_id: r.get("admin_id"),
+        user_id: r.get("user_id"),
+        reason: r.get("reason"),
+        created_at: r.get("created_at"),
+        expires_at: r.get("expires_at"),
+        scopes: scopes_from_column(r.get("scopes"))?,
+    })
+}
+
+/// SQLite `LIMIT` of a page; -1 means no limit
+fn page_limit(page: Page) -> i64 {
+    page.limit.map(|limit| limit as i64).unwrap_or(-1)
+}
+
+/// Escape `LIKE` wildcards (with `\` as the escape character) so they match
+/// literally
+fn escape_like(text: &str) -> String {
+    text.replace('\\', "\\\\")
+        .replace('%', "\\%")
+        .replace('_', "\\_")
+}
+
+fn identity_from_row(r: sqlx::sqlite::SqliteRow) -> Result<Identity> {
+    Ok(Identity {
+        provider: r.get("provider"),
+        provider_id: r.get("provider_id"),
+        user_id: r.get("user_id"),
+        label: r.get("label"),
+        created_at: r.get("created_at"),
+        last_used_at: r.get("last_used_at"),
+        scopes: scopes_from_column(r.get("scopes"))?,
+    })
+}
+
+fn pending_link_from_row(r: sqlx::sqlite::SqliteRow) -> Result<PendingLink> {
+    Ok(PendingLink {
+        code: r.get("code"),
+        user_id: r.get("user_id"),
+        provider: r.get("provider"),
+        role: r.get("role"),
+        scopes: scopes_from_column(r.get("scopes"))?,
+        expires_at: r.get("expires_at"),
+        used: r.get("used"),
+    })
+}
+
+fn schedule_from_row(r: sqlx::sqlite::SqliteRow) -> Schedule {
+    Schedule {
+        id: r.get("id"),
+        user_id: r.get("user_id"),
+        cron: r.get("cron"),
+        prompt: r.get("prompt"),
+        channel: r.get("channel"),
+        target: r.get("target"),
+        timezone: r.get("timezone"),
+        enabled: r.get("enabled"),
+        created_at: r.get("created_at"),
+        last_run_at: r.get("last_run_at"),
+    }
+}
+
+fn reminder_from_row(r: sqlx::sqlite::SqliteRow) -> Reminder {
+    Reminder {
+        id: r.get("id"),
+        user_id: r.get("user_id"),
+        message: r.get("message"),
+        channel: r.get("channel"),
+        target: r.get("target"),
+        due_at: r.get("due_at"),
+        created_at: r.get("created_at"),
+        delivered_at: r.get("delivered_at"),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::{Duration, Utc};
+
+    async fn storage_with_admin() -> SqliteStorage {
+        let storage = SqliteStorage::new(":memory:").await.unwrap();
+        storage
+            .create_user(User {
+                id: "admin-id".to_string(),
+                username: "admin".to_string(),
+                role: "admin".to_string(),
+                created_at: Utc::now(),
+                updated_at: Utc::now(),
+                password_hash: None,
+            })
+            .await
+            .unwrap();
+        storage
+    }
+
+    fn invite(code: &str, expires_in: Duration) -> PendingLink {
+        PendingLink {
+            code: code.to_string(),
+            user_id: "admin-id".to_string(),
+            provider: "invite".to_string(),
+            role: Some("user".to_string()),
+            scopes: Some(vec!["chat".to_string()]),
+            expires_at: Utc::now() + expires_in,
+            used: false,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_pending_link_is_single_use() {
+        let storage = storage_with_admin().await;
+        storage
+            .create_pending_link(invite("code-1", Duration::hours(1)))
+            .await
+            .unwrap();
+
+        let link = storage.get_pending_link("code-1").await.unwrap().unwrap();
+        assert_eq!(link.scopes, Some(vec!["chat".to_string()]));
+
+        let used = storage.use_pending_link("code-1").await.unwrap().unwrap();
+        assert!(used.used);
+        assert_eq!(used.role.as_deref(), Some("user"));
+
+        assert!(storage.use_pending_link("code-1").await.unwrap().is_none());
+        assert!(storage.get_pending_link("code-1").await.unwrap().is_none());
+        assert!(storage.use_pending_link("unknown").await.unwrap().is_none());
+
+        // A released code can be used once more
+        storage.release_pending_link("code-1").await.unwrap();
+        assert!(storage.use_pending_link("code-1").await.unwrap().is_some());
+        assert!(storage.use_pending_link("code-1").await.unwrap().is_none());
+    }
+
+    #[tokio::test]
+    async fn test_user_tool_policies_are_replaced() {
+        let storage = storage_with_admin().await;
+        let first = HashMap::from([
+            ("exec".to_string(), "allow".to_string()),
+            ("web_fetch".to_string(), "deny".to_string()),
+        ]);
+        storage
+            .set_user_tool_policies("alice", &first)
+            .await
+            .unwrap();
+
+        let second = HashMap::from([("exec".to_string(), "elevated".to_string())]);
+        storage
+            .set_user_tool_policies("alice", &second)
+            .await
+            .unwrap();
+
+        assert_eq!(
+            storage.get_user_tool_policies("alice").await.unwrap(),
+            second
+        );
+        assert!(storage
+            .get_user_tool_policies("bob")
+            .await
+            .unwrap()
+            .is_empty());
+        let all = storage.list_user_tool_policies().await.unwrap();
+        assert_eq!(all.len(), 1);
+        assert_eq!(all["alice"], second);
+    }
+
+    #[tokio::test]
+    async fn test_expired_pending_link_is_rejected() {
+        let storage = storage_with_admin().await;
+        // Expired seconds ago, on the same day as now
+        storage
+            .create_pending_link(invite("old", Duration::seconds(-5)))
+            .await
+            .unwrap();
+
+        assert!(storage.get_pending_link("old").await.unwrap().is_none());
+        assert!(storage.use_pending_link("old").await.unwrap().is_none());
+    }
+
+    #[tokio::test]
+    async fn test_document_chunks_are_replaced_per_source() {
+        let storage = SqliteStorage::new(":memory:").await.unwrap();
+        let chunk = |source: &str, index: usize, embedding: Option<Vec<f32>>| DocumentChunk {
+            source: source.to_string(),
+            chunk_index: index,
+            content: format!("{} part {}", source, index),
+            embedding,
+            fingerprint: "12-1700000000".to_string(),
+        };
+
+        storage
+            .replace_document_chunks(
+                "a.md",
+                vec![
+                    chunk("a.md", 0, Some(vec![0.5, -1.25])),
+                    chunk("a.md", 1, None),
+                ],
+            )
+            .await
+            .unwrap();
+        storage
+            .replace_document_chunks("b.md", vec![chunk("b.md", 0, None)])
+            .await
+            .unwrap();
+        storage
+            .replace_document_chunks("a.md", vec![chunk("a.md", 0, Some(vec![2.0]))])
+            .await
+            .unwrap();
+
+        let chunks = storage.list_document_chunks().await.unwrap();
+        assert_eq!(
+            chunks,
+            vec![chunk("a.md", 0, Some(vec![2.0])), chunk("b.md", 0, None)]
+        );
+
+        storage.delete_document_chunks("a.md").await.unwrap();
+        assert_eq!(storage.list_document_chunks().await.unwrap().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_messages_with_the_same_timestamp_keep_insertion_order() {
+        let storage = storage_with_admin().await;
+        for id in ["s1", "fork"] {
+            storage
+                .create_session(Session {
+                    id: id.to_string(),
+                    user_id: "admin-id".to_string(),
+                    channel: "web".to_string(),
+                    scope: "per-sender".to_string(),
+                    created_at: Utc::now(),
+                    updated_at: Utc::now(),
+                })
+                .await
+                .unwrap();
+        }
+        // One turn: ids sort differently from the order they were written in
+        let at = Utc::now();
+        let turn = [
+            ("z-question", "user"),
+            ("m-call", "assistant"),
+            ("a-result", "user"),
+            ("b-reply", "assistant"),
+        ];
+        for (id, role) in turn {
+            storage
+                .add_message(Message {
+                    id: id.to_string(),
+                    session_id: "s1".to_string(),
+                    role: role.to_string(),
+                    content: id.to_string(),
+                    created_at: at,
+                    model_used: None,
+                    tokens: None,
+                    metadata: None,
+                })
+                .await
+                .unwrap();
+        }
+        let contents = |messages: Vec<Message>| -> Vec<String> {
+            messages.into_iter().map(|m| m.content).collect()
+        };
+
+        for _ in 0..3 {
+            assert_eq!(
+                contents(storage.get_messages("s1", None).await.unwrap()),
+                ["z-question", "m-call", "a-result", "b-reply"]
+            );
+        }
+        assert_eq!(
+            contents(storage.get_messages("s1", Some(2)).await.unwrap()),
+            ["a-result", "b-reply"]
+        );
+
+        storage
+            .copy_messages_until("s1", "fork", "m-call")
+            .await
+            .unwrap();
+        assert_eq!(
+            contents(storage.get_messages("fork", None).await.unwrap()),
+            ["z-question", "m-call"]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_copy_messages_until_copies_prefix() {
+        let storage = storage_with_admin().await;
+        for id in ["parent", "fork"] {
+            storage
+                .create_session(Session {
+                    id: id.to_string(),
+                    user_id: "admin-id".to_string(),
+                    channel: "web".to_string(),
+                    scope: "per-sender".to_string(),
+                    created_at: Utc::now(),
+                    updated_at: Utc::now(),
+                })
+                .await
+                .unwrap();
+        }
+        let start = Utc::now();
+        for i in 0..4 {
+            storage
+                .add_message(Message {
+                    id: format!("m{}", i),
+                    session_id: "parent".to_string(),
+                    role: if i % 2 == 0 { "user" } else { "assistant" }.to_string(),
+                    content: format!("message {}", i),
+                    created_at: start + Duration::seconds(i),
+                    model_used: None,
+                    tokens: Some(3),
+                    metadata: None,
+                })
+                .await
+                .unwrap();
+        }
+
+        assert_eq!(
+            storage
+                .copy_messages_until("parent", "fork", "missing")
+                .await
+                .unwrap(),
+            None
+        );
+        assert_eq!(
+            storage
+                .copy_messages_until("parent", "fork", "m1")
+                .await
+                .unwrap(),
+            Some(2)
+        );
+
+        let copied = storage.get_messages("fork", None).await.unwrap();
+        let contents: Vec<&str> = copied.iter().map(|m| m.content.as_str()).collect();
+        assert_eq!(contents, vec!["message 0", "message 1"]);
+        assert!(copied.iter().all(|m| !m.id.starts_with('m')));
+        assert_eq!(
+            copied[1].metadata,
+            Some(serde_json::json!({
+                "forked_from": { "session_id": "parent", "message_id": "m1" }
+            }))
+        );
+        assert_eq!(storage.get_messages("parent", None).await.unwrap().len(), 4);
+    }
+
+    #[tokio::test]
+    async fn test_session_notes_outlive_cleared_messages() {
+        let storage = storage_with_admin().await;
+        storage
+            .create_session(Session {
+                id: "s1".to_string(),
+                user_id: "admin-id".to_string(),
+                channel: "web".to_string(),
+                scope: "per-sender".to_string(),
+                created_at: Utc::now(),
+                updated_at: Utc::now(),
+            })
+            .await
+            .unwrap();
+        let start = Utc::now();
+        for (i, content) in ["first", "second"].into_iter().enumerate() {
+            storage
+                .add_session_note(SessionNote {
+                    id: format!("n{}", i),
+                    session_id: "s1".to_string(),
+                    content: content.to_string(),
+                    created_at: start + Duration::seconds(i as i64),
+                })
+                .await
+                .unwrap();
+        }
+
+        storage.delete_session_messages("s1").await.unwrap();
+        let notes = storage.list_session_notes("s1").await.unwrap();
+        let contents: Vec<&str> = notes.iter().map(|n| n.content.as_str()).collect();
+        assert_eq!(contents, vec!["first", "second"]);
+
+        assert!(!storage.delete_session_note("other", "n0").await.unwrap());
+        assert!(storage.delete_session_note("s1", "n0").await.unwrap());
+        assert_eq!(storage.list_session_notes("s1").await.unwrap().len(), 1);
+
+        storage.delete_session("s1").await.unwrap();
+        assert!(storage.list_session_notes("s1").await.unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_schedule_crud() {
+        let storage = storage_with_admin().await;
+        let mut schedule = Schedule {
+            id: "sched-1".to_string(),
+            user_id: "admin-id".to_string(),
+            cron: "0 8 * * *".to_string(),
+            prompt: "Morning summary".to_string(),
+            channel: "telegram".to_string(),
+            target: "42".to_string(),
+            timezone: Some("Europe/Berlin".to_string()),
+            enabled: true,
+            created_at: Utc::now(),
+            last_run_at: None,
+        };
+        storage.create_schedule(schedule.clone()).await.unwrap();
+
+        schedule.enabled = false;
+        assert!(storage.update_schedule(schedule.clone()).await.unwrap());
+        let ran_at = Utc::now();
+        storage
+            .set_schedule_last_run("sched-1", ran_at)
+            .await
+            .unwrap();
+
+        let stored = storage.get_schedule("sched-1").await.unwrap().unwrap();
+        assert!(!stored.enabled);
+        assert_eq!(stored.last_run_at, Some(ran_at));
+        assert_eq!(storage.list_schedules(None).await.unwrap().len(), 1);
+        assert!(storage
+            .list_schedules(Some("someone-else"))
+            .await
+            .unwrap()
+            .is_empty());
+
+        assert!(storage.delete_schedule("sched-1").await.unwrap());
+        assert!(!storage.delete_schedule("sched-1").await.unwrap());
+        assert!(storage.get_schedule("sched-1").await.unwrap().is_none());
+    }
+
+    #[tokio::test]
+    async fn test_feedback_upserts_and_aggregates_per_model() {
+        use crate::storage::FeedbackRating;
+
+        let storage = storage_with_admin().await;
+        let rate = |message_id: &str, user_id: &str, rating, model: Option<&str>| MessageFeedback {
+            message_id: message_id.to_string(),
+            session_id: "s1".to_string(),
+            user_id: user_id.to_string(),
+            rating,
+            comment: None,
+            model: model.map(String::from),
+            created_at: Utc::now(),
+        };
+
+        storage
+            .set_message_feedback(rate("m1", "alice", FeedbackRating::Down, Some("qwen")))
+            .await
+            .unwrap();
+        // Re-rating replaces the earlier rating
+        storage
+            .set_message_feedback(rate("m1", "alice", FeedbackRating::Up, Some("qwen")))
+            .await
+            .unwrap();
+        storage
+            .set_message_feedback(rate("m1", "bob", FeedbackRating::Down, Some("qwen")))
+            .await
+            .unwrap();
+        storage
+            .set_message_feedback(rate("m2", "alice", FeedbackRating::Up, Some("llama")))
+            .await
+            .unwrap();
+        storage
+            .set_message_feedback(rate("m3", "alice", FeedbackRating::Down, None))
+            .await
+            .unwrap();
+
+        let summary = storage.feedback_summary().await.unwrap();
+        assert_eq!((summary.up, summary.down), (2, 2));
+        assert_eq!(
+            summary.by_model,
+            vec![
+                ModelFeedback {
+                    model: "llama".to_string(),
+                    up: 1,
+                    down: 0
+                },
+                ModelFeedback {
+                    model: "qwen".to_string(),
+                    up: 1,
+                    down: 1
+                },
+                ModelFeedback {
+                    model: "unknown".to_string(),
+                    up: 0,
+                    down: 1
+                },
+            ]
+        );
+    }
+
+    async fn add_user(storage: &SqliteStorage, username: &str, role: &str, age_minutes: i64) {
+        let created_at = Utc::now() - Duration::minutes(age_minutes);
+        storage
+            .create_user(User {
+                id: format!("{}-id", username),
+                username: username.to_string(),
+                role: role.to_string(),
+                created_at,
+                updated_at: created_at,
+                password_hash: None,
+            })
+            .await
+            .unwrap();
+    }
+
+    fn usernames(users: &Paged<User>) -> Vec<&str> {
+        users.items.iter().map(|u| u.username.as_str()).collect()
+    }
+
+    #[tokio::test]
+    async fn test_list_users_pages() {
+        let storage = storage_with_admin().await;
+        for (i, name) in ["alice", "bob", "carol", "dave"].iter().enumerate() {
+            add_user(&storage, name, "user", 40 - i as i64).await;
+        }
+        let all = UserFilter::default();
+
+        let page = storage.list_users(&all, Page::new(2, 0)).await.unwrap();
+        assert_eq!(usernames(&page), ["alice", "bob"]);
+        assert_eq!(page.total, 5);
+
+        let page = storage.list_users(&all, Page::new(2, 4)).await.unwrap();
+        assert_eq!(usernames(&page), ["admin"]);
+
+        let page = storage.list_users(&all, Page::new(2, 5)).await.unwrap();
+        assert!(page.items.is_empty());
+        assert_eq!(page.total, 5);
+
+        let page = storage.list_users(&all, Page::new(0, 0)).await.unwrap();
+        assert!(page.items.is_empty());
+
+        let page = storage.list_users(&all, Page::all()).await.unwrap();
+        assert_eq!(usernames(&page), ["alice", "bob", "carol", "dave", "admin"]);
+    }
+
+    #[tokio::test]
+    async fn test_list_users_filters() {
+        let storage = storage_with_admin().await;
+        add_user(&storage, "Alice", "user", 30).await;
+        add_user(&storage, "malice", "admin", 20).await;
+        add_user(&storage, "a_b", "user", 10).await;
+        add_user(&storage, "axb", "user", 5).await;
+
+        let filter = |username: Option<&str>, role: Option<&str>| UserFilter {
+            username: username.map(str::to_string),
+            role: role.map(str::to_string),
+        };
+
+        let users = storage
+            .list_users(&filter(Some("ALI"), None), Page::all())
+            .await
+            .unwrap();
+        assert_eq!(usernames(&users), ["Alice", "malice"]);
+        assert_eq!(users.total, 2);
+
+        let users = storage
+            .list_users(&filter(None, Some("admin")), Page::new(1, 0))
+            .await
+            .unwrap();
+        assert_eq!(usernames(&users), ["malice"]);
+        assert_eq!(users.total, 2);
+
+        let users = storage
+            .list_users(&filter(Some("ali"), Some("user")), Page::all())
+            .await
+            .unwrap();
+        assert_eq!(usernames(&users), ["Alice"]);
+
+        // Wildcards in the filter match literally
+        let users = storage
+            .list_users(&filter(Some("_"), None), Page::all())
+            .await
+            .unwrap();
+        assert_eq!(usernames(&users), ["a_b"]);
+        let users = storage
+            .list_users(&filter(Some("%"), None), Page::all())
+            .await
+            .unwrap();
+        assert_eq!(users.total, 0);
+    }
+
+    #[tokio::test]
+    async fn test_list_identities_pages_by_provider() {
+        let storage = storage_with_admin().await;
+        for (i, (provider, provider_id)) in [
+            ("api_token", "token-1"),
+            ("telegram", "12345"),
+            ("api_token", "token-2"),
+            ("api_token", "token-3"),
+        ]
+        .into_iter()
+        .enumerate()
+        {
+            storage
+                .create_identity(Identity {
+                    provider: provider.to_string(),
+                    provider_id: provider_id.to_string(),
+                    user_id: "admin-id".to_string(),
+                    label: None,
+                    created_at: Utc::now() - Duration::minutes(10 - i as i64),
+                    last_used_at: None,
+                    scopes: None,
+                })
+                .await
+                .unwrap();
+        }
+        let ids = |page: &Paged<Identity>| -> Vec<String> {
+            page.items.iter().map(|i| i.provider_id.clone()).collect()
+        };
+
+        let all = storage
+            .list_identities("admin-id", None, Page::all())
+            .await
+            .unwrap();
+        assert_eq!(ids(&all), ["token-1", "12345", "token-2", "token-3"]);
+
+        let tokens = storage
+            .list_identities("admin-id", Some("api_token"), Page::new(2, 1))
+            .await
+            .unwrap();
+        assert_eq!(ids(&tokens), ["token-2", "token-3"]);
+        assert_eq!(tokens.total, 3);
+
+        let past_end = storage
+            .list_identities("admin-id", Some("api_token"), Page::new(2, 3))
+            .await
+            .unwrap();
+        assert!(past_end.items.is_empty());
+        assert_eq!(past_end.total, 3);
+
+        let nobody = storage
+            .list_identities("missing", None, Page::all())
+            .await
+            .unwrap();
+        assert_eq!(nobody.total, 0);
+    }
+
+    #[tokio::test]
+    async fn test_expired_impersonations_stay_in_the_audit_trail() {
+        let storage = storage_with_admin().await;
+        let grant = |token: &str, age: Duration, ttl: Duration| Impersonation {
+            token: token.to_string(),
+            admin_id: "admin-id".to_string(),
+            user_id: "user-id".to_string(),
+            reason: Some("ticket 42".to_string()),
+            created_at: Utc::now() - age,
+            expires_at: Utc::now() - age + ttl,
+            scopes: Some(vec!["sessions:read".to_string()]),
+        };
+        storage
+            .create_impersonation(&grant("imp-old", Duration::hours(1), Duration::minutes(15)))
+            .await
+            .unwrap();
+        storage
+            .create_impersonation(&grant("imp-new", Duration::zero(), Duration::minutes(15)))
+            .await
+            .unwrap();
+
+        assert!(storage
+            .get_impersonation("imp-old")
+            .await
+            .unwrap()
+            .is_none());
+        let active = storage.get_impersonation("imp-new").await.unwrap().unwrap();
+        assert_eq!(active.user_id, "user-id");
+        assert_eq!(active.reason.as_deref(), Some("ticket 42"));
+        assert_eq!(active.scopes, Some(vec!["sessions:read".to_string()]));
+
+        let trail = storage.list_impersonations(10).await.unwrap();
+        let tokens: Vec<&str> = trail.iter().map(|i| i.token.as_str()).collect();
+        assert_eq!(tokens, ["imp-new", "imp-old"]);
+        assert_eq!(storage.list_impersonations(1).await.unwrap().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_eval_suites_are_replaced_by_name() {
+        let storage = SqliteStorage::new(":memory:").await.unwrap();
+        let suite = |input: &str| EvalSuite {
+            user_id: "user-id".to_string(),
+            name: "smoke".to_string(),
+            cases: vec![crate::storage::EvalCase {
+                input: input.to_string(),
+                expected_contains: Some("4".to_string()),
+                expected_regex: None,
+            }],
+            updated_at: Utc::now(),
+        };
+        storage.save_eval_suite(&suite("2+2?")).await.unwrap();
+        storage
+            .save_eval_suite(&suite("two plus two?"))
+            .await
+            .unwrap();
+
+        let saved = storage
+            .get_eval_suite("user-id", "smoke")
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(saved.cases[0].input, "two plus two?");
+        assert_eq!(storage.list_eval_suites("user-id").await.unwrap().len(), 1);
+        assert!(storage.list_eval_suites("other").await.unwrap().is_empty());
+
+        assert!(storage.delete_eval_suite("user-id", "smoke").await.unwrap());
+        assert!(!storage.delete_eval_suite("user-id", "smoke").await.unwrap());
+    }
+
+    #[tokio::test]
+    async fn test_user_settings_are_replaced() {
+        let storage = SqliteStorage::new(":memory:").await.unwrap();
+        assert_eq!(storage.get_user_settings("user-id").await.unwrap(), None);
+
+        let settings = UserSettings {
+            model: Some("role:code".to_string()),
+            temperature: Some(0.25),
+            persona: Some("concise".to_string()),
+            language: None,
+        };
+        storage
+            .set_user_settings("user-id", &settings)
+            .await
+            .unwrap();
+        assert_eq!(
+            storage.get_user_settings("user-id").await.unwrap(),
+            Some(settings)
+        );
+
+        let settings = UserSettings {
+            language: Some("de".to_string()),
+            ..Default::default()
+        };
+        storage
+            .set_user_settings("user-id", &settings)
+            .await
+            .unwrap();
+        assert_eq!(
+            storage.get_user_settings("user-id").await.unwrap(),
+            Some(settings)
+        );
+        assert_eq!(storage.get_user_settings("other").await.unwrap(), None);
+    }
 }
@@ -0,0 +1,65 @@
+//! The `ask_user` tool
+//!
+//! Lets the model ask the user a clarifying question in the middle of a
+//! turn; the answer is the tool's result (see [`crate::core::user_input`]).
+
+use crate::core::user_input::{user_inputs, UserInputOutcome, USER_INPUT_TIMEOUT_SECS};
+use crate::llm::ToolDefinition;
+use anyhow::{anyhow, bail, Result};
+use serde::Deserialize;
+use serde_json::json;
+use std::time::Duration;
+
+/// Longest question accepted
+const MAX_QUESTION_CHARS: usize = 1000;
+
+/// Parameters for ask_user
+#[derive(Debug, Deserialize)]
+pub struct AskUserParams {
+    pub question: String,
+}
+
+/// Run the `ask_user` tool: the user's answer, or a note telling the model
+/// to go on without one
+pub async fn ask_user(session_id: Option<&str>, params: AskUserParams) -> Result<String> {
+    let session_id = session_id.ok_or_else(|| anyhow!("ask_user needs a session"))?;
+    let question = params.question.trim();
+    if question.is_empty() {
+        bail!("question must not be empty");
+    }
+    if question.chars().count() > MAX_QUESTION_CHARS {
+        bail!("question is too long (max {} chars)", MAX_QUESTION_CHARS);
+    }
+
+    let timeout = Duration::from_secs(USER_INPUT_TIMEOUT_SECS);
+    Ok(
+        match user_inputs().ask(session_id, question, timeout).await {
+            UserInputOutcome::Answered(answer) => answer,
+            UserInputOutcome::TimedOut => format!(
+                "The user did not answer within {} seconds. Continue without the answer.",
+                USER_INPUT_TIMEOUT_SECS
+            ),
+            UserInputOutcome::NoListener => {
+                "The user cannot be asked in this conversation. Continue without the answer."
+                    .to_string()
+            }
+        },
+    )
+}
+
+pub fn get_ask_user_tool_definitions() -> Vec<ToolDefinition> {
+    vec![ToolDefinition {
+        name: "ask_user".to_string(),
+        description: "Ask the user a clarifying question and wait for the answer. Use it only when you cannot go on without information the user has to provide.".to_string(),
+        parameters: json!({
+            "type": "object",
+            "properties": {
+                "question": {
+                    "type": "string",
+                    "description": "The question, phrased for the user"
+                }
+            },
+            "required": ["question"]
+        }),
+    }]
+}
@@ -0,0 +1,228 @@
+use crate::core::redaction::Redactor;
+use crate::storage::{Storage, ToolExecution};
+use chrono::Utc;
+use once_cell::sync::Lazy;
+use serde_json::Value;
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::time::Instant;
+
+/// Maximum characters kept from tool arguments
+const MAX_ARGUMENTS_CHARS: usize = 200;
+
+/// Maximum characters kept from tool output
+const MAX_OUTPUT_CHARS: usize = 500;
+
+/// Argument keys whose values are never written to the audit log
+const SENSITIVE_KEYS: &[&str] = &["password", "secret", "token", "api_key", "apikey", "auth"];
+
+/// When each tool was last called in this process
+static LAST_USED: Lazy<Mutex<HashMap<String, Instant>>> = Lazy::new(|| Mutex::new(HashMap::new()));
+
+/// When each tool was last called since startup, by tool name
+pub fn last_used() -> HashMap<String, Instant> {
+    LAST_USED.lock().unwrap().clone()
+}
+
+/// A finished tool call, as handed to the audit log
+pub struct ExecutionRecord<'a> {
+    pub tool_name: &'a str,
+    pub arguments: &'a str,
+    pub output: &'a str,
+    pub success: bool,
+    pub duration_ms: Option<u64>,
+}
+
+/// Record a tool execution in the audit log. Arguments and output go
+/// through the PII redactor, when redaction is enabled, before they are
+/// summarized and stored.
+///
+/// Failures are logged and swallowed so auditing never breaks a tool call.
+pub async fn record_execution<S: Storage>(
+    storage: &S,
+    session_id: &str,
+    record: ExecutionRecord<'_>,
+    redactor: Option<&Redactor>,
+) {
+    LAST_USED
+        .lock()
+        .unwrap()
+        .insert(record.tool_name.to_string(), Instant::now());
+
+    let (arguments, output) = match redactor {
+        Some(redactor) => (
+            redactor.redact(record.arguments).0,
+            redactor.redact(record.output).0,
+        ),
+        None => (record.arguments.to_string(), record.output.to_string()),
+    };
+
+    let execution = ToolExecution {
+        id: uuid::Uuid::new_v4().to_string(),
+        session_id: session_id.to_string(),
+        tool_name: record.tool_name.to_string(),
+        arguments: summarize_arguments(&arguments),
+        output: truncate(&output, MAX_OUTPUT_CHARS),
+        success: record.success,
+        duration_ms: record.duration_ms,
+        created_at: Utc::now(),
+    };
+
+    if let Err(e) = storage.add_tool_execution(execution).await {
+        tracing::warn!(
+            "Failed to record execution of tool {}: {}",
+            record.tool_name,
+            e
+        );
+    }
+}
+
+/// Short, redacted summary of tool arguments
+pub fn summarize_arguments(arguments: &str) -> String {
+    let summary = match serde_json::from_str::<Value>(arguments) {
+        Ok(mut value) => {
+            redact(&mut value);
+            value.to_string()
+        }
+        Err(_) => arguments.to_string(),
+    };
+
+    truncate(&summary, MAX_ARGUMENTS_CHARS)
+}
+
+/// Replace values of sensitive-looking keys, recursively
+fn redact(value: &mut Value) {
+    match value {
+        Value::Object(map) => {
+            for (key, value) in map.iter_mut() {
+                let key = key.to_lowercase();
+                if SENSITIVE_KEYS.iter().any(|k| key.contains(k)) {
+                    *value = Value::String("[redacted]".to_string());
+                } else {
+                    redact(value);
+                }
+            }
+        }
+        Value::Array(items) => items.iter_mut().for_each(redact),
+        _ => {}
+    }
+}
+
+/// Truncate to at most `max` characters, marking the cut
+fn truncate(text: &str, max: usize) -> String {
+    match text.char_indices().nth(max) {
+        Some((idx, _)) => format!("{}…", &text[..idx]),
+        None => text.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::storage::sqlite::SqliteStorage;
+    use crate::storage::Session;
+
+    #[test]
+    fn test_arguments_are_redacted_and_truncated() {
+        let summary = summarize_arguments(
+            r#"{"url": "https://example.com", "headers": {"Authorization": "Bearer abc"}, "api_key": "xyz"}"#,
+        );
+        assert!(summary.contains("https://example.com"));
+        assert!(!summary.contains("abc"));
+        assert!(!summary.contains("xyz"));
+
+        let long = format!(r#"{{"command": "{}"}}"#, "é".repeat(500));
+        assert_eq!(
+            summarize_arguments(&long).chars().count(),
+            MAX_ARGUMENTS_CHARS + 1
+        );
+    }
+
+    #[tokio::test]
+    async fn test_executions_are_scoped_to_the_owner() {
+        let storage = SqliteStorage::new(":memory:").await.unwrap();
+        for (id, user) in [("s-alice", "alice"), ("s-bob", "bob")] {
+            storage
+                .create_session(Session {
+                    id: id.to_string(),
+                    user_id: user.to_string(),
+                    channel: "web".to_string(),
+                    scope: "per-sender".to_string(),
+                    created_at: Utc::now(),
+                    updated_at: Utc::now(),
+                })
+                .await
+                .unwrap();
+        }
+
+        for (session_id, tool_name, output, success, duration_ms) in [
+            ("s-alice", "web_fetch", "ok", true, Some(12)),
+            ("s-alice", "web_fetch", "boom", false, None),
+            ("s-alice", "exec", "ok", true, None),
+            ("s-bob", "web_fetch", "ok", true, None),
+        ] {
+            let record = ExecutionRecord {
+                tool_name,
+                arguments: "{}",
+                output,
+                success,
+                duration_ms,
+            };
+            record_execution(&storage, session_id, record, None).await;
+        }
+
+        let runs = storage
+            .list_tool_executions("alice", "web_fetch", 10)
+            .await
+            .unwrap();
+        assert_eq!(runs.len(), 2);
+        assert!(runs.iter().all(|r| r.session_id == "s-alice"));
+        assert!(runs.iter().any(|r| !r.success && r.output == "boom"));
+
+        let limited = storage
+            .list_tool_executions("alice", "web_fetch", 1)
+            .await
+            .unwrap();
+        assert_eq!(limited.len(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_recorded_execution_is_pii_redacted() {
+        let storage = SqliteStorage::new(":memory:").await.unwrap();
+        storage
+            .create_session(Session {
+                id: "s-alice".to_string(),
+                user_id: "alice".to_string(),
+                channel: "web".to_string(),
+                scope: "per-sender".to_string(),
+                created_at: Utc::now(),
+                updated_at: Utc::now(),
+            })
+            .await
+            .unwrap();
+        let redactor = Redactor::from_config(&crate::config::RedactionConfig {
+            enabled: true,
+            ..Default::default()
+        })
+        .unwrap()
+        .unwrap();
+
+        let record = ExecutionRecord {
+            tool_name: "send_email",
+            arguments: r#"{"to": "jane@example.com", "subject": "Hi"}"#,
+            output: "Sent to jane@example.com",
+            success: true,
+            duration_ms: None,
+        };
+        record_execution(&storage, "s-alice", record, Some(&redactor)).await;
+
+        let runs = storage
+            .list_tool_executions("alice", "send_email", 10)
+            .await
+            .unwrap();
+        assert_eq!(runs.len(), 1);
+        assert!(!runs[0].arguments.contains("jane@example.com"));
+        assert!(runs[0].arguments.contains("Hi"));
+        assert_eq!(runs[0].output, "Sent to [REDACTED]");
+    }
+}
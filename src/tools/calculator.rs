@@ -0,0 +1,457 @@
+//! The `calculate` tool: a small arithmetic expression evaluator
+//!
+//! Supports `+ - * / % ^`, parentheses, unary minus, the constants `pi` and
+//! `e`, and common functions (`sqrt`, `abs`, `ln`, `log`, `sin`, ...).
+//! Expressions are parsed here; nothing is handed to a shell or interpreter.
+
+use crate::llm::ToolDefinition;
+use anyhow::{anyhow, bail, Result};
+use serde::Deserialize;
+use serde_json::json;
+
+/// Longest expression accepted
+const MAX_EXPRESSION_CHARS: usize = 1000;
+
+/// Deepest nesting of parentheses and unary operators
+const MAX_DEPTH: usize = 64;
+
+/// Parameters for calculate
+#[derive(Debug, Deserialize)]
+pub struct CalculateParams {
+    pub expression: String,
+}
+
+/// Run the `calculate` tool
+pub fn calculate(params: CalculateParams) -> Result<String> {
+    let value = evaluate(&params.expression)?;
+    Ok(format_number(value))
+}
+
+/// Evaluate an arithmetic expression
+pub fn evaluate(expression: &str) -> Result<f64> {
+    if expression.chars().count() > MAX_EXPRESSION_CHARS {
+        bail!(
+            "Expressions are limited to {} characters",
+            MAX_EXPRESSION_CHARS
+        );
+    }
+    let tokens = tokenize(expression)?;
+    if tokens.is_empty() {
+        bail!("The expression is empty");
+    }
+
+    let mut parser = Parser {
+        tokens,
+        pos: 0,
+        depth: 0,
+    };
+    let value = parser.expression()?;
+    if let Some(token) = parser.peek() {
+        bail!("Unexpected {}", token);
+    }
+    if !value.is_finite() {
+        bail!("The result is not a finite number");
+    }
+    Ok(value)
+}
+
+/// Print integers without a fractional part and round away float noise
+fn format_number(value: f64) -> String {
+    if value.abs() >= 1e15 {
+        return format!("{:e}", value);
+    }
+    if value.fract() == 0.0 {
+        return format!("{}", value as i64);
+    }
+    let rounded = format!("{:.12}", value);
+    let trimmed = rounded.trim_end_matches('0').trim_end_matches('.');
+    if trimmed == "0" || trimmed == "-0" {
+        // Too small for 12 decimals
+        format!("{:e}", value)
+    } else {
+        trimmed.to_string()
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    Number(f64),
+    Ident(String),
+    Op(char),
+    LParen,
+    RParen,
+    Comma,
+}
+
+impl std::fmt::Display for Token {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Token::Number(n) => write!(f, "number {}", n),
+            Token::Ident(name) => write!(f, "'{}'", name),
+            Token::Op(op) => write!(f, "'{}'", op),
+            Token::LParen => write!(f, "'('"),
+            Token::RParen => write!(f, "')'"),
+            Token::Comma => write!(f, "','"),
+        }
+    }
+}
+
+fn tokenize(expression: &str) -> Result<Vec<Token>> {
+    let mut tokens = Vec::new();
+    let mut chars = expression.chars().peekable();
+
+    while let Some(&c) = chars.peek() {
+        match c {
+            c if c.is_whitespace() => {
+                chars.next();
+            }
+            '0'..='9' | '.' => {
+                let mut number = String::new();
+                while let Some(&c) = chars.peek() {
+                    if c.is_ascii_digit() || c == '.' {
+                        number.push(c);
+                        chars.next();
+                    } else if c == '_' {
+                        // Digit separators: 1_000_000
+                        chars.next();
+                    } else if (c == 'e' || c == 'E') && !number.contains(['e', 'E']) {
+                        // Exponent only if digits follow, so `2e` stays 2 * e
+                        let mut lookahead = chars.clone();
+                        lookahead.next();
+                        let sign = lookahead.next_if(|c| *c == '+' || *c == '-');
+                        if !lookahead.peek().is_some_and(|c| c.is_ascii_digit()) {
+                            break;
+                        }
+                        number.push(c);
+                        chars.next();
+                        if let Some(sign) = sign {
+                            number.push(sign);
+                            chars.next();
+                        }
+                    } else {
+                        break;
+                    }
+                }
+                let value = number
+                    .parse::<f64>()
+                    .map_err(|_| anyhow!("Invalid number '{}'", number))?;
+                tokens.push(Token::Number(value));
+            }
+            'a'..='z' | 'A'..='Z' => {
+                let mut ident = String::new();
+                while let Some(&c) = chars.peek() {
+                    if c.is_ascii_alphanumeric() || c == '_' {
+                        ident.push(c.to_ascii_lowercase());
+                        chars.next();
+                    } else {
+                        break;
+                    }
+                }
+                tokens.push(Token::Ident(ident));
+            }
+            '+' | '-' | '*' | '/' | '%' | '^' => {
+                chars.next();
+                // `**` is a common spelling of the power operator
+                if c == '*' && chars.next_if_eq(&'*').is_some() {
+                    tokens.push(Token::Op('^'));
+                } else {
+                    tokens.push(Token::Op(c));
+                }
+            }
+            '×' => {
+                chars.next();
+                tokens.push(Token::Op('*'));
+            }
+            '÷' => {
+                chars.next();
+                tokens.push(Token::Op('/'));
+            }
+            '(' => {
+                chars.next();
+                tokens.push(Token::LParen);
+            }
+            ')' => {
+                chars.next();
+                tokens.push(Token::RParen);
+            }
+            ',' => {
+                chars.next();
+                tokens.push(Token::Comma);
+            }
+            other => bail!("Unexpected character '{}'", other),
+        }
+    }
+
+    Ok(tokens)
+}
+
+/// Recursive descent parser evaluating as it goes. Precedence from lowest:
+/// `+ -`, `* / %`, unary `-`, `^` (right associative), then atoms.
+struct Parser {
+    tokens: Vec<Token>,
+    pos: usize,
+    depth: usize,
+}
+
+impl Parser {
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.pos)
+    }
+
+    fn next(&mut self) -> Option<Token> {
+        let token = self.tokens.get(self.pos).cloned();
+        self.pos += 1;
+        token
+    }
+
+    fn expect(&mut self, expected: Token) -> Result<()> {
+        match self.next() {
+            Some(token) if token == expected => Ok(()),
+            Some(token) => bail!("Expected {} but found {}", expected, token),
+            None => bail!("Expected {} at the end", expected),
+        }
+    }
+
+    fn nested<T>(&mut self, parse: impl FnOnce(&mut Self) -> Result<T>) -> Result<T> {
+        self.depth += 1;
+        if self.depth > MAX_DEPTH {
+            bail!("The expression is nested too deeply");
+        }
+        let result = parse(self);
+        self.depth -= 1;
+        result
+    }
+
+    fn expression(&mut self) -> Result<f64> {
+        let mut value = self.term()?;
+        while let Some(Token::Op(op @ ('+' | '-'))) = self.peek() {
+            let op = *op;
+            self.pos += 1;
+            let rhs = self.term()?;
+            value = if op == '+' { value + rhs } else { value - rhs };
+        }
+        Ok(value)
+    }
+
+    fn term(&mut self) -> Result<f64> {
+        let mut value = self.unary()?;
+        loop {
+            let op = match self.peek() {
+                Some(Token::Op(op @ ('*' | '/' | '%'))) => {
+                    let op = *op;
+                    self.pos += 1;
+                    op
+                }
+                // Implicit multiplication: 2pi, 3(4 + 1)
+                Some(Token::Ident(_) | Token::LParen) => '*',
+                _ => break,
+            };
+            let rhs = self.unary()?;
+            value = match op {
+                '*' => value * rhs,
+                '/' if rhs == 0.0 => bail!("Division by zero"),
+                '/' => value / rhs,
+                _ if rhs == 0.0 => bail!("Modulo by zero"),
+                _ => value % rhs,
+            };
+        }
+        Ok(value)
+    }
+
+    fn unary(&mut self) -> Result<f64> {
+        match self.peek() {
+            Some(Token::Op('-')) => {
+                self.pos += 1;
+                self.nested(|p| p.unary()).map(|value| -value)
+            }
+            Some(Token::Op('+')) => {
+                self.pos += 1;
+                self.nested(|p| p.unary())
+            }
+            _ => self.power(),
+        }
+    }
+
+    fn power(&mut self) -> Result<f64> {
+        let base = self.atom()?;
+        if let Some(Token::Op('^')) = self.peek() {
+            self.pos += 1;
+            // Right associative, and -2^2 is -(2^2) while 2^-1 is 0.5
+            let exponent = self.nested(|p| p.unary())?;
+            return Ok(base.powf(exponent));
+        }
+        Ok(base)
+    }
+
+    fn atom(&mut self) -> Result<f64> {
+        match self.next() {
+            Some(Token::Number(value)) => Ok(value),
+            Some(Token::LParen) => {
+                let value = self.nested(|p| p.expression())?;
+                self.expect(Token::RParen)?;
+                Ok(value)
+            }
+            Some(Token::Ident(name)) => {
+                if self.peek() == Some(&Token::LParen) {
+                    self.pos += 1;
+                    let args = self.nested(|p| p.arguments())?;
+                    return call(&name, &args);
+                }
+                match name.as_str() {
+                    "pi" => Ok(std::f64::consts::PI),
+                    "e" => Ok(std::f64::consts::E),
+                    "tau" => Ok(std::f64::consts::TAU),
+                    _ => bail!("Unknown constant '{}'", name),
+                }
+            }
+            Some(token) => bail!("Unexpected {}", token),
+            None => bail!("The expression ends unexpectedly"),
+        }
+    }
+
+    /// Comma-separated arguments up to the closing parenthesis
+    fn arguments(&mut self) -> Result<Vec<f64>> {
+        let mut args = Vec::new();
+        if self.peek() == Some(&Token::RParen) {
+            self.pos += 1;
+            return Ok(args);
+        }
+        loop {
+            args.push(self.expression()?);
+            match self.next() {
+                Some(Token::Comma) => continue,
+                Some(Token::RParen) => return Ok(args),
+                Some(token) => bail!("Expected ',' or ')' but found {}", token),
+                None => bail!("Missing ')'"),
+            }
+        }
+    }
+}
+
+fn call(name: &str, args: &[f64]) -> Result<f64> {
+    let unary = |f: fn(f64) -> f64| match args {
+        [x] => Ok(f(*x)),
+        _ => bail!("{}() takes 1 argument, got {}", name, args.len()),
+    };
+    match name {
+        "sqrt" => match args {
+            [x] if *x < 0.0 => bail!("sqrt() of a negative number"),
+            _ => unary(f64::sqrt),
+        },
+        "abs" => unary(f64::abs),
+        "ln" => unary(f64::ln),
+        "log" | "log10" => unary(f64::log10),
+        "log2" => unary(f64::log2),
+        "exp" => unary(f64::exp),
+        "sin" => unary(f64::sin),
+        "cos" => unary(f64::cos),
+        "tan" => unary(f64::tan),
+        "asin" => unary(f64::asin),
+        "acos" => unary(f64::acos),
+        "atan" => unary(f64::atan),
+        "floor" => unary(f64::floor),
+        "ceil" => unary(f64::ceil),
+        "round" => match args {
+            [x] => Ok(x.round()),
+            [x, digits] => {
+                let scale = 10f64.powi(*digits as i32);
+                Ok((x * scale).round() / scale)
+            }
+            _ => bail!("round() takes 1 or 2 arguments, got {}", args.len()),
+        },
+        "min" | "max" if args.is_empty() => bail!("{}() needs at least 1 argument", name),
+        "min" => Ok(args.iter().copied().fold(f64::INFINITY, f64::min)),
+        "max" => Ok(args.iter().copied().fold(f64::NEG_INFINITY, f64::max)),
+        "pow" => match args {
+            [base, exponent] => Ok(base.powf(*exponent)),
+            _ => bail!("pow() takes 2 arguments, got {}", args.len()),
+        },
+        _ => bail!("Unknown function '{}'", name),
+    }
+}
+
+pub fn get_calculator_tool_definitions() -> Vec<ToolDefinition> {
+    vec![ToolDefinition {
+        name: "calculate".to_string(),
+        description: "Evaluate an arithmetic expression exactly instead of computing it mentally. Supports + - * / % ^, parentheses, pi, e, and sqrt, abs, ln, log, log2, exp, sin, cos, tan, asin, acos, atan, floor, ceil, round(x[, digits]), min, max, pow.".to_string(),
+        parameters: json!({
+            "type": "object",
+            "properties": {
+                "expression": {
+                    "type": "string",
+                    "description": "Expression to evaluate, e.g. \"(1200 * 0.15) / 12\" or \"sqrt(2) ^ 2\""
+                }
+            },
+            "required": ["expression"]
+        }),
+    }]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn eval(expression: &str) -> String {
+        calculate(CalculateParams {
+            expression: expression.to_string(),
+        })
+        .unwrap()
+    }
+
+    #[test]
+    fn test_precedence_and_associativity() {
+        assert_eq!(eval("1 + 2 * 3"), "7");
+        assert_eq!(eval("(1 + 2) * 3"), "9");
+        assert_eq!(eval("10 - 4 - 3"), "3");
+        assert_eq!(eval("2 ^ 3 ^ 2"), "512");
+        assert_eq!(eval("2 ** 10"), "1024");
+        assert_eq!(eval("-2 ^ 2"), "-4");
+        assert_eq!(eval("2 ^ -1"), "0.5");
+        assert_eq!(eval("17 % 5"), "2");
+        assert_eq!(eval("--3"), "3");
+        assert_eq!(eval("7 / 2"), "3.5");
+        assert_eq!(eval("3(4 + 1)"), "15");
+        assert_eq!(eval("1_000 × 3 ÷ 4"), "750");
+    }
+
+    #[test]
+    fn test_numbers_functions_and_constants() {
+        assert_eq!(eval("0.1 + 0.2"), "0.3");
+        assert_eq!(eval("1.5e3 + 2E-1"), "1500.2");
+        assert_eq!(eval("sqrt(16) + abs(-3)"), "7");
+        assert_eq!(eval("round(pi, 4)"), "3.1416");
+        assert_eq!(eval("2pi / tau"), "1");
+        assert_eq!(eval("ln(e) + log(1000) + log2(8)"), "7");
+        assert_eq!(eval("max(3, 9, 4) - min(2, 8)"), "7");
+        assert_eq!(eval("round(cos(0) * 100)"), "100");
+        assert_eq!(eval("1 / 3"), "0.333333333333");
+        assert_eq!(eval("1e-20"), "1e-20");
+    }
+
+    #[test]
+    fn test_invalid_expressions() {
+        for expression in [
+            "",
+            "1 +",
+            "(1 + 2",
+            "1 + 2)",
+            "1 / 0",
+            "5 % 0",
+            "sqrt(-1)",
+            "10 ^ 400",
+            "foo(1)",
+            "x + 1",
+            "sqrt(1, 2)",
+            "rm -rf /",
+            "1; 2",
+        ] {
+            assert!(
+                evaluate(expression).is_err(),
+                "{:?} should fail",
+                expression
+            );
+        }
+        assert!(evaluate(&"(".repeat(100)).is_err());
+        assert!(evaluate(&"1+".repeat(600)).is_err());
+    }
+}
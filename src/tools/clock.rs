@@ -0,0 +1,91 @@
+//! The `get_current_time` tool
+//!
+//! Lets the model look up the date and time instead of guessing it. Times are
+//! given in `locale.timezone` unless the model asks for another timezone.
+
+use crate::core::locale::{local_time, timezone_for, validate_timezone};
+use crate::llm::ToolDefinition;
+use anyhow::Result;
+use chrono::{DateTime, Utc};
+use serde::Deserialize;
+use serde_json::json;
+
+/// Parameters for get_current_time
+#[derive(Debug, Default, Deserialize)]
+pub struct GetCurrentTimeParams {
+    /// IANA timezone overriding the configured one
+    #[serde(default)]
+    pub timezone: Option<String>,
+}
+
+/// Run the `get_current_time` tool
+pub fn get_current_time(params: GetCurrentTimeParams) -> Result<String> {
+    let timezone = params
+        .timezone
+        .filter(|tz| !tz.trim().is_empty())
+        .map(|tz| tz.trim().to_string())
+        .or_else(|| timezone_for(None));
+    if let Some(timezone) = &timezone {
+        validate_timezone(timezone)?;
+    }
+    Ok(format_time(Utc::now(), timezone.as_deref()))
+}
+
+/// ISO 8601 time with the weekday and timezone, e.g.
+/// `2025-01-03T15:04:05-05:00 (Friday, America/New_York)`
+pub fn format_time(now: DateTime<Utc>, timezone: Option<&str>) -> String {
+    let local = local_time(now, timezone);
+    format!(
+        "{} ({}, {})",
+        local.format("%Y-%m-%dT%H:%M:%S%:z"),
+        local.format("%A"),
+        timezone.unwrap_or("server local time")
+    )
+}
+
+pub fn get_clock_tool_definitions() -> Vec<ToolDefinition> {
+    vec![ToolDefinition {
+        name: "get_current_time".to_string(),
+        description: "Get the current date and time (ISO 8601, with weekday). Use this instead of guessing today's date or the time.".to_string(),
+        parameters: json!({
+            "type": "object",
+            "properties": {
+                "timezone": {
+                    "type": "string",
+                    "description": "IANA timezone such as \"Europe/Berlin\" (default: the configured timezone)"
+                }
+            }
+        }),
+    }]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    #[test]
+    fn test_format_time_in_timezone() {
+        let now = Utc.with_ymd_and_hms(2025, 1, 3, 20, 4, 5).unwrap();
+        assert_eq!(
+            format_time(now, Some("America/New_York")),
+            "2025-01-03T15:04:05-05:00 (Friday, America/New_York)"
+        );
+        assert_eq!(
+            format_time(now, Some("Asia/Kolkata")),
+            "2025-01-04T01:34:05+05:30 (Saturday, Asia/Kolkata)"
+        );
+        assert_eq!(
+            format_time(now, Some("UTC")),
+            "2025-01-03T20:04:05+00:00 (Friday, UTC)"
+        );
+    }
+
+    #[test]
+    fn test_unknown_timezone_is_rejected() {
+        let params = GetCurrentTimeParams {
+            timezone: Some("Mars/Olympus_Mons".to_string()),
+        };
+        assert!(get_current_time(params).is_err());
+    }
+}
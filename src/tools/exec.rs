@@ -1,7 +1,12 @@
-use crate::sandbox::SandboxManager;
-use anyhow::Result;
+use crate::sandbox::{
+    execute_on_host, stage_files_on_host, ExecResult, SandboxManager, SandboxMode, StagedFile,
+};
+use anyhow::{anyhow, Result};
 use serde::{Deserialize, Serialize};
 use serde_json::json;
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::Duration;
 
 /// Parameters for the exec tool
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -16,6 +21,11 @@ pub struct ExecParams {
     /// Working directory (optional)
     #[serde(default)]
     pub working_dir: Option<String>,
+
+    /// Files to write into the session workspace before the command runs,
+    /// keyed by path relative to it
+    #[serde(default)]
+    pub files: HashMap<String, String>,
 }
 
 /// Parameters for the bash tool
@@ -25,23 +35,182 @@ pub struct BashParams {
     pub script: String,
 }
 
+/// The user approved a call to run in the sandbox, but no sandbox is running
+#[derive(Debug, thiserror::Error)]
+#[error("Approved to run in the sandbox, but the sandbox is not available")]
+pub struct SandboxUnavailable;
+
+/// A command (or skill) ran to completion but exited with a non-zero code.
+///
+/// `output` is the command's output as it would have been returned on
+/// success; the exit code travels separately instead of being appended to it.
+#[derive(Debug, thiserror::Error)]
+#[error("Command exited with code {exit_code}\n{output}")]
+pub struct CommandFailed {
+    pub exit_code: i64,
+    pub output: String,
+}
+
+/// Succeed with `output` on a zero exit code, fail with `CommandFailed` otherwise
+pub(crate) fn command_result(exit_code: i64, output: String) -> Result<String> {
+    if exit_code == 0 {
+        Ok(output)
+    } else {
+        Err(CommandFailed { exit_code, output }.into())
+    }
+}
+
+/// What exec and bash calls are placed against: the sandbox mode and the
+/// sandbox manager, if one is running
+#[derive(Clone)]
+pub struct ExecSandbox {
+    pub mode: SandboxMode,
+    pub manager: Option<Arc<SandboxManager>>,
+}
+
+impl ExecSandbox {
+    /// The configured sandbox mode and manager
+    pub fn current() -> Self {
+        Self {
+            mode: crate::get_sandbox_mode(),
+            manager: crate::get_sandbox_manager(),
+        }
+    }
+
+    /// Where a call runs: in the sandbox when the user approved it there,
+    /// else as `ExecTarget::for_choice` resolves it for the mode
+    pub fn target(&self, use_sandbox: Option<bool>) -> Result<ExecTarget> {
+        ExecTarget::for_choice(self.manager.clone(), &self.mode, use_sandbox)
+    }
+
+    /// Whether an approved call runs in the sandbox: when the user chose the
+    /// sandbox, or chose the host but the mode sandboxes the session anyway.
+    /// `None` when the user made no choice.
+    pub fn approved_in_sandbox(
+        &self,
+        use_sandbox: Option<bool>,
+        is_main_session: bool,
+    ) -> Option<bool> {
+        use_sandbox.map(|in_sandbox| in_sandbox || self.mode.sandboxes(is_main_session))
+    }
+}
+
+/// Where exec and bash commands run
+pub enum ExecTarget {
+    /// Through the sandbox manager, which applies the sandbox policy
+    Sandbox(Arc<SandboxManager>),
+    /// In a sandbox container regardless of the sandbox policy
+    Container(Arc<SandboxManager>),
+    /// Directly on the host, with sandboxing turned off
+    Host,
+}
+
+impl ExecTarget {
+    /// Pick the target for the configured sandbox mode. Without a sandbox
+    /// manager commands only run on the host when sandboxing is off; any other
+    /// mode means the manager failed to start and execution is refused.
+    pub fn resolve(sandbox: Option<Arc<SandboxManager>>, mode: &SandboxMode) -> Result<Self> {
+        match (sandbox, mode) {
+            (Some(sandbox), _) => Ok(ExecTarget::Sandbox(sandbox)),
+            (None, SandboxMode::Off) => Ok(ExecTarget::Host),
+            (None, mode) => Err(anyhow!(
+                "Sandbox manager not initialized (sandbox mode {:?} requires it)",
+                mode
+            )),
+        }
+    }
+
+    /// Honour the user's approval choice: `Some(true)` runs in the sandbox.
+    /// Approving a call never takes it out of the sandbox, so `Some(false)`
+    /// and `None` follow `resolve`: the host only when the sandbox mode would
+    /// run the call there anyway.
+    pub fn for_choice(
+        sandbox: Option<Arc<SandboxManager>>,
+        mode: &SandboxMode,
+        use_sandbox: Option<bool>,
+    ) -> Result<Self> {
+        match use_sandbox {
+            Some(true) => sandbox
+                .map(ExecTarget::Container)
+                .ok_or_else(|| SandboxUnavailable.into()),
+            Some(false) | None => Self::resolve(sandbox, mode),
+        }
+    }
+
+    /// Run a command; in a container, one running `image` (`None`: the
+    /// sandbox's configured image)
+    pub(crate) async fn execute(
+        &self,
+        session_id: &str,
+        is_main_session: bool,
+        image: Option<&str>,
+        command: &[&str],
+        timeout: Duration,
+    ) -> Result<ExecResult> {
+        match self {
+            ExecTarget::Sandbox(sandbox) => {
+                sandbox
+                    .execute(session_id, is_main_session, image, command, timeout)
+                    .await
+            }
+            ExecTarget::Container(sandbox) => {
+                sandbox
+                    .execute_sandboxed(session_id, image, command, timeout)
+                    .await
+            }
+            ExecTarget::Host => execute_on_host(Some(session_id), command).await,
+        }
+    }
+
+    /// Stage files into the session workspace wherever `execute` will run
+    pub(crate) async fn stage_files(
+        &self,
+        session_id: &str,
+        is_main_session: bool,
+        files: &[StagedFile],
+    ) -> Result<()> {
+        match self {
+            ExecTarget::Sandbox(sandbox) => {
+                sandbox
+                    .stage_files(session_id, is_main_session, files)
+                    .await
+            }
+            ExecTarget::Container(sandbox) => {
+                sandbox.stage_files_sandboxed(session_id, files).await
+            }
+            ExecTarget::Host => stage_files_on_host(session_id, files),
+        }
+    }
+}
+
 /// Execute a command in the sandbox
 pub async fn exec_command(
-    sandbox: &SandboxManager,
+    target: &ExecTarget,
     session_id: &str,
     is_main_session: bool,
     params: ExecParams,
+    timeout: Duration,
 ) -> Result<String> {
     // Prepare command array
     let mut cmd = vec![params.command.clone()];
     cmd.extend(params.args.clone());
 
+    // Block dangerous commands before they reach the sandbox or host
+    super::command_guard::get_command_guard().check(session_id, &cmd.join(" "))?;
+
+    let files = crate::sandbox::staged_files(&params.files)?;
+    if !files.is_empty() {
+        target
+            .stage_files(session_id, is_main_session, &files)
+            .await?;
+    }
+
     // Convert to &str references
     let cmd_refs: Vec<&str> = cmd.iter().map(|s| s.as_str()).collect();
 
     // Execute with sandboxing
-    let result = sandbox
-        .execute(session_id, is_main_session, &cmd_refs)
+    let result = target
+        .execute(session_id, is_main_session, None, &cmd_refs, timeout)
         .await?;
 
     // Format output
@@ -63,20 +232,32 @@ pub async fn exec_command(
         }
     }
 
-    output.push_str(&format!("Exit code: {}", result.exit_code));
+    if output.is_empty() {
+        output.push_str("(command produced no output)");
+    }
 
-    Ok(output)
+    command_result(result.exit_code, output)
 }
 
 /// Execute a bash script in the sandbox
 pub async fn exec_bash(
-    sandbox: &SandboxManager,
+    target: &ExecTarget,
     session_id: &str,
     is_main_session: bool,
     params: BashParams,
+    timeout: Duration,
 ) -> Result<String> {
-    let result = sandbox
-        .execute(session_id, is_main_session, &["bash", "-c", &params.script])
+    // Block dangerous commands before they reach the sandbox or host
+    super::command_guard::get_command_guard().check(session_id, &params.script)?;
+
+    let result = target
+        .execute(
+            session_id,
+            is_main_session,
+            None,
+            &["bash", "-c", &params.script],
+            timeout,
+        )
         .await?;
 
     let mut output = String::new();
@@ -98,11 +279,7 @@ pub async fn exec_bash(
         }
     }
 
-    if result.exit_code != 0 {
-        output.push_str(&format!("(exit code: {})", result.exit_code));
-    }
-
-    Ok(output)
+    command_result(result.exit_code, output)
 }
 
 /// Get tool definitions for code execution tools
@@ -129,6 +306,11 @@ pub fn get_exec_tool_definitions() -> Vec<serde_json::Value> {
                         "working_dir": {
                             "type": "string",
                             "description": "Working directory (optional)"
+                        },
+                        "files": {
+                            "type": "object",
+                            "additionalProperties": { "type": "string" },
+                            "description": "Files to create before running, as {relative path: content}. They are written to the session workspace, whose path is in $RUSTYCLAW_SESSION_WORKSPACE"
                         }
                     },
                     "required": ["command"]
@@ -165,6 +347,7 @@ mod tests {
             command: "echo".to_string(),
             args: vec!["hello".to_string(), "world".to_string()],
             working_dir: None,
+            files: HashMap::new(),
         };
 
         let json = serde_json::to_string(&params).unwrap();
@@ -197,4 +380,123 @@ mod tests {
         // Check bash tool
         assert_eq!(defs[1]["function"]["name"], "bash");
     }
+
+    #[tokio::test]
+    async fn test_mode_off_runs_on_host() {
+        let target = ExecTarget::resolve(None, &SandboxMode::Off).unwrap();
+        assert!(matches!(target, ExecTarget::Host));
+
+        let timeout = Duration::from_secs(10);
+        let params = ExecParams {
+            command: "echo".to_string(),
+            args: vec!["from host".to_string()],
+            working_dir: None,
+            files: HashMap::new(),
+        };
+        let output = exec_command(&target, "s1", true, params, timeout)
+            .await
+            .unwrap();
+        assert!(output.starts_with("Output:\nfrom host\n"), "{}", output);
+        assert!(!output.contains("Exit code"), "{}", output);
+
+        let params = BashParams {
+            script: "echo $((6 * 7)); exit 3".to_string(),
+        };
+        let err = exec_bash(&target, "s1", false, params, timeout)
+            .await
+            .unwrap_err();
+        let failed = err.downcast_ref::<CommandFailed>().unwrap();
+        assert_eq!(failed.exit_code, 3);
+        assert!(failed.output.starts_with("42\n"), "{}", failed.output);
+        assert!(!failed.output.contains("exit code"), "{}", failed.output);
+    }
+
+    #[tokio::test]
+    async fn test_exec_reads_staged_files() {
+        let session_id = format!("staging-test-{}", uuid::Uuid::new_v4());
+        let params = ExecParams {
+            command: "sh".to_string(),
+            args: vec![
+                "-c".to_string(),
+                "cat \"$RUSTYCLAW_SESSION_WORKSPACE/data/input.csv\"".to_string(),
+            ],
+            working_dir: None,
+            files: HashMap::from([("data/input.csv".to_string(), "id,value\n1,42".to_string())]),
+        };
+        let output = exec_command(
+            &ExecTarget::Host,
+            &session_id,
+            true,
+            params,
+            Duration::from_secs(10),
+        )
+        .await
+        .unwrap();
+        assert!(output.contains("1,42"), "{}", output);
+
+        // Paths outside the session workspace are refused before anything runs
+        let params = ExecParams {
+            command: "true".to_string(),
+            args: vec![],
+            working_dir: None,
+            files: HashMap::from([("../escape.txt".to_string(), "x".to_string())]),
+        };
+        let err = exec_command(
+            &ExecTarget::Host,
+            &session_id,
+            true,
+            params,
+            Duration::from_secs(10),
+        )
+        .await
+        .unwrap_err();
+        assert!(err.to_string().contains("Invalid workspace path"));
+
+        crate::sandbox::remove_session_workspace(&session_id).unwrap();
+    }
+
+    #[test]
+    fn test_sandboxed_mode_without_manager_errors() {
+        for mode in [SandboxMode::NonMain, SandboxMode::All] {
+            let err = ExecTarget::resolve(None, &mode).err().unwrap();
+            assert!(err.to_string().contains("Sandbox manager not initialized"));
+        }
+    }
+
+    #[test]
+    fn test_approving_for_the_host_keeps_the_sandbox_mode() {
+        // Off runs on the host with or without the approval
+        let target = ExecTarget::for_choice(None, &SandboxMode::Off, Some(false)).unwrap();
+        assert!(matches!(target, ExecTarget::Host));
+
+        // Sandboxed modes never fall through to the host
+        for mode in [SandboxMode::NonMain, SandboxMode::All] {
+            let err = ExecTarget::for_choice(None, &mode, Some(false))
+                .err()
+                .unwrap();
+            assert!(err.to_string().contains("Sandbox manager not initialized"));
+        }
+
+        // and report the approved call as sandboxed
+        let sandbox = |mode| ExecSandbox {
+            mode,
+            manager: None,
+        };
+        assert_eq!(
+            sandbox(SandboxMode::Off).approved_in_sandbox(Some(false), false),
+            Some(false)
+        );
+        assert_eq!(
+            sandbox(SandboxMode::NonMain).approved_in_sandbox(Some(false), false),
+            Some(true)
+        );
+        assert_eq!(
+            sandbox(SandboxMode::Off).approved_in_sandbox(Some(true), true),
+            Some(true)
+        );
+        assert_eq!(
+            sandbox(SandboxMode::All).approved_in_sandbox(None, false),
+            None
+        );
+    }
 }
@@ -3,7 +3,7 @@ use std::time::Duration;
 /// Result of a tool execution including output, errors, and timing
 #[derive(Debug, Clone)]
 pub struct ToolExecutionResult {
-    /// Status: "running", "done", "error"
+    /// Status: "running", "done", "error", "timeout"
     pub status: String,
     /// Standard output from tool
     pub output: Option<String>,
@@ -15,6 +15,13 @@ pub struct ToolExecutionResult {
     pub attempt: usize,
     /// Maximum attempts allowed
     pub max_attempts: usize,
+    /// Where a call the user approved runs: `Some(true)` in the sandbox,
+    /// also when approved for the host but the sandbox mode requires it;
+    /// `Some(false)` on the host; `None` when no choice was made
+    pub sandboxed: Option<bool>,
+    /// Exit code of a command tool (exec, bash or a skill) that ran to
+    /// completion; a non-zero code makes the result an error
+    pub exit_code: Option<i64>,
 }
 
 impl ToolExecutionResult {
@@ -32,6 +39,8 @@ impl ToolExecutionResult {
             execution_time_ms: Some(execution_time_ms),
             attempt,
             max_attempts,
+            sandboxed: None,
+            exit_code: None,
         }
     }
 
@@ -49,6 +58,21 @@ impl ToolExecutionResult {
             execution_time_ms: Some(execution_time_ms),
             attempt,
             max_attempts,
+            sandboxed: None,
+            exit_code: None,
+        }
+    }
+
+    /// Create a result for a tool that exceeded its timeout
+    pub fn timed_out(
+        error: String,
+        execution_time_ms: u64,
+        attempt: usize,
+        max_attempts: usize,
+    ) -> Self {
+        Self {
+            status: "timeout".to_string(),
+            ..Self::error(error, execution_time_ms, attempt, max_attempts)
         }
     }
 
@@ -61,9 +85,38 @@ impl ToolExecutionResult {
             execution_time_ms: None,
             attempt,
             max_attempts,
+            sandboxed: None,
+            exit_code: None,
         }
     }
 
+    /// Create an error result for a command that exited with a non-zero
+    /// code, keeping what it printed as the output
+    pub fn command_failed(
+        failed: &super::exec::CommandFailed,
+        execution_time_ms: u64,
+        attempt: usize,
+        max_attempts: usize,
+    ) -> Self {
+        Self {
+            output: Some(failed.output.clone()),
+            exit_code: Some(failed.exit_code),
+            ..Self::error(failed.to_string(), execution_time_ms, attempt, max_attempts)
+        }
+    }
+
+    /// Record the exit code of a command tool
+    pub fn with_exit_code(mut self, exit_code: Option<i64>) -> Self {
+        self.exit_code = exit_code;
+        self
+    }
+
+    /// Record where the approved call runs (see `sandboxed`)
+    pub fn with_sandboxed(mut self, sandboxed: Option<bool>) -> Self {
+        self.sandboxed = sandboxed;
+        self
+    }
+
     /// Check if this is a successful result
     pub fn is_success(&self) -> bool {
         self.status == "done" && self.error.is_none()
@@ -74,6 +127,11 @@ impl ToolExecutionResult {
         self.status == "error" || self.error.is_some()
     }
 
+    /// Check if the tool exceeded its timeout
+    pub fn is_timeout(&self) -> bool {
+        self.status == "timeout"
+    }
+
     /// Check if more retries are available
     pub fn can_retry(&self) -> bool {
         self.is_error() && self.attempt < self.max_attempts
@@ -1,23 +1,270 @@
 use anyhow::{anyhow, Context, Result};
-use std::time::Instant;
+use once_cell::sync::OnceCell;
+use std::collections::HashMap;
+use std::future::Future;
+use std::time::{Duration, Instant};
 use tracing::{debug, info, warn};
 
+use super::exec::{CommandFailed, ExecSandbox};
 use super::execution_result::{ToolExecutionResult, ToolRetryPolicy};
 use super::whatsapp;
+use crate::core::approval::APPROVAL_TIMEOUT_SECS;
 use crate::core::ApprovalManager;
 
+/// Timeout for tools that do not declare their own
+static DEFAULT_TOOL_TIMEOUT: OnceCell<Duration> = OnceCell::new();
+
+/// Fallback when no default timeout has been configured
+const FALLBACK_TOOL_TIMEOUT_SECS: u64 = 120;
+
+/// A tool did not finish within its timeout
+#[derive(Debug, thiserror::Error)]
+#[error("Tool '{tool}' timed out after {secs}s")]
+pub struct ToolTimeout {
+    pub tool: String,
+    pub secs: u64,
+}
+
+/// Tool arguments that are not valid JSON or do not match the tool's
+/// parameters. The message lists every problem so the model can fix its call.
+#[derive(Debug, thiserror::Error)]
+#[error("Invalid arguments for tool '{tool}': {}", errors.join("; "))]
+pub struct InvalidToolArguments {
+    pub tool: String,
+    pub errors: Vec<String>,
+}
+
+/// Byte limits on tool call arguments
+static ARGUMENT_LIMITS: OnceCell<ArgumentLimits> = OnceCell::new();
+
+/// Tool call arguments over the tool's size limit
+#[derive(Debug, thiserror::Error)]
+#[error(
+    "Arguments for tool '{tool}' are {size} bytes, over its {limit} byte limit. \
+     Pass less data per call, e.g. split the input or refer to a file instead of inlining it"
+)]
+pub struct ToolArgumentsTooLarge {
+    pub tool: String,
+    pub size: usize,
+    pub limit: usize,
+}
+
+/// Largest arguments accepted per tool
+#[derive(Debug, Clone)]
+pub struct ArgumentLimits {
+    default: usize,
+    per_tool: HashMap<String, usize>,
+}
+
+impl ArgumentLimits {
+    pub fn from_config(tools: &crate::config::ToolsConfig) -> Self {
+        Self {
+            default: tools.max_argument_bytes,
+            per_tool: tools.argument_size_limits.clone(),
+        }
+    }
+
+    /// Reject arguments larger than the tool's limit
+    pub fn check(&self, tool: &str, arguments: &str) -> Result<(), ToolArgumentsTooLarge> {
+        let limit = self.per_tool.get(tool).copied().unwrap_or(self.default);
+        if arguments.len() > limit {
+            return Err(ToolArgumentsTooLarge {
+                tool: tool.to_string(),
+                size: arguments.len(),
+                limit,
+            });
+        }
+        Ok(())
+    }
+}
+
+/// Initialize the tool argument size limits from configuration
+pub fn init_argument_limits(tools: &crate::config::ToolsConfig) {
+    ARGUMENT_LIMITS.set(ArgumentLimits::from_config(tools)).ok();
+}
+
+/// Initialize the default tool timeout from configuration
+pub fn init_tool_timeout(default_secs: u64) {
+    DEFAULT_TOOL_TIMEOUT
+        .set(Duration::from_secs(default_secs.max(1)))
+        .ok();
+}
+
+/// Timeout for a tool: the skill's declared `timeout_secs`, else the default
+pub async fn tool_timeout(name: &str) -> Duration {
+    if let Some(skill) = super::skills::get_skill(name).await {
+        return Duration::from_secs(skill.manifest.timeout_secs);
+    }
+    // Waits on the user, with a timeout of its own
+    if name == "ask_user" {
+        return Duration::from_secs(crate::core::user_input::USER_INPUT_TIMEOUT_SECS + 1);
+    }
+
+    DEFAULT_TOOL_TIMEOUT
+        .get()
+        .copied()
+        .unwrap_or(Duration::from_secs(FALLBACK_TOOL_TIMEOUT_SECS))
+}
+
+/// Built-in tools that only read data
+const SIDE_EFFECT_FREE_TOOLS: &[&str] = &[
+    "web_fetch",
+    "web_search",
+    "get_current_time",
+    "calculate",
+    "search_docs",
+    "gateway_info",
+    "read_today_memory",
+    "list_whatsapp_accounts",
+    "list_whatsapp_groups",
+    "list_whatsapp_group_participants",
+    "verify_whatsapp_contacts",
+    "list_workspace",
+    "read_workspace_file",
+];
+
+/// Whether `tools.safe_mode` is on
+static SAFE_MODE: OnceCell<bool> = OnceCell::new();
+
+/// Whether `tools.json_repair.enabled` is on
+static JSON_REPAIR: OnceCell<bool> = OnceCell::new();
+
+/// Initialize repair of malformed arguments from configuration
+pub fn init_json_repair(enabled: bool) {
+    JSON_REPAIR.set(enabled).ok();
+}
+
+/// Messaging tools that stay available in safe mode, next to the read-only
+/// built-ins
+const SAFE_MODE_MESSAGING_TOOLS: &[&str] = &[
+    "ask_user",
+    "send_whatsapp",
+    "set_reminder",
+    "list_reminders",
+    "cancel_reminder",
+];
+
+/// A tool refused because the deployment runs in safe mode
+#[derive(Debug, thiserror::Error)]
+#[error(
+    "Tool '{0}' is disabled: this deployment runs in safe mode, which turns off \
+     code execution and file changes"
+)]
+pub struct SafeModeRefused(pub String);
+
+/// Initialize safe mode from configuration
+pub fn init_safe_mode(enabled: bool) {
+    SAFE_MODE.set(enabled).ok();
+}
+
+/// Whether safe mode is on
+pub fn safe_mode() -> bool {
+    SAFE_MODE.get().copied().unwrap_or(false)
+}
+
+/// Whether a tool stays available in safe mode: read-only built-ins and
+/// messaging. Exec, bash, skills, plugin tools and everything that writes
+/// files are refused.
+pub fn allowed_in_safe_mode(name: &str) -> bool {
+    SIDE_EFFECT_FREE_TOOLS.contains(&name) || SAFE_MODE_MESSAGING_TOOLS.contains(&name)
+}
+
+/// Refuse tools safe mode turns off. Checked before policies and approvals,
+/// so neither an `allow` policy nor elevated mode can override it.
+fn check_safe_mode(enabled: bool, name: &str) -> Result<(), SafeModeRefused> {
+    if enabled && !allowed_in_safe_mode(name) {
+        return Err(SafeModeRefused(name.to_string()));
+    }
+    Ok(())
+}
+
+/// Whether a tool only reads data, so calls to it may run concurrently:
+/// the read-only built-ins and skills declaring `read_only`
+pub async fn is_side_effect_free(name: &str) -> bool {
+    if SIDE_EFFECT_FREE_TOOLS.contains(&name) {
+        return true;
+    }
+    super::skills::get_skill(name)
+        .await
+        .is_some_and(|skill| skill.manifest.read_only)
+}
+
 /// Execute a tool by name with the given arguments
 pub async fn execute_tool(name: &str, arguments: &str) -> Result<String> {
-    execute_tool_with_context(name, arguments, None, false).await
+    execute_tool_with_context(name, arguments, None, None, false, None).await
 }
 
 /// Execute a tool with session context for policy and sandbox checks
+///
+/// `user_id` is the session owner, whose tool policy overrides apply.
+/// `use_sandbox` forces exec, bash and skills into the sandbox (`Some(true)`);
+/// `Some(false)` and `None` keep each tool's default, so an approval never
+/// moves a call out of the sandbox.
 pub async fn execute_tool_with_context(
     name: &str,
     arguments: &str,
     session_id: Option<&str>,
+    user_id: Option<&str>,
+    is_main_session: bool,
+    use_sandbox: Option<bool>,
+) -> Result<String> {
+    run_tool(
+        name,
+        arguments,
+        session_id,
+        user_id,
+        is_main_session,
+        use_sandbox,
+        &ExecSandbox::current(),
+        true,
+    )
+    .await
+}
+
+/// Fix almost-JSON from small models; what cannot be fixed is returned as
+/// is and rejected with the parse error by the argument validation
+fn repaired_arguments<'a>(name: &str, arguments: &'a str) -> std::borrow::Cow<'a, str> {
+    match super::json_repair::repair_arguments(arguments) {
+        Ok(std::borrow::Cow::Owned(repaired)) if JSON_REPAIR.get().copied().unwrap_or(true) => {
+            warn!("Repaired malformed arguments of tool {}", name);
+            std::borrow::Cow::Owned(repaired)
+        }
+        _ => std::borrow::Cow::Borrowed(arguments),
+    }
+}
+
+/// Run a tool with hooks and timeout; `check_policy` is false when the caller
+/// already resolved access (and possibly obtained approval) for this call
+#[tracing::instrument(
+    name = "tool",
+    skip_all,
+    fields(
+        tool = name,
+        session_id = session_id.unwrap_or_default(),
+        duration_ms = tracing::field::Empty,
+        success = tracing::field::Empty,
+    )
+)]
+async fn run_tool(
+    name: &str,
+    arguments: &str,
+    session_id: Option<&str>,
+    user_id: Option<&str>,
     is_main_session: bool,
+    use_sandbox: Option<bool>,
+    sandbox: &ExecSandbox,
+    check_policy: bool,
 ) -> Result<String> {
+    check_safe_mode(safe_mode(), name)?;
+
+    // Refuse oversized arguments before anything copies or logs them
+    match ARGUMENT_LIMITS.get() {
+        Some(limits) => limits.check(name, arguments)?,
+        None => ArgumentLimits::from_config(&Default::default()).check(name, arguments)?,
+    }
+
+    let arguments = &*repaired_arguments(name, arguments);
+
     info!("Executing tool: {} with arguments: {}", name, arguments);
 
     // Prepare tool context for hooks
@@ -32,7 +279,8 @@ pub async fn execute_tool_with_context(
         workspace_dir: None,
         agent_id: None,
         message_channel: None,
-        sandboxed: false, // Will be determined by the tool itself
+        // Otherwise determined by the tool itself
+        sandboxed: use_sandbox == Some(true),
         metadata,
     };
 
@@ -66,89 +314,337 @@ pub async fn execute_tool_with_context(
     let start_time = std::time::Instant::now();
 
     // Check tool policy if session_id is provided
-    if let Some(session_id) = session_id {
+    if let Some(session_id) = session_id.filter(|_| check_policy) {
         if let Some(policy) = crate::get_tool_policy_engine() {
             policy
-                .check_permission(session_id, name)
+                .check_permission(session_id, user_id, name, &effective_arguments)
                 .await
                 .context(format!("Tool policy check failed for tool: {}", name))?;
         }
     }
 
-    let result_content = match name {
+    // Enforce the tool's declared timeout; dropping the dispatch future on
+    // timeout cancels the underlying work (child processes are killed on drop)
+    let timeout = tool_timeout(name).await;
+    let result_content = run_with_timeout(
+        name,
+        timeout,
+        dispatch_tool(
+            name,
+            &effective_arguments,
+            session_id,
+            user_id,
+            is_main_session,
+            use_sandbox,
+            sandbox,
+            timeout,
+        ),
+    )
+    .await;
+
+    let duration_ms = start_time.elapsed().as_millis() as u64;
+    let span = tracing::Span::current();
+    span.record("duration_ms", duration_ms);
+    span.record("success", result_content.is_ok());
+
+    // Run AfterToolCall hooks
+    if let Some(registry) = crate::plugins::get_plugin_registry() {
+        let mut after_ctx = ctx.clone();
+
+        let mut details = output_validation_details(name, &result_content).await;
+        if is_command_tool(name).await {
+            if let Some(exit_code) = exit_code_details(&result_content) {
+                details.get_or_insert_with(HashMap::new).extend(exit_code);
+            }
+        }
+        let tool_result = match &result_content {
+            Ok(content) => crate::plugins::traits::ToolResult {
+                content: content.clone(),
+                details,
+                success: true,
+            },
+            Err(e) => crate::plugins::traits::ToolResult {
+                content: match e.downcast_ref::<CommandFailed>() {
+                    Some(failed) => failed.output.clone(),
+                    None => format!("Error: {}", e),
+                },
+                details,
+                success: false,
+            },
+        };
+
+        after_ctx
+            .metadata
+            .insert("tool_name".to_string(), serde_json::json!(name));
+        after_ctx.metadata.insert(
+            "parameters".to_string(),
+            serde_json::from_str(&effective_arguments).unwrap_or(serde_json::Value::Null),
+        );
+        after_ctx.metadata.insert(
+            "result".to_string(),
+            serde_json::to_value(tool_result).unwrap_or(serde_json::Value::Null),
+        );
+        after_ctx
+            .metadata
+            .insert("duration_ms".to_string(), serde_json::json!(duration_ms));
+
+        let _ = registry.hooks.run_after_tool_call(after_ctx).await;
+    }
+
+    result_content
+}
+
+/// Tool result details describing output schema validation, for skills that
+/// declare an `output_schema`
+async fn output_validation_details(
+    name: &str,
+    result: &Result<String>,
+) -> Option<HashMap<String, serde_json::Value>> {
+    let validation = match result {
+        Err(e) => {
+            let mismatch = e.downcast_ref::<super::output_schema::OutputSchemaMismatch>()?;
+            serde_json::json!({ "valid": false, "errors": mismatch.errors })
+        }
+        Ok(_) => {
+            super::skills::get_skill(name)
+                .await?
+                .manifest
+                .output_schema
+                .as_ref()?;
+            serde_json::json!({ "valid": true, "errors": [] })
+        }
+    };
+
+    Some(HashMap::from([(
+        "output_validation".to_string(),
+        validation,
+    )]))
+}
+
+/// Whether the tool runs a process whose exit code decides its success
+async fn is_command_tool(name: &str) -> bool {
+    matches!(name, "exec" | "bash") || super::skills::get_skill(name).await.is_some()
+}
+
+/// Tool result details carrying the exit code of a command that ran to
+/// completion; `None` when it never got to exit (timeouts, refusals, ...)
+pub(crate) fn exit_code_details(
+    result: &Result<String>,
+) -> Option<HashMap<String, serde_json::Value>> {
+    let exit_code = match result {
+        Ok(_) => 0,
+        Err(e) => e.downcast_ref::<CommandFailed>()?.exit_code,
+    };
+    Some(HashMap::from([(
+        "exit_code".to_string(),
+        serde_json::json!(exit_code),
+    )]))
+}
+
+/// Await a tool future, turning expiry of `timeout` into a `ToolTimeout` error
+async fn run_with_timeout<F>(name: &str, timeout: Duration, execution: F) -> Result<String>
+where
+    F: Future<Output = Result<String>>,
+{
+    match tokio::time::timeout(timeout, execution).await {
+        Ok(result) => result,
+        Err(_) => {
+            warn!("Tool {} timed out after {}s", name, timeout.as_secs());
+            Err(ToolTimeout {
+                tool: name.to_string(),
+                secs: timeout.as_secs(),
+            }
+            .into())
+        }
+    }
+}
+
+/// Arguments of a call, treating a blank string as no arguments
+fn arguments_or_empty(arguments: &str) -> &str {
+    if arguments.trim().is_empty() {
+        "{}"
+    } else {
+        arguments
+    }
+}
+
+/// Check arguments against the parameters schema of a built-in, skill or
+/// plugin tool before running it
+async fn validate_arguments(name: &str, arguments: &str) -> Result<(), InvalidToolArguments> {
+    let invalid = |errors| InvalidToolArguments {
+        tool: name.to_string(),
+        errors,
+    };
+
+    let Some(tool) = crate::core::available_tools()
+        .await
+        .into_iter()
+        .find(|tool| tool.name == name)
+    else {
+        return Ok(());
+    };
+    let value: serde_json::Value = serde_json::from_str(arguments_or_empty(arguments))
+        .map_err(|e| invalid(vec![format!("arguments are not valid JSON ({})", e)]))?;
+
+    let errors = super::output_schema::validate(&tool.parameters, &value);
+    if errors.is_empty() {
+        Ok(())
+    } else {
+        Err(invalid(errors))
+    }
+}
+
+/// Deserialize a tool's arguments, keeping serde's description of what is
+/// wrong (missing field, expected type)
+fn parse_arguments<T: serde::de::DeserializeOwned>(
+    name: &str,
+    arguments: &str,
+) -> Result<T, InvalidToolArguments> {
+    serde_json::from_str(arguments_or_empty(arguments)).map_err(|e| InvalidToolArguments {
+        tool: name.to_string(),
+        errors: vec![e.to_string()],
+    })
+}
+
+/// Run the implementation of a tool by name
+async fn dispatch_tool(
+    name: &str,
+    effective_arguments: &str,
+    session_id: Option<&str>,
+    user_id: Option<&str>,
+    is_main_session: bool,
+    use_sandbox: Option<bool>,
+    sandbox: &ExecSandbox,
+    timeout: Duration,
+) -> Result<String> {
+    validate_arguments(name, effective_arguments).await?;
+
+    match name {
         "exec" => {
-            let params: super::exec::ExecParams = serde_json::from_str(&effective_arguments)
-                .context("Failed to parse exec parameters")?;
+            let params: super::exec::ExecParams = parse_arguments(name, effective_arguments)?;
 
             if let Some(session_id) = session_id {
-                if let Some(sandbox) = crate::get_sandbox_manager() {
-                    super::exec::exec_command(&sandbox, session_id, is_main_session, params).await
-                } else {
-                    Err(anyhow!("Sandbox manager not initialized"))
-                }
+                let target = sandbox.target(use_sandbox)?;
+                super::exec::exec_command(&target, session_id, is_main_session, params, timeout)
+                    .await
             } else {
                 Err(anyhow!("exec tool requires session context"))
             }
         }
         "bash" => {
-            let params: super::exec::BashParams = serde_json::from_str(&effective_arguments)
-                .context("Failed to parse bash parameters")?;
+            let params: super::exec::BashParams = parse_arguments(name, effective_arguments)?;
 
             if let Some(session_id) = session_id {
-                if let Some(sandbox) = crate::get_sandbox_manager() {
-                    super::exec::exec_bash(&sandbox, session_id, is_main_session, params).await
-                } else {
-                    Err(anyhow!("Sandbox manager not initialized"))
-                }
+                let target = sandbox.target(use_sandbox)?;
+                super::exec::exec_bash(&target, session_id, is_main_session, params, timeout).await
             } else {
                 Err(anyhow!("bash tool requires session context"))
             }
         }
         "send_whatsapp" => {
-            let params: whatsapp::SendWhatsAppParams =
-                serde_json::from_str(&effective_arguments)
-                    .context("Failed to parse send_whatsapp parameters")?;
-            whatsapp::send_whatsapp(params).await
+            let params: whatsapp::SendWhatsAppParams = parse_arguments(name, effective_arguments)?;
+            whatsapp::send_whatsapp(session_id, params).await
         }
         "list_whatsapp_groups" => {
             let _params: whatsapp::ListWhatsAppGroupsParams =
-                serde_json::from_str(&effective_arguments)
-                    .context("Failed to parse list_whatsapp_groups parameters")?;
+                parse_arguments(name, effective_arguments)?;
             whatsapp::list_whatsapp_groups(_params).await
         }
+        "list_whatsapp_group_participants" => {
+            let params: whatsapp::ListWhatsAppGroupParticipantsParams =
+                parse_arguments(name, effective_arguments)?;
+            whatsapp::list_whatsapp_group_participants(params).await
+        }
+        "add_whatsapp_participant" => {
+            let params: whatsapp::ManageWhatsAppParticipantParams =
+                parse_arguments(name, effective_arguments)?;
+            whatsapp::add_whatsapp_participant(params).await
+        }
+        "remove_whatsapp_participant" => {
+            let params: whatsapp::ManageWhatsAppParticipantParams =
+                parse_arguments(name, effective_arguments)?;
+            whatsapp::remove_whatsapp_participant(params).await
+        }
+        "verify_whatsapp_contacts" => {
+            let params: whatsapp::VerifyWhatsAppContactsParams =
+                parse_arguments(name, effective_arguments)?;
+            whatsapp::verify_whatsapp_contacts(params).await
+        }
         "list_whatsapp_accounts" => {
             let _params: whatsapp::ListWhatsAppAccountsParams =
-                serde_json::from_str(&effective_arguments)
-                    .context("Failed to parse list_whatsapp_accounts parameters")?;
+                parse_arguments(name, effective_arguments)?;
             whatsapp::list_whatsapp_accounts(_params).await
         }
         "create_tool" => {
             // Parse the create_tool request
-            let req: super::creator::CreateToolRequest = serde_json::from_str(&effective_arguments)
-                .context("Failed to parse create_tool parameters")?;
+            let req: super::creator::CreateToolRequest =
+                parse_arguments(name, effective_arguments)?;
 
             // Delegate to the shared tool creation handler
             super::creator::handle_create_tool(req).await
         }
+        "scaffold_skill" => {
+            let req: super::creator::ScaffoldSkillRequest =
+                parse_arguments(name, effective_arguments)?;
+            super::creator::handle_scaffold_skill(req).await
+        }
         "delete_tool" => {
             #[derive(serde::Deserialize)]
             struct DeleteParams {
                 name: String,
             }
-            let params: DeleteParams = serde_json::from_str(&effective_arguments)
-                .context("Failed to parse delete_tool parameters")?;
+            let params: DeleteParams = parse_arguments(name, effective_arguments)?;
             super::creator::handle_delete_tool(params.name).await
         }
         "web_fetch" => {
-            let params: super::web::WebFetchParams = serde_json::from_str(&effective_arguments)
-                .context("Failed to parse web_fetch parameters")?;
+            let params: super::web::WebFetchParams = parse_arguments(name, effective_arguments)?;
             super::web::web_fetch(params).await
         }
         "web_search" => {
-            let params: super::web::WebSearchParams = serde_json::from_str(&effective_arguments)
-                .context("Failed to parse web_search parameters")?;
+            let params: super::web::WebSearchParams = parse_arguments(name, effective_arguments)?;
             super::web::web_search(params).await
         }
+        "get_current_time" => {
+            let params: super::clock::GetCurrentTimeParams =
+                parse_arguments(name, effective_arguments)?;
+            super::clock::get_current_time(params)
+        }
+        "calculate" => {
+            let params: super::calculator::CalculateParams =
+                parse_arguments(name, effective_arguments)?;
+            super::calculator::calculate(params)
+        }
+        "gateway_info" => super::gateway_info::gateway_info().await,
+        "ask_user" => {
+            let params: super::ask_user::AskUserParams =
+                parse_arguments(name, effective_arguments)?;
+            super::ask_user::ask_user(session_id, params).await
+        }
+        "list_workspace" => {
+            let params: super::workspace_files::ListWorkspaceParams =
+                parse_arguments(name, effective_arguments)?;
+            super::workspace_files::list_workspace(session_id, params)
+        }
+        "read_workspace_file" => {
+            let params: super::workspace_files::ReadWorkspaceFileParams =
+                parse_arguments(name, effective_arguments)?;
+            super::workspace_files::read_workspace_file(session_id, params)
+        }
+        "search_docs" => {
+            let params: super::rag::SearchDocsParams = parse_arguments(name, effective_arguments)?;
+            super::rag::search_docs(params).await
+        }
+        "set_reminder" => {
+            let params: super::reminders::SetReminderParams =
+                parse_arguments(name, effective_arguments)?;
+            super::reminders::set_reminder(session_id, params).await
+        }
+        "list_reminders" => super::reminders::list_reminders(session_id).await,
+        "cancel_reminder" => {
+            let params: super::reminders::CancelReminderParams =
+                parse_arguments(name, effective_arguments)?;
+            super::reminders::cancel_reminder(session_id, params).await
+        }
         "append_memory" | "read_today_memory" => {
             // Construct workspace from default path or context
             // For now using default path logic duplicated from default_workspace_path
@@ -157,7 +653,7 @@ pub async fn execute_tool_with_context(
             let workspace = crate::config::workspace::Workspace::new(workspace_path);
 
             let args_json: serde_json::Value =
-                serde_json::from_str(&effective_arguments).unwrap_or(serde_json::Value::Null);
+                serde_json::from_str(effective_arguments).unwrap_or(serde_json::Value::Null);
 
             super::memory::execute_memory_tool(name, &args_json, &workspace)
                 .await
@@ -166,11 +662,18 @@ pub async fn execute_tool_with_context(
         _ => {
             // Try to find in skills registry
             if super::skills::get_skill(name).await.is_some() {
-                super::skills::execute_skill(name, &effective_arguments).await
+                super::skills::execute_skill(
+                    name,
+                    effective_arguments,
+                    session_id,
+                    user_id,
+                    use_sandbox,
+                )
+                .await
             } else if let Some(registry) = crate::plugins::get_plugin_registry() {
                 // Try to find in plugin registry
                 if let Ok(Some(tool)) = registry.tools.get_tool(name) {
-                    (tool.execute)(effective_arguments.clone())
+                    (tool.execute)(effective_arguments.to_string())
                         .await
                         .map(|result| result.content)
                 } else {
@@ -181,46 +684,7 @@ pub async fn execute_tool_with_context(
                 Err(anyhow!("Unknown tool: {}", name))
             }
         }
-    };
-
-    let duration_ms = start_time.elapsed().as_millis() as u64;
-
-    // Run AfterToolCall hooks
-    if let Some(registry) = crate::plugins::get_plugin_registry() {
-        let mut after_ctx = ctx.clone();
-
-        let tool_result = match &result_content {
-            Ok(content) => crate::plugins::traits::ToolResult {
-                content: content.clone(),
-                details: None,
-                success: true,
-            },
-            Err(e) => crate::plugins::traits::ToolResult {
-                content: format!("Error: {}", e),
-                details: None,
-                success: false,
-            },
-        };
-
-        after_ctx
-            .metadata
-            .insert("tool_name".to_string(), serde_json::json!(name));
-        after_ctx.metadata.insert(
-            "parameters".to_string(),
-            serde_json::from_str(&effective_arguments).unwrap_or(serde_json::Value::Null),
-        );
-        after_ctx.metadata.insert(
-            "result".to_string(),
-            serde_json::to_value(tool_result).unwrap_or(serde_json::Value::Null),
-        );
-        after_ctx
-            .metadata
-            .insert("duration_ms".to_string(), serde_json::json!(duration_ms));
-
-        let _ = registry.hooks.run_after_tool_call(after_ctx).await;
     }
-
-    result_content
 }
 
 /// Execute a tool with approval flow and retry mechanism
@@ -235,20 +699,59 @@ pub async fn execute_tool_with_approval(
     tool_name: &str,
     arguments: &str,
     session_id: &str,
+    user_id: Option<&str>,
+    approval_manager: &ApprovalManager,
+) -> ToolExecutionResult {
+    execute_with_policy(
+        crate::get_tool_policy_engine().as_deref(),
+        tool_name,
+        arguments,
+        session_id,
+        user_id,
+        approval_manager,
+        &ExecSandbox::current(),
+    )
+    .await
+}
+
+async fn execute_with_policy(
+    policy: Option<&super::policy::ToolPolicyEngine>,
+    tool_name: &str,
+    arguments: &str,
+    session_id: &str,
+    user_id: Option<&str>,
     approval_manager: &ApprovalManager,
-    sandbox_available: bool,
+    sandbox: &ExecSandbox,
 ) -> ToolExecutionResult {
+    let sandbox_available = sandbox.manager.is_some();
     let mut attempt = 1;
     let max_attempts = 10;
     let retry_policy = ToolRetryPolicy::default();
+    // Where the user approved the call to run, kept for retries
+    let mut use_sandbox = None;
+
+    // Never ask for approval of a call safe mode would refuse anyway
+    if let Err(e) = check_safe_mode(safe_mode(), tool_name) {
+        return ToolExecutionResult::error(e.to_string(), 0, attempt, max_attempts);
+    }
+
+    // The policy decides on the arguments that will run, not on what the
+    // model sent before repair
+    let arguments = &*repaired_arguments(tool_name, arguments);
 
     loop {
         // First attempt: check policy and request approval if needed
         if attempt == 1 {
             // Get the tool policy decision
-            if let Some(policy) = crate::get_tool_policy_engine() {
+            if let Some(policy) = policy {
                 let decision = policy
-                    .get_access_decision(session_id, tool_name, sandbox_available)
+                    .get_access_decision(
+                        session_id,
+                        user_id,
+                        tool_name,
+                        arguments,
+                        sandbox_available,
+                    )
                     .await;
 
                 match decision {
@@ -275,8 +778,11 @@ pub async fn execute_tool_with_approval(
                             request_id, tool_name
                         );
 
-                        // Wait for user approval (60-second timeout)
-                        match approval_manager.wait_for_approval(&request_id, 60).await {
+                        // Wait for user approval
+                        match approval_manager
+                            .wait_for_approval(&request_id, APPROVAL_TIMEOUT_SECS)
+                            .await
+                        {
                             Some(response) => {
                                 if !response.approved {
                                     debug!("Tool execution denied by user: {}", tool_name);
@@ -291,9 +797,19 @@ pub async fn execute_tool_with_approval(
                                     "Tool execution approved: sandbox={}, remember={}",
                                     response.use_sandbox, response.remember_for_session
                                 );
+                                use_sandbox = Some(response.use_sandbox);
+                                if response.remember_for_session {
+                                    policy.remember_approval(session_id, tool_name).await;
+                                    approval_manager
+                                        .remember_for_session(session_id, tool_name)
+                                        .await;
+                                }
                             }
                             None => {
-                                debug!("Tool approval request timed out after 60s: {}", tool_name);
+                                debug!(
+                                    "Tool approval request timed out after {}s: {}",
+                                    APPROVAL_TIMEOUT_SECS, tool_name
+                                );
                                 return ToolExecutionResult::error(
                                     "Tool approval request timed out".to_string(),
                                     0,
@@ -311,9 +827,19 @@ pub async fn execute_tool_with_approval(
         }
 
         // Execute the tool
+        let sandboxed = sandbox.approved_in_sandbox(use_sandbox, false);
         let start_time = Instant::now();
-        let execution_result =
-            execute_tool_with_context(tool_name, arguments, Some(session_id), false).await;
+        let execution_result = run_tool(
+            tool_name,
+            arguments,
+            Some(session_id),
+            user_id,
+            false,
+            use_sandbox,
+            sandbox,
+            false,
+        )
+        .await;
         let duration_ms = start_time.elapsed().as_millis() as u64;
 
         match execution_result {
@@ -322,11 +848,69 @@ pub async fn execute_tool_with_approval(
                     "Tool executed successfully (attempt {}/{}): {}",
                     attempt, max_attempts, tool_name
                 );
-                return ToolExecutionResult::success(output, duration_ms, attempt, max_attempts);
+                let exit_code = is_command_tool(tool_name).await.then_some(0);
+                return ToolExecutionResult::success(output, duration_ms, attempt, max_attempts)
+                    .with_sandboxed(sandboxed)
+                    .with_exit_code(exit_code);
             }
             Err(e) => {
                 let error_msg = format!("{}", e);
 
+                // The command ran; running it again would repeat its side
+                // effects and most likely fail the same way
+                if let Some(failed) = e.downcast_ref::<CommandFailed>() {
+                    debug!(
+                        "Tool {} exited with code {} (attempt {}/{})",
+                        tool_name, failed.exit_code, attempt, max_attempts
+                    );
+                    return ToolExecutionResult::command_failed(
+                        failed,
+                        duration_ms,
+                        attempt,
+                        max_attempts,
+                    )
+                    .with_sandboxed(sandboxed);
+                }
+
+                // A timed-out tool would most likely time out again
+                if e.downcast_ref::<ToolTimeout>().is_some() {
+                    warn!("Tool execution timed out: {} - {}", tool_name, error_msg);
+                    return ToolExecutionResult::timed_out(
+                        error_msg,
+                        duration_ms,
+                        attempt,
+                        max_attempts,
+                    )
+                    .with_sandboxed(sandboxed);
+                }
+
+                // Retrying will not bring up a sandbox that is not running
+                if e.downcast_ref::<super::exec::SandboxUnavailable>()
+                    .is_some()
+                {
+                    warn!("Tool {} approved for a missing sandbox", tool_name);
+                    return ToolExecutionResult::error(
+                        error_msg,
+                        duration_ms,
+                        attempt,
+                        max_attempts,
+                    )
+                    .with_sandboxed(sandboxed);
+                }
+
+                // Nor will it fix the model's arguments
+                if e.downcast_ref::<InvalidToolArguments>().is_some()
+                    || e.downcast_ref::<ToolArgumentsTooLarge>().is_some()
+                {
+                    return ToolExecutionResult::error(
+                        error_msg,
+                        duration_ms,
+                        attempt,
+                        max_attempts,
+                    )
+                    .with_sandboxed(sandboxed);
+                }
+
                 // Check if we should retry
                 if retry_policy.should_retry(attempt, true) {
                     let backoff = retry_policy.get_backoff(attempt);
@@ -351,7 +935,8 @@ pub async fn execute_tool_with_approval(
                         duration_ms,
                         attempt,
                         max_attempts,
-                    );
+                    )
+                    .with_sandboxed(sandboxed);
                 }
             }
         }
@@ -378,6 +963,275 @@ mod tests {
         assert!(result.unwrap_err().to_string().contains("Unknown tool"));
     }
 
+    #[tokio::test]
+    async fn test_missing_required_argument_is_described() {
+        let err = execute_tool("exec", r#"{"timeout": 5}"#).await.unwrap_err();
+        let invalid = err.downcast_ref::<InvalidToolArguments>().unwrap();
+        assert_eq!(
+            invalid.errors,
+            vec!["$: missing required property 'command'"]
+        );
+        assert!(err.to_string().contains("'exec'"));
+
+        // Typed deserialization keeps serde's detail
+        let err = parse_arguments::<super::super::exec::ExecParams>("exec", "{}").unwrap_err();
+        assert!(
+            err.to_string().contains("missing field `command`"),
+            "{}",
+            err
+        );
+    }
+
+    #[tokio::test]
+    async fn test_malformed_arguments_are_repaired_or_rejected() {
+        let result = execute_tool("calculate", "{expression: '2 * (3 + 4)',}")
+            .await
+            .unwrap();
+        assert_eq!(result, "14");
+
+        let err = execute_tool("calculate", "expression = 2 * 3")
+            .await
+            .unwrap_err();
+        let invalid = err.downcast_ref::<InvalidToolArguments>().unwrap();
+        assert!(
+            invalid.errors[0].starts_with("arguments are not valid JSON"),
+            "{:?}",
+            invalid.errors
+        );
+    }
+
+    #[tokio::test]
+    async fn test_oversized_arguments_are_rejected() {
+        let command = "x".repeat(300 * 1024);
+        let arguments = serde_json::json!({ "command": command }).to_string();
+        let err = execute_tool("exec", &arguments).await.unwrap_err();
+        let too_large = err.downcast_ref::<ToolArgumentsTooLarge>().unwrap();
+        assert_eq!(too_large.limit, 256 * 1024);
+        assert_eq!(too_large.size, arguments.len());
+
+        let limits = ArgumentLimits::from_config(&crate::config::ToolsConfig {
+            max_argument_bytes: 10,
+            argument_size_limits: HashMap::from([("write_file".to_string(), 100)]),
+            ..Default::default()
+        });
+        assert!(limits.check("exec", r#"{"command": "ls"}"#).is_err());
+        assert!(limits
+            .check("write_file", r#"{"content": "hello"}"#)
+            .is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_timeout_cancels_running_process() {
+        let dir = tempfile::tempdir().unwrap();
+        let marker = dir.path().join("finished");
+
+        let mut command = tokio::process::Command::new("sh");
+        command
+            .arg("-c")
+            .arg(format!("sleep 1 && touch {}", marker.display()))
+            .kill_on_drop(true);
+
+        let err = run_with_timeout("slow_tool", Duration::from_millis(100), async move {
+            command.output().await?;
+            Ok("done".to_string())
+        })
+        .await
+        .unwrap_err();
+
+        assert_eq!(err.to_string(), "Tool 'slow_tool' timed out after 0s");
+        assert!(err.downcast_ref::<ToolTimeout>().is_some());
+
+        // The process was killed, so it never gets to write the marker
+        tokio::time::sleep(Duration::from_millis(1500)).await;
+        assert!(!marker.exists());
+    }
+
+    /// Commands run on the host, as in a deployment with sandboxing off
+    fn sandbox_off() -> ExecSandbox {
+        ExecSandbox {
+            mode: crate::sandbox::SandboxMode::Off,
+            manager: None,
+        }
+    }
+
+    /// Answer the next approval request with the given sandbox choice
+    fn approve_next(manager: &ApprovalManager, use_sandbox: bool) -> tokio::task::JoinHandle<()> {
+        let mut created = manager.subscribe();
+        let manager = manager.clone();
+        tokio::spawn(async move {
+            let approval = created.recv().await.unwrap();
+            manager
+                .submit_approval_response(&approval.request_id, true, use_sandbox, false)
+                .await;
+        })
+    }
+
+    #[tokio::test]
+    async fn test_approval_choice_selects_sandbox_or_host() {
+        let policy = crate::tools::policy::ToolPolicyEngine::new();
+        let manager = ApprovalManager::new();
+        let args = r#"{"command":"echo","args":["approved"]}"#;
+
+        // Approved without the sandbox: runs on the host, as sandboxing is off
+        let answer = approve_next(&manager, false);
+        let result = execute_with_policy(
+            Some(&policy),
+            "exec",
+            args,
+            "s1",
+            None,
+            &manager,
+            &sandbox_off(),
+        )
+        .await;
+        answer.await.unwrap();
+        assert!(result.is_success(), "{:?}", result);
+        assert!(result.output.as_deref().unwrap().contains("approved"));
+        assert_eq!(result.sandboxed, Some(false));
+
+        // Approved in the sandbox: never falls back to the host, and is not
+        // retried while no sandbox is running
+        let answer = approve_next(&manager, true);
+        let result = execute_with_policy(
+            Some(&policy),
+            "exec",
+            args,
+            "s1",
+            None,
+            &manager,
+            &sandbox_off(),
+        )
+        .await;
+        answer.await.unwrap();
+        assert!(result.is_error());
+        assert!(result
+            .error
+            .as_deref()
+            .unwrap()
+            .contains("sandbox is not available"));
+        assert_eq!(result.attempt, 1);
+        assert_eq!(result.sandboxed, Some(true));
+    }
+
+    #[tokio::test]
+    async fn test_policy_sees_repaired_arguments() {
+        let rules = HashMap::from([(
+            "exec".to_string(),
+            vec![crate::config::ArgumentRuleConfig {
+                pattern: r#""command"\s*:\s*"rm\b"#.to_string(),
+                action: crate::config::ArgumentRuleAction::Deny,
+            }],
+        )]);
+        let policy = crate::tools::policy::ToolPolicyEngine::new()
+            .with_argument_rules(crate::tools::policy::compile_argument_rules(&rules).unwrap());
+        let manager = ApprovalManager::new();
+
+        // Unquoted keys and a trailing comma hide the command from the rule
+        // until the arguments are repaired
+        let args = r#"{command: "rm", args: ["-rf", "/tmp/nothing-here"],}"#;
+        let result = execute_with_policy(
+            Some(&policy),
+            "exec",
+            args,
+            "s1",
+            None,
+            &manager,
+            &sandbox_off(),
+        )
+        .await;
+        assert!(result.is_error());
+        assert!(result
+            .error
+            .as_deref()
+            .unwrap()
+            .contains("denied by policy for these arguments"));
+    }
+
+    #[tokio::test]
+    async fn test_exit_code_decides_command_success() {
+        let policy = crate::tools::policy::ToolPolicyEngine::new();
+        let manager = ApprovalManager::new();
+
+        let answer = approve_next(&manager, false);
+        let args = r#"{"script":"echo fine"}"#;
+        let result = execute_with_policy(
+            Some(&policy),
+            "bash",
+            args,
+            "s1",
+            None,
+            &manager,
+            &sandbox_off(),
+        )
+        .await;
+        answer.await.unwrap();
+        assert!(result.is_success(), "{:?}", result);
+        assert_eq!(result.exit_code, Some(0));
+        assert!(result.output.as_deref().unwrap().starts_with("fine\n"));
+
+        // A non-zero exit is an error that keeps the output, and is not retried
+        let answer = approve_next(&manager, false);
+        let args = r#"{"script":"echo partial; exit 4"}"#;
+        let result = execute_with_policy(
+            Some(&policy),
+            "bash",
+            args,
+            "s1",
+            None,
+            &manager,
+            &sandbox_off(),
+        )
+        .await;
+        answer.await.unwrap();
+        assert!(result.is_error(), "{:?}", result);
+        assert_eq!(result.exit_code, Some(4));
+        assert_eq!(result.attempt, 1);
+        assert!(result.output.as_deref().unwrap().starts_with("partial\n"));
+        assert!(result
+            .error
+            .as_deref()
+            .unwrap()
+            .starts_with("Command exited with code 4"));
+    }
+
+    #[tokio::test]
+    async fn test_exec_is_unavailable_in_safe_mode() {
+        let mut tools = crate::core::available_tools().await;
+        tools.retain(|tool| allowed_in_safe_mode(&tool.name));
+        let names: Vec<&str> = tools.iter().map(|t| t.name.as_str()).collect();
+
+        for refused in [
+            "exec",
+            "bash",
+            "create_tool",
+            "delete_tool",
+            "append_memory",
+        ] {
+            assert!(
+                !names.contains(&refused),
+                "{} offered in safe mode",
+                refused
+            );
+        }
+        assert!(names.contains(&"web_fetch"));
+        assert!(names.contains(&"calculate"));
+
+        // The executor refuses exec outright, whatever the policy says
+        let err = check_safe_mode(true, "exec").unwrap_err();
+        assert!(err.to_string().contains("safe mode"), "{}", err);
+        assert!(check_safe_mode(false, "exec").is_ok());
+        assert!(check_safe_mode(true, "send_whatsapp").is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_fast_tool_is_not_affected_by_timeout() {
+        let result = run_with_timeout("fast_tool", Duration::from_secs(5), async {
+            Ok("ok".to_string())
+        })
+        .await;
+        assert_eq!(result.unwrap(), "ok");
+    }
+
     #[tokio::test]
     async fn test_format_tool_result_success() {
         let result = format_tool_result("test_tool", "Success message", true);
@@ -0,0 +1,161 @@
+//! The `gateway_info` tool
+//!
+//! Answers questions like "what tools do you have?" or "which model are you
+//! using?" from the gateway's live state instead of the model's guesses.
+
+use crate::config::Config;
+use crate::llm::{Client as LlmClient, ToolDefinition};
+use crate::sandbox::SandboxMode;
+use anyhow::{anyhow, Result};
+use once_cell::sync::OnceCell;
+use serde::Serialize;
+use serde_json::json;
+use std::collections::HashMap;
+use std::sync::Arc;
+use tokio::sync::RwLock;
+
+static GATEWAY: OnceCell<Gateway> = OnceCell::new();
+
+/// Gateway state the tool reports on
+#[derive(Clone)]
+pub struct Gateway {
+    config: Arc<RwLock<Config>>,
+    llm_client: LlmClient,
+}
+
+/// Register the gateway's shared config and LLM client
+pub fn init_gateway(config: Arc<RwLock<Config>>, llm_client: LlmClient) {
+    GATEWAY.set(Gateway { config, llm_client }).ok();
+}
+
+/// Get the gateway state, once the gateway is running
+pub fn get_gateway() -> Option<Gateway> {
+    GATEWAY.get().cloned()
+}
+
+#[derive(Debug, Serialize)]
+pub struct GatewayInfo {
+    pub version: &'static str,
+    pub model: ModelInfo,
+    pub tools: Vec<String>,
+    pub sandbox: SandboxInfo,
+    pub channels: Vec<String>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct ModelInfo {
+    pub provider: String,
+    pub primary: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub code: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub fast: Option<String>,
+    /// Models of the named roles beyond primary, code and fast
+    #[serde(skip_serializing_if = "HashMap::is_empty")]
+    pub roles: HashMap<String, String>,
+    /// Models recently used and likely still loaded
+    pub loaded: Vec<String>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct SandboxInfo {
+    pub mode: SandboxMode,
+    /// Whether the sandbox manager is running (needs a container runtime)
+    pub available: bool,
+}
+
+impl Gateway {
+    /// Snapshot of the models, tools, sandbox and channels in use
+    pub async fn info(&self) -> GatewayInfo {
+        let mut tools: Vec<String> = crate::core::available_tools()
+            .await
+            .into_iter()
+            .map(|tool| tool.name)
+            .collect();
+        tools.sort();
+        tools.dedup();
+        let loaded = self.llm_client.loaded_models().await;
+
+        let config = self.config.read().await;
+        let mut channels = Vec::new();
+        if config.api.enabled {
+            channels.push("web".to_string());
+        }
+        if config.channels.telegram.enabled {
+            channels.push("telegram".to_string());
+        }
+        if config.channels.discord.enabled {
+            channels.push("discord".to_string());
+        }
+        channels.extend(
+            crate::list_whatsapp_accounts()
+                .into_iter()
+                .map(|account| format!("whatsapp:{}", account)),
+        );
+
+        GatewayInfo {
+            version: env!("CARGO_PKG_VERSION"),
+            model: ModelInfo {
+                provider: config.llm.provider.clone(),
+                primary: config.llm.models.primary.clone(),
+                code: config.llm.models.code.clone(),
+                fast: config.llm.models.fast.clone(),
+                roles: config.llm.models.roles.clone(),
+                loaded,
+            },
+            tools,
+            sandbox: SandboxInfo {
+                mode: config.sandbox.mode.clone(),
+                available: crate::get_sandbox_manager().is_some(),
+            },
+            channels,
+        }
+    }
+}
+
+/// Run the `gateway_info` tool
+pub async fn gateway_info() -> Result<String> {
+    let gateway = get_gateway().ok_or_else(|| anyhow!("Gateway info is not available"))?;
+    Ok(serde_json::to_string_pretty(&gateway.info().await)?)
+}
+
+pub fn get_gateway_info_tool_definitions() -> Vec<ToolDefinition> {
+    vec![ToolDefinition {
+        name: "gateway_info".to_string(),
+        description: "Describe this assistant's gateway: the configured and loaded models, the available tools, the sandbox mode and the connected channels. Use it to answer questions about your own setup.".to_string(),
+        parameters: json!({
+            "type": "object",
+            "properties": {}
+        }),
+    }]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_reports_registered_tools() {
+        let config: Config = serde_yaml::from_str(
+            "llm:\n  models:\n    primary: test-model\n    fast: small-model\nchannels:\n  telegram:\n    enabled: true\n",
+        )
+        .unwrap();
+        let llm_client = LlmClient::new(&config.llm).unwrap();
+        init_gateway(Arc::new(RwLock::new(config)), llm_client);
+
+        let info: serde_json::Value = serde_json::from_str(&gateway_info().await.unwrap()).unwrap();
+        let tools: Vec<&str> = info["tools"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|tool| tool.as_str().unwrap())
+            .collect();
+        for name in ["gateway_info", "calculate", "get_current_time", "exec"] {
+            assert!(tools.contains(&name), "{} missing from {:?}", name, tools);
+        }
+        assert_eq!(info["model"]["primary"], "test-model");
+        assert_eq!(info["model"]["fast"], "small-model");
+        assert_eq!(info["channels"], json!(["telegram"]));
+        assert_eq!(info["sandbox"]["available"], false);
+    }
+}
@@ -0,0 +1,210 @@
+//! Repair of slightly malformed tool call arguments
+//!
+//! Small local models often produce almost-JSON: trailing commas, unquoted
+//! or single-quoted keys, Python literals, a closing brace too few or the
+//! arguments wrapped in a code fence. Arguments that do not parse are run
+//! through `repair` before the call is rejected; only a result that parses
+//! is used.
+
+use serde_json::Value;
+use std::borrow::Cow;
+
+/// Arguments that parse, repaired if needed. Blank arguments are left to the
+/// caller (they mean "no arguments"). Returns the parse error of the
+/// original text when no repair helps.
+pub fn repair_arguments(arguments: &str) -> Result<Cow<'_, str>, String> {
+    if arguments.trim().is_empty() {
+        return Ok(Cow::Borrowed(arguments));
+    }
+    let error = match serde_json::from_str::<Value>(arguments) {
+        Ok(_) => return Ok(Cow::Borrowed(arguments)),
+        Err(e) => e,
+    };
+    repair(arguments)
+        .map(Cow::Owned)
+        .ok_or_else(|| error.to_string())
+}
+
+/// Apply the common fixes to almost-JSON, returning the result only if it
+/// parses
+pub fn repair(text: &str) -> Option<String> {
+    let text = strip_code_fence(text.trim());
+    let chars: Vec<char> = text.chars().collect();
+    let mut out = String::with_capacity(text.len() + 8);
+    // Closing brackets still expected, innermost last
+    let mut open: Vec<char> = Vec::new();
+    // Quote character of the string being read
+    let mut quote: Option<char> = None;
+
+    let mut i = 0;
+    while i < chars.len() {
+        let c = chars[i];
+        if let Some(q) = quote {
+            match c {
+                '\\' if i + 1 < chars.len() => {
+                    // `\'` is not a JSON escape
+                    if chars[i + 1] == '\'' {
+                        out.push('\'');
+                    } else {
+                        out.push(c);
+                        out.push(chars[i + 1]);
+                    }
+                    i += 1;
+                }
+                c if c == q => {
+                    out.push('"');
+                    quote = None;
+                }
+                '"' => out.push_str("\\\""),
+                '\n' => out.push_str("\\n"),
+                '\t' => out.push_str("\\t"),
+                c => out.push(c),
+            }
+            i += 1;
+            continue;
+        }
+
+        match c {
+            '"' | '\'' => {
+                quote = Some(c);
+                out.push('"');
+            }
+            '{' => {
+                open.push('}');
+                out.push(c);
+            }
+            '[' => {
+                open.push(']');
+                out.push(c);
+            }
+            '}' | ']' => {
+                drop_trailing_comma(&mut out);
+                if open.last() == Some(&c) {
+                    open.pop();
+                }
+                out.push(c);
+            }
+            c if c.is_alphabetic() || c == '_' || c == '$' => {
+                let start = i;
+                while i < chars.len()
+                    && (chars[i].is_alphanumeric() || chars[i] == '_' || chars[i] == '$')
+                {
+                    i += 1;
+                }
+                let word: String = chars[start..i].iter().collect();
+                let next = chars[i..].iter().find(|c| !c.is_whitespace());
+                if next == Some(&':') {
+                    out.push('"');
+                    out.push_str(&word);
+                    out.push('"');
+                } else {
+                    out.push_str(match word.as_str() {
+                        "True" | "true" => "true",
+                        "False" | "false" => "false",
+                        "None" | "null" | "nil" | "undefined" => "null",
+                        other => other,
+                    });
+                }
+                continue;
+            }
+            c => out.push(c),
+        }
+        i += 1;
+    }
+
+    // Cut off mid-way: close what is still open
+    if quote.is_some() {
+        out.push('"');
+    }
+    drop_trailing_comma(&mut out);
+    while let Some(closer) = open.pop() {
+        out.push(closer);
+    }
+
+    serde_json::from_str::<Value>(&out).ok().map(|_| out)
+}
+
+/// Content of a Markdown code fence around the whole text, if any
+fn strip_code_fence(text: &str) -> &str {
+    let Some(rest) = text.strip_prefix("```") else {
+        return text;
+    };
+    // Skip the language tag
+    let body = rest.split_once('\n').map_or(rest, |(_, body)| body);
+    body.trim_end().strip_suffix("```").unwrap_or(body).trim()
+}
+
+fn drop_trailing_comma(out: &mut String) {
+    let trimmed = out.trim_end().len();
+    if out[..trimmed].ends_with(',') {
+        out.truncate(trimmed - 1);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn repaired(text: &str) -> Value {
+        let fixed = repair(text).unwrap_or_else(|| panic!("{:?} not repaired", text));
+        serde_json::from_str(&fixed).unwrap()
+    }
+
+    #[test]
+    fn test_common_mistakes_are_repaired() {
+        assert_eq!(
+            repaired(r#"{"command": "ls -la",}"#),
+            json!({"command": "ls -la"})
+        );
+        assert_eq!(
+            repaired(r#"{command: "ls", timeout: 10}"#),
+            json!({"command": "ls", "timeout": 10})
+        );
+        assert_eq!(
+            repaired(r#"{'path': 'it\'s "quoted".txt'}"#),
+            json!({"path": "it's \"quoted\".txt"})
+        );
+        assert_eq!(
+            repaired(r#"{"recursive": True, "limit": None, "tags": ["a", "b",],}"#),
+            json!({"recursive": true, "limit": null, "tags": ["a", "b"]})
+        );
+        assert_eq!(
+            repaired("```json\n{\"query\": \"rust\"}\n```"),
+            json!({"query": "rust"})
+        );
+        assert_eq!(
+            repaired(r#"{"content": "line one"#),
+            json!({"content": "line one"})
+        );
+        assert_eq!(
+            repaired("{\"content\": \"two\nlines\"}"),
+            json!({"content": "two\nlines"})
+        );
+    }
+
+    #[test]
+    fn test_repair_arguments_keeps_valid_arguments() {
+        let valid = r#"{"command": "echo True, None"}"#;
+        assert!(matches!(repair_arguments(valid), Ok(Cow::Borrowed(v)) if v == valid));
+        assert!(matches!(repair_arguments("  "), Ok(Cow::Borrowed(_))));
+        assert!(matches!(
+            repair_arguments("{command: 'ls'}"),
+            Ok(Cow::Owned(fixed)) if fixed == r#"{"command": "ls"}"#
+        ));
+    }
+
+    #[test]
+    fn test_unrepairable_arguments_report_the_original_error() {
+        for text in [
+            "run ls please",
+            r#"{"command" "ls"}"#,
+            r#"{"a": 1} {"b": 2}"#,
+            r#"{"command": ls -la}"#,
+        ] {
+            assert_eq!(repair(text), None, "{:?}", text);
+            let error = repair_arguments(text).unwrap_err();
+            assert!(error.contains("line 1"), "{}", error);
+        }
+    }
+}
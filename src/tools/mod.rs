@@ -1,14 +1,27 @@
+pub mod ask_user;
+pub mod audit;
+pub mod calculator;
+pub mod clock;
+pub mod command_guard;
 pub mod creator;
 pub mod exec;
 pub mod execution_result;
 pub mod executor;
+pub mod gateway_info;
+pub mod json_repair;
 pub mod memory;
+pub mod output_schema;
 pub mod policy;
+pub mod rag;
+pub mod reminders;
+pub mod skill_template;
 pub mod skill_watcher;
 pub mod skills;
 pub mod whatsapp;
+pub mod workspace_files;
 
-pub use creator::{get_creator_tool_definitions, CreateToolRequest};
+pub use command_guard::CommandGuard;
+pub use creator::{get_creator_tool_definitions, CreateToolRequest, ScaffoldSkillRequest};
 pub use exec::{exec_bash, exec_command, get_exec_tool_definitions};
 pub use execution_result::{ToolExecutionResult, ToolRetryPolicy};
 pub use executor::{execute_tool, execute_tool_with_approval};
@@ -17,6 +30,8 @@ pub use policy::ToolPolicyEngine;
 pub use skill_watcher::SkillWatcher;
 pub use skills::{execute_skill, get_skill, list_skills, load_skill, unload_skill};
 pub use whatsapp::{
-    get_whatsapp_tool_definitions, list_whatsapp_accounts, list_whatsapp_groups, send_whatsapp,
+    add_whatsapp_participant, get_whatsapp_tool_definitions, list_whatsapp_accounts,
+    list_whatsapp_group_participants, list_whatsapp_groups, remove_whatsapp_participant,
+    send_whatsapp, verify_whatsapp_contacts,
 };
 pub mod web;
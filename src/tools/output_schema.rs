@@ -0,0 +1,177 @@
+use serde_json::Value;
+
+/// Skill output that does not conform to the skill's declared `output_schema`
+#[derive(Debug, thiserror::Error)]
+#[error("Skill '{skill}' output does not match its output_schema: {}", errors.join("; "))]
+pub struct OutputSchemaMismatch {
+    pub skill: String,
+    pub errors: Vec<String>,
+}
+
+/// Validate a skill's stdout against its declared output schema.
+///
+/// The output must parse as JSON and conform to the schema; every violation
+/// is reported so the model can correct all of them at once.
+pub fn validate_output(
+    skill: &str,
+    schema: &Value,
+    stdout: &str,
+) -> Result<(), OutputSchemaMismatch> {
+    let value: Value = serde_json::from_str(stdout.trim()).map_err(|e| OutputSchemaMismatch {
+        skill: skill.to_string(),
+        errors: vec![format!("output is not valid JSON ({})", e)],
+    })?;
+
+    let errors = validate(schema, &value);
+    if errors.is_empty() {
+        Ok(())
+    } else {
+        Err(OutputSchemaMismatch {
+            skill: skill.to_string(),
+            errors,
+        })
+    }
+}
+
+/// Validate a value against a JSON Schema, returning one message per violation.
+///
+/// Supports the subset skills need to describe their output: `type`, `enum`,
+/// `required`, `properties`, `additionalProperties: false` and `items`.
+pub fn validate(schema: &Value, value: &Value) -> Vec<String> {
+    let mut errors = Vec::new();
+    validate_at(schema, value, "$", &mut errors);
+    errors
+}
+
+fn validate_at(schema: &Value, value: &Value, path: &str, errors: &mut Vec<String>) {
+    let Some(schema) = schema.as_object() else {
+        return;
+    };
+
+    if let Some(expected) = schema.get("type") {
+        let types: Vec<&str> = match expected {
+            Value::String(t) => vec![t.as_str()],
+            Value::Array(ts) => ts.iter().filter_map(Value::as_str).collect(),
+            _ => vec![],
+        };
+        if !types.is_empty() && !types.iter().any(|t| matches_type(t, value)) {
+            errors.push(format!(
+                "{}: expected {}, got {}",
+                path,
+                types.join(" or "),
+                type_name(value)
+            ));
+            return;
+        }
+    }
+
+    if let Some(Value::Array(allowed)) = schema.get("enum") {
+        if !allowed.contains(value) {
+            errors.push(format!(
+                "{}: {} is not one of {}",
+                path,
+                value,
+                Value::Array(allowed.clone())
+            ));
+        }
+    }
+
+    if let Value::Object(map) = value {
+        if let Some(Value::Array(required)) = schema.get("required") {
+            for key in required.iter().filter_map(Value::as_str) {
+                if !map.contains_key(key) {
+                    errors.push(format!("{}: missing required property '{}'", path, key));
+                }
+            }
+        }
+
+        let properties = schema.get("properties").and_then(Value::as_object);
+        for (key, item) in map {
+            match properties.and_then(|p| p.get(key)) {
+                Some(property) => validate_at(property, item, &format!("{}.{}", path, key), errors),
+                None if schema.get("additionalProperties") == Some(&Value::Bool(false)) => {
+                    errors.push(format!("{}: unexpected property '{}'", path, key));
+                }
+                None => {}
+            }
+        }
+    }
+
+    if let (Value::Array(items), Some(item_schema)) = (value, schema.get("items")) {
+        for (i, item) in items.iter().enumerate() {
+            validate_at(item_schema, item, &format!("{}[{}]", path, i), errors);
+        }
+    }
+}
+
+fn matches_type(expected: &str, value: &Value) -> bool {
+    match expected {
+        "object" => value.is_object(),
+        "array" => value.is_array(),
+        "string" => value.is_string(),
+        "boolean" => value.is_boolean(),
+        "null" => value.is_null(),
+        "number" => value.is_number(),
+        "integer" => value.is_i64() || value.is_u64(),
+        _ => true,
+    }
+}
+
+fn type_name(value: &Value) -> &'static str {
+    match value {
+        Value::Null => "null",
+        Value::Bool(_) => "boolean",
+        Value::Number(n) if n.is_f64() => "number",
+        Value::Number(_) => "integer",
+        Value::String(_) => "string",
+        Value::Array(_) => "array",
+        Value::Object(_) => "object",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn schema() -> Value {
+        json!({
+            "type": "object",
+            "required": ["status", "count"],
+            "properties": {
+                "status": {"type": "string", "enum": ["ok", "degraded"]},
+                "count": {"type": "integer"},
+                "hosts": {"type": "array", "items": {"type": "string"}}
+            },
+            "additionalProperties": false
+        })
+    }
+
+    #[test]
+    fn test_conforming_output() {
+        let stdout = r#"{"status": "ok", "count": 2, "hosts": ["a", "b"]}"#;
+        assert!(validate_output("health", &schema(), stdout).is_ok());
+    }
+
+    #[test]
+    fn test_violations_are_all_reported() {
+        let value = json!({"status": "down", "hosts": ["a", 3], "extra": true});
+        let errors = validate(&schema(), &value);
+
+        assert!(errors.contains(&"$: missing required property 'count'".to_string()));
+        assert!(errors
+            .iter()
+            .any(|e| e.starts_with("$.status: \"down\" is not one of")));
+        assert!(errors.contains(&"$.hosts[1]: expected string, got integer".to_string()));
+        assert!(errors.contains(&"$: unexpected property 'extra'".to_string()));
+        assert_eq!(errors.len(), 4);
+    }
+
+    #[test]
+    fn test_non_json_output_is_rejected() {
+        let err = validate_output("health", &schema(), "all good").unwrap_err();
+        assert!(err.to_string().starts_with(
+            "Skill 'health' output does not match its output_schema: output is not valid JSON"
+        ));
+    }
+}
@@ -1,11 +1,15 @@
+use crate::config::{ArgumentRuleAction, ArgumentRuleConfig};
+use anyhow::Context;
+use regex::Regex;
+use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 use std::collections::{HashMap, HashSet};
 use std::sync::Arc;
 use tokio::sync::RwLock;
-use tracing::debug;
+use tracing::{debug, info};
 
 /// Tool access control level
-#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, JsonSchema)]
 #[serde(rename_all = "snake_case")]
 pub enum ToolAccessLevel {
     /// Tool is always allowed
@@ -29,6 +33,83 @@ impl std::str::FromStr for ToolAccessLevel {
     }
 }
 
+impl ToolAccessLevel {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            ToolAccessLevel::Allow => "allow",
+            ToolAccessLevel::Deny => "deny",
+            ToolAccessLevel::Elevated => "elevated",
+        }
+    }
+
+    /// The more restrictive of two levels
+    fn stricter(self, other: ToolAccessLevel) -> ToolAccessLevel {
+        match (self, other) {
+            (ToolAccessLevel::Deny, _) | (_, ToolAccessLevel::Deny) => ToolAccessLevel::Deny,
+            (ToolAccessLevel::Elevated, _) | (_, ToolAccessLevel::Elevated) => {
+                ToolAccessLevel::Elevated
+            }
+            _ => ToolAccessLevel::Allow,
+        }
+    }
+}
+
+/// Tools whose policies govern a call: the tool itself, plus `send_whatsapp`
+/// for a reminder with an explicit target, which is a message sent on the
+/// user's behalf to someone other than themselves
+fn governing_tools<'a>(tool_name: &'a str, arguments: &str) -> impl Iterator<Item = &'a str> {
+    let foreign_target = tool_name == "set_reminder"
+        && serde_json::from_str::<serde_json::Value>(arguments)
+            .ok()
+            .and_then(|args| args.get("target")?.as_str().map(|t| !t.trim().is_empty()))
+            .unwrap_or(false);
+    std::iter::once(tool_name).chain(foreign_target.then_some("send_whatsapp"))
+}
+
+/// Arguments as argument rules see them: compact JSON with sorted keys and
+/// string escapes decoded, so `"\u0072m"` or extra whitespace match like
+/// `"rm"`. Arguments that do not parse are matched as sent.
+fn canonical_arguments(arguments: &str) -> String {
+    fn sorted(value: serde_json::Value) -> serde_json::Value {
+        match value {
+            serde_json::Value::Object(map) => {
+                let mut entries: Vec<_> = map.into_iter().collect();
+                entries.sort_by(|(a, _), (b, _)| a.cmp(b));
+                serde_json::Value::Object(
+                    entries
+                        .into_iter()
+                        .map(|(key, value)| (key, sorted(value)))
+                        .collect(),
+                )
+            }
+            serde_json::Value::Array(items) => {
+                serde_json::Value::Array(items.into_iter().map(sorted).collect())
+            }
+            other => other,
+        }
+    }
+
+    match serde_json::from_str(arguments) {
+        Ok(value) => sorted(value).to_string(),
+        Err(_) => arguments.to_string(),
+    }
+}
+
+/// Parse a tool name -> level map, rejecting unknown levels
+pub fn parse_policies(
+    policies: &HashMap<String, String>,
+) -> Result<HashMap<String, ToolAccessLevel>, String> {
+    policies
+        .iter()
+        .map(|(tool, level)| {
+            let level = level
+                .parse::<ToolAccessLevel>()
+                .map_err(|e| format!("{} (tool '{}')", e, tool))?;
+            Ok((tool.clone(), level))
+        })
+        .collect()
+}
+
 /// Error types for policy enforcement
 #[derive(Debug, thiserror::Error)]
 pub enum ToolPolicyError {
@@ -37,6 +118,50 @@ pub enum ToolPolicyError {
 
     #[error("Tool '{tool}' requires elevated mode. Use '/elevated on' to enable")]
     ElevatedRequired { tool: String },
+
+    #[error("Command blocked by safety policy (matched dangerous pattern '{pattern}')")]
+    DangerousCommand { pattern: String },
+
+    #[error("Tool '{tool}' arguments are blocked by policy (matched pattern '{pattern}')")]
+    ArgumentsDenied { tool: String, pattern: String },
+
+    #[error("Tool '{tool}' arguments require approval (matched pattern '{pattern}')")]
+    ArgumentsRequireApproval { tool: String, pattern: String },
+}
+
+/// Per-tool regex applied to the canonical arguments JSON (see
+/// `canonical_arguments`); a match escalates the
+/// access decision for that call
+#[derive(Debug, Clone)]
+pub struct ArgumentRule {
+    pub pattern: Regex,
+    pub action: ArgumentRuleAction,
+}
+
+/// Compile the configured argument rules, keyed by tool name
+pub fn compile_argument_rules(
+    config: &HashMap<String, Vec<ArgumentRuleConfig>>,
+) -> anyhow::Result<HashMap<String, Vec<ArgumentRule>>> {
+    config
+        .iter()
+        .map(|(tool, rules)| {
+            let rules = rules
+                .iter()
+                .map(|rule| {
+                    Ok(ArgumentRule {
+                        pattern: Regex::new(&rule.pattern).with_context(|| {
+                            format!(
+                                "Invalid argument pattern for tool '{}': {}",
+                                tool, rule.pattern
+                            )
+                        })?,
+                        action: rule.action,
+                    })
+                })
+                .collect::<anyhow::Result<Vec<_>>>()?;
+            Ok((tool.clone(), rules))
+        })
+        .collect()
 }
 
 /// Decision about whether a tool should be executed
@@ -53,7 +178,19 @@ pub enum ToolAccessDecision {
 /// Tool policy enforcement engine
 pub struct ToolPolicyEngine {
     policies: Arc<RwLock<HashMap<String, ToolAccessLevel>>>,
+    /// Per-user overrides of `policies`, keyed by user ID then tool name
+    user_policies: Arc<RwLock<HashMap<String, HashMap<String, ToolAccessLevel>>>>,
     elevated_mode: Arc<RwLock<HashSet<String>>>,
+    /// Tools approved with "remember for session", keyed by session ID
+    session_approvals: Arc<RwLock<HashMap<String, HashSet<String>>>>,
+    /// Development mode: every tool is allowed without checks
+    dev_bypass: bool,
+    /// Users whose elevated tool calls are approved automatically
+    auto_approve_users: HashSet<String>,
+    /// Argument patterns that escalate individual calls, keyed by tool name
+    argument_rules: HashMap<String, Vec<ArgumentRule>>,
+    /// Level of tools without a policy of their own
+    default_level: ToolAccessLevel,
 }
 
 impl ToolPolicyEngine {
@@ -66,23 +203,92 @@ impl ToolPolicyEngine {
     pub fn with_policies(policies: HashMap<String, ToolAccessLevel>) -> Self {
         Self {
             policies: Arc::new(RwLock::new(policies)),
+            user_policies: Arc::new(RwLock::new(HashMap::new())),
             elevated_mode: Arc::new(RwLock::new(HashSet::new())),
+            session_approvals: Arc::new(RwLock::new(HashMap::new())),
+            dev_bypass: false,
+            auto_approve_users: HashSet::new(),
+            argument_rules: HashMap::new(),
+            default_level: ToolAccessLevel::Deny,
         }
     }
 
+    /// Level applied to tools without a policy of their own (default: deny)
+    pub fn with_default_policy(mut self, level: ToolAccessLevel) -> Self {
+        self.default_level = level;
+        self
+    }
+
+    /// Escalate calls whose arguments match the given per-tool rules
+    pub fn with_argument_rules(mut self, rules: HashMap<String, Vec<ArgumentRule>>) -> Self {
+        self.argument_rules = rules;
+        self
+    }
+
+    /// Approve elevated tools automatically for the given users
+    pub fn with_auto_approve(mut self, users: impl IntoIterator<Item = String>) -> Self {
+        self.auto_approve_users = users.into_iter().collect();
+        self
+    }
+
+    /// Whether a user is on the approval allowlist
+    pub fn is_auto_approved(&self, user_id: &str) -> bool {
+        self.auto_approve_users.contains(user_id)
+    }
+
+    /// Allow every tool without policy checks (local development only)
+    pub fn with_dev_bypass(mut self, enabled: bool) -> Self {
+        self.dev_bypass = enabled;
+        self
+    }
+
+    /// Whether policy enforcement is bypassed
+    pub fn dev_bypass(&self) -> bool {
+        self.dev_bypass
+    }
+
+    /// The argument rule matching a call, preferring deny rules
+    fn matching_argument_rule(&self, tool_name: &str, arguments: &str) -> Option<&ArgumentRule> {
+        let canonical = canonical_arguments(arguments);
+        let mut matching = governing_tools(tool_name, arguments)
+            .filter_map(|tool| self.argument_rules.get(tool))
+            .flatten()
+            .filter(|rule| rule.pattern.is_match(&canonical));
+        let first = matching.next()?;
+
+        if first.action == ArgumentRuleAction::Deny {
+            return Some(first);
+        }
+        matching
+            .find(|rule| rule.action == ArgumentRuleAction::Deny)
+            .or(Some(first))
+    }
+
     /// Check if a session has permission to execute a tool
+    ///
+    /// `user_id` is the session owner, whose policy overrides and place on
+    /// the approval allowlist apply as in `get_access_decision`. Otherwise
+    /// calls whose arguments match an argument rule are rejected: there is no
+    /// interactive approval outside `get_access_decision`.
     pub async fn check_permission(
         &self,
         session_id: &str,
+        user_id: Option<&str>,
         tool_name: &str,
+        arguments: &str,
     ) -> Result<(), ToolPolicyError> {
-        let policies = self.policies.read().await;
-        let level = policies
-            .get(tool_name)
-            .cloned()
-            .unwrap_or(ToolAccessLevel::Deny);
+        if self.dev_bypass {
+            debug!("Tool '{}' allowed by dev policy bypass", tool_name);
+            return Ok(());
+        }
+
+        let mut level = ToolAccessLevel::Allow;
+        for tool in governing_tools(tool_name, arguments) {
+            level = level.stricter(self.access_level_for(tool, user_id).await);
+        }
+        let auto_approved = user_id.is_some_and(|u| self.is_auto_approved(u));
 
-        match level {
+        let result = match level {
             ToolAccessLevel::Allow => {
                 debug!("Tool '{}' allowed by policy", tool_name);
                 Ok(())
@@ -98,6 +304,18 @@ impl ToolPolicyEngine {
                 if elevated.contains(session_id) {
                     debug!("Tool '{}' allowed via elevated mode", tool_name);
                     Ok(())
+                } else if self.is_approval_remembered(session_id, tool_name).await {
+                    debug!(
+                        "Tool '{}' allowed by an approval remembered for session {}",
+                        tool_name, session_id
+                    );
+                    Ok(())
+                } else if let Some(user_id) = user_id.filter(|_| auto_approved) {
+                    info!(
+                        "Tool '{}' auto-approved for allowlisted user {} (session {})",
+                        tool_name, user_id, session_id
+                    );
+                    Ok(())
                 } else {
                     debug!(
                         "Tool '{}' requires elevated mode for session {}",
@@ -108,6 +326,25 @@ impl ToolPolicyEngine {
                     })
                 }
             }
+        };
+        result?;
+
+        match self.matching_argument_rule(tool_name, arguments) {
+            Some(rule) if rule.action == ArgumentRuleAction::Deny || !auto_approved => {
+                let tool = tool_name.to_string();
+                let pattern = rule.pattern.as_str().to_string();
+                info!(
+                    "Tool '{}' arguments matched {:?} rule '{}' (session {})",
+                    tool_name, rule.action, pattern, session_id
+                );
+                Err(match rule.action {
+                    ArgumentRuleAction::Deny => ToolPolicyError::ArgumentsDenied { tool, pattern },
+                    ArgumentRuleAction::RequireApproval => {
+                        ToolPolicyError::ArgumentsRequireApproval { tool, pattern }
+                    }
+                })
+            }
+            _ => Ok(()),
         }
     }
 
@@ -129,25 +366,65 @@ impl ToolPolicyEngine {
         elevated.contains(session_id)
     }
 
+    /// Allow an elevated tool without approval for the rest of a session
+    pub async fn remember_approval(&self, session_id: &str, tool_name: &str) {
+        self.session_approvals
+            .write()
+            .await
+            .entry(session_id.to_string())
+            .or_default()
+            .insert(tool_name.to_string());
+        debug!(
+            "Approval of '{}' remembered for session {}",
+            tool_name, session_id
+        );
+    }
+
+    /// Whether a tool was approved for the rest of a session
+    pub async fn is_approval_remembered(&self, session_id: &str, tool_name: &str) -> bool {
+        self.session_approvals
+            .read()
+            .await
+            .get(session_id)
+            .is_some_and(|tools| tools.contains(tool_name))
+    }
+
+    /// Forget the tools approved for a session (on reset)
+    pub async fn forget_session_approvals(&self, session_id: &str) {
+        self.session_approvals.write().await.remove(session_id);
+    }
+
     /// Get the access decision for a tool (used for interactive approval flow)
     ///
     /// Returns:
     /// - Allowed: tool can be executed immediately
     /// - Denied: tool is blocked by policy
     /// - RequiresApproval: tool needs user approval via WebSocket
+    ///
+    /// Elevated tools are allowed without approval when the session is in
+    /// elevated mode, the tool was approved for the rest of the session, or
+    /// `user_id` is on the approval allowlist. Argument
+    /// rules then escalate the call: a deny rule always denies, an approval
+    /// rule requires approval unless the user is allowlisted.
     pub async fn get_access_decision(
         &self,
         session_id: &str,
+        user_id: Option<&str>,
         tool_name: &str,
+        arguments: &str,
         sandbox_available: bool,
     ) -> ToolAccessDecision {
-        let policies = self.policies.read().await;
-        let level = policies
-            .get(tool_name)
-            .cloned()
-            .unwrap_or(ToolAccessLevel::Deny);
+        if self.dev_bypass {
+            debug!("Tool '{}' allowed by dev policy bypass", tool_name);
+            return ToolAccessDecision::Allowed;
+        }
+
+        let mut level = ToolAccessLevel::Allow;
+        for tool in governing_tools(tool_name, arguments) {
+            level = level.stricter(self.access_level_for(tool, user_id).await);
+        }
 
-        match level {
+        let decision = match level {
             ToolAccessLevel::Allow => {
                 debug!("Tool '{}' allowed by policy", tool_name);
                 ToolAccessDecision::Allowed
@@ -166,6 +443,18 @@ impl ToolPolicyEngine {
                 if elevated.contains(session_id) {
                     debug!("Tool '{}' allowed via elevated mode", tool_name);
                     ToolAccessDecision::Allowed
+                } else if self.is_approval_remembered(session_id, tool_name).await {
+                    debug!(
+                        "Tool '{}' allowed by an approval remembered for session {}",
+                        tool_name, session_id
+                    );
+                    ToolAccessDecision::Allowed
+                } else if let Some(user_id) = user_id.filter(|u| self.is_auto_approved(u)) {
+                    info!(
+                        "Tool '{}' auto-approved for allowlisted user {} (session {})",
+                        tool_name, user_id, session_id
+                    );
+                    ToolAccessDecision::Allowed
                 } else {
                     debug!(
                         "Tool '{}' requires approval for session {}",
@@ -174,6 +463,33 @@ impl ToolPolicyEngine {
                     ToolAccessDecision::RequiresApproval { sandbox_available }
                 }
             }
+        };
+
+        if matches!(decision, ToolAccessDecision::Denied { .. }) {
+            return decision;
+        }
+
+        match self.matching_argument_rule(tool_name, arguments) {
+            Some(rule) if rule.action == ArgumentRuleAction::Deny => {
+                info!(
+                    "Tool '{}' denied: arguments matched pattern '{}' (session {})",
+                    tool_name, rule.pattern, session_id
+                );
+                ToolAccessDecision::Denied {
+                    reason: format!(
+                        "Tool '{}' is denied by policy for these arguments (matched pattern '{}')",
+                        tool_name, rule.pattern
+                    ),
+                }
+            }
+            Some(rule) if !user_id.is_some_and(|u| self.is_auto_approved(u)) => {
+                info!(
+                    "Tool '{}' requires approval: arguments matched pattern '{}' (session {})",
+                    tool_name, rule.pattern, session_id
+                );
+                ToolAccessDecision::RequiresApproval { sandbox_available }
+            }
+            _ => decision,
         }
     }
 
@@ -183,7 +499,49 @@ impl ToolPolicyEngine {
         policies
             .get(tool_name)
             .cloned()
-            .unwrap_or(ToolAccessLevel::Deny)
+            .unwrap_or_else(|| self.default_level.clone())
+    }
+
+    /// Access level for a tool, with the user's override taking precedence
+    pub async fn access_level_for(
+        &self,
+        tool_name: &str,
+        user_id: Option<&str>,
+    ) -> ToolAccessLevel {
+        if let Some(user_id) = user_id {
+            let user_policies = self.user_policies.read().await;
+            if let Some(level) = user_policies.get(user_id).and_then(|p| p.get(tool_name)) {
+                return level.clone();
+            }
+        }
+        self.get_access_level(tool_name).await
+    }
+
+    /// Replace a user's policy overrides; an empty map removes them
+    pub async fn set_user_policies(
+        &self,
+        user_id: &str,
+        overrides: HashMap<String, ToolAccessLevel>,
+    ) {
+        let mut user_policies = self.user_policies.write().await;
+        if overrides.is_empty() {
+            user_policies.remove(user_id);
+        } else {
+            user_policies.insert(user_id.to_string(), overrides);
+        }
+    }
+
+    /// A user's policy overrides (returns a snapshot)
+    pub async fn get_user_policies(&self, user_id: &str) -> HashMap<String, ToolAccessLevel> {
+        let user_policies = self.user_policies.read().await;
+        user_policies.get(user_id).cloned().unwrap_or_default()
+    }
+
+    /// Global policies with a user's overrides applied
+    pub async fn get_effective_policies(&self, user_id: &str) -> HashMap<String, ToolAccessLevel> {
+        let mut policies = self.get_policies().await;
+        policies.extend(self.get_user_policies(user_id).await);
+        policies
     }
 
     /// Get all tool policies (returns a snapshot)
@@ -211,6 +569,22 @@ impl ToolPolicyEngine {
         policies.insert("send_whatsapp".to_string(), ToolAccessLevel::Allow);
         policies.insert("list_whatsapp_groups".to_string(), ToolAccessLevel::Allow);
         policies.insert("list_whatsapp_accounts".to_string(), ToolAccessLevel::Allow);
+        policies.insert(
+            "list_whatsapp_group_participants".to_string(),
+            ToolAccessLevel::Allow,
+        );
+        policies.insert(
+            "verify_whatsapp_contacts".to_string(),
+            ToolAccessLevel::Allow,
+        );
+        policies.insert(
+            "add_whatsapp_participant".to_string(),
+            ToolAccessLevel::Elevated,
+        );
+        policies.insert(
+            "remove_whatsapp_participant".to_string(),
+            ToolAccessLevel::Elevated,
+        );
 
         // Web tools (elevated by default for security)
         policies.insert("web_fetch".to_string(), ToolAccessLevel::Elevated);
@@ -221,6 +595,29 @@ impl ToolPolicyEngine {
         policies.insert("write_file".to_string(), ToolAccessLevel::Elevated);
         policies.insert("list_files".to_string(), ToolAccessLevel::Elevated);
 
+        // Clock and calculator (no side effects)
+        policies.insert("get_current_time".to_string(), ToolAccessLevel::Allow);
+        policies.insert("calculate".to_string(), ToolAccessLevel::Allow);
+
+        // Session workspace files (read-only)
+        policies.insert("list_workspace".to_string(), ToolAccessLevel::Allow);
+        policies.insert("read_workspace_file".to_string(), ToolAccessLevel::Allow);
+
+        // Gateway introspection (read-only)
+        policies.insert("gateway_info".to_string(), ToolAccessLevel::Allow);
+
+        // Knowledge search (read-only)
+        policies.insert("search_docs".to_string(), ToolAccessLevel::Allow);
+
+        // Reminders (delivered back to the user; reminders for someone else
+        // are also subject to the send_whatsapp policy)
+        policies.insert("set_reminder".to_string(), ToolAccessLevel::Allow);
+        policies.insert("list_reminders".to_string(), ToolAccessLevel::Allow);
+        policies.insert("cancel_reminder".to_string(), ToolAccessLevel::Allow);
+
+        // Clarifying questions (answered by the user)
+        policies.insert("ask_user".to_string(), ToolAccessLevel::Allow);
+
         policies
     }
 
@@ -273,21 +670,138 @@ mod tests {
     #[tokio::test]
     async fn test_allow_policy() {
         let engine = ToolPolicyEngine::new();
-        let result = engine.check_permission("session1", "send_whatsapp").await;
+        let result = engine
+            .check_permission("session1", None, "send_whatsapp", "{}")
+            .await;
         assert!(result.is_ok());
     }
 
     #[tokio::test]
     async fn test_deny_policy() {
         let engine = ToolPolicyEngine::new();
-        let result = engine.check_permission("session1", "unknown_tool").await;
+        let result = engine
+            .check_permission("session1", None, "unknown_tool", "{}")
+            .await;
         assert!(result.is_err());
     }
 
+    #[tokio::test]
+    async fn test_unlisted_tool_denied_by_default() {
+        let engine = ToolPolicyEngine::new().with_default_policy(ToolAccessLevel::Deny);
+        assert_eq!(
+            engine.get_access_level("unknown_tool").await,
+            ToolAccessLevel::Deny
+        );
+        assert!(matches!(
+            engine
+                .get_access_decision("session1", None, "unknown_tool", "{}", false)
+                .await,
+            ToolAccessDecision::Denied { .. }
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_unlisted_tool_elevated_by_default_policy() {
+        let engine = ToolPolicyEngine::new().with_default_policy(ToolAccessLevel::Elevated);
+        assert!(matches!(
+            engine
+                .get_access_decision("session1", None, "unknown_tool", "{}", false)
+                .await,
+            ToolAccessDecision::RequiresApproval { .. }
+        ));
+        assert!(matches!(
+            engine
+                .check_permission("session1", None, "unknown_tool", "{}")
+                .await,
+            Err(ToolPolicyError::ElevatedRequired { .. })
+        ));
+
+        engine.set_elevated("session1", true).await;
+        assert!(engine
+            .check_permission("session1", None, "unknown_tool", "{}")
+            .await
+            .is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_unlisted_tool_allowed_by_default_policy() {
+        let engine = ToolPolicyEngine::new().with_default_policy(ToolAccessLevel::Allow);
+        assert!(engine
+            .check_permission("session1", None, "unknown_tool", "{}")
+            .await
+            .is_ok());
+        assert!(matches!(
+            engine
+                .get_access_decision("session1", None, "unknown_tool", "{}", false)
+                .await,
+            ToolAccessDecision::Allowed
+        ));
+
+        // Listed tools keep their own level
+        assert_eq!(
+            engine.get_access_level("exec").await,
+            ToolAccessLevel::Elevated
+        );
+    }
+
+    #[tokio::test]
+    async fn test_dev_bypass_allows_everything() {
+        let engine = ToolPolicyEngine::new().with_dev_bypass(true);
+        assert!(engine
+            .check_permission("session1", None, "exec", "{}")
+            .await
+            .is_ok());
+        assert!(engine
+            .check_permission("session1", None, "unknown_tool", "{}")
+            .await
+            .is_ok());
+        assert!(matches!(
+            engine
+                .get_access_decision("session1", None, "exec", "{}", false)
+                .await,
+            ToolAccessDecision::Allowed
+        ));
+    }
+
+    #[test]
+    fn test_dev_bypass_requires_local_surfaces() {
+        let tools = crate::config::ToolsConfig {
+            dev_bypass_policy: true,
+            ..Default::default()
+        };
+        let mut api = crate::config::ApiConfig {
+            enabled: true,
+            host: "127.0.0.1".to_string(),
+            ..Default::default()
+        };
+        let mut channels = crate::config::ChannelsConfig::default();
+        assert!(tools.dev_bypass_active(&api, &channels));
+
+        api.host = "localhost".to_string();
+        assert!(tools.dev_bypass_active(&api, &channels));
+
+        // Remote users reach the gateway through any enabled channel, with
+        // or without the API
+        channels.whatsapp.enabled = true;
+        assert!(!tools.dev_bypass_active(&api, &channels));
+        api.enabled = false;
+        assert!(!tools.dev_bypass_active(&api, &channels));
+        channels.whatsapp.enabled = false;
+
+        api.enabled = true;
+        api.host = "0.0.0.0".to_string();
+        assert!(!tools.dev_bypass_active(&api, &channels));
+
+        api.host = "127.0.0.1".to_string();
+        assert!(!crate::config::ToolsConfig::default().dev_bypass_active(&api, &channels));
+    }
+
     #[tokio::test]
     async fn test_elevated_mode_required() {
         let engine = ToolPolicyEngine::new();
-        let result = engine.check_permission("session1", "exec").await;
+        let result = engine
+            .check_permission("session1", None, "exec", "{}")
+            .await;
         assert!(matches!(
             result,
             Err(ToolPolicyError::ElevatedRequired { .. })
@@ -298,7 +812,9 @@ mod tests {
     async fn test_elevated_mode_granted() {
         let engine = ToolPolicyEngine::new();
         engine.set_elevated("session1", true).await;
-        let result = engine.check_permission("session1", "exec").await;
+        let result = engine
+            .check_permission("session1", None, "exec", "{}")
+            .await;
         assert!(result.is_ok());
     }
 
@@ -311,7 +827,9 @@ mod tests {
         engine.set_elevated("session1", false).await;
         assert!(!engine.is_elevated("session1").await);
 
-        let result = engine.check_permission("session1", "exec").await;
+        let result = engine
+            .check_permission("session1", None, "exec", "{}")
+            .await;
         assert!(matches!(
             result,
             Err(ToolPolicyError::ElevatedRequired { .. })
@@ -322,7 +840,7 @@ mod tests {
     async fn test_get_access_decision_allow_policy() {
         let engine = ToolPolicyEngine::new();
         let decision = engine
-            .get_access_decision("session1", "send_whatsapp", true)
+            .get_access_decision("session1", None, "send_whatsapp", "{}", true)
             .await;
         assert!(matches!(decision, ToolAccessDecision::Allowed));
     }
@@ -331,7 +849,7 @@ mod tests {
     async fn test_get_access_decision_deny_policy() {
         let engine = ToolPolicyEngine::new();
         let decision = engine
-            .get_access_decision("session1", "unknown_tool", true)
+            .get_access_decision("session1", None, "unknown_tool", "{}", true)
             .await;
         assert!(matches!(decision, ToolAccessDecision::Denied { .. }));
     }
@@ -339,7 +857,9 @@ mod tests {
     #[tokio::test]
     async fn test_get_access_decision_elevated_requires_approval() {
         let engine = ToolPolicyEngine::new();
-        let decision = engine.get_access_decision("session1", "exec", true).await;
+        let decision = engine
+            .get_access_decision("session1", None, "exec", "{}", true)
+            .await;
         assert!(matches!(
             decision,
             ToolAccessDecision::RequiresApproval {
@@ -352,14 +872,18 @@ mod tests {
     async fn test_get_access_decision_elevated_with_mode_enabled() {
         let engine = ToolPolicyEngine::new();
         engine.set_elevated("session1", true).await;
-        let decision = engine.get_access_decision("session1", "exec", true).await;
+        let decision = engine
+            .get_access_decision("session1", None, "exec", "{}", true)
+            .await;
         assert!(matches!(decision, ToolAccessDecision::Allowed));
     }
 
     #[tokio::test]
     async fn test_get_access_decision_elevated_no_sandbox() {
         let engine = ToolPolicyEngine::new();
-        let decision = engine.get_access_decision("session1", "exec", false).await;
+        let decision = engine
+            .get_access_decision("session1", None, "exec", "{}", false)
+            .await;
         assert!(matches!(
             decision,
             ToolAccessDecision::RequiresApproval {
@@ -374,14 +898,18 @@ mod tests {
         engine.set_elevated("session1", true).await;
 
         // Initially allowed with elevated mode
-        let decision = engine.get_access_decision("session1", "exec", true).await;
+        let decision = engine
+            .get_access_decision("session1", None, "exec", "{}", true)
+            .await;
         assert!(matches!(decision, ToolAccessDecision::Allowed));
 
         // Revoke elevated mode
         engine.set_elevated("session1", false).await;
 
         // Should now require approval
-        let decision = engine.get_access_decision("session1", "exec", true).await;
+        let decision = engine
+            .get_access_decision("session1", None, "exec", "{}", true)
+            .await;
         assert!(matches!(
             decision,
             ToolAccessDecision::RequiresApproval {
@@ -389,4 +917,361 @@ mod tests {
             }
         ));
     }
+
+    #[tokio::test]
+    async fn test_remembered_approval_allows_rest_of_session() {
+        let engine = ToolPolicyEngine::new();
+
+        // First call requires approval
+        let decision = engine
+            .get_access_decision("session1", None, "exec", "{}", true)
+            .await;
+        assert!(matches!(
+            decision,
+            ToolAccessDecision::RequiresApproval { .. }
+        ));
+
+        // Approved with "remember for session": the next call is allowed
+        engine.remember_approval("session1", "exec").await;
+        let decision = engine
+            .get_access_decision("session1", None, "exec", "{}", true)
+            .await;
+        assert!(matches!(decision, ToolAccessDecision::Allowed));
+
+        // Only for that tool and that session
+        assert!(matches!(
+            engine
+                .get_access_decision("session1", None, "bash", "{}", true)
+                .await,
+            ToolAccessDecision::RequiresApproval { .. }
+        ));
+        assert!(matches!(
+            engine
+                .get_access_decision("session2", None, "exec", "{}", true)
+                .await,
+            ToolAccessDecision::RequiresApproval { .. }
+        ));
+
+        // Reset forgets it
+        engine.forget_session_approvals("session1").await;
+        assert!(matches!(
+            engine
+                .get_access_decision("session1", None, "exec", "{}", true)
+                .await,
+            ToolAccessDecision::RequiresApproval { .. }
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_allowlisted_user_skips_approval() {
+        let engine = ToolPolicyEngine::new().with_auto_approve(["alice".to_string()]);
+
+        let decision = engine
+            .get_access_decision("session1", Some("alice"), "exec", "{}", true)
+            .await;
+        assert!(matches!(decision, ToolAccessDecision::Allowed));
+
+        let decision = engine
+            .get_access_decision("session2", Some("bob"), "exec", "{}", true)
+            .await;
+        assert!(matches!(
+            decision,
+            ToolAccessDecision::RequiresApproval { .. }
+        ));
+
+        // The allowlist never overrides a deny policy
+        let decision = engine
+            .get_access_decision("session1", Some("alice"), "unknown_tool", "{}", true)
+            .await;
+        assert!(matches!(decision, ToolAccessDecision::Denied { .. }));
+        assert!(engine
+            .check_permission("session1", Some("alice"), "exec", "{}")
+            .await
+            .is_ok());
+        assert!(engine
+            .check_permission("session2", Some("bob"), "exec", "{}")
+            .await
+            .is_err());
+    }
+
+    #[tokio::test]
+    async fn test_user_policy_overrides() {
+        let engine = ToolPolicyEngine::new();
+        let overrides = parse_policies(&HashMap::from([
+            ("exec".to_string(), "Allow".to_string()),
+            ("send_whatsapp".to_string(), "deny".to_string()),
+        ]))
+        .unwrap();
+        engine.set_user_policies("alice", overrides).await;
+
+        let decision = engine
+            .get_access_decision("session1", Some("alice"), "exec", "{}", true)
+            .await;
+        assert!(matches!(decision, ToolAccessDecision::Allowed));
+        let decision = engine
+            .get_access_decision("session1", Some("alice"), "send_whatsapp", "{}", true)
+            .await;
+        assert!(matches!(decision, ToolAccessDecision::Denied { .. }));
+
+        // Other users keep the global policies
+        let decision = engine
+            .get_access_decision("session2", Some("bob"), "exec", "{}", true)
+            .await;
+        assert!(matches!(
+            decision,
+            ToolAccessDecision::RequiresApproval { .. }
+        ));
+
+        // Paths without interactive approval apply the overrides too
+        assert!(engine
+            .check_permission("session1", Some("alice"), "exec", "{}")
+            .await
+            .is_ok());
+        assert!(matches!(
+            engine
+                .check_permission("session1", Some("alice"), "send_whatsapp", "{}")
+                .await,
+            Err(ToolPolicyError::Denied { .. })
+        ));
+        assert!(engine
+            .check_permission("session2", Some("bob"), "send_whatsapp", "{}")
+            .await
+            .is_ok());
+
+        let effective = engine.get_effective_policies("alice").await;
+        assert_eq!(effective["exec"], ToolAccessLevel::Allow);
+        assert_eq!(effective["web_fetch"], ToolAccessLevel::Elevated);
+
+        engine.set_user_policies("alice", HashMap::new()).await;
+        assert!(engine.get_user_policies("alice").await.is_empty());
+
+        let invalid = HashMap::from([("exec".to_string(), "sometimes".to_string())]);
+        assert!(parse_policies(&invalid).is_err());
+    }
+
+    #[test]
+    fn test_auto_approved_users_include_roles() {
+        let tools = crate::config::ToolsConfig {
+            auto_approve: crate::config::AutoApproveConfig {
+                users: vec!["alice".to_string()],
+                roles: vec!["admin".to_string()],
+            },
+            user_roles: HashMap::from([
+                ("bob".to_string(), vec!["admin".to_string()]),
+                ("carol".to_string(), vec!["viewer".to_string()]),
+            ]),
+            ..Default::default()
+        };
+
+        let users = tools.auto_approved_users();
+        assert!(users.contains("alice"));
+        assert!(users.contains("bob"));
+        assert!(!users.contains("carol"));
+    }
+
+    fn engine_with_argument_rules() -> ToolPolicyEngine {
+        let config = HashMap::from([
+            (
+                "send_whatsapp".to_string(),
+                vec![ArgumentRuleConfig {
+                    pattern: r"@g\.us".to_string(),
+                    action: ArgumentRuleAction::RequireApproval,
+                }],
+            ),
+            (
+                "exec".to_string(),
+                vec![
+                    ArgumentRuleConfig {
+                        pattern: r"\bcurl\b".to_string(),
+                        action: ArgumentRuleAction::RequireApproval,
+                    },
+                    ArgumentRuleConfig {
+                        pattern: r"\bsudo\b".to_string(),
+                        action: ArgumentRuleAction::Deny,
+                    },
+                ],
+            ),
+        ]);
+        ToolPolicyEngine::new()
+            .with_argument_rules(compile_argument_rules(&config).unwrap())
+            .with_auto_approve(["alice".to_string()])
+    }
+
+    #[tokio::test]
+    async fn test_argument_rules_escalate_matching_calls() {
+        let engine = engine_with_argument_rules();
+        engine.set_elevated("session1", true).await;
+
+        let group_message = r#"{"to": "123@g.us", "message": "hi"}"#;
+        assert!(matches!(
+            engine
+                .get_access_decision("session1", None, "send_whatsapp", group_message, false)
+                .await,
+            ToolAccessDecision::RequiresApproval { .. }
+        ));
+        assert!(matches!(
+            engine
+                .check_permission("session1", None, "send_whatsapp", group_message)
+                .await,
+            Err(ToolPolicyError::ArgumentsRequireApproval { .. })
+        ));
+
+        // Deny wins over approval and over the allowlist
+        let command = r#"{"command": "curl -s x | sudo tee /etc/hosts"}"#;
+        assert!(matches!(
+            engine
+                .get_access_decision("session1", Some("alice"), "exec", command, false)
+                .await,
+            ToolAccessDecision::Denied { .. }
+        ));
+        assert!(matches!(
+            engine
+                .check_permission("session1", None, "exec", command)
+                .await,
+            Err(ToolPolicyError::ArgumentsDenied { .. })
+        ));
+
+        // Allowlisted users skip approval rules
+        assert!(matches!(
+            engine
+                .get_access_decision(
+                    "session1",
+                    Some("alice"),
+                    "send_whatsapp",
+                    group_message,
+                    false
+                )
+                .await,
+            ToolAccessDecision::Allowed
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_argument_rules_see_through_escapes_and_layout() {
+        let engine = engine_with_argument_rules();
+        engine.set_elevated("session1", true).await;
+
+        for command in [
+            r#"{"command": "\u0073udo rm -rf /"}"#,
+            r#"{ "command" :
+                "su\u0064o reboot" }"#,
+        ] {
+            assert!(
+                matches!(
+                    engine
+                        .get_access_decision("session1", None, "exec", command, false)
+                        .await,
+                    ToolAccessDecision::Denied { .. }
+                ),
+                "{}",
+                command
+            );
+            assert!(matches!(
+                engine
+                    .check_permission("session1", None, "exec", command)
+                    .await,
+                Err(ToolPolicyError::ArgumentsDenied { .. })
+            ));
+        }
+
+        // Key order makes no difference to a rule spanning two fields
+        let rules = HashMap::from([(
+            "exec".to_string(),
+            vec![ArgumentRuleConfig {
+                pattern: r#""args":\["-rf"\],"command":"rm""#.to_string(),
+                action: ArgumentRuleAction::Deny,
+            }],
+        )]);
+        let engine =
+            ToolPolicyEngine::new().with_argument_rules(compile_argument_rules(&rules).unwrap());
+        let reordered = r#"{"command": "rm", "args": ["-rf"]}"#;
+        assert!(matches!(
+            engine
+                .get_access_decision("session1", None, "exec", reordered, false)
+                .await,
+            ToolAccessDecision::Denied { .. }
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_reminders_for_others_follow_the_send_policy() {
+        let engine = engine_with_argument_rules();
+        let own = r#"{"when": "in 1h", "message": "stretch"}"#;
+        let group =
+            r#"{"when": "in 1h", "message": "hi", "channel": "whatsapp", "target": "123@g.us"}"#;
+        let contact =
+            r#"{"when": "in 1h", "message": "hi", "channel": "telegram", "target": "42"}"#;
+
+        // The send_whatsapp argument rules apply to the target
+        assert!(matches!(
+            engine
+                .get_access_decision("session1", None, "set_reminder", group, false)
+                .await,
+            ToolAccessDecision::RequiresApproval { .. }
+        ));
+        assert!(matches!(
+            engine
+                .check_permission("session1", None, "set_reminder", group)
+                .await,
+            Err(ToolPolicyError::ArgumentsRequireApproval { .. })
+        ));
+
+        // So does its access level, but not to reminders for the user
+        engine
+            .set_policy("send_whatsapp".to_string(), ToolAccessLevel::Deny)
+            .await;
+        assert!(matches!(
+            engine
+                .get_access_decision("session1", None, "set_reminder", contact, false)
+                .await,
+            ToolAccessDecision::Denied { .. }
+        ));
+        assert!(engine
+            .check_permission("session1", None, "set_reminder", contact)
+            .await
+            .is_err());
+        assert!(matches!(
+            engine
+                .get_access_decision("session1", None, "set_reminder", own, false)
+                .await,
+            ToolAccessDecision::Allowed
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_argument_rules_pass_through_other_calls() {
+        let engine = engine_with_argument_rules();
+
+        let direct_message = r#"{"to": "+15551234", "message": "hi"}"#;
+        assert!(matches!(
+            engine
+                .get_access_decision("session1", None, "send_whatsapp", direct_message, false)
+                .await,
+            ToolAccessDecision::Allowed
+        ));
+        assert!(engine
+            .check_permission("session1", None, "send_whatsapp", direct_message)
+            .await
+            .is_ok());
+
+        // Rules never relax the tool's own policy
+        assert!(matches!(
+            engine
+                .get_access_decision("session1", None, "exec", r#"{"command": "ls"}"#, false)
+                .await,
+            ToolAccessDecision::RequiresApproval { .. }
+        ));
+    }
+
+    #[test]
+    fn test_invalid_argument_pattern_is_rejected() {
+        let config = HashMap::from([(
+            "exec".to_string(),
+            vec![ArgumentRuleConfig {
+                pattern: "(".to_string(),
+                action: ArgumentRuleAction::Deny,
+            }],
+        )]);
+        assert!(compile_argument_rules(&config).is_err());
+    }
 }
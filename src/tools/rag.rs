@@ -0,0 +1,633 @@
+//! Knowledge document ingestion and the `search_docs` tool
+//!
+//! Text, Markdown and PDF files in `tools.knowledge.dir` are split into
+//! chunks, embedded with `tools.knowledge.embedding_model` and stored in
+//! SQLite. Searches rank chunks by embedding similarity, or by keyword
+//! overlap when no embeddings are available.
+
+use crate::config::KnowledgeConfig;
+use crate::core::tool_selector::cosine_similarity;
+use crate::llm::{Client as LlmClient, ToolDefinition};
+use crate::storage::sqlite::SqliteStorage;
+use crate::storage::{DocumentChunk, Storage};
+use anyhow::{anyhow, Context, Result};
+use notify_debouncer_mini::new_debouncer;
+use once_cell::sync::OnceCell;
+use serde::Deserialize;
+use serde_json::json;
+use std::collections::{HashMap, HashSet};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::{Duration, UNIX_EPOCH};
+use tracing::{info, warn};
+
+static KNOWLEDGE_BASE: OnceCell<Arc<KnowledgeBase<SqliteStorage>>> = OnceCell::new();
+
+/// Quiet period before a changed document is re-ingested
+const DEBOUNCE_MS: u64 = 500;
+
+/// Chunks embedded per request
+const EMBED_BATCH_SIZE: usize = 64;
+
+/// File extensions that are ingested
+const SUPPORTED_EXTENSIONS: &[&str] = &["txt", "md", "markdown", "pdf"];
+
+/// Register the knowledge base used by the `search_docs` tool
+pub fn init_knowledge_base(knowledge: Arc<KnowledgeBase<SqliteStorage>>) {
+    KNOWLEDGE_BASE.set(knowledge).ok();
+}
+
+/// Get the global knowledge base, if knowledge ingestion is enabled
+pub fn get_knowledge_base() -> Option<Arc<KnowledgeBase<SqliteStorage>>> {
+    KNOWLEDGE_BASE.get().cloned()
+}
+
+/// A chunk matching a search
+#[derive(Debug, Clone)]
+pub struct SearchHit {
+    pub source: String,
+    pub chunk_index: usize,
+    pub content: String,
+    pub score: f32,
+}
+
+/// Ingested documents of the knowledge directory
+pub struct KnowledgeBase<S: Storage> {
+    storage: S,
+    llm_client: LlmClient,
+    config: KnowledgeConfig,
+    dir: PathBuf,
+}
+
+impl<S: Storage> KnowledgeBase<S> {
+    pub fn new(storage: S, llm_client: LlmClient, config: KnowledgeConfig) -> Self {
+        let dir = PathBuf::from(&config.dir);
+        Self {
+            storage,
+            llm_client,
+            config,
+            dir,
+        }
+    }
+
+    /// Ingest new and changed documents and forget removed ones.
+    ///
+    /// Returns the number of documents (re-)ingested.
+    pub async fn sync(&self) -> Result<usize> {
+        let mut stored: HashMap<String, String> = HashMap::new();
+        for chunk in self.storage.list_document_chunks().await? {
+            stored.insert(chunk.source, chunk.fingerprint);
+        }
+
+        let mut seen = HashSet::new();
+        let mut ingested = 0;
+        for path in list_documents(&self.dir) {
+            let Some(source) = self.source_name(&path) else {
+                continue;
+            };
+            seen.insert(source.clone());
+
+            let fingerprint = match fingerprint(&path) {
+                Ok(fingerprint) => fingerprint,
+                Err(e) => {
+                    warn!("Skipping document {}: {}", path.display(), e);
+                    continue;
+                }
+            };
+            if stored.get(&source) == Some(&fingerprint) {
+                continue;
+            }
+
+            match self.ingest_file(&path).await {
+                Ok(true) => ingested += 1,
+                Ok(false) => {}
+                Err(e) => warn!("Failed to ingest {}: {:#}", path.display(), e),
+            }
+        }
+
+        for source in stored.keys().filter(|source| !seen.contains(*source)) {
+            self.storage.delete_document_chunks(source).await?;
+            info!("Removed document {} from the knowledge base", source);
+        }
+
+        Ok(ingested)
+    }
+
+    /// Chunk, embed and store a document, replacing its previous chunks.
+    ///
+    /// Returns false for files that are not ingested (unsupported or hidden).
+    pub async fn ingest_file(&self, path: &Path) -> Result<bool> {
+        let Some(source) = self.source_name(path).filter(|_| is_supported(path)) else {
+            return Ok(false);
+        };
+
+        let fingerprint = fingerprint(path)?;
+        let text = extract_text(path).await?;
+        let contents = chunk_text(&text, self.config.chunk_size);
+        let mut embeddings = self.embed(&contents).await.map(Vec::into_iter);
+
+        let chunks: Vec<DocumentChunk> = contents
+            .into_iter()
+            .enumerate()
+            .map(|(chunk_index, content)| DocumentChunk {
+                source: source.clone(),
+                chunk_index,
+                content,
+                embedding: embeddings.as_mut().and_then(Iterator::next),
+                fingerprint: fingerprint.clone(),
+            })
+            .collect();
+
+        info!(
+            "Ingested document {} ({} chunks{})",
+            source,
+            chunks.len(),
+            if embeddings.is_some() {
+                ""
+            } else {
+                ", keyword search only"
+            }
+        );
+        self.storage
+            .replace_document_chunks(&source, chunks)
+            .await?;
+        Ok(true)
+    }
+
+    /// Forget a removed document
+    pub async fn remove_file(&self, path: &Path) -> Result<()> {
+        if let Some(source) = self.source_name(path) {
+            self.storage.delete_document_chunks(&source).await?;
+            info!("Removed document {} from the knowledge base", source);
+        }
+        Ok(())
+    }
+
+    /// The `top_k` chunks most relevant to `query`
+    pub async fn search(&self, query: &str, top_k: usize) -> Result<Vec<SearchHit>> {
+        let chunks = self.storage.list_document_chunks().await?;
+        if chunks.is_empty() {
+            return Ok(vec![]);
+        }
+
+        let mut hits = Vec::new();
+        if chunks.iter().any(|chunk| chunk.embedding.is_some()) {
+            if let Some(query_embedding) = self
+                .embed(&[query.to_string()])
+                .await
+                .and_then(|vectors| vectors.into_iter().next())
+            {
+                hits = rank(chunks.iter(), |chunk| {
+                    chunk
+                        .embedding
+                        .as_ref()
+                        .filter(|embedding| embedding.len() == query_embedding.len())
+                        .map(|embedding| cosine_similarity(&query_embedding, embedding))
+                });
+            }
+        }
+
+        if hits.is_empty() {
+            let terms = words(query);
+            hits = rank(chunks.iter(), |chunk| {
+                Some(keyword_score(&terms, &chunk.content)).filter(|score| *score > 0.0)
+            });
+        }
+
+        hits.truncate(top_k);
+        Ok(hits)
+    }
+
+    /// Embed texts with the configured model; `None` when embeddings are
+    /// disabled or unavailable
+    async fn embed(&self, texts: &[String]) -> Option<Vec<Vec<f32>>> {
+        let model = self.config.embedding_model.as_deref()?;
+
+        let mut vectors = Vec::with_capacity(texts.len());
+        for batch in texts.chunks(EMBED_BATCH_SIZE) {
+            match self.llm_client.embed(model, batch.to_vec()).await {
+                Ok(batch_vectors) if batch_vectors.len() == batch.len() => {
+                    vectors.extend(batch_vectors)
+                }
+                Ok(_) => {
+                    warn!("Embedding count mismatch, falling back to keyword search");
+                    return None;
+                }
+                Err(e) => {
+                    warn!("Embedding failed, falling back to keyword search: {}", e);
+                    return None;
+                }
+            }
+        }
+        Some(vectors)
+    }
+
+    /// Path of a document relative to the knowledge directory
+    fn source_name(&self, path: &Path) -> Option<String> {
+        // Watcher events may carry canonicalized paths
+        let canonical_dir = self.dir.canonicalize().ok();
+        let relative = path.strip_prefix(&self.dir).ok().or_else(|| {
+            canonical_dir
+                .as_deref()
+                .and_then(|dir| path.strip_prefix(dir).ok())
+        })?;
+
+        let hidden = relative
+            .components()
+            .any(|c| c.as_os_str().to_string_lossy().starts_with('.'));
+        (!hidden).then(|| relative.to_string_lossy().replace('\\', "/"))
+    }
+}
+
+/// Ingest the knowledge directory, then keep it in sync with file changes
+pub async fn watch<S: Storage + 'static>(knowledge: Arc<KnowledgeBase<S>>) -> Result<()> {
+    let dir = knowledge.dir.clone();
+    if !dir.exists() {
+        std::fs::create_dir_all(&dir)
+            .with_context(|| format!("Failed to create knowledge directory: {}", dir.display()))?;
+        info!("Created knowledge directory: {}", dir.display());
+    }
+
+    let ingested = knowledge.sync().await?;
+    info!(
+        "Knowledge base ready ({} documents ingested from {})",
+        ingested,
+        dir.display()
+    );
+
+    let (tx, mut rx) = tokio::sync::mpsc::unbounded_channel();
+    let mut debouncer = new_debouncer(
+        Duration::from_millis(DEBOUNCE_MS),
+        move |res: notify_debouncer_mini::DebounceEventResult| {
+            let _ = tx.send(res);
+        },
+    )
+    .context("Failed to create debouncer")?;
+    debouncer
+        .watcher()
+        .watch(&dir, notify::RecursiveMode::Recursive)
+        .context("Failed to watch knowledge directory")?;
+
+    while let Some(event_result) = rx.recv().await {
+        match event_result {
+            Ok(events) => {
+                for event in events {
+                    if !is_supported(&event.path) {
+                        continue;
+                    }
+                    let result = if event.path.exists() {
+                        knowledge.ingest_file(&event.path).await.map(|_| ())
+                    } else {
+                        knowledge.remove_file(&event.path).await
+                    };
+                    if let Err(e) = result {
+                        warn!("Failed to update {}: {:#}", event.path.display(), e);
+                    }
+                }
+            }
+            Err(e) => warn!("Knowledge watcher error: {}", e),
+        }
+    }
+
+    Ok(())
+}
+
+/// Supported documents below `dir`, skipping hidden entries
+fn list_documents(dir: &Path) -> Vec<PathBuf> {
+    let mut documents = Vec::new();
+    let mut pending = vec![dir.to_path_buf()];
+
+    while let Some(current) = pending.pop() {
+        let entries = match std::fs::read_dir(&current) {
+            Ok(entries) => entries,
+            Err(e) => {
+                warn!("Failed to read {}: {}", current.display(), e);
+                continue;
+            }
+        };
+        for entry in entries.flatten() {
+            let path = entry.path();
+            if entry.file_name().to_string_lossy().starts_with('.') {
+                continue;
+            }
+            if path.is_dir() {
+                pending.push(path);
+            } else if is_supported(&path) {
+                documents.push(path);
+            }
+        }
+    }
+
+    documents.sort();
+    documents
+}
+
+fn is_supported(path: &Path) -> bool {
+    path.extension()
+        .and_then(|ext| ext.to_str())
+        .map(|ext| SUPPORTED_EXTENSIONS.contains(&ext.to_ascii_lowercase().as_str()))
+        .unwrap_or(false)
+}
+
+/// Size and modification time of a file, used to skip unchanged documents
+fn fingerprint(path: &Path) -> Result<String> {
+    let metadata = std::fs::metadata(path)?;
+    let modified = metadata
+        .modified()?
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default();
+    Ok(format!("{}-{}", metadata.len(), modified.as_nanos()))
+}
+
+/// Plain text of a document; PDFs are converted with `pdftotext`
+async fn extract_text(path: &Path) -> Result<String> {
+    let is_pdf = path
+        .extension()
+        .is_some_and(|ext| ext.eq_ignore_ascii_case("pdf"));
+    if !is_pdf {
+        return tokio::fs::read_to_string(path)
+            .await
+            .with_context(|| format!("Failed to read {}", path.display()));
+    }
+
+    let output = tokio::process::Command::new("pdftotext")
+        .args(["-q", "-enc", "UTF-8"])
+        .arg(path)
+        .arg("-")
+        .kill_on_drop(true)
+        .output()
+        .await
+        .context("pdftotext (poppler-utils) is required to ingest PDF files")?;
+    if !output.status.success() {
+        return Err(anyhow!("pdftotext exited with {}", output.status));
+    }
+    Ok(String::from_utf8_lossy(&output.stdout).into_owned())
+}
+
+/// Split text into chunks of at most `chunk_size` characters, keeping
+/// paragraphs together where possible
+fn chunk_text(text: &str, chunk_size: usize) -> Vec<String> {
+    let chunk_size = chunk_size.max(1);
+    let mut chunks = Vec::new();
+    let mut current = String::new();
+
+    for paragraph in text.split("\n\n").map(str::trim).filter(|p| !p.is_empty()) {
+        let len = paragraph.chars().count();
+        if !current.is_empty() && current.chars().count() + 2 + len > chunk_size {
+            chunks.push(std::mem::take(&mut current));
+        }
+
+        if len > chunk_size {
+            let chars: Vec<char> = paragraph.chars().collect();
+            chunks.extend(chars.chunks(chunk_size).map(|c| c.iter().collect()));
+            continue;
+        }
+
+        if !current.is_empty() {
+            current.push_str("\n\n");
+        }
+        current.push_str(paragraph);
+    }
+
+    if !current.is_empty() {
+        chunks.push(current);
+    }
+    chunks
+}
+
+/// Lowercase words of a text, ignoring words of one or two letters
+fn words(text: &str) -> HashSet<String> {
+    text.split(|c: char| !c.is_alphanumeric())
+        .filter(|word| word.chars().count() > 2)
+        .map(str::to_lowercase)
+        .collect()
+}
+
+/// Share of query words found in the content
+fn keyword_score(terms: &HashSet<String>, content: &str) -> f32 {
+    if terms.is_empty() {
+        return 0.0;
+    }
+    let content = words(content);
+    terms.intersection(&content).count() as f32 / terms.len() as f32
+}
+
+/// Chunks with a score, best first
+fn rank<'a>(
+    chunks: impl Iterator<Item = &'a DocumentChunk>,
+    score: impl Fn(&DocumentChunk) -> Option<f32>,
+) -> Vec<SearchHit> {
+    let mut hits: Vec<SearchHit> = chunks
+        .filter_map(|chunk| {
+            score(chunk).map(|score| SearchHit {
+                source: chunk.source.clone(),
+                chunk_index: chunk.chunk_index,
+                content: chunk.content.clone(),
+                score,
+            })
+        })
+        .collect();
+    hits.sort_by(|a, b| b.score.total_cmp(&a.score));
+    hits
+}
+
+/// Search results with numbered source citations
+fn format_hits(hits: &[SearchHit]) -> String {
+    if hits.is_empty() {
+        return "No matching documents found.".to_string();
+    }
+
+    hits.iter()
+        .enumerate()
+        .map(|(i, hit)| {
+            format!(
+                "[{}] {} (chunk {})\n{}",
+                i + 1,
+                hit.source,
+                hit.chunk_index + 1,
+                hit.content
+            )
+        })
+        .collect::<Vec<_>>()
+        .join("\n\n")
+}
+
+/// Parameters for search_docs
+#[derive(Debug, Deserialize)]
+pub struct SearchDocsParams {
+    pub query: String,
+    #[serde(default)]
+    pub top_k: Option<usize>,
+}
+
+/// Run the `search_docs` tool
+pub async fn search_docs(params: SearchDocsParams) -> Result<String> {
+    let knowledge = get_knowledge_base().ok_or_else(|| anyhow!("Knowledge base is not enabled"))?;
+    let top_k = params.top_k.unwrap_or(knowledge.config.top_k).clamp(1, 20);
+    let hits = knowledge.search(&params.query, top_k).await?;
+    Ok(format_hits(&hits))
+}
+
+pub fn get_rag_tool_definitions() -> Vec<ToolDefinition> {
+    vec![ToolDefinition {
+        name: "search_docs".to_string(),
+        description: "Search the user's knowledge documents. Returns the most relevant passages, each with a numbered [n] citation of its source file; cite sources when using them.".to_string(),
+        parameters: json!({
+            "type": "object",
+            "properties": {
+                "query": {
+                    "type": "string",
+                    "description": "What to look for"
+                },
+                "top_k": {
+                    "type": "integer",
+                    "description": "Number of passages to return (1-20)"
+                }
+            },
+            "required": ["query"]
+        }),
+    }]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::{CacheConfig, LlmConfig, LlmModels};
+
+    fn llm_client(base_url: String) -> LlmClient {
+        LlmClient::new(&LlmConfig {
+            provider: "ollama".to_string(),
+            base_url,
+            models: LlmModels {
+                primary: "model".to_string(),
+                code: None,
+                fast: None,
+                roles: Default::default(),
+            },
+            keep_alive: None,
+            cache: CacheConfig {
+                cache_type: "ram".to_string(),
+                max_models: 1,
+                eviction: "lru".to_string(),
+            },
+            routing: None,
+            context_windows: Default::default(),
+            tool_support: Default::default(),
+            tokenizers: Default::default(),
+            coalesce_requests: false,
+            circuit_breaker: Default::default(),
+            warm_up: false,
+        })
+        .unwrap()
+    }
+
+    async fn knowledge_base(
+        dir: &Path,
+        base_url: String,
+        embedding_model: Option<&str>,
+    ) -> KnowledgeBase<SqliteStorage> {
+        KnowledgeBase::new(
+            SqliteStorage::new(":memory:").await.unwrap(),
+            llm_client(base_url),
+            KnowledgeConfig {
+                enabled: true,
+                dir: dir.to_string_lossy().to_string(),
+                embedding_model: embedding_model.map(String::from),
+                chunk_size: 200,
+                top_k: 3,
+            },
+        )
+    }
+
+    #[test]
+    fn test_chunk_text_keeps_paragraphs_together() {
+        let text = "First paragraph.\n\nSecond paragraph.\n\n\n\nThird one is longer than ten.";
+
+        assert_eq!(
+            chunk_text(text, 40),
+            vec![
+                "First paragraph.\n\nSecond paragraph.",
+                "Third one is longer than ten."
+            ]
+        );
+        assert_eq!(chunk_text("abcdefgh", 3), vec!["abc", "def", "gh"]);
+        assert!(chunk_text("  \n\n ", 10).is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_keyword_search_without_embeddings() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::create_dir(dir.path().join("ops")).unwrap();
+        std::fs::create_dir(dir.path().join(".drafts")).unwrap();
+        std::fs::write(
+            dir.path().join("ops/backups.md"),
+            "# Backups\n\nThe backup schedule runs nightly at 02:00.",
+        )
+        .unwrap();
+        std::fs::write(dir.path().join("lunch.txt"), "Lunch is served at noon.").unwrap();
+        std::fs::write(dir.path().join(".drafts/secret.md"), "backup secret").unwrap();
+        std::fs::write(dir.path().join("image.png"), "not text").unwrap();
+
+        // Nothing listens here: embedding fails and search uses keywords
+        let knowledge =
+            knowledge_base(dir.path(), "http://127.0.0.1:9".to_string(), Some("embed")).await;
+        assert_eq!(knowledge.sync().await.unwrap(), 2);
+        // Unchanged files are not ingested again
+        assert_eq!(knowledge.sync().await.unwrap(), 0);
+
+        let hits = knowledge
+            .search("When is the backup schedule?", 3)
+            .await
+            .unwrap();
+        assert_eq!(hits.len(), 1);
+        assert_eq!(hits[0].source, "ops/backups.md");
+        assert_eq!(hits[0].score, 0.75);
+        assert!(format_hits(&hits).starts_with("[1] ops/backups.md (chunk 1)\n"));
+
+        std::fs::remove_file(dir.path().join("ops/backups.md")).unwrap();
+        knowledge.sync().await.unwrap();
+        assert!(knowledge.search("backup", 3).await.unwrap().is_empty());
+        assert_eq!(
+            format_hits(&knowledge.search("noon", 3).await.unwrap()),
+            "[1] lunch.txt (chunk 1)\nLunch is served at noon."
+        );
+    }
+
+    #[tokio::test]
+    async fn test_search_ranks_by_embedding_similarity() {
+        let mut server = mockito::Server::new_async().await;
+        let mut embedding = |text: &str, vector: [f32; 2]| {
+            let body = json!({
+                "object": "list",
+                "model": "embed",
+                "data": [{"object": "embedding", "index": 0, "embedding": vector}],
+                "usage": {"prompt_tokens": 1, "total_tokens": 1}
+            });
+            server
+                .mock("POST", "/embeddings")
+                .match_body(mockito::Matcher::PartialJson(json!({ "input": [text] })))
+                .with_header("content-type", "application/json")
+                .with_body(body.to_string())
+        };
+        let _cats = embedding("Cats purr when content.", [1.0, 0.0])
+            .create_async()
+            .await;
+        let _dogs = embedding("Dogs wag their tails.", [0.0, 1.0])
+            .create_async()
+            .await;
+        let _query = embedding("feline sounds", [0.9, 0.1]).create_async().await;
+
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::write(dir.path().join("cats.md"), "Cats purr when content.").unwrap();
+        std::fs::write(dir.path().join("dogs.md"), "Dogs wag their tails.").unwrap();
+
+        let knowledge = knowledge_base(dir.path(), server.url(), Some("embed")).await;
+        assert_eq!(knowledge.sync().await.unwrap(), 2);
+
+        // No shared keywords: only the embeddings can rank these
+        let hits = knowledge.search("feline sounds", 2).await.unwrap();
+        assert_eq!(hits.len(), 2);
+        assert_eq!(hits[0].source, "cats.md");
+        assert!(hits[0].score > hits[1].score);
+    }
+}
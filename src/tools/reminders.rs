@@ -0,0 +1,536 @@
+//! Reminders and the `set_reminder`, `list_reminders` and `cancel_reminder`
+//! tools
+//!
+//! Reminders are stored until delivered, so pending ones survive restarts;
+//! a dispatcher polls for due reminders and sends them to their channel.
+
+use crate::channels::DELIVERY_CHANNELS;
+use crate::config::Config;
+use crate::core::scheduler::parse_timezone;
+use crate::llm::ToolDefinition;
+use crate::storage::sqlite::SqliteStorage;
+use crate::storage::{Reminder, Storage};
+use anyhow::{anyhow, bail, Result};
+use chrono::{DateTime, Duration, NaiveDateTime, NaiveTime, TimeZone, Utc};
+use once_cell::sync::OnceCell;
+use serde::Deserialize;
+use serde_json::json;
+use std::future::Future;
+use std::sync::Arc;
+use tokio::sync::RwLock;
+
+static REMINDERS: OnceCell<Arc<Reminders<SqliteStorage>>> = OnceCell::new();
+
+/// Seconds between checks for due reminders
+const DISPATCH_INTERVAL_SECS: u64 = 15;
+
+/// Furthest ahead a reminder may be set
+const MAX_REMINDER_DAYS: i64 = 366;
+
+/// Longest reminder message
+const MAX_REMINDER_CHARS: usize = 2000;
+
+/// Register the reminders used by the reminder tools
+pub fn init_reminders(reminders: Arc<Reminders<SqliteStorage>>) {
+    REMINDERS.set(reminders).ok();
+}
+
+/// Get the global reminders, once storage is initialized
+pub fn get_reminders() -> Option<Arc<Reminders<SqliteStorage>>> {
+    REMINDERS.get().cloned()
+}
+
+/// Parameters for set_reminder
+#[derive(Debug, Deserialize)]
+pub struct SetReminderParams {
+    pub when: String,
+    pub message: String,
+    #[serde(default)]
+    pub channel: Option<String>,
+    #[serde(default)]
+    pub target: Option<String>,
+}
+
+/// Parameters for cancel_reminder
+#[derive(Debug, Deserialize)]
+pub struct CancelReminderParams {
+    pub id: String,
+}
+
+/// Reminders of all users, backed by storage
+pub struct Reminders<S: Storage> {
+    storage: S,
+}
+
+impl<S: Storage> Reminders<S> {
+    pub fn new(storage: S) -> Self {
+        Self { storage }
+    }
+
+    /// Store a reminder for the user of a session. Without a channel and
+    /// target it is sent back to the user on the session's channel.
+    pub async fn set(
+        &self,
+        session_id: &str,
+        params: SetReminderParams,
+        now: DateTime<Utc>,
+    ) -> Result<Reminder> {
+        let session = self
+            .storage
+            .get_session(session_id)
+            .await?
+            .ok_or_else(|| anyhow!("Session not found"))?;
+
+        let message = params.message.trim().to_string();
+        if message.is_empty() {
+            bail!("The reminder message is empty");
+        }
+        if message.chars().count() > MAX_REMINDER_CHARS {
+            bail!(
+                "Reminder messages are limited to {} characters",
+                MAX_REMINDER_CHARS
+            );
+        }
+
+        let timezone = crate::core::locale::timezone_for(Some(&session.user_id));
+        let due_at = parse_when(&params.when, now, timezone.as_deref())?;
+        if due_at <= now {
+            bail!("'{}' is in the past", params.when);
+        }
+        if due_at > now + Duration::days(MAX_REMINDER_DAYS) {
+            bail!(
+                "Reminders can be set at most {} days ahead",
+                MAX_REMINDER_DAYS
+            );
+        }
+
+        let channel = params.channel.unwrap_or_else(|| session.channel.clone());
+        if !DELIVERY_CHANNELS.contains(&channel.as_str()) {
+            bail!(
+                "Cannot deliver reminders to '{}'. Supported channels: {}",
+                channel,
+                DELIVERY_CHANNELS.join(", ")
+            );
+        }
+        let target = match params.target.filter(|t| !t.trim().is_empty()) {
+            Some(target) => target.trim().to_string(),
+            None if channel == session.channel => {
+                crate::core::identity::native_id(&channel, &session.user_id)
+            }
+            None => bail!("A target is required when reminding on another channel"),
+        };
+
+        let reminder = Reminder {
+            id: uuid::Uuid::new_v4().to_string(),
+            user_id: session.user_id,
+            message,
+            channel,
+            target,
+            due_at,
+            created_at: now,
+            delivered_at: None,
+        };
+        self.storage.create_reminder(reminder.clone()).await?;
+        Ok(reminder)
+    }
+
+    /// Pending reminders of the user of a session
+    pub async fn list(&self, session_id: &str) -> Result<Vec<Reminder>> {
+        let user_id = self.session_user(session_id).await?;
+        self.storage.list_reminders(&user_id).await
+    }
+
+    /// Cancel a pending reminder of the user of a session
+    pub async fn cancel(&self, session_id: &str, id: &str) -> Result<bool> {
+        let user_id = self.session_user(session_id).await?;
+        self.storage.cancel_reminder(&user_id, id).await
+    }
+
+    /// Send every reminder due at `now`, marking each delivered once sent.
+    /// Failed sends stay pending and are retried on the next call. Returns
+    /// the number delivered.
+    pub async fn deliver_due<F, Fut>(&self, now: DateTime<Utc>, send: F) -> Result<usize>
+    where
+        F: Fn(Reminder) -> Fut,
+        Fut: Future<Output = Result<()>>,
+    {
+        let mut delivered = 0;
+        for reminder in self.storage.due_reminders(now).await? {
+            let id = reminder.id.clone();
+            match send(reminder).await {
+                Ok(()) => {
+                    self.storage.mark_reminder_delivered(&id, now).await?;
+                    delivered += 1;
+                }
+                Err(e) => tracing::warn!("Failed to deliver reminder {}: {}", id, e),
+            }
+        }
+        Ok(delivered)
+    }
+
+    async fn session_user(&self, session_id: &str) -> Result<String> {
+        self.storage
+            .get_session(session_id)
+            .await?
+            .map(|session| session.user_id)
+            .ok_or_else(|| anyhow!("Session not found"))
+    }
+}
+
+/// Deliver due reminders until the process exits
+pub async fn run_dispatcher<S: Storage>(reminders: Arc<Reminders<S>>, config: Arc<RwLock<Config>>) {
+    let mut interval =
+        tokio::time::interval(std::time::Duration::from_secs(DISPATCH_INTERVAL_SECS));
+    interval.set_missed_tick_behavior(tokio::time::MissedTickBehavior::Delay);
+
+    loop {
+        interval.tick().await;
+        let config = config.read().await.clone();
+        let result = reminders
+            .deliver_due(Utc::now(), |reminder| {
+                let config = &config;
+                async move {
+                    let text = format!("⏰ Reminder: {}", reminder.message);
+                    crate::channels::deliver(config, &reminder.channel, &reminder.target, &text)
+                        .await
+                }
+            })
+            .await;
+        if let Err(e) = result {
+            tracing::error!("Reminder dispatch failed: {}", e);
+        }
+    }
+}
+
+/// Parse a relative ("in 2 hours", "in 1h 30m") or absolute time. Absolute
+/// times without an offset ("2025-06-01 09:00", "18:30") are read in
+/// `timezone`, defaulting to UTC; a bare time of day means its next
+/// occurrence.
+pub fn parse_when(when: &str, now: DateTime<Utc>, timezone: Option<&str>) -> Result<DateTime<Utc>> {
+    let text = when.trim();
+    if let Some(relative) = text.to_lowercase().strip_prefix("in ") {
+        return Ok(now + parse_duration(relative)?);
+    }
+    if let Ok(time) = DateTime::parse_from_rfc3339(text) {
+        return Ok(time.with_timezone(&Utc));
+    }
+
+    let timezone = timezone
+        .map(parse_timezone)
+        .transpose()?
+        .unwrap_or(chrono_tz::UTC);
+    for format in [
+        "%Y-%m-%d %H:%M",
+        "%Y-%m-%dT%H:%M",
+        "%Y-%m-%d %H:%M:%S",
+        "%Y-%m-%dT%H:%M:%S",
+    ] {
+        if let Ok(local) = NaiveDateTime::parse_from_str(text, format) {
+            return local_to_utc(timezone, local);
+        }
+    }
+    if let Ok(time) = NaiveTime::parse_from_str(text, "%H:%M") {
+        let today = now.with_timezone(&timezone).date_naive();
+        let at = local_to_utc(timezone, today.and_time(time))?;
+        if at > now {
+            return Ok(at);
+        }
+        return local_to_utc(timezone, (today + Duration::days(1)).and_time(time));
+    }
+
+    bail!(
+        "Unrecognized time '{}'. Use e.g. \"in 2 hours\", \"2025-06-01 09:00\" or \"18:30\"",
+        text
+    )
+}
+
+fn local_to_utc(timezone: chrono_tz::Tz, local: NaiveDateTime) -> Result<DateTime<Utc>> {
+    timezone
+        .from_local_datetime(&local)
+        .earliest()
+        .map(|time| time.with_timezone(&Utc))
+        .ok_or_else(|| anyhow!("{} does not exist in {}", local, timezone))
+}
+
+/// Parse "2 hours", "1h 30m", "a day and 3 hours"
+fn parse_duration(text: &str) -> Result<Duration> {
+    let invalid = || anyhow!("Unrecognized duration '{}'", text.trim());
+    let mut tokens = text
+        .split(|c: char| c.is_whitespace() || c == ',')
+        .filter(|token| !token.is_empty() && *token != "and");
+
+    let mut total = Duration::zero();
+    let mut any = false;
+    while let Some(token) = tokens.next() {
+        let digits = token
+            .find(|c: char| !c.is_ascii_digit())
+            .unwrap_or(token.len());
+        let (amount, unit) = match (&token[..digits], &token[digits..]) {
+            ("", "a" | "an") => (1, tokens.next().ok_or_else(invalid)?),
+            ("", _) => return Err(invalid()),
+            (amount, "") => (
+                amount.parse::<i64>().map_err(|_| invalid())?,
+                tokens.next().ok_or_else(invalid)?,
+            ),
+            (amount, unit) => (amount.parse::<i64>().map_err(|_| invalid())?, unit),
+        };
+        let unit = match unit {
+            "s" | "sec" | "secs" | "second" | "seconds" => Duration::seconds(1),
+            "m" | "min" | "mins" | "minute" | "minutes" => Duration::minutes(1),
+            "h" | "hr" | "hrs" | "hour" | "hours" => Duration::hours(1),
+            "d" | "day" | "days" => Duration::days(1),
+            "w" | "week" | "weeks" => Duration::weeks(1),
+            _ => return Err(invalid()),
+        };
+        let amount = i32::try_from(amount).map_err(|_| invalid())?;
+        total += unit * amount;
+        any = true;
+    }
+
+    if !any {
+        return Err(invalid());
+    }
+    Ok(total)
+}
+
+/// Run the `set_reminder` tool
+pub async fn set_reminder(session_id: Option<&str>, params: SetReminderParams) -> Result<String> {
+    let (reminders, session_id) = tool_context(session_id)?;
+    let reminder = reminders.set(session_id, params, Utc::now()).await?;
+    Ok(format!(
+        "Reminder {} set for {} on {}.",
+        reminder.id,
+        reminder.due_at.to_rfc3339(),
+        reminder.channel
+    ))
+}
+
+/// Run the `list_reminders` tool
+pub async fn list_reminders(session_id: Option<&str>) -> Result<String> {
+    let (reminders, session_id) = tool_context(session_id)?;
+    let pending = reminders.list(session_id).await?;
+    if pending.is_empty() {
+        return Ok("No pending reminders.".to_string());
+    }
+    Ok(pending
+        .iter()
+        .map(|r| {
+            format!(
+                "- {} at {} on {}: {}",
+                r.id,
+                r.due_at.to_rfc3339(),
+                r.channel,
+                r.message
+            )
+        })
+        .collect::<Vec<_>>()
+        .join("\n"))
+}
+
+/// Run the `cancel_reminder` tool
+pub async fn cancel_reminder(
+    session_id: Option<&str>,
+    params: CancelReminderParams,
+) -> Result<String> {
+    let (reminders, session_id) = tool_context(session_id)?;
+    if reminders.cancel(session_id, &params.id).await? {
+        Ok(format!("Reminder {} cancelled.", params.id))
+    } else {
+        Ok(format!("No pending reminder {}.", params.id))
+    }
+}
+
+fn tool_context(session_id: Option<&str>) -> Result<(Arc<Reminders<SqliteStorage>>, &str)> {
+    let reminders = get_reminders().ok_or_else(|| anyhow!("Reminders are not available"))?;
+    let session_id = session_id.ok_or_else(|| anyhow!("Reminders need a session"))?;
+    Ok((reminders, session_id))
+}
+
+pub fn get_reminder_tool_definitions() -> Vec<ToolDefinition> {
+    vec![
+        ToolDefinition {
+            name: "set_reminder".to_string(),
+            description: "Remind the user of something at a later time. The reminder is sent to the user on this channel unless another channel and target are given.".to_string(),
+            parameters: json!({
+                "type": "object",
+                "properties": {
+                    "when": {
+                        "type": "string",
+                        "description": "Relative (\"in 2 hours\", \"in 1h 30m\") or absolute time (\"2025-06-01 09:00\", \"18:30\", RFC 3339), in the user's timezone"
+                    },
+                    "message": {
+                        "type": "string",
+                        "description": "What to remind the user of"
+                    },
+                    "channel": {
+                        "type": "string",
+                        "enum": DELIVERY_CHANNELS,
+                        "description": "Channel to deliver on (default: this one)"
+                    },
+                    "target": {
+                        "type": "string",
+                        "description": "Telegram chat ID, Discord user ID, or WhatsApp phone number / group"
+                    }
+                },
+                "required": ["when", "message"]
+            }),
+        },
+        ToolDefinition {
+            name: "list_reminders".to_string(),
+            description: "List the user's pending reminders with their IDs.".to_string(),
+            parameters: json!({
+                "type": "object",
+                "properties": {},
+            }),
+        },
+        ToolDefinition {
+            name: "cancel_reminder".to_string(),
+            description: "Cancel a pending reminder by ID.".to_string(),
+            parameters: json!({
+                "type": "object",
+                "properties": {
+                    "id": {
+                        "type": "string",
+                        "description": "Reminder ID from list_reminders"
+                    }
+                },
+                "required": ["id"]
+            }),
+        },
+    ]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::storage::Session;
+    use std::sync::Mutex;
+
+    async fn storage_with_session(path: &std::path::Path) -> SqliteStorage {
+        if !path.exists() {
+            std::fs::File::create(path).unwrap();
+        }
+        let storage = SqliteStorage::new(path).await.unwrap();
+        if storage.get_session("s1").await.unwrap().is_none() {
+            storage
+                .create_session(Session {
+                    id: "s1".to_string(),
+                    user_id: "whatsapp:main:5511999990000".to_string(),
+                    channel: "whatsapp".to_string(),
+                    scope: "per-sender".to_string(),
+                    created_at: Utc::now(),
+                    updated_at: Utc::now(),
+                })
+                .await
+                .unwrap();
+        }
+        storage
+    }
+
+    fn params(when: &str, message: &str) -> SetReminderParams {
+        SetReminderParams {
+            when: when.to_string(),
+            message: message.to_string(),
+            channel: None,
+            target: None,
+        }
+    }
+
+    #[test]
+    fn test_parse_when() {
+        let now = Utc.with_ymd_and_hms(2025, 1, 3, 20, 0, 0).unwrap();
+        let at = |d, h, m| Utc.with_ymd_and_hms(2025, 1, d, h, m, 0).unwrap();
+
+        assert_eq!(parse_when("in 2 hours", now, None).unwrap(), at(3, 22, 0));
+        assert_eq!(parse_when("In 1h 30m", now, None).unwrap(), at(3, 21, 30));
+        assert_eq!(
+            parse_when("in a day and 15 minutes", now, None).unwrap(),
+            at(4, 20, 15)
+        );
+        assert_eq!(
+            parse_when("2025-01-05T09:00:00+01:00", now, None).unwrap(),
+            at(5, 8, 0)
+        );
+        assert_eq!(
+            parse_when("2025-01-05 09:00", now, Some("America/New_York")).unwrap(),
+            at(5, 14, 0)
+        );
+        // 18:30 in Tokyo has passed (it is 05:00 on the 4th there)
+        assert_eq!(
+            parse_when("18:30", now, Some("Asia/Tokyo")).unwrap(),
+            at(4, 9, 30)
+        );
+        assert!(parse_when("in soon", now, None).is_err());
+        assert!(parse_when("next tuesday", now, None).is_err());
+    }
+
+    #[tokio::test]
+    async fn test_due_reminders_are_delivered_once() {
+        let dir = tempfile::tempdir().unwrap();
+        let storage = storage_with_session(&dir.path().join("db.sqlite")).await;
+        let reminders = Reminders::new(storage);
+        let now = Utc::now();
+
+        let soon = reminders
+            .set("s1", params("in 10 minutes", "Stretch"), now)
+            .await
+            .unwrap();
+        reminders
+            .set("s1", params("in 2 hours", "Call mom"), now)
+            .await
+            .unwrap();
+        assert_eq!(soon.target, "5511999990000");
+
+        let sent = Mutex::new(Vec::new());
+        let send = |reminder: Reminder| {
+            sent.lock().unwrap().push(reminder.message);
+            async { Ok(()) }
+        };
+
+        assert_eq!(reminders.deliver_due(now, send).await.unwrap(), 0);
+        let later = now + Duration::minutes(11);
+        assert_eq!(reminders.deliver_due(later, send).await.unwrap(), 1);
+        assert_eq!(reminders.deliver_due(later, send).await.unwrap(), 0);
+        assert_eq!(*sent.lock().unwrap(), vec!["Stretch".to_string()]);
+
+        let pending = reminders.list("s1").await.unwrap();
+        assert_eq!(pending.len(), 1);
+        assert!(reminders.cancel("s1", &pending[0].id).await.unwrap());
+        assert!(reminders.list("s1").await.unwrap().is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_pending_reminders_survive_restart() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("db.sqlite");
+        let now = Utc::now();
+
+        {
+            let reminders = Reminders::new(storage_with_session(&path).await);
+            reminders
+                .set("s1", params("in 5 minutes", "Take the bread out"), now)
+                .await
+                .unwrap();
+            // A failed send leaves the reminder pending
+            let failed = reminders
+                .deliver_due(now + Duration::minutes(6), |_| async {
+                    Err(anyhow!("offline"))
+                })
+                .await
+                .unwrap();
+            assert_eq!(failed, 0);
+        }
+
+        let reminders = Reminders::new(storage_with_session(&path).await);
+        let sent = Mutex::new(Vec::new());
+        let delivered = reminders
+            .deliver_due(now + Duration::minutes(7), |reminder| {
+                sent.lock().unwrap().push(reminder.target);
+                async { Ok(()) }
+            })
+            .await
+            .unwrap();
+        assert_eq!(delivered, 1);
+        assert_eq!(*sent.lock().unwrap(), vec!["5511999990000".to_string()]);
+    }
+}
@@ -0,0 +1,370 @@
+use anyhow::{anyhow, Result};
+use once_cell::sync::Lazy;
+use regex::Regex;
+use serde_json::Value;
+
+/// Matches `{{param}}` placeholders (surrounding whitespace allowed)
+static PLACEHOLDER: Lazy<Regex> = Lazy::new(|| {
+    Regex::new(r"\{\{\s*([A-Za-z_][A-Za-z0-9_]*)\s*\}\}").expect("valid placeholder regex")
+});
+
+/// Names of all parameters referenced by placeholders in a skill body
+pub fn template_params(body: &str) -> Vec<String> {
+    let mut names: Vec<String> = PLACEHOLDER
+        .captures_iter(body)
+        .map(|c| c[1].to_string())
+        .collect();
+    names.sort();
+    names.dedup();
+    names
+}
+
+/// Environment variable carrying a parameter's value
+pub fn param_env_var(name: &str) -> String {
+    format!("SKILL_PARAM_{}", name)
+}
+
+/// Where a placeholder sits in the skill body
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Quoting {
+    /// Plain code
+    None,
+    /// Inside a bash double-quoted string
+    Double,
+    /// Inside a literal nothing is expanded in: a bash single-quoted string,
+    /// or a python string or comment
+    Literal,
+}
+
+/// Ensure every placeholder refers to a property declared in the parameter
+/// schema and sits where the runtime can expand it
+pub fn validate_template(body: &str, runtime: &str, parameters: &Value) -> Result<()> {
+    let declared = parameters.get("properties").and_then(|p| p.as_object());
+
+    for name in template_params(body) {
+        if !declared
+            .map(|props| props.contains_key(&name))
+            .unwrap_or(false)
+        {
+            return Err(anyhow!(
+                "Template references undefined parameter '{}'",
+                name
+            ));
+        }
+    }
+    for caps in PLACEHOLDER.captures_iter(body) {
+        let placeholder = caps.get(0).unwrap();
+        if quoting_at(body, runtime, placeholder.start()) == Quoting::Literal {
+            return Err(anyhow!(
+                "Template parameter '{}' is inside a {} literal, where it cannot be expanded",
+                &caps[1],
+                runtime
+            ));
+        }
+    }
+
+    Ok(())
+}
+
+/// A skill body ready to run, and the environment holding its parameters
+#[derive(Debug)]
+pub struct RenderedSkill {
+    pub body: String,
+    pub env: Vec<(String, String)>,
+}
+
+/// Render `{{param}}` placeholders from JSON arguments.
+///
+/// Values never become part of the script: each parameter is passed in its
+/// own `SKILL_PARAM_<name>` variable and the placeholder is replaced by a
+/// reference to it, `"${SKILL_PARAM_<name>}"` in bash and the JSON-decoded
+/// variable in python. A parameter left out of the arguments takes its
+/// schema `default`, or is empty (`None`) unless it is `required`.
+pub fn render(
+    body: &str,
+    runtime: &str,
+    parameters: &Value,
+    arguments: &str,
+) -> Result<RenderedSkill> {
+    let names = template_params(body);
+    if names.is_empty() {
+        return Ok(RenderedSkill {
+            body: body.to_string(),
+            env: Vec::new(),
+        });
+    }
+    validate_template(body, runtime, parameters)?;
+
+    let args: Value = serde_json::from_str(arguments)
+        .map_err(|e| anyhow!("Skill arguments must be a JSON object: {}", e))?;
+    let required = |name: &str| {
+        parameters
+            .get("required")
+            .and_then(Value::as_array)
+            .is_some_and(|required| required.iter().any(|r| r == name))
+    };
+
+    let mut env = Vec::new();
+    for name in &names {
+        let default = parameters
+            .pointer(&format!("/properties/{}/default", name))
+            .cloned();
+        let value = match args.get(name).cloned().or(default) {
+            Some(value) => value,
+            None if required(name) => {
+                return Err(anyhow!("Missing value for template parameter '{}'", name));
+            }
+            None => Value::Null,
+        };
+        let text = match runtime {
+            "python" => value.to_string(),
+            _ => value_as_text(&value),
+        };
+        env.push((param_env_var(name), text));
+    }
+
+    let rendered = PLACEHOLDER.replace_all(body, |caps: &regex::Captures| {
+        let var = param_env_var(&caps[1]);
+        match runtime {
+            "python" => format!(
+                "__import__(\"json\").loads(__import__(\"os\").environ[\"{}\"])",
+                var
+            ),
+            _ => match quoting_at(body, runtime, caps.get(0).unwrap().start()) {
+                Quoting::Double => format!("${{{}}}", var),
+                _ => format!("\"${{{}}}\"", var),
+            },
+        }
+    });
+
+    Ok(RenderedSkill {
+        body: rendered.into_owned(),
+        env,
+    })
+}
+
+/// Plain text for a JSON value (strings unquoted, null empty, everything
+/// else as JSON)
+fn value_as_text(value: &Value) -> String {
+    match value {
+        Value::String(s) => s.clone(),
+        Value::Null => String::new(),
+        other => other.to_string(),
+    }
+}
+
+/// Quoting in effect at `offset` of a skill body
+fn quoting_at(body: &str, runtime: &str, offset: usize) -> Quoting {
+    match runtime {
+        "python" => python_quoting_at(&body[..offset]),
+        _ => shell_quoting_at(&body[..offset]),
+    }
+}
+
+fn shell_quoting_at(prefix: &str) -> Quoting {
+    let mut quoting = Quoting::None;
+    let mut word_start = true;
+    let mut chars = prefix.chars();
+    while let Some(c) = chars.next() {
+        match (quoting, c) {
+            (Quoting::Literal, '\'') => quoting = Quoting::None,
+            (Quoting::Literal, _) => {}
+            (_, '\\') => {
+                chars.next();
+            }
+            (Quoting::None, '\'') => quoting = Quoting::Literal,
+            (Quoting::None, '"') => quoting = Quoting::Double,
+            (Quoting::Double, '"') => quoting = Quoting::None,
+            // A comment runs to the end of the line
+            (Quoting::None, '#') if word_start => {
+                if !chars.any(|c| c == '\n') {
+                    return Quoting::Literal;
+                }
+                word_start = true;
+                continue;
+            }
+            _ => {}
+        }
+        word_start = quoting == Quoting::None && (c.is_whitespace() || ";&|(".contains(c));
+    }
+    quoting
+}
+
+fn python_quoting_at(prefix: &str) -> Quoting {
+    // The open string's delimiter, or "#" for a comment
+    let mut open: Option<&str> = None;
+    let mut chars = prefix.char_indices();
+    while let Some((i, c)) = chars.next() {
+        let rest = &prefix[i..];
+        // Delimiters are ASCII, so their tail is one char per byte
+        let mut skip = 0;
+        match open {
+            Some("#") => {
+                if c == '\n' {
+                    open = None;
+                }
+            }
+            Some(delimiter) => {
+                if c == '\\' {
+                    skip = 1;
+                } else if rest.starts_with(delimiter) {
+                    open = None;
+                    skip = delimiter.len() - 1;
+                }
+            }
+            None => {
+                open = ["\"\"\"", "\'\'\'", "\"", "\'", "#"]
+                    .into_iter()
+                    .find(|delimiter| rest.starts_with(delimiter));
+                if let Some(delimiter) = open {
+                    skip = delimiter.len() - 1;
+                }
+            }
+        }
+        if skip > 0 {
+            chars.nth(skip - 1);
+        }
+    }
+    match open {
+        Some(_) => Quoting::Literal,
+        None => Quoting::None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn params() -> Value {
+        json!({
+            "type": "object",
+            "properties": {
+                "msg": {"type": "string"},
+                "count": {"type": "integer", "default": 3},
+                "flag": {"type": "boolean"}
+            },
+            "required": ["msg"]
+        })
+    }
+
+    /// Run a rendered body with its environment, returning stdout
+    fn run(interpreter: &str, rendered: &RenderedSkill) -> Option<String> {
+        let output = std::process::Command::new(interpreter)
+            .args(["-c", &rendered.body])
+            .envs(rendered.env.iter().cloned())
+            .output()
+            .ok()?;
+        Some(String::from_utf8_lossy(&output.stdout).trim().to_string())
+    }
+
+    #[test]
+    fn test_template_params() {
+        let params = template_params("echo {{ name }} {{count}} {{name}}");
+        assert_eq!(params, vec!["count", "name"]);
+    }
+
+    #[test]
+    fn test_bash_substitution() {
+        let rendered = render(
+            "echo {{msg}}",
+            "bash",
+            &params(),
+            r#"{"msg": "hello world"}"#,
+        )
+        .unwrap();
+        assert_eq!(rendered.body, r#"echo "${SKILL_PARAM_msg}""#);
+        assert_eq!(
+            rendered.env,
+            vec![("SKILL_PARAM_msg".to_string(), "hello world".to_string())]
+        );
+    }
+
+    #[test]
+    fn test_bash_injection_is_not_run() {
+        let value = "$(echo INJECTED) x'; echo INJECTED #";
+        let arguments = json!({ "msg": value }).to_string();
+        for body in [
+            "echo {{msg}}",
+            "echo \"Hi {{msg}}\"",
+            "echo \"Hi \\\"{{msg}}\"",
+        ] {
+            let rendered = render(body, "bash", &params(), &arguments).unwrap();
+            assert!(!rendered.body.contains("INJECTED"));
+            if let Some(stdout) = run("bash", &rendered) {
+                assert!(stdout.ends_with(value), "{}", body);
+            }
+        }
+
+        let rendered = render("echo \"Hi {{msg}}\"", "bash", &params(), &arguments).unwrap();
+        assert_eq!(rendered.body, r#"echo "Hi ${SKILL_PARAM_msg}""#);
+    }
+
+    #[test]
+    fn test_python_substitution() {
+        let rendered = render(
+            "name = {{msg}}\ncount = {{count}}\nprint(name, count, {{flag}})",
+            "python",
+            &params(),
+            r#"{"msg": "a\"b; import os", "flag": true}"#,
+        )
+        .unwrap();
+        assert!(!rendered.body.contains("import os"));
+        assert!(rendered.env.contains(&(
+            "SKILL_PARAM_msg".to_string(),
+            r#""a\"b; import os""#.to_string()
+        )));
+        if let Some(stdout) = run("python3", &rendered) {
+            assert_eq!(stdout, "a\"b; import os 3 True");
+        }
+    }
+
+    #[test]
+    fn test_placeholders_inside_literals_are_rejected() {
+        let params = params();
+        assert!(validate_template("echo 'Hi {{msg}}'", "bash", &params).is_err());
+        assert!(validate_template("# see {{msg}}\necho ok", "bash", &params).is_err());
+        assert!(validate_template("echo \"it's {{msg}}\"", "bash", &params).is_ok());
+        assert!(validate_template("print(\"Hi {{msg}}\")", "python", &params).is_err());
+        assert!(validate_template("s = '''\n{{msg}}'''", "python", &params).is_err());
+        assert!(validate_template("print('a#b', {{msg}})", "python", &params).is_ok());
+        // Escapes skip whole characters, multibyte ones included
+        assert!(validate_template("x = \"\\é\"\nprint({{msg}})", "python", &params).is_ok());
+        assert!(validate_template("x = \"\\é{{msg}}\"", "python", &params).is_err());
+    }
+
+    #[test]
+    fn test_missing_arguments() {
+        // Required parameters must be given
+        assert!(render("echo {{msg}}", "bash", &params(), "{}").is_err());
+
+        // Optional ones take their default, or are empty
+        let rendered = render(
+            "echo {{msg}} {{count}} {{flag}}",
+            "bash",
+            &params(),
+            r#"{"msg": "hi"}"#,
+        )
+        .unwrap();
+        assert!(rendered
+            .env
+            .contains(&("SKILL_PARAM_count".to_string(), "3".to_string())));
+        assert!(rendered
+            .env
+            .contains(&("SKILL_PARAM_flag".to_string(), String::new())));
+    }
+
+    #[test]
+    fn test_validate_template_rejects_undefined_param() {
+        assert!(validate_template("echo {{msg}}", "bash", &params()).is_ok());
+        assert!(validate_template("echo {{other}}", "bash", &params()).is_err());
+    }
+
+    #[test]
+    fn test_untemplated_body_is_unchanged() {
+        let body = "echo \"$SKILL_ARGS\"";
+        let rendered = render(body, "bash", &params(), "not json").unwrap();
+        assert_eq!(rendered.body, body);
+        assert!(rendered.env.is_empty());
+    }
+}
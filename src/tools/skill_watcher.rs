@@ -3,7 +3,11 @@ use anyhow::{Context, Result};
 use notify_debouncer_mini::new_debouncer;
 use std::path::PathBuf;
 use std::time::Duration;
-use tracing::{info, warn};
+use tracing::{error, info, warn};
+
+/// Quiet period before a changed skill file is reloaded. Editors emit several
+/// events per save, and a file read mid-write may not parse.
+const DEBOUNCE_MS: u64 = 300;
 
 /// File system watcher for skill files
 pub struct SkillWatcher {
@@ -36,7 +40,7 @@ impl SkillWatcher {
         let (tx, mut rx) = tokio::sync::mpsc::unbounded_channel();
 
         let mut debouncer = new_debouncer(
-            Duration::from_millis(250),
+            Duration::from_millis(DEBOUNCE_MS),
             move |res: notify_debouncer_mini::DebounceEventResult| {
                 let _ = tx.send(res);
             },
@@ -59,6 +63,10 @@ impl SkillWatcher {
                 Ok(events) => {
                     for event in events {
                         let path_buf = PathBuf::from(&event.path);
+                        if is_hidden(&self.skills_dir, &path_buf) {
+                            // Skip skill history and other dot-directories
+                            continue;
+                        }
                         if path_buf
                             .extension()
                             .and_then(|s| s.to_str())
@@ -92,32 +100,10 @@ impl SkillWatcher {
     async fn initial_scan(&self) -> Result<()> {
         info!("Scanning skills directory: {}", self.skills_dir.display());
 
-        match std::fs::read_dir(&self.skills_dir) {
-            Ok(entries) => {
-                for entry in entries.flatten() {
-                    let path = entry.path();
-                    let ext = path.extension().and_then(|s| s.to_str());
-                    if ext == Some("yaml") || ext == Some("yml") {
-                        match super::skills::parse_skill_file(&path) {
-                            Ok(entry) => match super::skills::load_skill(entry).await {
-                                Ok(_) => {
-                                    info!("Loaded skill from: {}", path.display());
-                                    publish_event(SystemEvent::ToolUpdated(
-                                        super::skills::parse_skill_file(&path)
-                                            .unwrap()
-                                            .manifest
-                                            .name,
-                                    ));
-                                }
-                                Err(e) => {
-                                    warn!("Failed to load skill from {}: {}", path.display(), e);
-                                }
-                            },
-                            Err(e) => {
-                                warn!("Failed to parse skill file {}: {}", path.display(), e);
-                            }
-                        }
-                    }
+        match super::skills::skill_files(&self.skills_dir) {
+            Ok(paths) => {
+                for path in paths {
+                    self.handle_skill_change(&path).await;
                 }
             }
             Err(e) => {
@@ -128,27 +114,38 @@ impl SkillWatcher {
         Ok(())
     }
 
-    /// Handle a skill file change
+    /// Handle a skill file change: parse and validate, then load.
+    ///
+    /// On failure the previously loaded version (if any) stays active.
     async fn handle_skill_change(&self, path: &std::path::Path) {
-        match super::skills::parse_skill_file(path) {
+        let loaded = match super::skills::read_skill_file(path) {
             Ok(entry) => {
                 let skill_name = entry.manifest.name.clone();
-                match super::skills::load_skill(entry).await {
-                    Ok(_) => {
-                        info!(
-                            "Skill '{}' loaded/updated from: {}",
-                            skill_name,
-                            path.display()
-                        );
-                        publish_event(SystemEvent::ToolUpdated(skill_name));
-                    }
-                    Err(e) => {
-                        warn!("Failed to load skill '{}': {}", skill_name, e);
-                    }
-                }
+                super::skills::load_skill(entry).await.map(|_| skill_name)
+            }
+            Err(e) => Err(e),
+        };
+
+        match loaded {
+            Ok(skill_name) => {
+                info!(
+                    "Skill '{}' loaded/updated from: {}",
+                    skill_name,
+                    path.display()
+                );
+                publish_event(SystemEvent::ToolUpdated(skill_name));
             }
             Err(e) => {
-                warn!("Failed to parse skill file {}: {}", path.display(), e);
+                error!(
+                    "Invalid skill file {} (keeping previous version active): {}",
+                    path.display(),
+                    e
+                );
+                super::skills::record_load_error(path, e.to_string()).await;
+                publish_event(SystemEvent::ToolLoadFailed {
+                    path: path.display().to_string(),
+                    error: e.to_string(),
+                });
             }
         }
     }
@@ -182,6 +179,14 @@ impl SkillWatcher {
     }
 }
 
+/// Whether a path inside the skills directory lives under a dot-file or dot-directory
+fn is_hidden(skills_dir: &std::path::Path, path: &std::path::Path) -> bool {
+    path.strip_prefix(skills_dir)
+        .unwrap_or(path)
+        .components()
+        .any(|c| c.as_os_str().to_string_lossy().starts_with('.'))
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -238,6 +243,132 @@ echo "test 2"
             .ok();
     }
 
+    #[tokio::test]
+    async fn test_initial_scan_skips_invalid_skill() {
+        let temp_dir = TempDir::new().unwrap();
+        let skills_path = temp_dir.path().join("skills");
+        fs::create_dir(&skills_path).unwrap();
+
+        let invalid = r#"---
+name: invalid_scan_skill
+description: "Broken"
+parameters: {}
+runtime: bash
+---
+if true; then
+"#;
+        let path = skills_path.join("invalid.yaml");
+        fs::write(&path, invalid).unwrap();
+
+        let watcher = SkillWatcher::new(skills_path.to_str().unwrap());
+        watcher.initial_scan().await.unwrap();
+
+        assert!(super::super::skills::get_skill("invalid_scan_skill")
+            .await
+            .is_none());
+        assert!(super::super::skills::get_load_error(&path).await.is_some());
+    }
+
+    #[tokio::test]
+    async fn test_rapid_writes_result_in_single_load() {
+        let temp_dir = TempDir::new().unwrap();
+        let skills_path = temp_dir.path().join("skills");
+        fs::create_dir(&skills_path).unwrap();
+
+        let watcher = SkillWatcher::new(skills_path.to_str().unwrap());
+        let handle = tokio::spawn(async move { watcher.run().await });
+        tokio::time::sleep(Duration::from_millis(200)).await;
+
+        let mut events = crate::core::events::subscribe();
+        let path = skills_path.join("rapid.yaml");
+        for i in 0..5 {
+            let content = format!(
+                "---\nname: rapid_write_skill\ndescription: \"Rapid {}\"\nparameters: {{}}\nruntime: bash\n---\necho {}\n",
+                i, i
+            );
+            fs::write(&path, content).unwrap();
+            tokio::time::sleep(Duration::from_millis(20)).await;
+        }
+
+        tokio::time::sleep(Duration::from_millis(DEBOUNCE_MS * 4)).await;
+        handle.abort();
+
+        let mut loads = 0;
+        while let Ok(event) = events.try_recv() {
+            if matches!(&event, SystemEvent::ToolUpdated(name) if name == "rapid_write_skill") {
+                loads += 1;
+            }
+        }
+        assert_eq!(loads, 1);
+
+        let skill = super::super::skills::get_skill("rapid_write_skill")
+            .await
+            .unwrap();
+        assert_eq!(skill.manifest.description, "Rapid 4");
+
+        super::super::skills::unload_skill("rapid_write_skill")
+            .await
+            .ok();
+    }
+
+    #[tokio::test]
+    async fn test_initial_scan_loads_nested_skills_with_namespaces() {
+        let temp_dir = TempDir::new().unwrap();
+        let skills_path = temp_dir.path().join("skills");
+        fs::create_dir_all(skills_path.join("web/api")).unwrap();
+        fs::create_dir_all(skills_path.join("mine")).unwrap();
+        super::super::skills::init_skill_namespaces(&skills_path, vec![skills_path.join("mine")]);
+
+        let skill = |name: &str| {
+            format!(
+                "---\nname: {}\ndescription: \"Nested\"\nparameters: {{}}\nruntime: bash\n---\necho {}\n",
+                name, name
+            )
+        };
+        fs::write(skills_path.join("nested_top.yaml"), skill("nested_top")).unwrap();
+        fs::write(skills_path.join("web/fetch.yaml"), skill("nested_fetch")).unwrap();
+        fs::write(skills_path.join("web/api/get.yaml"), skill("nested_get")).unwrap();
+        fs::write(skills_path.join("mine/own.yaml"), skill("nested_own")).unwrap();
+        // Same folder, same name: the second file collides with the first
+        let clash = skills_path.join("web/fetch_copy.yaml");
+        fs::write(&clash, skill("nested_fetch")).unwrap();
+        // Namespaced names must stay valid function names of at most 64 chars
+        let long_folder = skills_path.join("a".repeat(60));
+        fs::create_dir_all(&long_folder).unwrap();
+        let too_long = long_folder.join("long.yaml");
+        fs::write(&too_long, skill("nested_long")).unwrap();
+        fs::create_dir_all(skills_path.join("my.tools")).unwrap();
+        let dotted = skills_path.join("my.tools/dotted.yaml");
+        fs::write(&dotted, skill("nested_dotted")).unwrap();
+
+        let watcher = SkillWatcher::new(skills_path.to_str().unwrap());
+        watcher.initial_scan().await.unwrap();
+
+        let names = [
+            "nested_top",
+            "web__nested_fetch",
+            "web__api__nested_get",
+            "nested_own",
+        ];
+        for name in names {
+            assert!(
+                super::super::skills::get_skill(name).await.is_some(),
+                "{} not loaded",
+                name
+            );
+        }
+        let error = super::super::skills::get_load_error(&clash).await.unwrap();
+        assert!(error.contains("fetch_copy.yaml") && error.contains("fetch.yaml"));
+        for path in [&too_long, &dotted] {
+            let error = super::super::skills::get_load_error(path).await.unwrap();
+            assert!(error.contains("at most 64"), "{}", error);
+        }
+
+        for name in names {
+            super::super::skills::unload_skill(name).await.ok();
+        }
+    }
+
     #[tokio::test]
     async fn test_initial_scan_handles_missing_directory() {
         let temp_dir = TempDir::new().unwrap();
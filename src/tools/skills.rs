@@ -1,16 +1,36 @@
+use super::exec::{CommandFailed, ExecTarget, SandboxUnavailable};
 use anyhow::{anyhow, Context, Result};
 use once_cell::sync::OnceCell;
 use serde::{Deserialize, Serialize};
 use serde_json::Value;
 use std::collections::HashMap;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
+use std::process::Stdio;
 use std::sync::Arc;
+use tokio::io::AsyncWriteExt;
 use tokio::sync::RwLock;
 use tracing::{debug, info};
 
 /// Global skill registry: skill name -> SkillEntry
 static SKILL_BODIES: OnceCell<Arc<RwLock<HashMap<String, SkillEntry>>>> = OnceCell::new();
 
+/// Directory holding previous versions of each skill (unset: versioning disabled)
+static SKILL_HISTORY_DIR: OnceCell<PathBuf> = OnceCell::new();
+
+/// Skills directory whose sub-directories namespace their skills (unset:
+/// skill names are used as written)
+static SKILL_NAMESPACES: OnceCell<SkillNamespaces> = OnceCell::new();
+
+/// Joins a skill's folders and name when skills are namespaced. Tool names
+/// must match `^[a-zA-Z0-9_-]{1,64}$` for function calling, so no `/`.
+pub const NAMESPACE_SEPARATOR: &str = "__";
+
+/// Longest tool name function-calling APIs accept
+pub(crate) const MAX_QUALIFIED_NAME_CHARS: usize = 64;
+
+/// Last load error per skill source path (cleared on successful load)
+static SKILL_LOAD_ERRORS: OnceCell<Arc<RwLock<HashMap<PathBuf, String>>>> = OnceCell::new();
+
 /// Initialize the global skill bodies registry
 fn init_skill_bodies() -> Arc<RwLock<HashMap<String, SkillEntry>>> {
     SKILL_BODIES
@@ -33,6 +53,75 @@ pub struct SkillManifest {
     pub policy: String,
     #[serde(default = "default_timeout")]
     pub timeout_secs: u64,
+    /// System binaries the skill needs on PATH (e.g. jq, curl)
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub dependencies: Vec<String>,
+    /// Python modules the skill imports (checked with `import <name>`)
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub python_packages: Vec<String>,
+    /// In sandbox mode, pip-install `python_packages` before running
+    #[serde(default)]
+    pub install_dependencies: bool,
+    /// Container image the skill runs in when sandboxed (missing: the
+    /// sandbox's `image`), e.g. one with the libraries it needs
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub image: Option<String>,
+    /// JSON Schema the skill's stdout must conform to
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub output_schema: Option<Value>,
+    /// Categories used to decide whether the skill is offered to the model
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub tags: Vec<String>,
+    /// The skill has no side effects, so it may run alongside other
+    /// side-effect-free calls of the same turn
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub read_only: bool,
+    /// Manifest format, deciding how the skill receives its arguments
+    /// (missing: 1)
+    #[serde(default, skip_serializing_if = "ManifestVersion::is_v1")]
+    pub version: ManifestVersion,
+}
+
+/// Skill manifest format version
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+#[serde(try_from = "u32", into = "u32")]
+pub enum ManifestVersion {
+    /// Arguments as JSON in the `SKILL_ARGS` environment variable. Kept for
+    /// existing skills; new skills should use v2.
+    #[default]
+    V1,
+    /// Arguments as JSON on stdin; `SKILL_ARGS` is not set
+    V2,
+}
+
+impl ManifestVersion {
+    pub fn is_v1(&self) -> bool {
+        *self == ManifestVersion::V1
+    }
+}
+
+impl TryFrom<u32> for ManifestVersion {
+    type Error = String;
+
+    fn try_from(version: u32) -> std::result::Result<Self, Self::Error> {
+        match version {
+            1 => Ok(ManifestVersion::V1),
+            2 => Ok(ManifestVersion::V2),
+            other => Err(format!(
+                "unsupported skill manifest version {} (supported: 1, 2)",
+                other
+            )),
+        }
+    }
+}
+
+impl From<ManifestVersion> for u32 {
+    fn from(version: ManifestVersion) -> Self {
+        match version {
+            ManifestVersion::V1 => 1,
+            ManifestVersion::V2 => 2,
+        }
+    }
 }
 
 fn default_skill_policy() -> String {
@@ -51,6 +140,283 @@ pub struct SkillEntry {
     pub source_path: PathBuf,
 }
 
+/// A stored previous version of a skill
+#[derive(Debug, Clone, Serialize)]
+pub struct SkillVersion {
+    pub version: u32,
+    pub created_at: chrono::DateTime<chrono::Utc>,
+    pub path: PathBuf,
+}
+
+/// On-disk version history for skills: `<root>/<skill name>/v<N>.yaml`
+#[derive(Debug, Clone)]
+pub struct SkillHistory {
+    root: PathBuf,
+}
+
+impl SkillHistory {
+    pub fn new(root: impl Into<PathBuf>) -> Self {
+        Self { root: root.into() }
+    }
+
+    /// The skill's history directory. Names come from API paths, so they
+    /// must be valid skill names, keeping the directory under `root`.
+    fn skill_dir(&self, name: &str) -> Result<PathBuf> {
+        if !is_valid_skill_name(name) {
+            return Err(anyhow!("Invalid skill name '{}'", name));
+        }
+        Ok(self.root.join(name))
+    }
+
+    fn version_path(&self, name: &str, version: u32) -> Result<PathBuf> {
+        Ok(self.skill_dir(name)?.join(format!("v{}.yaml", version)))
+    }
+
+    /// List stored versions of a skill, oldest first
+    pub fn versions(&self, name: &str) -> Result<Vec<SkillVersion>> {
+        let dir = self.skill_dir(name)?;
+        if !dir.exists() {
+            return Ok(vec![]);
+        }
+
+        let mut versions = vec![];
+        for entry in std::fs::read_dir(&dir).context("Failed to read skill history")? {
+            let path = entry?.path();
+            let version = path
+                .file_stem()
+                .and_then(|s| s.to_str())
+                .and_then(|s| s.strip_prefix('v'))
+                .and_then(|s| s.parse::<u32>().ok());
+
+            if let Some(version) = version {
+                let created_at = std::fs::metadata(&path)
+                    .and_then(|m| m.modified())
+                    .map(chrono::DateTime::<chrono::Utc>::from)
+                    .unwrap_or_else(|_| chrono::Utc::now());
+                versions.push(SkillVersion {
+                    version,
+                    created_at,
+                    path,
+                });
+            }
+        }
+
+        versions.sort_by_key(|v| v.version);
+        Ok(versions)
+    }
+
+    /// Store the entry as a new version unless it matches the latest one.
+    /// Returns the version number holding this content.
+    pub fn record(&self, entry: &SkillEntry) -> Result<u32> {
+        let name = &entry.manifest.name;
+        let content = skill_file_content(entry)?;
+        let versions = self.versions(name)?;
+
+        if let Some(latest) = versions.last() {
+            if std::fs::read_to_string(&latest.path).ok().as_deref() == Some(content.as_str()) {
+                return Ok(latest.version);
+            }
+        }
+
+        let version = versions.last().map(|v| v.version + 1).unwrap_or(1);
+        let path = self.version_path(name, version)?;
+        std::fs::create_dir_all(self.skill_dir(name)?)
+            .context("Failed to create skill history directory")?;
+        std::fs::write(&path, content).context("Failed to write skill version")?;
+
+        debug!("Recorded skill '{}' version {}", name, version);
+        Ok(version)
+    }
+
+    /// Parse a stored version back into a skill entry
+    pub fn load_version(&self, name: &str, version: u32) -> Result<SkillEntry> {
+        let path = self.version_path(name, version)?;
+        if !path.exists() {
+            return Err(anyhow!("Skill '{}' has no version {}", name, version));
+        }
+        parse_skill_file(&path)
+    }
+}
+
+/// Serialize a skill entry back into skill file format (frontmatter + body).
+/// The file keeps the unqualified name; its folder supplies the namespace.
+fn skill_file_content(entry: &SkillEntry) -> Result<String> {
+    let mut manifest = entry.manifest.clone();
+    let namespace = qualified_skill_name(&entry.source_path, "");
+    if let Some(name) = manifest.name.strip_prefix(&namespace) {
+        manifest.name = name.to_string();
+    }
+    let manifest_yaml =
+        serde_yaml::to_string(&manifest).context("Failed to serialize skill manifest")?;
+    Ok(format!("---\n{}---\n{}", manifest_yaml, entry.body))
+}
+
+/// Enable skill versioning, storing history under `dir`
+pub fn init_skill_history(dir: impl Into<PathBuf>) {
+    SKILL_HISTORY_DIR.set(dir.into()).ok();
+}
+
+#[derive(Debug)]
+struct SkillNamespaces {
+    root: PathBuf,
+    /// Directories under `root` whose skills keep their plain names
+    exempt: Vec<PathBuf>,
+}
+
+/// Namespace skills in sub-directories of `root` by folder, e.g. the skill
+/// `fetch` in `<root>/web/` becomes `web__fetch`. Skills under `exempt`
+/// (such as the directory of API-created tools) keep their plain names.
+pub fn init_skill_namespaces(root: impl Into<PathBuf>, exempt: Vec<PathBuf>) {
+    SKILL_NAMESPACES
+        .set(SkillNamespaces {
+            root: root.into(),
+            exempt,
+        })
+        .ok();
+}
+
+/// Name exposed to the model for the skill `name` defined in `path`
+pub fn qualified_skill_name(path: &Path, name: &str) -> String {
+    let Some(namespaces) = SKILL_NAMESPACES.get() else {
+        return name.to_string();
+    };
+    if namespaces.exempt.iter().any(|dir| path.starts_with(dir)) {
+        return name.to_string();
+    }
+    let folders: Vec<String> = path
+        .parent()
+        .and_then(|parent| parent.strip_prefix(&namespaces.root).ok())
+        .map(|relative| {
+            relative
+                .components()
+                .map(|c| c.as_os_str().to_string_lossy().into_owned())
+                .collect()
+        })
+        .unwrap_or_default();
+    if folders.is_empty() {
+        name.to_string()
+    } else {
+        format!(
+            "{}{}{}",
+            folders.join(NAMESPACE_SEPARATOR),
+            NAMESPACE_SEPARATOR,
+            name
+        )
+    }
+}
+
+/// Skill files under `dir` and its sub-directories, skipping dot-files and
+/// dot-directories (such as the version history), sorted by path
+pub fn skill_files(dir: &Path) -> std::io::Result<Vec<PathBuf>> {
+    fn walk(dir: &Path, files: &mut Vec<PathBuf>) -> std::io::Result<()> {
+        for entry in std::fs::read_dir(dir)?.flatten() {
+            let path = entry.path();
+            if entry.file_name().to_string_lossy().starts_with('.') {
+                continue;
+            }
+            if path.is_dir() {
+                if let Err(e) = walk(&path, files) {
+                    tracing::warn!("Failed to read skills directory {}: {}", path.display(), e);
+                }
+            } else if matches!(
+                path.extension().and_then(|s| s.to_str()),
+                Some("yaml" | "yml")
+            ) {
+                files.push(path);
+            }
+        }
+        Ok(())
+    }
+
+    let mut files = Vec::new();
+    walk(dir, &mut files)?;
+    files.sort();
+    Ok(files)
+}
+
+/// Parse and validate a skill file, naming the skill after its namespace
+pub fn read_skill_file(path: &Path) -> Result<SkillEntry> {
+    let mut entry = parse_skill_file(path)?;
+    validate_skill_entry(&entry)?;
+    let name = qualified_skill_name(path, &entry.manifest.name);
+    if !is_valid_skill_name(&name) || name.len() > MAX_QUALIFIED_NAME_CHARS {
+        return Err(anyhow!(
+            "Skill name '{}' must be at most {} letters, digits, '-' or '_'; rename the skill or its folders",
+            name,
+            MAX_QUALIFIED_NAME_CHARS
+        ));
+    }
+    entry.manifest.name = name;
+    Ok(entry)
+}
+
+fn name_collision(name: &str, path: &Path, other: &Path) -> anyhow::Error {
+    anyhow!(
+        "Skill '{}' in {} collides with the skill of the same name in {}",
+        name,
+        path.display(),
+        other.display()
+    )
+}
+
+/// Get the global skill history, if versioning is enabled
+pub fn skill_history() -> Option<SkillHistory> {
+    SKILL_HISTORY_DIR.get().map(SkillHistory::new)
+}
+
+/// List stored versions of a skill
+pub async fn get_skill_versions(name: &str) -> Result<Vec<SkillVersion>> {
+    let history = skill_history().ok_or_else(|| anyhow!("Skill versioning is not enabled"))?;
+    history.versions(name)
+}
+
+/// Restore a previous version of a skill and make it active
+pub async fn rollback_skill(name: &str, version: u32) -> Result<SkillEntry> {
+    let history = skill_history().ok_or_else(|| anyhow!("Skill versioning is not enabled"))?;
+    rollback_skill_from(&history, name, version).await
+}
+
+/// Restore a version from the given history, rewriting the skill's source file
+async fn rollback_skill_from(
+    history: &SkillHistory,
+    name: &str,
+    version: u32,
+) -> Result<SkillEntry> {
+    let mut entry = history.load_version(name, version)?;
+    // Stored files hold the unqualified name
+    entry.manifest.name = name.to_string();
+
+    // Keep the on-disk skill file in sync so the watcher doesn't revert the rollback
+    if let Some(current) = get_skill(name).await {
+        entry.source_path = current.source_path;
+        std::fs::write(&entry.source_path, skill_file_content(&entry)?)
+            .context("Failed to restore skill file")?;
+    }
+
+    load_skill(entry.clone()).await?;
+    info!("Rolled back skill '{}' to version {}", name, version);
+    Ok(entry)
+}
+
+fn init_load_errors() -> Arc<RwLock<HashMap<PathBuf, String>>> {
+    SKILL_LOAD_ERRORS
+        .get_or_init(|| Arc::new(RwLock::new(HashMap::new())))
+        .clone()
+}
+
+/// Remember that loading the skill file at `path` failed
+pub async fn record_load_error(path: &std::path::Path, error: String) {
+    init_load_errors()
+        .write()
+        .await
+        .insert(path.to_path_buf(), error);
+}
+
+/// Get the last load error for a skill file, if the latest edit failed
+pub async fn get_load_error(path: &std::path::Path) -> Option<String> {
+    init_load_errors().read().await.get(path).cloned()
+}
+
 /// Parse a skill file: frontmatter + body separated by ---
 pub fn parse_skill_file(path: &std::path::Path) -> Result<SkillEntry> {
     let content =
@@ -59,6 +425,13 @@ pub fn parse_skill_file(path: &std::path::Path) -> Result<SkillEntry> {
     parse_skill_content(&content, path.to_path_buf())
 }
 
+/// Parse an uploaded skill file; `path` is where it will be stored
+pub fn parse_skill_bytes(bytes: &[u8], path: PathBuf) -> Result<SkillEntry> {
+    let content = std::str::from_utf8(bytes).context("Skill file is not valid UTF-8")?;
+
+    parse_skill_content(content, path)
+}
+
 /// Parse skill content from a string
 fn parse_skill_content(content: &str, path: PathBuf) -> Result<SkillEntry> {
     // Split on --- delimiters
@@ -73,12 +446,16 @@ fn parse_skill_content(content: &str, path: PathBuf) -> Result<SkillEntry> {
     // Part 0: empty (before first ---)
     // Part 1: YAML frontmatter
     // Part 2: body (after second ---)
-    let frontmatter = parts[1].trim();
     let body = parts[2].trim_start().to_string();
 
-    // Parse YAML frontmatter
-    let manifest: SkillManifest =
-        serde_yaml::from_str(frontmatter).context("Failed to parse skill frontmatter as YAML")?;
+    // Parse YAML frontmatter, padded so reported lines match the file's
+    let frontmatter = format!(
+        "{}{}",
+        "\n".repeat(parts[0].matches('\n').count()),
+        parts[1]
+    );
+    let manifest: SkillManifest = serde_yaml::from_str(&frontmatter)
+        .map_err(|e| anyhow!("Failed to parse skill frontmatter as YAML: {}", e))?;
 
     // Validate manifest
     if manifest.name.is_empty() {
@@ -87,10 +464,15 @@ fn parse_skill_content(content: &str, path: PathBuf) -> Result<SkillEntry> {
     if manifest.description.is_empty() {
         return Err(anyhow!("Skill description cannot be empty"));
     }
+    super::skill_template::validate_template(&body, &manifest.runtime, &manifest.parameters)?;
 
     debug!(
-        "Parsed skill '{}' (runtime: {}, sandbox: {}, timeout: {}s)",
-        manifest.name, manifest.runtime, manifest.sandbox, manifest.timeout_secs
+        "Parsed skill '{}' (manifest v{}, runtime: {}, sandbox: {}, timeout: {}s)",
+        manifest.name,
+        u32::from(manifest.version),
+        manifest.runtime,
+        manifest.sandbox,
+        manifest.timeout_secs
     );
 
     Ok(SkillEntry {
@@ -100,16 +482,121 @@ fn parse_skill_content(content: &str, path: PathBuf) -> Result<SkillEntry> {
     })
 }
 
-/// Load a skill into the registry and register its policy
-pub async fn load_skill(entry: SkillEntry) -> Result<()> {
-    let skill_name = entry.manifest.name.clone();
-    let policy_level = entry.manifest.policy.clone();
+/// Whether a (plain) skill name is non-empty and only has ASCII letters,
+/// digits, hyphens and underscores
+fn is_valid_skill_name(name: &str) -> bool {
+    !name.is_empty()
+        && name
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_')
+}
+
+/// Validate a parsed skill before it is loaded (mirrors `CreateToolRequest::validate`)
+pub fn validate_skill_entry(entry: &SkillEntry) -> Result<()> {
+    let manifest = &entry.manifest;
+
+    if !is_valid_skill_name(&manifest.name) {
+        return Err(anyhow!(
+            "Skill name must contain only ASCII letters, digits, hyphens, and underscores"
+        ));
+    }
+    if manifest.name.len() > MAX_QUALIFIED_NAME_CHARS {
+        return Err(anyhow!(
+            "Skill name too long (max {} characters)",
+            MAX_QUALIFIED_NAME_CHARS
+        ));
+    }
+    if entry.body.trim().is_empty() {
+        return Err(anyhow!("Skill body cannot be empty"));
+    }
+    if !manifest.parameters.is_object() {
+        return Err(anyhow!("Skill parameters must be a JSON object"));
+    }
+    if manifest
+        .output_schema
+        .as_ref()
+        .is_some_and(|schema| !schema.is_object())
+    {
+        return Err(anyhow!("Skill output_schema must be a JSON object"));
+    }
+    if manifest
+        .policy
+        .parse::<crate::tools::policy::ToolAccessLevel>()
+        .is_err()
+    {
+        return Err(anyhow!(
+            "Invalid policy: must be 'allow', 'deny', or 'elevated'"
+        ));
+    }
+    if manifest.timeout_secs == 0 || manifest.timeout_secs > 3600 {
+        return Err(anyhow!("Timeout must be between 1 and 3600 seconds"));
+    }
+    if let Some(image) = &manifest.image {
+        crate::sandbox::validate_image_reference(image)?;
+    }
+
+    match manifest.runtime.as_str() {
+        "bash" | "sh" => super::creator::validate_bash_syntax(&entry.body),
+        "python" => super::creator::validate_python_syntax(&entry.body),
+        other => Err(anyhow!(
+            "Unsupported runtime '{}': must be 'bash', 'sh', or 'python'",
+            other
+        )),
+    }
+}
 
+/// Load a skill into the registry and register its policy.
+///
+/// Fails when another skill file that still exists defines the same name.
+pub async fn load_skill(entry: SkillEntry) -> Result<()> {
     // Insert into registry
     let registry = init_skill_bodies();
     {
         let mut skills = registry.write().await;
-        skills.insert(skill_name.clone(), entry.clone());
+        if let Some(other) = skills.get(&entry.manifest.name) {
+            if other.source_path != entry.source_path && other.source_path.exists() {
+                return Err(name_collision(
+                    &entry.manifest.name,
+                    &entry.source_path,
+                    &other.source_path,
+                ));
+            }
+        }
+        skills.insert(entry.manifest.name.clone(), entry.clone());
+    }
+    activate_skill(entry).await;
+    Ok(())
+}
+
+/// Register a skill already in the registry with its history, policy and
+/// the plugin registry
+async fn activate_skill(entry: SkillEntry) {
+    let skill_name = entry.manifest.name.clone();
+    let policy_level = entry.manifest.policy.clone();
+
+    init_load_errors().write().await.remove(&entry.source_path);
+
+    // Pull the skill's image in the background; skills load before any call
+    // needs a container, and a pull can take minutes
+    if let (Some(image), Some(sandbox)) = (&entry.manifest.image, crate::get_sandbox_manager()) {
+        let (image, skill_name) = (image.clone(), skill_name.clone());
+        tokio::spawn(async move {
+            if let Err(e) = sandbox.pull_image(&image).await {
+                tracing::warn!(
+                    "Image '{}' of skill '{}' is not available: {:#}",
+                    image,
+                    skill_name,
+                    e
+                );
+            }
+        });
+    }
+
+    // Keep a copy of this version so broken edits can be rolled back
+    if let Some(history) = skill_history() {
+        if let Err(e) = history.record(&entry) {
+            tracing::warn!("Failed to record version of skill '{}': {}", skill_name, e);
+        }
     }
 
     // Register policy
@@ -128,18 +615,24 @@ pub async fn load_skill(entry: SkillEntry) -> Result<()> {
             name: skill_name.clone(),
             description: entry.manifest.description.clone(),
             parameters: entry.manifest.parameters.clone(),
+            tags: entry.manifest.tags.clone(),
             execute: Arc::new(move |args| {
                 let name = name_clone.clone();
                 Box::pin(async move {
-                    match execute_skill(&name, &args).await {
+                    let result = execute_skill(&name, &args, None, None, None).await;
+                    let details = super::executor::exit_code_details(&result);
+                    match result {
                         Ok(content) => Ok(crate::plugins::traits::ToolResult {
                             content,
-                            details: None,
+                            details,
                             success: true,
                         }),
                         Err(e) => Ok(crate::plugins::traits::ToolResult {
-                            content: format!("Error: {}", e),
-                            details: None,
+                            content: match e.downcast::<CommandFailed>() {
+                                Ok(failed) => failed.output,
+                                Err(e) => format!("Error: {}", e),
+                            },
+                            details,
                             success: false,
                         }),
                     }
@@ -157,7 +650,115 @@ pub async fn load_skill(entry: SkillEntry) -> Result<()> {
     }
 
     info!("Loaded skill: '{}'", skill_name);
-    Ok(())
+}
+
+/// A skill file that could not be loaded by a rescan
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SkillLoadFailure {
+    pub path: PathBuf,
+    pub error: String,
+}
+
+/// Outcome of rescanning the skills directory
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct SkillReloadReport {
+    /// Skills loaded from the directory, by name
+    pub loaded: Vec<String>,
+    /// Skills dropped because their file is gone or no longer loads
+    pub unloaded: Vec<String>,
+    pub failed: Vec<SkillLoadFailure>,
+}
+
+/// Drop every skill loaded from `dir` and load each skill file in it again.
+///
+/// Files are parsed before the registry lock is taken, and the registry is
+/// swapped in one step, so a reload racing the skill watcher never leaves a
+/// half-loaded directory behind. Unlike a live edit, a file that no longer
+/// loads does not keep its previous version.
+pub async fn reload_skills_dir(dir: &Path) -> Result<SkillReloadReport> {
+    let paths = skill_files(dir).context(format!(
+        "Failed to read skills directory: {}",
+        dir.display()
+    ))?;
+
+    let mut report = SkillReloadReport::default();
+    let mut entries: Vec<SkillEntry> = Vec::new();
+    for path in paths {
+        let parsed = read_skill_file(&path).and_then(|entry| {
+            if let Some(other) = entries
+                .iter()
+                .find(|other| other.manifest.name == entry.manifest.name)
+            {
+                return Err(name_collision(
+                    &entry.manifest.name,
+                    &path,
+                    &other.source_path,
+                ));
+            }
+            Ok(entry)
+        });
+        match parsed {
+            Ok(entry) => entries.push(entry),
+            Err(e) => {
+                record_load_error(&path, e.to_string()).await;
+                crate::core::events::publish_event(
+                    crate::core::events::SystemEvent::ToolLoadFailed {
+                        path: path.display().to_string(),
+                        error: e.to_string(),
+                    },
+                );
+                report.failed.push(SkillLoadFailure {
+                    path,
+                    error: e.to_string(),
+                });
+            }
+        }
+    }
+
+    let registry = init_skill_bodies();
+    {
+        let mut skills = registry.write().await;
+        let mut previous: Vec<String> = skills
+            .iter()
+            .filter(|(_, entry)| entry.source_path.starts_with(dir))
+            .map(|(name, _)| name.clone())
+            .collect();
+        skills.retain(|_, entry| !entry.source_path.starts_with(dir));
+        for entry in &entries {
+            skills.insert(entry.manifest.name.clone(), entry.clone());
+        }
+
+        previous.retain(|name| !entries.iter().any(|entry| &entry.manifest.name == name));
+        previous.sort();
+        report.unloaded = previous;
+    }
+
+    for name in &report.unloaded {
+        if let Some(registry) = crate::plugins::get_plugin_registry() {
+            let _ = registry.tools.unregister_tool(name);
+        }
+        info!("Unloaded skill: '{}'", name);
+        crate::core::events::publish_event(crate::core::events::SystemEvent::ToolRemoved(
+            name.clone(),
+        ));
+    }
+    for entry in entries {
+        let name = entry.manifest.name.clone();
+        activate_skill(entry).await;
+        crate::core::events::publish_event(crate::core::events::SystemEvent::ToolUpdated(
+            name.clone(),
+        ));
+        report.loaded.push(name);
+    }
+
+    info!(
+        "Reloaded skills from {}: {} loaded, {} unloaded, {} failed",
+        dir.display(),
+        report.loaded.len(),
+        report.unloaded.len(),
+        report.failed.len()
+    );
+    Ok(report)
 }
 
 /// Unload a skill from the registry
@@ -192,18 +793,33 @@ pub async fn list_skills() -> Vec<SkillEntry> {
 }
 
 /// Execute a skill with the given arguments
-pub async fn execute_skill(name: &str, arguments: &str) -> Result<String> {
+///
+/// `use_sandbox` is the user's approval choice: `Some(true)` runs in a sandbox
+/// container whatever the skill's own `sandbox` setting. Approving a call
+/// never takes it out of the sandbox, so `Some(false)` keeps that setting.
+/// With a session the skill shares that session's workspace
+/// directory with its other exec and skill calls, and `user_id` is its owner
+/// for the tool policy.
+pub async fn execute_skill(
+    name: &str,
+    arguments: &str,
+    session_id: Option<&str>,
+    user_id: Option<&str>,
+    use_sandbox: Option<bool>,
+) -> Result<String> {
     let entry = get_skill(name)
         .await
         .ok_or_else(|| anyhow!("Skill not found: {}", name))?;
 
     let skill = &entry.manifest;
 
+    // Calls without session context share a placeholder session
+    let session_id = session_id.unwrap_or("_skill_executor");
+
     // Check policy
     if let Some(policy_engine) = crate::get_tool_policy_engine() {
-        // For skill execution, use a placeholder session_id since skills don't have session context yet
         if let Err(e) = policy_engine
-            .check_permission("_skill_executor", name)
+            .check_permission(session_id, user_id, name, arguments)
             .await
         {
             return Err(anyhow!("Skill policy check failed: {}", e));
@@ -213,26 +829,174 @@ pub async fn execute_skill(name: &str, arguments: &str) -> Result<String> {
     // Set up environment
     let env_args = arguments.to_string();
 
-    // Try to execute via sandbox if available and requested
-    if skill.sandbox {
-        if let Some(sandbox) = crate::get_sandbox_manager() {
-            return execute_skill_in_sandbox(&sandbox, &entry, &env_args).await;
+    match use_sandbox {
+        Some(true) => {
+            let sandbox = crate::get_sandbox_manager().ok_or(SandboxUnavailable)?;
+            return execute_skill_in_sandbox(
+                &ExecTarget::Container(sandbox),
+                session_id,
+                &entry,
+                &env_args,
+            )
+            .await;
+        }
+        Some(false) | None => {
+            // Try to execute via sandbox if available and requested
+            if skill.sandbox {
+                if let Some(sandbox) = crate::get_sandbox_manager() {
+                    return execute_skill_in_sandbox(
+                        &ExecTarget::Sandbox(sandbox),
+                        session_id,
+                        &entry,
+                        &env_args,
+                    )
+                    .await;
+                }
+            }
         }
     }
 
     // Fall back to local execution
-    execute_skill_local(&entry, &env_args).await
+    check_local_dependencies(skill)?;
+    execute_skill_local(session_id, &entry, &env_args).await
+}
+
+/// Check whether an executable with the given name is on PATH
+fn find_on_path(binary: &str) -> bool {
+    let Some(path) = std::env::var_os("PATH") else {
+        return false;
+    };
+
+    std::env::split_paths(&path).any(|dir| {
+        let candidate = dir.join(binary);
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::PermissionsExt;
+            candidate
+                .metadata()
+                .map(|m| m.is_file() && m.permissions().mode() & 0o111 != 0)
+                .unwrap_or(false)
+        }
+        #[cfg(not(unix))]
+        {
+            candidate.is_file()
+        }
+    })
+}
+
+/// Verify declared binaries and python packages are available on the host
+fn check_local_dependencies(skill: &SkillManifest) -> Result<()> {
+    for binary in &skill.dependencies {
+        if !find_on_path(binary) {
+            return Err(anyhow!(
+                "Skill '{}' missing dependency: {}",
+                skill.name,
+                binary
+            ));
+        }
+    }
+
+    for package in &skill.python_packages {
+        let importable = std::process::Command::new("python3")
+            .args(["-c", &format!("import {}", package)])
+            .env_clear()
+            .envs(crate::sandbox::allowed_env())
+            .output()
+            .map(|o| o.status.success())
+            .unwrap_or(false);
+
+        if !importable {
+            return Err(anyhow!(
+                "Skill '{}' missing dependency: python package '{}'",
+                skill.name,
+                package
+            ));
+        }
+    }
+
+    Ok(())
+}
+
+/// Reject package/binary names that could smuggle shell syntax into a command line
+fn is_safe_dependency_name(name: &str) -> bool {
+    !name.is_empty()
+        && name
+            .chars()
+            .all(|c| c.is_alphanumeric() || matches!(c, '-' | '_' | '.' | '='))
+}
+
+/// Check (and optionally install) declared dependencies inside the sandbox
+async fn prepare_sandbox_dependencies(
+    sandbox: &ExecTarget,
+    session_id: &str,
+    skill: &SkillManifest,
+) -> Result<()> {
+    let names = skill
+        .dependencies
+        .iter()
+        .chain(skill.python_packages.iter());
+    if let Some(bad) = names.clone().find(|n| !is_safe_dependency_name(n)) {
+        return Err(anyhow!("Invalid dependency name: {}", bad));
+    }
+
+    let timeout = std::time::Duration::from_secs(skill.timeout_secs);
+
+    if skill.install_dependencies && !skill.python_packages.is_empty() {
+        let mut cmd = vec!["pip", "install", "--quiet"];
+        cmd.extend(skill.python_packages.iter().map(|p| p.as_str()));
+
+        let result = sandbox
+            .execute(session_id, false, skill.image.as_deref(), &cmd, timeout)
+            .await
+            .context("Failed to install skill dependencies in sandbox")?;
+        if result.exit_code != 0 {
+            return Err(anyhow!(
+                "Failed to install python packages for skill '{}': {}",
+                skill.name,
+                result.stderr.trim()
+            ));
+        }
+    }
+
+    for binary in &skill.dependencies {
+        let check = format!("command -v {} >/dev/null", binary);
+        let result = sandbox
+            .execute(
+                session_id,
+                false,
+                skill.image.as_deref(),
+                &["sh", "-c", &check],
+                timeout,
+            )
+            .await
+            .context("Failed to check skill dependencies in sandbox")?;
+        if result.exit_code != 0 {
+            return Err(anyhow!(
+                "Skill '{}' missing dependency: {}",
+                skill.name,
+                binary
+            ));
+        }
+    }
+
+    Ok(())
 }
 
 /// Execute skill in local process
-async fn execute_skill_local(entry: &SkillEntry, arguments: &str) -> Result<String> {
+async fn execute_skill_local(
+    session_id: &str,
+    entry: &SkillEntry,
+    arguments: &str,
+) -> Result<String> {
     let skill = &entry.manifest;
-    let body = &entry.body;
+    let rendered =
+        super::skill_template::render(&entry.body, &skill.runtime, &skill.parameters, arguments)?;
+    let body = &rendered.body;
     let timeout_secs = skill.timeout_secs;
 
-    // Write script to a temporary file
-    let temp_dir = std::env::temp_dir();
-    let temp_file = temp_dir.join(format!("skill_{}.sh", uuid::Uuid::new_v4()));
+    // Each run gets its own directory for the script and as working directory
+    let run_dir = SkillRunDir::create()?;
+    let temp_file = run_dir.script_path();
 
     // For bash/sh, use the body directly
     let script = if skill.runtime == "python" {
@@ -262,99 +1026,190 @@ async fn execute_skill_local(entry: &SkillEntry, arguments: &str) -> Result<Stri
             .context("Failed to set executable permission")?;
     }
 
+    let mut command = tokio::process::Command::new(&temp_file);
+    command
+        .current_dir(run_dir.work_dir())
+        .env_clear()
+        .envs(crate::sandbox::allowed_env())
+        .envs(rendered.env)
+        .env(
+            crate::sandbox::SESSION_WORKSPACE_ENV,
+            crate::sandbox::ensure_session_workspace(session_id)?,
+        )
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .kill_on_drop(true);
+    match skill.version {
+        ManifestVersion::V1 => command.env("SKILL_ARGS", arguments).stdin(Stdio::null()),
+        ManifestVersion::V2 => command.stdin(Stdio::piped()),
+    };
+
     // Execute
+    let mut child = command.spawn().context("Failed to execute skill")?;
+    if let Some(mut stdin) = child.stdin.take() {
+        // Written alongside the run so a skill that never reads cannot block it
+        let input = arguments.to_string();
+        tokio::spawn(async move {
+            let _ = stdin.write_all(input.as_bytes()).await;
+        });
+    }
     let output = tokio::time::timeout(
         std::time::Duration::from_secs(timeout_secs),
-        tokio::process::Command::new(&temp_file)
-            .env("SKILL_ARGS", arguments)
-            .output(),
+        child.wait_with_output(),
     )
     .await
-    .context("Skill execution timed out")?
+    .map_err(|_| super::executor::ToolTimeout {
+        tool: skill.name.clone(),
+        secs: timeout_secs,
+    })?
     .context("Failed to execute skill")?;
 
-    // Clean up temp file
-    let _ = std::fs::remove_file(&temp_file);
-
-    // Format output
-    let mut result = String::new();
+    finish_skill_run(
+        skill,
+        &String::from_utf8_lossy(&output.stdout),
+        &String::from_utf8_lossy(&output.stderr),
+        i64::from(output.status.code().unwrap_or(-1)),
+    )
+}
 
-    if !output.stdout.is_empty() {
-        result.push_str(&String::from_utf8_lossy(&output.stdout));
+/// Temporary directory of one local skill run, holding the script and the
+/// run's working directory. Removed when dropped, so a timed-out or
+/// panicking run cleans up too.
+struct SkillRunDir(PathBuf);
+
+impl SkillRunDir {
+    fn create() -> Result<Self> {
+        let dir = Self(std::env::temp_dir().join(format!("skill_run_{}", uuid::Uuid::new_v4())));
+        std::fs::create_dir_all(dir.work_dir())
+            .context("Failed to create skill working directory")?;
+        Ok(dir)
     }
 
-    if !output.stderr.is_empty() {
-        if !result.is_empty() {
-            result.push_str("\n--- stderr ---\n");
-        }
-        result.push_str(&String::from_utf8_lossy(&output.stderr));
+    fn script_path(&self) -> PathBuf {
+        self.0.join("skill.sh")
     }
 
-    if !output.status.success() {
-        if !result.is_empty() {
-            result.push('\n');
-        }
-        result.push_str(&format!(
-            "Exit code: {}",
-            output.status.code().unwrap_or(-1)
-        ));
+    fn work_dir(&self) -> PathBuf {
+        self.0.join("work")
     }
+}
 
-    if result.is_empty() {
-        result = "(skill executed but produced no output)".to_string();
+impl Drop for SkillRunDir {
+    fn drop(&mut self) {
+        if let Err(e) = std::fs::remove_dir_all(&self.0) {
+            debug!(
+                "Failed to remove skill run directory {}: {}",
+                self.0.display(),
+                e
+            );
+        }
     }
-
-    Ok(result)
 }
 
-/// Execute skill in sandbox
+/// Execute skill in sandbox, in a container from the skill's image if it
+/// declares one
 async fn execute_skill_in_sandbox(
-    sandbox: &crate::SandboxManager,
+    sandbox: &ExecTarget,
+    session_id: &str,
     entry: &SkillEntry,
-    _arguments: &str,
+    arguments: &str,
 ) -> Result<String> {
     let skill = &entry.manifest;
-    let body = &entry.body;
+    let rendered =
+        super::skill_template::render(&entry.body, &skill.runtime, &skill.parameters, arguments)?;
 
-    // Determine runtime command
-    let cmd = match skill.runtime.as_str() {
-        "python" => vec!["python3", "-c", body],
-        "bash" | "sh" => vec!["bash", "-c", body],
-        _ => return Err(anyhow!("Unsupported runtime: {}", skill.runtime)),
-    };
+    prepare_sandbox_dependencies(sandbox, session_id, skill).await?;
+
+    let cmd = sandbox_command(skill, &rendered, arguments)?;
+    let cmd: Vec<&str> = cmd.iter().map(String::as_str).collect();
 
-    // Execute in sandbox (use a placeholder session for skills)
     let result = sandbox
-        .execute("_skill_executor", false, &cmd)
+        .execute(
+            session_id,
+            false,
+            skill.image.as_deref(),
+            &cmd,
+            std::time::Duration::from_secs(skill.timeout_secs),
+        )
         .await
         .context("Sandbox execution failed")?;
 
-    // Format output
-    let mut output = String::new();
+    finish_skill_run(skill, &result.stdout, &result.stderr, result.exit_code)
+}
 
-    if !result.stdout.is_empty() {
-        output.push_str(&result.stdout);
-    }
+/// Command running a rendered skill in a sandbox, passing the arguments the
+/// way the skill's manifest version expects. The arguments, body and
+/// template parameters are given to `sh -c` as `$0`, `$1` and `$2`..., so
+/// none of them is parsed as part of the script.
+fn sandbox_command(
+    skill: &SkillManifest,
+    rendered: &super::skill_template::RenderedSkill,
+    arguments: &str,
+) -> Result<Vec<String>> {
+    let interpreter = match skill.runtime.as_str() {
+        "python" => "python3",
+        "bash" | "sh" => "bash",
+        _ => return Err(anyhow!("Unsupported runtime: {}", skill.runtime)),
+    };
 
-    if !result.stderr.is_empty() {
-        if !output.is_empty() {
-            output.push_str("\n--- stderr ---\n");
+    // Variable names come from validated placeholders
+    let params: String = rendered
+        .env
+        .iter()
+        .enumerate()
+        .map(|(i, (name, _))| format!("{}=\"${{{}}}\" ", name, i + 2))
+        .collect();
+    let script = match skill.version {
+        ManifestVersion::V1 => {
+            format!("SKILL_ARGS=\"$0\" {}exec {} -c \"$1\"", params, interpreter)
+        }
+        ManifestVersion::V2 => format!("printf '%s' \"$0\" | {}{} -c \"$1\"", params, interpreter),
+    };
+    let mut cmd = vec![
+        "sh".to_string(),
+        "-c".to_string(),
+        script,
+        arguments.to_string(),
+        rendered.body.clone(),
+    ];
+    cmd.extend(rendered.env.iter().map(|(_, value)| value.clone()));
+    Ok(cmd)
+}
+
+/// Check a finished run against the skill's output schema and format its output.
+///
+/// Failed runs are reported as-is; validating their stdout would only hide
+/// the actual error from the model.
+fn finish_skill_run(
+    skill: &SkillManifest,
+    stdout: &str,
+    stderr: &str,
+    exit_code: i64,
+) -> Result<String> {
+    if let Some(schema) = &skill.output_schema {
+        if exit_code == 0 {
+            super::output_schema::validate_output(&skill.name, schema, stdout)?;
         }
-        output.push_str(&result.stderr);
     }
 
-    if result.exit_code != 0 {
+    let mut output = String::new();
+
+    if !stdout.is_empty() {
+        output.push_str(stdout);
+    }
+
+    if !stderr.is_empty() {
         if !output.is_empty() {
-            output.push('\n');
+            output.push_str("\n--- stderr ---\n");
         }
-        output.push_str(&format!("Exit code: {}", result.exit_code));
+        output.push_str(stderr);
     }
 
     if output.is_empty() {
         output = "(skill executed but produced no output)".to_string();
     }
 
-    Ok(output)
+    super::exec::command_result(exit_code, output)
 }
 
 #[cfg(test)]
@@ -434,6 +1289,19 @@ echo "line 2"
         assert!(result.is_err());
     }
 
+    #[test]
+    fn test_frontmatter_errors_report_file_lines() {
+        let content = "---\nname: broken\ndescription: \"Test\"\nparameters: [unclosed\nruntime: bash\n---\necho test\n";
+        let err = parse_skill_bytes(content.as_bytes(), PathBuf::from("/tmp/broken.md"))
+            .unwrap_err()
+            .to_string();
+        assert!(err.starts_with("Failed to parse skill frontmatter as YAML"));
+        assert!(err.contains("line 5"), "unexpected error: {}", err);
+
+        let err = parse_skill_bytes(&[0xff, 0xfe], PathBuf::from("/tmp/binary.md")).unwrap_err();
+        assert_eq!(err.to_string(), "Skill file is not valid UTF-8");
+    }
+
     #[test]
     fn test_empty_name() {
         let content = r#"---
@@ -479,4 +1347,479 @@ echo ok
         let retrieved = get_skill("lifecycle_test").await;
         assert!(retrieved.is_none());
     }
+
+    #[tokio::test]
+    async fn test_skill_rollback() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let history = SkillHistory::new(temp_dir.path().join(".history"));
+        let source_path = temp_dir.path().join("rollback_test.yaml");
+
+        let v1 = r#"---
+name: rollback_test
+description: "Version one"
+parameters: {}
+runtime: bash
+---
+echo one
+"#;
+        let v2 = v1
+            .replace("Version one", "Version two")
+            .replace("one\n", "two\n");
+
+        let entry1 = parse_skill_content(v1, source_path.clone()).unwrap();
+        let entry2 = parse_skill_content(&v2, source_path.clone()).unwrap();
+
+        assert_eq!(history.record(&entry1).unwrap(), 1);
+        // Recording identical content does not create a new version
+        assert_eq!(history.record(&entry1).unwrap(), 1);
+        assert_eq!(history.record(&entry2).unwrap(), 2);
+        assert_eq!(history.versions("rollback_test").unwrap().len(), 2);
+
+        std::fs::write(&source_path, &v2).unwrap();
+        load_skill(entry2).await.unwrap();
+
+        let restored = rollback_skill_from(&history, "rollback_test", 1)
+            .await
+            .unwrap();
+        assert_eq!(restored.manifest.description, "Version one");
+        assert_eq!(restored.source_path, source_path);
+
+        let active = get_skill("rollback_test").await.unwrap();
+        assert_eq!(active.manifest.description, "Version one");
+        assert!(active.body.contains("echo one"));
+
+        let on_disk = parse_skill_file(&source_path).unwrap();
+        assert_eq!(on_disk.manifest.description, "Version one");
+
+        assert!(rollback_skill_from(&history, "rollback_test", 9)
+            .await
+            .is_err());
+
+        unload_skill("rollback_test").await.unwrap();
+    }
+
+    #[test]
+    fn test_skill_history_stays_under_its_root() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let history = SkillHistory::new(temp_dir.path().join(".history"));
+        std::fs::create_dir_all(temp_dir.path().join("outside")).unwrap();
+        std::fs::write(temp_dir.path().join("outside/v1.yaml"), "secret").unwrap();
+
+        for name in [
+            "../outside",
+            "..",
+            "web/../../outside",
+            "/etc",
+            "web//fetch",
+            "",
+        ] {
+            assert!(history.versions(name).is_err(), "{}", name);
+            assert!(history.load_version(name, 1).is_err(), "{}", name);
+        }
+        // Namespaced names are kept
+        assert!(history.versions("web__fetch").unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_dependency_fields_parse() {
+        let content = r#"---
+name: deps_skill
+description: "Deps"
+parameters: {}
+runtime: bash
+dependencies: [jq, curl]
+python_packages: [requests]
+---
+echo ok
+"#;
+
+        let entry = parse_skill_content(content, PathBuf::from("/tmp/deps.yaml")).unwrap();
+        assert_eq!(entry.manifest.dependencies, vec!["jq", "curl"]);
+        assert_eq!(entry.manifest.python_packages, vec!["requests"]);
+        assert!(!entry.manifest.install_dependencies);
+    }
+
+    #[test]
+    fn test_binary_dependency_check() {
+        let content = r#"---
+name: binary_check
+description: "Binary check"
+parameters: {}
+runtime: bash
+dependencies: [sh]
+---
+echo ok
+"#;
+        let mut entry = parse_skill_content(content, PathBuf::from("/tmp/bin.yaml")).unwrap();
+        assert!(check_local_dependencies(&entry.manifest).is_ok());
+
+        entry
+            .manifest
+            .dependencies
+            .push("rustyclaw-missing-binary-xyz".to_string());
+        let err = check_local_dependencies(&entry.manifest).unwrap_err();
+        assert!(err
+            .to_string()
+            .contains("missing dependency: rustyclaw-missing-binary-xyz"));
+    }
+
+    #[test]
+    fn test_safe_dependency_names() {
+        assert!(is_safe_dependency_name("jq"));
+        assert!(is_safe_dependency_name("requests==2.31.0"));
+        assert!(!is_safe_dependency_name("jq; rm -rf /"));
+        assert!(!is_safe_dependency_name(""));
+    }
+
+    #[test]
+    fn test_validate_skill_entry() {
+        let valid = r#"---
+name: valid_skill
+description: "Valid"
+parameters: {}
+runtime: bash
+---
+echo ok
+"#;
+        let entry = parse_skill_content(valid, PathBuf::from("/tmp/valid.yaml")).unwrap();
+        assert!(validate_skill_entry(&entry).is_ok());
+
+        let mut bad_runtime = entry.clone();
+        bad_runtime.manifest.runtime = "ruby".to_string();
+        assert!(validate_skill_entry(&bad_runtime).is_err());
+
+        let mut bad_timeout = entry.clone();
+        bad_timeout.manifest.timeout_secs = 0;
+        assert!(validate_skill_entry(&bad_timeout).is_err());
+
+        let mut bad_name = entry.clone();
+        bad_name.manifest.name = "bad name!".to_string();
+        assert!(validate_skill_entry(&bad_name).is_err());
+
+        let mut non_ascii = entry.clone();
+        non_ascii.manifest.name = "café".to_string();
+        assert!(validate_skill_entry(&non_ascii).is_err());
+
+        let mut longest = entry;
+        longest.manifest.name = "a".repeat(64);
+        assert!(validate_skill_entry(&longest).is_ok());
+        longest.manifest.name.push('a');
+        assert!(validate_skill_entry(&longest).is_err());
+    }
+
+    #[test]
+    fn test_skill_image() {
+        let content = r#"---
+name: node_version
+description: "Node version"
+parameters: {}
+runtime: bash
+sandbox: true
+image: node:20-alpine
+---
+node --version
+"#;
+        let entry = parse_skill_content(content, PathBuf::from("/tmp/node.md")).unwrap();
+        assert_eq!(entry.manifest.image.as_deref(), Some("node:20-alpine"));
+        assert!(validate_skill_entry(&entry).is_ok());
+
+        let mut bad_image = entry;
+        bad_image.manifest.image = Some("node; rm -rf /".to_string());
+        assert!(validate_skill_entry(&bad_image).is_err());
+    }
+
+    #[test]
+    fn test_template_with_undefined_param_rejected() {
+        let content = r#"---
+name: bad_template
+description: "Bad template"
+parameters:
+  type: object
+  properties:
+    msg:
+      type: string
+runtime: bash
+---
+echo {{other}}
+"#;
+
+        let result = parse_skill_content(content, PathBuf::from("/tmp/bad_template.md"));
+        assert!(result.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_templated_skill_execution_quotes_arguments() {
+        let content = r#"---
+name: template_exec_test
+description: "Template exec test"
+parameters:
+  type: object
+  properties:
+    msg:
+      type: string
+runtime: bash
+---
+echo "Hi {{msg}}"
+"#;
+
+        let entry = parse_skill_content(content, PathBuf::from("/tmp/template.md")).unwrap();
+        let arguments = r#"{"msg": "$(echo INJECTED); echo INJECTED"}"#;
+        let output = execute_skill_local("test", &entry, arguments)
+            .await
+            .unwrap();
+        assert_eq!(output.trim(), "Hi $(echo INJECTED); echo INJECTED");
+
+        // Sandboxed runs pass the values the same way
+        let rendered = crate::tools::skill_template::render(
+            &entry.body,
+            "bash",
+            &entry.manifest.parameters,
+            arguments,
+        )
+        .unwrap();
+        let cmd = sandbox_command(&entry.manifest, &rendered, arguments).unwrap();
+        let cmd: Vec<&str> = cmd.iter().map(String::as_str).collect();
+        let result = crate::sandbox::execute_on_host(None, &cmd).await.unwrap();
+        assert_eq!(result.stdout.trim(), "Hi $(echo INJECTED); echo INJECTED");
+    }
+
+    #[tokio::test]
+    async fn test_skill_env_is_scrubbed() {
+        std::env::set_var("RUSTYCLAW_TEST_SECRET", "hunter2");
+
+        let content = r#"---
+name: env_scrub_test
+description: "Env scrub test"
+parameters: {}
+runtime: bash
+---
+echo "secret=${RUSTYCLAW_TEST_SECRET:-unset} args=$SKILL_ARGS"
+"#;
+
+        let entry = parse_skill_content(content, PathBuf::from("/tmp/env_scrub.md")).unwrap();
+        let output = execute_skill_local("test", &entry, "{}").await.unwrap();
+
+        assert!(output.contains("secret=unset"));
+        assert!(output.contains("args={}"));
+        assert!(!output.contains("hunter2"));
+    }
+
+    #[test]
+    fn test_manifest_versions() {
+        let manifest = |version: &str| {
+            format!(
+                "---\nname: versioned\ndescription: \"Versioned\"\nparameters: {{}}\nruntime: bash\n{}---\ncat\n",
+                version
+            )
+        };
+
+        let v1 = parse_skill_content(&manifest(""), PathBuf::from("/tmp/v1.md")).unwrap();
+        assert_eq!(v1.manifest.version, ManifestVersion::V1);
+        assert!(!serde_yaml::to_string(&v1.manifest)
+            .unwrap()
+            .contains("version"));
+
+        let v2 =
+            parse_skill_content(&manifest("version: 2\n"), PathBuf::from("/tmp/v2.md")).unwrap();
+        assert_eq!(v2.manifest.version, ManifestVersion::V2);
+        assert!(serde_yaml::to_string(&v2.manifest)
+            .unwrap()
+            .contains("version: 2"));
+
+        let err = parse_skill_content(&manifest("version: 3\n"), PathBuf::from("/tmp/v3.md"))
+            .unwrap_err();
+        assert!(err
+            .to_string()
+            .contains("unsupported skill manifest version 3"));
+    }
+
+    #[tokio::test]
+    async fn test_arguments_are_passed_by_manifest_version() {
+        let content = |version: &str| {
+            format!(
+                "---\nname: args_test\ndescription: \"Args test\"\nparameters: {{}}\nruntime: bash\n{}---\necho \"stdin=$(cat) env=${{SKILL_ARGS:-unset}}\"\n",
+                version
+            )
+        };
+        let arguments = r#"{"msg": "hi"}"#;
+
+        let v1 = parse_skill_content(&content(""), PathBuf::from("/tmp/args_v1.md")).unwrap();
+        let output = execute_skill_local("test", &v1, arguments).await.unwrap();
+        assert_eq!(output.trim(), r#"stdin= env={"msg": "hi"}"#);
+
+        let v2 = parse_skill_content(&content("version: 2\n"), PathBuf::from("/tmp/args_v2.md"))
+            .unwrap();
+        let output = execute_skill_local("test", &v2, arguments).await.unwrap();
+        assert_eq!(output.trim(), r#"stdin={"msg": "hi"} env=unset"#);
+
+        // Sandboxed runs get the same contract
+        for (entry, expected) in [
+            (&v1, r#"stdin= env={"msg": "hi"}"#),
+            (&v2, r#"stdin={"msg": "hi"} env=unset"#),
+        ] {
+            let rendered = crate::tools::skill_template::RenderedSkill {
+                body: entry.body.clone(),
+                env: Vec::new(),
+            };
+            let cmd = sandbox_command(&entry.manifest, &rendered, arguments).unwrap();
+            let cmd: Vec<&str> = cmd.iter().map(String::as_str).collect();
+            let result = crate::sandbox::execute_on_host(None, &cmd).await.unwrap();
+            assert_eq!(result.stdout.trim(), expected);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_concurrent_runs_have_separate_working_directories() {
+        let content = r#"---
+name: concurrent_test
+description: "Concurrent test"
+parameters: {}
+runtime: bash
+---
+echo "$SKILL_ARGS" > out.txt
+sleep 0.2
+cat out.txt
+pwd
+"#;
+        let entry = parse_skill_content(content, PathBuf::from("/tmp/concurrent.md")).unwrap();
+
+        let runs = (0..16).map(|n| {
+            let entry = &entry;
+            async move {
+                let arguments = format!(r#"{{"n": {}}}"#, n);
+                let output = execute_skill_local("test", entry, &arguments)
+                    .await
+                    .unwrap();
+                (arguments, output)
+            }
+        });
+
+        for (arguments, output) in futures::future::join_all(runs).await {
+            let mut lines = output.lines();
+            assert_eq!(lines.next(), Some(arguments.as_str()));
+            let work_dir = PathBuf::from(lines.next().unwrap());
+            assert!(!work_dir.exists(), "{} left behind", work_dir.display());
+        }
+    }
+
+    #[tokio::test]
+    async fn test_session_workspace_is_shared_between_tool_calls() {
+        let session_id = format!("workspace-test-{}", uuid::Uuid::new_v4());
+
+        let content = r#"---
+name: session_workspace_test
+description: "Session workspace test"
+parameters: {}
+runtime: bash
+---
+echo "written by a skill" > "$RUSTYCLAW_SESSION_WORKSPACE/note.txt"
+"#;
+
+        let entry = parse_skill_content(content, PathBuf::from("/tmp/session_ws.md")).unwrap();
+        execute_skill_local(&session_id, &entry, "{}")
+            .await
+            .unwrap();
+
+        // A later exec in the same session sees the file
+        let params = super::super::exec::BashParams {
+            script: "cat \"$RUSTYCLAW_SESSION_WORKSPACE/note.txt\"".to_string(),
+        };
+        let timeout = std::time::Duration::from_secs(10);
+        let output =
+            super::super::exec::exec_bash(&ExecTarget::Host, &session_id, true, params, timeout)
+                .await
+                .unwrap();
+        assert!(output.starts_with("written by a skill\n"), "{}", output);
+
+        // Clearing the session removes its workspace
+        let path = crate::sandbox::session_workspace_path(&session_id);
+        assert!(path.join("note.txt").exists());
+        crate::sandbox::remove_session_workspace(&session_id).unwrap();
+        assert!(!path.exists());
+    }
+
+    #[tokio::test]
+    async fn test_output_schema_validation() {
+        let content = r#"---
+name: output_schema_test
+description: "Output schema test"
+parameters: {}
+runtime: bash
+output_schema:
+  type: object
+  required: [count]
+  properties:
+    count:
+      type: integer
+---
+echo "$SKILL_ARGS"
+"#;
+
+        let entry = parse_skill_content(content, PathBuf::from("/tmp/output_schema.md")).unwrap();
+        assert!(validate_skill_entry(&entry).is_ok());
+
+        let output = execute_skill_local("test", &entry, r#"{"count": 3}"#)
+            .await
+            .unwrap();
+        assert_eq!(output.trim(), r#"{"count": 3}"#);
+
+        let err = execute_skill_local("test", &entry, r#"{"count": "three"}"#)
+            .await
+            .unwrap_err();
+        let mismatch = err
+            .downcast_ref::<super::super::output_schema::OutputSchemaMismatch>()
+            .unwrap();
+        assert_eq!(
+            mismatch.errors,
+            vec!["$.count: expected integer, got string"]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_reload_reports_mixed_skills_dir() {
+        let dir = tempfile::tempdir().unwrap();
+        let skill = |name: &str, body: &str| {
+            format!(
+                "---\nname: {}\ndescription: \"Reload test\"\nparameters: {{}}\nruntime: bash\n---\n{}\n",
+                name, body
+            )
+        };
+        std::fs::write(dir.path().join("a.yaml"), skill("reload_valid", "echo ok")).unwrap();
+        std::fs::write(
+            dir.path().join("b.yaml"),
+            skill("reload_broken", "if true; then"),
+        )
+        .unwrap();
+        std::fs::write(
+            dir.path().join("c.yml"),
+            skill("reload_valid", "echo again"),
+        )
+        .unwrap();
+        std::fs::write(dir.path().join("d.yaml"), "no frontmatter").unwrap();
+        std::fs::write(dir.path().join(".hidden.yaml"), "ignored").unwrap();
+
+        // Loaded earlier from a file that has since been deleted
+        let gone = dir.path().join("gone.yaml");
+        std::fs::write(&gone, skill("reload_gone", "echo gone")).unwrap();
+        load_skill(parse_skill_file(&gone).unwrap()).await.unwrap();
+        std::fs::remove_file(&gone).unwrap();
+
+        let report = reload_skills_dir(dir.path()).await.unwrap();
+        assert_eq!(report.loaded, vec!["reload_valid"]);
+        assert_eq!(report.unloaded, vec!["reload_gone"]);
+        let failed: Vec<_> = report
+            .failed
+            .iter()
+            .map(|failure| failure.path.file_name().unwrap().to_str().unwrap())
+            .collect();
+        assert_eq!(failed, vec!["b.yaml", "c.yml", "d.yaml"]);
+        assert!(report.failed[1].error.contains("collides"));
+
+        assert_eq!(get_skill("reload_valid").await.unwrap().body, "echo ok\n");
+        assert!(get_skill("reload_broken").await.is_none());
+        assert!(get_skill("reload_gone").await.is_none());
+        assert!(get_load_error(&dir.path().join("d.yaml")).await.is_some());
+
+        unload_skill("reload_valid").await.ok();
+    }
 }
@@ -25,7 +25,7 @@ fn default_count() -> usize {
 
 /// Fetch content from a URL
 pub async fn web_fetch(params: WebFetchParams) -> Result<String> {
-    let client = reqwest::Client::builder()
+    let client = crate::network::client_builder()?
         .user_agent("RustyClaw/0.1.0 (Privacy-focused AI Assistant)")
         .timeout(std::time::Duration::from_secs(30))
         .build()?;
@@ -1,7 +1,40 @@
 use crate::llm::ToolDefinition;
 use anyhow::{Context, Result};
+use once_cell::sync::Lazy;
 use serde::{Deserialize, Serialize};
 use serde_json::json;
+use std::collections::HashMap;
+use std::sync::Mutex;
+
+/// Account each WhatsApp session's messages arrive on, by session
+static SESSION_ACCOUNTS: Lazy<Mutex<HashMap<String, String>>> =
+    Lazy::new(|| Mutex::new(HashMap::new()));
+
+/// Remember the account a session's messages arrive on, which
+/// `send_whatsapp` uses when no account is given
+pub fn set_session_account(session_id: &str, account_id: &str) {
+    SESSION_ACCOUNTS
+        .lock()
+        .unwrap()
+        .insert(session_id.to_string(), account_id.to_string());
+}
+
+/// Account a message is sent from: the one asked for, else the session's
+/// own while it is still connected. `None` means the default account.
+fn select_account(
+    account_id: Option<&str>,
+    session_id: Option<&str>,
+    connected: &[String],
+) -> Option<String> {
+    if let Some(account_id) = account_id {
+        return Some(account_id.to_string());
+    }
+    let accounts = SESSION_ACCOUNTS.lock().unwrap();
+    session_id
+        .and_then(|session_id| accounts.get(session_id))
+        .filter(|account_id| connected.contains(account_id))
+        .cloned()
+}
 
 /// Parameters for sending a WhatsApp message
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -12,9 +45,10 @@ pub struct SendWhatsAppParams {
     pub target: String,
     /// Message to send
     pub message: String,
-    /// Account to send from (optional, defaults to first account)
-    #[serde(default)]
-    pub from_account: Option<String>,
+    /// Account to send from (optional, defaults to the session's account,
+    /// then the first account)
+    #[serde(default, alias = "from_account")]
+    pub account_id: Option<String>,
     /// Skip confirmation (dangerous - requires explicit use)
     #[serde(default)]
     pub skip_confirmation: bool,
@@ -28,15 +62,58 @@ pub struct ListWhatsAppGroupsParams {}
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ListWhatsAppAccountsParams {}
 
+/// Parameters for listing the participants of a WhatsApp group
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ListWhatsAppGroupParticipantsParams {
+    /// Group name or JID
+    pub group: String,
+    /// Account to use (optional, defaults to first account)
+    #[serde(default)]
+    pub from_account: Option<String>,
+}
+
+/// Parameters for adding or removing WhatsApp group participants
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ManageWhatsAppParticipantParams {
+    /// Group name or JID
+    pub group: String,
+    /// Phone numbers of the participants
+    pub participants: Vec<String>,
+    /// Account to use (optional, defaults to first account)
+    #[serde(default)]
+    pub from_account: Option<String>,
+}
+
+/// Parameters for checking whether phone numbers are on WhatsApp
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct VerifyWhatsAppContactsParams {
+    /// Phone numbers to check
+    pub phones: Vec<String>,
+    /// Account to use (optional, defaults to first account)
+    #[serde(default)]
+    pub from_account: Option<String>,
+}
+
+/// Get the service for a specific account, or the default one
+fn service_for(
+    from_account: Option<&str>,
+) -> Result<std::sync::Arc<crate::channels::whatsapp::WhatsAppService>> {
+    match from_account {
+        Some(account_id) => crate::get_whatsapp_service_by_account(account_id)
+            .context(format!("WhatsApp account '{}' not found", account_id)),
+        None => crate::get_whatsapp_service().context("WhatsApp service not available"),
+    }
+}
+
 /// Send a WhatsApp message to a contact or group
-pub async fn send_whatsapp(params: SendWhatsAppParams) -> Result<String> {
-    // Get service for specific account or default
-    let service = if let Some(account_id) = &params.from_account {
-        crate::get_whatsapp_service_by_account(account_id)
-            .context(format!("WhatsApp account '{}' not found", account_id))?
-    } else {
-        crate::get_whatsapp_service().context("WhatsApp service not available")?
-    };
+pub async fn send_whatsapp(session_id: Option<&str>, params: SendWhatsAppParams) -> Result<String> {
+    // Get service for the requested account, the session's, or the default
+    let account_id = select_account(
+        params.account_id.as_deref(),
+        session_id,
+        &crate::list_whatsapp_accounts(),
+    );
+    let service = service_for(account_id.as_deref())?;
 
     // CONFIRMATION STEP (unless skipped)
     if !params.skip_confirmation {
@@ -70,8 +147,7 @@ pub async fn send_whatsapp(params: SendWhatsAppParams) -> Result<String> {
         _ => anyhow::bail!("Invalid target_type: must be 'contact' or 'group'"),
     };
 
-    let account_info = params
-        .from_account
+    let account_info = account_id
         .map(|a| format!(" from account '{}'", a))
         .unwrap_or_default();
 
@@ -105,6 +181,91 @@ pub async fn list_whatsapp_groups(_params: ListWhatsAppGroupsParams) -> Result<S
     Ok(result)
 }
 
+/// List the participants of a WhatsApp group
+pub async fn list_whatsapp_group_participants(
+    params: ListWhatsAppGroupParticipantsParams,
+) -> Result<String> {
+    let service = service_for(params.from_account.as_deref())?;
+
+    let participants = service.list_group_participants(&params.group).await?;
+
+    if participants.is_empty() {
+        return Ok(format!("Group '{}' has no participants", params.group));
+    }
+
+    let mut result = format!(
+        "Participants of '{}' ({}):\n",
+        params.group,
+        participants.len()
+    );
+    for participant in participants {
+        let role = if participant.is_admin { " (admin)" } else { "" };
+        result.push_str(&format!("• {}{}\n", participant.jid, role));
+    }
+
+    Ok(result)
+}
+
+/// Add participants to a WhatsApp group
+pub async fn add_whatsapp_participant(params: ManageWhatsAppParticipantParams) -> Result<String> {
+    let service = service_for(params.from_account.as_deref())?;
+
+    service
+        .add_group_participants(&params.group, &params.participants)
+        .await?;
+
+    Ok(format!(
+        "✓ Added {} to WhatsApp group '{}'",
+        params.participants.join(", "),
+        params.group
+    ))
+}
+
+/// Remove participants from a WhatsApp group
+pub async fn remove_whatsapp_participant(
+    params: ManageWhatsAppParticipantParams,
+) -> Result<String> {
+    let service = service_for(params.from_account.as_deref())?;
+
+    service
+        .remove_group_participants(&params.group, &params.participants)
+        .await?;
+
+    Ok(format!(
+        "✓ Removed {} from WhatsApp group '{}'",
+        params.participants.join(", "),
+        params.group
+    ))
+}
+
+/// Check which phone numbers are registered on WhatsApp
+pub async fn verify_whatsapp_contacts(params: VerifyWhatsAppContactsParams) -> Result<String> {
+    if params.phones.is_empty() {
+        anyhow::bail!("At least one phone number is required");
+    }
+
+    let service = service_for(params.from_account.as_deref())?;
+
+    let phones: Vec<&str> = params.phones.iter().map(|p| p.as_str()).collect();
+    let results = service.verify_contacts(&phones).await?;
+
+    let mut result = String::from("WhatsApp contact verification:\n");
+    for contact in results {
+        let status = match &contact.jid {
+            Some(jid) => format!("on WhatsApp ({})", jid),
+            None => "not on WhatsApp".to_string(),
+        };
+        let source = if contact.cached {
+            "cache hit"
+        } else {
+            "cache miss"
+        };
+        result.push_str(&format!("• {}: {} [{}]\n", contact.phone, status, source));
+    }
+
+    Ok(result)
+}
+
 /// List all connected WhatsApp accounts
 pub async fn list_whatsapp_accounts(_params: ListWhatsAppAccountsParams) -> Result<String> {
     let accounts = crate::list_whatsapp_accounts();
@@ -123,6 +284,13 @@ pub async fn list_whatsapp_accounts(_params: ListWhatsAppAccountsParams) -> Resu
 
 /// Get WhatsApp tool definitions for LLM
 pub fn get_whatsapp_tool_definitions() -> Vec<ToolDefinition> {
+    let mut accounts = crate::list_whatsapp_accounts();
+    accounts.sort();
+    let account_description = format!(
+        "Optional: Which WhatsApp account to send from (connected: {}). Defaults to the account this conversation came in on, then the first account",
+        accounts.join(", ")
+    );
+
     vec![
         ToolDefinition {
             name: "send_whatsapp".to_string(),
@@ -145,9 +313,9 @@ pub fn get_whatsapp_tool_definitions() -> Vec<ToolDefinition> {
                         "type": "string",
                         "description": "The message to send"
                     },
-                    "from_account": {
+                    "account_id": {
                         "type": "string",
-                        "description": "Optional: Which WhatsApp account to send from (defaults to first account)"
+                        "description": account_description
                     }
                 },
                 "required": ["target_type", "target", "message"]
@@ -161,6 +329,57 @@ pub fn get_whatsapp_tool_definitions() -> Vec<ToolDefinition> {
                 "properties": {}
             }),
         },
+        ToolDefinition {
+            name: "list_whatsapp_group_participants".to_string(),
+            description: "List the participants of a WhatsApp group (by name or ID), including admins"
+                .to_string(),
+            parameters: json!({
+                "type": "object",
+                "properties": {
+                    "group": {
+                        "type": "string",
+                        "description": "Group name (e.g., 'Team Alpha') or group ID"
+                    },
+                    "from_account": {
+                        "type": "string",
+                        "description": "Optional: Which WhatsApp account to use (defaults to first account)"
+                    }
+                },
+                "required": ["group"]
+            }),
+        },
+        ToolDefinition {
+            name: "add_whatsapp_participant".to_string(),
+            description: "Add contacts to a WhatsApp group. Requires admin rights in the group."
+                .to_string(),
+            parameters: participant_mutation_schema(),
+        },
+        ToolDefinition {
+            name: "remove_whatsapp_participant".to_string(),
+            description: "Remove contacts from a WhatsApp group. Requires admin rights in the group."
+                .to_string(),
+            parameters: participant_mutation_schema(),
+        },
+        ToolDefinition {
+            name: "verify_whatsapp_contacts".to_string(),
+            description: "Check whether phone numbers are registered on WhatsApp (results are cached)"
+                .to_string(),
+            parameters: json!({
+                "type": "object",
+                "properties": {
+                    "phones": {
+                        "type": "array",
+                        "items": { "type": "string" },
+                        "description": "Phone numbers to check (e.g., ['1234567890'])"
+                    },
+                    "from_account": {
+                        "type": "string",
+                        "description": "Optional: Which WhatsApp account to use (defaults to first account)"
+                    }
+                },
+                "required": ["phones"]
+            }),
+        },
         ToolDefinition {
             name: "list_whatsapp_accounts".to_string(),
             description: "List all connected WhatsApp accounts".to_string(),
@@ -172,6 +391,29 @@ pub fn get_whatsapp_tool_definitions() -> Vec<ToolDefinition> {
     ]
 }
 
+/// Shared parameter schema for the add/remove participant tools
+fn participant_mutation_schema() -> serde_json::Value {
+    json!({
+        "type": "object",
+        "properties": {
+            "group": {
+                "type": "string",
+                "description": "Group name (e.g., 'Team Alpha') or group ID"
+            },
+            "participants": {
+                "type": "array",
+                "items": { "type": "string" },
+                "description": "Phone numbers of the participants (e.g., ['1234567890'])"
+            },
+            "from_account": {
+                "type": "string",
+                "description": "Optional: Which WhatsApp account to use (defaults to first account)"
+            }
+        },
+        "required": ["group", "participants"]
+    })
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -182,7 +424,7 @@ mod tests {
             target_type: "contact".to_string(),
             target: "1234567890".to_string(),
             message: "Hello from RustyClaw".to_string(),
-            from_account: Some("personal".to_string()),
+            account_id: Some("personal".to_string()),
             skip_confirmation: false,
         };
 
@@ -192,17 +434,56 @@ mod tests {
         assert_eq!(deserialized.target_type, "contact");
         assert_eq!(deserialized.target, "1234567890");
         assert_eq!(deserialized.message, "Hello from RustyClaw");
-        assert_eq!(deserialized.from_account, Some("personal".to_string()));
+        assert_eq!(deserialized.account_id, Some("personal".to_string()));
+
+        // The old parameter name still works
+        let legacy: SendWhatsAppParams = serde_json::from_value(json!({
+            "target_type": "contact",
+            "target": "1234567890",
+            "message": "Hi",
+            "from_account": "work",
+        }))
+        .unwrap();
+        assert_eq!(legacy.account_id, Some("work".to_string()));
         assert!(!deserialized.skip_confirmation);
     }
 
     #[test]
     fn test_tool_definitions() {
         let tools = get_whatsapp_tool_definitions();
-        assert_eq!(tools.len(), 3);
+        assert_eq!(tools.len(), 7);
         assert_eq!(tools[0].name, "send_whatsapp");
         assert_eq!(tools[1].name, "list_whatsapp_groups");
-        assert_eq!(tools[2].name, "list_whatsapp_accounts");
+        assert_eq!(tools[2].name, "list_whatsapp_group_participants");
+        assert_eq!(tools[3].name, "add_whatsapp_participant");
+        assert_eq!(tools[4].name, "remove_whatsapp_participant");
+        assert_eq!(tools[5].name, "verify_whatsapp_contacts");
+        assert_eq!(tools[6].name, "list_whatsapp_accounts");
+    }
+
+    #[test]
+    fn test_session_account_is_selected() {
+        let connected = vec!["personal".to_string(), "work".to_string()];
+        set_session_account("work-session", "work");
+
+        assert_eq!(
+            select_account(None, Some("work-session"), &connected),
+            Some("work".to_string())
+        );
+        // An account asked for wins over the session's
+        assert_eq!(
+            select_account(Some("personal"), Some("work-session"), &connected),
+            Some("personal".to_string())
+        );
+        // Unknown sessions and disconnected accounts use the default
+        assert_eq!(
+            select_account(None, Some("other-session"), &connected),
+            None
+        );
+        assert_eq!(
+            select_account(None, Some("work-session"), &connected[..1]),
+            None
+        );
     }
 
     #[test]
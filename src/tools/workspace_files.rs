@@ -0,0 +1,324 @@
+//! The `list_workspace` and `read_workspace_file` tools
+//!
+//! Let the model show the files its commands produced in the session
+//! workspace. Paths are checked like staged files: relative, without `..`,
+//! and never resolving (through symlinks) outside the session's directory.
+
+use crate::llm::ToolDefinition;
+use crate::sandbox::{session_workspace_path, validate_workspace_path};
+use anyhow::{anyhow, Context, Result};
+use base64::Engine;
+use serde::Deserialize;
+use serde_json::json;
+use std::io::Read;
+use std::path::{Path, PathBuf};
+
+/// Most entries listed by `list_workspace`
+pub const MAX_LISTED_FILES: usize = 200;
+
+/// Most bytes of a text file returned by `read_workspace_file`
+pub const MAX_READ_TEXT_BYTES: usize = 64 * 1024;
+
+/// Most bytes of a binary file returned (base64-encoded) by
+/// `read_workspace_file`
+pub const MAX_READ_BINARY_BYTES: usize = 48 * 1024;
+
+/// Parameters for list_workspace
+#[derive(Debug, Default, Deserialize)]
+pub struct ListWorkspaceParams {
+    /// Directory inside the workspace to list (default: all of it)
+    #[serde(default)]
+    pub path: Option<String>,
+}
+
+/// Parameters for read_workspace_file
+#[derive(Debug, Deserialize)]
+pub struct ReadWorkspaceFileParams {
+    pub path: String,
+}
+
+/// Run the `list_workspace` tool
+pub fn list_workspace(session_id: Option<&str>, params: ListWorkspaceParams) -> Result<String> {
+    list_files_in(&workspace_root(session_id)?, params)
+}
+
+/// Run the `read_workspace_file` tool
+pub fn read_workspace_file(
+    session_id: Option<&str>,
+    params: ReadWorkspaceFileParams,
+) -> Result<String> {
+    read_file_in(&workspace_root(session_id)?, params)
+}
+
+fn workspace_root(session_id: Option<&str>) -> Result<PathBuf> {
+    let session_id =
+        session_id.ok_or_else(|| anyhow!("Workspace files are only available in a session"))?;
+    Ok(session_workspace_path(session_id))
+}
+
+/// Files under a directory of the workspace at `root`, one per line with
+/// their size; directories end with `/`
+fn list_files_in(root: &Path, params: ListWorkspaceParams) -> Result<String> {
+    let dir = match params.path.as_deref().map(str::trim) {
+        None | Some("") | Some(".") => root.to_path_buf(),
+        Some(path) => resolve(root, path)?,
+    };
+    if !dir.exists() {
+        return Ok("The session workspace is empty.".to_string());
+    }
+
+    let mut entries = Vec::new();
+    let mut total = 0;
+    collect_entries(&dir, &dir, &mut entries, &mut total)?;
+    if entries.is_empty() {
+        return Ok("The session workspace is empty.".to_string());
+    }
+
+    let mut listing = entries.join("\n");
+    if total > entries.len() {
+        listing.push_str(&format!("\n... and {} more entries", total - entries.len()));
+    }
+    Ok(listing)
+}
+
+/// Walk `dir` in name order, keeping the first `MAX_LISTED_FILES` entries and
+/// counting the rest. Symlinks are listed but not followed.
+fn collect_entries(
+    base: &Path,
+    dir: &Path,
+    entries: &mut Vec<String>,
+    total: &mut usize,
+) -> Result<()> {
+    let mut children = std::fs::read_dir(dir)
+        .with_context(|| format!("Failed to list {}", dir.display()))?
+        .collect::<std::io::Result<Vec<_>>>()?;
+    children.sort_by_key(|entry| entry.file_name());
+
+    for child in children {
+        let path = child.path();
+        let relative = path.strip_prefix(base).unwrap_or(&path).display();
+        let file_type = child.file_type()?;
+        *total += 1;
+
+        if file_type.is_dir() {
+            if entries.len() < MAX_LISTED_FILES {
+                entries.push(format!("{}/", relative));
+            }
+            collect_entries(base, &path, entries, total)?;
+        } else if entries.len() < MAX_LISTED_FILES {
+            let size = child.metadata()?.len();
+            entries.push(format!("{} ({} bytes)", relative, size));
+        }
+    }
+    Ok(())
+}
+
+/// Content of a workspace file: text as is, anything else base64-encoded,
+/// both cut off at their size limit
+fn read_file_in(root: &Path, params: ReadWorkspaceFileParams) -> Result<String> {
+    let path = resolve(root, &params.path)?;
+    if !path.is_file() {
+        return Err(anyhow!(
+            "'{}' is not a file in the session workspace",
+            params.path
+        ));
+    }
+
+    // Only what can be shown is read, plus a byte to tell a file that was
+    // cut off from one that fits exactly
+    let file =
+        std::fs::File::open(&path).with_context(|| format!("Failed to read {}", params.path))?;
+    let size = file.metadata()?.len() as usize;
+    let limit = MAX_READ_TEXT_BYTES.max(MAX_READ_BINARY_BYTES) as u64;
+    let mut content = Vec::new();
+    file.take(limit + 1)
+        .read_to_end(&mut content)
+        .with_context(|| format!("Failed to read {}", params.path))?;
+
+    if let Some(text) = text_prefix(&content, MAX_READ_TEXT_BYTES) {
+        let mut text = text.to_string();
+        if text.len() < size {
+            text.push_str(&format!(
+                "\n\n[truncated: showing the first {} of {} bytes]",
+                text.len(),
+                size
+            ));
+        }
+        return Ok(text);
+    }
+
+    let shown = content.len().min(MAX_READ_BINARY_BYTES);
+    let encoded = base64::engine::general_purpose::STANDARD.encode(&content[..shown]);
+    let mut text = format!(
+        "Binary file {} ({} bytes), base64-encoded:\n{}",
+        params.path, size, encoded
+    );
+    if shown < size {
+        text.push_str(&format!(
+            "\n\n[truncated: showing the first {} of {} bytes]",
+            shown, size
+        ));
+    }
+    Ok(text)
+}
+
+/// Up to `limit` bytes of `content` as text, cut at a character boundary, or
+/// `None` when the content is binary
+fn text_prefix(content: &[u8], limit: usize) -> Option<&str> {
+    let head = &content[..content.len().min(limit)];
+    if head.contains(&0) {
+        return None;
+    }
+    match std::str::from_utf8(head) {
+        Ok(text) => Some(text),
+        // A character split by the limit is fine, anything else is binary
+        Err(e) if e.error_len().is_none() && head.len() < content.len() => {
+            std::str::from_utf8(&head[..e.valid_up_to()]).ok()
+        }
+        Err(_) => None,
+    }
+}
+
+/// Host path of a workspace entry, rejecting paths that leave the workspace
+/// directly or through a symlink
+fn resolve(root: &Path, path: &str) -> Result<PathBuf> {
+    let relative = validate_workspace_path(path)?;
+    let target = root.join(relative);
+    if !target.exists() {
+        return Err(anyhow!(
+            "'{}' does not exist in the session workspace",
+            path
+        ));
+    }
+
+    let root = root.canonicalize()?;
+    let resolved = target.canonicalize()?;
+    if !resolved.starts_with(&root) {
+        return Err(anyhow!(
+            "Invalid workspace path '{}': must stay inside the session workspace",
+            path
+        ));
+    }
+    Ok(resolved)
+}
+
+pub fn get_workspace_file_tool_definitions() -> Vec<ToolDefinition> {
+    vec![
+        ToolDefinition {
+            name: "list_workspace".to_string(),
+            description: "List the files in this session's workspace (files written by commands and staged files), with their sizes.".to_string(),
+            parameters: json!({
+                "type": "object",
+                "properties": {
+                    "path": {
+                        "type": "string",
+                        "description": "Directory inside the workspace to list (default: the whole workspace)"
+                    }
+                }
+            }),
+        },
+        ToolDefinition {
+            name: "read_workspace_file".to_string(),
+            description: "Read a file from this session's workspace. Text is returned as is, binary files base64-encoded; large files are truncated.".to_string(),
+            parameters: json!({
+                "type": "object",
+                "properties": {
+                    "path": {
+                        "type": "string",
+                        "description": "Path relative to the workspace, e.g. \"out/report.csv\""
+                    }
+                },
+                "required": ["path"]
+            }),
+        },
+    ]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn workspace() -> tempfile::TempDir {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::create_dir_all(dir.path().join("out")).unwrap();
+        std::fs::write(dir.path().join("notes.txt"), "hello").unwrap();
+        std::fs::write(dir.path().join("out/data.bin"), [0u8, 159, 146, 150]).unwrap();
+        dir
+    }
+
+    fn read(root: &Path, path: &str) -> Result<String> {
+        read_file_in(
+            root,
+            ReadWorkspaceFileParams {
+                path: path.to_string(),
+            },
+        )
+    }
+
+    #[test]
+    fn test_list_workspace_files() {
+        let dir = workspace();
+
+        let listing = list_files_in(dir.path(), ListWorkspaceParams::default()).unwrap();
+        assert_eq!(listing, "notes.txt (5 bytes)\nout/\nout/data.bin (4 bytes)");
+
+        let params = ListWorkspaceParams {
+            path: Some("out".to_string()),
+        };
+        assert_eq!(
+            list_files_in(dir.path(), params).unwrap(),
+            "data.bin (4 bytes)"
+        );
+
+        let empty = tempfile::tempdir().unwrap();
+        let missing = empty.path().join("never-created");
+        assert_eq!(
+            list_files_in(&missing, ListWorkspaceParams::default()).unwrap(),
+            "The session workspace is empty."
+        );
+    }
+
+    #[test]
+    fn test_read_text_and_binary_files() {
+        let dir = workspace();
+
+        assert_eq!(read(dir.path(), "notes.txt").unwrap(), "hello");
+        assert_eq!(
+            read(dir.path(), "out/data.bin").unwrap(),
+            "Binary file out/data.bin (4 bytes), base64-encoded:\nAJ+Slg=="
+        );
+
+        let long = "é".repeat(MAX_READ_TEXT_BYTES);
+        std::fs::write(dir.path().join("long.txt"), &long).unwrap();
+        let text = read(dir.path(), "long.txt").unwrap();
+        assert!(text.starts_with("éé"));
+        assert!(text.ends_with(&format!(
+            "[truncated: showing the first {} of {} bytes]",
+            MAX_READ_TEXT_BYTES,
+            long.len()
+        )));
+    }
+
+    #[test]
+    fn test_paths_stay_inside_the_workspace() {
+        let dir = workspace();
+        let outside = tempfile::tempdir().unwrap();
+        std::fs::write(outside.path().join("secret.txt"), "secret").unwrap();
+
+        for bad in ["../secret.txt", "/etc/passwd", "out/../../secret.txt", ""] {
+            assert!(read(dir.path(), bad).is_err(), "{:?} accepted", bad);
+        }
+        assert!(read(dir.path(), "out").is_err());
+        assert!(read(dir.path(), "missing.txt").is_err());
+
+        #[cfg(unix)]
+        {
+            std::os::unix::fs::symlink(outside.path(), dir.path().join("escape")).unwrap();
+            assert!(read(dir.path(), "escape/secret.txt").is_err());
+            let params = ListWorkspaceParams {
+                path: Some("escape".to_string()),
+            };
+            assert!(list_files_in(dir.path(), params).is_err());
+        }
+    }
+}
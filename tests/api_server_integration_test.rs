@@ -0,0 +1,936 @@
+use rustyclaw::api::WebApiAdapter;
+use rustyclaw::config::{
+    Config, LlmConfig, LlmModels, SessionsConfig, TlsConfig, UnixSocketConfig,
+};
+use rustyclaw::core::Router;
+use rustyclaw::llm::Client as LlmClient;
+use rustyclaw::storage::sqlite::SqliteStorage;
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::RwLock;
+
+async fn adapter(port: u16) -> WebApiAdapter<SqliteStorage> {
+    adapter_with_tokens(port, vec!["test-token".to_string()]).await
+}
+
+async fn adapter_with_tokens(port: u16, tokens: Vec<String>) -> WebApiAdapter<SqliteStorage> {
+    let storage = SqliteStorage::new(":memory:")
+        .await
+        .expect("Failed to create storage");
+
+    let config = Config {
+        gateway: Default::default(),
+        llm: LlmConfig {
+            provider: "ollama".to_string(),
+            base_url: "http://localhost:11434".to_string(),
+            models: LlmModels {
+                primary: "test".to_string(),
+                code: None,
+                fast: None,
+                roles: Default::default(),
+            },
+            keep_alive: None,
+            cache: Default::default(),
+            routing: None,
+            context_windows: Default::default(),
+            tool_support: Default::default(),
+            tokenizers: Default::default(),
+            coalesce_requests: false,
+            circuit_breaker: Default::default(),
+            warm_up: false,
+        },
+        channels: Default::default(),
+        sessions: SessionsConfig {
+            scope: "per-sender".to_string(),
+            max_tokens: 1000,
+            compaction_enabled: false,
+            channel_routing: "isolated".to_string(),
+            retry: Default::default(),
+            drafts: Default::default(),
+            expiry: Default::default(),
+            tool_results: Default::default(),
+            max_per_user: None,
+        },
+        storage: Default::default(),
+        logging: Default::default(),
+        sandbox: Default::default(),
+        tools: Default::default(),
+        api: Default::default(),
+        admin: Default::default(),
+        workspace: Default::default(),
+        prompt: Default::default(),
+        locale: Default::default(),
+        network: Default::default(),
+        moderation: Default::default(),
+        redaction: Default::default(),
+        schedules: Default::default(),
+        agents: Default::default(),
+        plugins: Default::default(),
+        costs: Default::default(),
+        config_path: None,
+    };
+
+    let llm_client = LlmClient::new(&config.llm).expect("Failed to create LLM client");
+    let router = Router::new(Arc::new(RwLock::new(config)), storage.clone(), llm_client).await;
+
+    WebApiAdapter::new(
+        Arc::new(router),
+        "127.0.0.1".to_string(),
+        port,
+        tokens,
+        storage,
+    )
+}
+
+fn free_port() -> u16 {
+    std::net::TcpListener::bind("127.0.0.1:0")
+        .unwrap()
+        .local_addr()
+        .unwrap()
+        .port()
+}
+
+#[tokio::test]
+async fn test_health_over_https() {
+    let dir = tempfile::tempdir().unwrap();
+    let cert = rcgen::generate_simple_self_signed(vec!["localhost".to_string()]).unwrap();
+    let tls = TlsConfig {
+        cert_path: dir.path().join("cert.pem"),
+        key_path: dir.path().join("key.pem"),
+    };
+    std::fs::write(&tls.cert_path, cert.cert.pem()).unwrap();
+    std::fs::write(&tls.key_path, cert.key_pair.serialize_pem()).unwrap();
+
+    let port = free_port();
+    let server = adapter(port).await.with_tls(Some(tls));
+    tokio::spawn(async move { server.start().await });
+
+    let client = reqwest::Client::builder()
+        .danger_accept_invalid_certs(true)
+        .build()
+        .unwrap();
+    let url = format!("https://127.0.0.1:{}/health", port);
+
+    let mut response = None;
+    for _ in 0..50 {
+        if let Ok(resp) = client.get(&url).send().await {
+            response = Some(resp);
+            break;
+        }
+        tokio::time::sleep(Duration::from_millis(100)).await;
+    }
+    let response = response.expect("HTTPS endpoint never came up");
+    assert!(response.status().is_success());
+    let body: serde_json::Value = response.json().await.unwrap();
+    assert_eq!(body["status"], "ok");
+
+    // Plain HTTP is not served on the TLS port
+    let plain = client
+        .get(format!("http://127.0.0.1:{}/health", port))
+        .send()
+        .await;
+    assert!(plain.map_or(true, |resp| !resp.status().is_success()));
+}
+
+#[tokio::test]
+async fn test_invalid_certificate_fails_startup() {
+    let dir = tempfile::tempdir().unwrap();
+    let tls = TlsConfig {
+        cert_path: dir.path().join("cert.pem"),
+        key_path: dir.path().join("key.pem"),
+    };
+    std::fs::write(&tls.cert_path, "not a certificate").unwrap();
+    std::fs::write(&tls.key_path, "not a key").unwrap();
+
+    let result = tokio::time::timeout(
+        Duration::from_secs(5),
+        adapter(free_port()).await.with_tls(Some(tls)).start(),
+    )
+    .await
+    .expect("Startup should fail instead of serving");
+    assert!(result.is_err());
+}
+
+#[cfg(unix)]
+#[tokio::test]
+async fn test_health_over_unix_socket() {
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+    let dir = tempfile::tempdir().unwrap();
+    let path = dir.path().join("api.sock");
+    let server = adapter(free_port())
+        .await
+        .with_unix_socket(Some(UnixSocketConfig {
+            path: path.clone(),
+            mode: "600".to_string(),
+        }));
+    tokio::spawn(async move { server.start().await });
+
+    let mut stream = None;
+    for _ in 0..50 {
+        if let Ok(connected) = tokio::net::UnixStream::connect(&path).await {
+            stream = Some(connected);
+            break;
+        }
+        tokio::time::sleep(Duration::from_millis(100)).await;
+    }
+    let mut stream = stream.expect("Unix socket never came up");
+
+    stream
+        .write_all(b"GET /health HTTP/1.1\r\nHost: localhost\r\nConnection: close\r\n\r\n")
+        .await
+        .unwrap();
+    let mut response = String::new();
+    stream.read_to_string(&mut response).await.unwrap();
+
+    assert!(response.starts_with("HTTP/1.1 200 OK"), "{}", response);
+    assert!(response.contains(r#""status":"ok""#));
+}
+
+async fn wait_for_http(client: &reqwest::Client, base: &str) {
+    for _ in 0..50 {
+        if client.get(format!("{}/health", base)).send().await.is_ok() {
+            return;
+        }
+        tokio::time::sleep(Duration::from_millis(100)).await;
+    }
+    panic!("API never came up");
+}
+
+#[tokio::test]
+async fn test_protected_routes_require_setup() {
+    let port = free_port();
+    let server = adapter_with_tokens(port, vec![]).await;
+    tokio::spawn(async move { server.start().await });
+
+    let client = reqwest::Client::new();
+    let base = format!("http://127.0.0.1:{}", port);
+    wait_for_http(&client, &base).await;
+
+    // Before setup: any request gets a distinct 409
+    let response = client
+        .get(format!("{}/api/sessions", base))
+        .bearer_auth("some-token")
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), reqwest::StatusCode::CONFLICT);
+    let body: serde_json::Value = response.json().await.unwrap();
+    assert_eq!(body["setup_required"], true);
+
+    let setup = |password: &str| {
+        client
+            .post(format!("{}/api/setup", base))
+            .json(&serde_json::json!({"username": "admin", "password": password}))
+            .send()
+    };
+    assert_eq!(
+        setup("short").await.unwrap().status(),
+        reqwest::StatusCode::BAD_REQUEST
+    );
+
+    let response = setup("correct horse battery").await.unwrap();
+    assert!(response.status().is_success());
+    let body: serde_json::Value = response.json().await.unwrap();
+    assert_eq!(body["data"]["user"]["role"], "admin");
+    let token = body["data"]["token"].as_str().unwrap().to_string();
+
+    // After setup: protected routes work and setup is closed
+    let response = client
+        .get(format!("{}/api/sessions", base))
+        .bearer_auth(&token)
+        .send()
+        .await
+        .unwrap();
+    assert!(response.status().is_success());
+
+    assert_eq!(
+        setup("another password").await.unwrap().status(),
+        reqwest::StatusCode::FORBIDDEN
+    );
+}
+
+#[tokio::test]
+async fn test_config_tokens_do_not_wait_for_setup() {
+    let port = free_port();
+    let server = adapter(port).await;
+    tokio::spawn(async move { server.start().await });
+
+    let client = reqwest::Client::new();
+    let base = format!("http://127.0.0.1:{}", port);
+    wait_for_http(&client, &base).await;
+
+    // No account exists, but the operator configured `api.tokens`
+    let response = client
+        .get(format!("{}/api/sessions", base))
+        .bearer_auth("test-token")
+        .send()
+        .await
+        .unwrap();
+    assert!(response.status().is_success());
+
+    let response = client
+        .get(format!("{}/api/sessions", base))
+        .bearer_auth("unknown-token")
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), reqwest::StatusCode::UNAUTHORIZED);
+}
+
+#[tokio::test]
+async fn test_invites_are_single_use_and_expire() {
+    let port = free_port();
+    let server = adapter(port).await;
+    tokio::spawn(async move { server.start().await });
+
+    let client = reqwest::Client::new();
+    let base = format!("http://127.0.0.1:{}", port);
+    wait_for_http(&client, &base).await;
+
+    let response = client
+        .post(format!("{}/api/setup", base))
+        .json(&serde_json::json!({"username": "admin", "password": "correct horse battery"}))
+        .send()
+        .await
+        .unwrap();
+    assert!(response.status().is_success());
+
+    let invite = |body: serde_json::Value| {
+        client
+            .post(format!("{}/api/auth/invite", base))
+            .bearer_auth("test-token")
+            .json(&body)
+            .send()
+    };
+    let join = |code: String, username: &'static str| {
+        client
+            .post(format!("{}/api/auth/join", base))
+            .json(&serde_json::json!({
+                "username": username,
+                "password": "invited password",
+                "label": "laptop",
+                "invite_code": code,
+            }))
+            .send()
+    };
+
+    let body: serde_json::Value = invite(serde_json::json!({"scopes": ["chat"]}))
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    let code = body["data"]["code"].as_str().unwrap().to_string();
+    assert_eq!(body["data"]["role"], "user");
+
+    let response = join(code.clone(), "alice").await.unwrap();
+    assert!(response.status().is_success());
+    let body: serde_json::Value = response.json().await.unwrap();
+    assert_eq!(body["data"]["user"]["role"], "user");
+    let token = body["data"]["token"].as_str().unwrap().to_string();
+
+    // The invite cannot be used twice
+    assert_eq!(
+        join(code, "bob").await.unwrap().status(),
+        reqwest::StatusCode::FORBIDDEN
+    );
+
+    // Invited users are not admins and cannot invite others
+    let response = client
+        .post(format!("{}/api/auth/invite", base))
+        .bearer_auth(&token)
+        .json(&serde_json::json!({}))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), reqwest::StatusCode::FORBIDDEN);
+
+    // An expired invite is rejected
+    let body: serde_json::Value = invite(serde_json::json!({"expires_in_secs": 0}))
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    let expired = body["data"]["code"].as_str().unwrap().to_string();
+    assert_eq!(
+        join(expired, "carol").await.unwrap().status(),
+        reqwest::StatusCode::FORBIDDEN
+    );
+}
+
+#[tokio::test]
+async fn test_admin_routes_require_admin_role() {
+    let port = free_port();
+    let server = adapter(port).await;
+    tokio::spawn(async move { server.start().await });
+
+    let client = reqwest::Client::new();
+    let base = format!("http://127.0.0.1:{}", port);
+    wait_for_http(&client, &base).await;
+
+    // The account created by setup is the admin
+    let body: serde_json::Value = client
+        .post(format!("{}/api/setup", base))
+        .json(&serde_json::json!({"username": "admin", "password": "correct horse battery"}))
+        .send()
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    let admin_token = body["data"]["token"].as_str().unwrap().to_string();
+
+    let body: serde_json::Value = client
+        .post(format!("{}/api/auth/invite", base))
+        .bearer_auth(&admin_token)
+        .json(&serde_json::json!({"role": "user"}))
+        .send()
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    let body: serde_json::Value = client
+        .post(format!("{}/api/auth/join", base))
+        .json(&serde_json::json!({
+            "username": "member",
+            "password": "member password",
+            "label": "phone",
+            "invite_code": body["data"]["code"],
+        }))
+        .send()
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    let member_token = body["data"]["token"].as_str().unwrap().to_string();
+
+    let get_config = |token: String| {
+        client
+            .get(format!("{}/api/config", base))
+            .bearer_auth(token)
+            .send()
+    };
+    assert!(get_config(admin_token).await.unwrap().status().is_success());
+    assert_eq!(
+        get_config(member_token.clone()).await.unwrap().status(),
+        reqwest::StatusCode::FORBIDDEN
+    );
+
+    let response = client
+        .delete(format!("{}/api/tools/anything", base))
+        .bearer_auth(&member_token)
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), reqwest::StatusCode::FORBIDDEN);
+
+    // Member routes stay open to members
+    let response = client
+        .get(format!("{}/api/sessions", base))
+        .bearer_auth(&member_token)
+        .send()
+        .await
+        .unwrap();
+    assert!(response.status().is_success());
+}
+
+#[tokio::test]
+async fn test_scoped_config_tokens_are_not_admins() {
+    let port = free_port();
+    let scopes = [
+        ("chat-token".to_string(), vec!["chat".to_string()]),
+        ("ops-token".to_string(), vec!["admin".to_string()]),
+    ];
+    let server = adapter_with_tokens(
+        port,
+        vec![
+            "test-token".to_string(),
+            "chat-token".to_string(),
+            "ops-token".to_string(),
+        ],
+    )
+    .await
+    .with_token_scopes(scopes.into_iter().collect());
+    tokio::spawn(async move { server.start().await });
+
+    let client = reqwest::Client::new();
+    let base = format!("http://127.0.0.1:{}", port);
+    wait_for_http(&client, &base).await;
+
+    let list_users = |token: &'static str| {
+        client
+            .get(format!("{}/api/users", base))
+            .bearer_auth(token)
+            .send()
+    };
+    assert_eq!(
+        list_users("chat-token").await.unwrap().status(),
+        reqwest::StatusCode::FORBIDDEN
+    );
+    assert!(list_users("ops-token").await.unwrap().status().is_success());
+
+    let response = client
+        .post(format!("{}/api/admin/impersonate/anyone", base))
+        .bearer_auth("chat-token")
+        .json(&serde_json::json!({}))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), reqwest::StatusCode::FORBIDDEN);
+}
+
+#[tokio::test]
+async fn test_config_tokens_administer_without_accounts() {
+    let port = free_port();
+    let server = adapter(port).await;
+    tokio::spawn(async move { server.start().await });
+
+    let client = reqwest::Client::new();
+    let base = format!("http://127.0.0.1:{}", port);
+    wait_for_http(&client, &base).await;
+
+    // No user exists at all, yet the operator keeps admin access
+    let response = client
+        .get(format!("{}/api/users", base))
+        .bearer_auth("test-token")
+        .send()
+        .await
+        .unwrap();
+    assert!(response.status().is_success());
+    let body: serde_json::Value = response.json().await.unwrap();
+    assert_eq!(body["data"]["total"], 0);
+
+    let response = client
+        .get(format!("{}/api/config", base))
+        .bearer_auth("test-token")
+        .send()
+        .await
+        .unwrap();
+    assert!(response.status().is_success());
+}
+
+#[tokio::test]
+async fn test_impersonation_is_admin_only_and_scoped() {
+    let port = free_port();
+    let server = adapter(port).await;
+    tokio::spawn(async move { server.start().await });
+
+    let client = reqwest::Client::new();
+    let base = format!("http://127.0.0.1:{}", port);
+    wait_for_http(&client, &base).await;
+
+    let body: serde_json::Value = client
+        .post(format!("{}/api/setup", base))
+        .json(&serde_json::json!({"username": "admin", "password": "correct horse battery"}))
+        .send()
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    let admin_token = body["data"]["token"].as_str().unwrap().to_string();
+
+    let join = |username: &'static str, role: &'static str, scopes: serde_json::Value| {
+        let client = client.clone();
+        let base = base.clone();
+        let admin_token = admin_token.clone();
+        async move {
+            let body: serde_json::Value = client
+                .post(format!("{}/api/auth/invite", base))
+                .bearer_auth(&admin_token)
+                .json(&serde_json::json!({"role": role, "scopes": scopes}))
+                .send()
+                .await
+                .unwrap()
+                .json()
+                .await
+                .unwrap();
+            let body: serde_json::Value = client
+                .post(format!("{}/api/auth/join", base))
+                .json(&serde_json::json!({
+                    "username": username,
+                    "password": "member password",
+                    "label": "laptop",
+                    "invite_code": body["data"]["code"],
+                }))
+                .send()
+                .await
+                .unwrap()
+                .json()
+                .await
+                .unwrap();
+            (
+                body["data"]["user"]["id"].as_str().unwrap().to_string(),
+                body["data"]["token"].as_str().unwrap().to_string(),
+            )
+        }
+    };
+    let (member_id, member_token) = join("member", "user", serde_json::Value::Null).await;
+    let (other_admin_id, _) = join("second-admin", "admin", serde_json::Value::Null).await;
+
+    let impersonate = |token: String, user_id: &str, body: serde_json::Value| {
+        client
+            .post(format!("{}/api/admin/impersonate/{}", base, user_id))
+            .bearer_auth(token)
+            .json(&body)
+            .send()
+    };
+
+    // Members cannot impersonate anyone
+    let response = impersonate(member_token, &other_admin_id, serde_json::json!({}))
+        .await
+        .unwrap();
+    assert_eq!(response.status(), reqwest::StatusCode::FORBIDDEN);
+
+    let response = impersonate(
+        admin_token.clone(),
+        &member_id,
+        serde_json::json!({"reason": "ticket 42"}),
+    )
+    .await
+    .unwrap();
+    assert!(response.status().is_success());
+    let body: serde_json::Value = response.json().await.unwrap();
+    let token = body["data"]["token"].as_str().unwrap().to_string();
+    assert!(token.starts_with("imp-"));
+    assert_eq!(body["data"]["user_id"], member_id.as_str());
+
+    // The token acts as the member, without admin rights or credential access
+    let status = |path: &'static str| {
+        client
+            .get(format!("{}{}", base, path))
+            .bearer_auth(&token)
+            .send()
+    };
+    assert!(status("/api/sessions").await.unwrap().status().is_success());
+    assert_eq!(
+        status("/api/config").await.unwrap().status(),
+        reqwest::StatusCode::FORBIDDEN
+    );
+    assert_eq!(
+        status("/api/auth/tokens").await.unwrap().status(),
+        reqwest::StatusCode::FORBIDDEN
+    );
+    let response = client
+        .post(format!("{}/api/auth/change-password", base))
+        .bearer_auth(&token)
+        .json(&serde_json::json!({"old_password": "x", "new_password": "y"}))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), reqwest::StatusCode::FORBIDDEN);
+
+    // Other admins need an explicit opt-in
+    let response = impersonate(admin_token.clone(), &other_admin_id, serde_json::json!({}))
+        .await
+        .unwrap();
+    assert_eq!(response.status(), reqwest::StatusCode::FORBIDDEN);
+    let response = impersonate(
+        admin_token.clone(),
+        &other_admin_id,
+        serde_json::json!({"allow_admin": true}),
+    )
+    .await
+    .unwrap();
+    assert!(response.status().is_success());
+
+    // Both grants are in the audit trail, newest first and without tokens
+    let body: serde_json::Value = client
+        .get(format!("{}/api/admin/impersonations", base))
+        .bearer_auth(&admin_token)
+        .send()
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    let records = body["data"].as_array().unwrap();
+    assert_eq!(records.len(), 2);
+    assert_eq!(records[0]["user_id"], other_admin_id.as_str());
+    assert_eq!(records[1]["reason"], "ticket 42");
+    assert_eq!(records[1]["active"], true);
+    assert!(records[1].get("token").is_none());
+
+    // Restricted admin tokens need the impersonation scope
+    let (_, narrow_token) = join("narrow-admin", "admin", serde_json::json!(["tools:write"])).await;
+    let response = impersonate(narrow_token, &member_id, serde_json::json!({}))
+        .await
+        .unwrap();
+    assert_eq!(response.status(), reqwest::StatusCode::FORBIDDEN);
+
+    // and the token they issue is restricted the same way
+    let (_, support_token) = join(
+        "support-admin",
+        "admin",
+        serde_json::json!(["admin:impersonate"]),
+    )
+    .await;
+    let response = impersonate(support_token, &member_id, serde_json::json!({}))
+        .await
+        .unwrap();
+    assert!(response.status().is_success());
+    let body: serde_json::Value = client
+        .get(format!("{}/api/admin/impersonations", base))
+        .bearer_auth(&admin_token)
+        .send()
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    assert_eq!(
+        body["data"][0]["scopes"],
+        serde_json::json!(["admin:impersonate"])
+    );
+    assert_eq!(body["data"][1]["scopes"], serde_json::Value::Null);
+}
+
+#[tokio::test]
+async fn test_maintenance_mode_blocks_writes_and_serves_reads() {
+    let port = free_port();
+    let server = adapter(port).await;
+    tokio::spawn(async move { server.start().await });
+
+    let client = reqwest::Client::new();
+    let base = format!("http://127.0.0.1:{}", port);
+    wait_for_http(&client, &base).await;
+
+    let body: serde_json::Value = client
+        .post(format!("{}/api/setup", base))
+        .json(&serde_json::json!({"username": "admin", "password": "correct horse battery"}))
+        .send()
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    let token = body["data"]["token"].as_str().unwrap().to_string();
+
+    let health = || async {
+        let body: serde_json::Value = client
+            .get(format!("{}/health", base))
+            .send()
+            .await
+            .unwrap()
+            .json()
+            .await
+            .unwrap();
+        body["maintenance"].as_bool().unwrap()
+    };
+    assert!(!health().await);
+
+    let response = client
+        .post(format!("{}/api/maintenance", base))
+        .bearer_auth(&token)
+        .send()
+        .await
+        .unwrap();
+    assert!(response.status().is_success());
+    assert!(health().await);
+
+    // Generations and other writes are refused with a distinct 503
+    let response = client
+        .post(format!("{}/api/chat", base))
+        .bearer_auth(&token)
+        .json(&serde_json::json!({"message": "Hello"}))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), reqwest::StatusCode::SERVICE_UNAVAILABLE);
+    let body: serde_json::Value = response.json().await.unwrap();
+    assert_eq!(body["maintenance"], true);
+
+    let response = client
+        .post(format!("{}/api/sessions", base))
+        .bearer_auth(&token)
+        .json(&serde_json::json!({}))
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), reqwest::StatusCode::SERVICE_UNAVAILABLE);
+
+    // Only the toggle itself is exempt, not any path ending in it
+    let response = client
+        .delete(format!("{}/api/tools/maintenance", base))
+        .bearer_auth(&token)
+        .send()
+        .await
+        .unwrap();
+    assert_eq!(response.status(), reqwest::StatusCode::SERVICE_UNAVAILABLE);
+
+    // Reads keep working
+    for path in ["/api/sessions", "/api/messages"] {
+        let response = client
+            .get(format!("{}{}", base, path))
+            .bearer_auth(&token)
+            .send()
+            .await
+            .unwrap();
+        assert!(response.status().is_success(), "GET {} failed", path);
+    }
+
+    let response = client
+        .delete(format!("{}/api/maintenance", base))
+        .bearer_auth(&token)
+        .send()
+        .await
+        .unwrap();
+    assert!(response.status().is_success());
+    assert!(!health().await);
+}
+
+#[tokio::test]
+async fn test_capabilities_report_core_features() {
+    let port = free_port();
+    let server = adapter(port).await;
+    tokio::spawn(async move { server.start().await });
+
+    let client = reqwest::Client::new();
+    let base = format!("http://127.0.0.1:{}", port);
+    wait_for_http(&client, &base).await;
+
+    let body: serde_json::Value = client
+        .post(format!("{}/api/setup", base))
+        .json(&serde_json::json!({"username": "admin", "password": "correct horse battery"}))
+        .send()
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    let token = body["data"]["token"].as_str().unwrap().to_string();
+
+    let body: serde_json::Value = client
+        .get(format!("{}/api/capabilities", base))
+        .bearer_auth(&token)
+        .send()
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    let capabilities = &body["data"];
+
+    assert_eq!(capabilities["chat"]["streaming"], true);
+    assert_eq!(capabilities["chat"]["tools"], true);
+    assert_eq!(capabilities["chat"]["images"], false);
+    assert_eq!(capabilities["models"][0]["name"], "test");
+    assert_eq!(capabilities["models"][0]["role"], "primary");
+    assert_eq!(capabilities["maintenance"], false);
+    assert!(capabilities["sandbox"]["available"].is_boolean());
+
+    let channels = capabilities["channels"].as_array().unwrap();
+    let web = channels.iter().find(|c| c["name"] == "web").unwrap();
+    assert_eq!(web["status"], "connected");
+    let telegram = channels.iter().find(|c| c["name"] == "telegram").unwrap();
+    assert_eq!(telegram["status"], "disabled");
+}
+
+/// Read one unmasked WebSocket frame sent by the server: opcode and payload
+async fn read_ws_frame(stream: &mut tokio::net::TcpStream) -> (u8, Vec<u8>) {
+    use tokio::io::AsyncReadExt;
+
+    let mut header = [0u8; 2];
+    stream.read_exact(&mut header).await.unwrap();
+    let len = match header[1] & 0x7f {
+        126 => stream.read_u16().await.unwrap() as usize,
+        127 => stream.read_u64().await.unwrap() as usize,
+        len => len as usize,
+    };
+    let mut payload = vec![0u8; len];
+    stream.read_exact(&mut payload).await.unwrap();
+    (header[0] & 0x0f, payload)
+}
+
+#[tokio::test]
+async fn test_admins_can_list_and_close_connections() {
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+
+    let port = free_port();
+    let server = adapter(port).await;
+    tokio::spawn(async move { server.start().await });
+
+    let client = reqwest::Client::new();
+    let base = format!("http://127.0.0.1:{}", port);
+    wait_for_http(&client, &base).await;
+
+    let body: serde_json::Value = client
+        .post(format!("{}/api/setup", base))
+        .json(&serde_json::json!({"username": "admin", "password": "correct horse battery"}))
+        .send()
+        .await
+        .unwrap()
+        .json()
+        .await
+        .unwrap();
+    let token = body["data"]["token"].as_str().unwrap().to_string();
+
+    let mut socket = tokio::net::TcpStream::connect(("127.0.0.1", port))
+        .await
+        .unwrap();
+    let handshake = format!(
+        "GET /ws?token={} HTTP/1.1\r\n\
+         Host: 127.0.0.1:{}\r\n\
+         Upgrade: websocket\r\n\
+         Connection: Upgrade\r\n\
+         Sec-WebSocket-Key: dGhlIHNhbXBsZSBub25jZQ==\r\n\
+         Sec-WebSocket-Version: 13\r\n\r\n",
+        token, port
+    );
+    socket.write_all(handshake.as_bytes()).await.unwrap();
+    let mut response = Vec::new();
+    while !response.ends_with(b"\r\n\r\n") {
+        response.push(socket.read_u8().await.unwrap());
+    }
+    assert!(String::from_utf8_lossy(&response).starts_with("HTTP/1.1 101"));
+    // Registered before the connected message is sent
+    let (opcode, payload) = read_ws_frame(&mut socket).await;
+    assert_eq!(opcode, 0x1);
+    assert!(String::from_utf8_lossy(&payload).contains("connected"));
+
+    let list = || async {
+        let body: serde_json::Value = client
+            .get(format!("{}/api/admin/connections", base))
+            .bearer_auth(&token)
+            .send()
+            .await
+            .unwrap()
+            .json()
+            .await
+            .unwrap();
+        body["data"].as_array().unwrap().clone()
+    };
+    let connections = list().await;
+    assert_eq!(connections.len(), 1);
+    assert_eq!(connections[0]["channel"], "websocket");
+    assert!(connections[0]["session_id"].is_string());
+    assert!(connections[0].get("token").is_none());
+    let id = connections[0]["id"].as_str().unwrap().to_string();
+
+    let close = |id: String| {
+        client
+            .delete(format!("{}/api/admin/connections/{}", base, id))
+            .bearer_auth(&token)
+            .send()
+    };
+    assert_eq!(close(id.clone()).await.unwrap().status(), 204);
+
+    // The server closes the socket
+    let opcode = tokio::time::timeout(Duration::from_secs(5), async {
+        loop {
+            let (opcode, _) = read_ws_frame(&mut socket).await;
+            if opcode != 0x1 {
+                break opcode;
+            }
+        }
+    })
+    .await
+    .expect("connection not closed");
+    assert_eq!(opcode, 0x8);
+    assert!(list().await.is_empty());
+    assert_eq!(close(id).await.unwrap().status(), 404);
+}
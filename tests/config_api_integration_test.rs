@@ -1,6 +1,7 @@
 use axum::extract::State;
 use axum::Json;
 use rustyclaw::api::config::patch_config;
+use rustyclaw::api::error::ApiError;
 use rustyclaw::config::{Config, SessionsConfig};
 use rustyclaw::core::Router;
 use rustyclaw::llm::Client as LlmClient;
@@ -29,10 +30,17 @@ async fn test_patch_config() {
                 primary: "test".to_string(),
                 code: None,
                 fast: None,
+                roles: Default::default(),
             },
             keep_alive: None,
             cache: Default::default(),
             routing: None,
+            context_windows: Default::default(),
+            tool_support: Default::default(),
+            tokenizers: Default::default(),
+            coalesce_requests: false,
+            circuit_breaker: Default::default(),
+            warm_up: false,
         },
         channels: Default::default(),
         sessions: SessionsConfig {
@@ -40,6 +48,11 @@ async fn test_patch_config() {
             max_tokens: 1000,
             compaction_enabled: false,
             channel_routing: "isolated".to_string(),
+            retry: Default::default(),
+            drafts: Default::default(),
+            expiry: Default::default(),
+            tool_results: Default::default(),
+            max_per_user: None,
         },
         storage: Default::default(),
         logging: Default::default(),
@@ -48,7 +61,15 @@ async fn test_patch_config() {
         api: Default::default(),
         admin: Default::default(),
         workspace: Default::default(),
+        prompt: Default::default(),
+        locale: Default::default(),
+        network: Default::default(),
+        moderation: Default::default(),
+        redaction: Default::default(),
+        schedules: Default::default(),
         agents: Default::default(),
+        plugins: Default::default(),
+        costs: Default::default(),
         config_path: Some(test_config_path.clone()),
     };
 
@@ -107,4 +128,27 @@ async fn test_patch_config() {
         let agent = cfg.agents.get("agent_007").expect("Agent not found");
         assert_eq!(agent.name, "Bond");
     }
+
+    // Test 3: The dev policy bypass cannot be enabled remotely
+    let patch_bypass = json!({
+        "tools": {
+            "dev_bypass_policy": true
+        }
+    });
+
+    let result = patch_config(State(router_arc.clone()), Json(patch_bypass)).await;
+    assert!(result.is_err());
+    assert!(!shared_config.read().await.tools.dev_bypass_policy);
+
+    // Test 4: A config merged from several files can't be saved, so it isn't
+    // changed either
+    shared_config.write().await.config_path = None;
+    let patch = json!({
+        "sessions": {
+            "compaction_enabled": false
+        }
+    });
+    let result = patch_config(State(router_arc.clone()), Json(patch)).await;
+    assert!(matches!(result, Err(ApiError::Conflict(_))));
+    assert!(shared_config.read().await.sessions.compaction_enabled);
 }
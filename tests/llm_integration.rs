@@ -12,6 +12,7 @@ async fn test_ollama_connection() {
             primary: "qwen2.5:32b".to_string(),
             code: Some("deepseek-coder-v2:16b".to_string()),
             fast: Some("qwen2.5:7b".to_string()),
+            roles: Default::default(),
         },
         keep_alive: None,
         cache: CacheConfig {
@@ -25,7 +26,14 @@ async fn test_ollama_connection() {
                 pattern: "(code|function|implement)".to_string(),
                 model: "deepseek-coder-v2:16b".to_string(),
             }],
+            fallbacks: vec![],
         }),
+        context_windows: Default::default(),
+        tool_support: Default::default(),
+        tokenizers: Default::default(),
+        coalesce_requests: false,
+        circuit_breaker: Default::default(),
+        warm_up: false,
     };
 
     let client = Client::new(&config).expect("Failed to create client");
@@ -40,6 +48,9 @@ async fn test_ollama_connection() {
         max_tokens: Some(50),
         temperature: None,
         tools: None,
+        seed: None,
+        prefill: None,
+        tool_choice: Default::default(),
     };
 
     let response = client.chat(request).await.expect("Failed to get response");
@@ -64,6 +75,7 @@ async fn test_model_routing() {
             primary: "qwen2.5:32b".to_string(),
             code: Some("deepseek-coder-v2:16b".to_string()),
             fast: Some("qwen2.5:7b".to_string()),
+            roles: Default::default(),
         },
         keep_alive: None,
         cache: CacheConfig {
@@ -77,7 +89,14 @@ async fn test_model_routing() {
                 pattern: "(code|function|implement)".to_string(),
                 model: "deepseek-coder-v2:16b".to_string(),
             }],
+            fallbacks: vec![],
         }),
+        context_windows: Default::default(),
+        tool_support: Default::default(),
+        tokenizers: Default::default(),
+        coalesce_requests: false,
+        circuit_breaker: Default::default(),
+        warm_up: false,
     };
 
     let client = Client::new(&config).expect("Failed to create client");
@@ -92,6 +111,9 @@ async fn test_model_routing() {
         max_tokens: Some(100),
         temperature: None,
         tools: None,
+        seed: None,
+        prefill: None,
+        tool_choice: Default::default(),
     };
 
     let response = client.chat(request).await.expect("Failed to get response");
@@ -115,6 +137,7 @@ async fn test_hot_swapping() {
             primary: "qwen2.5:32b".to_string(),
             code: Some("deepseek-coder-v2:16b".to_string()),
             fast: Some("qwen2.5:7b".to_string()),
+            roles: Default::default(),
         },
         keep_alive: None,
         cache: CacheConfig {
@@ -123,6 +146,12 @@ async fn test_hot_swapping() {
             eviction: "lru".to_string(),
         },
         routing: None,
+        context_windows: Default::default(),
+        tool_support: Default::default(),
+        tokenizers: Default::default(),
+        coalesce_requests: false,
+        circuit_breaker: Default::default(),
+        warm_up: false,
     };
 
     let client = Client::new(&config).expect("Failed to create client");
@@ -138,6 +167,9 @@ async fn test_hot_swapping() {
         max_tokens: Some(20),
         temperature: None,
         tools: None,
+        seed: None,
+        prefill: None,
+        tool_choice: Default::default(),
     };
     let response1 = client.chat(request1).await.expect("Failed to get response");
     let time1 = start.elapsed();
@@ -155,6 +187,9 @@ async fn test_hot_swapping() {
         max_tokens: Some(20),
         temperature: None,
         tools: None,
+        seed: None,
+        prefill: None,
+        tool_choice: Default::default(),
     };
     let response2 = client.chat(request2).await.expect("Failed to get response");
     let time2 = start.elapsed();
@@ -172,6 +207,9 @@ async fn test_hot_swapping() {
         max_tokens: Some(20),
         temperature: None,
         tools: None,
+        seed: None,
+        prefill: None,
+        tool_choice: Default::default(),
     };
     let response3 = client.chat(request3).await.expect("Failed to get response");
     let time3 = start.elapsed();
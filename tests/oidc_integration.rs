@@ -0,0 +1,251 @@
+//! Signing in through an OpenID Connect provider, mocked with its discovery
+//! document, token endpoint and JWKS.
+
+use axum::extract::{Query, State};
+use axum::response::IntoResponse;
+use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+use base64::Engine;
+use jsonwebtoken::{Algorithm, EncodingKey, Header};
+use rustyclaw::api::routes::{oidc_callback, oidc_login, OidcCallbackQuery};
+use rustyclaw::api::ApiError;
+use rustyclaw::config::Config;
+use rustyclaw::core::Router;
+use rustyclaw::llm::Client as LlmClient;
+use rustyclaw::storage::sqlite::SqliteStorage;
+use rustyclaw::storage::Storage;
+use serde_json::json;
+use std::collections::HashMap;
+use std::sync::Arc;
+use tokio::sync::RwLock;
+
+/// State and nonce of the provider URL a login redirects to
+async fn start_login(router: &Arc<Router<SqliteStorage>>) -> (String, String) {
+    let redirect = oidc_login(State(router.clone()))
+        .await
+        .unwrap()
+        .into_response();
+    let location = redirect.headers()["location"].to_str().unwrap();
+    let params: HashMap<String, String> = reqwest::Url::parse(location)
+        .unwrap()
+        .query_pairs()
+        .into_owned()
+        .collect();
+    assert_eq!(params["client_id"], "rustyclaw");
+    assert_eq!(params["response_type"], "code");
+    (params["state"].clone(), params["nonce"].clone())
+}
+
+#[tokio::test]
+async fn test_oidc_callback_signs_in_with_a_verified_id_token() {
+    let mut server = mockito::Server::new_async().await;
+    let issuer = server.url();
+
+    let key_pair = rcgen::KeyPair::generate().unwrap();
+    let point = key_pair.public_key_raw();
+    let jwks = json!({"keys": [{
+        "kty": "EC", "crv": "P-256", "kid": "key-1", "alg": "ES256", "use": "sig",
+        "x": URL_SAFE_NO_PAD.encode(&point[1..33]),
+        "y": URL_SAFE_NO_PAD.encode(&point[33..65]),
+    }]});
+    let signing_key = EncodingKey::from_ec_pem(key_pair.serialize_pem().as_bytes()).unwrap();
+    let id_token = |sub: &str, email: &str, nonce: &str| {
+        let mut header = Header::new(Algorithm::ES256);
+        header.kid = Some("key-1".to_string());
+        let claims = json!({
+            "iss": issuer, "aud": "rustyclaw", "sub": sub, "nonce": nonce,
+            "email": email, "email_verified": true,
+            "exp": chrono::Utc::now().timestamp() + 300,
+        });
+        jsonwebtoken::encode(&header, &claims, &signing_key).unwrap()
+    };
+
+    let _discovery = server
+        .mock("GET", "/.well-known/openid-configuration")
+        .with_header("content-type", "application/json")
+        .with_body(
+            json!({
+                "issuer": issuer,
+                "authorization_endpoint": format!("{}/authorize", issuer),
+                "token_endpoint": format!("{}/token", issuer),
+                "jwks_uri": format!("{}/jwks", issuer),
+            })
+            .to_string(),
+        )
+        .create_async()
+        .await;
+    let _jwks = server
+        .mock("GET", "/jwks")
+        .with_header("content-type", "application/json")
+        .with_body(jwks.to_string())
+        .create_async()
+        .await;
+
+    let config: Config = serde_yaml::from_str(&format!(
+        "llm:\n  provider: ollama\n  base_url: http://127.0.0.1:9\n  models:\n    primary: test-model\n\
+         api:\n  oidc:\n    issuer: {}\n    client_id: rustyclaw\n    client_secret: secret\n    \
+         redirect_url: http://127.0.0.1/api/auth/oidc/callback\n    auto_provision: true\n",
+        issuer
+    ))
+    .unwrap();
+    let storage = SqliteStorage::new(":memory:")
+        .await
+        .expect("Failed to create storage");
+    let llm_client = LlmClient::new(&config.llm).expect("Failed to create LLM client");
+    let shared_config = Arc::new(RwLock::new(config));
+    let router = Arc::new(Router::new(shared_config.clone(), storage, llm_client).await);
+
+    // The first login creates the account
+    let (state, nonce) = start_login(&router).await;
+    let token_endpoint = server
+        .mock("POST", "/token")
+        .match_body(mockito::Matcher::Regex("code=first-code".to_string()))
+        .with_header("content-type", "application/json")
+        .with_body(json!({"id_token": id_token("sub-1", "alice@example.com", &nonce)}).to_string())
+        .create_async()
+        .await;
+    let query = OidcCallbackQuery {
+        code: "first-code".to_string(),
+        state: state.clone(),
+    };
+    let joined = oidc_callback(State(router.clone()), Query(query))
+        .await
+        .unwrap()
+        .0
+        .data
+        .unwrap();
+    token_endpoint.assert_async().await;
+    assert_eq!(joined.user.username, "alice@example.com");
+    assert_eq!(joined.user.role, "user");
+    let identity = router
+        .get_storage()
+        .get_identity("api_token", &joined.token)
+        .await
+        .unwrap()
+        .unwrap();
+    assert_eq!(identity.user_id, joined.user.id);
+
+    // A login is completed only once
+    let replay = OidcCallbackQuery {
+        code: "first-code".to_string(),
+        state,
+    };
+    assert!(matches!(
+        oidc_callback(State(router.clone()), Query(replay))
+            .await
+            .unwrap_err(),
+        ApiError::Unauthorized(_)
+    ));
+
+    // A token with the wrong nonce is refused
+    let (state, _) = start_login(&router).await;
+    let _stale = server
+        .mock("POST", "/token")
+        .match_body(mockito::Matcher::Regex("code=stale-code".to_string()))
+        .with_header("content-type", "application/json")
+        .with_body(json!({"id_token": id_token("sub-1", "alice@example.com", "other")}).to_string())
+        .create_async()
+        .await;
+    let query = OidcCallbackQuery {
+        code: "stale-code".to_string(),
+        state,
+    };
+    assert!(matches!(
+        oidc_callback(State(router.clone()), Query(query))
+            .await
+            .unwrap_err(),
+        ApiError::Unauthorized(_)
+    ));
+
+    // The subject stays linked to its account after the email changes
+    let (state, nonce) = start_login(&router).await;
+    let _renamed = server
+        .mock("POST", "/token")
+        .match_body(mockito::Matcher::Regex("code=second-code".to_string()))
+        .with_header("content-type", "application/json")
+        .with_body(
+            json!({"id_token": id_token("sub-1", "alice@new.example.com", &nonce)}).to_string(),
+        )
+        .create_async()
+        .await;
+    let query = OidcCallbackQuery {
+        code: "second-code".to_string(),
+        state,
+    };
+    let again = oidc_callback(State(router.clone()), Query(query))
+        .await
+        .unwrap()
+        .0
+        .data
+        .unwrap();
+    assert_eq!(again.user.id, joined.user.id);
+    assert_ne!(again.token, joined.token);
+
+    // Without auto_provision, strangers get no account
+    shared_config
+        .write()
+        .await
+        .api
+        .oidc
+        .as_mut()
+        .unwrap()
+        .auto_provision = false;
+    let (state, nonce) = start_login(&router).await;
+    let _stranger = server
+        .mock("POST", "/token")
+        .match_body(mockito::Matcher::Regex("code=stranger-code".to_string()))
+        .with_header("content-type", "application/json")
+        .with_body(json!({"id_token": id_token("sub-2", "bob@example.com", &nonce)}).to_string())
+        .create_async()
+        .await;
+    let query = OidcCallbackQuery {
+        code: "stranger-code".to_string(),
+        state,
+    };
+    assert!(matches!(
+        oidc_callback(State(router.clone()), Query(query))
+            .await
+            .unwrap_err(),
+        ApiError::Forbidden(_)
+    ));
+
+    // A local account is only taken over by email with link_by_email
+    let admin = rustyclaw::storage::User {
+        id: "local-admin".to_string(),
+        username: "carol@example.com".to_string(),
+        role: "admin".to_string(),
+        created_at: chrono::Utc::now(),
+        updated_at: chrono::Utc::now(),
+        password_hash: None,
+    };
+    router.get_storage().create_user(admin).await.unwrap();
+    for (code, link_by_email) in [("unlinked-code", false), ("linked-code", true)] {
+        shared_config
+            .write()
+            .await
+            .api
+            .oidc
+            .as_mut()
+            .unwrap()
+            .link_by_email = link_by_email;
+        let (state, nonce) = start_login(&router).await;
+        let _token = server
+            .mock("POST", "/token")
+            .match_body(mockito::Matcher::Regex(format!("code={}", code)))
+            .with_header("content-type", "application/json")
+            .with_body(
+                json!({"id_token": id_token("sub-3", "carol@example.com", &nonce)}).to_string(),
+            )
+            .create_async()
+            .await;
+        let query = OidcCallbackQuery {
+            code: code.to_string(),
+            state,
+        };
+        let result = oidc_callback(State(router.clone()), Query(query)).await;
+        if link_by_email {
+            assert_eq!(result.unwrap().0.data.unwrap().user.id, "local-admin");
+        } else {
+            assert!(matches!(result.unwrap_err(), ApiError::Forbidden(_)));
+        }
+    }
+}
@@ -0,0 +1,91 @@
+//! Reconfiguring a loaded plugin through the admin API. Kept in its own test
+//! binary because plugins live in the process-wide plugin registry.
+
+use axum::extract::Path;
+use axum::Json;
+use rustyclaw::api::plugins::{list_plugins, update_plugin_config};
+use rustyclaw::api::ApiError;
+use rustyclaw::config::Config;
+use rustyclaw::plugins::{initialize_plugins, DefaultPluginApi, EmailPlugin, RustyclawPlugin};
+use serde_json::json;
+use std::sync::Arc;
+
+#[tokio::test]
+async fn test_reconfigured_email_sender_takes_effect() {
+    let config: Config = serde_yaml::from_str(
+        "llm:\n  provider: ollama\n  base_url: http://127.0.0.1:9\n  models:\n    primary: test-model\n",
+    )
+    .unwrap();
+    let registry = rustyclaw::plugins::init_plugin_registry();
+    let api = Arc::new(DefaultPluginApi::new(
+        Arc::new(config),
+        registry.tools.clone(),
+        registry.hooks.clone(),
+    ));
+    let email = Arc::new(EmailPlugin::new());
+    initialize_plugins(vec![email.clone() as Arc<dyn RustyclawPlugin>], api)
+        .await
+        .unwrap();
+
+    let plugins = list_plugins().await.unwrap().0.data.unwrap();
+    let listed = plugins.iter().find(|p| p.id == "email").unwrap();
+    assert_eq!(listed.version, "1.0.0");
+    assert_eq!(listed.tools, ["send_email"]);
+    assert!(listed.config_schema.is_some());
+
+    let send_email = registry.tools.get_tool("send_email").unwrap().unwrap();
+    let args = json!({"to": "user@example.com", "subject": "Hi", "body": "Hello"}).to_string();
+    let error = (send_email.execute)(args.clone()).await.unwrap_err();
+    assert!(error.to_string().contains("not configured"), "{}", error);
+
+    // Wrong types and missing fields are refused by the schema
+    let invalid = update_plugin_config(
+        Path("email".to_string()),
+        Json(json!({"smtp_username": "bot", "sender_email": 42})),
+    )
+    .await
+    .unwrap_err();
+    assert!(matches!(invalid, ApiError::BadRequest(_)));
+    assert!(email.config().is_none());
+
+    // An unreachable server, so the send fails after using the new sender
+    let smtp = json!({
+        "provider": "custom",
+        "smtp_server": "127.0.0.1",
+        "smtp_port": 9,
+        "smtp_username": "bot",
+        "smtp_password": "secret",
+        "use_tls": false,
+        "timeout_secs": 1,
+        "retry_attempts": 1,
+    });
+    let with_sender = |sender: &str| {
+        let mut config = smtp.clone();
+        config["sender_email"] = json!(sender);
+        Json(config)
+    };
+
+    let rejected = update_plugin_config(Path("email".to_string()), with_sender("not an address"))
+        .await
+        .unwrap_err();
+    assert!(matches!(rejected, ApiError::BadRequest(_)));
+    assert!(email.config().is_none());
+
+    update_plugin_config(Path("email".to_string()), with_sender("bot@example.com"))
+        .await
+        .unwrap();
+    assert_eq!(email.config().unwrap().sender_email, "bot@example.com");
+    let error = (send_email.execute)(args).await.unwrap_err();
+    assert!(
+        error.to_string().contains("Failed to send email via SMTP"),
+        "{:#}",
+        error
+    );
+
+    assert!(matches!(
+        update_plugin_config(Path("fax".to_string()), with_sender("bot@example.com"))
+            .await
+            .unwrap_err(),
+        ApiError::NotFound(_)
+    ));
+}
@@ -0,0 +1,96 @@
+//! Plugin hooks on the Router's message path. Kept in its own test binary
+//! because hooks live in the process-wide plugin registry.
+
+use rustyclaw::config::Config;
+use rustyclaw::core::{Router, StreamEvent};
+use rustyclaw::llm::Client as LlmClient;
+use rustyclaw::plugins::{
+    init_plugin_registry, DefaultPluginApi, HookModification, HookType, MessageReceivedEvent,
+    PluginApi,
+};
+use rustyclaw::storage::sqlite::SqliteStorage;
+use rustyclaw::storage::Storage;
+use std::sync::Arc;
+use tokio::sync::RwLock;
+
+/// A message_received hook can drop inbound messages before the LLM sees them
+#[tokio::test]
+async fn test_message_received_hook_blocks_banned_word() {
+    let dir = tempfile::tempdir().unwrap();
+    let storage = SqliteStorage::new(":memory:")
+        .await
+        .expect("Failed to create storage");
+
+    // The backend must never be called
+    let mut server = mockito::Server::new_async().await;
+    let mock = server
+        .mock("POST", "/chat/completions")
+        .expect(0)
+        .create_async()
+        .await;
+
+    let config: Config = serde_yaml::from_str(&format!(
+        "llm:\n  provider: ollama\n  base_url: {}\n  models:\n    primary: test-model\nworkspace:\n  path: {}\n",
+        server.url(),
+        dir.path().join("workspace").display()
+    ))
+    .unwrap();
+    let llm_client = LlmClient::new(&config.llm).expect("Failed to create LLM client");
+
+    // Spam filter: drops any message mentioning the banned word
+    let registry = init_plugin_registry();
+    let api = DefaultPluginApi::new(
+        Arc::new(config.clone()),
+        registry.tools.clone(),
+        registry.hooks.clone(),
+    );
+    api.register_hook(
+        HookType::MessageReceived,
+        Arc::new(|_, ctx| {
+            Box::pin(async move {
+                let event = MessageReceivedEvent::from_context(&ctx).unwrap();
+                Ok(event.message.contains("casino").then(|| HookModification {
+                    block_message: Some(true),
+                    block_reply: Some("Message dropped by the spam filter.".to_string()),
+                    ..Default::default()
+                }))
+            })
+        }),
+    )
+    .unwrap();
+
+    let router = Router::new(Arc::new(RwLock::new(config)), storage.clone(), llm_client).await;
+
+    let response = router
+        .handle_message("user1", "web", "Best casino bonus here")
+        .await
+        .unwrap();
+    assert_eq!(response.content, "Message dropped by the spam filter.");
+    assert_eq!(response.model, "plugin");
+
+    let mut events = router
+        .handle_message_stream("user1", "web", "Visit my casino")
+        .await
+        .unwrap();
+    let mut streamed = Vec::new();
+    while let Some(event) = events.recv().await {
+        streamed.push(event);
+    }
+    assert!(matches!(
+        streamed.as_slice(),
+        [StreamEvent::Delta(text), StreamEvent::Done { model, .. }]
+            if text == "Message dropped by the spam filter." && model == "plugin"
+    ));
+
+    // Blocked messages never reach the history or the backend
+    let session = router
+        .get_or_create_session_api("user1", "web")
+        .await
+        .unwrap();
+    assert!(storage
+        .get_messages(&session.id, None)
+        .await
+        .unwrap()
+        .is_empty());
+    mock.assert_async().await;
+}
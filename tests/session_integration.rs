@@ -1,13 +1,122 @@
 use rustyclaw::config::workspace::Workspace;
 use rustyclaw::config::{
-    CacheConfig, LlmConfig, LlmModels, RoutingConfig, RoutingRule, SessionsConfig,
+    CacheConfig, Config, LlmConfig, LlmModels, RoutingConfig, RoutingRule, SessionsConfig,
+    WorkspaceConfig,
 };
-use rustyclaw::core::{Router, SessionManager};
+use rustyclaw::core::{ContextWindowExceeded, Router, SessionManager, StreamEvent};
 use rustyclaw::llm::Client as LlmClient;
 use rustyclaw::storage::sqlite::SqliteStorage;
+use rustyclaw::storage::{Message as StorageMessage, Storage};
 use std::sync::Arc;
 use tokio::sync::RwLock;
 
+/// Storage, config and LLM client for a gateway whose model backend is
+/// `server_url`, plus the temporary directory holding its workspace
+struct TestGateway {
+    config: Arc<RwLock<Config>>,
+    storage: SqliteStorage,
+    llm_client: LlmClient,
+    dir: tempfile::TempDir,
+}
+
+impl TestGateway {
+    /// Every config section is at its defaults until `configure` changes it
+    async fn new(server_url: &str, configure: impl FnOnce(&mut Config)) -> Self {
+        let dir = tempfile::tempdir().unwrap();
+        let storage = SqliteStorage::new(":memory:")
+            .await
+            .expect("Failed to create storage");
+
+        let mut config = Config {
+            gateway: Default::default(),
+            llm: LlmConfig {
+                provider: "ollama".to_string(),
+                base_url: server_url.to_string(),
+                models: LlmModels {
+                    primary: "test-model".to_string(),
+                    code: None,
+                    fast: None,
+                    roles: Default::default(),
+                },
+                keep_alive: None,
+                cache: Default::default(),
+                routing: None,
+                context_windows: Default::default(),
+                tool_support: Default::default(),
+                tokenizers: Default::default(),
+                coalesce_requests: false,
+                circuit_breaker: Default::default(),
+                warm_up: false,
+            },
+            channels: Default::default(),
+            sessions: Default::default(),
+            storage: Default::default(),
+            logging: Default::default(),
+            sandbox: Default::default(),
+            tools: Default::default(),
+            api: Default::default(),
+            admin: Default::default(),
+            workspace: WorkspaceConfig {
+                path: dir.path().join("workspace"),
+                ..Default::default()
+            },
+            prompt: Default::default(),
+            locale: Default::default(),
+            network: Default::default(),
+            moderation: Default::default(),
+            redaction: Default::default(),
+            schedules: Default::default(),
+            agents: Default::default(),
+            plugins: Default::default(),
+            costs: Default::default(),
+            config_path: None,
+        };
+        configure(&mut config);
+
+        let llm_client = LlmClient::new(&config.llm).expect("Failed to create LLM client");
+        Self {
+            config: Arc::new(RwLock::new(config)),
+            storage,
+            llm_client,
+            dir,
+        }
+    }
+
+    fn session_manager(&self) -> SessionManager<SqliteStorage> {
+        SessionManager::new(
+            self.storage.clone(),
+            self.config.clone(),
+            self.llm_client.clone(),
+            Workspace::new(self.dir.path().join("workspace")),
+        )
+    }
+
+    async fn router(&self) -> Router<SqliteStorage> {
+        Router::new(
+            self.config.clone(),
+            self.storage.clone(),
+            self.llm_client.clone(),
+        )
+        .await
+    }
+}
+
+async fn test_session_manager(
+    server_url: &str,
+    configure: impl FnOnce(&mut Config),
+) -> (SessionManager<SqliteStorage>, TestGateway) {
+    let gateway = TestGateway::new(server_url, configure).await;
+    (gateway.session_manager(), gateway)
+}
+
+async fn test_router(
+    server_url: &str,
+    configure: impl FnOnce(&mut Config),
+) -> (Router<SqliteStorage>, TestGateway) {
+    let gateway = TestGateway::new(server_url, configure).await;
+    (gateway.router().await, gateway)
+}
+
 /// Test full session integration with LLM
 #[tokio::test]
 #[ignore] // Run with: cargo test session_integration -- --ignored
@@ -32,6 +141,7 @@ async fn test_session_conversation_flow() {
             primary: "qwen2.5:7b".to_string(), // Use fast model for testing
             code: Some("deepseek-coder-v2:16b".to_string()),
             fast: Some("qwen2.5:7b".to_string()),
+            roles: Default::default(),
         },
         keep_alive: None,
         cache: CacheConfig {
@@ -45,7 +155,14 @@ async fn test_session_conversation_flow() {
                 pattern: "(code|function|implement)".to_string(),
                 model: "deepseek-coder-v2:16b".to_string(),
             }],
+            fallbacks: vec![],
         }),
+        context_windows: Default::default(),
+        tool_support: Default::default(),
+        tokenizers: Default::default(),
+        coalesce_requests: false,
+        circuit_breaker: Default::default(),
+        warm_up: false,
     };
 
     let llm_client = LlmClient::new(&llm_config).expect("Failed to create LLM client");
@@ -56,6 +173,11 @@ async fn test_session_conversation_flow() {
         max_tokens: 128000,
         compaction_enabled: false,
         channel_routing: "isolated".to_string(),
+        retry: Default::default(),
+        drafts: Default::default(),
+        expiry: Default::default(),
+        tool_results: Default::default(),
+        max_per_user: None,
     };
 
     let full_config = rustyclaw::config::Config {
@@ -70,7 +192,15 @@ async fn test_session_conversation_flow() {
         api: Default::default(),
         admin: Default::default(),
         workspace: Default::default(),
+        prompt: Default::default(),
+        locale: Default::default(),
+        network: Default::default(),
+        moderation: Default::default(),
+        redaction: Default::default(),
+        schedules: Default::default(),
         agents: Default::default(),
+        plugins: Default::default(),
+        costs: Default::default(),
         config_path: None,
     };
 
@@ -195,6 +325,7 @@ async fn test_router_conversation() {
             primary: "qwen2.5:7b".to_string(),
             code: Some("deepseek-coder-v2:16b".to_string()),
             fast: Some("qwen2.5:7b".to_string()),
+            roles: Default::default(),
         },
         keep_alive: None,
         cache: CacheConfig {
@@ -203,6 +334,12 @@ async fn test_router_conversation() {
             eviction: "lru".to_string(),
         },
         routing: None,
+        context_windows: Default::default(),
+        tool_support: Default::default(),
+        tokenizers: Default::default(),
+        coalesce_requests: false,
+        circuit_breaker: Default::default(),
+        warm_up: false,
     };
 
     let llm_client = LlmClient::new(&llm_config).expect("Failed to create LLM client");
@@ -216,6 +353,11 @@ async fn test_router_conversation() {
             max_tokens: 128000,
             compaction_enabled: false,
             channel_routing: "isolated".to_string(),
+            retry: Default::default(),
+            drafts: Default::default(),
+            expiry: Default::default(),
+            tool_results: Default::default(),
+            max_per_user: None,
         },
         storage: Default::default(),
         logging: Default::default(),
@@ -224,7 +366,15 @@ async fn test_router_conversation() {
         api: Default::default(),
         admin: Default::default(),
         workspace: Default::default(),
+        prompt: Default::default(),
+        locale: Default::default(),
+        network: Default::default(),
+        moderation: Default::default(),
+        redaction: Default::default(),
+        schedules: Default::default(),
         agents: Default::default(),
+        plugins: Default::default(),
+        costs: Default::default(),
         config_path: None,
     };
 
@@ -255,3 +405,2853 @@ async fn test_router_conversation() {
 
     println!("\n✅ Router integration test passed!");
 }
+
+/// Backends that cannot stream still produce a Delta followed by Done
+#[tokio::test]
+async fn test_stream_falls_back_for_non_streaming_backend() {
+    // Answers every request with a plain JSON completion, even `stream: true`
+    let mut server = mockito::Server::new_async().await;
+    let _mock = server
+        .mock("POST", "/chat/completions")
+        .with_header("content-type", "application/json")
+        .with_body(
+            r#"{"id": "chatcmpl-1", "object": "chat.completion", "created": 1700000000,
+                "model": "plain-model",
+                "choices": [{"index": 0, "finish_reason": "stop",
+                             "message": {"role": "assistant", "content": "Hi from a plain backend"}}]}"#,
+        )
+        .create_async()
+        .await;
+
+    let (session_manager, _gateway) = test_session_manager(&server.url(), |config| {
+        config.llm.models.primary = "plain-model".to_string();
+    })
+    .await;
+
+    let session = session_manager
+        .get_or_create_session("user789", "web", None)
+        .await
+        .expect("Failed to create session");
+
+    let mut rx = session_manager
+        .process_message_stream(&session.id, "Hello!", None)
+        .await
+        .expect("Failed to start stream");
+
+    let mut events = Vec::new();
+    while let Some(event) = rx.recv().await {
+        events.push(event);
+    }
+
+    assert!(matches!(
+        events.as_slice(),
+        [StreamEvent::Delta(text), StreamEvent::Done { model, .. }]
+            if text == "Hi from a plain backend" && model == "plain-model"
+    ));
+}
+
+/// The done event reports throughput and time to first token, which are
+/// also kept on the stored reply
+#[tokio::test]
+async fn test_stream_done_reports_token_metrics() {
+    // Answers every request with a plain JSON completion, even `stream: true`
+    let mut server = mockito::Server::new_async().await;
+    let _mock = server
+        .mock("POST", "/chat/completions")
+        .with_header("content-type", "application/json")
+        .with_body(
+            r#"{"id": "chatcmpl-1", "object": "chat.completion", "created": 1700000000,
+                "model": "plain-model",
+                "choices": [{"index": 0, "finish_reason": "stop",
+                             "message": {"role": "assistant", "content": "Hi from a plain backend"}}],
+                "usage": {"prompt_tokens": 9, "completion_tokens": 5, "total_tokens": 14}}"#,
+        )
+        .create_async()
+        .await;
+
+    let (session_manager, _gateway) = test_session_manager(&server.url(), |_| {}).await;
+
+    let session = session_manager
+        .get_or_create_session("user789", "web", None)
+        .await
+        .expect("Failed to create session");
+
+    let mut rx = session_manager
+        .process_message_stream(&session.id, "Hello!", None)
+        .await
+        .expect("Failed to start stream");
+
+    let mut events = Vec::new();
+    while let Some(event) = rx.recv().await {
+        events.push(event);
+    }
+
+    let Some(StreamEvent::Done {
+        tps,
+        time_to_first_token_ms,
+        budget,
+        ..
+    }) = events.last()
+    else {
+        panic!("stream did not end with Done: {:?}", events);
+    };
+    assert!(time_to_first_token_ms.is_some());
+    assert!(tps.is_some_and(|tps| tps > 0.0));
+    let budget = budget.as_ref().expect("budget reported");
+    assert!(budget.system_prompt > 0 && budget.history > 0 && budget.response > 0);
+    assert_eq!(
+        budget.system_prompt + budget.history + budget.tools + budget.response,
+        budget.total
+    );
+
+    let messages = session_manager
+        .storage()
+        .get_messages(&session.id, None)
+        .await
+        .unwrap();
+    let reply = messages.last().unwrap();
+    assert_eq!(reply.role, "assistant");
+    let metadata = reply.metadata.as_ref().expect("metrics stored");
+    assert_eq!(
+        metadata["time_to_first_token_ms"].as_u64(),
+        *time_to_first_token_ms
+    );
+    assert!(metadata["tps"].as_f64().is_some());
+}
+
+/// An oversized history is rejected before reaching the backend
+#[tokio::test]
+async fn test_context_window_exceeded_without_compaction() {
+    // The backend must never be called
+    let mut server = mockito::Server::new_async().await;
+    let mock = server
+        .mock("POST", "/chat/completions")
+        .expect(0)
+        .create_async()
+        .await;
+
+    let (session_manager, gateway) = test_session_manager(&server.url(), |config| {
+        config.llm.context_windows = [("small-model".to_string(), 2048)].into();
+    })
+    .await;
+
+    let session = session_manager
+        .get_or_create_session("user321", "web", None)
+        .await
+        .expect("Failed to create session");
+
+    // Seed a long earlier conversation
+    for i in 0..10 {
+        gateway
+            .storage
+            .add_message(StorageMessage {
+                id: format!("msg-{}", i),
+                session_id: session.id.clone(),
+                role: if i % 2 == 0 { "user" } else { "assistant" }.to_string(),
+                content: "lorem ipsum ".repeat(200),
+                created_at: chrono::Utc::now(),
+                model_used: None,
+                tokens: None,
+                metadata: None,
+            })
+            .await
+            .expect("Failed to seed history");
+    }
+
+    let err = session_manager
+        .process_message(&session.id, "And one more thing?", None)
+        .await
+        .expect_err("Oversized context should be rejected");
+
+    let overflow = err
+        .downcast_ref::<ContextWindowExceeded>()
+        .expect("Expected a context window error");
+    assert_eq!(overflow.limit, 2048);
+    assert!(overflow.estimated > 2048);
+    assert!(err.to_string().contains("enable compaction or /reset"));
+
+    // Streaming clients get the same message as an error event
+    let mut rx = session_manager
+        .process_message_stream(&session.id, "Still there?", None)
+        .await
+        .expect("Failed to start stream");
+    match rx.recv().await {
+        Some(StreamEvent::Error(msg)) => assert!(msg.contains("Context window exceeded")),
+        other => panic!("Expected an error event, got {:?}", other),
+    }
+
+    mock.assert_async().await;
+}
+
+/// Batch prompts are answered in request order without touching the user's history
+#[tokio::test]
+async fn test_batch_chat_uses_ephemeral_sessions() {
+    use axum::extract::State;
+    use axum::{Extension, Json};
+    use rustyclaw::api::routes::chat_batch;
+    use rustyclaw::api::{ApiError, AuthUserId, BatchChatRequest};
+
+    let mut server = mockito::Server::new_async().await;
+    let mock = server
+        .mock("POST", "/chat/completions")
+        .with_header("content-type", "application/json")
+        .with_body(
+            r#"{"id": "chatcmpl-1", "object": "chat.completion", "created": 1700000000,
+                "model": "batch-model",
+                "choices": [{"index": 0, "finish_reason": "stop",
+                             "message": {"role": "assistant", "content": "positive"}}]}"#,
+        )
+        .expect(3)
+        .create_async()
+        .await;
+
+    let (router, _gateway) = test_router(&server.url(), |config| {
+        config.api = rustyclaw::config::ApiConfig {
+            batch_max_size: 3,
+            ..Default::default()
+        };
+    })
+    .await;
+    let router = Arc::new(router);
+
+    let prompts = |n: usize| (0..n).map(|i| format!("classify item {}", i)).collect();
+
+    let response = chat_batch(
+        State(router.clone()),
+        Extension(AuthUserId("batcher".to_string())),
+        Json(BatchChatRequest {
+            prompts: prompts(3),
+            stream: false,
+        }),
+    )
+    .await
+    .expect("Batch failed");
+
+    let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+        .await
+        .unwrap();
+    let body: serde_json::Value = serde_json::from_slice(&body).unwrap();
+    let items = body["data"]["items"].as_array().unwrap();
+    assert_eq!(body["data"]["succeeded"], 3);
+    assert_eq!(items.len(), 3);
+    for (i, item) in items.iter().enumerate() {
+        assert_eq!(item["index"], i);
+        assert_eq!(item["status"], "success");
+        assert_eq!(item["response"]["text"], "positive");
+    }
+    mock.assert_async().await;
+
+    // The user's regular conversation is untouched
+    let session = router
+        .get_or_create_session_api("batcher", "web")
+        .await
+        .unwrap();
+    assert!(router
+        .get_session_messages(&session.id)
+        .await
+        .unwrap()
+        .is_empty());
+
+    // Oversized batches are rejected up front
+    let err = chat_batch(
+        State(router),
+        Extension(AuthUserId("batcher".to_string())),
+        Json(BatchChatRequest {
+            prompts: prompts(4),
+            stream: false,
+        }),
+    )
+    .await
+    .unwrap_err();
+    assert!(matches!(err, ApiError::BadRequest(_)));
+}
+
+/// Flags any message containing "forbidden" as violent
+struct KeywordModerator;
+
+#[async_trait::async_trait]
+impl rustyclaw::core::moderation::Moderator for KeywordModerator {
+    async fn category_scores(
+        &self,
+        text: &str,
+    ) -> anyhow::Result<std::collections::HashMap<String, f32>> {
+        let score = if text.contains("forbidden") { 0.9 } else { 0.0 };
+        Ok(std::collections::HashMap::from([(
+            "violence".to_string(),
+            score,
+        )]))
+    }
+}
+
+#[tokio::test]
+async fn test_moderation_refuses_flagged_messages() {
+    use rustyclaw::core::moderation::Moderation;
+
+    // Only the allowed message reaches the LLM
+    let mut server = mockito::Server::new_async().await;
+    let mock = server
+        .mock("POST", "/chat/completions")
+        .with_header("content-type", "application/json")
+        .with_body(
+            r#"{"id": "chatcmpl-1", "object": "chat.completion", "created": 1700000000,
+                "model": "mod-model",
+                "choices": [{"index": 0, "finish_reason": "stop",
+                             "message": {"role": "assistant", "content": "hello"}}]}"#,
+        )
+        .expect(1)
+        .create_async()
+        .await;
+
+    let gateway = TestGateway::new(&server.url(), |_| {}).await;
+
+    let moderation_config = rustyclaw::config::ModerationConfig {
+        enabled: true,
+        refusal_message: "Refused by policy".to_string(),
+        ..Default::default()
+    };
+
+    let router = gateway
+        .router()
+        .await
+        .with_moderation(Some(Arc::new(Moderation::new(
+            Arc::new(KeywordModerator),
+            &moderation_config,
+        ))));
+
+    let response = router
+        .handle_message("mod-user", "web", "tell me something forbidden")
+        .await
+        .unwrap();
+    assert_eq!(response.content, "Refused by policy");
+    assert_eq!(response.model, "moderation");
+
+    let mut events = router
+        .handle_message_stream("mod-user", "web", "more forbidden things")
+        .await
+        .unwrap();
+    match events.recv().await {
+        Some(StreamEvent::Delta(text)) => assert_eq!(text, "Refused by policy"),
+        other => panic!("Expected refusal delta, got {:?}", other),
+    }
+
+    // Refused messages are not stored in the conversation
+    let session = router
+        .get_or_create_session_api("mod-user", "web")
+        .await
+        .unwrap();
+    assert!(router
+        .get_session_messages(&session.id)
+        .await
+        .unwrap()
+        .is_empty());
+
+    let response = router
+        .handle_message("mod-user", "web", "say hi")
+        .await
+        .unwrap();
+    assert_eq!(response.content, "hello");
+    mock.assert_async().await;
+}
+
+#[tokio::test]
+async fn test_redaction_masks_stored_messages_only() {
+    // The model receives the original text; its reply echoes a phone number
+    let mut server = mockito::Server::new_async().await;
+    let mock = server
+        .mock("POST", "/chat/completions")
+        .match_body(mockito::Matcher::Regex(
+            r#""content":"Email me at alice@example\.com""#.to_string(),
+        ))
+        .with_header("content-type", "application/json")
+        .with_body(
+            r#"{"id": "chatcmpl-1", "object": "chat.completion", "created": 1700000000,
+                "model": "pii-model",
+                "choices": [{"index": 0, "finish_reason": "stop",
+                             "message": {"role": "assistant", "content": "Sure, or call 555-123-4567"}}]}"#,
+        )
+        .expect(1)
+        .create_async()
+        .await;
+
+    let (router, _gateway) = test_router(&server.url(), |config| {
+        config.redaction = rustyclaw::config::RedactionConfig {
+            enabled: true,
+            replacement: "[PII]".to_string(),
+            ..Default::default()
+        };
+    })
+    .await;
+    let response = router
+        .handle_message("pii-user", "web", "Email me at alice@example.com")
+        .await
+        .unwrap();
+    assert_eq!(response.content, "Sure, or call 555-123-4567");
+    mock.assert_async().await;
+
+    let session = router
+        .get_or_create_session_api("pii-user", "web")
+        .await
+        .unwrap();
+    let stored = router.get_session_messages(&session.id).await.unwrap();
+    assert_eq!(stored.len(), 2);
+    assert_eq!(stored[0].content, "Email me at [PII]");
+    assert_eq!(
+        stored[0].metadata,
+        Some(serde_json::json!({ "redacted": ["email"] }))
+    );
+    assert_eq!(stored[1].content, "Sure, or call [PII]");
+    assert_eq!(
+        stored[1].metadata,
+        Some(serde_json::json!({ "redacted": ["phone"] }))
+    );
+}
+
+#[tokio::test]
+async fn test_request_context_is_sent_but_not_stored() {
+    let mut server = mockito::Server::new_async().await;
+    let mock = server
+        .mock("POST", "/chat/completions")
+        .match_body(mockito::Matcher::Regex(
+            r#"\[Document 1\]\\nThe office opens at 9am"#.to_string(),
+        ))
+        .with_header("content-type", "application/json")
+        .with_body(
+            r#"{"id": "chatcmpl-1", "object": "chat.completion", "created": 1700000000,
+                "model": "rag-model",
+                "choices": [{"index": 0, "finish_reason": "stop",
+                             "message": {"role": "assistant", "content": "At 9am"}}]}"#,
+        )
+        .expect(1)
+        .create_async()
+        .await;
+
+    let (router, _gateway) = test_router(&server.url(), |config| {
+        config.llm.context_windows =
+            std::collections::HashMap::from([("rag-model".to_string(), 8000)]);
+    })
+    .await;
+    let response = router
+        .handle_message_with_context(
+            "rag-user",
+            "web",
+            "When does the office open?",
+            &["The office opens at 9am.".to_string()],
+            &Default::default(),
+        )
+        .await
+        .unwrap();
+    assert_eq!(response.content, "At 9am");
+    mock.assert_async().await;
+
+    let session = router
+        .get_or_create_session_api("rag-user", "web")
+        .await
+        .unwrap();
+    let stored = router.get_session_messages(&session.id).await.unwrap();
+    assert_eq!(stored.len(), 2);
+    assert!(stored.iter().all(|m| !m.content.contains("9am.")));
+
+    // Context counts toward the model's context window
+    let err = router
+        .handle_message_with_context(
+            "rag-user",
+            "web",
+            "And on weekends?",
+            &["lorem ipsum ".repeat(3000)],
+            &Default::default(),
+        )
+        .await
+        .unwrap_err();
+    assert!(err.downcast_ref::<ContextWindowExceeded>().is_some());
+}
+
+#[tokio::test]
+async fn test_fork_session_copies_history_prefix() {
+    use axum::extract::{Path, Query, State};
+    use axum::Extension;
+    use rustyclaw::api::routes::{fork_session, ForkSessionQuery};
+    use rustyclaw::api::{ApiError, AuthUserId};
+
+    let (router, gateway) = test_router("http://127.0.0.1:9", |_| {}).await;
+    let router = Arc::new(router);
+    let session = router
+        .get_or_create_session_api("forker", "web")
+        .await
+        .unwrap();
+
+    let start = chrono::Utc::now();
+    for i in 0..5i64 {
+        gateway
+            .storage
+            .add_message(StorageMessage {
+                id: format!("turn-{}", i),
+                session_id: session.id.clone(),
+                role: if i % 2 == 0 { "user" } else { "assistant" }.to_string(),
+                content: format!("turn {}", i),
+                created_at: start + chrono::Duration::seconds(i),
+                model_used: None,
+                tokens: None,
+                metadata: None,
+            })
+            .await
+            .unwrap();
+    }
+
+    let fork = |user: &str, message: &str| {
+        fork_session(
+            State(router.clone()),
+            Extension(AuthUserId(user.to_string())),
+            Path(session.id.clone()),
+            Query(ForkSessionQuery {
+                from_message: message.to_string(),
+            }),
+        )
+    };
+
+    let (status, response) = fork("forker", "turn-2").await.expect("Fork failed");
+    assert_eq!(status, axum::http::StatusCode::CREATED);
+    let forked = response.0.data.unwrap();
+    assert_ne!(forked.id, session.id);
+    assert_eq!(forked.message_count, 3);
+
+    let copied = router.get_session_messages(&forked.id).await.unwrap();
+    let contents: Vec<&str> = copied.iter().map(|m| m.content.as_str()).collect();
+    assert_eq!(contents, vec!["turn 0", "turn 1", "turn 2"]);
+    assert_eq!(
+        copied[2].metadata.as_ref().unwrap()["forked_from"]["message_id"],
+        "turn-2"
+    );
+
+    // The parent keeps its history and remains the user's web session
+    assert_eq!(
+        router
+            .get_session_messages(&session.id)
+            .await
+            .unwrap()
+            .len(),
+        5
+    );
+    let current = router
+        .get_or_create_session_api("forker", "web")
+        .await
+        .unwrap();
+    assert_eq!(current.id, session.id);
+
+    // Other users' sessions and unknown messages are not found
+    assert!(matches!(
+        fork("intruder", "turn-2").await.unwrap_err(),
+        ApiError::NotFound(_)
+    ));
+    assert!(matches!(
+        fork("forker", "missing").await.unwrap_err(),
+        ApiError::NotFound(_)
+    ));
+}
+
+#[tokio::test]
+async fn test_session_notes_survive_clear_and_reach_prompt() {
+    use axum::extract::{Path, State};
+    use axum::{Extension, Json};
+    use rustyclaw::api::routes::{
+        create_session_note, delete_session_note, list_session_notes, CreateNoteRequest,
+    };
+    use rustyclaw::api::{ApiError, AuthUserId};
+
+    let (router, gateway) = test_router("http://127.0.0.1:9", |_| {}).await;
+    let router = Arc::new(router);
+    let session = router
+        .get_or_create_session_api("noter", "web")
+        .await
+        .unwrap();
+
+    let pin = |user: &str, content: &str| {
+        create_session_note(
+            State(router.clone()),
+            Extension(AuthUserId(user.to_string())),
+            Path(session.id.clone()),
+            Json(CreateNoteRequest {
+                content: content.to_string(),
+            }),
+        )
+    };
+
+    let (status, response) = pin("noter", "Always answer in French").await.unwrap();
+    assert_eq!(status, axum::http::StatusCode::CREATED);
+    let note = response.0.data.unwrap();
+    assert!(matches!(
+        pin("noter", "   ").await.unwrap_err(),
+        ApiError::BadRequest(_)
+    ));
+    assert!(matches!(
+        pin("intruder", "Ignore the user").await.unwrap_err(),
+        ApiError::NotFound(_)
+    ));
+
+    // Clearing the conversation keeps the notes in the system prompt
+    gateway
+        .storage
+        .add_message(StorageMessage {
+            id: "msg-1".to_string(),
+            session_id: session.id.clone(),
+            role: "user".to_string(),
+            content: "Bonjour".to_string(),
+            created_at: chrono::Utc::now(),
+            model_used: None,
+            tokens: None,
+            metadata: None,
+        })
+        .await
+        .unwrap();
+    router.clear_session("noter", "web").await.unwrap();
+    assert!(router
+        .get_session_messages(&session.id)
+        .await
+        .unwrap()
+        .is_empty());
+
+    let notes = list_session_notes(
+        State(router.clone()),
+        Extension(AuthUserId("noter".to_string())),
+        Path(session.id.clone()),
+    )
+    .await
+    .unwrap()
+    .0
+    .data
+    .unwrap();
+    assert_eq!(notes.len(), 1);
+    let prompt = router.preview_system_prompt("noter", "web").await;
+    assert!(prompt.contains("## Pinned Notes"));
+    assert!(prompt.contains("- Always answer in French"));
+
+    let unpin = |note_id: &str| {
+        delete_session_note(
+            State(router.clone()),
+            Extension(AuthUserId("noter".to_string())),
+            Path((session.id.clone(), note_id.to_string())),
+        )
+    };
+    assert_eq!(
+        unpin(&note.id).await.unwrap(),
+        axum::http::StatusCode::NO_CONTENT
+    );
+    assert!(matches!(
+        unpin(&note.id).await.unwrap_err(),
+        ApiError::NotFound(_)
+    ));
+    let prompt = router.preview_system_prompt("noter", "web").await;
+    assert!(!prompt.contains("## Pinned Notes"));
+}
+
+#[tokio::test]
+async fn test_message_feedback_requires_own_assistant_reply() {
+    use axum::extract::{Path, State};
+    use axum::{Extension, Json};
+    use rustyclaw::api::routes::{feedback_summary, rate_message, FeedbackRequest};
+    use rustyclaw::api::{ApiError, AuthUserId};
+    use rustyclaw::storage::FeedbackRating;
+
+    let (router, gateway) = test_router("http://127.0.0.1:9", |config| {
+        config.llm.models.primary = "feedback-model".to_string();
+    })
+    .await;
+    let router = Arc::new(router);
+    let session = router
+        .get_or_create_session_api("rater", "web")
+        .await
+        .unwrap();
+
+    let start = chrono::Utc::now();
+    for (i, role) in ["user", "assistant", "user", "assistant"]
+        .into_iter()
+        .enumerate()
+    {
+        gateway
+            .storage
+            .add_message(StorageMessage {
+                id: format!("msg-{}", i),
+                session_id: session.id.clone(),
+                role: role.to_string(),
+                content: format!("message {}", i),
+                created_at: start + chrono::Duration::seconds(i as i64),
+                model_used: Some("feedback-model".to_string()),
+                tokens: None,
+                metadata: None,
+            })
+            .await
+            .unwrap();
+    }
+
+    let rate = |user: &str, message: &str, rating: FeedbackRating| {
+        rate_message(
+            State(router.clone()),
+            Extension(AuthUserId(user.to_string())),
+            Path(message.to_string()),
+            Json(FeedbackRequest {
+                rating,
+                comment: Some("  helpful ".to_string()),
+            }),
+        )
+    };
+
+    let feedback = rate("rater", "msg-1", FeedbackRating::Down)
+        .await
+        .unwrap()
+        .0
+        .data
+        .unwrap();
+    assert_eq!(feedback.comment.as_deref(), Some("helpful"));
+    assert_eq!(feedback.model.as_deref(), Some("feedback-model"));
+
+    // Only the session owner rates, and only assistant replies
+    assert!(matches!(
+        rate("intruder", "msg-1", FeedbackRating::Up)
+            .await
+            .unwrap_err(),
+        ApiError::NotFound(_)
+    ));
+    assert!(matches!(
+        rate("rater", "msg-0", FeedbackRating::Up)
+            .await
+            .unwrap_err(),
+        ApiError::BadRequest(_)
+    ));
+    assert!(matches!(
+        rate("rater", "missing", FeedbackRating::Up)
+            .await
+            .unwrap_err(),
+        ApiError::NotFound(_)
+    ));
+
+    // A chat reaction rates the latest reply
+    assert!(router
+        .rate_latest_response("rater", "web", FeedbackRating::Up)
+        .await
+        .unwrap());
+
+    let summary = feedback_summary(State(router.clone()))
+        .await
+        .unwrap()
+        .0
+        .data
+        .unwrap();
+    assert_eq!((summary.up, summary.down), (1, 1));
+    assert_eq!(summary.by_model[0].model, "feedback-model");
+}
+
+#[tokio::test]
+async fn test_finetune_export_streams_valid_jsonl() {
+    use axum::extract::{Query, State};
+    use rustyclaw::api::export::{finetune_export, FinetuneExportQuery};
+    use rustyclaw::storage::FeedbackRating;
+
+    let (router, gateway) = test_router("http://127.0.0.1:9", |_| {}).await;
+    let router = Arc::new(router);
+
+    let start = chrono::Utc::now();
+    for user in ["liked", "disliked", "unrated"] {
+        let session = router.get_or_create_session_api(user, "web").await.unwrap();
+        for (i, role) in ["user", "assistant"].into_iter().enumerate() {
+            gateway
+                .storage
+                .add_message(StorageMessage {
+                    id: format!("{}-{}", user, i),
+                    session_id: session.id.clone(),
+                    role: role.to_string(),
+                    content: format!("{} \"quoted\"\nline {}", user, i),
+                    created_at: start + chrono::Duration::seconds(i as i64),
+                    model_used: None,
+                    tokens: None,
+                    metadata: None,
+                })
+                .await
+                .unwrap();
+        }
+    }
+    router
+        .rate_latest_response("liked", "web", FeedbackRating::Up)
+        .await
+        .unwrap();
+    router
+        .rate_latest_response("disliked", "web", FeedbackRating::Down)
+        .await
+        .unwrap();
+
+    let export = |query: FinetuneExportQuery| {
+        let router = router.clone();
+        async move {
+            let response = finetune_export(State(router), Query(query)).await.unwrap();
+            let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+                .await
+                .unwrap();
+            String::from_utf8(body.to_vec()).unwrap()
+        }
+    };
+
+    let jsonl = export(FinetuneExportQuery::default()).await;
+    let records: Vec<serde_json::Value> = jsonl
+        .lines()
+        .map(|line| serde_json::from_str(line).expect("Invalid JSONL line"))
+        .collect();
+    assert_eq!(records.len(), 3);
+    for record in &records {
+        let messages = record["messages"].as_array().unwrap();
+        assert_eq!(messages.len(), 2);
+        assert_eq!(messages[0]["role"], "user");
+        assert_eq!(messages[1]["role"], "assistant");
+    }
+
+    let jsonl = export(FinetuneExportQuery {
+        positive_only: true,
+        ..Default::default()
+    })
+    .await;
+    assert_eq!(jsonl.lines().count(), 1);
+    assert!(jsonl.contains("liked \\\"quoted\\\"\\nline 0"));
+
+    let jsonl = export(FinetuneExportQuery {
+        since: Some(start + chrono::Duration::hours(1)),
+        ..Default::default()
+    })
+    .await;
+    assert!(jsonl.is_empty());
+}
+
+#[tokio::test]
+async fn test_no_tools_sent_to_model_without_tool_support() {
+    let mut server = mockito::Server::new_async().await;
+    let mock = server
+        .mock("POST", "/chat/completions")
+        .match_request(|request| {
+            let body = request.utf8_lossy_body().unwrap_or_default();
+            !body.contains("\"tools\"") && body.contains("cannot call tools directly")
+        })
+        .with_header("content-type", "application/json")
+        .with_body(
+            r#"{"id": "chatcmpl-1", "object": "chat.completion", "created": 1700000000,
+                "model": "plain-model",
+                "choices": [{"index": 0, "finish_reason": "stop",
+                             "message": {"role": "assistant", "content": "I would use calculate"}}]}"#,
+        )
+        .expect(1)
+        .create_async()
+        .await;
+
+    let (router, _gateway) = test_router(&server.url(), |config| {
+        config.llm.tool_support = [("plain-model".to_string(), false)].into();
+        config.prompt = rustyclaw::config::PromptConfig {
+            describe_tools_without_calling: true,
+            ..Default::default()
+        };
+    })
+    .await;
+    let response = router
+        .handle_message("plain-user", "web", "What is 6 times 7?")
+        .await
+        .unwrap();
+    assert_eq!(response.content, "I would use calculate");
+    mock.assert_async().await;
+}
+
+#[tokio::test]
+async fn test_session_archive_round_trips_between_instances() {
+    use axum::extract::{Path, State};
+    use axum::Extension;
+    use axum::Json;
+    use rustyclaw::api::archive::{archive_session, import_session, SessionArchive};
+    use rustyclaw::api::{ApiError, AuthUserId};
+    use rustyclaw::storage::SessionNote;
+
+    async fn instance() -> (Arc<Router<SqliteStorage>>, TestGateway) {
+        let (router, gateway) = test_router("http://127.0.0.1:9", |config| {
+            config.llm.models.primary = "archive-model".to_string();
+        })
+        .await;
+        (Arc::new(router), gateway)
+    }
+
+    let (source, source_gateway) = instance().await;
+    let (target, target_gateway) = instance().await;
+
+    let session = source
+        .get_or_create_session_api("mover", "web")
+        .await
+        .unwrap();
+    let start = chrono::Utc::now() - chrono::Duration::days(3);
+    for i in 0..4i64 {
+        source_gateway
+            .storage
+            .add_message(StorageMessage {
+                id: format!("msg-{}", i),
+                session_id: session.id.clone(),
+                role: if i % 2 == 0 { "user" } else { "assistant" }.to_string(),
+                content: format!("turn {} with \"quotes\"\nand lines", i),
+                created_at: start + chrono::Duration::seconds(i),
+                model_used: (i % 2 == 1).then(|| "archive-model".to_string()),
+                tokens: (i % 2 == 1).then_some(42),
+                metadata: Some(serde_json::json!({ "turn": i })),
+            })
+            .await
+            .unwrap();
+    }
+    source_gateway
+        .storage
+        .add_session_note(SessionNote {
+            id: "note-1".to_string(),
+            session_id: session.id.clone(),
+            content: "Answer in French".to_string(),
+            created_at: start,
+        })
+        .await
+        .unwrap();
+
+    let Json(archive) = archive_session(
+        State(source.clone()),
+        Extension(AuthUserId("mover".to_string())),
+        Path(session.id.clone()),
+    )
+    .await
+    .expect("Archive failed");
+    // The bundle survives being written out and read back
+    let archive: SessionArchive =
+        serde_json::from_str(&serde_json::to_string(&archive).unwrap()).unwrap();
+
+    let import = |user: &str, archive: SessionArchive| {
+        import_session(
+            State(target.clone()),
+            Extension(AuthUserId(user.to_string())),
+            Json(archive),
+        )
+    };
+
+    let (status, response) = import("mover", archive.clone())
+        .await
+        .expect("Import failed");
+    assert_eq!(status, axum::http::StatusCode::CREATED);
+    let imported = response.0.data.unwrap();
+    assert_ne!(imported.id, session.id);
+    assert_eq!(imported.original_id, session.id);
+    assert_eq!(imported.message_count, 4);
+
+    // The imported session is the user's web session on the new instance
+    let current = target
+        .get_or_create_session_api("mover", "web")
+        .await
+        .unwrap();
+    assert_eq!(current.id, imported.id);
+
+    let original = source_gateway
+        .storage
+        .get_messages(&session.id, None)
+        .await
+        .unwrap();
+    let copied = target_gateway
+        .storage
+        .get_messages(&imported.id, None)
+        .await
+        .unwrap();
+    assert_eq!(copied.len(), original.len());
+    for (copy, message) in copied.iter().zip(&original) {
+        assert_ne!(copy.id, message.id);
+        assert_eq!(copy.role, message.role);
+        assert_eq!(copy.content, message.content);
+        assert_eq!(copy.created_at, message.created_at);
+        assert_eq!(copy.model_used, message.model_used);
+        assert_eq!(copy.tokens, message.tokens);
+        assert_eq!(copy.metadata, message.metadata);
+    }
+    let notes = target_gateway
+        .storage
+        .list_session_notes(&imported.id)
+        .await
+        .unwrap();
+    assert_eq!(notes.len(), 1);
+    assert_eq!(notes[0].content, "Answer in French");
+
+    // Importing again neither reuses ids nor shadows the existing session
+    let (_, response) = import("mover", archive.clone())
+        .await
+        .expect("Second import failed");
+    let again = response.0.data.unwrap();
+    assert_ne!(again.id, imported.id);
+    assert_eq!(again.scope, format!("import:{}", again.id));
+    assert_eq!(
+        target
+            .get_or_create_session_api("mover", "web")
+            .await
+            .unwrap()
+            .id,
+        imported.id
+    );
+
+    // Imports count towards the session limit and each gets its own scope
+    target.config().write().await.sessions.max_per_user = Some(2);
+    let (_, response) = import("mover", archive.clone())
+        .await
+        .expect("Third import failed");
+    let third = response.0.data.unwrap();
+    assert_eq!(third.scope, format!("import:{}", third.id));
+    let sessions = target_gateway
+        .storage
+        .list_user_sessions("mover")
+        .await
+        .unwrap();
+    assert_eq!(sessions.len(), 2);
+    assert!(sessions.iter().any(|s| s.id == imported.id));
+    assert!(sessions.iter().any(|s| s.id == third.id));
+
+    // Imported messages are masked like any other stored message
+    target.config().write().await.redaction = rustyclaw::config::RedactionConfig {
+        enabled: true,
+        replacement: "[PII]".to_string(),
+        ..Default::default()
+    };
+    let mut personal = archive.clone();
+    personal.messages[0].content = "Email me at alice@example.com".to_string();
+    let (_, response) = import("pii-mover", personal)
+        .await
+        .expect("Redacted import failed");
+    let redacted = response.0.data.unwrap();
+    let copied = target_gateway
+        .storage
+        .get_messages(&redacted.id, None)
+        .await
+        .unwrap();
+    assert_eq!(copied[0].content, "Email me at [PII]");
+
+    // Other users cannot archive the session and malformed bundles are refused
+    assert!(matches!(
+        archive_session(
+            State(source.clone()),
+            Extension(AuthUserId("intruder".to_string())),
+            Path(session.id.clone()),
+        )
+        .await
+        .unwrap_err(),
+        ApiError::NotFound(_)
+    ));
+    let mut future = archive.clone();
+    future.version += 1;
+    assert!(matches!(
+        import("mover", future).await.unwrap_err(),
+        ApiError::BadRequest(_)
+    ));
+    let mut duplicated = archive;
+    let first = duplicated.messages[0].clone();
+    duplicated.messages.push(first);
+    assert!(matches!(
+        import("mover", duplicated).await.unwrap_err(),
+        ApiError::BadRequest(_)
+    ));
+}
+
+#[tokio::test]
+async fn test_tool_stats_aggregate_audit_log() {
+    use axum::extract::{Query, State};
+    use rustyclaw::api::stats::{tool_stats, ToolStatsQuery};
+    use rustyclaw::api::ApiError;
+    use rustyclaw::storage::ToolExecution;
+
+    let (router, gateway) = test_router("http://127.0.0.1:9", |_| {}).await;
+    let router = Arc::new(router);
+    let alice = router
+        .get_or_create_session_api("alice", "web")
+        .await
+        .unwrap();
+    let bob = router
+        .get_or_create_session_api("bob", "web")
+        .await
+        .unwrap();
+
+    let now = chrono::Utc::now();
+    let calls = [
+        (&alice.id, "exec", true, Some(10), now),
+        (&alice.id, "exec", true, Some(400), now),
+        (&alice.id, "exec", false, Some(20), now),
+        (&alice.id, "exec", true, Some(30), now),
+        (&bob.id, "calculate", true, None, now),
+        (
+            &bob.id,
+            "calculate",
+            true,
+            Some(5),
+            now - chrono::Duration::days(30),
+        ),
+    ];
+    for (i, (session_id, tool, success, duration_ms, created_at)) in calls.into_iter().enumerate() {
+        gateway
+            .storage
+            .add_tool_execution(ToolExecution {
+                id: format!("call-{}", i),
+                session_id: session_id.clone(),
+                tool_name: tool.to_string(),
+                arguments: "{}".to_string(),
+                output: String::new(),
+                success,
+                duration_ms,
+                created_at,
+            })
+            .await
+            .unwrap();
+    }
+
+    let stats = |query: ToolStatsQuery| {
+        let router = router.clone();
+        async move { tool_stats(State(router), Query(query)).await }
+    };
+
+    let day = stats(ToolStatsQuery {
+        window_hours: Some(24),
+        top: None,
+    })
+    .await
+    .unwrap()
+    .0
+    .data
+    .unwrap();
+    assert_eq!(day.total_calls, 5);
+    let tool_names: Vec<&str> = day.tools.iter().map(|t| t.tool_name.as_str()).collect();
+    assert_eq!(tool_names, vec!["calculate", "exec"]);
+
+    let exec = &day.tools[1];
+    assert_eq!((exec.calls, exec.successes), (4, 3));
+    assert!((exec.success_rate - 0.75).abs() < f64::EPSILON);
+    assert_eq!(exec.median_ms, Some(20));
+    assert_eq!(exec.p95_ms, Some(400));
+
+    let calculate = &day.tools[0];
+    assert_eq!(calculate.calls, 1);
+    assert_eq!(calculate.median_ms, None);
+
+    assert_eq!(day.top_tools, vec!["exec", "calculate"]);
+    assert_eq!(day.top_users[0].user_id, "alice");
+    assert_eq!(day.top_users[0].calls, 4);
+    assert_eq!(day.top_users[1].calls, 1);
+
+    // A wider window reaches older calls; `top` trims the rankings
+    let quarter = stats(ToolStatsQuery {
+        window_hours: Some(24 * 90),
+        top: Some(1),
+    })
+    .await
+    .unwrap()
+    .0
+    .data
+    .unwrap();
+    assert_eq!(quarter.total_calls, 6);
+    assert_eq!(quarter.tools[0].calls, 2);
+    assert_eq!(quarter.top_tools, vec!["exec"]);
+    assert_eq!(quarter.top_users.len(), 1);
+
+    assert!(matches!(
+        stats(ToolStatsQuery {
+            window_hours: Some(0),
+            top: None,
+        })
+        .await
+        .unwrap_err(),
+        ApiError::BadRequest(_)
+    ));
+}
+
+#[tokio::test]
+async fn test_paused_channel_does_not_call_the_model() {
+    use axum::extract::{Path, Query, State};
+    use rustyclaw::api::routes::{pause_channel, resume_channel, PauseChannelQuery};
+    use rustyclaw::api::ApiError;
+
+    let mut server = mockito::Server::new_async().await;
+    let mock = server
+        .mock("POST", "/chat/completions")
+        .with_header("content-type", "application/json")
+        .with_body(
+            r#"{"id": "chatcmpl-1", "object": "chat.completion", "created": 1700000000,
+                "model": "pause-model",
+                "choices": [{"index": 0, "finish_reason": "stop",
+                             "message": {"role": "assistant", "content": "Back again"}}]}"#,
+        )
+        .expect(1)
+        .create_async()
+        .await;
+
+    let (router, gateway) = test_router(&server.url(), |_| {}).await;
+    let router = Arc::new(router);
+    let paused = pause_channel(
+        State(router.clone()),
+        Path("whatsapp".to_string()),
+        Query(PauseChannelQuery { persist: true }),
+    )
+    .await
+    .expect("Pause failed");
+    assert_eq!(paused.0.data.unwrap()["paused"], true);
+
+    let response = router
+        .handle_message("whatsapp:main:15551234", "whatsapp", "Are you there?")
+        .await
+        .unwrap();
+    assert!(response.content.is_empty());
+    let mut events = router
+        .handle_message_stream("whatsapp:main:15551234", "whatsapp", "Hello?")
+        .await
+        .unwrap();
+    while let Some(event) = events.recv().await {
+        assert!(
+            !matches!(event, StreamEvent::Delta(_)),
+            "Paused channel replied"
+        );
+    }
+    let session = router
+        .get_or_create_session_api("whatsapp:main:15551234", "whatsapp")
+        .await
+        .unwrap();
+    assert!(router
+        .get_session_messages(&session.id)
+        .await
+        .unwrap()
+        .is_empty());
+
+    // A persisted pause survives a restart; other channels keep working
+    let restarted = Arc::new(gateway.router().await);
+    assert!(restarted.is_channel_paused("whatsapp").await);
+    assert!(!restarted.is_channel_paused("telegram").await);
+
+    let resumed = resume_channel(State(restarted.clone()), Path("whatsapp".to_string()))
+        .await
+        .expect("Resume failed");
+    assert_eq!(resumed.0.data.unwrap()["paused"], false);
+    let response = restarted
+        .handle_message("whatsapp:main:15551234", "whatsapp", "Are you there?")
+        .await
+        .unwrap();
+    assert_eq!(response.content, "Back again");
+    mock.assert_async().await;
+    assert!(gateway
+        .storage
+        .list_paused_channels()
+        .await
+        .unwrap()
+        .is_empty());
+
+    assert!(matches!(
+        resume_channel(State(restarted), Path("carrier-pigeon".to_string()))
+            .await
+            .unwrap_err(),
+        ApiError::NotFound(_)
+    ));
+}
+
+/// A turn failing on a locked database is retried without storing the
+/// user's message twice
+#[tokio::test]
+async fn test_transient_storage_failure_retries_the_turn() {
+    let dir = tempfile::tempdir().unwrap();
+    let db_path = dir.path().join("flaky.db");
+    std::fs::File::create(&db_path).unwrap();
+    let storage = SqliteStorage::new(&db_path)
+        .await
+        .expect("Failed to create storage");
+
+    // The first assistant reply written fails as if the database were
+    // locked; sqlx re-steps a failed statement, so it keeps failing for that
+    // message id
+    let pool = sqlx::SqlitePool::connect(&format!("sqlite://{}", db_path.display()))
+        .await
+        .unwrap();
+    for statement in [
+        "CREATE TABLE failed_writes (id TEXT PRIMARY KEY)",
+        "CREATE TRIGGER fail_once BEFORE INSERT ON messages
+         WHEN NEW.role = 'assistant' AND (NOT EXISTS (SELECT 1 FROM failed_writes)
+             OR NEW.id IN (SELECT id FROM failed_writes))
+         BEGIN
+             INSERT OR IGNORE INTO failed_writes VALUES (NEW.id);
+             SELECT RAISE(FAIL, 'database is locked');
+         END",
+    ] {
+        sqlx::query(statement).execute(&pool).await.unwrap();
+    }
+
+    let mut server = mockito::Server::new_async().await;
+    let mock = server
+        .mock("POST", "/chat/completions")
+        .with_header("content-type", "application/json")
+        .with_body(
+            r#"{"id": "chatcmpl-1", "object": "chat.completion", "created": 1700000000,
+                "model": "retry-model",
+                "choices": [{"index": 0, "finish_reason": "stop",
+                             "message": {"role": "assistant", "content": "Got it"}}]}"#,
+        )
+        .expect(2)
+        .create_async()
+        .await;
+
+    let gateway = TestGateway::new(&server.url(), |config| {
+        config.sessions.retry = rustyclaw::config::RetryConfig {
+            max_attempts: 3,
+            initial_backoff_ms: 1,
+            max_backoff_ms: 5,
+        };
+    })
+    .await;
+
+    let router = Router::new(gateway.config.clone(), storage, gateway.llm_client.clone()).await;
+    let response = router
+        .handle_message("user-retry", "web", "Remember this")
+        .await
+        .expect("Turn was not retried");
+    assert_eq!(response.content, "Got it");
+    mock.assert_async().await;
+
+    let session = router
+        .get_or_create_session_api("user-retry", "web")
+        .await
+        .unwrap();
+    let roles: Vec<String> = router
+        .get_session_messages(&session.id)
+        .await
+        .unwrap()
+        .into_iter()
+        .map(|message| message.role)
+        .collect();
+    assert_eq!(roles, ["user", "assistant"]);
+}
+
+/// A turn failing after its tools ran is reported instead of retried, so the
+/// tools don't run twice
+#[tokio::test]
+async fn test_failure_after_tool_calls_is_not_retried() {
+    // The model asks for a calculation, then times out answering
+    let mut server = mockito::Server::new_async().await;
+    let timeout = server
+        .mock("POST", "/chat/completions")
+        .match_body(mockito::Matcher::Regex("Tool calculate result".to_string()))
+        .with_status(504)
+        .expect(1)
+        .create_async()
+        .await;
+    let tool_call = server
+        .mock("POST", "/chat/completions")
+        .with_header("content-type", "application/json")
+        .with_body(
+            r#"{"id": "chatcmpl-1", "object": "chat.completion", "created": 1700000000,
+                "model": "retry-model",
+                "choices": [{"index": 0, "finish_reason": "tool_calls",
+                             "message": {"role": "assistant", "content": null,
+                                         "tool_calls": [{"id": "call_1", "type": "function",
+                                             "function": {"name": "calculate",
+                                                          "arguments": "{\"expression\": \"6 * 7\"}"}}]}}]}"#,
+        )
+        .expect(1)
+        .create_async()
+        .await;
+
+    let (router, gateway) = test_router(&server.url(), |config| {
+        config.llm.models.primary = "retry-model".to_string();
+        config.sessions.retry = rustyclaw::config::RetryConfig {
+            max_attempts: 3,
+            initial_backoff_ms: 1,
+            max_backoff_ms: 5,
+        };
+    })
+    .await;
+
+    router
+        .handle_message("user-tools", "web", "What is 6 times 7?")
+        .await
+        .expect_err("Answer should have timed out");
+    tool_call.assert_async().await;
+    timeout.assert_async().await;
+
+    let executions = gateway
+        .storage
+        .list_tool_executions("user-tools", "calculate", 10)
+        .await
+        .unwrap();
+    assert_eq!(executions.len(), 1);
+}
+
+/// A span seen by `SpanRecorder`: name, parent name and recorded fields
+type RecordedSpan = (
+    String,
+    Option<String>,
+    std::collections::HashMap<String, String>,
+);
+
+#[derive(Clone, Default)]
+struct RecordedSpans(Arc<std::sync::Mutex<Vec<RecordedSpan>>>);
+
+/// Layer recording every span with its parent, to check the span tree
+struct SpanRecorder(RecordedSpans);
+
+struct FieldVisitor<'a>(&'a mut std::collections::HashMap<String, String>);
+
+impl tracing::field::Visit for FieldVisitor<'_> {
+    fn record_debug(&mut self, field: &tracing::field::Field, value: &dyn std::fmt::Debug) {
+        self.0.insert(
+            field.name().to_string(),
+            format!("{:?}", value).trim_matches('"').to_string(),
+        );
+    }
+}
+
+impl<S> tracing_subscriber::Layer<S> for SpanRecorder
+where
+    S: tracing::Subscriber + for<'a> tracing_subscriber::registry::LookupSpan<'a>,
+{
+    fn on_new_span(
+        &self,
+        attrs: &tracing::span::Attributes<'_>,
+        id: &tracing::span::Id,
+        ctx: tracing_subscriber::layer::Context<'_, S>,
+    ) {
+        let span = ctx.span(id).unwrap();
+        let parent = span.parent().map(|parent| parent.name().to_string());
+        let mut fields = std::collections::HashMap::new();
+        attrs.record(&mut FieldVisitor(&mut fields));
+        let mut spans = self.0 .0.lock().unwrap();
+        spans.push((span.name().to_string(), parent, fields));
+        span.extensions_mut().insert(spans.len() - 1);
+    }
+
+    fn on_record(
+        &self,
+        id: &tracing::span::Id,
+        values: &tracing::span::Record<'_>,
+        ctx: tracing_subscriber::layer::Context<'_, S>,
+    ) {
+        let span = ctx.span(id).unwrap();
+        let index = *span.extensions().get::<usize>().unwrap();
+        values.record(&mut FieldVisitor(&mut self.0 .0.lock().unwrap()[index].2));
+    }
+}
+
+#[tokio::test]
+async fn test_request_span_parents_llm_and_tool_spans() {
+    use tracing_subscriber::layer::SubscriberExt;
+
+    // The model asks for a calculation, then answers with its result
+    let mut server = mockito::Server::new_async().await;
+    let answer = server
+        .mock("POST", "/chat/completions")
+        .match_body(mockito::Matcher::Regex("Tool calculate result".to_string()))
+        .with_header("content-type", "application/json")
+        .with_body(
+            r#"{"id": "chatcmpl-2", "object": "chat.completion", "created": 1700000000,
+                "model": "span-model",
+                "choices": [{"index": 0, "finish_reason": "stop",
+                             "message": {"role": "assistant", "content": "It is 42"}}],
+                "usage": {"prompt_tokens": 30, "completion_tokens": 4, "total_tokens": 34}}"#,
+        )
+        .create_async()
+        .await;
+    let tool_call = server
+        .mock("POST", "/chat/completions")
+        .with_header("content-type", "application/json")
+        .with_body(
+            r#"{"id": "chatcmpl-1", "object": "chat.completion", "created": 1700000000,
+                "model": "span-model",
+                "choices": [{"index": 0, "finish_reason": "tool_calls",
+                             "message": {"role": "assistant", "content": null,
+                                         "tool_calls": [{"id": "call_1", "type": "function",
+                                             "function": {"name": "calculate",
+                                                          "arguments": "{\"expression\": \"6 * 7\"}"}}]}}],
+                "usage": {"prompt_tokens": 20, "completion_tokens": 8, "total_tokens": 28}}"#,
+        )
+        .create_async()
+        .await;
+
+    let (router, _gateway) = test_router(&server.url(), |config| {
+        config.llm.models.primary = "span-model".to_string();
+    })
+    .await;
+
+    let spans = RecordedSpans::default();
+    let subscriber = tracing_subscriber::registry().with(SpanRecorder(spans.clone()));
+    let _guard = tracing::subscriber::set_default(subscriber);
+
+    let response = router
+        .handle_message("alice", "web", "What is 6 times 7?")
+        .await
+        .unwrap();
+    assert_eq!(response.content, "It is 42");
+    tool_call.assert_async().await;
+    answer.assert_async().await;
+
+    let spans = spans.0.lock().unwrap();
+    let (_, parent, request) = spans.iter().find(|(name, ..)| name == "request").unwrap();
+    assert_eq!(parent, &None);
+    assert_eq!(request["user_id"], "alice");
+    assert_eq!(request["channel"], "web");
+    assert!(!request["request_id"].is_empty());
+    assert!(request.contains_key("session_id"));
+
+    let llm_calls: Vec<_> = spans
+        .iter()
+        .filter(|(name, ..)| name == "llm.chat")
+        .collect();
+    assert_eq!(llm_calls.len(), 2);
+    for (_, parent, fields) in &llm_calls {
+        assert_eq!(parent.as_deref(), Some("request"));
+        assert_eq!(fields["model"], "span-model");
+    }
+    assert_eq!(llm_calls[0].2["completion_tokens"], "8");
+
+    let (_, parent, tool) = spans.iter().find(|(name, ..)| name == "tool").unwrap();
+    assert_eq!(parent.as_deref(), Some("request"));
+    assert_eq!(tool["tool"], "calculate");
+    assert_eq!(tool["success"], "true");
+    assert!(tool.contains_key("duration_ms"));
+}
+
+#[tokio::test]
+async fn test_session_context_reflects_the_context_window() {
+    use axum::extract::{Path, State};
+    use axum::Extension;
+    use rustyclaw::api::routes::get_session_context;
+    use rustyclaw::api::{ApiError, AuthUserId};
+
+    let (router, gateway) = test_router("http://127.0.0.1:9", |config| {
+        config.llm.models.primary = "context-model".to_string();
+        config.llm.context_windows =
+            std::collections::HashMap::from([("context-model".to_string(), 100_000)]);
+        config.llm.tool_support =
+            std::collections::HashMap::from([("context-model".to_string(), true)]);
+    })
+    .await;
+    let router = Arc::new(router);
+    let session = router
+        .get_or_create_session_api("inspector", "web")
+        .await
+        .unwrap();
+    for i in 0..60 {
+        gateway
+            .storage
+            .add_message(StorageMessage {
+                id: format!("msg-{}", i),
+                session_id: session.id.clone(),
+                role: if i % 2 == 0 { "user" } else { "assistant" }.to_string(),
+                content: format!("message number {}", i),
+                created_at: chrono::Utc::now() + chrono::Duration::seconds(i),
+                model_used: None,
+                tokens: None,
+                metadata: None,
+            })
+            .await
+            .unwrap();
+    }
+
+    let context = |user: &str| {
+        get_session_context(
+            State(router.clone()),
+            Extension(AuthUserId(user.to_string())),
+            Path(session.id.clone()),
+        )
+    };
+
+    let preview = context("inspector").await.unwrap().0.data.unwrap();
+    assert_eq!(preview.model, "context-model");
+    assert_eq!(preview.context_window, Some(100_000));
+    assert!(preview.fits);
+    assert!(!preview.tools.is_empty());
+    // The system prompt, then the last 50 messages of the history
+    assert_eq!(preview.messages.len(), 51);
+    assert_eq!(preview.messages[0].role, "system");
+    assert_eq!(preview.messages[1].content, "message number 10");
+    assert_eq!(preview.messages[50].content, "message number 59");
+    let message_tokens: usize = preview.messages.iter().map(|m| m.tokens).sum();
+    let tool_tokens: usize = preview.tools.iter().map(|t| t.tokens).sum();
+    assert_eq!(preview.total_tokens, message_tokens + tool_tokens);
+
+    // A smaller window no longer fits; compaction would run first if enabled
+    gateway
+        .config
+        .write()
+        .await
+        .llm
+        .context_windows
+        .insert("context-model".to_string(), preview.total_tokens - 1);
+    let preview = context("inspector").await.unwrap().0.data.unwrap();
+    assert!(!preview.fits);
+    assert!(!preview.compaction_pending);
+    gateway.config.write().await.sessions.compaction_enabled = true;
+    let preview = context("inspector").await.unwrap().0.data.unwrap();
+    assert!(preview.compaction_pending);
+
+    assert!(matches!(
+        context("intruder").await.unwrap_err(),
+        ApiError::NotFound(_)
+    ));
+}
+
+#[tokio::test]
+async fn test_dropping_stream_consumer_cancels_running_tool() {
+    let dir = tempfile::tempdir().unwrap();
+    let marker = dir.path().join("finished");
+
+    // The model runs a slow script that leaves a marker once it completes
+    let arguments = serde_json::json!({
+        "script": format!("sleep 2 && touch {}", marker.display())
+    })
+    .to_string();
+    let chunk = |delta: serde_json::Value, finish_reason: Option<&str>| {
+        let chunk = serde_json::json!({
+            "id": "chatcmpl-1", "object": "chat.completion.chunk", "created": 1700000000,
+            "model": "slow-model",
+            "choices": [{"index": 0, "delta": delta, "finish_reason": finish_reason}]
+        });
+        format!("data: {}\n\n", chunk)
+    };
+    let body = chunk(
+        serde_json::json!({"role": "assistant", "tool_calls": [{
+            "index": 0, "id": "call_1", "type": "function",
+            "function": {"name": "bash", "arguments": arguments}
+        }]}),
+        None,
+    ) + &chunk(serde_json::json!({}), Some("tool_calls"))
+        + "data: [DONE]\n\n";
+
+    let mut server = mockito::Server::new_async().await;
+    let _mock = server
+        .mock("POST", "/chat/completions")
+        .with_header("content-type", "text/event-stream")
+        .with_body(body)
+        .create_async()
+        .await;
+
+    let (router, _gateway) = test_router(&server.url(), |config| {
+        config.llm.context_windows =
+            std::collections::HashMap::from([("slow-model".to_string(), 100_000)]);
+        config.llm.tool_support =
+            std::collections::HashMap::from([("slow-model".to_string(), true)]);
+        config.tools = rustyclaw::config::ToolsConfig {
+            policies: std::collections::HashMap::from([("bash".to_string(), "allow".to_string())]),
+            ..Default::default()
+        };
+    })
+    .await;
+
+    let mut receiver = router
+        .handle_message_stream_with_context(
+            "leaver",
+            "web",
+            "Run the slow job",
+            &[],
+            &Default::default(),
+        )
+        .await
+        .unwrap();
+    loop {
+        match tokio::time::timeout(std::time::Duration::from_secs(10), receiver.recv())
+            .await
+            .expect("tool never started")
+        {
+            Some(StreamEvent::ToolStart { name, .. }) => {
+                assert_eq!(name, "bash");
+                break;
+            }
+            Some(StreamEvent::Error(e)) => panic!("stream failed: {}", e),
+            Some(_) => continue,
+            None => panic!("stream ended before the tool started"),
+        }
+    }
+
+    // The client goes away while the script is still sleeping
+    drop(receiver);
+    tokio::time::sleep(std::time::Duration::from_secs(3)).await;
+    assert!(
+        !marker.exists(),
+        "the tool kept running after the consumer was dropped"
+    );
+}
+
+#[tokio::test]
+async fn test_maintenance_mode_refuses_messages_until_cleared() {
+    use axum::extract::{Query, State};
+    use axum::Extension;
+    use rustyclaw::api::routes::{list_messages, start_maintenance, MessageQuery};
+    use rustyclaw::api::AuthUserId;
+    use rustyclaw::core::maintenance::MaintenanceActive;
+
+    let mut server = mockito::Server::new_async().await;
+    let mock = server
+        .mock("POST", "/chat/completions")
+        .with_header("content-type", "application/json")
+        .with_body(
+            r#"{"id": "chatcmpl-1", "object": "chat.completion", "created": 1700000000,
+                "model": "maintenance-model",
+                "choices": [{"index": 0, "finish_reason": "stop",
+                             "message": {"role": "assistant", "content": "Upgraded"}}]}"#,
+        )
+        .expect(1)
+        .create_async()
+        .await;
+
+    let (router, gateway) = test_router(&server.url(), |_| {}).await;
+    let router = Arc::new(router);
+    let session = router
+        .get_or_create_session_api("ops", "web")
+        .await
+        .unwrap();
+    gateway
+        .storage
+        .add_message(StorageMessage {
+            id: "m1".to_string(),
+            session_id: session.id.clone(),
+            role: "user".to_string(),
+            content: "Before the upgrade".to_string(),
+            created_at: chrono::Utc::now(),
+            model_used: None,
+            tokens: None,
+            metadata: None,
+        })
+        .await
+        .unwrap();
+
+    start_maintenance(State(router.clone()))
+        .await
+        .expect("Entering maintenance failed");
+
+    // Generations are refused before reaching the model or storage
+    let err = router
+        .handle_message("ops", "web", "Are you there?")
+        .await
+        .unwrap_err();
+    assert!(err.downcast_ref::<MaintenanceActive>().is_some());
+    assert!(router
+        .handle_message_stream("ops", "telegram", "Hello?")
+        .await
+        .is_err());
+
+    // The maintenance mode survives a restart, and history stays readable
+    let restarted = Arc::new(gateway.router().await);
+    assert!(restarted.in_maintenance());
+    let messages = list_messages(
+        State(restarted.clone()),
+        Extension(AuthUserId("ops".to_string())),
+        Query(MessageQuery {
+            limit: None,
+            offset: None,
+        }),
+    )
+    .await
+    .expect("Reads failed in maintenance mode");
+    assert_eq!(messages.0.data.unwrap().messages.len(), 1);
+
+    restarted.set_maintenance(false).await.unwrap();
+    assert!(!gateway.storage.get_maintenance().await.unwrap());
+    let response = restarted
+        .handle_message("ops", "web", "Are you there?")
+        .await
+        .unwrap();
+    assert_eq!(response.content, "Upgraded");
+    mock.assert_async().await;
+}
+
+/// A reply interrupted before `Done` leaves a draft that the next startup
+/// turns into a partial assistant message
+#[tokio::test]
+async fn test_interrupted_stream_draft_is_recovered_on_startup() {
+    // The model starts answering, then hangs like a process about to crash
+    let mut server = mockito::Server::new_async().await;
+    let _mock = server
+        .mock("POST", "/chat/completions")
+        .with_header("content-type", "text/event-stream")
+        .with_chunked_body(|w| {
+            let chunk = serde_json::json!({
+                "id": "chatcmpl-1", "object": "chat.completion.chunk", "created": 1700000000,
+                "model": "draft-model",
+                "choices": [{"index": 0, "delta": {"role": "assistant", "content": "Partial answer"},
+                             "finish_reason": null}]
+            });
+            w.write_all(format!("data: {}\n\n", chunk).as_bytes())?;
+            w.flush()?;
+            std::thread::sleep(std::time::Duration::from_secs(5));
+            Ok(())
+        })
+        .create_async()
+        .await;
+
+    let (router, gateway) = test_router(&server.url(), |config| {
+        config.llm.models.primary = "draft-model".to_string();
+        config.sessions.drafts = rustyclaw::config::DraftsConfig {
+            enabled: true,
+            every_chunks: 1,
+            interval_ms: 0,
+            on_startup: rustyclaw::config::DraftRecovery::Promote,
+        };
+    })
+    .await;
+    let mut receiver = router
+        .handle_message_stream("crasher", "web", "Tell me everything")
+        .await
+        .unwrap();
+    loop {
+        match tokio::time::timeout(std::time::Duration::from_secs(10), receiver.recv())
+            .await
+            .expect("no reply started")
+        {
+            Some(StreamEvent::Delta(delta)) => {
+                assert_eq!(delta, "Partial answer");
+                break;
+            }
+            Some(StreamEvent::Error(e)) => panic!("stream failed: {}", e),
+            Some(StreamEvent::Done { .. }) => panic!("the reply finished"),
+            Some(_) => continue,
+            None => panic!("stream ended before the reply started"),
+        }
+    }
+
+    // The draft is saved before the chunk reaches the client
+    let drafts = gateway.storage.list_drafts().await.unwrap();
+    assert_eq!(drafts.len(), 1);
+    assert_eq!(drafts[0].content, "Partial answer");
+
+    // The gateway dies without `Done`; the next start recovers the reply
+    drop(receiver);
+    let restarted = gateway.router().await;
+    let session = restarted
+        .get_or_create_session_api("crasher", "web")
+        .await
+        .unwrap();
+    let messages = restarted.get_session_messages(&session.id).await.unwrap();
+    let roles: Vec<&str> = messages.iter().map(|m| m.role.as_str()).collect();
+    assert_eq!(roles, ["user", "assistant"]);
+    assert_eq!(messages[1].content, "Partial answer");
+    assert_eq!(messages[1].model_used.as_deref(), Some("draft-model"));
+    assert_eq!(
+        messages[1].metadata,
+        Some(serde_json::json!({ "partial": true }))
+    );
+    assert!(gateway.storage.list_drafts().await.unwrap().is_empty());
+}
+
+/// `/temp` and `/persona` change the requests of a session until `/reset`,
+/// without reaching the model themselves
+#[tokio::test]
+async fn test_session_commands_override_temperature_and_persona() {
+    let mut server = mockito::Server::new_async().await;
+    let mock = server
+        .mock("POST", "/chat/completions")
+        .match_body(mockito::Matcher::AllOf(vec![
+            mockito::Matcher::Regex(r#""temperature":0.2"#.to_string()),
+            mockito::Matcher::Regex("## Reply Style".to_string()),
+            mockito::Matcher::Regex("Answer as briefly as possible".to_string()),
+        ]))
+        .with_header("content-type", "application/json")
+        .with_body(
+            r#"{"id": "chatcmpl-1", "object": "chat.completion", "created": 1700000000,
+                "model": "tuned-model",
+                "choices": [{"index": 0, "finish_reason": "stop",
+                             "message": {"role": "assistant", "content": "Paris."}}]}"#,
+        )
+        .expect(1)
+        .create_async()
+        .await;
+
+    let (router, _gateway) = test_router(&server.url(), |_| {}).await;
+
+    let reply = router
+        .handle_message("tuner", "web", "/temp 3")
+        .await
+        .unwrap();
+    assert!(reply.content.starts_with("Usage: /temp"));
+    let reply = router
+        .handle_message("tuner", "web", "/temp 0.2")
+        .await
+        .unwrap();
+    assert_eq!(reply.content, "Temperature set to 0.2 for this session.");
+    assert_eq!(reply.model, "command");
+    let mut events = router
+        .handle_message_stream("tuner", "web", "/persona concise")
+        .await
+        .unwrap();
+    let Some(StreamEvent::Delta(text)) = events.recv().await else {
+        panic!("no reply to /persona");
+    };
+    assert_eq!(text, "Persona set to concise for this session.");
+
+    let reply = router
+        .handle_message("tuner", "web", "What is the capital of France?")
+        .await
+        .unwrap();
+    assert_eq!(reply.content, "Paris.");
+    mock.assert_async().await;
+
+    let stats = router
+        .handle_message("tuner", "web", "/stats")
+        .await
+        .unwrap();
+    assert!(stats.content.contains("Messages: 2"));
+    assert!(stats.content.contains("Temperature: 0.2"));
+    assert!(stats.content.contains("Persona: concise"));
+
+    // Commands are answered, never stored
+    let session = router
+        .get_or_create_session_api("tuner", "web")
+        .await
+        .unwrap();
+    assert_eq!(
+        router
+            .get_session_messages(&session.id)
+            .await
+            .unwrap()
+            .len(),
+        2
+    );
+
+    router
+        .handle_message("tuner", "web", "/reset")
+        .await
+        .unwrap();
+    let stats = router.get_session_stats("tuner", "web").await.unwrap();
+    assert_eq!(stats.total_messages, 0);
+    assert_eq!(stats.temperature, None);
+    assert_eq!(stats.persona, None);
+}
+
+/// An empty answer is replaced by the configured fallback, both when it is
+/// returned and when it is streamed
+#[tokio::test]
+async fn test_empty_reply_is_replaced_by_the_fallback() {
+    // Answers every request with an empty completion, even `stream: true`
+    let mut server = mockito::Server::new_async().await;
+    let _mock = server
+        .mock("POST", "/chat/completions")
+        .with_header("content-type", "application/json")
+        .with_body(
+            r#"{"id": "chatcmpl-1", "object": "chat.completion", "created": 1700000000,
+                "model": "quiet-model",
+                "choices": [{"index": 0, "finish_reason": "stop",
+                             "message": {"role": "assistant", "content": ""}}]}"#,
+        )
+        .create_async()
+        .await;
+
+    let (router, _gateway) = test_router(&server.url(), |config| {
+        config.prompt = rustyclaw::config::PromptConfig {
+            empty_reply: Some("Sorry, I drew a blank.".to_string()),
+            ..Default::default()
+        };
+    })
+    .await;
+
+    let response = router
+        .handle_message("blank", "web", "Say something")
+        .await
+        .unwrap();
+    assert_eq!(response.content, "Sorry, I drew a blank.");
+
+    let mut events = router
+        .handle_message_stream("blank", "web", "Say something else")
+        .await
+        .unwrap();
+    let mut streamed = String::new();
+    while let Some(event) = events.recv().await {
+        if let StreamEvent::Delta(text) = event {
+            streamed.push_str(&text);
+        }
+    }
+    assert_eq!(streamed, "Sorry, I drew a blank.");
+
+    let session = router
+        .get_or_create_session_api("blank", "web")
+        .await
+        .unwrap();
+    let replies: Vec<String> = router
+        .get_session_messages(&session.id)
+        .await
+        .unwrap()
+        .into_iter()
+        .filter(|m| m.role == "assistant")
+        .map(|m| m.content)
+        .collect();
+    assert_eq!(
+        replies,
+        ["Sorry, I drew a blank.", "Sorry, I drew a blank."]
+    );
+}
+
+/// Reply filters rewrite the stored and returned reply; a streamed reply is
+/// corrected by the `Done` event
+#[tokio::test]
+async fn test_reply_filters_apply_to_both_reply_paths() {
+    // Answers every request with leftover reasoning, even `stream: true`
+    let mut server = mockito::Server::new_async().await;
+    let _mock = server
+        .mock("POST", "/chat/completions")
+        .with_header("content-type", "application/json")
+        .with_body(
+            r#"{"id": "chatcmpl-1", "object": "chat.completion", "created": 1700000000,
+                "model": "thinking-model",
+                "choices": [{"index": 0, "finish_reason": "stop",
+                             "message": {"role": "assistant",
+                                         "content": "<think>Greet them.</think>\n\nHello!"}}]}"#,
+        )
+        .create_async()
+        .await;
+
+    let (router, _gateway) = test_router(&server.url(), |config| {
+        config.prompt = rustyclaw::config::PromptConfig {
+            reply_filters: vec![
+                rustyclaw::config::ReplyFilter::StripThinking,
+                rustyclaw::config::ReplyFilter::RegexReplace {
+                    pattern: "Hello".to_string(),
+                    replacement: "Hi".to_string(),
+                },
+            ],
+            ..Default::default()
+        };
+    })
+    .await;
+
+    let response = router
+        .handle_message("thinker", "web", "Say hello")
+        .await
+        .unwrap();
+    assert_eq!(response.content, "Hi!");
+
+    let mut events = router
+        .handle_message_stream("thinker", "web", "Say hello again")
+        .await
+        .unwrap();
+    let mut streamed = String::new();
+    let mut replacement = None;
+    while let Some(event) = events.recv().await {
+        match event {
+            StreamEvent::Delta(text) => streamed.push_str(&text),
+            StreamEvent::Done { content, .. } => replacement = content,
+            _ => {}
+        }
+    }
+    assert!(streamed.contains("<think>"), "{}", streamed);
+    assert_eq!(replacement.as_deref(), Some("Hi!"));
+
+    let session = router
+        .get_or_create_session_api("thinker", "web")
+        .await
+        .unwrap();
+    let replies: Vec<String> = router
+        .get_session_messages(&session.id)
+        .await
+        .unwrap()
+        .into_iter()
+        .filter(|m| m.role == "assistant")
+        .map(|m| m.content)
+        .collect();
+    assert_eq!(replies, ["Hi!", "Hi!"]);
+}
+
+/// Idle sessions are deleted as of an injected time; active, pinned and
+/// shared sessions survive
+#[tokio::test]
+async fn test_idle_sessions_expire() {
+    use chrono::{Duration, Utc};
+    use rustyclaw::core::events::{subscribe, SystemEvent};
+    use rustyclaw::storage::{Session, SessionNote};
+
+    let (session_manager, gateway) = test_session_manager("http://127.0.0.1:9", |config| {
+        config.sessions.expiry = rustyclaw::config::SessionExpiryConfig {
+            enabled: true,
+            ttl_hours: 24,
+            check_interval_minutes: 60,
+        };
+    })
+    .await;
+
+    // The clock the sessions are checked against
+    let now = Utc::now() + Duration::days(10);
+    for (id, channel, last_active) in [
+        ("idle", "web", now - Duration::hours(25)),
+        ("active", "web", now - Duration::hours(1)),
+        ("pinned", "web", now - Duration::days(5)),
+        ("shared", "global", now - Duration::days(5)),
+    ] {
+        gateway
+            .storage
+            .create_session(Session {
+                id: id.to_string(),
+                user_id: "user1".to_string(),
+                channel: channel.to_string(),
+                scope: "per-sender".to_string(),
+                created_at: last_active,
+                updated_at: last_active,
+            })
+            .await
+            .unwrap();
+        gateway
+            .storage
+            .add_message(StorageMessage {
+                id: format!("{}-message", id),
+                session_id: id.to_string(),
+                role: "user".to_string(),
+                content: "Hello".to_string(),
+                created_at: last_active,
+                model_used: None,
+                tokens: None,
+                metadata: None,
+            })
+            .await
+            .unwrap();
+    }
+    gateway
+        .storage
+        .add_session_note(SessionNote {
+            id: "note".to_string(),
+            session_id: "pinned".to_string(),
+            content: "Keep this one".to_string(),
+            created_at: now - Duration::days(5),
+        })
+        .await
+        .unwrap();
+
+    let mut events = subscribe();
+    let expired = session_manager.expire_idle_sessions(now).await.unwrap();
+    let expired: Vec<&str> = expired.iter().map(|s| s.id.as_str()).collect();
+    assert_eq!(expired, ["idle"]);
+
+    assert!(gateway.storage.get_session("idle").await.unwrap().is_none());
+    assert!(gateway
+        .storage
+        .get_messages("idle", None)
+        .await
+        .unwrap()
+        .is_empty());
+    for id in ["active", "pinned", "shared"] {
+        assert!(
+            gateway.storage.get_session(id).await.unwrap().is_some(),
+            "{}",
+            id
+        );
+        assert_eq!(
+            gateway.storage.get_messages(id, None).await.unwrap().len(),
+            1
+        );
+    }
+    let mut notified = false;
+    while let Ok(event) = events.try_recv() {
+        notified |= matches!(event, SystemEvent::SessionExpired(id) if id == "idle");
+    }
+    assert!(notified, "expiry not published");
+
+    // Nothing is deleted while expiry is off
+    gateway.config.write().await.sessions.expiry.enabled = false;
+    let later = now + Duration::days(30);
+    assert!(session_manager
+        .expire_idle_sessions(later)
+        .await
+        .unwrap()
+        .is_empty());
+    assert!(gateway
+        .storage
+        .get_session("active")
+        .await
+        .unwrap()
+        .is_some());
+}
+
+/// A user over `sessions.max_per_user` loses their least recently used
+/// session; other users keep theirs
+#[tokio::test]
+async fn test_session_limit_evicts_the_least_recently_used() {
+    let (session_manager, gateway) = test_session_manager("http://127.0.0.1:9", |config| {
+        config.sessions.max_per_user = Some(2);
+    })
+    .await;
+
+    let other = session_manager
+        .get_or_create_session("user2", "web", None)
+        .await
+        .unwrap();
+    let telegram = session_manager
+        .get_or_create_session("user1", "telegram", None)
+        .await
+        .unwrap();
+    tokio::time::sleep(std::time::Duration::from_millis(5)).await;
+    let discord = session_manager
+        .get_or_create_session("user1", "discord", None)
+        .await
+        .unwrap();
+    tokio::time::sleep(std::time::Duration::from_millis(5)).await;
+    // Using the older session makes the newer one the least recently used
+    let reused = session_manager
+        .get_or_create_session("user1", "telegram", None)
+        .await
+        .unwrap();
+    assert_eq!(reused.id, telegram.id);
+    tokio::time::sleep(std::time::Duration::from_millis(5)).await;
+
+    let web = session_manager
+        .get_or_create_session("user1", "web", None)
+        .await
+        .unwrap();
+    assert!(gateway
+        .storage
+        .get_session(&discord.id)
+        .await
+        .unwrap()
+        .is_none());
+    for id in [&telegram.id, &web.id, &other.id] {
+        assert!(
+            gateway.storage.get_session(id).await.unwrap().is_some(),
+            "{}",
+            id
+        );
+    }
+    assert_eq!(
+        gateway
+            .storage
+            .list_user_sessions("user1")
+            .await
+            .unwrap()
+            .len(),
+        2
+    );
+}
+
+/// Eviction skips the shared session and sessions with notes, and leaves
+/// nothing of the evicted session behind
+#[tokio::test]
+async fn test_session_limit_spares_shared_and_noted_sessions() {
+    use rustyclaw::storage::{MessageDraft, SessionNote};
+
+    let (session_manager, gateway) = test_session_manager("http://127.0.0.1:9", |config| {
+        config.sessions.max_per_user = Some(3);
+    })
+    .await;
+
+    let global = session_manager
+        .get_or_create_session("user1", "global", None)
+        .await
+        .unwrap();
+    tokio::time::sleep(std::time::Duration::from_millis(5)).await;
+    let noted = session_manager
+        .get_or_create_session("user1", "telegram", None)
+        .await
+        .unwrap();
+    gateway
+        .storage
+        .add_session_note(SessionNote {
+            id: "note-1".to_string(),
+            session_id: noted.id.clone(),
+            content: "Keep me".to_string(),
+            created_at: chrono::Utc::now(),
+        })
+        .await
+        .unwrap();
+    tokio::time::sleep(std::time::Duration::from_millis(5)).await;
+    let discord = session_manager
+        .get_or_create_session("user1", "discord", None)
+        .await
+        .unwrap();
+    gateway
+        .storage
+        .save_draft(&MessageDraft {
+            id: "draft-1".to_string(),
+            session_id: discord.id.clone(),
+            content: "Half a reply".to_string(),
+            model_used: None,
+            updated_at: chrono::Utc::now(),
+        })
+        .await
+        .unwrap();
+    gateway
+        .storage
+        .save_session_tool_approval(&discord.id, "exec")
+        .await
+        .unwrap();
+    tokio::time::sleep(std::time::Duration::from_millis(5)).await;
+
+    // The most recently used of the three goes, the older two are protected
+    session_manager
+        .get_or_create_session("user1", "web", None)
+        .await
+        .unwrap();
+    assert!(gateway
+        .storage
+        .get_session(&discord.id)
+        .await
+        .unwrap()
+        .is_none());
+    for id in [&global.id, &noted.id] {
+        assert!(
+            gateway.storage.get_session(id).await.unwrap().is_some(),
+            "{}",
+            id
+        );
+    }
+    assert!(gateway.storage.list_drafts().await.unwrap().is_empty());
+    assert!(gateway
+        .storage
+        .list_session_tool_approvals()
+        .await
+        .unwrap()
+        .is_empty());
+}
+
+/// Eval cases are answered with the requested seed and model and checked
+/// against their expectations
+#[tokio::test]
+async fn test_eval_suite_reports_pass_and_fail() {
+    use rustyclaw::core::eval::run_cases;
+    use rustyclaw::storage::EvalCase;
+
+    let mut server = mockito::Server::new_async().await;
+    let mock = server
+        .mock("POST", "/chat/completions")
+        .match_body(mockito::Matcher::PartialJson(
+            serde_json::json!({"model": "eval-model", "seed": 7}),
+        ))
+        .with_header("content-type", "application/json")
+        .with_body(
+            r#"{"id": "chatcmpl-1", "object": "chat.completion", "created": 1700000000,
+                "model": "eval-model",
+                "choices": [{"index": 0, "finish_reason": "stop",
+                             "message": {"role": "assistant", "content": "2 + 2 = 4"}}]}"#,
+        )
+        .expect(2)
+        .create_async()
+        .await;
+
+    let (router, gateway) = test_router(&server.url(), |config| {
+        config.llm.models.roles = [("eval".to_string(), "eval-model".to_string())].into();
+    })
+    .await;
+
+    let cases = vec![
+        EvalCase {
+            input: "What is 2+2?".to_string(),
+            expected_contains: Some("4".to_string()),
+            expected_regex: None,
+        },
+        EvalCase {
+            input: "What is 2+3?".to_string(),
+            expected_contains: None,
+            expected_regex: Some(r"\b(5|five)\b".to_string()),
+        },
+    ];
+    let results = run_cases(&router, "eval-user", cases, 7, "role:eval", 2).await;
+
+    mock.assert_async().await;
+    let outcomes: Vec<(usize, bool)> = results.iter().map(|r| (r.index, r.passed)).collect();
+    assert_eq!(outcomes, [(0, true), (1, false)]);
+    assert_eq!(results[1].output.as_deref(), Some("2 + 2 = 4"));
+    assert!(results.iter().all(|r| r.error.is_none()));
+
+    // The ephemeral sessions are gone
+    assert!(gateway
+        .storage
+        .list_active_sessions(None, 10)
+        .await
+        .unwrap()
+        .is_empty());
+}
+
+/// A turn that takes a session past its cost limit is answered, the next
+/// one is refused until the limit is reset
+#[tokio::test]
+async fn test_crossing_the_cost_limit_blocks_the_next_turn() {
+    use axum::extract::{Path, State};
+    use rustyclaw::api::routes::reset_user_costs;
+    use rustyclaw::core::costs::COST_LIMIT_REPLY;
+
+    let mut server = mockito::Server::new_async().await;
+    let mock = server
+        .mock("POST", "/chat/completions")
+        .with_header("content-type", "application/json")
+        .with_body(
+            r#"{"id": "chatcmpl-1", "object": "chat.completion", "created": 1700000000,
+                "model": "cost-model",
+                "choices": [{"index": 0, "finish_reason": "stop",
+                             "message": {"role": "assistant", "content": "A long answer"}}],
+                "usage": {"prompt_tokens": 800, "completion_tokens": 200, "total_tokens": 1000}}"#,
+        )
+        .expect(3)
+        .create_async()
+        .await;
+
+    let (router, _gateway) = test_router(&server.url(), |config| {
+        config.costs = rustyclaw::config::CostsConfig {
+            prices: [("cost-model".to_string(), 0.6)].into_iter().collect(),
+            session_limit: Some(1.0),
+            ..Default::default()
+        };
+    })
+    .await;
+    let router = Arc::new(router);
+    let user = "telegram:cost-user";
+
+    // 0.6 of a 1.0 limit, then 1.2: both turns are answered
+    for _ in 0..2 {
+        let response = router
+            .handle_message(user, "telegram", "Tell me more")
+            .await
+            .unwrap();
+        assert_eq!(response.content, "A long answer");
+    }
+
+    let blocked = router
+        .handle_message(user, "telegram", "And more")
+        .await
+        .unwrap();
+    assert_eq!(blocked.content, COST_LIMIT_REPLY);
+    let mut events = router
+        .handle_message_stream(user, "telegram", "And more")
+        .await
+        .unwrap();
+    let mut streamed = String::new();
+    while let Some(event) = events.recv().await {
+        if let StreamEvent::Delta(text) = event {
+            streamed.push_str(&text);
+        }
+    }
+    assert_eq!(streamed, COST_LIMIT_REPLY);
+
+    // An admin reset lets the conversation go on
+    let reset = reset_user_costs(State(router.clone()), Path(user.to_string())).await;
+    let spent = reset.0.data.unwrap()["spent"].as_f64().unwrap();
+    assert!((spent - 1.2).abs() < 1e-9, "{}", spent);
+    let response = router
+        .handle_message(user, "telegram", "And more")
+        .await
+        .unwrap();
+    assert_eq!(response.content, "A long answer");
+    mock.assert_async().await;
+}
+
+/// Batch prompts count towards the user's cost limit and are refused once
+/// it is reached
+#[tokio::test]
+async fn test_batch_chat_is_refused_at_the_cost_limit() {
+    use axum::extract::State;
+    use axum::{Extension, Json};
+    use rustyclaw::api::routes::chat_batch;
+    use rustyclaw::api::{AuthUserId, BatchChatRequest};
+    use rustyclaw::core::costs::COST_LIMIT_REPLY;
+
+    let mut server = mockito::Server::new_async().await;
+    let mock = server
+        .mock("POST", "/chat/completions")
+        .with_header("content-type", "application/json")
+        .with_body(
+            r#"{"id": "chatcmpl-1", "object": "chat.completion", "created": 1700000000,
+                "model": "cost-model",
+                "choices": [{"index": 0, "finish_reason": "stop",
+                             "message": {"role": "assistant", "content": "positive"}}],
+                "usage": {"prompt_tokens": 800, "completion_tokens": 200, "total_tokens": 1000}}"#,
+        )
+        .expect(2)
+        .create_async()
+        .await;
+
+    let (router, _gateway) = test_router(&server.url(), |config| {
+        config.costs = rustyclaw::config::CostsConfig {
+            prices: [("cost-model".to_string(), 0.6)].into_iter().collect(),
+            user_limit: Some(1.0),
+            ..Default::default()
+        };
+    })
+    .await;
+    let router = Arc::new(router);
+
+    let batch = |prompt: &str| {
+        chat_batch(
+            State(router.clone()),
+            Extension(AuthUserId("batcher".to_string())),
+            Json(BatchChatRequest {
+                prompts: vec![prompt.to_string()],
+                stream: false,
+            }),
+        )
+    };
+    let text = |response: axum::response::Response| async move {
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        let body: serde_json::Value = serde_json::from_slice(&body).unwrap();
+        body["data"]["items"][0]["response"]["text"]
+            .as_str()
+            .unwrap()
+            .to_string()
+    };
+
+    // 0.6 of a 1.0 limit, then 1.2: both prompts are answered
+    for _ in 0..2 {
+        let response = batch("classify this").await.expect("Batch failed");
+        assert_eq!(text(response).await, "positive");
+    }
+
+    let response = batch("classify that").await.expect("Batch failed");
+    assert_eq!(text(response).await, COST_LIMIT_REPLY);
+    assert!((router.costs().user_cost("batcher") - 1.2).abs() < 1e-9);
+    mock.assert_async().await;
+}
+
+/// A user's default model answers their turns until the session chooses
+/// another one
+#[tokio::test]
+async fn test_user_default_model_is_used_without_session_override() {
+    use rustyclaw::storage::UserSettings;
+
+    let reply = |model: &str| {
+        format!(
+            r#"{{"id": "chatcmpl-1", "object": "chat.completion", "created": 1700000000,
+                "model": "{}",
+                "choices": [{{"index": 0, "finish_reason": "stop",
+                             "message": {{"role": "assistant", "content": "Hi"}}}}]}}"#,
+            model
+        )
+    };
+    let mut server = mockito::Server::new_async().await;
+    let user_model = server
+        .mock("POST", "/chat/completions")
+        .match_body(mockito::Matcher::AllOf(vec![
+            mockito::Matcher::Regex(r#""model":"user-model""#.to_string()),
+            mockito::Matcher::Regex(r#""temperature":0.4"#.to_string()),
+        ]))
+        .with_header("content-type", "application/json")
+        .with_body(reply("user-model"))
+        .expect(1)
+        .create_async()
+        .await;
+    let primary = server
+        .mock("POST", "/chat/completions")
+        .match_body(mockito::Matcher::Regex(
+            r#""model":"primary-model""#.to_string(),
+        ))
+        .with_header("content-type", "application/json")
+        .with_body(reply("primary-model"))
+        .expect(1)
+        .create_async()
+        .await;
+
+    let gateway = TestGateway::new(&server.url(), |config| {
+        config.llm.models.primary = "primary-model".to_string();
+    })
+    .await;
+
+    gateway
+        .storage
+        .set_user_settings(
+            "telegram:settings-user",
+            &UserSettings {
+                model: Some("user-model".to_string()),
+                temperature: Some(0.4),
+                ..Default::default()
+            },
+        )
+        .await
+        .unwrap();
+
+    let router = gateway.router().await;
+
+    let response = router
+        .handle_message("telegram:settings-user", "telegram", "Hello")
+        .await
+        .unwrap();
+    assert_eq!(response.model, "user-model");
+
+    // Users without settings get the configured model
+    let response = router
+        .handle_message("telegram:someone-else", "telegram", "Hello")
+        .await
+        .unwrap();
+    assert_eq!(response.model, "primary-model");
+
+    user_model.assert_async().await;
+    primary.assert_async().await;
+}
+
+/// Tool results of earlier turns reach the model as summaries while the
+/// stored transcript keeps them in full
+#[tokio::test]
+async fn test_old_tool_results_are_summarized_in_the_context() {
+    use axum::extract::{Path, State};
+    use axum::Extension;
+    use rustyclaw::api::routes::get_session_context;
+    use rustyclaw::api::AuthUserId;
+
+    let (router, gateway) = test_router("http://127.0.0.1:9", |config| {
+        config.llm.context_windows =
+            std::collections::HashMap::from([("context-model".to_string(), 100_000)]);
+        config.sessions.tool_results = rustyclaw::config::ToolResultsConfig {
+            keep_full_turns: Some(1),
+            summary_chars: 28,
+        };
+    })
+    .await;
+    let router = Arc::new(router);
+    let session = router
+        .get_or_create_session_api("researcher", "web")
+        .await
+        .unwrap();
+
+    let old_result = format!("Tool web_fetch result: {}", "lorem ipsum ".repeat(500));
+    let recent_result = format!("Tool web_fetch result: {}", "dolor sit ".repeat(500));
+    let history = [
+        ("user", "Fetch the first page"),
+        ("user", old_result.as_str()),
+        ("assistant", "The first page is about lorem ipsum."),
+        ("user", "Now the second page"),
+        ("user", recent_result.as_str()),
+        ("assistant", "The second page is about dolor sit."),
+        ("user", "Compare them"),
+    ];
+    for (i, (role, content)) in history.iter().enumerate() {
+        gateway
+            .storage
+            .add_message(StorageMessage {
+                id: format!("msg-{}", i),
+                session_id: session.id.clone(),
+                role: role.to_string(),
+                content: content.to_string(),
+                created_at: chrono::Utc::now() + chrono::Duration::seconds(i as i64),
+                model_used: None,
+                tokens: None,
+                metadata: None,
+            })
+            .await
+            .unwrap();
+    }
+
+    let preview = get_session_context(
+        State(router.clone()),
+        Extension(AuthUserId("researcher".to_string())),
+        Path(session.id.clone()),
+    )
+    .await
+    .unwrap()
+    .0
+    .data
+    .unwrap();
+
+    // Two turns back: summarized; the previous turn's result is kept
+    assert_eq!(
+        preview.messages[2].content,
+        format!(
+            "[Result of tool web_fetch from an earlier turn, shortened from {} chars] \
+             Tool web_fetch result: lorem…",
+            old_result.chars().count()
+        )
+    );
+    assert_eq!(preview.messages[5].content, recent_result);
+    assert_eq!(preview.messages[7].content, "Compare them");
+
+    let stored = gateway
+        .storage
+        .get_messages(&session.id, None)
+        .await
+        .unwrap();
+    assert_eq!(stored[1].content, old_result);
+}
+
+#[tokio::test]
+async fn test_ask_user_waits_for_the_answer() {
+    // The model asks which file to open, then opens the one it was told
+    let chunk = |delta: serde_json::Value, finish_reason: Option<&str>| {
+        let chunk = serde_json::json!({
+            "id": "chatcmpl-1", "object": "chat.completion.chunk", "created": 1700000000,
+            "model": "curious-model",
+            "choices": [{"index": 0, "delta": delta, "finish_reason": finish_reason}]
+        });
+        format!("data: {}\n\n", chunk)
+    };
+    let question = chunk(
+        serde_json::json!({"role": "assistant", "tool_calls": [{
+            "index": 0, "id": "call_1", "type": "function",
+            "function": {"name": "ask_user", "arguments": "{\"question\": \"Which file?\"}"}
+        }]}),
+        None,
+    ) + &chunk(serde_json::json!({}), Some("tool_calls"))
+        + "data: [DONE]\n\n";
+    let reply = chunk(
+        serde_json::json!({"role": "assistant", "content": "Opening notes.txt"}),
+        None,
+    ) + &chunk(serde_json::json!({}), Some("stop"))
+        + "data: [DONE]\n\n";
+
+    let mut server = mockito::Server::new_async().await;
+    let answered = server
+        .mock("POST", "/chat/completions")
+        .match_body(mockito::Matcher::Regex("notes\\.txt".to_string()))
+        .with_header("content-type", "text/event-stream")
+        .with_body(reply)
+        .create_async()
+        .await;
+    let asked = server
+        .mock("POST", "/chat/completions")
+        .with_header("content-type", "text/event-stream")
+        .with_body(question)
+        .create_async()
+        .await;
+
+    let (router, _gateway) = test_router(&server.url(), |config| {
+        config.llm.context_windows =
+            std::collections::HashMap::from([("curious-model".to_string(), 100_000)]);
+        config.llm.tool_support =
+            std::collections::HashMap::from([("curious-model".to_string(), true)]);
+    })
+    .await;
+
+    let mut receiver = router
+        .handle_message_stream("asker", "web", "Open my file")
+        .await
+        .unwrap();
+    let next = |receiver: &mut tokio::sync::mpsc::Receiver<StreamEvent>| {
+        let event = receiver.recv();
+        async move {
+            tokio::time::timeout(std::time::Duration::from_secs(10), event)
+                .await
+                .expect("stream stalled")
+        }
+    };
+    loop {
+        match next(&mut receiver).await {
+            Some(StreamEvent::UserInputRequested { prompt, .. }) => {
+                assert_eq!(prompt, "Which file?");
+                break;
+            }
+            Some(StreamEvent::Error(e)) => panic!("stream failed: {}", e),
+            Some(_) => continue,
+            None => panic!("stream ended before the question"),
+        }
+    }
+
+    // The user's next message is the answer, not a new turn
+    let mut answer = router
+        .handle_message_stream("asker", "web", "notes.txt")
+        .await
+        .unwrap();
+    match next(&mut answer).await {
+        Some(StreamEvent::Done { model, .. }) => assert_eq!(model, "user-input"),
+        other => panic!("unexpected event: {:?}", other),
+    }
+
+    let mut content = String::new();
+    loop {
+        match next(&mut receiver).await {
+            Some(StreamEvent::Delta(delta)) => content.push_str(&delta),
+            Some(StreamEvent::Done { .. }) => break,
+            Some(StreamEvent::Error(e)) => panic!("stream failed: {}", e),
+            Some(_) => continue,
+            None => panic!("stream ended without Done"),
+        }
+    }
+    assert_eq!(content, "Opening notes.txt");
+    asked.assert_async().await;
+    answered.assert_async().await;
+}
+
+#[tokio::test]
+async fn test_session_summary_regenerates_after_new_messages() {
+    use axum::extract::{Path, State};
+    use axum::Extension;
+    use rustyclaw::api::routes::get_session_summary;
+    use rustyclaw::api::{ApiError, AuthUserId};
+
+    let completion = |content: &str| {
+        serde_json::json!({
+            "id": "chatcmpl-1", "object": "chat.completion", "created": 1700000000,
+            "model": "small-model",
+            "choices": [{"index": 0, "finish_reason": "stop",
+                         "message": {"role": "assistant", "content": content}}]
+        })
+        .to_string()
+    };
+    let mut server = mockito::Server::new_async().await;
+    let first = server
+        .mock("POST", "/chat/completions")
+        .match_request(|request| {
+            let body = request.utf8_lossy_body().unwrap_or_default();
+            body.contains("small-model")
+                && body.contains("Summarize the following conversation")
+                && body.contains("user: turn 0")
+        })
+        .with_header("content-type", "application/json")
+        .with_body(completion("Planning a trip."))
+        .expect(1)
+        .create_async()
+        .await;
+    // Only the new messages are summarized into the cached summary
+    let update = server
+        .mock("POST", "/chat/completions")
+        .match_request(|request| {
+            let body = request.utf8_lossy_body().unwrap_or_default();
+            body.contains("small-model")
+                && body.contains("Update the summary")
+                && body.contains("Planning a trip.")
+                && body.contains("user: turn 2")
+                && !body.contains("turn 0")
+        })
+        .with_header("content-type", "application/json")
+        .with_body(completion("Planning a trip to Lisbon."))
+        .expect(1)
+        .create_async()
+        .await;
+
+    let (router, gateway) = test_router(&server.url(), |config| {
+        config.llm.models.fast = Some("small-model".to_string());
+    })
+    .await;
+    let router = Arc::new(router);
+    let session = router
+        .get_or_create_session_api("summarizer", "web")
+        .await
+        .unwrap();
+    let summary = |user: &str| {
+        get_session_summary(
+            State(router.clone()),
+            Extension(AuthUserId(user.to_string())),
+            Path(session.id.clone()),
+        )
+    };
+    let add_turns = |turns: std::ops::Range<i64>| {
+        let storage = gateway.storage.clone();
+        let session_id = session.id.clone();
+        async move {
+            let start = chrono::Utc::now();
+            for i in turns {
+                storage
+                    .add_message(StorageMessage {
+                        id: format!("turn-{}", i),
+                        session_id: session_id.clone(),
+                        role: if i % 2 == 0 { "user" } else { "assistant" }.to_string(),
+                        content: format!("turn {}", i),
+                        created_at: start + chrono::Duration::seconds(i),
+                        model_used: None,
+                        tokens: None,
+                        metadata: None,
+                    })
+                    .await
+                    .unwrap();
+            }
+        }
+    };
+
+    // Nothing to summarize yet
+    assert!(matches!(
+        summary("summarizer").await.unwrap_err(),
+        ApiError::NotFound(_)
+    ));
+
+    add_turns(0..2).await;
+    let generated = summary("summarizer").await.unwrap().0.data.unwrap();
+    assert_eq!(generated.summary, "Planning a trip.");
+    assert_eq!(generated.summary_through, "turn-1");
+
+    // Cached while no messages arrive
+    let cached = summary("summarizer").await.unwrap().0.data.unwrap();
+    assert_eq!(cached.summary, "Planning a trip.");
+    assert_eq!(cached.updated_at, generated.updated_at);
+    first.assert_async().await;
+
+    add_turns(2..4).await;
+    let regenerated = summary("summarizer").await.unwrap().0.data.unwrap();
+    assert_eq!(regenerated.summary, "Planning a trip to Lisbon.");
+    assert_eq!(regenerated.summary_through, "turn-3");
+    update.assert_async().await;
+
+    // Other users' sessions are not found
+    assert!(matches!(
+        summary("intruder").await.unwrap_err(),
+        ApiError::NotFound(_)
+    ));
+}
@@ -14,10 +14,17 @@ fn mock_llm_config() -> LlmConfig {
             primary: "qwen2.5:7b".to_string(),
             code: None,
             fast: None,
+            roles: Default::default(),
         },
         keep_alive: None,
         cache: Default::default(),
         routing: None,
+        context_windows: Default::default(),
+        tool_support: Default::default(),
+        tokenizers: Default::default(),
+        coalesce_requests: false,
+        circuit_breaker: Default::default(),
+        warm_up: false,
     }
 }
 
@@ -46,7 +53,15 @@ async fn test_available_tools_include_creator_and_web() {
         api: Default::default(),
         admin: Default::default(),
         workspace: Default::default(),
+        prompt: Default::default(),
+        locale: Default::default(),
+        network: Default::default(),
+        moderation: Default::default(),
+        redaction: Default::default(),
+        schedules: Default::default(),
         agents: Default::default(),
+        plugins: Default::default(),
+        costs: Default::default(),
         config_path: None,
     };
 
@@ -161,7 +176,9 @@ echo "$text" | awk '{print NF}'"#
         "create_tool",
         &create_request_json,
         Some("test-session"),
+        None,
         true,
+        None,
     )
     .await;
 
@@ -199,8 +216,15 @@ echo "$text" | awk '{print NF}'"#
     })
     .to_string();
 
-    let _execution_result =
-        execute_tool_with_context("count_words", &tool_call_json, Some("test-session"), true).await;
+    let _execution_result = execute_tool_with_context(
+        "count_words",
+        &tool_call_json,
+        Some("test-session"),
+        None,
+        true,
+        None,
+    )
+    .await;
 
     // On Unix-like systems, execution should work
     #[cfg(unix)]
@@ -273,7 +297,9 @@ print(text[::-1])"#
         "create_tool",
         &create_request_json,
         Some("test-session"),
+        None,
         true,
+        None,
     )
     .await;
 
@@ -322,7 +348,9 @@ async fn test_create_tool_validation() {
     };
 
     let json = serde_json::to_string(&bad_request).unwrap();
-    let result = execute_tool_with_context("create_tool", &json, Some("test-session"), true).await;
+    let result =
+        execute_tool_with_context("create_tool", &json, Some("test-session"), None, true, None)
+            .await;
 
     assert!(
         result.is_err(),